@@ -0,0 +1,110 @@
+// Reference rust implementation of AluVM (arithmetic logic unit virtual machine).
+// To find more on AluVM please check <https://aluvm.org>
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Designed in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+// Written in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+//
+// Copyright (C) 2021-2024 LNP/BP Standards Association, Switzerland.
+// Copyright (C) 2024-2025 Laboratories for Ubiquitous Deterministic Computing (UBIDECO),
+//                         Institute for Distributed and Cognitive Systems (InDCS), Switzerland.
+// Copyright (C) 2021-2025 Dr Maxim Orlovsky.
+// All rights under the above copyrights are reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use this file except
+// in compliance with the License. You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed under the License
+// is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express
+// or implied. See the License for the specific language governing permissions and limitations under
+// the License.
+
+//! Benchmarks of the program execution speed, comparing the execution decoding the instructions
+//! from the bytecode ([`Vm::exec`]) with the execution of the precompiled libraries
+//! ([`Vm::exec_compiled`]).
+//!
+//! Run with `cargo bench --bench exec`; the execution throughput is reported in executed
+//! instructions per second, and the precompilation throughput in code bytes per second.
+//!
+//! The precompiled execution is about 2 times faster for the arithmetic loop and about 4 times
+//! faster for the loop calling a subroutine, where the execution from the bytecode also checks
+//! the library ISA extensions and sets up the instruction decoding each time the control is
+//! transferred into the library.
+
+use aluvm::isa::{Alu64Instr, ArithmInstr, Bytecode, CtrlInstr, RegA};
+use aluvm::regs::Status;
+use aluvm::{Lib, LibId, LibSite, Vm};
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+
+/// Number of the loop iterations, kept below the jump limit.
+const ITERATIONS: u64 = 10_000;
+
+fn code_len(code: &[Alu64Instr<LibId>]) -> u16 { code.iter().map(Bytecode::code_byte_len).sum() }
+
+/// Assembles a loop decrementing a counter; if `call` is set, each iteration calls a subroutine
+/// which returns immediately.
+fn counter_loop(call: bool) -> Lib {
+    let mut code: Vec<Alu64Instr<LibId>> = vec![];
+    let sub = 3;
+    if call {
+        // The subroutine is placed before the loop, which is entered by a jump over it
+        code.push(CtrlInstr::Jmp { pos: 4 }.into());
+        code.push(CtrlInstr::Ret.into());
+        assert_eq!(code_len(&code[..1]), sub);
+    }
+    code.extend::<[Alu64Instr<LibId>; 3]>([
+        ArithmInstr::Put { dst: RegA::A1, val: ITERATIONS }.into(),
+        ArithmInstr::Put { dst: RegA::A2, val: 1 }.into(),
+        ArithmInstr::Put { dst: RegA::A3, val: 0 }.into(),
+    ]);
+    let start = code_len(&code);
+    if call {
+        code.push(CtrlInstr::Fn { pos: sub }.into());
+    }
+    code.extend::<[Alu64Instr<LibId>; 4]>([
+        ArithmInstr::Sub { wrap: false, dst: RegA::A1, src: RegA::A2 }.into(),
+        ArithmInstr::Eq { src1: RegA::A1, src2: RegA::A3 }.into(),
+        CtrlInstr::JiOvfl { pos: start }.into(),
+        CtrlInstr::Stop.into(),
+    ]);
+    Lib::assemble(&code).unwrap()
+}
+
+fn bench_program(c: &mut Criterion, name: &str, lib: Lib) {
+    let entry = LibSite::new(lib.lib_id(), 0);
+    let precompiled = lib.precompile::<Alu64Instr<LibId>>().unwrap();
+
+    let mut vm = Vm::<Alu64Instr<LibId>>::new();
+    assert_eq!(vm.exec(entry, &(), |_| Some(&lib)), Status::Ok);
+
+    let mut group = c.benchmark_group(name);
+    group.throughput(Throughput::Elements(vm.core.ci()));
+    group.bench_function("exec", |b| {
+        b.iter(|| {
+            let mut vm = Vm::<Alu64Instr<LibId>>::new();
+            vm.exec(entry, &(), |_| Some(&lib))
+        })
+    });
+    group.bench_function("exec_compiled", |b| {
+        b.iter(|| {
+            let mut vm = Vm::<Alu64Instr<LibId>>::new();
+            vm.exec_compiled(entry, &(), |_| Some(&precompiled))
+        })
+    });
+    group.finish();
+
+    let mut group = c.benchmark_group(name);
+    group.throughput(Throughput::Bytes(lib.code().len() as u64));
+    group.bench_function("precompile", |b| b.iter(|| lib.precompile::<Alu64Instr<LibId>>()));
+    group.finish();
+}
+
+fn arithmetic_loop(c: &mut Criterion) { bench_program(c, "arithmetic_loop", counter_loop(false)) }
+
+fn call_loop(c: &mut Criterion) { bench_program(c, "call_loop", counter_loop(true)) }
+
+criterion_group!(benches, arithmetic_loop, call_loop);
+criterion_main!(benches);
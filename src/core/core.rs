@@ -22,24 +22,317 @@
 // or implied. See the License for the specific language governing permissions and limitations under
 // the License.
 
-use core::fmt::{self, Debug, Formatter};
+use core::fmt::{self, Debug, Display, Formatter};
+use core::hash::{Hash, Hasher};
+use core::mem;
 
 use amplify::confinement::ConfinedVec;
 
-use super::{Site, SiteId, Status};
-use crate::{Register, LIB_NAME_ALUVM};
+use super::{Profile, Site, SiteId, Status};
+use crate::isa::ComplexityModel;
+#[cfg(any(test, feature = "tests"))]
+use crate::testing::fixture::CoreFixture;
+use crate::{IsaId, Register, LIB_NAME_ALUVM};
 
 /// Maximal size of the call stack.
 ///
-/// Equals to 0xFFFF (i.e., maximum limited by `cy` and `cp` bit size).
+/// Equals to 0xFF, which is below the maximum of 0xFFFF imposed by the bit size of the `cp`
+/// register. Cores with a smaller call stack can be created by providing a lower value for the
+/// `CALL_STACK_SIZE` generic parameter of [`Core`].
 pub const CALL_STACK_SIZE_MAX: u16 = 0xFF;
 
+/// Size of the call stack for memory-constrained environments, such as embedded targets.
+///
+/// Can be used as the `CALL_STACK_SIZE` generic parameter of [`Core`] and [`crate::Vm`]; see also
+/// [`crate::SmallStackVm`].
+pub const CALL_STACK_SIZE_SMALL: u16 = 0x10;
+
+// The call stack depth must be representable by the `cp` register
+const _: () = assert!(CALL_STACK_SIZE_MAX as u32 <= u16::MAX as u32);
+
+/// Initial value of the call stack integrity hash chain.
+pub(super) const CS_CHAIN_SEED: u64 = 0x6A09_E667_F3BC_C908;
+
+/// Multiplier used by the call stack integrity hash chain mix function.
+const CS_CHAIN_MUL: u64 = 0x9E37_79B9_7F4A_7C15;
+
+/// Call stack integrity hash chain accumulator.
+///
+/// Each call stack frame is absorbed into the chain by hashing it with [`Hash`]: every 8-byte
+/// little-endian word `w` of the hashed data (the last one padded with zeros) updates the
+/// accumulator as `acc = (acc ^ w).wrapping_mul(0x9E37_79B9_7F4A_7C15).rotate_left(29)`.
+pub(super) struct CsChain(u64);
+
+impl CsChain {
+    pub(super) fn mix(acc: u64, frame: &impl Hash) -> u64 {
+        let mut chain = CsChain(acc);
+        frame.hash(&mut chain);
+        chain.0
+    }
+
+    #[inline]
+    fn absorb(&mut self, word: u64) {
+        self.0 = (self.0 ^ word).wrapping_mul(CS_CHAIN_MUL).rotate_left(29);
+    }
+}
+
+impl Hasher for CsChain {
+    fn finish(&self) -> u64 { self.0 }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for chunk in bytes.chunks(8) {
+            let mut word = [0u8; 8];
+            word[..chunk.len()].copy_from_slice(chunk);
+            self.absorb(u64::from_le_bytes(word));
+        }
+    }
+
+    fn write_u16(&mut self, i: u16) { self.absorb(i as u64) }
+
+    fn write_u64(&mut self, i: u64) { self.absorb(i) }
+
+    fn write_usize(&mut self, i: usize) { self.absorb(i as u64) }
+}
+
+/// Call stack integrity violation, detected when the call stack integrity mode is on (see
+/// [`CoreConfig::cs_integrity`]).
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Display)]
+#[display(doc_comments)]
+pub enum CallStackFault<Id: SiteId> {
+    /// call stack frame {found} doesn't match the expected frame {expected}.
+    FrameMismatch {
+        /// Frame recorded in the shadow stack.
+        expected: Site<Id>,
+        /// Frame found in the call stack.
+        found: Site<Id>,
+    },
+
+    /// call stack frame {found} is absent from the shadow stack.
+    NoShadowFrame {
+        /// Frame found in the call stack.
+        found: Site<Id>,
+    },
+
+    /// integrity hash chain is broken at call stack frame {frame}.
+    ChainMismatch {
+        /// Frame at which the hash chain is broken.
+        frame: Site<Id>,
+    },
+}
+
+/// Violation of an invariant relating the values of the control registers.
+///
+/// Violations are never caused by the executed programs and indicate a bug either in the engine or
+/// in an ISA extension. See [`Core::check_invariants`].
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum InvariantViolation {
+    /// `CK` is in a failed state, while the failure counter `CF` is zero.
+    UncountedFailure,
+
+    /// complexity accumulator `CA` value {ca} has reached the limit {cl}, while `CK` is not in a
+    /// failed state.
+    ComplexityOverrun {
+        /// Value of the `CA` register.
+        ca: u64,
+        /// Value of the `CL` register.
+        cl: u64,
+    },
+
+    /// complexity warning site is recorded, while `CA` value {ca} hasn't reached the warning
+    /// limit.
+    PrematureWarning {
+        /// Value of the `CA` register.
+        ca: u64,
+        /// Value of the `CW` register.
+        cw: Option<u64>,
+    },
+
+    /// call stack high-water mark {high_water} exceeds the call stack capacity {capacity}.
+    CallStackOverflow {
+        /// Maximal call stack depth reached during the execution.
+        high_water: u16,
+        /// Call stack capacity.
+        capacity: usize,
+    },
+
+    /// call stack depth {depth} exceeds its high-water mark {high_water}.
+    CallStackAboveHighWater {
+        /// Value of the `CP` register.
+        depth: u16,
+        /// Maximal call stack depth reached during the execution.
+        high_water: u16,
+    },
+
+    /// number of cross-library frames {xcp} exceeds the call stack depth {depth}.
+    CrossFramesOverflow {
+        /// Number of cross-library frames.
+        xcp: u16,
+        /// Value of the `CP` register.
+        depth: u16,
+    },
+
+    /// number of cross-library frames {xcp} exceeds its high-water mark {high_water}.
+    CrossFramesAboveHighWater {
+        /// Number of cross-library frames.
+        xcp: u16,
+        /// Maximal number of cross-library frames reached during the execution.
+        high_water: u16,
+    },
+
+    /// shadow call stack depth {shadow} doesn't match the call stack depth {depth}.
+    ShadowStackMismatch {
+        /// Value of the `CP` register.
+        depth: u16,
+        /// Depth of the shadow call stack.
+        shadow: usize,
+    },
+}
+
+/// Control transfer to an offset which is not an instruction boundary, detected at runtime when
+/// the `paranoid` feature is on, or when executing a precompiled library (see
+/// [`crate::PrecompiledLib`]).
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct JumpFault<Id: SiteId> {
+    /// Site of the instruction transferring the control.
+    ///
+    /// The site is not known (and is `None`) if the control was transferred from another library
+    /// by a call, return or an external jump.
+    pub source: Option<Site<Id>>,
+    /// Site the control was transferred to.
+    pub target: Site<Id>,
+}
+
+impl<Id: SiteId> Display for JumpFault<Id> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "control transfer ")?;
+        if let Some(source) = self.source {
+            write!(f, "from {source} ")?;
+        }
+        write!(f, "to {}, which is not an instruction boundary", self.target)
+    }
+}
+
+/// Cause of a `CK` failure, recorded by the core for the diagnostic purposes.
+///
+/// Instructions provide the reason with [`Core::set_failure`] before returning
+/// [`crate::ExecStep::Fail`]; the core itself provides it when failing `CK` on the exceeded
+/// limits and faults. The reason of the most recent failure is available as
+/// [`Core::last_failure`].
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Display)]
+#[display(doc_comments)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(rename_all = "camelCase"))]
+#[non_exhaustive]
+pub enum FailureReason {
+    /// failure reason was not provided.
+    Unspecified,
+
+    /// explicit failure of the `CK` register.
+    Explicit,
+
+    /// `CO` register check has failed.
+    CheckFailed,
+
+    /// call stack overflow.
+    CallStackOverflow,
+
+    /// call stack underflow.
+    CallStackUnderflow,
+
+    /// call stack integrity violation.
+    CallStackFault,
+
+    /// register save stack overflow.
+    SaveStackOverflow,
+
+    /// jump offset overflows the code segment address space.
+    JumpOffsetOverflow,
+
+    /// control transfer to an invalid code offset.
+    InvalidJump,
+
+    /// execution of a reserved instruction.
+    ReservedInstr,
+
+    /// access to a register which has no value.
+    NoValue,
+
+    /// arithmetic overflow.
+    Overflow,
+
+    /// jump limit exceeded.
+    JumpLimit,
+
+    /// instruction limit exceeded.
+    InstrLimit,
+
+    /// complexity limit exceeded.
+    ComplexityLimit,
+
+    /// complexity budget of a call stack frame is exhausted.
+    BudgetExhausted,
+
+    /// execution error detected by the VM (see [`crate::Vm::last_error`] for the details).
+    Vm,
+
+    /// failure {1:#06X} specific to the {0} ISA extension.
+    Custom(IsaId, u16),
+}
+
+/// Cause of the program execution termination, recorded by the core for the diagnostic purposes.
+///
+/// Allows a host to tell a program which has run to completion from a program which has detected
+/// an error and bailed out, even if the `CK` failure was later reset. Instructions provide the
+/// cause with [`Core::set_termination`] before returning [`crate::ExecStep::Stop`]; the execution
+/// loop records it (or its own cause, if the execution ends for another reason) exactly once per
+/// execution. The cause is available as [`Core::termination`].
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Display)]
+#[display(doc_comments)]
+#[non_exhaustive]
+pub enum TerminationCause<Id: SiteId> {
+    /// program has explicitly stopped its execution.
+    ExplicitStop,
+
+    /// program has stopped its execution at {site} since `CK` was checked to be in a failed
+    /// state.
+    CheckFailed {
+        /// Site of the instruction which has checked `CK`.
+        site: Site<Id>,
+    },
+
+    /// program has returned from its main routine.
+    ReturnFromMain,
+
+    /// program execution has halted at {site} on a `CK` failure, since `CH` is set.
+    Halted {
+        /// Site of the instruction which has failed `CK`.
+        site: Site<Id>,
+    },
+
+    /// program has exceeded its complexity limit or the complexity budget of a call stack frame.
+    ComplexityExceeded,
+
+    /// program execution has reached the end of the code segment, or a byte sequence which can't
+    /// be decoded as an instruction.
+    EndOfCode,
+
+    /// program execution was aborted by the virtual machine (see [`crate::Vm::last_error`] and
+    /// [`Core::jump_fault`] for the details).
+    Aborted,
+}
+
+/// Values of the ISA extension registers saved by a register save stack frame.
+pub(super) type SavedRegs<Cx> =
+    Vec<(<Cx as CoreExt>::Reg, Option<<<Cx as CoreExt>::Reg as Register>::Value>)>;
+
 /// Extension to the AluVM core provided by an ISA.
 pub trait CoreExt: Clone + Debug {
     /// A type of registers provided by the ISA.
     type Reg: Register;
     /// A configuration used in initializing the core extension.
     type Config: Default;
+    /// State of the core extension registers, stored in a [`crate::CoreSnapshot`].
+    type Snapshot: Clone + Eq + Debug;
 
     /// Constructs the core extensions to be added to AluVM core.
     fn with(config: Self::Config) -> Self;
@@ -60,6 +353,19 @@ pub trait CoreExt: Clone + Debug {
 
     /// Reset the core extension by setting all the registers to `None`.
     fn reset(&mut self);
+
+    /// Takes a snapshot of the register values, used by [`Core::snapshot`].
+    fn snapshot(&self) -> Self::Snapshot;
+
+    /// Restores the register values from a snapshot taken with [`CoreExt::snapshot`], used by
+    /// [`Core::restore`].
+    fn restore(&mut self, snapshot: Self::Snapshot);
+
+    /// Seed the register values from a stream of arbitrary bytes.
+    ///
+    /// Used by fuzzing harnesses to start program execution from an arbitrary register state. The
+    /// default implementation does nothing.
+    fn set_arbitrary(&mut self, _bytes: &mut impl Iterator<Item = u8>) {}
 }
 
 /// A trait for the external part of AluVM core which can operate with core ISA extensions.
@@ -72,7 +378,12 @@ pub trait Supercore<Subcore> {
 }
 
 /// Registers of a single CPU/VM core.
-#[derive(Clone)]
+///
+/// Cores are equal only if all their registers match bit-for-bit, including the configuration
+/// registers, the ISA extension registers, the register save stack, the diagnostic reports and the
+/// execution profile. To compare just the state affecting the program execution, use
+/// [`Core::snapshot`] or [`Core::state_digest`].
+#[derive(Clone, PartialEq, Eq)]
 pub struct Core<
     Id: SiteId,
     Cx: CoreExt,
@@ -107,10 +418,34 @@ pub struct Core<
     /// Test register, which acts as a boolean test result (also a carry flag).
     pub(super) co: Status,
 
-    /// Counts number of jumps (possible cycles). The number of jumps is limited by 2^16 per
-    /// script.
+    /// Counts number of jumps (possible cycles), including calls and returns. The number of jumps
+    /// is limited by 2^16 per script, or by [`Core::cy_lim`], if set.
+    ///
+    /// # See also
+    ///
+    /// - [`Core::cy_lim`] register
     pub(super) cy: u16,
 
+    /// Jump limit.
+    ///
+    /// If this register has a value set, once [`Core::cy`] reaches this value, a subsequent jump
+    /// sets `CK` to a failure, halting the program if `CH` is set.
+    pub(super) cy_lim: Option<u16>,
+
+    /// Counts number of executed instructions, independently of their complexity. The number of
+    /// instructions is limited by [`Core::ci_lim`], if set.
+    ///
+    /// # See also
+    ///
+    /// - [`Core::ci_lim`] register
+    pub(super) ci: u64,
+
+    /// Instruction limit.
+    ///
+    /// If this register has a value set, once [`Core::ci`] reaches this value, the program halts
+    /// before executing the next instruction, setting `CK` to a failure.
+    pub(super) ci_lim: Option<u64>,
+
     /// Complexity accumulator / counter.
     ///
     /// Each instruction has an associated computational complexity level. This register sums
@@ -128,6 +463,21 @@ pub struct Core<
     /// program execution setting `CK` to a failure.
     pub(super) cl: Option<u64>,
 
+    /// Complexity warning limit.
+    ///
+    /// If this register has a value set, once [`Core::ca`] reaches this value, the VM records the
+    /// site of the instruction which crossed the limit into [`Core::cw_site`], but continues
+    /// program execution.
+    ///
+    /// # See also
+    ///
+    /// - [`Core::ca`] register
+    /// - [`Core::cl`] register
+    pub(super) cw: Option<u64>,
+
+    /// Site of the instruction at which the [`Core::cw`] complexity warning limit was crossed.
+    pub(super) cw_site: Option<Site<Id>>,
+
     /// Call stack.
     ///
     /// # See also
@@ -136,6 +486,110 @@ pub struct Core<
     /// - [`Core::cp`] register
     pub(super) cs: ConfinedVec<Site<Id>, 0, CALL_STACK_SIZE>,
 
+    /// Shadow call stack, present only if the call stack integrity mode is on.
+    ///
+    /// Each item contains a copy of the call stack frame and the value of the integrity hash chain
+    /// after absorbing the frame.
+    ///
+    /// # See also
+    ///
+    /// - [`CoreConfig::cs_integrity`]
+    /// - [`Core::cs`] register
+    pub(super) cs_shadow: Option<Vec<(Site<Id>, u64)>>,
+
+    /// Complexity budgets of the call stack frames.
+    ///
+    /// Each item contains the call stack depth of a frame which was given a budget and the value
+    /// of [`Core::ca`] at which the budget is exhausted. The values are capped by the budgets of
+    /// the outer frames, and thus are non-increasing.
+    ///
+    /// # See also
+    ///
+    /// - [`Core::push_budget`]
+    pub(super) cs_budgets: Vec<(u16, u64)>,
+
+    /// Complexity budget for the next call stack frame, set by [`Core::push_budget`].
+    pub(super) cs_budget_next: Option<u64>,
+
+    /// Register save stack.
+    ///
+    /// Each item contains the call stack depth of a frame which has executed the `save`
+    /// instruction and the mask of the ISA extension registers it has saved. The registers are
+    /// restored once the frame returns.
+    ///
+    /// # See also
+    ///
+    /// - [`Core::push_ss`]
+    pub(super) ss: ConfinedVec<(u16, u16), 0, CALL_STACK_SIZE>,
+
+    /// Values of the registers saved by the register save stack frames.
+    ///
+    /// A subcore has no access to the registers of its supercore, and thus keeps `None` here,
+    /// leaving saving and restoring of the registers to the supercore (see
+    /// [`Supercore::merge_subcore`]).
+    pub(super) ss_regs: Option<Vec<SavedRegs<Cx>>>,
+
+    /// Call stack integrity violation, if any was detected.
+    pub(super) cs_fault: Option<CallStackFault<Id>>,
+
+    /// Control transfer to an invalid offset, if any was detected.
+    pub(super) jump_fault: Option<JumpFault<Id>>,
+
+    /// Site of the call instruction which has failed due to the call stack overflow, if any.
+    pub(super) cs_overflow: Option<Site<Id>>,
+
+    /// Reason of the last `CK` failure, if any.
+    pub(super) last_failure: Option<FailureReason>,
+
+    /// Reason for the next `CK` failure, set by [`Core::set_failure`].
+    pub(super) next_failure: Option<FailureReason>,
+
+    /// Cause of the program execution termination, if the execution has terminated.
+    pub(super) termination: Option<TerminationCause<Id>>,
+
+    /// Cause for the next program termination, set by [`Core::set_termination`].
+    pub(super) next_termination: Option<TerminationCause<Id>>,
+
+    /// Deterministic execution profile, present only if the profiling is on.
+    ///
+    /// # See also
+    ///
+    /// - [`Core::start_profiling`]
+    pub(super) profile: Option<Profile<Id>>,
+
+    /// Maximal call stack depth reached during the execution.
+    ///
+    /// # See also
+    ///
+    /// - [`Core::cp`] register
+    pub(super) cs_high_water: u16,
+
+    /// Current number of cross-library frames in the call stack, i.e. frames created by calls
+    /// into other libraries.
+    pub(super) xcp: u16,
+
+    /// Maximal number of cross-library frames in the call stack reached during the execution.
+    ///
+    /// # See also
+    ///
+    /// - [`Core::xcp`] register
+    pub(super) xcs_high_water: u16,
+
+    /// Behavior of the reserved instructions.
+    ///
+    /// # See also
+    ///
+    /// - [`CoreConfig::unknown_instr`]
+    pub(super) unknown_instr: UnknownInstrPolicy,
+
+    /// Model mapping the instruction complexity classes to the complexity values accounted by the
+    /// `CA` register.
+    ///
+    /// # See also
+    ///
+    /// - [`Core::set_complexity_model`]
+    pub(super) complexity_model: ComplexityModel,
+
     /// Core extension module.
     pub cx: Cx,
 }
@@ -150,18 +604,91 @@ pub struct CoreConfig {
     pub halt: bool,
     /// Initial value for the `CL` register.
     pub complexity_lim: Option<u64>,
+    /// Initial value for the `CW` register.
+    pub complexity_warn: Option<u64>,
+    /// Limit for the number of jumps counted by the `CY` register.
+    ///
+    /// If not set, the number of jumps is limited only by the `CY` register capacity, i.e. 2^16.
+    pub jump_lim: Option<u16>,
+    /// Limit for the number of executed instructions counted by the `CI` register.
+    ///
+    /// Unlike the complexity limit, the instruction limit doesn't depend on the complexity of the
+    /// instructions, and thus is not affected by changes in the complexity model.
+    pub instr_lim: Option<u64>,
+    /// Whether to verify call stack integrity.
+    ///
+    /// If set, each call stack frame is also pushed to a shadow stack together with a running
+    /// hash chain, and each frame popped from the call stack is verified against them. On a
+    /// mismatch, the program is terminated setting `CK` to a failure, and the violation is
+    /// reported by [`Core::cs_fault`].
+    ///
+    /// When a subcore is merged with [`Supercore::merge_subcore`], the hash chain is recomputed
+    /// over the whole call stack and verified.
+    pub cs_integrity: bool,
+    /// Behavior of the reserved instructions, i.e. instructions with opcodes not assigned to any
+    /// operation in the ISA used by the virtual machine.
+    pub unknown_instr: UnknownInstrPolicy,
+}
+
+/// Behavior of reserved instructions, which may be present in libraries compiled for a future
+/// version of the ISA.
+#[derive(Copy, Clone, Eq, PartialEq, PartialOrd, Ord, Hash, Debug, Default, Display)]
+#[derive(StrictType, StrictEncode, StrictDecode)]
+#[strict_type(lib = LIB_NAME_ALUVM, tags = repr, into_u8, try_from_u8)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(rename_all = "camelCase"))]
+#[repr(u8)]
+pub enum UnknownInstrPolicy {
+    /// Reserved instruction sets `CK` to a failure, halting the program if `CH` is set.
+    ///
+    /// The instruction has the maximal complexity, such that the program also fails if a
+    /// complexity limit is set. This is the default policy, required for consensus-critical
+    /// execution.
+    #[default]
+    #[strict_type(dumb)]
+    #[display("fail")]
+    Fail = 0,
+
+    /// Reserved instruction is skipped as a trivial no-operation.
+    ///
+    /// Used by analysis tools to trace program execution past unknown code regions.
+    #[display("nop")]
+    Nop = 1,
+
+    /// Reserved instruction stops the program without a failure.
+    #[display("halt")]
+    Halt = 2,
 }
 
 impl Default for CoreConfig {
     /// Sets
     /// - [`CoreConfig::halt`] to `true`,
     /// - [`CoreConfig::complexity_lim`] to `None`
+    /// - [`CoreConfig::complexity_warn`] to `None`
+    /// - [`CoreConfig::jump_lim`] to `None`
+    /// - [`CoreConfig::instr_lim`] to `None`
+    /// - [`CoreConfig::cs_integrity`] to `false`
+    /// - [`CoreConfig::unknown_instr`] to [`UnknownInstrPolicy::Fail`]
     ///
     /// # See also
     ///
     /// - [`CoreConfig::halt`]
     /// - [`CoreConfig::complexity_lim`]
-    fn default() -> Self { CoreConfig { halt: true, complexity_lim: None } }
+    /// - [`CoreConfig::complexity_warn`]
+    /// - [`CoreConfig::jump_lim`]
+    /// - [`CoreConfig::instr_lim`]
+    /// - [`CoreConfig::cs_integrity`]
+    /// - [`CoreConfig::unknown_instr`]
+    fn default() -> Self {
+        CoreConfig {
+            halt: true,
+            complexity_lim: None,
+            complexity_warn: None,
+            jump_lim: None,
+            instr_lim: None,
+            cs_integrity: false,
+            unknown_instr: UnknownInstrPolicy::Fail,
+        }
+    }
 }
 
 impl<Id: SiteId, Cx: CoreExt, const CALL_STACK_SIZE: usize> Default
@@ -171,28 +698,65 @@ impl<Id: SiteId, Cx: CoreExt, const CALL_STACK_SIZE: usize> Default
 }
 
 impl<Id: SiteId, Cx: CoreExt, const CALL_STACK_SIZE: usize> Core<Id, Cx, CALL_STACK_SIZE> {
+    /// Capacity of the call stack, i.e. the maximal depth of nested calls, defined by the
+    /// `CALL_STACK_SIZE` generic parameter.
+    ///
+    /// The parameter must not exceed [`CALL_STACK_SIZE_MAX`], such that the call stack depth is
+    /// always representable by the `cp` register. This is checked at compile time, once a core
+    /// with the given parameter is constructed:
+    ///
+    /// ```compile_fail
+    /// use aluvm::regs::CALL_STACK_SIZE_MAX;
+    /// use aluvm::{Core, LibId, NoExt};
+    ///
+    /// let core = Core::<LibId, NoExt, { CALL_STACK_SIZE_MAX as usize + 1 }>::new();
+    /// ```
+    pub const CALL_STACK_SIZE: usize = {
+        assert!(CALL_STACK_SIZE <= CALL_STACK_SIZE_MAX as usize, "Call stack size is too large");
+        CALL_STACK_SIZE
+    };
+
     /// Initializes registers. Sets `CK` to `true`, counters to zero, call stack to empty and the
     /// rest of registers to `None` value.
     ///
     /// An alias for [`Core::with`]`(`[`CoreConfig::default()`]`, Cx::default())`.
     #[inline]
-    pub fn new() -> Self {
-        assert!(CALL_STACK_SIZE <= CALL_STACK_SIZE_MAX as usize, "Call stack size is too large");
-        Core::with(default!(), default!())
-    }
+    pub fn new() -> Self { Core::with(default!(), default!()) }
 
     /// Initializes registers using a configuration object [`CoreConfig`].
     pub fn with(config: CoreConfig, cx_config: Cx::Config) -> Self {
-        assert!(CALL_STACK_SIZE <= CALL_STACK_SIZE_MAX as usize, "Call stack size is too large");
         Core {
             ch: config.halt,
             ck: Status::Ok,
             cf: 0,
             co: Status::Ok,
             cy: 0,
+            cy_lim: config.jump_lim,
+            ci: 0,
+            ci_lim: config.instr_lim,
             ca: 0,
             cl: config.complexity_lim,
-            cs: ConfinedVec::with_capacity(CALL_STACK_SIZE),
+            cw: config.complexity_warn,
+            cw_site: None,
+            cs: ConfinedVec::with_capacity(Self::CALL_STACK_SIZE),
+            cs_shadow: config.cs_integrity.then(Vec::new),
+            cs_budgets: Vec::new(),
+            cs_budget_next: None,
+            ss: ConfinedVec::new(),
+            ss_regs: Some(Vec::new()),
+            cs_fault: None,
+            jump_fault: None,
+            cs_overflow: None,
+            last_failure: None,
+            next_failure: None,
+            termination: None,
+            next_termination: None,
+            profile: None,
+            cs_high_water: 0,
+            xcp: 0,
+            xcs_high_water: 0,
+            unknown_instr: config.unknown_instr,
+            complexity_model: ComplexityModel::DEFAULT,
             cx: Cx::with(cx_config),
         }
     }
@@ -201,44 +765,52 @@ impl<Id: SiteId, Cx: CoreExt, const CALL_STACK_SIZE: usize> Core<Id, Cx, CALL_ST
     pub fn reset(&mut self) {
         let mut new = Self::new();
         new.ch = self.ch;
+        new.cy_lim = self.cy_lim;
+        new.ci_lim = self.ci_lim;
+        new.unknown_instr = self.unknown_instr;
+        new.complexity_model = mem::take(&mut self.complexity_model);
         new.cl = self.cl;
+        new.cw = self.cw;
+        new.cs_shadow = self.cs_shadow.as_ref().map(|_| Vec::new());
+        new.profile = self.profile.as_ref().map(|p| Profile::with_cap(p.cap()));
         new.cx.reset();
         *self = new;
     }
+
+    /// Runs a closure over a copy of the core, leaving all of its registers intact.
+    ///
+    /// Allows a host to probe the core state (for instance, seeding `CO` and checking how the
+    /// control registers change) without perturbing an ongoing program execution. To probe the
+    /// execution of a program, use [`crate::Vm::run_isolated`].
+    pub fn run_isolated<T>(&self, f: impl FnOnce(&mut Self) -> T) -> T { f(&mut self.clone()) }
+
+    /// Constructs a core from a fixture, writing the register values as they are.
+    #[cfg(any(test, feature = "tests"))]
+    pub(crate) fn from_fixture(fixture: CoreFixture<Id>, cx_config: Cx::Config) -> Self {
+        let mut core = Self::with(fixture.config, cx_config);
+        for (from, to) in fixture.frames {
+            core.push_xcs(from, to)
+                .expect("fixture frames exceed the call stack capacity");
+        }
+        core.ck = fixture.ck.unwrap_or(core.ck);
+        core.cf = fixture.cf.unwrap_or(core.cf);
+        core.co = fixture.co.unwrap_or(core.co);
+        core.ca = fixture.ca.unwrap_or(core.ca);
+        core.cw_site = fixture.cw_site.or(core.cw_site);
+        core.cs_high_water = fixture.cs_high_water.unwrap_or(core.cs_high_water);
+        core.xcp = fixture.xcp.unwrap_or(core.xcp);
+        core.xcs_high_water = fixture.xcs_high_water.unwrap_or(core.xcs_high_water);
+        if let (Some(shadow), Some(depth)) = (&mut core.cs_shadow, fixture.shadow_depth) {
+            shadow.truncate(depth);
+        }
+        core
+    }
 }
 
-#[cfg_attr(coverage_nightly, coverage(off))]
 impl<Id: SiteId, Cx: CoreExt, const CALL_STACK_SIZE: usize> Debug
     for Core<Id, Cx, CALL_STACK_SIZE>
 {
-    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        let (sect, reg, val, reset) = if f.alternate() {
-            ("\x1B[0;4;1m", "\x1B[0;1m", "\x1B[0;32m", "\x1B[0m")
-        } else {
-            ("", "", "", "")
-        };
-
-        writeln!(f, "{sect}C-regs:{reset}")?;
-        write!(f, "{reg}CH{reset} {val}{}{reset}, ", self.ch)?;
-        write!(f, "{reg}CK{reset} {val}{}{reset}, ", self.ck)?;
-        write!(f, "{reg}CF{reset} {val}{}{reset}, ", self.cf)?;
-        write!(f, "{reg}CO{reset} {val}{}{reset}, ", self.co)?;
-        write!(f, "{reg}CY{reset} {val}{}{reset}, ", self.cy)?;
-        write!(f, "{reg}CA{reset} {val}{}{reset}, ", self.ca)?;
-        let cl = self
-            .cl
-            .map(|v| v.to_string())
-            .unwrap_or_else(|| "~".to_string());
-        write!(f, "{reg}CL{reset} {val}{cl}{reset}, ")?;
-        write!(f, "{reg}CP{reset} {val}{}{reset}, ", self.cp())?;
-        write!(f, "\n{reg}CS{reset} {val}{reset}")?;
-        for item in &self.cs {
-            write!(f, "{}   ", item)?;
-        }
-        writeln!(f)?;
-
-        Debug::fmt(&self.cx, f)
-    }
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result { Display::fmt(&self.dump(), f) }
 }
 
 impl<Id: SiteId, Cx: CoreExt + Supercore<Cx2>, Cx2: CoreExt, const CALL_STACK_SIZE: usize>
@@ -251,22 +823,570 @@ impl<Id: SiteId, Cx: CoreExt + Supercore<Cx2>, Cx2: CoreExt, const CALL_STACK_SI
             cf: self.cf,
             co: self.co,
             cy: self.cy,
+            cy_lim: self.cy_lim,
+            ci: self.ci,
+            ci_lim: self.ci_lim,
             ca: self.ca,
             cl: self.cl,
+            cw: self.cw,
+            cw_site: self.cw_site,
             cs: self.cs.clone(),
+            cs_shadow: self.cs_shadow.clone(),
+            cs_budgets: self.cs_budgets.clone(),
+            cs_budget_next: self.cs_budget_next,
+            ss: self.ss.clone(),
+            ss_regs: None,
+            cs_fault: self.cs_fault,
+            jump_fault: self.jump_fault,
+            cs_overflow: self.cs_overflow,
+            last_failure: self.last_failure.clone(),
+            next_failure: self.next_failure.clone(),
+            termination: self.termination,
+            next_termination: self.next_termination,
+            profile: self.profile.clone(),
+            cs_high_water: self.cs_high_water,
+            xcp: self.xcp,
+            xcs_high_water: self.xcs_high_water,
+            unknown_instr: self.unknown_instr,
+            complexity_model: self.complexity_model.clone(),
             cx: self.cx.subcore(),
         }
     }
 
+    /// Merges the registers of a subcore, previously created from this core with
+    /// [`Supercore::subcore`], back into the core.
+    ///
+    /// The register save stack frames pushed by the subcore save the registers of this core,
+    /// while the registers saved by the frames popped by the subcore are restored.
+    ///
+    /// # Panics
+    ///
+    /// If the `CH`, `CL` or `CW` registers, the jump or instruction limits, the reserved
+    /// instruction policy, the complexity model, or the call stack integrity mode of the
+    /// subcore differ from the ones of this core. Since no instruction can modify them, this
+    /// never happens for a subcore created with [`Supercore::subcore`].
     fn merge_subcore(&mut self, subcore: Core<Id, Cx2, CALL_STACK_SIZE>) {
         assert_eq!(self.ch, subcore.ch);
         self.ck = subcore.ck;
         self.co = subcore.co;
         self.cf = subcore.cf;
         self.cy = subcore.cy;
+        assert_eq!(self.cy_lim, subcore.cy_lim);
+        self.ci = subcore.ci;
+        assert_eq!(self.ci_lim, subcore.ci_lim);
+        assert_eq!(self.unknown_instr, subcore.unknown_instr);
+        assert_eq!(self.complexity_model, subcore.complexity_model);
         self.ca = subcore.ca;
         assert_eq!(self.cl, subcore.cl);
+        assert_eq!(self.cw, subcore.cw);
+        self.cw_site = subcore.cw_site;
+        assert_eq!(self.cs_shadow.is_some(), subcore.cs_shadow.is_some());
         self.cs = subcore.cs;
+        self.cs_shadow = subcore.cs_shadow;
+        self.cs_budgets = subcore.cs_budgets;
+        self.cs_budget_next = subcore.cs_budget_next;
+        self.ss = subcore.ss;
+        self.cs_fault = subcore.cs_fault;
+        self.jump_fault = subcore.jump_fault;
+        self.cs_overflow = subcore.cs_overflow;
+        self.last_failure = subcore.last_failure;
+        self.next_failure = subcore.next_failure;
+        self.termination = subcore.termination;
+        self.next_termination = subcore.next_termination;
+        self.profile = subcore.profile;
+        self.cs_high_water = self.cs_high_water.max(subcore.cs_high_water);
+        self.xcp = subcore.xcp;
+        self.xcs_high_water = self.xcs_high_water.max(subcore.xcs_high_water);
+        if let Err(fault) = self.verify_cs() {
+            self.cs_fault = Some(fault);
+            let _ = self.fail_ck_with(FailureReason::CallStackFault);
+        }
         self.cx.merge_subcore(subcore.cx);
+        self.restore_ss();
+        self.save_ss();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![cfg_attr(coverage_nightly, coverage(off))]
+
+    use super::*;
+    use crate::{LibId, NoExt};
+
+    fn integrity_core() -> Core<LibId, NoExt> {
+        Core::with(CoreConfig { cs_integrity: true, ..default!() }, ())
+    }
+
+    fn site(offset: u16) -> Site<LibId> { Site::new(LibId::from([0xA5u8; 32]), offset) }
+
+    impl Supercore<NoExt> for NoExt {
+        fn subcore(&self) -> NoExt { NoExt }
+        fn merge_subcore(&mut self, _: NoExt) {}
+    }
+
+    #[test]
+    fn debug_dump() {
+        let mut core = Core::<LibId, NoExt>::with(
+            CoreConfig { complexity_lim: Some(10_000_000_000), ..default!() },
+            (),
+        );
+        core.acc_complexity_at(site(0), 1_234_567);
+        assert_eq!(
+            format!("{core:?}"),
+            "C-regs:\nCH true, CK ok, CF 0, CO ok, CY 0, CI 0, CA 1_234_567, CL 10_000_000_000, \
+             CW ~, CP 0 (max 0, cross-lib 0, max 0), \nCS \n"
+        );
+    }
+
+    #[test]
+    fn failure_reason() {
+        let mut core = Core::<LibId, NoExt>::new();
+        assert_eq!(core.last_failure(), None);
+        assert!(core.fail_ck());
+        assert_eq!(core.last_failure(), Some(&FailureReason::Unspecified));
+
+        core.set_failure(FailureReason::Overflow);
+        assert_eq!(core.last_failure(), Some(&FailureReason::Unspecified));
+        assert!(core.fail_ck());
+        assert_eq!(core.last_failure(), Some(&FailureReason::Overflow));
+        // The reason is consumed by the failure it was provided for
+        assert!(core.fail_ck());
+        assert_eq!(core.last_failure(), Some(&FailureReason::Unspecified));
+
+        let custom = FailureReason::Custom(IsaId::from("TEST"), 0x0A);
+        assert!(core.fail_ck_with(custom.clone()));
+        assert_eq!(core.cf(), 4);
+        core.reset_ck();
+        assert_eq!(core.last_failure(), Some(&custom));
+        assert_eq!(custom.to_string(), "failure 0x000A specific to the TEST ISA extension.");
+        core.reset();
+        assert_eq!(core.last_failure(), None);
+    }
+
+    #[test]
+    fn call_stack_overflow() {
+        let mut core = Core::<LibId, NoExt, 4>::new();
+        assert_eq!(core.call_stack_capacity(), 4);
+        assert_eq!(Core::<LibId, NoExt, 4>::CALL_STACK_SIZE, 4);
+        // Only the chosen call stack size is reserved, and not the maximal one
+        assert!(core.cs.capacity() < CALL_STACK_SIZE_MAX as usize);
+        for offset in 1..=4 {
+            assert_eq!(core.push_cs(site(offset)), Some(offset));
+        }
+        assert_eq!(core.call_stack(), [site(1), site(2), site(3), site(4)]);
+        assert_eq!(core.cs_overflow(), None);
+
+        assert_eq!(core.push_cs(site(5)), None);
+        assert_eq!(core.cs_overflow(), Some(site(5)));
+        assert_eq!(core.call_stack(), [site(1), site(2), site(3), site(4)]);
+        assert_eq!(core.cs_high_water(), 4);
+        assert_eq!(core.check_invariants(), Ok(()));
+
+        assert_eq!(core.pop_cs(), Some(site(4)));
+        assert_eq!(core.call_stack(), [site(1), site(2), site(3)]);
+        core.reset();
+        assert_eq!(core.cs_overflow(), None);
+        assert!(core.call_stack().is_empty());
+    }
+
+    #[test]
+    fn call_stack_depth() {
+        let mut core = Core::<LibId, NoExt>::new();
+        assert_eq!(core.call_stack_capacity(), CALL_STACK_SIZE_MAX as usize);
+        assert_eq!(core.cp(), 0);
+        assert_eq!(core.pop_cs(), None);
+        assert_eq!(core.cp(), 0);
+
+        for offset in 1..=CALL_STACK_SIZE_MAX {
+            assert_eq!(core.push_cs(site(offset)), Some(offset));
+            assert_eq!(core.cp(), offset);
+        }
+        assert_eq!(core.cs_overflow(), None);
+        assert_eq!(core.push_cs(site(0)), None);
+        assert_eq!(core.cs_overflow(), Some(site(0)));
+        assert_eq!(core.cp(), CALL_STACK_SIZE_MAX);
+        assert_eq!(core.cs_high_water(), CALL_STACK_SIZE_MAX);
+
+        for offset in (1..=CALL_STACK_SIZE_MAX).rev() {
+            assert_eq!(core.pop_cs(), Some(site(offset)));
+            assert_eq!(core.cp(), offset - 1);
+        }
+        assert_eq!(core.pop_cs(), None);
+        assert_eq!(core.cp(), 0);
+        assert_eq!(core.cs_high_water(), CALL_STACK_SIZE_MAX);
+    }
+
+    #[test]
+    fn cs_integrity_nested() {
+        let mut core = integrity_core();
+        assert!(core.cs_integrity());
+        for offset in 1..=5 {
+            assert_eq!(core.push_cs(site(offset)), Some(offset));
+            assert_eq!(core.verify_cs(), Ok(()));
+        }
+        assert_eq!(core.pop_cs(), Some(site(5)));
+        assert_eq!(core.pop_cs(), Some(site(4)));
+        assert_eq!(core.push_cs(site(10)), Some(4));
+        assert_eq!(core.verify_cs(), Ok(()));
+        for offset in [10, 3, 2, 1] {
+            assert_eq!(core.pop_cs(), Some(site(offset)));
+        }
+        assert_eq!(core.pop_cs(), None);
+        assert_eq!(core.cs_fault(), None);
+        assert_eq!(core.ck(), Status::Ok);
+    }
+
+    #[test]
+    fn cs_integrity_frame_mismatch() {
+        let mut core = integrity_core();
+        core.push_cs(site(1)).unwrap();
+        core.push_cs(site(2)).unwrap();
+        core.cs.pop();
+        core.cs.push(site(3)).unwrap();
+        let fault = CallStackFault::FrameMismatch { expected: site(2), found: site(3) };
+        assert_eq!(core.verify_cs(), Err(fault));
+        assert_eq!(core.pop_cs(), None);
+        assert_eq!(core.cs_fault(), Some(fault));
+        assert_eq!(core.ck(), Status::Fail);
+        assert_eq!(core.cf(), 1);
+    }
+
+    #[test]
+    fn cs_integrity_chain_mismatch() {
+        let mut core = integrity_core();
+        core.push_cs(site(1)).unwrap();
+        core.push_cs(site(2)).unwrap();
+        core.cs_shadow.as_mut().unwrap()[0].1 ^= 1;
+        let fault = CallStackFault::ChainMismatch { frame: site(1) };
+        assert_eq!(core.verify_cs(), Err(fault));
+        assert_eq!(core.pop_cs(), None);
+        assert_eq!(core.cs_fault(), Some(CallStackFault::ChainMismatch { frame: site(2) }));
+        assert_eq!(core.ck(), Status::Fail);
+    }
+
+    #[test]
+    fn cs_integrity_no_shadow_frame() {
+        let mut core = integrity_core();
+        core.cs.push(site(1)).unwrap();
+        let fault = CallStackFault::NoShadowFrame { found: site(1) };
+        assert_eq!(core.verify_cs(), Err(fault));
+        assert_eq!(core.pop_cs(), None);
+        assert_eq!(core.cs_fault(), Some(fault));
+    }
+
+    #[test]
+    fn cs_integrity_off() {
+        let mut core = Core::<LibId, NoExt>::new();
+        assert!(!core.cs_integrity());
+        core.push_cs(site(1)).unwrap();
+        core.cs.pop();
+        core.cs.push(site(3)).unwrap();
+        assert_eq!(core.verify_cs(), Ok(()));
+        assert_eq!(core.pop_cs(), Some(site(3)));
+        assert_eq!(core.cs_fault(), None);
+        assert_eq!(core.ck(), Status::Ok);
+    }
+
+    #[test]
+    fn cs_high_water() {
+        let mut core = Core::<LibId, NoExt>::new();
+        let other = LibId::from([0x5Au8; 32]);
+        core.push_cs(site(1)).unwrap();
+        core.push_xcs(site(2), other).unwrap();
+        core.push_xcs(Site::new(other, 3), other).unwrap();
+        core.push_xcs(Site::new(other, 4), site(0).prog_id).unwrap();
+        assert_eq!((core.cs_high_water(), core.xcs_high_water(), core.xcp()), (4, 2, 2));
+        assert_eq!(core.pop_xcs(site(0).prog_id), Some(Site::new(other, 4)));
+        assert_eq!(core.pop_xcs(other), Some(Site::new(other, 3)));
+        assert_eq!(core.pop_xcs(other), Some(site(2)));
+        assert_eq!(core.xcp(), 0);
+        core.push_cs(site(5)).unwrap();
+        assert_eq!((core.cs_high_water(), core.xcs_high_water(), core.cp()), (4, 2, 2));
+        core.reset();
+        assert_eq!((core.cs_high_water(), core.xcs_high_water(), core.xcp()), (0, 0, 0));
+    }
+
+    #[test]
+    fn cs_high_water_subcore() {
+        let mut core = Core::<LibId, NoExt>::new();
+        core.push_cs(site(1)).unwrap();
+        core.push_cs(site(2)).unwrap();
+        core.pop_cs().unwrap();
+        let mut subcore: Core<LibId, NoExt> = core.subcore();
+        subcore.push_cs(site(3)).unwrap();
+        subcore.push_cs(site(4)).unwrap();
+        subcore.pop_cs().unwrap();
+        subcore.pop_cs().unwrap();
+        core.merge_subcore(subcore);
+        assert_eq!(core.cs_high_water(), 3);
+
+        let subcore: Core<LibId, NoExt> = core.subcore();
+        core.push_cs(site(5)).unwrap();
+        core.push_cs(site(6)).unwrap();
+        core.push_cs(site(7)).unwrap();
+        core.merge_subcore(subcore);
+        assert_eq!(core.cs_high_water(), 4);
+        assert_eq!(core.cp(), 1);
+    }
+
+    #[test]
+    fn complexity_overflow() {
+        let mut core = Core::<LibId, NoExt>::new();
+        assert!(core.acc_complexity(u64::MAX - 1));
+        assert!(core.acc_complexity(1));
+        assert!(!core.acc_complexity(1));
+        assert_eq!(core.ca(), u64::MAX);
+
+        let config = CoreConfig { complexity_warn: Some(u64::MAX), ..default!() };
+        let mut core = Core::<LibId, NoExt>::with(config, ());
+        assert!(core.acc_complexity_at(site(0), u64::MAX - 1));
+        assert!(!core.acc_complexity_at(site(1), 2));
+        assert_eq!(core.ca(), u64::MAX);
+        assert_eq!(core.cw_site(), Some(site(1)));
+    }
+
+    #[test]
+    fn frame_budgets() {
+        let mut core = Core::<LibId, NoExt>::new();
+        let other = LibId::from([0x5Au8; 32]);
+        core.push_cs(site(1)).unwrap();
+        assert_eq!(core.frame_budget(), None);
+
+        core.acc_complexity(100);
+        core.push_budget(50);
+        core.push_xcs(site(2), other).unwrap();
+        assert_eq!(core.frame_budget(), Some(50));
+        // The budget is capped by the outer one
+        core.push_budget(1000);
+        core.push_cs(Site::new(other, 3)).unwrap();
+        assert_eq!(core.frame_budget(), Some(50));
+        core.push_cs(Site::new(other, 4)).unwrap();
+        assert_eq!(core.cs_budgets, [(2, 150), (3, 150)]);
+
+        assert!(core.acc_complexity(49));
+        assert_eq!(core.exhausted_frame(), None);
+        assert!(core.acc_complexity(1));
+        assert_eq!(core.exhausted_frame(), Some(2));
+        assert_eq!(core.unwind_cs(2, other), Some(site(2)));
+        assert_eq!(core.cp(), 1);
+        assert_eq!(core.xcp(), 0);
+        assert_eq!(core.ck(), Status::Fail);
+        assert_eq!(core.cf(), 1);
+        assert_eq!(core.frame_budget(), None);
+        assert_eq!(core.exhausted_frame(), None);
+        assert_eq!(core.unwind_cs(2, other), None);
+        assert_eq!(core.check_invariants(), Ok(()));
+    }
+
+    #[test]
+    fn frame_budget_returned() {
+        let mut core = Core::<LibId, NoExt>::new();
+        core.push_budget(10);
+        // The budget applies to the next frame only
+        core.push_cs(site(1)).unwrap();
+        core.push_cs(site(2)).unwrap();
+        assert_eq!(core.cs_budgets, [(1, 10)]);
+        core.pop_cs().unwrap();
+        assert_eq!(core.frame_budget(), Some(10));
+        core.pop_cs().unwrap();
+        assert_eq!(core.frame_budget(), None);
+        assert!(core.cs_budgets.is_empty());
+        core.acc_complexity(100);
+        assert_eq!(core.exhausted_frame(), None);
+
+        // Failed call doesn't retain the budget
+        let mut core = Core::<LibId, NoExt, 1>::new();
+        core.push_cs(site(1)).unwrap();
+        core.push_budget(10);
+        assert_eq!(core.push_cs(site(2)), None);
+        core.pop_cs().unwrap();
+        core.push_cs(site(3)).unwrap();
+        assert_eq!(core.frame_budget(), None);
+    }
+
+    #[test]
+    fn jump_count() {
+        let mut core =
+            Core::<LibId, NoExt>::with(CoreConfig { jump_lim: Some(2), ..default!() }, ());
+        assert_eq!(core.cy_lim(), Some(2));
+        assert!(core.acc_jump());
+        assert!(core.acc_jump());
+        assert!(!core.acc_jump());
+        assert_eq!(core.cy(), 2);
+        core.reset();
+        assert_eq!(core.cy(), 0);
+        assert_eq!(core.cy_lim(), Some(2));
+
+        let mut core = Core::<LibId, NoExt>::new();
+        core.cy = u16::MAX - 1;
+        assert!(core.acc_jump());
+        assert!(!core.acc_jump());
+        assert_eq!(core.cy(), u16::MAX);
+    }
+
+    #[test]
+    fn instr_count() {
+        let config = CoreConfig { instr_lim: Some(2), ..default!() };
+        let mut core = Core::<LibId, NoExt>::with(config, ());
+        assert_eq!(core.ci_lim(), Some(2));
+        assert!(core.acc_instr());
+        let mut subcore: Core<LibId, NoExt> = core.subcore();
+        assert_eq!(subcore.ci(), 1);
+        assert!(subcore.acc_instr());
+        assert!(!subcore.acc_instr());
+        core.merge_subcore(subcore);
+        assert_eq!(core.ci(), 2);
+        assert!(!core.acc_instr());
+        assert!(format!("{core:?}").contains(", CI 2, "));
+        core.reset();
+        assert_eq!(core.ci(), 0);
+        assert_eq!(core.ci_lim(), Some(2));
+
+        let mut core = Core::<LibId, NoExt>::new();
+        core.ci = u64::MAX - 1;
+        assert!(core.acc_instr());
+        assert!(!core.acc_instr());
+        assert_eq!(core.ci(), u64::MAX);
+    }
+
+    #[test]
+    fn cs_integrity_reset() {
+        let mut core = integrity_core();
+        core.push_cs(site(1)).unwrap();
+        core.reset();
+        assert!(core.cs_integrity());
+        assert_eq!(core.cp(), 0);
+        assert_eq!(core.verify_cs(), Ok(()));
+    }
+
+    fn check(fixture: CoreFixture<LibId>) -> Result<(), InvariantViolation> {
+        fixture
+            .build::<NoExt, { CALL_STACK_SIZE_MAX as usize }>(())
+            .check_invariants()
+    }
+
+    fn lib(byte: u8) -> LibId { LibId::from([byte; 32]) }
+
+    #[test]
+    fn invariants_hold() {
+        assert_eq!(check(CoreFixture::new()), Ok(()));
+        let config = CoreConfig {
+            complexity_lim: Some(100),
+            complexity_warn: Some(10),
+            cs_integrity: true,
+            ..default!()
+        };
+        let fixture = CoreFixture::with(config)
+            .call(site(1), lib(1))
+            .call(Site::new(lib(1), 2), lib(1))
+            .ck(Status::Fail)
+            .cf(1)
+            .ca(100)
+            .cw_site(site(1));
+        assert_eq!(check(fixture), Ok(()));
+    }
+
+    #[test]
+    fn invariant_uncounted_failure() {
+        assert_eq!(
+            check(CoreFixture::new().ck(Status::Fail)),
+            Err(InvariantViolation::UncountedFailure)
+        );
+    }
+
+    #[test]
+    fn invariant_complexity_overrun() {
+        let config = CoreConfig { complexity_lim: Some(100), ..default!() };
+        assert_eq!(check(CoreFixture::with(config).ca(99)), Ok(()));
+        assert_eq!(
+            check(CoreFixture::with(config).ca(100)),
+            Err(InvariantViolation::ComplexityOverrun { ca: 100, cl: 100 })
+        );
+    }
+
+    #[test]
+    fn invariant_premature_warning() {
+        assert_eq!(
+            check(CoreFixture::new().ca(10).cw_site(site(1))),
+            Err(InvariantViolation::PrematureWarning { ca: 10, cw: None })
+        );
+        let config = CoreConfig { complexity_warn: Some(10), ..default!() };
+        assert_eq!(
+            check(CoreFixture::with(config).ca(9).cw_site(site(1))),
+            Err(InvariantViolation::PrematureWarning { ca: 9, cw: Some(10) })
+        );
+    }
+
+    #[test]
+    fn invariant_call_stack_overflow() {
+        assert_eq!(
+            check(CoreFixture::new().cs_high_water(CALL_STACK_SIZE_MAX + 1)),
+            Err(InvariantViolation::CallStackOverflow {
+                high_water: CALL_STACK_SIZE_MAX + 1,
+                capacity: CALL_STACK_SIZE_MAX as usize
+            })
+        );
+        let core = CoreFixture::<LibId>::new()
+            .cs_high_water(2)
+            .build::<NoExt, 1>(());
+        assert_eq!(
+            core.check_invariants(),
+            Err(InvariantViolation::CallStackOverflow { high_water: 2, capacity: 1 })
+        );
+    }
+
+    #[test]
+    fn invariant_call_stack_above_high_water() {
+        assert_eq!(
+            check(CoreFixture::new().call(site(1), lib(1)).cs_high_water(0)),
+            Err(InvariantViolation::CallStackAboveHighWater { depth: 1, high_water: 0 })
+        );
+    }
+
+    #[test]
+    fn invariant_cross_frames_overflow() {
+        assert_eq!(
+            check(
+                CoreFixture::new()
+                    .call(site(1), lib(1))
+                    .xcp(2)
+                    .xcs_high_water(2)
+            ),
+            Err(InvariantViolation::CrossFramesOverflow { xcp: 2, depth: 1 })
+        );
+    }
+
+    #[test]
+    fn invariant_cross_frames_above_high_water() {
+        assert_eq!(
+            check(CoreFixture::new().call(site(1), lib(1)).xcs_high_water(0)),
+            Err(InvariantViolation::CrossFramesAboveHighWater { xcp: 1, high_water: 0 })
+        );
+    }
+
+    #[test]
+    fn invariant_shadow_stack_mismatch() {
+        let config = CoreConfig { cs_integrity: true, ..default!() };
+        let fixture = CoreFixture::with(config)
+            .call(site(1), lib(1))
+            .call(site(2), lib(1))
+            .shadow_depth(1);
+        assert_eq!(
+            check(fixture),
+            Err(InvariantViolation::ShadowStackMismatch { depth: 2, shadow: 1 })
+        );
+        // Ignored without the integrity mode
+        assert_eq!(check(CoreFixture::new().call(site(1), lib(1)).shadow_depth(0)), Ok(()));
+    }
+
+    #[test]
+    #[cfg(any(feature = "paranoid", debug_assertions))]
+    #[should_panic(expected = "core invariant violated: `CK` is in a failed state")]
+    fn invariants_asserted() {
+        CoreFixture::<LibId>::new()
+            .ck(Status::Fail)
+            .build::<NoExt, 1>(())
+            .assert_invariants(true);
     }
 }
@@ -0,0 +1,252 @@
+// Reference rust implementation of AluVM (arithmetic logic unit virtual machine).
+// To find more on AluVM please check <https://aluvm.org>
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Designed in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+// Written in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+//
+// Copyright (C) 2021-2024 LNP/BP Standards Association, Switzerland.
+// Copyright (C) 2024-2025 Laboratories for Ubiquitous Deterministic Computing (UBIDECO),
+//                         Institute for Distributed and Cognitive Systems (InDCS), Switzerland.
+// Copyright (C) 2021-2025 Dr Maxim Orlovsky.
+// All rights under the above copyrights are reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use this file except
+// in compliance with the License. You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed under the License
+// is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express
+// or implied. See the License for the specific language governing permissions and limitations under
+// the License.
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt::{self, Display, Formatter};
+
+use super::{Core, CoreExt, FailureReason, Register, Site, SiteId, Status};
+use crate::fmt::Dec;
+
+/// Snapshot of the core registers, including the registers of the ISA extension.
+///
+/// Produced by [`Core::dump`]. The snapshot is rendered by its [`Display`] implementation as plain
+/// text; the alternate form (`{:#}`) adds ANSI terminal colors.
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(rename_all = "camelCase"))]
+pub struct CoreDump<Id: SiteId> {
+    /// Value of the `CH` register.
+    pub ch: bool,
+    /// Value of the `CK` register.
+    pub ck: Status,
+    /// Value of the `CF` register.
+    pub cf: u64,
+    /// Value of the `CO` register.
+    pub co: Status,
+    /// Value of the `CY` register.
+    pub cy: u16,
+    /// Value of the `CI` register.
+    pub ci: u64,
+    /// Value of the `CA` register.
+    pub ca: u64,
+    /// Value of the `CL` register.
+    pub cl: Option<u64>,
+    /// Value of the `CW` register.
+    pub cw: Option<u64>,
+    /// Call stack (the `CS` register), with the most recent call being the last.
+    pub cs: Vec<Site<Id>>,
+    /// Maximal depth of the call stack reached during the execution.
+    pub cs_high_water: u16,
+    /// Number of cross-library calls in the call stack.
+    pub xcp: u16,
+    /// Maximal number of cross-library calls in the call stack reached during the execution.
+    pub xcs_high_water: u16,
+    /// Reason of the last `CK` failure, if any.
+    pub last_failure: Option<FailureReason>,
+    /// All registers of the ISA extension, in the order of [`Register::enumerate`].
+    pub regs: Vec<RegDump>,
+}
+
+/// Snapshot of a single ISA extension register, with the register name and value rendered as
+/// strings.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(rename_all = "camelCase"))]
+pub struct RegDump {
+    /// Name of the register.
+    pub reg: String,
+    /// Value of the register, or `None` if the register is not set.
+    pub val: Option<String>,
+}
+
+impl<Id: SiteId, Cx: CoreExt, const CALL_STACK_SIZE: usize> Core<Id, Cx, CALL_STACK_SIZE> {
+    /// Takes a snapshot of all core registers, including the registers of the ISA extension.
+    pub fn dump(&self) -> CoreDump<Id> {
+        CoreDump {
+            ch: self.ch,
+            ck: self.ck,
+            cf: self.cf,
+            co: self.co,
+            cy: self.cy,
+            ci: self.ci,
+            ca: self.ca,
+            cl: self.cl,
+            cw: self.cw,
+            cs: self.cs.iter().copied().collect(),
+            cs_high_water: self.cs_high_water,
+            xcp: self.xcp,
+            xcs_high_water: self.xcs_high_water,
+            last_failure: self.last_failure.clone(),
+            regs: Cx::Reg::enumerate()
+                .map(|reg| RegDump {
+                    reg: reg.to_string(),
+                    val: self.cx.get(reg).as_ref().map(ToString::to_string),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl<Id: SiteId> CoreDump<Id> {
+    /// Returns the value of the `CP` register, i.e. the depth of the call stack.
+    pub fn cp(&self) -> u16 { self.cs.len() as u16 }
+
+    /// Returns the snapshot of the ISA extension register with the given name, if present.
+    pub fn reg(&self, name: &str) -> Option<&RegDump> {
+        self.regs.iter().find(|reg| reg.reg == name)
+    }
+}
+
+impl<Id: SiteId> Display for CoreDump<Id> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let (sect, reg, val, reset) = if f.alternate() {
+            ("\x1B[0;4;1m", "\x1B[0;1m", "\x1B[0;32m", "\x1B[0m")
+        } else {
+            ("", "", "", "")
+        };
+
+        writeln!(f, "{sect}C-regs:{reset}")?;
+        write!(f, "{reg}CH{reset} {val}{}{reset}, ", self.ch)?;
+        write!(f, "{reg}CK{reset} {val}{}{reset}, ", self.ck)?;
+        write!(f, "{reg}CF{reset} {val}{}{reset}, ", Dec(self.cf))?;
+        write!(f, "{reg}CO{reset} {val}{}{reset}, ", self.co)?;
+        write!(f, "{reg}CY{reset} {val}{}{reset}, ", Dec(self.cy as u64))?;
+        write!(f, "{reg}CI{reset} {val}{}{reset}, ", Dec(self.ci))?;
+        write!(f, "{reg}CA{reset} {val}{}{reset}, ", Dec(self.ca))?;
+        match self.cl {
+            Some(cl) => write!(f, "{reg}CL{reset} {val}{}{reset}, ", Dec(cl))?,
+            None => write!(f, "{reg}CL{reset} {val}~{reset}, ")?,
+        }
+        match self.cw {
+            Some(cw) => write!(f, "{reg}CW{reset} {val}{}{reset}, ", Dec(cw))?,
+            None => write!(f, "{reg}CW{reset} {val}~{reset}, ")?,
+        }
+        write!(f, "{reg}CP{reset} {val}{}{reset} ", self.cp())?;
+        write!(f, "(max {val}{}{reset}, ", self.cs_high_water)?;
+        write!(
+            f,
+            "cross-lib {val}{}{reset}, max {val}{}{reset}), ",
+            self.xcp, self.xcs_high_water
+        )?;
+        write!(f, "\n{reg}CS{reset} {val}{reset}")?;
+        for item in &self.cs {
+            write!(f, "{}   ", item)?;
+        }
+        writeln!(f)?;
+        if let Some(reason) = &self.last_failure {
+            writeln!(f, "{reg}Last failure:{reset} {val}{reason}{reset}")?;
+        }
+
+        if self.regs.is_empty() {
+            return Ok(());
+        }
+        writeln!(f, "{sect}X-regs:{reset}")?;
+        for (no, dump) in self.regs.iter().enumerate() {
+            if no > 0 {
+                f.write_str(", ")?;
+            }
+            match &dump.val {
+                Some(v) => write!(f, "{reg}{}{reset} {val}{v}{reset}", dump.reg)?,
+                None => write!(f, "{reg}{}{reset} {val}~{reset}", dump.reg)?,
+            }
+        }
+        writeln!(f)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![cfg_attr(coverage_nightly, coverage(off))]
+
+    use super::*;
+    use crate::isa::{Alu64Core, RegA};
+    use crate::{CoreConfig, LibId};
+
+    fn site(offset: u16) -> Site<LibId> { Site::new(LibId::from([0xA5u8; 32]), offset) }
+
+    fn core() -> Core<LibId, Alu64Core> {
+        let mut core = Core::with(CoreConfig { complexity_lim: None, ..default!() }, ());
+        core.set(RegA::A1, 42);
+        core.set(RegA::A15, u64::MAX);
+        core.acc_complexity(2_000);
+        core.push_cs(site(7));
+        core
+    }
+
+    #[test]
+    fn dump() {
+        let dump = core().dump();
+        assert_eq!(dump.cp(), 1);
+        assert_eq!(dump.cs, vec![site(7)]);
+        assert_eq!(dump.ca, 2_000);
+        assert_eq!(dump.regs.len(), 16);
+        assert_eq!(dump.regs[0], RegDump { reg: s!("A0"), val: None });
+        assert_eq!(dump.reg("A1").unwrap().val.as_deref(), Some("42"));
+        assert_eq!(dump.reg("A15").unwrap().val.as_deref(), Some("18446744073709551615"));
+        assert_eq!(dump.reg("A16"), None);
+    }
+
+    #[test]
+    fn display() {
+        let dump = core().dump();
+        let plain = dump.to_string();
+        let (cregs, xregs) = plain.split_once("X-regs:\n").unwrap();
+        assert!(
+            cregs.starts_with("C-regs:\nCH true, CK ok, CF 0, CO ok, CY 0, CI 0, CA 2_000, CL ~, ")
+        );
+        assert!(cregs.ends_with(&format!("\nCS {}   \n", site(7))));
+        assert!(xregs.starts_with("A0 ~, A1 42, A2 ~, "));
+        assert!(xregs.ends_with(", A15 18446744073709551615\n"));
+        assert!(!plain.contains('\x1B'));
+
+        let colored = format!("{dump:#}");
+        assert!(colored.contains("\x1B[0;1mA1\x1B[0m \x1B[0;32m42\x1B[0m"));
+        assert_eq!(format!("{:#?}", core()), colored);
+        assert_eq!(format!("{:?}", core()), plain);
+    }
+
+    #[test]
+    fn last_failure() {
+        let mut core = core();
+        assert_eq!(core.dump().last_failure, None);
+        assert!(!core.dump().to_string().contains("Last failure:"));
+
+        assert!(core.fail_ck_with(FailureReason::NoValue));
+        let dump = core.dump();
+        assert_eq!(dump.last_failure, Some(FailureReason::NoValue));
+        assert!(dump
+            .to_string()
+            .contains("\nLast failure: access to a register which has no value.\n"));
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn serde_roundtrip() {
+        let dump = core().dump();
+        let json = serde_json::to_string(&dump).unwrap();
+        assert!(json.contains(r#""ck":"ok""#));
+        assert!(json.contains(r#"{"reg":"A1","val":"42"}"#));
+        assert!(json.contains(r#"{"reg":"A2","val":null}"#));
+        assert_eq!(serde_json::from_str::<CoreDump<LibId>>(&json).unwrap(), dump);
+    }
+}
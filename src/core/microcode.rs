@@ -22,7 +22,14 @@
 // or implied. See the License for the specific language governing permissions and limitations under
 // the License.
 
-use crate::core::{Core, CoreExt, SiteId, Status};
+use core::mem;
+
+use super::core::{CsChain, CS_CHAIN_SEED};
+use crate::core::{
+    CallStackFault, Core, CoreExt, FailureReason, InvariantViolation, JumpFault, Profile, SiteId,
+    Status, TerminationCause, UnknownInstrPolicy,
+};
+use crate::isa::ComplexityModel;
 use crate::{Register, Site};
 
 /// Microcode for flag registers.
@@ -31,6 +38,28 @@ impl<Id: SiteId, Cx: CoreExt, const CALL_STACK_SIZE: usize> Core<Id, Cx, CALL_ST
     pub fn co(&self) -> Status { self.co }
 
     /// Set overflow/carry flag to a value.
+    ///
+    /// # Example
+    ///
+    /// A host may seed `CO` before running a routine which branches on it:
+    ///
+    /// ```
+    /// # extern crate alloc;
+    /// # use aluvm::isa::Instr;
+    /// # use aluvm::regs::Status;
+    /// # use aluvm::{aluasm, Lib, LibId, LibSite, Vm};
+    /// let lib = Lib::assemble::<Instr<LibId>>(&aluasm! { chk CO; stop; })?;
+    /// let entry = LibSite::new(lib.lib_id(), 0);
+    ///
+    /// let mut vm = Vm::<Instr<LibId>>::new();
+    /// assert_eq!(vm.exec(entry, &(), |_| Some(&lib)), Status::Ok);
+    ///
+    /// let mut vm = Vm::<Instr<LibId>>::new();
+    /// vm.core.set_co(Status::Fail);
+    /// assert_eq!(vm.exec(entry, &(), |_| Some(&lib)), Status::Fail);
+    /// assert_eq!(vm.core.co(), Status::Fail);
+    /// # Ok::<_, aluvm::AssemblerError>(())
+    /// ```
     pub fn set_co(&mut self, co: Status) { self.co = co; }
 
     /// Return how many times `ck` was set to a failed state.
@@ -39,51 +68,607 @@ impl<Id: SiteId, Cx: CoreExt, const CALL_STACK_SIZE: usize> Core<Id, Cx, CALL_ST
     /// Return `true` if `ck` was in a failed state for at least once.
     pub fn has_failed(&self) -> bool { self.cf > 0 }
 
+    /// Return whether the program execution halts on the first failure (i.e. `ch` register value).
+    pub fn ch(&self) -> bool { self.ch }
+
     /// Return whether check register `ck` is in a failed state.
     pub fn ck(&self) -> Status { self.ck }
 
-    /// Set `CK` register to a failed state.
+    /// Set `CK` register to a failed state, incrementing the `CF` failure counter.
+    ///
+    /// The reason provided with [`Self::set_failure`] is recorded as [`Self::last_failure`]; if
+    /// there was none, the reason is [`FailureReason::Unspecified`].
     ///
     /// Returns whether further execution should be stopped (i.e. `ch` register value).
     #[must_use]
-    pub(crate) fn fail_ck(&mut self) -> bool {
+    pub fn fail_ck(&mut self) -> bool {
         self.ck = Status::Fail;
         self.cf += 1;
+        self.last_failure = Some(
+            self.next_failure
+                .take()
+                .unwrap_or(FailureReason::Unspecified),
+        );
         self.ch
     }
 
+    /// Set `CK` register to a failed state for the given reason (see [`Self::fail_ck`]).
+    #[must_use]
+    pub fn fail_ck_with(&mut self, reason: FailureReason) -> bool {
+        self.set_failure(reason);
+        self.fail_ck()
+    }
+
+    /// Set the reason for the next failure of the `CK` register.
+    ///
+    /// Instructions call this method before returning [`crate::ExecStep::Fail`], such that the
+    /// host can learn the failure cause from [`Self::last_failure`].
+    pub fn set_failure(&mut self, reason: FailureReason) { self.next_failure = Some(reason); }
+
+    /// Return the reason of the last failure of the `CK` register, if any.
+    ///
+    /// Unlike `CK`, the reason is not cleared when `CK` is reset.
+    pub fn last_failure(&self) -> Option<&FailureReason> { self.last_failure.as_ref() }
+
+    /// Set the cause for the next program termination.
+    ///
+    /// Instructions call this method before returning [`crate::ExecStep::Stop`], such that the
+    /// host can learn the termination cause from [`Self::termination`]. Without it, the
+    /// termination cause is [`TerminationCause::ExplicitStop`].
+    pub fn set_termination(&mut self, cause: TerminationCause<Id>) {
+        self.next_termination = Some(cause);
+    }
+
+    /// Record the program termination, with the cause provided by [`Self::set_termination`], or
+    /// [`TerminationCause::ExplicitStop`] if there was none.
+    ///
+    /// Called by the execution loop once an instruction returns [`crate::ExecStep::Stop`].
+    pub fn terminate(&mut self) {
+        let cause = self
+            .next_termination
+            .take()
+            .unwrap_or(TerminationCause::ExplicitStop);
+        self.terminate_with(cause);
+    }
+
+    /// Record the program termination for the given cause, discarding the one provided by
+    /// [`Self::set_termination`].
+    ///
+    /// Only the first termination is recorded: the cause is kept until [`Self::reset_termination`]
+    /// is called at the start of the next execution.
+    pub fn terminate_with(&mut self, cause: TerminationCause<Id>) {
+        self.next_termination = None;
+        self.termination.get_or_insert(cause);
+    }
+
+    /// Return the cause of the program execution termination, or `None` if the program execution
+    /// hasn't terminated (yet).
+    pub fn termination(&self) -> Option<TerminationCause<Id>> { self.termination }
+
+    /// Clear the recorded program termination cause before a new execution.
+    pub fn reset_termination(&mut self) {
+        self.termination = None;
+        self.next_termination = None;
+    }
+
     /// Reset `CK` register.
     pub fn reset_ck(&mut self) { self.ck = Status::Ok }
 
     /// Return the size of the call stack.
     pub fn cp(&self) -> u16 { self.cs.len() as u16 }
 
+    /// Return the call stack, starting from the outermost frame.
+    ///
+    /// Each item is the site of the call instruction which has created the frame, such that the
+    /// call stack can be used to render a backtrace.
+    pub fn call_stack(&self) -> &[Site<Id>] { self.cs.as_slice() }
+
+    /// Return the maximal size of the call stack.
+    pub const fn call_stack_capacity(&self) -> usize { CALL_STACK_SIZE }
+
     /// Push a location to a call stack.
     ///
+    /// If the call stack is full, records the location as the call stack overflow (see
+    /// [`Self::cs_overflow`]).
+    ///
     /// # Returns
     ///
-    /// Top of the call stack.
+    /// Top of the call stack, or `None` if the call stack is full.
     pub fn push_cs(&mut self, from: Site<Id>) -> Option<u16> {
-        self.cs.push(from).ok()?;
+        let budget = self.cs_budget_next.take();
+        if self.cs.push(from).is_err() {
+            self.cs_overflow = Some(from);
+            return None;
+        }
+        if let Some(shadow) = &mut self.cs_shadow {
+            let acc = shadow
+                .last()
+                .map(|(_, chain)| *chain)
+                .unwrap_or(CS_CHAIN_SEED);
+            shadow.push((from, CsChain::mix(acc, &from)));
+        }
+        if let Some(limit) = budget {
+            let deadline = self.ca.saturating_add(limit);
+            let outer = self.cs_budgets.last().map_or(u64::MAX, |(_, outer)| *outer);
+            self.cs_budgets.push((self.cp(), deadline.min(outer)));
+        }
+        self.cs_high_water = self.cs_high_water.max(self.cp());
         Some(self.cp())
     }
 
+    /// Push a location to a call stack when calling a code located in the program `to`.
+    ///
+    /// If the call is made into another program, the call is accounted as a cross-library frame
+    /// (see [`Self::xcp`]).
+    ///
+    /// # Returns
+    ///
+    /// Top of the call stack.
+    pub fn push_xcs(&mut self, from: Site<Id>, to: Id) -> Option<u16> {
+        let cp = self.push_cs(from)?;
+        if to != from.prog_id {
+            self.xcp += 1;
+            self.xcs_high_water = self.xcs_high_water.max(self.xcp);
+        }
+        Some(cp)
+    }
+
+    /// Return the register save stack, starting from the outermost frame.
+    ///
+    /// Each item contains the call stack depth of the frame which has saved the registers and the
+    /// mask of the saved registers (see [`Self::push_ss`]).
+    pub fn save_stack(&self) -> &[(u16, u16)] { self.ss.as_slice() }
+
+    /// Saves the values of the ISA extension registers selected by `mask` until the current call
+    /// stack frame returns.
+    ///
+    /// Bit `n` of the mask selects the `n`-th register in the order of [`Register::enumerate`];
+    /// the bits not matching any register are ignored. The values are restored by
+    /// [`Self::pop_cs`] once the frame is popped from the call stack, such that the registers
+    /// saved outside any subroutine (with an empty call stack) are never restored.
+    ///
+    /// # Returns
+    ///
+    /// Depth of the register save stack, or `None` if the save stack is full.
+    pub fn push_ss(&mut self, mask: u16) -> Option<u16> {
+        self.ss.push((self.cp(), mask)).ok()?;
+        self.save_ss();
+        Some(self.ss.len() as u16)
+    }
+
+    /// Saves the registers for the register save stack frames which have no saved values yet.
+    pub(super) fn save_ss(&mut self) {
+        let Some(saved) = &mut self.ss_regs else {
+            return;
+        };
+        for (_, mask) in self.ss.iter().skip(saved.len()) {
+            let regs = Cx::Reg::enumerate()
+                .take(u16::BITS as usize)
+                .enumerate()
+                .filter(|(no, _)| mask & (1 << no) != 0)
+                .map(|(_, reg)| (reg, self.cx.get(reg)));
+            saved.push(regs.collect());
+        }
+    }
+
+    /// Restores the registers saved by the register save stack frames which were popped.
+    pub(super) fn restore_ss(&mut self) {
+        let Some(saved) = &mut self.ss_regs else {
+            return;
+        };
+        while saved.len() > self.ss.len() {
+            for (reg, val) in saved.pop().into_iter().flatten() {
+                self.cx.put(reg, val);
+            }
+        }
+    }
+
+    /// Pops a call stack item when returning from the code located in the program `at`.
+    ///
+    /// If the return is made into another program, the cross-library frame counter is decreased
+    /// (see [`Self::xcp`]).
+    pub fn pop_xcs(&mut self, at: Id) -> Option<Site<Id>> {
+        let site = self.pop_cs()?;
+        if site.prog_id != at {
+            self.xcp = self.xcp.saturating_sub(1);
+        }
+        Some(site)
+    }
+
     /// Pops a call stack item.
-    pub fn pop_cs(&mut self) -> Option<Site<Id>> { self.cs.pop() }
+    ///
+    /// Restores the registers saved by the popped frame (see [`Self::push_ss`]).
+    ///
+    /// If the call stack integrity mode is on, verifies the popped item against the shadow stack;
+    /// on a mismatch, records the violation (see [`Self::cs_fault`]), sets `CK` to a failure and
+    /// returns `None`.
+    pub fn pop_cs(&mut self) -> Option<Site<Id>> {
+        let found = self.cs.pop()?;
+        while matches!(self.cs_budgets.last(), Some((depth, _)) if *depth > self.cp()) {
+            self.cs_budgets.pop();
+        }
+        while matches!(self.ss.last(), Some((depth, _)) if *depth > self.cp()) {
+            self.ss.pop();
+        }
+        self.restore_ss();
+        if let Some(shadow) = &mut self.cs_shadow {
+            let fault = match shadow.pop() {
+                None => Some(CallStackFault::NoShadowFrame { found }),
+                Some((expected, _)) if expected != found => {
+                    Some(CallStackFault::FrameMismatch { expected, found })
+                }
+                Some((_, chain)) => {
+                    let acc = shadow
+                        .last()
+                        .map(|(_, chain)| *chain)
+                        .unwrap_or(CS_CHAIN_SEED);
+                    (CsChain::mix(acc, &found) != chain)
+                        .then_some(CallStackFault::ChainMismatch { frame: found })
+                }
+            };
+            if let Some(fault) = fault {
+                self.cs_fault = Some(fault);
+                let _ = self.fail_ck_with(FailureReason::CallStackFault);
+                return None;
+            }
+        }
+        Some(found)
+    }
+
+    /// Sets the complexity budget for the call stack frame which is pushed next (by a call
+    /// instruction like `fn` or `call`).
+    ///
+    /// Once the complexity accumulated since the call (including the complexity of the call
+    /// instruction) reaches `limit`, the frame fails before executing its next instruction: `CK` is
+    /// set to a failure, and the execution returns to the caller (see [`Self::unwind_cs`]). The
+    /// budget of a nested frame never exceeds the remaining budget of the outer frames. The global
+    /// complexity limit `CL` takes precedence over the frame budgets, halting the whole program.
+    ///
+    /// Instructions which do not push a call stack frame, like `exec`, keep the budget for the
+    /// next call.
+    pub fn push_budget(&mut self, limit: u64) { self.cs_budget_next = Some(limit); }
+
+    /// Return the remaining complexity budget of the current call stack frame, if it or some of
+    /// the outer frames have a budget.
+    pub fn frame_budget(&self) -> Option<u64> {
+        self.cs_budgets
+            .last()
+            .map(|(_, deadline)| deadline.saturating_sub(self.ca))
+    }
+
+    /// Return the call stack depth of the outermost frame which has exhausted its complexity
+    /// budget, if any.
+    pub fn exhausted_frame(&self) -> Option<u16> {
+        self.cs_budgets
+            .iter()
+            .find(|(_, deadline)| self.ca >= *deadline)
+            .map(|(depth, _)| *depth)
+    }
+
+    /// Fails the call stack frame at the given `depth`, unwinding it together with all the nested
+    /// frames, when executing a code located in the program `at`.
+    ///
+    /// Sets `CK` to a failure irrespectively of the `CH` register value, so the caller may
+    /// continue the execution and handle the failure.
+    ///
+    /// # Returns
+    ///
+    /// The site of the call which has created the failed frame, or `None` if the call stack has no
+    /// frame at `depth`, or if popping a frame has failed the call stack integrity check (see
+    /// [`Self::pop_cs`]).
+    pub fn unwind_cs(&mut self, depth: u16, at: Id) -> Option<Site<Id>> {
+        if depth == 0 || depth > self.cp() {
+            return None;
+        }
+        let _ = self.fail_ck();
+        let mut at = at;
+        loop {
+            let site = self.pop_xcs(at)?;
+            if self.cp() < depth {
+                return Some(site);
+            }
+            at = site.prog_id;
+        }
+    }
+
+    /// Return maximal call stack depth reached during the execution.
+    pub fn cs_high_water(&self) -> u16 { self.cs_high_water }
+
+    /// Replaces the maximal call stack depth, returning the previous value.
+    pub(crate) fn replace_cs_high_water(&mut self, high_water: u16) -> u16 {
+        mem::replace(&mut self.cs_high_water, high_water)
+    }
+
+    /// Return current number of cross-library frames in the call stack.
+    pub fn xcp(&self) -> u16 { self.xcp }
+
+    /// Return maximal number of cross-library frames in the call stack reached during the
+    /// execution.
+    pub fn xcs_high_water(&self) -> u16 { self.xcs_high_water }
+
+    /// Return whether the call stack integrity mode is on.
+    pub fn cs_integrity(&self) -> bool { self.cs_shadow.is_some() }
+
+    /// Return call stack integrity violation, if any was detected.
+    pub fn cs_fault(&self) -> Option<CallStackFault<Id>> { self.cs_fault }
+
+    /// Return the site of the call instruction which has failed due to the call stack overflow, if
+    /// any.
+    ///
+    /// Allows distinguishing the call stack overflow from other causes of the `CK` failure.
+    pub fn cs_overflow(&self) -> Option<Site<Id>> { self.cs_overflow }
+
+    /// Return control transfer to an invalid offset, if any was detected.
+    pub fn jump_fault(&self) -> Option<JumpFault<Id>> { self.jump_fault }
+
+    /// Records a control transfer to an offset which is not an instruction boundary, sets `CK` to a
+    /// failure and terminates the program execution (see [`TerminationCause::Aborted`]).
+    pub fn fail_jump(&mut self, fault: JumpFault<Id>) {
+        self.jump_fault = Some(fault);
+        let _ = self.fail_ck_with(FailureReason::InvalidJump);
+        self.terminate_with(TerminationCause::Aborted);
+    }
+
+    /// Verify the whole call stack against the shadow stack, recomputing the integrity hash chain.
+    ///
+    /// Always succeeds if the call stack integrity mode is off.
+    pub fn verify_cs(&self) -> Result<(), CallStackFault<Id>> {
+        let Some(shadow) = &self.cs_shadow else {
+            return Ok(());
+        };
+        let mut acc = CS_CHAIN_SEED;
+        for (no, found) in self.cs.iter().copied().enumerate() {
+            let Some((expected, chain)) = shadow.get(no).copied() else {
+                return Err(CallStackFault::NoShadowFrame { found });
+            };
+            if expected != found {
+                return Err(CallStackFault::FrameMismatch { expected, found });
+            }
+            acc = CsChain::mix(acc, &found);
+            if acc != chain {
+                return Err(CallStackFault::ChainMismatch { frame: found });
+            }
+        }
+        if let Some((frame, _)) = shadow.get(self.cs.len()).copied() {
+            return Err(CallStackFault::ChainMismatch { frame });
+        }
+        Ok(())
+    }
+
+    /// Check the invariants relating the values of the control registers.
+    ///
+    /// The invariants hold between any two instructions executed by a correct engine with correct
+    /// ISA extensions; a violation indicates a bug in one of them. If the `paranoid` feature is on
+    /// or the debug assertions are enabled, the invariants are checked after each executed
+    /// instruction, and a violation introduced by the instruction causes a panic. ISA extensions
+    /// may call the method from their own tests to check that the instructions keep the core
+    /// consistent.
+    ///
+    /// The host may still put the core into a state violating the invariants through the public
+    /// API, for instance by resetting `CK` with [`Self::reset_ck`] after the complexity limit is
+    /// reached. Such a state is not asserted; it is corrected by the engine during the further
+    /// execution.
+    ///
+    /// The checked invariants are:
+    /// - `CF` is non-zero if `CK` is in a failed state;
+    /// - `CK` is in a failed state if `CA` has reached the complexity limit `CL`;
+    /// - the complexity warning site is recorded only after `CA` has reached the warning limit
+    ///   `CW`;
+    /// - the call stack high-water mark doesn't exceed the call stack capacity and is not below the
+    ///   current call stack depth `CP`;
+    /// - the number of cross-library frames doesn't exceed `CP` and its high-water mark;
+    /// - the shadow call stack, if present, has the same depth as the call stack.
+    ///
+    /// The checks relating the call stack to the cross-library frames and the shadow stack are
+    /// skipped once a call stack integrity violation is detected (see [`Self::cs_fault`]).
+    ///
+    /// # Errors
+    ///
+    /// The first violated invariant.
+    pub fn check_invariants(&self) -> Result<(), InvariantViolation> {
+        if self.ck == Status::Fail && self.cf == 0 {
+            return Err(InvariantViolation::UncountedFailure);
+        }
+        if let Some(cl) = self.cl {
+            if self.ca >= cl && self.ck == Status::Ok {
+                return Err(InvariantViolation::ComplexityOverrun { ca: self.ca, cl });
+            }
+        }
+        if self.cw_site.is_some() && self.cw.map_or(true, |cw| self.ca < cw) {
+            return Err(InvariantViolation::PrematureWarning { ca: self.ca, cw: self.cw });
+        }
+        if self.cs_high_water as usize > CALL_STACK_SIZE {
+            return Err(InvariantViolation::CallStackOverflow {
+                high_water: self.cs_high_water,
+                capacity: CALL_STACK_SIZE,
+            });
+        }
+        let depth = self.cp();
+        if depth > self.cs_high_water {
+            return Err(InvariantViolation::CallStackAboveHighWater {
+                depth,
+                high_water: self.cs_high_water,
+            });
+        }
+        if self.xcp > self.xcs_high_water {
+            return Err(InvariantViolation::CrossFramesAboveHighWater {
+                xcp: self.xcp,
+                high_water: self.xcs_high_water,
+            });
+        }
+        if self.cs_fault.is_some() {
+            return Ok(());
+        }
+        if self.xcp > depth {
+            return Err(InvariantViolation::CrossFramesOverflow { xcp: self.xcp, depth });
+        }
+        match &self.cs_shadow {
+            Some(shadow) if shadow.len() != depth as usize => {
+                Err(InvariantViolation::ShadowStackMismatch { depth, shadow: shadow.len() })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Panics if some of the invariants checked by [`Self::check_invariants`] are violated, while
+    /// they have `held` before the last state transition performed by the engine.
+    ///
+    /// # Returns
+    ///
+    /// Whether the invariants hold, to be passed to the check of the next state transition.
+    #[cfg(any(feature = "paranoid", debug_assertions))]
+    pub(crate) fn assert_invariants(&self, held: bool) -> bool {
+        match self.check_invariants() {
+            Err(violation) if held => panic!("core invariant violated: {violation}\n{self:?}"),
+            res => res.is_ok(),
+        }
+    }
+
+    /// Return the behavior of the reserved instructions.
+    pub fn unknown_instr(&self) -> UnknownInstrPolicy { self.unknown_instr }
+
+    /// Returns the model mapping the instruction complexity classes to the complexity values
+    /// accounted by the `CA` register.
+    ///
+    /// Defaults to [`ComplexityModel::DEFAULT`].
+    pub fn complexity_model(&self) -> &ComplexityModel { &self.complexity_model }
+
+    /// Sets the model mapping the instruction complexity classes to the complexity values
+    /// accounted by the `CA` register.
+    ///
+    /// The model is kept by [`Self::reset`]; it applies to the instructions executed afterwards.
+    pub fn set_complexity_model(&mut self, model: ComplexityModel) { self.complexity_model = model }
+
+    /// Return number of jumps performed.
+    pub fn cy(&self) -> u16 { self.cy }
+
+    /// Return jump limit value.
+    pub fn cy_lim(&self) -> Option<u16> { self.cy_lim }
+
+    /// Count a jump, call or return.
+    ///
+    /// The counter saturates at the jump limit (see [`Self::cy_lim`]) or at the maximal value of
+    /// the `CY` register.
+    ///
+    /// # Returns
+    ///
+    /// Boolean indicating whether the jump is within the limit, i.e. `false` if the counter has
+    /// already reached the limit before the jump.
+    pub fn acc_jump(&mut self) -> bool {
+        let lim = self.cy_lim.unwrap_or(u16::MAX);
+        if self.cy >= lim {
+            return false;
+        }
+        self.cy += 1;
+        true
+    }
+
+    /// Return number of executed instructions.
+    pub fn ci(&self) -> u64 { self.ci }
+
+    /// Return instruction limit value.
+    pub fn ci_lim(&self) -> Option<u64> { self.ci_lim }
+
+    /// Count an instruction which is about to be executed.
+    ///
+    /// The counter saturates at the instruction limit (see [`Self::ci_lim`]) or at the maximal
+    /// value of the `CI` register.
+    ///
+    /// # Returns
+    ///
+    /// Boolean indicating whether the instruction is within the limit, i.e. `false` if the counter
+    /// has already reached the limit before the instruction.
+    pub fn acc_instr(&mut self) -> bool {
+        let lim = self.ci_lim.unwrap_or(u64::MAX);
+        if self.ci >= lim {
+            return false;
+        }
+        self.ci += 1;
+        true
+    }
+
+    /// Return accumulated complexity value.
+    pub fn ca(&self) -> u64 { self.ca }
 
     /// Return complexity limit value.
     pub fn cl(&self) -> Option<u64> { self.cl }
 
+    /// Return complexity warning limit value.
+    pub fn cw(&self) -> Option<u64> { self.cw }
+
+    /// Return the site of the instruction which has crossed the complexity warning limit, if any.
+    pub fn cw_site(&self) -> Option<Site<Id>> { self.cw_site }
+
     /// Accumulate complexity value.
     ///
+    /// If the accumulated value overflows, `CA` saturates at `u64::MAX` and the complexity limit
+    /// is considered reached, even if `CL` is not set.
+    ///
     /// # Returns
     ///
     /// Boolean indicating whether the complexity limit is reached.
     pub fn acc_complexity(&mut self, complexity: u64) -> bool {
-        self.ca = self.ca.saturating_add(complexity);
+        let Some(ca) = self.ca.checked_add(complexity) else {
+            self.ca = u64::MAX;
+            return false;
+        };
+        self.ca = ca;
         self.cl().map(|lim| self.ca < lim).unwrap_or(true)
     }
 
+    /// Accumulate complexity value of an instruction located at a given site.
+    ///
+    /// The warning limit `CW` is evaluated before the complexity limit `CL`, such that if both
+    /// limits are crossed by the same instruction, the warning is recorded first. The warning is
+    /// recorded only once. Overflow of the accumulated value is handled as in
+    /// [`Self::acc_complexity`].
+    ///
+    /// # Returns
+    ///
+    /// Boolean indicating whether the complexity limit is reached.
+    pub fn acc_complexity_at(&mut self, site: Site<Id>, complexity: u64) -> bool {
+        let overflow = self.ca.checked_add(complexity).is_none();
+        self.ca = self.ca.saturating_add(complexity);
+        if self.cw_site.is_none() && matches!(self.cw, Some(lim) if self.ca >= lim) {
+            self.cw_site = Some(site);
+        }
+        !overflow && self.cl().map(|lim| self.ca < lim).unwrap_or(true)
+    }
+
+    /// Starts collecting the deterministic execution profile, tracking at most `cap` distinct
+    /// sites individually. Discards the previously collected profile, if any.
+    pub fn start_profiling(&mut self, cap: usize) { self.profile = Some(Profile::with_cap(cap)); }
+
+    /// Returns the execution profile collected so far, if the profiling is on.
+    pub fn profile(&self) -> Option<&Profile<Id>> { self.profile.as_ref() }
+
+    /// Stops the profiling, returning the collected execution profile.
+    pub fn take_profile(&mut self) -> Option<Profile<Id>> { self.profile.take() }
+
+    /// Records execution of an instruction in the execution profile, if the profiling is on.
+    pub fn record_profile(&mut self, site: Site<Id>, opcode: u8, complexity: u64, jumped: bool) {
+        if let Some(profile) = &mut self.profile {
+            profile.record(site, opcode, complexity, jumped);
+        }
+    }
+
     /// Get register value.
     pub fn get(&self, reg: Cx::Reg) -> Option<<Cx::Reg as Register>::Value> { self.cx.get(reg) }
+
+    /// Set register value.
+    pub fn set(&mut self, reg: Cx::Reg, val: <Cx::Reg as Register>::Value) { self.cx.set(reg, val) }
+
+    /// Put either a value or `None` to the register.
+    pub fn put(&mut self, reg: Cx::Reg, val: Option<<Cx::Reg as Register>::Value>) {
+        self.cx.put(reg, val)
+    }
+
+    /// Clear the register by setting it to `None`.
+    pub fn clr(&mut self, reg: Cx::Reg) { self.cx.clr(reg) }
+
+    /// Seed the core extension registers from a stream of arbitrary bytes.
+    #[cfg(any(test, feature = "fuzzing"))]
+    pub(crate) fn set_arbitrary(&mut self, bytes: &mut impl Iterator<Item = u8>) {
+        self.cx.set_arbitrary(bytes)
+    }
 }
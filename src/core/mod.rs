@@ -26,8 +26,18 @@
 
 #[allow(clippy::module_inception)]
 mod core;
+mod dump;
 mod microcode;
+mod profile;
+mod snapshot;
 mod util;
 
-pub use self::core::{Core, CoreConfig, CoreExt, Supercore, CALL_STACK_SIZE_MAX};
-pub use self::util::{NoExt, NoRegs, Register, Site, SiteId, Status};
+pub use self::core::{
+    CallStackFault, Core, CoreConfig, CoreExt, FailureReason, InvariantViolation, JumpFault,
+    Supercore, TerminationCause, UnknownInstrPolicy, CALL_STACK_SIZE_MAX, CALL_STACK_SIZE_SMALL,
+};
+pub use self::dump::{CoreDump, RegDump};
+pub use self::profile::{Profile, ProfileData, SiteStats};
+pub use self::snapshot::{CoreSnapshot, SnapshotError};
+pub(crate) use self::util::parse_site_offset;
+pub use self::util::{NoExt, NoRegs, Register, Site, SiteId, SiteParseError, Status};
@@ -0,0 +1,233 @@
+// Reference rust implementation of AluVM (arithmetic logic unit virtual machine).
+// To find more on AluVM please check <https://aluvm.org>
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Designed in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+// Written in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+//
+// Copyright (C) 2021-2024 LNP/BP Standards Association, Switzerland.
+// Copyright (C) 2024-2025 Laboratories for Ubiquitous Deterministic Computing (UBIDECO),
+//                         Institute for Distributed and Cognitive Systems (InDCS), Switzerland.
+// Copyright (C) 2021-2025 Dr Maxim Orlovsky.
+// All rights under the above copyrights are reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use this file except
+// in compliance with the License. You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed under the License
+// is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express
+// or implied. See the License for the specific language governing permissions and limitations under
+// the License.
+
+//! Deterministic profiling of program execution.
+
+use alloc::collections::BTreeMap;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+use core::fmt::{self, Display, Formatter};
+use core::ops::AddAssign;
+
+use super::{Site, SiteId};
+use crate::fmt::{Abbrev, Dec};
+
+/// Execution statistics collected for a code site or a group of sites.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct SiteStats {
+    /// Number of times the instruction was executed.
+    pub count: u64,
+    /// Cumulative complexity charged for the instruction execution.
+    pub complexity: u64,
+    /// Number of times the instruction performed a local jump.
+    pub jumps: u64,
+}
+
+impl AddAssign for SiteStats {
+    fn add_assign(&mut self, rhs: Self) {
+        self.count = self.count.saturating_add(rhs.count);
+        self.complexity = self.complexity.saturating_add(rhs.complexity);
+        self.jumps = self.jumps.saturating_add(rhs.jumps);
+    }
+}
+
+/// Aggregation and rendering API shared by the execution profiles.
+pub trait ProfileData<Id: SiteId> {
+    /// Returns statistics aggregated by instruction opcode, including the sites which were not
+    /// tracked individually.
+    fn by_opcode(&self) -> BTreeMap<u8, SiteStats>;
+
+    /// Returns up to `n` individually tracked sites with the largest cumulative complexity, in the
+    /// descending order.
+    fn top_n(&self, n: usize) -> Vec<(Site<Id>, SiteStats)>;
+
+    /// Returns whether some of the sites were not tracked individually since the limit on the
+    /// number of tracked sites was reached.
+    fn is_capped(&self) -> bool;
+
+    /// Renders a text table with the `n` sites having the largest cumulative complexity.
+    fn render_table(&self, n: usize, f: &mut impl fmt::Write) -> fmt::Result {
+        writeln!(f, "{:<64} {:>12} {:>16} {:>12}", "site", "count", "complexity", "jumps")?;
+        for (site, stats) in self.top_n(n) {
+            writeln!(
+                f,
+                "{:<64} {:>12} {:>16} {:>12}",
+                site.to_string(),
+                Dec(stats.count),
+                Abbrev(stats.complexity),
+                Dec(stats.jumps)
+            )?;
+        }
+        if self.is_capped() {
+            writeln!(f, "; site limit reached, some sites are accounted only by opcode")?;
+        }
+        Ok(())
+    }
+}
+
+/// Deterministic execution profile, collected using the instruction count and complexity instead
+/// of the time measurements.
+///
+/// The memory used by the profile is bounded: statistics are tracked individually for at most
+/// [`Profile::cap`] distinct sites, and the sites executed after the limit is reached are
+/// accounted only by their opcode.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Profile<Id: SiteId> {
+    cap: usize,
+    sites: BTreeMap<Site<Id>, (u8, SiteStats)>,
+    overflow: BTreeMap<u8, SiteStats>,
+}
+
+impl<Id: SiteId> Profile<Id> {
+    /// Constructs an empty profile tracking at most `cap` distinct sites.
+    pub fn with_cap(cap: usize) -> Self {
+        Self { cap, sites: BTreeMap::new(), overflow: BTreeMap::new() }
+    }
+
+    /// Returns the limit on the number of individually tracked sites.
+    pub fn cap(&self) -> usize { self.cap }
+
+    /// Returns the number of individually tracked sites.
+    pub fn len(&self) -> usize { self.sites.len() }
+
+    /// Returns whether the profile doesn't contain any data.
+    pub fn is_empty(&self) -> bool { self.sites.is_empty() && self.overflow.is_empty() }
+
+    /// Returns statistics for an individually tracked site.
+    pub fn site(&self, site: Site<Id>) -> Option<SiteStats> {
+        self.sites.get(&site).map(|(_, stats)| *stats)
+    }
+
+    /// Returns statistics for the sites which were not tracked individually.
+    pub fn overflow(&self) -> SiteStats {
+        let mut total = SiteStats::default();
+        for stats in self.overflow.values() {
+            total += *stats;
+        }
+        total
+    }
+
+    /// Records execution of an instruction.
+    pub fn record(&mut self, site: Site<Id>, opcode: u8, complexity: u64, jumped: bool) {
+        let stats = SiteStats { count: 1, complexity, jumps: jumped as u64 };
+        if let Some((_, site_stats)) = self.sites.get_mut(&site) {
+            *site_stats += stats;
+        } else if self.sites.len() < self.cap {
+            self.sites.insert(site, (opcode, stats));
+        } else {
+            *self.overflow.entry(opcode).or_default() += stats;
+        }
+    }
+}
+
+impl<Id: SiteId> ProfileData<Id> for Profile<Id> {
+    fn by_opcode(&self) -> BTreeMap<u8, SiteStats> {
+        let mut map = self.overflow.clone();
+        for (opcode, stats) in self.sites.values() {
+            *map.entry(*opcode).or_default() += *stats;
+        }
+        map
+    }
+
+    fn top_n(&self, n: usize) -> Vec<(Site<Id>, SiteStats)> {
+        let mut sites = self
+            .sites
+            .iter()
+            .map(|(site, (_, stats))| (*site, *stats))
+            .collect::<Vec<_>>();
+        // Sorting is stable, thus sites with the same complexity remain ordered by site
+        sites.sort_by_key(|(_, stats)| core::cmp::Reverse(stats.complexity));
+        sites.truncate(n);
+        sites
+    }
+
+    fn is_capped(&self) -> bool { !self.overflow.is_empty() }
+}
+
+impl<Id: SiteId> Display for Profile<Id> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result { self.render_table(self.sites.len(), f) }
+}
+
+#[cfg(test)]
+mod test {
+    #![cfg_attr(coverage_nightly, coverage(off))]
+
+    use alloc::string::String;
+
+    use super::*;
+    use crate::LibId;
+
+    fn site(offset: u16) -> Site<LibId> { Site::new(LibId::default(), offset) }
+
+    #[test]
+    fn record() {
+        let mut profile = Profile::with_cap(2);
+        assert!(profile.is_empty());
+        profile.record(site(0), 1, 10, false);
+        profile.record(site(3), 2, 30, true);
+        profile.record(site(0), 1, 10, false);
+        assert!(!profile.is_capped());
+        assert_eq!(profile.site(site(0)), Some(SiteStats { count: 2, complexity: 20, jumps: 0 }));
+        assert_eq!(profile.site(site(3)), Some(SiteStats { count: 1, complexity: 30, jumps: 1 }));
+        assert_eq!(profile.top_n(1), [(site(3), SiteStats { count: 1, complexity: 30, jumps: 1 })]);
+        assert_eq!(profile.top_n(5).len(), 2);
+    }
+
+    #[test]
+    fn overflow() {
+        let mut profile = Profile::with_cap(1);
+        profile.record(site(0), 1, 10, false);
+        profile.record(site(1), 1, 10, false);
+        profile.record(site(2), 2, 5, true);
+        profile.record(site(0), 1, 10, false);
+        assert!(profile.is_capped());
+        assert_eq!(profile.len(), 1);
+        assert_eq!(profile.site(site(1)), None);
+        assert_eq!(profile.overflow(), SiteStats { count: 2, complexity: 15, jumps: 1 });
+        assert_eq!(profile.by_opcode(), bmap! {
+            1 => SiteStats { count: 3, complexity: 30, jumps: 0 },
+            2 => SiteStats { count: 1, complexity: 5, jumps: 1 }
+        });
+
+        profile.record(site(0), 1, 1_234_567, false);
+        let mut table = String::new();
+        profile.render_table(1, &mut table).unwrap();
+        assert_eq!(table, profile.to_string());
+        assert_eq!(
+            table,
+            format!(
+                "{:<64} {:>12} {:>16} {:>12}\n{:<64} {:>12} {:>16} {:>12}\n; site limit reached, \
+                 some sites are accounted only by opcode\n",
+                "site",
+                "count",
+                "complexity",
+                "jumps",
+                site(0).to_string(),
+                "3",
+                "1.2M",
+                "0"
+            )
+        );
+    }
+}
@@ -0,0 +1,316 @@
+// Reference rust implementation of AluVM (arithmetic logic unit virtual machine).
+// To find more on AluVM please check <https://aluvm.org>
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Designed in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+// Written in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+//
+// Copyright (C) 2021-2024 LNP/BP Standards Association, Switzerland.
+// Copyright (C) 2024-2025 Laboratories for Ubiquitous Deterministic Computing (UBIDECO),
+//                         Institute for Distributed and Cognitive Systems (InDCS), Switzerland.
+// Copyright (C) 2021-2025 Dr Maxim Orlovsky.
+// All rights under the above copyrights are reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use this file except
+// in compliance with the License. You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed under the License
+// is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express
+// or implied. See the License for the specific language governing permissions and limitations under
+// the License.
+
+use amplify::confinement::{ConfinedVec, TinyVec};
+
+use super::core::{CsChain, CS_CHAIN_SEED};
+use super::{Core, CoreExt, InvariantViolation, Profile, Site, SiteId, Status, UnknownInstrPolicy};
+
+/// Complete state of a core, allowing to suspend a program execution and to resume it later,
+/// possibly on another machine.
+///
+/// Produced by [`Core::snapshot`] and restored with [`Core::restore`]. Unlike [`super::CoreDump`],
+/// the snapshot keeps the register values as they are, including the registers of the ISA
+/// extension in the form of [`CoreExt::Snapshot`].
+///
+/// The snapshot doesn't include the execution profile and the diagnostic reports of the core
+/// (see [`Core::cs_fault`], [`Core::jump_fault`], [`Core::cs_overflow`] and
+/// [`Core::last_failure`]), since they don't affect the program execution. The shadow call stack is
+/// not stored either: it is recomputed from the call stack if the call stack integrity mode is on.
+/// The complexity model (see [`Core::complexity_model`]) is a metering setting of the host, and is
+/// kept by the core restoring the snapshot.
+/// The register save stack is not stored, thus the registers saved by the call stack frames (see
+/// [`Core::push_ss`]) are not restored when the frames of a restored core return.
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(rename_all = "camelCase"))]
+pub struct CoreSnapshot<Id: SiteId, S> {
+    /// Value of the `CH` register.
+    pub ch: bool,
+    /// Value of the `CK` register.
+    pub ck: Status,
+    /// Value of the `CF` register.
+    pub cf: u64,
+    /// Value of the `CO` register.
+    pub co: Status,
+    /// Value of the `CY` register.
+    pub cy: u16,
+    /// Jump limit.
+    pub cy_lim: Option<u16>,
+    /// Value of the `CI` register.
+    pub ci: u64,
+    /// Instruction limit.
+    pub ci_lim: Option<u64>,
+    /// Value of the `CA` register.
+    pub ca: u64,
+    /// Value of the `CL` register.
+    pub cl: Option<u64>,
+    /// Value of the `CW` register.
+    pub cw: Option<u64>,
+    /// Site of the instruction at which the complexity warning limit was crossed.
+    pub cw_site: Option<Site<Id>>,
+    /// Call stack (the `CS` register), with the most recent call being the last.
+    pub cs: TinyVec<Site<Id>>,
+    /// Whether the call stack integrity mode is on.
+    pub cs_integrity: bool,
+    /// Complexity budgets of the call stack frames, as pairs of the call stack depth and the value
+    /// of `CA` at which the budget is exhausted.
+    pub cs_budgets: TinyVec<(u16, u64)>,
+    /// Complexity budget for the next call stack frame.
+    pub cs_budget_next: Option<u64>,
+    /// Maximal depth of the call stack reached during the execution.
+    pub cs_high_water: u16,
+    /// Number of cross-library calls in the call stack.
+    pub xcp: u16,
+    /// Maximal number of cross-library calls in the call stack reached during the execution.
+    pub xcs_high_water: u16,
+    /// Behavior of the reserved instructions.
+    pub unknown_instr: UnknownInstrPolicy,
+    /// State of the ISA extension registers.
+    pub cx: S,
+}
+
+/// Errors restoring a core from a [`CoreSnapshot`].
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum SnapshotError {
+    /// call stack depth {depth} exceeds the call stack capacity {capacity}.
+    CallStackOverflow {
+        /// Depth of the call stack in the snapshot.
+        depth: usize,
+        /// Call stack capacity of the core.
+        capacity: usize,
+    },
+
+    /// complexity budget for the call stack depth {depth} doesn't match the call stack.
+    InvalidBudget {
+        /// Call stack depth of the frame the budget is given to.
+        depth: u16,
+    },
+
+    /// snapshot violates the core invariants: {0}
+    #[from]
+    Invariant(InvariantViolation),
+}
+
+impl<Id: SiteId, Cx: CoreExt, const CALL_STACK_SIZE: usize> Core<Id, Cx, CALL_STACK_SIZE> {
+    /// Takes a snapshot of the core state, which can be restored with [`Self::restore`].
+    pub fn snapshot(&self) -> CoreSnapshot<Id, Cx::Snapshot> {
+        CoreSnapshot {
+            ch: self.ch,
+            ck: self.ck,
+            cf: self.cf,
+            co: self.co,
+            cy: self.cy,
+            cy_lim: self.cy_lim,
+            ci: self.ci,
+            ci_lim: self.ci_lim,
+            ca: self.ca,
+            cl: self.cl,
+            cw: self.cw,
+            cw_site: self.cw_site,
+            cs: TinyVec::from_iter_checked(self.cs.iter().copied()),
+            cs_integrity: self.cs_shadow.is_some(),
+            cs_budgets: TinyVec::from_iter_checked(self.cs_budgets.iter().copied()),
+            cs_budget_next: self.cs_budget_next,
+            cs_high_water: self.cs_high_water,
+            xcp: self.xcp,
+            xcs_high_water: self.xcs_high_water,
+            unknown_instr: self.unknown_instr,
+            cx: self.cx.snapshot(),
+        }
+    }
+
+    /// Restores the core state from a snapshot taken with [`Self::snapshot`], possibly by a core
+    /// on another machine.
+    ///
+    /// Discards the diagnostic reports of the core; the execution profile, if it is on, is
+    /// restarted. The complexity model of the core is kept.
+    ///
+    /// # Errors
+    ///
+    /// If the snapshot call stack doesn't fit the call stack of the core, the complexity budgets
+    /// don't match the call stack, or the snapshot violates the core invariants (see
+    /// [`Self::check_invariants`]). In all cases the core is left intact.
+    pub fn restore(
+        &mut self,
+        snapshot: CoreSnapshot<Id, Cx::Snapshot>,
+    ) -> Result<(), SnapshotError> {
+        let depth = snapshot.cs.len();
+        let cs = ConfinedVec::try_from_iter(snapshot.cs.iter().copied()).map_err(|_| {
+            SnapshotError::CallStackOverflow { depth, capacity: Self::CALL_STACK_SIZE }
+        })?;
+        // Budgets are given to the existing frames and must be tightened towards the top frames
+        let mut prev: Option<(u16, u64)> = None;
+        for (depth, deadline) in snapshot.cs_budgets.iter().copied() {
+            if depth == 0
+                || depth as usize > cs.len()
+                || prev.is_some_and(|(outer, limit)| depth <= outer || deadline > limit)
+            {
+                return Err(SnapshotError::InvalidBudget { depth });
+            }
+            prev = Some((depth, deadline));
+        }
+        let cs_shadow = snapshot.cs_integrity.then(|| {
+            let mut acc = CS_CHAIN_SEED;
+            cs.iter()
+                .map(|frame| {
+                    acc = CsChain::mix(acc, frame);
+                    (*frame, acc)
+                })
+                .collect()
+        });
+
+        let mut cx = self.cx.clone();
+        cx.restore(snapshot.cx);
+        let core = Core {
+            ch: snapshot.ch,
+            ck: snapshot.ck,
+            cf: snapshot.cf,
+            co: snapshot.co,
+            cy: snapshot.cy,
+            cy_lim: snapshot.cy_lim,
+            ci: snapshot.ci,
+            ci_lim: snapshot.ci_lim,
+            ca: snapshot.ca,
+            cl: snapshot.cl,
+            cw: snapshot.cw,
+            cw_site: snapshot.cw_site,
+            cs,
+            cs_shadow,
+            cs_budgets: snapshot.cs_budgets.release(),
+            cs_budget_next: snapshot.cs_budget_next,
+            ss: ConfinedVec::new(),
+            ss_regs: self.ss_regs.as_ref().map(|_| Vec::new()),
+            cs_fault: None,
+            jump_fault: None,
+            cs_overflow: None,
+            last_failure: None,
+            next_failure: None,
+            termination: None,
+            next_termination: None,
+            profile: self.profile.as_ref().map(|p| Profile::with_cap(p.cap())),
+            cs_high_water: snapshot.cs_high_water,
+            xcp: snapshot.xcp,
+            xcs_high_water: snapshot.xcs_high_water,
+            unknown_instr: snapshot.unknown_instr,
+            complexity_model: self.complexity_model.clone(),
+            cx,
+        };
+        core.check_invariants()?;
+        *self = core;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![cfg_attr(coverage_nightly, coverage(off))]
+
+    use strict_encoding::{StrictDeserialize, StrictSerialize};
+
+    use super::*;
+    use crate::{CoreConfig, CoreDump, LibId, NoExt};
+
+    fn site(offset: u16) -> Site<LibId> { Site::new(LibId::from([0xA5u8; 32]), offset) }
+
+    fn lib(no: u8) -> LibId { LibId::from([no; 32]) }
+
+    fn core() -> Core<LibId, NoExt> {
+        let config = CoreConfig { complexity_lim: Some(1000), cs_integrity: true, ..default!() };
+        let mut core = Core::with(config, ());
+        core.push_budget(500);
+        core.push_xcs(site(1), lib(1)).unwrap();
+        core.acc_complexity_at(site(2), 100);
+        core.push_budget(200);
+        core.push_cs(site(3)).unwrap();
+        core.set_co(Status::Fail);
+        let _ = core.fail_ck();
+        core
+    }
+
+    #[test]
+    fn roundtrip() {
+        let core = core();
+        let snapshot = core.snapshot();
+        assert_eq!(snapshot.cs.as_slice(), &[site(1), site(3)]);
+        assert_eq!(snapshot.cs_budgets.as_slice(), &[(1, 500), (2, 300)]);
+        assert!(snapshot.cs_integrity);
+
+        let mut restored = Core::<LibId, NoExt>::new();
+        restored.restore(snapshot.clone()).unwrap();
+        assert_eq!(restored.snapshot(), snapshot);
+        // The failure reason is diagnostic-only and is not a part of the snapshot
+        assert_eq!(restored.last_failure(), None);
+        assert_eq!(restored.dump(), CoreDump { last_failure: None, ..core.dump() });
+        assert_eq!(restored.verify_cs(), Ok(()));
+        assert_eq!(restored.pop_cs(), Some(site(3)));
+        assert_eq!(restored.cs_fault(), None);
+    }
+
+    #[test]
+    fn strict_encoding() {
+        let snapshot = core().snapshot();
+        let data = snapshot.to_strict_serialized::<0xFFFF>().unwrap();
+        assert_eq!(CoreSnapshot::from_strict_serialized::<0xFFFF>(data).unwrap(), snapshot);
+    }
+
+    #[test]
+    fn call_stack_overflow() {
+        let snapshot = core().snapshot();
+        let mut small = Core::<LibId, NoExt, 1>::new();
+        let before = small.snapshot();
+        assert_eq!(
+            small.restore(snapshot),
+            Err(SnapshotError::CallStackOverflow { depth: 2, capacity: 1 })
+        );
+        assert_eq!(small.snapshot(), before);
+    }
+
+    #[test]
+    fn invalid_budget() {
+        let mut core = Core::<LibId, NoExt>::new();
+        for budgets in
+            [vec![(0, 10)], vec![(3, 10)], vec![(2, 10), (1, 10)], vec![(1, 10), (2, 20)]]
+        {
+            let mut snapshot = self::core().snapshot();
+            let depth = budgets.last().unwrap().0;
+            snapshot.cs_budgets = TinyVec::from_checked(budgets);
+            assert_eq!(core.restore(snapshot), Err(SnapshotError::InvalidBudget { depth }));
+        }
+    }
+
+    #[test]
+    fn invariant_violation() {
+        let mut snapshot = core().snapshot();
+        snapshot.ck = Status::Ok;
+        snapshot.ca = 1000;
+        let mut core = Core::<LibId, NoExt>::new();
+        assert_eq!(
+            core.restore(snapshot.clone()),
+            Err(InvariantViolation::ComplexityOverrun { ca: 1000, cl: 1000 }.into())
+        );
+        snapshot.ca = 999;
+        assert_eq!(core.restore(snapshot), Ok(()));
+    }
+}
@@ -22,20 +22,26 @@
 // or implied. See the License for the specific language governing permissions and limitations under
 // the License.
 
+use alloc::string::{String, ToString};
 use core::cmp::Ordering;
 use core::fmt::{self, Debug, Display, Formatter};
+use core::hash::Hash;
 use core::ops::Not;
 use core::str::FromStr;
 
 use crate::core::CoreExt;
+use crate::LIB_NAME_ALUVM;
 
 /// A trait for a set of registers provided by an ISA extension.
 pub trait Register: Copy + Ord + Debug + Display {
     /// The value type contained in the registers.
-    type Value: Copy + Debug + Display;
+    type Value: Copy + Eq + Debug + Display;
 
     /// The size of the value in the register, in bytes.
     fn bytes(self) -> u16;
+
+    /// Enumerates all registers of the set, in their order.
+    fn enumerate() -> impl Iterator<Item = Self>;
 }
 
 /// Default [`Register`] implementation for ISA extensions providing no new registers.
@@ -64,13 +70,18 @@ impl Display for NoRegs {
 impl Register for NoRegs {
     type Value = u8;
     fn bytes(self) -> u16 { unreachable!() }
+    fn enumerate() -> impl Iterator<Item = Self> { core::iter::empty() }
 }
 
 /// Status for flag registers.
 #[derive(Copy, Clone, Eq, PartialEq, Debug, Display)]
+#[derive(StrictType, StrictEncode, StrictDecode)]
+#[strict_type(lib = LIB_NAME_ALUVM, tags = repr, into_u8, try_from_u8)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(rename_all = "camelCase"))]
 #[repr(i8)]
 pub enum Status {
     /// Flag is not set, indicating absence of failures.
+    #[strict_type(dumb)]
     #[display("ok")]
     Ok = 0,
 
@@ -99,13 +110,14 @@ impl Not for Status {
 ///
 /// This type is required in addition to [`crate::LibId`] in order to achieve proper abstraction,
 /// layering, and separation of concerns: the core must know nothing about library structure.
-pub trait SiteId: Copy + Ord + Debug + Display + FromStr {}
+pub trait SiteId: Copy + Ord + Hash + Debug + Display + FromStr {}
 
 /// Location inside the instruction sequence which can be executed by the core.
 ///
 /// This type is required in addition to [`crate::LibSite`] in order to achieve proper abstraction,
 /// layering, and separation of concerns: the core must know nothing about library structure.
 #[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(rename_all = "camelCase"))]
 pub struct Site<Id: SiteId> {
     /// Identifier of the program.
     pub prog_id: Id,
@@ -119,12 +131,55 @@ impl<Id: SiteId> Site<Id> {
     pub fn new(prog_id: Id, offset: u16) -> Self { Self { prog_id, offset } }
 }
 
+/// Errors parsing the textual form of a code site.
+#[derive(Clone, Eq, PartialEq, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum SiteParseError<E: Debug + Display> {
+    /// code site `{0}` has no `@` separator between the program identifier and the offset.
+    NoSeparator(String),
+
+    /// invalid program identifier in the code site `{0}` - {1}
+    InvalidId(String, E),
+
+    /// invalid offset in the code site `{0}`; the offset must be a hexadecimal number of up to
+    /// four digits followed by `#h` suffix.
+    InvalidOffset(String),
+}
+
+/// The canonical textual form of a site is `<prog_id>@<offset>#h`, where the offset is written as
+/// four uppercase hexadecimal digits.
 impl<Id: SiteId> Display for Site<Id> {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        write!(f, "{}@{:04}", self.prog_id, self.offset)
+        write!(f, "{}@{:04X}#h", self.prog_id, self.offset)
+    }
+}
+
+/// Parses the canonical textual form of a site, accepting both upper- and lowercase hexadecimal
+/// digits in the offset.
+impl<Id: SiteId> FromStr for Site<Id>
+where Id::Err: Debug + Display
+{
+    type Err = SiteParseError<Id::Err>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (id, offset) = s
+            .rsplit_once('@')
+            .ok_or_else(|| SiteParseError::NoSeparator(s.to_string()))?;
+        let prog_id =
+            Id::from_str(id).map_err(|err| SiteParseError::InvalidId(s.to_string(), err))?;
+        let offset = parse_site_offset(offset)
+            .ok_or_else(|| SiteParseError::InvalidOffset(s.to_string()))?;
+        Ok(Site::new(prog_id, offset))
     }
 }
 
+/// Parses the offset part of the canonical textual form of a site, including the `#h` suffix.
+pub(crate) fn parse_site_offset(s: &str) -> Option<u16> {
+    s.strip_suffix("#h")
+        .filter(|hex| (1..=4).contains(&hex.len()) && hex.bytes().all(|b| b.is_ascii_hexdigit()))
+        .and_then(|hex| u16::from_str_radix(hex, 16).ok())
+}
+
 /// Helper data structure for base core which has no ISA extensions.
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
 pub struct NoExt;
@@ -132,6 +187,7 @@ pub struct NoExt;
 impl CoreExt for NoExt {
     type Reg = NoRegs;
     type Config = ();
+    type Snapshot = ();
 
     fn with(_config: Self::Config) -> Self { NoExt }
 
@@ -142,4 +198,8 @@ impl CoreExt for NoExt {
     fn put(&mut self, _reg: Self::Reg, _val: Option<u8>) { unreachable!() }
 
     fn reset(&mut self) {}
+
+    fn snapshot(&self) -> Self::Snapshot {}
+
+    fn restore(&mut self, _snapshot: Self::Snapshot) {}
 }
@@ -0,0 +1,235 @@
+// Reference rust implementation of AluVM (arithmetic logic unit virtual machine).
+// To find more on AluVM please check <https://aluvm.org>
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Designed in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+// Written in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+//
+// Copyright (C) 2021-2024 LNP/BP Standards Association, Switzerland.
+// Copyright (C) 2024-2025 Laboratories for Ubiquitous Deterministic Computing (UBIDECO),
+//                         Institute for Distributed and Cognitive Systems (InDCS), Switzerland.
+// Copyright (C) 2021-2025 Dr Maxim Orlovsky.
+// All rights under the above copyrights are reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use this file except
+// in compliance with the License. You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed under the License
+// is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express
+// or implied. See the License for the specific language governing permissions and limitations under
+// the License.
+
+//! Locale-independent formatting of numbers used in reports and dumps.
+//!
+//! The wrapper types implement [`Display`] without allocating memory, so they can be used in
+//! `no_std` environments. They respect width, fill and alignment of the format specification.
+
+use core::fmt::{self, Display, Formatter};
+
+/// Maximal length of the rendered numbers: `u64::MAX` in the binary form with prefix and
+/// separators is 81 characters long.
+const BUF_LEN: usize = 81;
+
+/// Digits of the numbers rendered in any of the supported radixes.
+const DIGITS: &[u8; 16] = b"0123456789ABCDEF";
+
+/// Stack buffer filled from the end.
+struct Buf {
+    bytes: [u8; BUF_LEN],
+    start: usize,
+}
+
+impl Buf {
+    fn new() -> Self { Self { bytes: [0; BUF_LEN], start: BUF_LEN } }
+
+    fn push_front(&mut self, byte: u8) {
+        self.start -= 1;
+        self.bytes[self.start] = byte;
+    }
+
+    fn push_digits(&mut self, val: u64, separated: bool) {
+        self.push_radix(val, 10, if separated { 3 } else { 0 })
+    }
+
+    /// Pushes digits of the value in the given radix, separating each `group` of them with `_`;
+    /// zero `group` means no separators.
+    fn push_radix(&mut self, mut val: u64, radix: u64, group: usize) {
+        let mut count = 0;
+        loop {
+            if group > 0 && count > 0 && count % group == 0 {
+                self.push_front(b'_');
+            }
+            self.push_front(DIGITS[(val % radix) as usize]);
+            count += 1;
+            val /= radix;
+            if val == 0 {
+                break;
+            }
+        }
+    }
+
+    fn push_prefix(&mut self, prefix: &[u8; 2]) {
+        self.push_front(prefix[1]);
+        self.push_front(prefix[0]);
+    }
+
+    fn as_str(&self) -> &str {
+        core::str::from_utf8(&self.bytes[self.start..]).expect("buffer contains only ASCII")
+    }
+}
+
+/// Decimal number with the digits grouped by three using `_` separator, like `1_234_567`.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct Dec(pub u64);
+
+impl Display for Dec {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut buf = Buf::new();
+        buf.push_digits(self.0, true);
+        f.pad(buf.as_str())
+    }
+}
+
+/// Decimal number abbreviated with an SI-style suffix (`k`, `M`, `G`, `T`, `P`, `E`) and a single
+/// fractional digit, like `1.2G`; numbers below 1000 are rendered without abbreviation.
+///
+/// The fractional digit is truncated rather than rounded, such that the abbreviated number never
+/// exceeds the actual one (`1_999_999` is rendered as `1.9M`, and `999_999` as `999.9k`).
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct Abbrev(pub u64);
+
+impl Display for Abbrev {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        const SUFFIXES: [u8; 6] = *b"kMGTPE";
+
+        let mut buf = Buf::new();
+        if self.0 < 1000 {
+            buf.push_digits(self.0, false);
+            return f.pad(buf.as_str());
+        }
+        let mut scale = 1000u64;
+        let mut suffix = 0;
+        while self.0 / scale >= 1000 && suffix < SUFFIXES.len() - 1 {
+            scale *= 1000;
+            suffix += 1;
+        }
+        buf.push_front(SUFFIXES[suffix]);
+        buf.push_front(b'0' + (self.0 % scale / (scale / 10)) as u8);
+        buf.push_front(b'.');
+        buf.push_digits(self.0 / scale, false);
+        f.pad(buf.as_str())
+    }
+}
+
+/// 16-bit number in a fixed-width hexadecimal form, like `0x0F3A`.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct Hex16(pub u16);
+
+impl Display for Hex16 {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut buf = Buf::new();
+        for shift in [0, 4, 8, 12] {
+            buf.push_front(DIGITS[(self.0 >> shift) as usize & 0xF]);
+        }
+        buf.push_prefix(b"0x");
+        f.pad(buf.as_str())
+    }
+}
+
+/// Hexadecimal number with the digits grouped by four using `_` separator, like `0xDEAD_BEEF`.
+///
+/// Unlike [`core::fmt::UpperHex`], the number always has the `0x` prefix and is never padded with
+/// zeros.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct Hex(pub u64);
+
+impl Display for Hex {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut buf = Buf::new();
+        buf.push_radix(self.0, 16, 4);
+        buf.push_prefix(b"0x");
+        f.pad(buf.as_str())
+    }
+}
+
+/// Octal number with the digits grouped by three using `_` separator, like `0o7_654`.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct Oct(pub u64);
+
+impl Display for Oct {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut buf = Buf::new();
+        buf.push_radix(self.0, 8, 3);
+        buf.push_prefix(b"0o");
+        f.pad(buf.as_str())
+    }
+}
+
+/// Binary number with the digits grouped by four using `_` separator, like `0b10_0110`.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct Bin(pub u64);
+
+impl Display for Bin {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut buf = Buf::new();
+        buf.push_radix(self.0, 2, 4);
+        buf.push_prefix(b"0b");
+        f.pad(buf.as_str())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![cfg_attr(coverage_nightly, coverage(off))]
+
+    use super::*;
+
+    #[test]
+    fn dec() {
+        assert_eq!(Dec(0).to_string(), "0");
+        assert_eq!(Dec(999).to_string(), "999");
+        assert_eq!(Dec(1000).to_string(), "1_000");
+        assert_eq!(Dec(1_234_567).to_string(), "1_234_567");
+        assert_eq!(Dec(u64::MAX).to_string(), "18_446_744_073_709_551_615");
+        assert_eq!(format!("{:>8}", Dec(1234)), "   1_234");
+    }
+
+    #[test]
+    fn abbrev() {
+        assert_eq!(Abbrev(0).to_string(), "0");
+        assert_eq!(Abbrev(999).to_string(), "999");
+        assert_eq!(Abbrev(1000).to_string(), "1.0k");
+        assert_eq!(Abbrev(12_345).to_string(), "12.3k");
+        assert_eq!(Abbrev(999_999).to_string(), "999.9k");
+        assert_eq!(Abbrev(1_999_999).to_string(), "1.9M");
+        assert_eq!(Abbrev(1_234_567_890).to_string(), "1.2G");
+        assert_eq!(Abbrev(u64::MAX).to_string(), "18.4E");
+        assert_eq!(format!("{:<6}|", Abbrev(1500)), "1.5k  |");
+    }
+
+    #[test]
+    fn hex16() {
+        assert_eq!(Hex16(0).to_string(), "0x0000");
+        assert_eq!(Hex16(0x0F3A).to_string(), "0x0F3A");
+        assert_eq!(Hex16(u16::MAX).to_string(), "0xFFFF");
+        assert_eq!(format!("{:>8}", Hex16(1)), "  0x0001");
+    }
+
+    #[test]
+    fn radix() {
+        assert_eq!(Hex(0).to_string(), "0x0");
+        assert_eq!(Hex(0xBEEF).to_string(), "0xBEEF");
+        assert_eq!(Hex(0xDEAD_BEEF).to_string(), "0xDEAD_BEEF");
+        assert_eq!(Hex(u64::MAX).to_string(), "0xFFFF_FFFF_FFFF_FFFF");
+        assert_eq!(Oct(0).to_string(), "0o0");
+        assert_eq!(Oct(0o7654).to_string(), "0o7_654");
+        assert_eq!(Oct(u64::MAX).to_string(), "0o1_777_777_777_777_777_777_777");
+        assert_eq!(Bin(0).to_string(), "0b0");
+        assert_eq!(Bin(0b10_0110).to_string(), "0b10_0110");
+        assert_eq!(Bin(u64::MAX).to_string().len(), BUF_LEN);
+        assert_eq!(format!("{:<8}|", Hex(0xAB)), "0xAB    |");
+    }
+}
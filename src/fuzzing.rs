@@ -0,0 +1,575 @@
+// Reference rust implementation of AluVM (arithmetic logic unit virtual machine).
+// To find more on AluVM please check <https://aluvm.org>
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Designed in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+// Written in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+//
+// Copyright (C) 2021-2024 LNP/BP Standards Association, Switzerland.
+// Copyright (C) 2024-2025 Laboratories for Ubiquitous Deterministic Computing (UBIDECO),
+//                         Institute for Distributed and Cognitive Systems (InDCS), Switzerland.
+// Copyright (C) 2021-2025 Dr Maxim Orlovsky.
+// All rights under the above copyrights are reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use this file except
+// in compliance with the License. You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed under the License
+// is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express
+// or implied. See the License for the specific language governing permissions and limitations under
+// the License.
+
+//! Harnesses for fuzzing ISA encoding and execution.
+//!
+//! The harnesses are designed to be called directly from `cargo-fuzz` targets of the crates
+//! providing ISA extensions (see the targets in the `fuzz` directory of this crate):
+//!
+//! ```ignore
+//! #![no_main]
+//! libfuzzer_sys::fuzz_target!(|data: &[u8]| {
+//!     aluvm::fuzzing::exec_roundtrip_target::<MyIsa<aluvm::LibId>>(data)
+//! });
+//! ```
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Debug;
+use core::iter;
+
+use crate::core::{
+    Core, CoreConfig, FailureReason, Status, UnknownInstrPolicy, CALL_STACK_SIZE_MAX,
+};
+use crate::isa::{Bytecode, BytecodeRead, CodeEofError, ExecStep, Instruction};
+use crate::library::{Lib, LibId, LibsSeg, MarshallError, Marshaller};
+use crate::Site;
+
+/// Complexity limit used by the fuzzing harnesses.
+pub const FUZZING_COMPLEXITY_LIM: u64 = 10_000_000;
+
+/// Maximal number of instructions executed by the fuzzing harnesses.
+pub const FUZZING_STEP_LIM: usize = 0x1000;
+
+/// Fuzzing target checking execution of an arbitrary program.
+///
+/// The input data are split into three parts:
+/// - code, which length is given by the first two bytes (little-endian);
+/// - data segment, which length is given by the next two bytes (little-endian);
+/// - the rest of the bytes, used to seed the core extension registers via
+///   [`crate::CoreExt::set_arbitrary`].
+///
+/// The code is decoded into instructions up to the first instruction which can't be decoded; the
+/// resulting program is then assembled into a library, which must disassemble back into the same
+/// instructions.
+///
+/// The library is executed in the call stack integrity mode with [`FUZZING_COMPLEXITY_LIM`]
+/// complexity limit for at most [`FUZZING_STEP_LIM`] instructions. Calls and returns into other
+/// libraries halt the execution.
+/// After each of the executed instructions, the following invariants are checked:
+/// - `CA` does not decrease;
+/// - `CP` does not exceed the call stack size;
+/// - `CY` increases at most by one, and only if the instruction performed a jump or a call;
+/// - `CF` does not decrease, and increases whenever `CK` switches to a failure;
+/// - `CK` is set to a failure whenever `CF` increases;
+/// - no call stack integrity violation is detected.
+///
+/// # Panics
+///
+/// If the assembly roundtrip fails or any of the invariants gets violated.
+pub fn exec_roundtrip_target<Isa>(data: &[u8])
+where
+    Isa: Instruction<LibId>,
+    Isa::Context<'static>: Default,
+{
+    let (code, rest) = split_prefixed(data);
+    let (data, seed) = split_prefixed(rest);
+
+    let libs = LibsSeg::default();
+    let mut reader = Marshaller::with(code, data, &libs).expect("prefixed segments fit the limits");
+    let mut program = Vec::<Isa>::new();
+    while !reader.is_eof() {
+        let Ok(instr) = Isa::decode_instr(&mut reader) else {
+            break;
+        };
+        program.push(instr);
+    }
+
+    let lib = Lib::assemble(&program).expect("decoded program must be assembled");
+    let disassembled = lib
+        .disassemble::<Isa>()
+        .expect("assembled program must be disassembled");
+    assert_eq!(disassembled, program, "assembly roundtrip failure");
+
+    let config = CoreConfig {
+        halt: true,
+        complexity_lim: Some(FUZZING_COMPLEXITY_LIM),
+        complexity_warn: None,
+        jump_lim: None,
+        instr_lim: None,
+        cs_integrity: true,
+        unknown_instr: UnknownInstrPolicy::Fail,
+    };
+    let mut core = Core::<LibId, Isa::Core>::with(config, default!());
+    core.set_arbitrary(&mut seed.iter().copied());
+    let context = Isa::Context::default();
+    exec_checked::<Isa>(&lib, &mut core, &context);
+}
+
+/// Fuzzing target checking decoding of an arbitrary bytecode.
+///
+/// The input data are split into three parts:
+/// - code, which length is given by the first two bytes (little-endian);
+/// - data segment, which length is given by the next two bytes (little-endian);
+/// - the rest of the bytes, split into 32-byte chunks making the libs segment.
+///
+/// The code is decoded into instructions up to the first instruction which can't be decoded.
+/// Decoding must never panic, only error; each of the decoded instructions must pass
+/// [`check_instr_roundtrip`].
+///
+/// # Panics
+///
+/// If the decoding panics or any of the decoded instructions fails the roundtrip check.
+pub fn decode_target<Isa>(data: &[u8])
+where Isa: Bytecode<LibId> + PartialEq + Debug {
+    let (code, rest) = split_prefixed(data);
+    let (data, libs) = split_prefixed(rest);
+    let libs = LibsSeg::from_iter_checked(
+        libs.chunks_exact(32)
+            .take(u8::MAX as usize)
+            .map(|chunk| LibId::from(<[u8; 32]>::try_from(chunk).expect("exact chunk"))),
+    );
+
+    let mut reader = Marshaller::with(code, data, &libs).expect("prefixed segments fit the limits");
+    while !reader.is_eof() {
+        let Ok(instr) = Isa::decode_instr(&mut reader) else {
+            break;
+        };
+        if let Err(err) = check_instr_roundtrip(&instr) {
+            panic!("roundtrip failure for {instr:?}: {err}");
+        }
+    }
+}
+
+/// Errors detected by [`check_instr_roundtrip`].
+#[derive(Clone, Eq, PartialEq, Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum RoundtripError {
+    /// unable to encode the instruction: {0}
+    #[from]
+    Encode(MarshallError),
+
+    /// the instruction reports bytecode length of {reported} bytes, while {written} bytes were
+    /// written.
+    LenMismatch {
+        /// Length reported by [`Bytecode::code_byte_len`].
+        reported: u16,
+        /// Number of bytes written to the code segment.
+        written: usize,
+    },
+
+    /// unable to decode the encoded instruction.
+    #[from(CodeEofError)]
+    Decode,
+
+    /// decoding consumed {read} bytes out of {written} bytes of the encoded instruction.
+    ReadMismatch {
+        /// Number of bytes read by the decoder.
+        read: u16,
+        /// Number of bytes written to the code segment.
+        written: usize,
+    },
+
+    /// decoded instruction {decoded} doesn't match the original instruction {original}.
+    Mismatch {
+        /// Debug representation of the original instruction.
+        original: String,
+        /// Debug representation of the decoded instruction.
+        decoded: String,
+    },
+}
+
+/// Checks that the instruction survives an encode-decode roundtrip.
+///
+/// The instruction is encoded with [`Marshaller`], which must write exactly the number of bytes
+/// reported by [`Bytecode::code_byte_len`]; the bytecode is then decoded back, which must consume
+/// all the written bytes and produce an instruction equal to the original one. The libs segment
+/// used for encoding contains the [`Bytecode::external_ref`] of the instruction, if any.
+///
+/// Designed for the crates implementing their own ISA extensions, which may use it both in tests
+/// and fuzzing targets.
+///
+/// # Errors
+///
+/// If any of the above checks fails.
+pub fn check_instr_roundtrip<Isa>(instr: &Isa) -> Result<(), RoundtripError>
+where Isa: Bytecode<LibId> + PartialEq + Debug {
+    let libs = LibsSeg::from_iter_checked(instr.external_ref());
+    let mut writer = Marshaller::new(&libs);
+    instr.encode_instr(&mut writer)?;
+    let (code, data) = writer.finish();
+
+    let reported = instr.code_byte_len();
+    if code.len() != reported as usize {
+        return Err(RoundtripError::LenMismatch { reported, written: code.len() });
+    }
+
+    let written = code.len();
+    let mut reader = Marshaller::with(code, data, &libs)?;
+    let decoded = Isa::decode_instr(&mut reader)?;
+    if reader.pos() as usize != written {
+        return Err(RoundtripError::ReadMismatch { read: reader.pos(), written });
+    }
+    if &decoded != instr {
+        return Err(RoundtripError::Mismatch {
+            original: format!("{instr:?}"),
+            decoded: format!("{decoded:?}"),
+        });
+    }
+    Ok(())
+}
+
+/// Types which can be constructed from a stream of arbitrary bytes.
+///
+/// Used by fuzzing targets generating arbitrary instructions. Implementations must be able to
+/// produce each of the type variants, and should favor boundary values of the operands.
+pub trait Arbitrary: Sized {
+    /// Constructs a value from a stream of arbitrary bytes.
+    ///
+    /// Returns `None` if the stream is exhausted before the first byte is read; a stream exhausted
+    /// later is padded with zeros.
+    fn arbitrary(bytes: &mut impl Iterator<Item = u8>) -> Option<Self>;
+}
+
+impl Arbitrary for u8 {
+    fn arbitrary(bytes: &mut impl Iterator<Item = u8>) -> Option<Self> { bytes.next() }
+}
+
+impl Arbitrary for u16 {
+    fn arbitrary(bytes: &mut impl Iterator<Item = u8>) -> Option<Self> {
+        Some(match bytes.next()? % 4 {
+            0 => 0,
+            1 => u16::MAX,
+            _ => u16::from_le_bytes([operand(bytes), operand(bytes)]),
+        })
+    }
+}
+
+impl Arbitrary for i8 {
+    fn arbitrary(bytes: &mut impl Iterator<Item = u8>) -> Option<Self> {
+        Some(match bytes.next()? % 4 {
+            0 => i8::MAX,
+            1 => -i8::MAX,
+            _ => operand::<u8>(bytes) as i8,
+        })
+    }
+}
+
+impl Arbitrary for i16 {
+    fn arbitrary(bytes: &mut impl Iterator<Item = u8>) -> Option<Self> {
+        Some(match bytes.next()? % 4 {
+            0 => i16::MAX,
+            1 => -i16::MAX,
+            _ => operand::<u16>(bytes) as i16,
+        })
+    }
+}
+
+impl Arbitrary for LibId {
+    fn arbitrary(bytes: &mut impl Iterator<Item = u8>) -> Option<Self> {
+        let mut buf = [bytes.next()?; 32];
+        for byte in &mut buf[1..] {
+            *byte = operand(bytes);
+        }
+        Some(LibId::from(buf))
+    }
+}
+
+impl Arbitrary for Site<LibId> {
+    fn arbitrary(bytes: &mut impl Iterator<Item = u8>) -> Option<Self> {
+        let lib_id = LibId::arbitrary(bytes)?;
+        Some(Site::new(lib_id, operand(bytes)))
+    }
+}
+
+/// Constructs an instruction operand, padding the exhausted stream with zeros.
+pub(crate) fn operand<T: Arbitrary>(bytes: &mut impl Iterator<Item = u8>) -> T {
+    T::arbitrary(&mut bytes.chain(iter::repeat(0))).expect("infinite stream")
+}
+
+fn split_prefixed(data: &[u8]) -> (&[u8], &[u8]) {
+    let (len, data) = match data {
+        [a, b, rest @ ..] => (u16::from_le_bytes([*a, *b]) as usize, rest),
+        _ => (0, &[][..]),
+    };
+    data.split_at(len.min(data.len()))
+}
+
+struct Snapshot {
+    ck: Status,
+    cf: u64,
+    cy: u16,
+    ca: u64,
+}
+
+impl Snapshot {
+    fn with<Isa: Instruction<LibId>>(core: &Core<LibId, Isa::Core>) -> Self {
+        Self { ck: core.ck(), cf: core.cf(), cy: core.cy(), ca: core.ca() }
+    }
+
+    fn check<Isa: Instruction<LibId>>(
+        &self,
+        core: &Core<LibId, Isa::Core>,
+        step: &ExecStep<Site<LibId>>,
+        instr: &Isa,
+    ) {
+        assert!(core.ca() >= self.ca, "`CA` decreased after `{instr}`");
+        assert!(
+            core.cp() as usize <= CALL_STACK_SIZE_MAX as usize,
+            "`CP` exceeds the call stack size after `{instr}`"
+        );
+        assert!(core.cy() >= self.cy, "`CY` decreased after `{instr}`");
+        assert!(core.cy() - self.cy <= 1, "`CY` increased more than once by `{instr}`");
+        if core.cy() != self.cy {
+            assert!(
+                matches!(step, ExecStep::Jump(_) | ExecStep::Call(_)),
+                "`CY` increased by non-jump instruction `{instr}`"
+            );
+        }
+        assert!(core.cf() >= self.cf, "`CF` decreased after `{instr}`");
+        if core.cf() != self.cf {
+            assert_eq!(core.ck(), Status::Fail, "`CF` increased without `CK` failure by `{instr}`");
+        }
+        if self.ck == Status::Ok && core.ck() == Status::Fail {
+            assert_ne!(core.cf(), self.cf, "`CK` failed without increasing `CF` by `{instr}`");
+        }
+        if let Some(fault) = core.cs_fault() {
+            panic!("call stack integrity violation after `{instr}`: {fault}");
+        }
+    }
+}
+
+fn exec_checked<Isa>(lib: &Lib, core: &mut Core<LibId, Isa::Core>, context: &Isa::Context<'_>)
+where Isa: Instruction<LibId> {
+    let lib_id = lib.lib_id();
+    let mut marshaller = Marshaller::with_unchecked(lib.code(), lib.data(), lib.libs());
+
+    for _ in 0..FUZZING_STEP_LIM {
+        if marshaller.is_eof() {
+            return;
+        }
+        let pos = marshaller.pos();
+        let Ok(instr) = Isa::decode_instr(&mut marshaller) else {
+            return;
+        };
+
+        let snapshot = Snapshot::with::<Isa>(core);
+        let site = Site::new(lib_id, pos);
+        let step = instr.exec(site, core, context);
+        let within_limit = core.acc_complexity_at(site, instr.complexity());
+
+        let mut halt = false;
+        let mut goto = None;
+        let mut skip = false;
+        match step {
+            _ if !within_limit => {
+                let _ = core.fail_ck_with(FailureReason::ComplexityLimit);
+                halt = true;
+            }
+            ExecStep::Stop => halt = true,
+            ExecStep::Fail => halt = core.fail_ck(),
+            ExecStep::Next => {}
+            ExecStep::Jump(pos) => goto = Some(pos),
+            ExecStep::Call(site) if site.prog_id == lib_id => goto = Some(site.offset),
+            ExecStep::Ret(site) if site.prog_id == lib_id => {
+                goto = Some(site.offset);
+                skip = true;
+            }
+            ExecStep::Call(_) | ExecStep::Ret(_) => halt = true,
+        }
+        if let Some(pos) = goto {
+            if marshaller.seek(pos).is_err() {
+                let _ = core.fail_ck_with(FailureReason::InvalidJump);
+                halt = true;
+            } else if skip {
+                halt = Isa::decode_instr(&mut marshaller).is_err();
+            }
+        }
+
+        snapshot.check(core, &step, &instr);
+        if halt {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![cfg_attr(coverage_nightly, coverage(off))]
+
+    use core::ops::RangeInclusive;
+    use std::collections::BTreeSet;
+
+    use super::*;
+    use crate::isa::{BytecodeWrite, CtrlInstr, Instr};
+
+    /// Deterministic xorshift pseudo-random generator
+    fn xorshift() -> impl FnMut() -> u64 {
+        let mut state = 0x2545_F491_4F6C_DD1D_u64;
+        move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        }
+    }
+
+    #[test]
+    fn exec_roundtrip_smoke() {
+        let mut next = xorshift();
+
+        for round in 0..4096 {
+            let len = (next() % 256) as usize;
+            let mut data = (0..len).map(|_| next() as u8).collect::<Vec<_>>();
+            if round % 2 == 1 && data.len() > 4 {
+                // Bias half of the inputs towards the defined opcodes and a long code segment
+                data[0] = 0xFF;
+                data[1] = 0xFF;
+                for byte in &mut data[4..] {
+                    *byte %= 0x18;
+                }
+            }
+            exec_roundtrip_target::<Instr<LibId>>(&data);
+        }
+    }
+
+    #[test]
+    fn decode_smoke() {
+        let mut next = xorshift();
+        for round in 0..4096 {
+            let len = (next() % 256) as usize;
+            let mut data = (0..len).map(|_| next() as u8).collect::<Vec<_>>();
+            if round % 2 == 1 && data.len() > 4 {
+                // Bias half of the inputs towards the defined opcodes
+                data[0] = 0xFF;
+                for byte in &mut data[4..] {
+                    *byte %= 0x18;
+                }
+            }
+            decode_target::<Instr<LibId>>(&data);
+        }
+    }
+
+    /// Feeds each opcode byte with random operands, data and libs segments to the decoder.
+    fn decode_opcodes<Isa: Bytecode<LibId> + PartialEq + Debug>() {
+        let mut next = xorshift();
+        for opcode in 0..=0xFF {
+            for _ in 0..64 {
+                let code_len = (next() % 16) as usize;
+                let data_len = (next() % 48) as usize;
+                let libs_len = (next() % 3) as usize * 32;
+                let mut input = Vec::with_capacity(code_len + data_len + libs_len + 5);
+                input.extend((code_len as u16 + 1).to_le_bytes());
+                input.push(opcode);
+                input.extend((0..code_len).map(|_| next() as u8));
+                input.extend((data_len as u16).to_le_bytes());
+                input.extend((0..data_len + libs_len).map(|_| next() as u8));
+                decode_target::<Isa>(&input);
+            }
+        }
+    }
+
+    #[test]
+    fn decode_every_opcode() {
+        decode_opcodes::<Instr<LibId>>();
+        decode_opcodes::<crate::isa::Alu64Instr<LibId>>();
+        #[cfg(feature = "secp256k1")]
+        decode_opcodes::<crate::isa::secp::SecpIsa<LibId>>();
+    }
+
+    #[test]
+    fn arbitrary_instr() {
+        assert_eq!(Instr::<LibId>::arbitrary(&mut iter::empty()), None);
+        assert_eq!(
+            Instr::<LibId>::arbitrary(&mut [1, CtrlInstr::<LibId>::JMP].into_iter()),
+            Some(CtrlInstr::Jmp { pos: 0 }.into())
+        );
+
+        let mut next = xorshift();
+        let mut opcodes = BTreeSet::new();
+        let mut generated = BTreeSet::new();
+        for _ in 0..4096 {
+            let mut bytes = (0..40).map(|_| next() as u8);
+            let instr = Instr::<LibId>::arbitrary(&mut bytes).unwrap();
+            check_instr_roundtrip(&instr).unwrap();
+            opcodes.insert(instr.opcode_byte());
+            generated.insert(instr.to_string());
+        }
+        for opcode in CtrlInstr::<LibId>::START..=CtrlInstr::<LibId>::END {
+            assert!(opcodes.contains(&opcode), "opcode {opcode} is never generated");
+        }
+        assert!(opcodes
+            .iter()
+            .any(|opcode| *opcode > CtrlInstr::<LibId>::END));
+        for instr in [
+            CtrlInstr::<LibId>::Jmp { pos: 0 },
+            CtrlInstr::JiFail { pos: u16::MAX },
+            CtrlInstr::Sh { shift: i8::MAX },
+            CtrlInstr::ShOvfl { shift: -i8::MAX },
+            CtrlInstr::ShW { shift: i16::MAX },
+            CtrlInstr::ShWFail { shift: -i16::MAX },
+            CtrlInstr::Save { mask: u16::MAX },
+        ] {
+            assert!(generated.contains(&instr.to_string()), "{instr} is never generated");
+        }
+    }
+
+    #[derive(Clone, Copy, PartialEq, Eq, Debug)]
+    enum FaultyInstr {
+        WrongLen,
+        WrongDecode,
+    }
+
+    impl Bytecode<LibId> for FaultyInstr {
+        fn op_range() -> RangeInclusive<u8> { 0..=1 }
+
+        fn opcode_byte(&self) -> u8 { *self as u8 }
+
+        fn code_byte_len(&self) -> u16 {
+            match self {
+                FaultyInstr::WrongLen => 2,
+                FaultyInstr::WrongDecode => 1,
+            }
+        }
+
+        fn external_ref(&self) -> Option<LibId> { None }
+
+        fn encode_operands<W>(&self, _writer: &mut W) -> Result<(), W::Error>
+        where W: BytecodeWrite<LibId> {
+            Ok(())
+        }
+
+        fn decode_operands<R>(_reader: &mut R, _opcode: u8) -> Result<Self, CodeEofError>
+        where R: BytecodeRead<LibId> {
+            Ok(FaultyInstr::WrongLen)
+        }
+    }
+
+    #[test]
+    fn roundtrip_errors() {
+        assert_eq!(
+            check_instr_roundtrip(&FaultyInstr::WrongLen),
+            Err(RoundtripError::LenMismatch { reported: 2, written: 1 })
+        );
+        assert_eq!(
+            check_instr_roundtrip(&FaultyInstr::WrongDecode),
+            Err(RoundtripError::Mismatch { original: s!("WrongDecode"), decoded: s!("WrongLen") })
+        );
+        check_instr_roundtrip(&Instr::<LibId>::Ctrl(CtrlInstr::Call {
+            site: Site::new(LibId::from([0xAC; 32]), u16::MAX),
+        }))
+        .unwrap();
+    }
+}
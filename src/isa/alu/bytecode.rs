@@ -0,0 +1,540 @@
+// Reference rust implementation of AluVM (arithmetic logic unit virtual machine).
+// To find more on AluVM please check <https://aluvm.org>
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Designed in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+// Written in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+//
+// Copyright (C) 2021-2024 LNP/BP Standards Association, Switzerland.
+// Copyright (C) 2024-2025 Laboratories for Ubiquitous Deterministic Computing (UBIDECO),
+//                         Institute for Distributed and Cognitive Systems (InDCS), Switzerland.
+// Copyright (C) 2021-2025 Dr Maxim Orlovsky.
+// All rights under the above copyrights are reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use this file except
+// in compliance with the License. You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed under the License
+// is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express
+// or implied. See the License for the specific language governing permissions and limitations under
+// the License.
+
+use alloc::vec::Vec;
+use core::ops::RangeInclusive;
+
+use amplify::num::u4;
+
+use super::{Alu64Instr, ArithmInstr, RegA, SelectInstr, TableInstr};
+use crate::core::SiteId;
+use crate::isa::bytecode::CodeEofError;
+use crate::isa::{Bytecode, BytecodeRead, BytecodeWrite, CtrlInstr, ReservedInstr};
+use crate::LibId;
+
+const _: () = assert!(
+    ArithmInstr::START > CtrlInstr::<LibId>::END,
+    "ALU64 opcodes overlap with the control flow instructions"
+);
+const _: () = assert!(
+    SelectInstr::START > ArithmInstr::END,
+    "SEL64 opcodes overlap with the ALU64 instructions"
+);
+const _: () = assert!(
+    TableInstr::START > SelectInstr::END,
+    "JMPTBL opcodes overlap with the SEL64 instructions"
+);
+
+impl<Id: SiteId> Bytecode<Id> for Alu64Instr<Id> {
+    fn op_range() -> RangeInclusive<u8> { 0..=0xFF }
+
+    fn opcode_byte(&self) -> u8 {
+        match self {
+            Alu64Instr::Ctrl(instr) => instr.opcode_byte(),
+            Alu64Instr::Arithm(instr) => Bytecode::<Id>::opcode_byte(instr),
+            Alu64Instr::Reserved(instr) => Bytecode::<Id>::opcode_byte(instr),
+        }
+    }
+
+    fn code_byte_len(&self) -> u16 {
+        match self {
+            Alu64Instr::Ctrl(instr) => instr.code_byte_len(),
+            Alu64Instr::Arithm(instr) => Bytecode::<Id>::code_byte_len(instr),
+            Alu64Instr::Reserved(instr) => Bytecode::<Id>::code_byte_len(instr),
+        }
+    }
+
+    fn external_ref(&self) -> Option<Id> {
+        match self {
+            Alu64Instr::Ctrl(instr) => instr.external_ref(),
+            Alu64Instr::Arithm(instr) => Bytecode::<Id>::external_ref(instr),
+            Alu64Instr::Reserved(instr) => Bytecode::<Id>::external_ref(instr),
+        }
+    }
+
+    fn encode_operands<W>(&self, writer: &mut W) -> Result<(), W::Error>
+    where W: BytecodeWrite<Id> {
+        match self {
+            Alu64Instr::Ctrl(instr) => instr.encode_operands(writer),
+            Alu64Instr::Arithm(instr) => instr.encode_operands(writer),
+            Alu64Instr::Reserved(instr) => instr.encode_operands(writer),
+        }
+    }
+
+    fn decode_operands<R>(reader: &mut R, opcode: u8) -> Result<Self, CodeEofError>
+    where
+        Self: Sized,
+        R: BytecodeRead<Id>,
+    {
+        if CtrlInstr::<Id>::op_range().contains(&opcode) {
+            CtrlInstr::<Id>::decode_operands(reader, opcode).map(Self::Ctrl)
+        } else if <ArithmInstr as Bytecode<Id>>::op_range().contains(&opcode) {
+            ArithmInstr::decode_operands(reader, opcode).map(Self::Arithm)
+        } else {
+            ReservedInstr::decode_operands(reader, opcode).map(Self::Reserved)
+        }
+    }
+}
+
+#[allow(missing_docs)]
+impl ArithmInstr {
+    pub(crate) const START: u8 = 0x40;
+    pub(crate) const END: u8 = Self::DATA;
+
+    pub const PUT: u8 = 0x40;
+    pub const MOV: u8 = 0x41;
+    pub const ADD: u8 = 0x42;
+    pub const ADDW: u8 = 0x43;
+    pub const SUB: u8 = 0x44;
+    pub const SUBW: u8 = 0x45;
+    pub const MUL: u8 = 0x46;
+    pub const MULW: u8 = 0x47;
+    pub const EQ: u8 = 0x48;
+    pub const DATA: u8 = 0x49;
+}
+
+#[allow(missing_docs)]
+impl SelectInstr {
+    pub(crate) const START: u8 = 0x50;
+    pub(crate) const END: u8 = Self::MOVCK;
+
+    pub const SEL: u8 = 0x50;
+    pub const MOVCO: u8 = 0x51;
+    pub const MOVCK: u8 = 0x52;
+}
+
+#[allow(missing_docs)]
+impl TableInstr {
+    pub(crate) const START: u8 = 0x58;
+    pub(crate) const END: u8 = Self::JMP;
+
+    pub const JMP: u8 = 0x58;
+}
+
+/// Number of bytes in the minimal little-endian representation of a constant, which is how
+/// [`ArithmInstr::Data`] stores it in the data segment.
+pub(super) fn const_len(val: u64) -> u16 {
+    (u64::BITS - val.leading_zeros()).div_ceil(8).max(1) as u16
+}
+
+fn write_regs<Id: SiteId, W: BytecodeWrite<Id>>(
+    writer: &mut W,
+    reg1: RegA,
+    reg2: RegA,
+) -> Result<(), W::Error> {
+    writer.write_4bits(u4::with(reg1.index()))?;
+    writer.write_4bits(u4::with(reg2.index()))
+}
+
+fn read_regs<Id: SiteId, R: BytecodeRead<Id>>(
+    reader: &mut R,
+) -> Result<(RegA, RegA), CodeEofError> {
+    let reg1 = RegA::from(reader.read_4bits()?);
+    let reg2 = RegA::from(reader.read_4bits()?);
+    Ok((reg1, reg2))
+}
+
+impl<Id: SiteId> Bytecode<Id> for ArithmInstr {
+    fn op_range() -> RangeInclusive<u8> { Self::START..=Self::END }
+
+    fn opcode_byte(&self) -> u8 {
+        match *self {
+            ArithmInstr::Put { .. } => Self::PUT,
+            ArithmInstr::Mov { .. } => Self::MOV,
+            ArithmInstr::Add { wrap: false, .. } => Self::ADD,
+            ArithmInstr::Add { wrap: true, .. } => Self::ADDW,
+            ArithmInstr::Sub { wrap: false, .. } => Self::SUB,
+            ArithmInstr::Sub { wrap: true, .. } => Self::SUBW,
+            ArithmInstr::Mul { wrap: false, .. } => Self::MUL,
+            ArithmInstr::Mul { wrap: true, .. } => Self::MULW,
+            ArithmInstr::Eq { .. } => Self::EQ,
+            ArithmInstr::Data { .. } => Self::DATA,
+        }
+    }
+
+    fn code_byte_len(&self) -> u16 {
+        let arg_bytes = match self {
+            // register and a reference to the value in the data segment
+            ArithmInstr::Put { .. } => 3,
+            // register and an offset-length pair of the constant in the data segment
+            ArithmInstr::Data { .. } => 5,
+            ArithmInstr::Mov { .. }
+            | ArithmInstr::Add { .. }
+            | ArithmInstr::Sub { .. }
+            | ArithmInstr::Mul { .. }
+            | ArithmInstr::Eq { .. } => 1,
+        };
+        arg_bytes + 1
+    }
+
+    fn external_ref(&self) -> Option<Id> { None }
+
+    fn encode_operands<W>(&self, writer: &mut W) -> Result<(), W::Error>
+    where W: BytecodeWrite<Id> {
+        match *self {
+            ArithmInstr::Put { dst, val } => {
+                writer.write_4bits(u4::with(dst.index()))?;
+                writer.write_4bits(u4::ZERO)?;
+                writer.write_fixed(val.to_le_bytes())?;
+            }
+            ArithmInstr::Data { dst, val } => {
+                writer.write_4bits(u4::with(dst.index()))?;
+                writer.write_4bits(u4::ZERO)?;
+                writer.write_bytes(&val.to_le_bytes()[..const_len(val) as usize])?;
+            }
+            ArithmInstr::Mov { dst, src }
+            | ArithmInstr::Add { dst, src, .. }
+            | ArithmInstr::Sub { dst, src, .. }
+            | ArithmInstr::Mul { dst, src, .. } => write_regs(writer, dst, src)?,
+            ArithmInstr::Eq { src1, src2 } => write_regs(writer, src1, src2)?,
+        }
+        Ok(())
+    }
+
+    fn decode_operands<R>(reader: &mut R, opcode: u8) -> Result<Self, CodeEofError>
+    where
+        Self: Sized,
+        R: BytecodeRead<Id>,
+    {
+        Ok(match opcode {
+            Self::PUT => {
+                let dst = RegA::from(reader.read_4bits()?);
+                let _ = reader.read_4bits()?;
+                let val = reader.read_fixed(u64::from_le_bytes)?;
+                ArithmInstr::Put { dst, val }
+            }
+            Self::DATA => {
+                let dst = RegA::from(reader.read_4bits()?);
+                let _ = reader.read_4bits()?;
+                let data = reader.read_bytes()?;
+                let mut buf = [0u8; 8];
+                // Only the minimal encoding is valid, such that each constant has a single
+                // representation, and its length is the one accounted by `ext_data_bytes`.
+                if data.is_empty() || data.len() > buf.len() {
+                    return Err(CodeEofError);
+                }
+                buf[..data.len()].copy_from_slice(data.as_slice());
+                let val = u64::from_le_bytes(buf);
+                if const_len(val) as usize != data.len() {
+                    return Err(CodeEofError);
+                }
+                ArithmInstr::Data { dst, val }
+            }
+            Self::MOV => {
+                let (dst, src) = read_regs(reader)?;
+                ArithmInstr::Mov { dst, src }
+            }
+            Self::ADD | Self::ADDW => {
+                let (dst, src) = read_regs(reader)?;
+                ArithmInstr::Add { wrap: opcode == Self::ADDW, dst, src }
+            }
+            Self::SUB | Self::SUBW => {
+                let (dst, src) = read_regs(reader)?;
+                ArithmInstr::Sub { wrap: opcode == Self::SUBW, dst, src }
+            }
+            Self::MUL | Self::MULW => {
+                let (dst, src) = read_regs(reader)?;
+                ArithmInstr::Mul { wrap: opcode == Self::MULW, dst, src }
+            }
+            Self::EQ => {
+                let (src1, src2) = read_regs(reader)?;
+                ArithmInstr::Eq { src1, src2 }
+            }
+
+            _ => return Err(CodeEofError),
+        })
+    }
+}
+
+impl<Id: SiteId> Bytecode<Id> for SelectInstr {
+    fn op_range() -> RangeInclusive<u8> { Self::START..=Self::END }
+
+    fn opcode_byte(&self) -> u8 {
+        match *self {
+            SelectInstr::Sel { .. } => Self::SEL,
+            SelectInstr::MovCo { .. } => Self::MOVCO,
+            SelectInstr::MovCk { .. } => Self::MOVCK,
+        }
+    }
+
+    fn code_byte_len(&self) -> u16 { 2 }
+
+    fn external_ref(&self) -> Option<Id> { None }
+
+    fn encode_operands<W>(&self, writer: &mut W) -> Result<(), W::Error>
+    where W: BytecodeWrite<Id> {
+        match *self {
+            SelectInstr::Sel { dst, src } => write_regs(writer, dst, src),
+            SelectInstr::MovCo { dst } | SelectInstr::MovCk { dst } => {
+                writer.write_4bits(u4::with(dst.index()))?;
+                writer.write_4bits(u4::ZERO)
+            }
+        }
+    }
+
+    fn decode_operands<R>(reader: &mut R, opcode: u8) -> Result<Self, CodeEofError>
+    where
+        Self: Sized,
+        R: BytecodeRead<Id>,
+    {
+        let (dst, src) = read_regs(reader)?;
+        Ok(match opcode {
+            Self::SEL => SelectInstr::Sel { dst, src },
+            Self::MOVCO => SelectInstr::MovCo { dst },
+            Self::MOVCK => SelectInstr::MovCk { dst },
+            _ => return Err(CodeEofError),
+        })
+    }
+}
+
+impl<Id: SiteId> Bytecode<Id> for TableInstr {
+    fn op_range() -> RangeInclusive<u8> { Self::START..=Self::END }
+
+    fn opcode_byte(&self) -> u8 {
+        match self {
+            TableInstr::Jmp { .. } => Self::JMP,
+        }
+    }
+
+    // register and an offset-length pair of the table in the data segment
+    fn code_byte_len(&self) -> u16 { 6 }
+
+    fn external_ref(&self) -> Option<Id> { None }
+
+    fn encode_operands<W>(&self, writer: &mut W) -> Result<(), W::Error>
+    where W: BytecodeWrite<Id> {
+        match self {
+            TableInstr::Jmp { idx, table } => {
+                writer.write_4bits(u4::with(idx.index()))?;
+                writer.write_4bits(u4::ZERO)?;
+                let data = table
+                    .iter()
+                    .flat_map(|pos| pos.to_le_bytes())
+                    .collect::<Vec<_>>();
+                writer.write_bytes(&data)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn decode_operands<R>(reader: &mut R, opcode: u8) -> Result<Self, CodeEofError>
+    where
+        Self: Sized,
+        R: BytecodeRead<Id>,
+    {
+        Ok(match opcode {
+            Self::JMP => {
+                let idx = RegA::from(reader.read_4bits()?);
+                let _ = reader.read_4bits()?;
+                let data = reader.read_bytes()?;
+                // A partial entry can't be a target; an empty table is valid, but always fails `CK`
+                if data.len() % 2 != 0 {
+                    return Err(CodeEofError);
+                }
+                let table = data
+                    .chunks_exact(2)
+                    .map(|chunk| u16::from_le_bytes([chunk[0], chunk[1]]))
+                    .collect();
+                TableInstr::Jmp { idx, table }
+            }
+            _ => return Err(CodeEofError),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![cfg_attr(coverage_nightly, coverage(off))]
+
+    use core::fmt::Debug;
+
+    use super::*;
+    use crate::library::{LibsSeg, Marshaller};
+
+    fn roundtrip(instr: impl Into<Alu64Instr<LibId>>, bytecode: impl AsRef<[u8]>) {
+        let instr = instr.into();
+        let libs = LibsSeg::new();
+        let mut marshaller = Marshaller::new(&libs);
+        instr.encode_instr(&mut marshaller).unwrap();
+        let (code, data) = marshaller.finish();
+        assert_eq!(code.len(), instr.code_byte_len() as usize);
+        assert_eq!(code.as_slice(), bytecode.as_ref());
+        let mut marshaller = Marshaller::with(code, data, &libs).unwrap();
+        let decoded = Alu64Instr::<LibId>::decode_instr(&mut marshaller).unwrap();
+        assert_eq!(decoded, instr);
+    }
+
+    #[test]
+    fn put() {
+        let instr = ArithmInstr::Put { dst: RegA::A5, val: 0xDEAD_BEEF };
+        roundtrip(instr, [ArithmInstr::PUT, 0x05, 0x00, 0x00]);
+        assert_eq!(instr.to_string(), "put     A5, 3735928559");
+    }
+
+    #[test]
+    fn data() {
+        let instr = ArithmInstr::Data { dst: RegA::A5, val: 0xDEAD_BEEF };
+        roundtrip(instr, [ArithmInstr::DATA, 0x05, 0x00, 0x00, 0x04, 0x00]);
+        assert_eq!(instr.to_string(), "data    A5, 3735928559");
+        for (val, len) in [(0, 1), (0x100, 2), (u64::MAX, 8)] {
+            roundtrip(ArithmInstr::Data { dst: RegA::A0, val }, [
+                ArithmInstr::DATA,
+                0,
+                0,
+                0,
+                len,
+                0,
+            ]);
+        }
+    }
+
+    #[test]
+    fn data_non_minimal() {
+        let libs = LibsSeg::new();
+        let code = [ArithmInstr::DATA, 0x00, 0x00, 0x00, 0x02, 0x00];
+        for data in [&[][..], &[0x01, 0x00], &[0x01; 9]] {
+            let mut code = code;
+            code[4] = data.len() as u8;
+            let mut marshaller = Marshaller::with(code, data, &libs).unwrap();
+            assert_eq!(Alu64Instr::<LibId>::decode_instr(&mut marshaller), Err(CodeEofError));
+        }
+    }
+
+    #[test]
+    fn binary() {
+        let (dst, src) = (RegA::A1, RegA::A15);
+        let cases = [
+            (ArithmInstr::Mov { dst, src }, ArithmInstr::MOV, "mov     A1, A15"),
+            (ArithmInstr::Add { wrap: false, dst, src }, ArithmInstr::ADD, "add     A1, A15"),
+            (ArithmInstr::Add { wrap: true, dst, src }, ArithmInstr::ADDW, "add.w   A1, A15"),
+            (ArithmInstr::Sub { wrap: false, dst, src }, ArithmInstr::SUB, "sub     A1, A15"),
+            (ArithmInstr::Sub { wrap: true, dst, src }, ArithmInstr::SUBW, "sub.w   A1, A15"),
+            (ArithmInstr::Mul { wrap: false, dst, src }, ArithmInstr::MUL, "mul     A1, A15"),
+            (ArithmInstr::Mul { wrap: true, dst, src }, ArithmInstr::MULW, "mul.w   A1, A15"),
+            (ArithmInstr::Eq { src1: dst, src2: src }, ArithmInstr::EQ, "eq      A1, A15"),
+        ];
+        for (instr, opcode, display) in cases {
+            roundtrip(instr, [opcode, 0xF1]);
+            assert_eq!(instr.to_string(), display);
+        }
+    }
+
+    #[test]
+    fn select() {
+        let (dst, src) = (RegA::A1, RegA::A15);
+        let cases = [
+            (SelectInstr::Sel { dst, src }, SelectInstr::SEL, 0xF1, "sel     A1, A15"),
+            (SelectInstr::MovCo { dst: RegA::A7 }, SelectInstr::MOVCO, 0x07, "mov     A7, CO"),
+            (SelectInstr::MovCk { dst: RegA::A15 }, SelectInstr::MOVCK, 0x0F, "mov     A15, CK"),
+        ];
+        let libs = LibsSeg::new();
+        for (instr, opcode, operands, display) in cases {
+            let mut marshaller = Marshaller::new(&libs);
+            instr.encode_instr(&mut marshaller).unwrap();
+            let (code, data) = marshaller.finish();
+            assert_eq!(code.as_slice(), &[opcode, operands]);
+            assert_eq!(Bytecode::<LibId>::code_byte_len(&instr), 2);
+            let mut marshaller = Marshaller::with(code, data, &libs).unwrap();
+            assert_eq!(SelectInstr::decode_instr(&mut marshaller), Ok(instr));
+            assert_eq!(instr.to_string(), display);
+        }
+    }
+
+    #[test]
+    fn table() {
+        let instr = TableInstr::Jmp { idx: RegA::A3, table: vec![0x10, 0x1234, 0x10] };
+        let libs = LibsSeg::new();
+        let mut marshaller = Marshaller::new(&libs);
+        instr.encode_instr(&mut marshaller).unwrap();
+        let (code, data) = marshaller.finish();
+        assert_eq!(code.as_slice(), &[TableInstr::JMP, 0x03, 0x00, 0x00, 0x06, 0x00]);
+        assert_eq!(data.as_slice(), &[0x10, 0x00, 0x34, 0x12, 0x10, 0x00]);
+        assert_eq!(code.len(), Bytecode::<LibId>::code_byte_len(&instr) as usize);
+        let mut marshaller = Marshaller::with(code, data, &libs).unwrap();
+        assert_eq!(TableInstr::decode_instr(&mut marshaller), Ok(instr.clone()));
+        assert_eq!(instr.to_string(), "jmp     A3, [16, 4660, 16]");
+    }
+
+    #[test]
+    fn table_empty() {
+        let instr = TableInstr::Jmp { idx: RegA::A3, table: vec![] };
+        let libs = LibsSeg::new();
+        let mut marshaller = Marshaller::new(&libs);
+        instr.encode_instr(&mut marshaller).unwrap();
+        let (code, data) = marshaller.finish();
+        assert_eq!(code.as_slice(), &[TableInstr::JMP, 0x03, 0x00, 0x00, 0x00, 0x00]);
+        assert!(data.is_empty());
+        let mut marshaller = Marshaller::with(code, data, &libs).unwrap();
+        assert_eq!(TableInstr::decode_instr(&mut marshaller), Ok(instr.clone()));
+        assert_eq!(instr.to_string(), "jmp     A3, []");
+    }
+
+    #[test]
+    fn table_invalid() {
+        let libs = LibsSeg::new();
+        let code = [TableInstr::JMP, 0x00, 0x00, 0x00, 0x00, 0x00];
+        for data in [&[0x01][..], &[0x01, 0x00, 0x02]] {
+            let mut code = code;
+            code[4] = data.len() as u8;
+            let mut marshaller = Marshaller::with(code, data, &libs).unwrap();
+            assert_eq!(
+                <TableInstr as Bytecode<LibId>>::decode_instr(&mut marshaller),
+                Err(CodeEofError)
+            );
+        }
+    }
+
+    #[test]
+    fn opcode_ranges() {
+        roundtrip(CtrlInstr::Stop, [CtrlInstr::<LibId>::STOP]);
+        roundtrip(ReservedInstr::default(), [0xFF]);
+        roundtrip(ReservedInstr(ArithmInstr::END + 1), [ArithmInstr::END + 1]);
+        for opcode in 0..=0xFF {
+            let is_ctrl = CtrlInstr::<LibId>::op_range().contains(&opcode);
+            let is_arithm = <ArithmInstr as Bytecode<LibId>>::op_range().contains(&opcode);
+            let is_select = <SelectInstr as Bytecode<LibId>>::op_range().contains(&opcode);
+            let is_table = <TableInstr as Bytecode<LibId>>::op_range().contains(&opcode);
+            let owners = [is_ctrl, is_arithm, is_select, is_table]
+                .into_iter()
+                .filter(|is| *is)
+                .count();
+            assert!(owners <= 1, "opcode {opcode:#04X} is assigned twice");
+        }
+    }
+
+    fn check_foreign_opcodes<I: Bytecode<LibId> + Debug + PartialEq>() {
+        let libs = LibsSeg::new();
+        for opcode in (0..=0xFF).filter(|opcode| !I::op_range().contains(opcode)) {
+            let mut marshaller = Marshaller::with([0u8; 8], [0u8; 8], &libs).unwrap();
+            assert_eq!(I::decode_operands(&mut marshaller, opcode), Err(CodeEofError));
+        }
+    }
+
+    #[test]
+    fn foreign_opcodes() {
+        check_foreign_opcodes::<ArithmInstr>();
+        check_foreign_opcodes::<SelectInstr>();
+        check_foreign_opcodes::<TableInstr>();
+    }
+}
@@ -0,0 +1,687 @@
+// Reference rust implementation of AluVM (arithmetic logic unit virtual machine).
+// To find more on AluVM please check <https://aluvm.org>
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Designed in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+// Written in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+//
+// Copyright (C) 2021-2024 LNP/BP Standards Association, Switzerland.
+// Copyright (C) 2024-2025 Laboratories for Ubiquitous Deterministic Computing (UBIDECO),
+//                         Institute for Distributed and Cognitive Systems (InDCS), Switzerland.
+// Copyright (C) 2021-2025 Dr Maxim Orlovsky.
+// All rights under the above copyrights are reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use this file except
+// in compliance with the License. You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed under the License
+// is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express
+// or implied. See the License for the specific language governing permissions and limitations under
+// the License.
+
+use alloc::collections::BTreeSet;
+
+use super::bytecode::const_len;
+use super::{Alu64Core, Alu64Instr, ArithmInstr, RegA, SelectInstr, TableInstr};
+use crate::core::{Core, FailureReason, NoExt, Site, SiteId, Status, Supercore};
+use crate::isa::{ComplexityClass, ExecStep, FlowKind, GotoTarget, Instruction};
+use crate::IsaId;
+
+impl<Id: SiteId> Instruction<Id> for Alu64Instr<Id> {
+    const ISA_EXT: &'static [&'static str] = &["ALU64"];
+
+    type Core = Alu64Core;
+    type Context<'ctx> = ();
+
+    fn isa_ext_id(&self) -> Option<&'static str> {
+        match self {
+            Alu64Instr::Ctrl(_) | Alu64Instr::Reserved(_) => None,
+            Alu64Instr::Arithm(instr) => Instruction::<Id>::isa_ext_id(instr),
+        }
+    }
+
+    fn is_reserved(&self) -> bool { matches!(self, Alu64Instr::Reserved(_)) }
+
+    fn is_goto_target(&self) -> bool {
+        match self {
+            Alu64Instr::Ctrl(instr) => instr.is_goto_target(),
+            Alu64Instr::Arithm(instr) => Instruction::<Id>::is_goto_target(instr),
+            Alu64Instr::Reserved(instr) => Instruction::<Id>::is_goto_target(instr),
+        }
+    }
+
+    fn local_goto_pos(&mut self) -> GotoTarget<'_> {
+        match self {
+            Alu64Instr::Ctrl(instr) => instr.local_goto_pos(),
+            Alu64Instr::Arithm(instr) => Instruction::<Id>::local_goto_pos(instr),
+            Alu64Instr::Reserved(instr) => Instruction::<Id>::local_goto_pos(instr),
+        }
+    }
+
+    fn remote_goto_pos(&mut self) -> Option<&mut Site<Id>> {
+        match self {
+            Alu64Instr::Ctrl(instr) => instr.remote_goto_pos(),
+            Alu64Instr::Arithm(instr) => Instruction::<Id>::remote_goto_pos(instr),
+            Alu64Instr::Reserved(instr) => Instruction::<Id>::remote_goto_pos(instr),
+        }
+    }
+
+    fn localize_goto(&self, pos: u16) -> Option<Self> {
+        match self {
+            Alu64Instr::Ctrl(instr) => instr.localize_goto(pos).map(Alu64Instr::Ctrl),
+            Alu64Instr::Arithm(_) | Alu64Instr::Reserved(_) => None,
+        }
+    }
+
+    fn flow_kind(&self) -> FlowKind {
+        match self {
+            Alu64Instr::Ctrl(instr) => instr.flow_kind(),
+            Alu64Instr::Arithm(instr) => Instruction::<Id>::flow_kind(instr),
+            Alu64Instr::Reserved(instr) => Instruction::<Id>::flow_kind(instr),
+        }
+    }
+
+    fn src_regs(&self) -> BTreeSet<RegA> {
+        match self {
+            Alu64Instr::Ctrl(_) | Alu64Instr::Reserved(_) => none!(),
+            Alu64Instr::Arithm(instr) => Instruction::<Id>::src_regs(instr),
+        }
+    }
+
+    fn dst_regs(&self) -> BTreeSet<RegA> {
+        match self {
+            Alu64Instr::Ctrl(_) | Alu64Instr::Reserved(_) => none!(),
+            Alu64Instr::Arithm(instr) => Instruction::<Id>::dst_regs(instr),
+        }
+    }
+
+    fn op_data_bytes(&self) -> u16 {
+        match self {
+            Alu64Instr::Ctrl(instr) => instr.op_data_bytes(),
+            Alu64Instr::Arithm(instr) => Instruction::<Id>::op_data_bytes(instr),
+            Alu64Instr::Reserved(instr) => Instruction::<Id>::op_data_bytes(instr),
+        }
+    }
+
+    fn ext_data_bytes(&self) -> u16 {
+        match self {
+            Alu64Instr::Ctrl(instr) => instr.ext_data_bytes(),
+            Alu64Instr::Arithm(instr) => Instruction::<Id>::ext_data_bytes(instr),
+            Alu64Instr::Reserved(instr) => Instruction::<Id>::ext_data_bytes(instr),
+        }
+    }
+
+    fn complexity_class(&self) -> ComplexityClass {
+        match self {
+            Alu64Instr::Ctrl(instr) => instr.complexity_class(),
+            Alu64Instr::Arithm(instr) => Instruction::<Id>::complexity_class(instr),
+            Alu64Instr::Reserved(instr) => Instruction::<Id>::complexity_class(instr),
+        }
+    }
+
+    fn exec<const CALL_STACK_SIZE: usize>(
+        &self,
+        site: Site<Id>,
+        core: &mut Core<Id, Self::Core, CALL_STACK_SIZE>,
+        _: &Self::Context<'_>,
+    ) -> ExecStep<Site<Id>> {
+        match self {
+            Alu64Instr::Ctrl(instr) => {
+                let mut subcore: Core<Id, NoExt, CALL_STACK_SIZE> = core.subcore();
+                let step = instr.exec(site, &mut subcore, &());
+                core.merge_subcore(subcore);
+                step
+            }
+            Alu64Instr::Arithm(instr) => instr.exec(site, core, &()),
+            Alu64Instr::Reserved(instr) => {
+                let mut subcore: Core<Id, NoExt, CALL_STACK_SIZE> = core.subcore();
+                let step = instr.exec(site, &mut subcore, &());
+                core.merge_subcore(subcore);
+                step
+            }
+        }
+    }
+}
+
+impl<Id: SiteId> Instruction<Id> for ArithmInstr {
+    const ISA_EXT: &'static [&'static str] = &["ALU64"];
+
+    type Core = Alu64Core;
+    type Context<'ctx> = ();
+
+    fn isa_ext_id(&self) -> Option<&'static str> { Some("ALU64") }
+
+    fn is_goto_target(&self) -> bool { false }
+
+    fn local_goto_pos(&mut self) -> GotoTarget<'_> { GotoTarget::None }
+
+    fn remote_goto_pos(&mut self) -> Option<&mut Site<Id>> { None }
+
+    fn flow_kind(&self) -> FlowKind { FlowKind::Continue }
+
+    fn src_regs(&self) -> BTreeSet<RegA> {
+        match *self {
+            ArithmInstr::Put { .. } | ArithmInstr::Data { .. } => none!(),
+            ArithmInstr::Mov { src, .. } => bset![src],
+            ArithmInstr::Add { dst, src, .. }
+            | ArithmInstr::Sub { dst, src, .. }
+            | ArithmInstr::Mul { dst, src, .. } => bset![dst, src],
+            ArithmInstr::Eq { src1, src2 } => bset![src1, src2],
+        }
+    }
+
+    fn dst_regs(&self) -> BTreeSet<RegA> {
+        match *self {
+            ArithmInstr::Put { dst, .. }
+            | ArithmInstr::Mov { dst, .. }
+            | ArithmInstr::Add { dst, .. }
+            | ArithmInstr::Sub { dst, .. }
+            | ArithmInstr::Mul { dst, .. }
+            | ArithmInstr::Data { dst, .. } => bset![dst],
+            ArithmInstr::Eq { .. } => none!(),
+        }
+    }
+
+    fn op_data_bytes(&self) -> u16 {
+        match self {
+            ArithmInstr::Put { .. } => 2,
+            ArithmInstr::Data { .. } => 4,
+            ArithmInstr::Mov { .. }
+            | ArithmInstr::Add { .. }
+            | ArithmInstr::Sub { .. }
+            | ArithmInstr::Mul { .. }
+            | ArithmInstr::Eq { .. } => 0,
+        }
+    }
+
+    fn ext_data_bytes(&self) -> u16 {
+        match self {
+            ArithmInstr::Put { .. } => 8,
+            ArithmInstr::Data { val, .. } => const_len(*val),
+            ArithmInstr::Mov { .. }
+            | ArithmInstr::Add { .. }
+            | ArithmInstr::Sub { .. }
+            | ArithmInstr::Mul { .. }
+            | ArithmInstr::Eq { .. } => 0,
+        }
+    }
+
+    fn complexity_class(&self) -> ComplexityClass {
+        match self {
+            ArithmInstr::Put { .. } | ArithmInstr::Mov { .. } | ArithmInstr::Data { .. } => {
+                ComplexityClass::Medium
+            }
+            ArithmInstr::Add { .. }
+            | ArithmInstr::Sub { .. }
+            | ArithmInstr::Mul { .. }
+            | ArithmInstr::Eq { .. } => ComplexityClass::Heavy,
+        }
+    }
+
+    fn exec<const CALL_STACK_SIZE: usize>(
+        &self,
+        _: Site<Id>,
+        core: &mut Core<Id, Self::Core, CALL_STACK_SIZE>,
+        _: &Self::Context<'_>,
+    ) -> ExecStep<Site<Id>> {
+        match *self {
+            ArithmInstr::Put { dst, val } | ArithmInstr::Data { dst, val } => core.set(dst, val),
+            ArithmInstr::Mov { dst, src } => {
+                let val = core.get(src);
+                core.put(dst, val);
+                if val.is_none() {
+                    core.set_failure(FailureReason::NoValue);
+                    return ExecStep::Fail;
+                }
+            }
+            ArithmInstr::Add { wrap, dst, src } => {
+                return arithm(core, wrap, dst, src, u64::overflowing_add)
+            }
+            ArithmInstr::Sub { wrap, dst, src } => {
+                return arithm(core, wrap, dst, src, u64::overflowing_sub)
+            }
+            ArithmInstr::Mul { wrap, dst, src } => {
+                return arithm(core, wrap, dst, src, u64::overflowing_mul)
+            }
+            ArithmInstr::Eq { src1, src2 } => {
+                let (Some(val1), Some(val2)) = (core.get(src1), core.get(src2)) else {
+                    core.set_co(Status::Fail);
+                    core.set_failure(FailureReason::NoValue);
+                    return ExecStep::Fail;
+                };
+                core.set_co(if val1 == val2 { Status::Ok } else { Status::Fail });
+            }
+        }
+        ExecStep::Next
+    }
+}
+
+impl<Id: SiteId> Instruction<Id> for SelectInstr {
+    const ISA_EXT: &'static [&'static str] = &["SEL64"];
+
+    type Core = Alu64Core;
+    type Context<'ctx> = ();
+
+    fn isa_ext_id(&self) -> Option<&'static str> { Some("SEL64") }
+
+    fn is_goto_target(&self) -> bool { false }
+
+    fn local_goto_pos(&mut self) -> GotoTarget<'_> { GotoTarget::None }
+
+    fn remote_goto_pos(&mut self) -> Option<&mut Site<Id>> { None }
+
+    fn flow_kind(&self) -> FlowKind { FlowKind::Continue }
+
+    fn src_regs(&self) -> BTreeSet<RegA> {
+        match *self {
+            SelectInstr::Sel { dst, src } => bset![dst, src],
+            SelectInstr::MovCo { .. } | SelectInstr::MovCk { .. } => none!(),
+        }
+    }
+
+    fn dst_regs(&self) -> BTreeSet<RegA> {
+        match *self {
+            SelectInstr::Sel { dst, .. }
+            | SelectInstr::MovCo { dst }
+            | SelectInstr::MovCk { dst } => bset![dst],
+        }
+    }
+
+    fn op_data_bytes(&self) -> u16 { 0 }
+
+    fn ext_data_bytes(&self) -> u16 { 0 }
+
+    fn complexity_class(&self) -> ComplexityClass {
+        match self {
+            SelectInstr::Sel { .. } => ComplexityClass::Heavy,
+            SelectInstr::MovCo { .. } | SelectInstr::MovCk { .. } => ComplexityClass::Medium,
+        }
+    }
+
+    fn exec<const CALL_STACK_SIZE: usize>(
+        &self,
+        _: Site<Id>,
+        core: &mut Core<Id, Self::Core, CALL_STACK_SIZE>,
+        _: &Self::Context<'_>,
+    ) -> ExecStep<Site<Id>> {
+        match *self {
+            SelectInstr::Sel { dst, src } => {
+                let val = if core.co().is_ok() { core.get(src) } else { core.get(dst) };
+                core.put(dst, val);
+                if val.is_none() {
+                    core.set_failure(FailureReason::NoValue);
+                    return ExecStep::Fail;
+                }
+            }
+            SelectInstr::MovCo { dst } => core.set(dst, !core.co().is_ok() as u64),
+            SelectInstr::MovCk { dst } => core.set(dst, !core.ck().is_ok() as u64),
+        }
+        ExecStep::Next
+    }
+}
+
+impl<Id: SiteId> Instruction<Id> for TableInstr {
+    const ISA_EXT: &'static [&'static str] = &["JMPTBL"];
+
+    type Core = Alu64Core;
+    type Context<'ctx> = ();
+
+    fn isa_ext_id(&self) -> Option<&'static str> { Some("JMPTBL") }
+
+    fn is_goto_target(&self) -> bool { false }
+
+    fn local_goto_pos(&mut self) -> GotoTarget<'_> {
+        match self {
+            TableInstr::Jmp { table, .. } => GotoTarget::Table(table),
+        }
+    }
+
+    fn remote_goto_pos(&mut self) -> Option<&mut Site<Id>> { None }
+
+    fn flow_kind(&self) -> FlowKind { FlowKind::Branch }
+
+    fn src_regs(&self) -> BTreeSet<RegA> {
+        match self {
+            TableInstr::Jmp { idx, .. } => bset![*idx],
+        }
+    }
+
+    fn dst_regs(&self) -> BTreeSet<RegA> { none!() }
+
+    fn op_data_bytes(&self) -> u16 { 4 }
+
+    fn ext_data_bytes(&self) -> u16 {
+        match self {
+            TableInstr::Jmp { table, .. } => (table.len() as u16).saturating_mul(2),
+        }
+    }
+
+    fn complexity_class(&self) -> ComplexityClass { ComplexityClass::Heavy }
+
+    fn exec<const CALL_STACK_SIZE: usize>(
+        &self,
+        _: Site<Id>,
+        core: &mut Core<Id, Self::Core, CALL_STACK_SIZE>,
+        _: &Self::Context<'_>,
+    ) -> ExecStep<Site<Id>> {
+        match self {
+            TableInstr::Jmp { idx, table } => {
+                let Some(val) = core.get(*idx) else {
+                    core.set_failure(FailureReason::NoValue);
+                    return ExecStep::Fail;
+                };
+                match usize::try_from(val).ok().and_then(|val| table.get(val)) {
+                    Some(pos) => ExecStep::Jump(*pos),
+                    None => {
+                        core.set_failure(FailureReason::Custom(
+                            IsaId::from("JMPTBL"),
+                            TableInstr::FAIL_OUT_OF_TABLE,
+                        ));
+                        ExecStep::Fail
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn arithm<Id: SiteId, const CALL_STACK_SIZE: usize>(
+    core: &mut Core<Id, Alu64Core, CALL_STACK_SIZE>,
+    wrap: bool,
+    dst: RegA,
+    src: RegA,
+    op: fn(u64, u64) -> (u64, bool),
+) -> ExecStep<Site<Id>> {
+    let (Some(val1), Some(val2)) = (core.get(dst), core.get(src)) else {
+        core.clr(dst);
+        core.set_failure(FailureReason::NoValue);
+        return ExecStep::Fail;
+    };
+    let (res, overflow) = op(val1, val2);
+    core.set_co(if overflow { Status::Fail } else { Status::Ok });
+    if overflow && !wrap {
+        core.clr(dst);
+        core.set_failure(FailureReason::Overflow);
+        return ExecStep::Fail;
+    }
+    core.set(dst, res);
+    ExecStep::Next
+}
+
+#[cfg(test)]
+mod test {
+    #![cfg_attr(coverage_nightly, coverage(off))]
+
+    use super::*;
+    use crate::isa::CtrlInstr;
+    use crate::{IsaId, LibId};
+
+    fn site() -> Site<LibId> { Site::new(LibId::from([0xA5u8; 32]), 0) }
+
+    fn exec(core: &mut Core<LibId, Alu64Core>, instr: ArithmInstr) -> ExecStep<Site<LibId>> {
+        Alu64Instr::from(instr).exec(site(), core, &())
+    }
+
+    fn core_with(a: u64, b: u64) -> Core<LibId, Alu64Core> {
+        let mut core = Core::new();
+        core.set(RegA::A0, a);
+        core.set(RegA::A1, b);
+        core
+    }
+
+    #[test]
+    fn put_mov() {
+        let mut core = Core::<LibId, Alu64Core>::new();
+        assert_eq!(exec(&mut core, ArithmInstr::Put { dst: RegA::A0, val: 42 }), ExecStep::Next);
+        assert_eq!(
+            exec(&mut core, ArithmInstr::Mov { dst: RegA::A1, src: RegA::A0 }),
+            ExecStep::Next
+        );
+        assert_eq!(core.get(RegA::A1), Some(42));
+        assert_eq!(
+            exec(&mut core, ArithmInstr::Mov { dst: RegA::A1, src: RegA::A2 }),
+            ExecStep::Fail
+        );
+        assert_eq!(core.get(RegA::A1), None);
+        assert!(core.fail_ck());
+        assert_eq!(core.last_failure(), Some(&FailureReason::NoValue));
+    }
+
+    #[test]
+    fn data() {
+        let mut core = Core::<LibId, Alu64Core>::new();
+        let instr = ArithmInstr::Data { dst: RegA::A3, val: 0x1234 };
+        assert_eq!(exec(&mut core, instr), ExecStep::Next);
+        assert_eq!(core.get(RegA::A3), Some(0x1234));
+
+        for (val, len) in [(0, 1), (0xFF, 1), (0x100, 2), (u32::MAX as u64, 4), (u64::MAX, 8)] {
+            let instr = Alu64Instr::<LibId>::from(ArithmInstr::Data { dst: RegA::A0, val });
+            assert_eq!(instr.ext_data_bytes(), len, "{instr}");
+            assert_eq!(instr.base_complexity(), (4 + 8 + len as u64 * 2) * 8000, "{instr}");
+            assert_eq!(instr.complexity(), 10_000, "{instr}");
+        }
+    }
+
+    #[test]
+    fn checked() {
+        let (dst, src) = (RegA::A0, RegA::A1);
+        let cases = [
+            (ArithmInstr::Add { wrap: false, dst, src }, 2, 3, Some(5)),
+            (ArithmInstr::Add { wrap: false, dst, src }, u64::MAX, 1, None),
+            (ArithmInstr::Sub { wrap: false, dst, src }, 3, 2, Some(1)),
+            (ArithmInstr::Sub { wrap: false, dst, src }, 2, 3, None),
+            (ArithmInstr::Mul { wrap: false, dst, src }, 3, 4, Some(12)),
+            (ArithmInstr::Mul { wrap: false, dst, src }, u64::MAX, 2, None),
+        ];
+        for (instr, a, b, res) in cases {
+            let mut core = core_with(a, b);
+            core.set_co(Status::Fail);
+            let step = exec(&mut core, instr);
+            assert_eq!(core.get(dst), res, "{instr}");
+            assert_eq!(core.get(src), Some(b), "{instr}");
+            if res.is_some() {
+                assert_eq!(step, ExecStep::Next, "{instr}");
+                assert_eq!(core.co(), Status::Ok, "{instr}");
+            } else {
+                assert_eq!(step, ExecStep::Fail, "{instr}");
+                assert_eq!(core.co(), Status::Fail, "{instr}");
+                assert!(core.fail_ck());
+                assert_eq!(core.last_failure(), Some(&FailureReason::Overflow), "{instr}");
+            }
+        }
+    }
+
+    #[test]
+    fn wrapping() {
+        let (dst, src) = (RegA::A0, RegA::A1);
+        let cases = [
+            (ArithmInstr::Add { wrap: true, dst, src }, 2, 3, 5, Status::Ok),
+            (ArithmInstr::Add { wrap: true, dst, src }, u64::MAX, 2, 1, Status::Fail),
+            (ArithmInstr::Sub { wrap: true, dst, src }, 2, 3, u64::MAX, Status::Fail),
+            (ArithmInstr::Mul { wrap: true, dst, src }, 1 << 63, 2, 0, Status::Fail),
+            (ArithmInstr::Mul { wrap: true, dst, src }, 1 << 62, 2, 1 << 63, Status::Ok),
+        ];
+        for (instr, a, b, res, co) in cases {
+            let mut core = core_with(a, b);
+            assert_eq!(exec(&mut core, instr), ExecStep::Next, "{instr}");
+            assert_eq!(core.get(dst), Some(res), "{instr}");
+            assert_eq!(core.co(), co, "{instr}");
+        }
+    }
+
+    #[test]
+    fn unset_operands() {
+        let mut core = Core::<LibId, Alu64Core>::new();
+        core.set(RegA::A0, 1);
+        let instr = ArithmInstr::Add { wrap: true, dst: RegA::A0, src: RegA::A1 };
+        assert_eq!(exec(&mut core, instr), ExecStep::Fail);
+        assert_eq!(core.get(RegA::A0), None);
+        assert_eq!(core.co(), Status::Ok);
+    }
+
+    #[test]
+    fn eq() {
+        let instr = ArithmInstr::Eq { src1: RegA::A0, src2: RegA::A1 };
+        let mut core = core_with(7, 7);
+        assert_eq!(exec(&mut core, instr), ExecStep::Next);
+        assert_eq!(core.co(), Status::Ok);
+        let mut core = core_with(7, 8);
+        assert_eq!(exec(&mut core, instr), ExecStep::Next);
+        assert_eq!(core.co(), Status::Fail);
+        core.clr(RegA::A1);
+        core.set_co(Status::Ok);
+        assert_eq!(exec(&mut core, instr), ExecStep::Fail);
+        assert_eq!(core.co(), Status::Fail);
+    }
+
+    fn select(core: &mut Core<LibId, Alu64Core>, instr: SelectInstr) -> ExecStep<Site<LibId>> {
+        Instruction::<LibId>::exec(&instr, site(), core, &())
+    }
+
+    #[test]
+    fn sel() {
+        let instr = SelectInstr::Sel { dst: RegA::A0, src: RegA::A1 };
+        for (co, res) in [(Status::Ok, 2), (Status::Fail, 1)] {
+            let mut core = core_with(1, 2);
+            core.set_co(co);
+            assert_eq!(select(&mut core, instr), ExecStep::Next, "{co}");
+            assert_eq!(core.get(RegA::A0), Some(res), "{co}");
+            assert_eq!(core.get(RegA::A1), Some(2), "{co}");
+            assert_eq!(core.co(), co, "{co}");
+        }
+    }
+
+    #[test]
+    fn sel_unset() {
+        let cases = [
+            // the register which is not selected doesn't matter
+            (Status::Ok, Some(1), None, None),
+            (Status::Ok, None, Some(2), Some(2)),
+            (Status::Fail, Some(1), None, Some(1)),
+            (Status::Fail, None, Some(2), None),
+        ];
+        let instr = SelectInstr::Sel { dst: RegA::A0, src: RegA::A1 };
+        for (co, a, b, res) in cases {
+            let mut core = Core::<LibId, Alu64Core>::new();
+            core.put(RegA::A0, a);
+            core.put(RegA::A1, b);
+            core.set_co(co);
+            let step = select(&mut core, instr);
+            assert_eq!(core.get(RegA::A0), res, "{co} {a:?} {b:?}");
+            assert_eq!(core.get(RegA::A1), b, "{co} {a:?} {b:?}");
+            let expected = if res.is_some() { ExecStep::Next } else { ExecStep::Fail };
+            assert_eq!(step, expected, "{co} {a:?} {b:?}");
+        }
+    }
+
+    #[test]
+    fn mov_flags() {
+        let mut core = Core::<LibId, Alu64Core>::new();
+        assert_eq!(select(&mut core, SelectInstr::MovCo { dst: RegA::A0 }), ExecStep::Next);
+        assert_eq!(select(&mut core, SelectInstr::MovCk { dst: RegA::A1 }), ExecStep::Next);
+        assert_eq!(core.get(RegA::A0), Some(0));
+        assert_eq!(core.get(RegA::A1), Some(0));
+
+        core.set_co(Status::Fail);
+        assert_eq!(select(&mut core, SelectInstr::MovCo { dst: RegA::A0 }), ExecStep::Next);
+        assert_eq!(select(&mut core, SelectInstr::MovCk { dst: RegA::A1 }), ExecStep::Next);
+        assert_eq!(core.get(RegA::A0), Some(1));
+        assert_eq!(core.get(RegA::A1), Some(0));
+
+        let _ = core.fail_ck();
+        assert_eq!(select(&mut core, SelectInstr::MovCk { dst: RegA::A1 }), ExecStep::Next);
+        assert_eq!(core.get(RegA::A1), Some(1));
+    }
+
+    #[test]
+    fn select_regs() {
+        let sel = SelectInstr::Sel { dst: RegA::A2, src: RegA::A3 };
+        assert_eq!(Instruction::<LibId>::src_regs(&sel), bset![RegA::A2, RegA::A3]);
+        assert_eq!(Instruction::<LibId>::dst_regs(&sel), bset![RegA::A2]);
+        for instr in [SelectInstr::MovCo { dst: RegA::A4 }, SelectInstr::MovCk { dst: RegA::A4 }] {
+            assert_eq!(Instruction::<LibId>::src_regs(&instr), none!());
+            assert_eq!(Instruction::<LibId>::dst_regs(&instr), bset![RegA::A4]);
+        }
+        assert_eq!(
+            <SelectInstr as Instruction<LibId>>::isa_ext(),
+            IsaId::canonical_set([IsaId::from("SEL64")])
+        );
+    }
+
+    #[test]
+    fn jmp_table() {
+        let instr = TableInstr::Jmp { idx: RegA::A0, table: vec![0x10, 0x20, 0x30] };
+        let out_of_table =
+            FailureReason::Custom(IsaId::from("JMPTBL"), TableInstr::FAIL_OUT_OF_TABLE);
+        let cases = [
+            (Some(0), ExecStep::Jump(0x10), None),
+            (Some(2), ExecStep::Jump(0x30), None),
+            (Some(3), ExecStep::Fail, Some(out_of_table.clone())),
+            (Some(u64::MAX), ExecStep::Fail, Some(out_of_table)),
+            (None, ExecStep::Fail, Some(FailureReason::NoValue)),
+        ];
+        for (idx, step, reason) in cases {
+            let mut core = Core::<LibId, Alu64Core>::new();
+            core.put(RegA::A0, idx);
+            assert_eq!(Instruction::<LibId>::exec(&instr, site(), &mut core, &()), step, "{idx:?}");
+            assert_eq!(core.get(RegA::A0), idx, "{idx:?}");
+            if step == ExecStep::Fail {
+                assert!(core.fail_ck());
+            }
+            assert_eq!(core.last_failure(), reason.as_ref(), "{idx:?}");
+        }
+
+        let empty = TableInstr::Jmp { idx: RegA::A0, table: vec![] };
+        let mut core = Core::<LibId, Alu64Core>::new();
+        core.put(RegA::A0, Some(0));
+        assert_eq!(Instruction::<LibId>::exec(&empty, site(), &mut core, &()), ExecStep::Fail);
+        assert!(core.fail_ck());
+        assert_eq!(
+            core.last_failure(),
+            Some(&FailureReason::Custom(IsaId::from("JMPTBL"), TableInstr::FAIL_OUT_OF_TABLE))
+        );
+    }
+
+    #[test]
+    fn jmp_table_props() {
+        let mut instr = TableInstr::Jmp { idx: RegA::A7, table: vec![0x10, 0x20] };
+        assert_eq!(Instruction::<LibId>::src_regs(&instr), bset![RegA::A7]);
+        assert_eq!(Instruction::<LibId>::dst_regs(&instr), none!());
+        assert_eq!(Instruction::<LibId>::flow_kind(&instr), FlowKind::Branch);
+        assert_eq!(Instruction::<LibId>::ext_data_bytes(&instr), 4);
+        let GotoTarget::Table(table) = Instruction::<LibId>::local_goto_pos(&mut instr) else {
+            panic!("jump table targets are not exposed");
+        };
+        table[1] = 0x40;
+        assert_eq!(instr, TableInstr::Jmp { idx: RegA::A7, table: vec![0x10, 0x40] });
+        assert_eq!(
+            <TableInstr as Instruction<LibId>>::isa_ext(),
+            IsaId::canonical_set([IsaId::from("JMPTBL")])
+        );
+    }
+
+    #[test]
+    fn ctrl_subcore() {
+        let mut core = core_with(1, 2);
+        core.set_co(Status::Fail);
+        let step = Alu64Instr::Ctrl(CtrlInstr::NotCo).exec(site(), &mut core, &());
+        assert_eq!(step, ExecStep::Next);
+        assert_eq!(core.co(), Status::Ok);
+        assert_eq!(core.get(RegA::A0), Some(1));
+        assert_eq!(core.get(RegA::A1), Some(2));
+    }
+
+    #[test]
+    fn isa_ext() {
+        let instr = Alu64Instr::<LibId>::from(ArithmInstr::Eq { src1: RegA::A0, src2: RegA::A1 });
+        assert_eq!(Alu64Instr::<LibId>::isa_ext(), IsaId::canonical_set([IsaId::from("ALU64")]));
+        assert_eq!(instr.isa_ext_id(), Some("ALU64"));
+        assert_eq!(Alu64Instr::<LibId>::from(CtrlInstr::Nop).isa_ext_id(), None);
+        assert_eq!(instr.src_regs(), bset![RegA::A0, RegA::A1]);
+        assert_eq!(instr.dst_regs(), none!());
+        assert_eq!(instr.complexity(), 20_000);
+    }
+}
@@ -0,0 +1,242 @@
+// Reference rust implementation of AluVM (arithmetic logic unit virtual machine).
+// To find more on AluVM please check <https://aluvm.org>
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Designed in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+// Written in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+//
+// Copyright (C) 2021-2024 LNP/BP Standards Association, Switzerland.
+// Copyright (C) 2024-2025 Laboratories for Ubiquitous Deterministic Computing (UBIDECO),
+//                         Institute for Distributed and Cognitive Systems (InDCS), Switzerland.
+// Copyright (C) 2021-2025 Dr Maxim Orlovsky.
+// All rights under the above copyrights are reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use this file except
+// in compliance with the License. You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed under the License
+// is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express
+// or implied. See the License for the specific language governing permissions and limitations under
+// the License.
+
+use alloc::vec::Vec;
+use core::fmt::{self, Display, Formatter};
+
+use super::RegA;
+use crate::core::SiteId;
+use crate::isa::{CtrlInstr, ReservedInstr};
+
+/// Arithmetic instructions over 64-bit `A`-registers.
+///
+/// Binary operations take the first operand from the destination register and put the result
+/// back into it. If any of the operand registers is not set, the destination register is cleared
+/// and `CK` is set to a failure.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum ArithmInstr {
+    /// Put an immediate value into a register.
+    Put {
+        /** Destination register */
+        dst: RegA,
+        /** Value to put */
+        val: u64,
+    },
+
+    /// Copy a value from one register to another.
+    ///
+    /// If the source register is not set, clears the destination register and sets `CK` to a
+    /// failure.
+    Mov {
+        /** Destination register */
+        dst: RegA,
+        /** Source register */
+        src: RegA,
+    },
+
+    /// Add the source register value to the destination register.
+    ///
+    /// Sets `CO` to a failed state on overflow, and to a non-failed state otherwise. In a checked
+    /// (non-wrapping) mode, the overflow also clears the destination register and sets `CK` to a
+    /// failure.
+    Add {
+        /** Whether to wrap the result on overflow */
+        wrap: bool,
+        /** Destination register, which is also the first operand */
+        dst: RegA,
+        /** Source register */
+        src: RegA,
+    },
+
+    /// Subtract the source register value from the destination register.
+    ///
+    /// Handles underflow in the same way as [`ArithmInstr::Add`] handles overflow.
+    Sub {
+        /** Whether to wrap the result on underflow */
+        wrap: bool,
+        /** Destination register, which is also the first operand */
+        dst: RegA,
+        /** Source register */
+        src: RegA,
+    },
+
+    /// Multiply the destination register by the source register value.
+    ///
+    /// Handles overflow in the same way as [`ArithmInstr::Add`].
+    Mul {
+        /** Whether to wrap the result on overflow */
+        wrap: bool,
+        /** Destination register, which is also the first operand */
+        dst: RegA,
+        /** Source register */
+        src: RegA,
+    },
+
+    /// Compare values of two registers, setting `CO` to a non-failed state if they are equal, and
+    /// to a failed state otherwise.
+    ///
+    /// If any of the registers is not set, sets both `CO` and `CK` to a failure.
+    Eq {
+        /** First register to compare */
+        src1: RegA,
+        /** Second register to compare */
+        src2: RegA,
+    },
+
+    /// Load a constant from the library data segment into a register.
+    ///
+    /// Unlike [`ArithmInstr::Put`], which always stores the full 8-byte value, the constant is
+    /// kept in the data segment in its minimal little-endian form, and the instruction refers to
+    /// it by an offset-length pair. Thus, small constants take less space, and the complexity of
+    /// the instruction accounts only for the bytes actually referenced.
+    Data {
+        /** Destination register */
+        dst: RegA,
+        /** Constant value */
+        val: u64,
+    },
+}
+
+impl Display for ArithmInstr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let op = |name: &'static str, wrapped: &'static str, wrap: bool| {
+            if wrap {
+                wrapped
+            } else {
+                name
+            }
+        };
+        match *self {
+            ArithmInstr::Put { dst, val } => write!(f, "put     {dst}, {val}"),
+            ArithmInstr::Mov { dst, src } => write!(f, "mov     {dst}, {src}"),
+            ArithmInstr::Add { wrap, dst, src } => {
+                write!(f, "{:<8}{dst}, {src}", op("add", "add.w", wrap))
+            }
+            ArithmInstr::Sub { wrap, dst, src } => {
+                write!(f, "{:<8}{dst}, {src}", op("sub", "sub.w", wrap))
+            }
+            ArithmInstr::Mul { wrap, dst, src } => {
+                write!(f, "{:<8}{dst}, {src}", op("mul", "mul.w", wrap))
+            }
+            ArithmInstr::Eq { src1, src2 } => write!(f, "eq      {src1}, {src2}"),
+            ArithmInstr::Data { dst, val } => write!(f, "data    {dst}, {val}"),
+        }
+    }
+}
+
+/// Conditional-select instructions over 64-bit `A`-registers (`SEL64` ISA extension).
+///
+/// The instructions turn the `CO` and `CK` flags into register values without branching, such
+/// that data-dependent decisions don't require conditional jumps. The extension uses the same
+/// register file as `ALU64` and can be combined with it using [`crate::isa::MultiIsa`].
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum SelectInstr {
+    /// Copy the source register value to the destination register if `CO` is in a non-failed
+    /// state, keeping the destination register value otherwise.
+    ///
+    /// Thus, the destination register must be initialized with the value selected on a `CO`
+    /// failure. If the selected value is not set, clears the destination register and sets `CK`
+    /// to a failure.
+    Sel {
+        /** Destination register, which is also the value selected on a `CO` failure */
+        dst: RegA,
+        /** Source register, which value is selected if `CO` is not failed */
+        src: RegA,
+    },
+
+    /// Put `1` into a register if `CO` is in a failed state, and `0` otherwise.
+    MovCo {
+        /** Destination register */
+        dst: RegA,
+    },
+
+    /// Put `1` into a register if `CK` is in a failed state, and `0` otherwise.
+    MovCk {
+        /** Destination register */
+        dst: RegA,
+    },
+}
+
+impl Display for SelectInstr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match *self {
+            SelectInstr::Sel { dst, src } => write!(f, "sel     {dst}, {src}"),
+            SelectInstr::MovCo { dst } => write!(f, "mov     {dst}, CO"),
+            SelectInstr::MovCk { dst } => write!(f, "mov     {dst}, CK"),
+        }
+    }
+}
+
+/// Computed jump instructions (`JMPTBL` ISA extension).
+///
+/// A jump table replaces a chain of conditional jumps in `switch`-like code and interpreter
+/// dispatch loops with a single instruction, taking the index of the jump target from an
+/// `A`-register. The table of the absolute target offsets is stored in the data segment. The
+/// extension uses the same register file as `ALU64` and can be combined with it using
+/// [`crate::isa::MultiIsa`].
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub enum TableInstr {
+    /// Jump to the table entry selected by the register value.
+    ///
+    /// If the register is not set or its value is out of the table bounds, sets `CK` to a failure
+    /// and proceeds to the next instruction. In the latter case, the failure reason is
+    /// [`TableInstr::FAIL_OUT_OF_TABLE`]. Thus, a jump through an empty table always fails.
+    Jmp {
+        /** Register holding the index of the table entry */
+        idx: RegA,
+        /** Absolute code offsets of the jump targets */
+        table: Vec<u16>,
+    },
+}
+
+impl TableInstr {
+    /// Code of the [`crate::FailureReason::Custom`] failure when the table index is out of the
+    /// table bounds.
+    pub const FAIL_OUT_OF_TABLE: u16 = 1;
+}
+
+impl Display for TableInstr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            TableInstr::Jmp { idx, table } => write!(f, "jmp     {idx}, {table:?}"),
+        }
+    }
+}
+
+/// Instruction set composed of the control flow instructions and the `ALU64` ISA extension.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Display, From)]
+#[display(inner)]
+pub enum Alu64Instr<Id: SiteId> {
+    /// Control flow instructions.
+    #[from]
+    Ctrl(CtrlInstr<Id>),
+
+    /// Arithmetic instructions.
+    #[from]
+    Arithm(ArithmInstr),
+
+    /// Reserved instruction for future use.
+    #[from]
+    Reserved(ReservedInstr),
+}
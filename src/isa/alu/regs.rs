@@ -0,0 +1,230 @@
+// Reference rust implementation of AluVM (arithmetic logic unit virtual machine).
+// To find more on AluVM please check <https://aluvm.org>
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Designed in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+// Written in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+//
+// Copyright (C) 2021-2024 LNP/BP Standards Association, Switzerland.
+// Copyright (C) 2024-2025 Laboratories for Ubiquitous Deterministic Computing (UBIDECO),
+//                         Institute for Distributed and Cognitive Systems (InDCS), Switzerland.
+// Copyright (C) 2021-2025 Dr Maxim Orlovsky.
+// All rights under the above copyrights are reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use this file except
+// in compliance with the License. You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed under the License
+// is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express
+// or implied. See the License for the specific language governing permissions and limitations under
+// the License.
+
+use core::fmt::{self, Display, Formatter};
+
+use amplify::num::u4;
+
+use crate::core::{CoreExt, NoExt, NoRegs, Register, Supercore};
+
+/// General-purpose 64-bit arithmetic registers provided by the `ALU64` ISA extension.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug)]
+#[repr(u8)]
+#[allow(missing_docs)]
+pub enum RegA {
+    A0 = 0,
+    A1,
+    A2,
+    A3,
+    A4,
+    A5,
+    A6,
+    A7,
+    A8,
+    A9,
+    A10,
+    A11,
+    A12,
+    A13,
+    A14,
+    A15,
+}
+
+impl RegA {
+    /// All `A`-registers, ordered by their index.
+    pub const ALL: [RegA; 16] = [
+        RegA::A0,
+        RegA::A1,
+        RegA::A2,
+        RegA::A3,
+        RegA::A4,
+        RegA::A5,
+        RegA::A6,
+        RegA::A7,
+        RegA::A8,
+        RegA::A9,
+        RegA::A10,
+        RegA::A11,
+        RegA::A12,
+        RegA::A13,
+        RegA::A14,
+        RegA::A15,
+    ];
+
+    /// Returns the register with the given index, or `None` if the index is not less than 16.
+    ///
+    /// The [`TryFrom`] implementation does the same, reporting the out-of-range index as
+    /// [`RegIndexError`]; the index is never wrapped around.
+    pub const fn with(index: u8) -> Option<Self> {
+        if index as usize >= Self::ALL.len() {
+            return None;
+        }
+        Some(Self::ALL[index as usize])
+    }
+
+    /// Index of the register (from 0 to 15).
+    pub const fn index(self) -> u8 { self as u8 }
+}
+
+impl From<u4> for RegA {
+    fn from(index: u4) -> Self { Self::ALL[index.to_u8() as usize] }
+}
+
+impl TryFrom<u8> for RegA {
+    type Error = RegIndexError;
+
+    fn try_from(index: u8) -> Result<Self, Self::Error> {
+        Self::with(index).ok_or(RegIndexError(index))
+    }
+}
+
+/// Error indicating that a register index is out of the range of the register set.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Display, Error)]
+#[display("register index {0} is out of range")]
+pub struct RegIndexError(pub u8);
+
+impl Display for RegA {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result { write!(f, "A{}", self.index()) }
+}
+
+impl Register for RegA {
+    type Value = u64;
+
+    fn bytes(self) -> u16 { 8 }
+
+    fn enumerate() -> impl Iterator<Item = Self> { Self::ALL.into_iter() }
+}
+
+impl From<NoRegs> for RegA {
+    fn from(regs: NoRegs) -> Self { match regs {} }
+}
+
+/// Extension of the AluVM core with the `A`-registers used by the `ALU64` ISA extension.
+///
+/// All registers are initialized to `None`.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct Alu64Core {
+    a: [Option<u64>; 16],
+}
+
+impl CoreExt for Alu64Core {
+    type Reg = RegA;
+    type Config = ();
+    type Snapshot = [Option<u64>; 16];
+
+    fn with(_config: Self::Config) -> Self { default!() }
+
+    fn get(&self, reg: Self::Reg) -> Option<u64> { self.a[reg.index() as usize] }
+
+    fn clr(&mut self, reg: Self::Reg) { self.a[reg.index() as usize] = None }
+
+    fn put(&mut self, reg: Self::Reg, val: Option<u64>) { self.a[reg.index() as usize] = val }
+
+    fn reset(&mut self) { *self = default!() }
+
+    fn snapshot(&self) -> Self::Snapshot { self.a }
+
+    fn restore(&mut self, snapshot: Self::Snapshot) { self.a = snapshot }
+
+    fn set_arbitrary(&mut self, bytes: &mut impl Iterator<Item = u8>) {
+        for reg in &mut self.a {
+            *reg = match bytes.next() {
+                Some(flag) if flag & 1 == 1 => {
+                    let mut buf = [0u8; 8];
+                    for byte in &mut buf {
+                        *byte = bytes.next().unwrap_or_default();
+                    }
+                    Some(u64::from_le_bytes(buf))
+                }
+                _ => None,
+            };
+        }
+    }
+}
+
+impl Supercore<NoExt> for Alu64Core {
+    fn subcore(&self) -> NoExt { NoExt }
+
+    fn merge_subcore(&mut self, _subcore: NoExt) {}
+}
+
+/// Allows ISA extensions sharing the `A`-registers (like `ALU64` and `SEL64`) to be combined with
+/// [`crate::isa::MultiIsa`].
+impl Supercore<Alu64Core> for Alu64Core {
+    fn subcore(&self) -> Alu64Core { *self }
+
+    fn merge_subcore(&mut self, subcore: Alu64Core) { *self = subcore }
+}
+
+#[cfg(test)]
+mod test {
+    #![cfg_attr(coverage_nightly, coverage(off))]
+
+    use super::*;
+
+    #[test]
+    fn reg_index() {
+        for (index, reg) in RegA::ALL.into_iter().enumerate() {
+            assert_eq!(reg.index() as usize, index);
+            assert_eq!(RegA::with(index as u8), Some(reg));
+            assert_eq!(RegA::from(u4::with(index as u8)), reg);
+            assert_eq!(reg.to_string(), format!("A{index}"));
+        }
+        assert_eq!(RegA::with(16), None);
+    }
+
+    #[test]
+    fn reg_index_bounds() {
+        assert_eq!(RegA::try_from(0), Ok(RegA::A0));
+        assert_eq!(RegA::try_from(15), Ok(RegA::A15));
+        for index in [16u8, 17, 31, 32, 255] {
+            assert_eq!(RegA::with(index), None);
+            assert_eq!(RegA::try_from(index), Err(RegIndexError(index)));
+        }
+        assert_eq!(RegIndexError(16).to_string(), "register index 16 is out of range");
+        for index in 0..16 {
+            assert_eq!(RegA::from(u4::with(index)).index(), index);
+        }
+    }
+
+    #[test]
+    fn core_ext() {
+        let mut core = Alu64Core::with(());
+        assert_eq!(core.get(RegA::A3), None);
+        core.set(RegA::A3, 7);
+        assert_eq!(core.get(RegA::A3), Some(7));
+        core.clr(RegA::A3);
+        assert_eq!(core.get(RegA::A3), None);
+        core.put(RegA::A15, Some(u64::MAX));
+        core.reset();
+        assert_eq!(core, Alu64Core::default());
+
+        let mut bytes = [1u8, 1, 0, 0, 0, 0, 0, 0, 0, 0, 1, 2].into_iter();
+        core.set_arbitrary(&mut bytes);
+        assert_eq!(core.get(RegA::A0), Some(1));
+        assert_eq!(core.get(RegA::A1), None);
+        assert_eq!(core.get(RegA::A2), Some(2));
+        assert_eq!(core.get(RegA::A3), None);
+    }
+}
@@ -22,14 +22,20 @@
 // or implied. See the License for the specific language governing permissions and limitations under
 // the License.
 
-use core::fmt::Debug;
+use alloc::string::{String, ToString};
+use core::fmt::{self, Debug, Display, Formatter};
+use core::str::FromStr;
 
+use amplify::confinement::TinyOrdSet;
 use strict_encoding::stl::AlphaCapsNum;
-use strict_encoding::{RString, StrictDumb};
+use strict_encoding::{
+    DecodeError, RString, StrictDecode, StrictDumb, StrictEncode, StrictType, TypeName, TypedRead,
+    TypedWrite,
+};
 
 use super::CtrlInstr;
 use crate::core::SiteId;
-use crate::LIB_NAME_ALUVM;
+use crate::{LibId, LIB_NAME_ALUVM};
 
 /// Maximal length of the ISA identifier.
 pub const ISA_ID_MAX_LEN: usize = 16;
@@ -64,15 +70,204 @@ impl From<&'static str> for IsaId {
     fn from(id: &'static str) -> Self { Self(RString::from(id)) }
 }
 
+impl IsaId {
+    /// Constructs a set of ISA extension identifiers in a canonical form.
+    ///
+    /// The canonical form is lexicographically ordered and contains no duplicates: duplicated
+    /// identifiers are merged silently. Thus, the set, and its strict encoding, doesn't depend on
+    /// the order and the number of times the identifiers are provided.
+    ///
+    /// # Panics
+    ///
+    /// If the number of distinct identifiers exceeds 255. Use [`IsaSeg::with`] to handle this
+    /// case as an error.
+    pub fn canonical_set(ids: impl IntoIterator<Item = IsaId>) -> IsaSeg {
+        IsaSeg(TinyOrdSet::from_iter_checked(ids))
+    }
+}
+
+/// Errors constructing an ISA extension segment ([`IsaSeg`]).
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum IsaSegError {
+    /// invalid ISA extension identifier '{0}', which must consist of 1 to 16 ASCII letters and
+    /// digits.
+    InvalidId(String),
+
+    /// the number of ISA extensions exceeds 255.
+    TooMany,
+}
+
+/// Set of ISA extensions, like the ones required by a library to execute its code (see
+/// [`crate::Lib::isae`]) or the ones provided by an instruction set (see
+/// [`crate::isa::Instruction::isa_ext`]).
+///
+/// The set is always in the canonical form (see [`IsaId::canonical_set`]), thus two sets are equal
+/// whenever they consist of the same identifiers, irrespective of their order. The set is
+/// displayed and parsed as a space-separated list of the identifiers.
+#[derive(Wrapper, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Default, From)]
+#[wrapper(Deref)]
+#[cfg_attr(feature = "serde", derive(Serialize), serde(transparent))]
+pub struct IsaSeg(TinyOrdSet<IsaId>);
+
+impl IsaSeg {
+    /// Constructs an empty set.
+    pub fn new() -> Self { default!() }
+
+    /// Constructs a set from the identifiers, merging the duplicates.
+    ///
+    /// # Errors
+    ///
+    /// If the number of distinct identifiers exceeds 255.
+    pub fn with(ids: impl IntoIterator<Item = IsaId>) -> Result<Self, IsaSegError> {
+        TinyOrdSet::try_from_iter(ids)
+            .map(Self)
+            .map_err(|_| IsaSegError::TooMany)
+    }
+
+    /// Constructs a set from the identifier strings, merging the duplicates.
+    ///
+    /// The identifiers are normalized by converting them to the upper case.
+    ///
+    /// # Errors
+    ///
+    /// If any of the identifiers is not a valid [`IsaId`] after the normalization, or if the
+    /// number of distinct identifiers exceeds 255.
+    pub fn parse_ids<'s>(ids: impl IntoIterator<Item = &'s str>) -> Result<Self, IsaSegError> {
+        let ids = ids
+            .into_iter()
+            .map(|id| {
+                IsaId::from_str(&id.to_ascii_uppercase())
+                    .map_err(|_| IsaSegError::InvalidId(id.to_string()))
+            })
+            .collect::<Result<alloc::vec::Vec<_>, _>>()?;
+        Self::with(ids)
+    }
+
+    /// Checks whether the host supports all the extensions from this set, i.e. whether this set is
+    /// a subset of the host set.
+    pub fn is_supported_by(&self, host: &IsaSeg) -> bool { self.unsupported_by(host).is_none() }
+
+    /// Returns the first (in the lexicographic order) extension from this set which is not
+    /// supported by the host set, if any.
+    pub fn unsupported_by(&self, host: &IsaSeg) -> Option<&IsaId> {
+        self.0.iter().find(|id| !host.0.contains(*id))
+    }
+}
+
+impl<'a> IntoIterator for &'a IsaSeg {
+    type Item = &'a IsaId;
+    type IntoIter = <&'a TinyOrdSet<IsaId> as IntoIterator>::IntoIter;
+
+    fn into_iter(self) -> Self::IntoIter { self.0.iter() }
+}
+
+impl Display for IsaSeg {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for (no, id) in self.0.iter().enumerate() {
+            if no > 0 {
+                f.write_str(" ")?;
+            }
+            Display::fmt(id, f)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for IsaSeg {
+    type Err = IsaSegError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> { Self::parse_ids(s.split_whitespace()) }
+}
+
+// Deserialization goes through the constructor, since the confined set doesn't check its size when
+// deserialized.
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for IsaSeg {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let ids = alloc::vec::Vec::<IsaId>::deserialize(deserializer)?;
+        Self::with(ids).map_err(serde::de::Error::custom)
+    }
+}
+
+// The segment is encoded as the set it wraps, such that it doesn't introduce a new type into the
+// type library and doesn't change the library ids.
+impl StrictType for IsaSeg {
+    const STRICT_LIB_NAME: &'static str = LIB_NAME_ALUVM;
+    fn strict_name() -> Option<TypeName> { None }
+}
+
+impl StrictEncode for IsaSeg {
+    fn strict_encode<W: TypedWrite>(&self, writer: W) -> std::io::Result<W> {
+        self.0.strict_encode(writer)
+    }
+}
+
+impl StrictDecode for IsaSeg {
+    fn strict_decode(reader: &mut impl TypedRead) -> Result<Self, DecodeError> {
+        TinyOrdSet::strict_decode(reader).map(Self)
+    }
+}
+
 /// Reserved instruction, which equal to [`crate::ExecStep::Fail`].
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Display)]
 #[display("halt    {0:#02X}.h")]
+#[derive(StrictType, StrictEncode, StrictDecode)]
+#[strict_type(lib = LIB_NAME_ALUVM)]
 pub struct ReservedInstr(/** Reserved instruction op code value */ pub(super) u8);
 
 impl Default for ReservedInstr {
     fn default() -> Self { Self(0xFF) }
 }
 
+#[cfg(any(test, feature = "fuzzing"))]
+impl crate::fuzzing::Arbitrary for ReservedInstr {
+    fn arbitrary(bytes: &mut impl Iterator<Item = u8>) -> Option<Self> {
+        let opcode = bytes.next()?;
+        Some(match OPCODE_TABLE[opcode as usize] {
+            IsaMember::Reserved => Self(opcode),
+            _ => default!(),
+        })
+    }
+}
+
+/// Member instruction set of the complete AluVM ISA ([`Instr`]) an opcode belongs to.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Display)]
+pub enum IsaMember {
+    /// Control flow instructions ([`CtrlInstr`]).
+    #[display("ctrl")]
+    Ctrl,
+
+    /// Reserved instruction ([`ReservedInstr`]).
+    #[display("reserved")]
+    Reserved,
+}
+
+/// Table mapping each opcode byte to the member instruction set of [`Instr`].
+///
+/// This is the single source of truth for opcode dispatch in [`Instr`] decoding. The table is
+/// constructed at compile time from the member opcode ranges, which are checked not to overlap;
+/// opcodes not covered by any of the members are mapped to [`IsaMember::Reserved`].
+pub const OPCODE_TABLE: [IsaMember; 256] =
+    opcode_table(&[(CtrlInstr::<LibId>::START, CtrlInstr::<LibId>::END, IsaMember::Ctrl)]);
+
+const fn opcode_table(members: &[(u8, u8, IsaMember)]) -> [IsaMember; 256] {
+    let mut table = [IsaMember::Reserved; 256];
+    let mut no = 0;
+    while no < members.len() {
+        let (start, end, member) = members[no];
+        assert!(start <= end, "invalid opcode range");
+        let mut op = start as usize;
+        while op <= end as usize {
+            assert!(matches!(table[op], IsaMember::Reserved), "overlapping opcode ranges");
+            table[op] = member;
+            op += 1;
+        }
+        no += 1;
+    }
+    table
+}
+
 /// Complete AluVM ISA.
 #[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Display, From)]
 #[display(inner)]
@@ -87,3 +282,84 @@ pub enum Instr<Id: SiteId> {
     #[from]
     Reserved(ReservedInstr),
 }
+
+#[cfg(any(test, feature = "fuzzing"))]
+impl crate::fuzzing::Arbitrary for Instr<LibId> {
+    fn arbitrary(bytes: &mut impl Iterator<Item = u8>) -> Option<Self> {
+        // Reserved instructions are generated less often than the control flow ones
+        Some(match bytes.next()? % 8 {
+            0 => Instr::Reserved(ReservedInstr::arbitrary(bytes)?),
+            _ => Instr::Ctrl(CtrlInstr::arbitrary(bytes)?),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![cfg_attr(coverage_nightly, coverage(off))]
+    use alloc::format;
+    use alloc::vec::Vec;
+
+    use super::*;
+
+    fn seg(s: &str) -> IsaSeg { IsaSeg::from_str(s).unwrap() }
+
+    #[test]
+    fn isa_seg_support() {
+        let host = seg("ALU GFA SECP");
+        assert!(seg("").is_supported_by(&host));
+        assert!(seg("GFA").is_supported_by(&host));
+        assert!(seg("SECP ALU").is_supported_by(&host));
+        assert!(seg("ALU GFA SECP").is_supported_by(&host));
+        assert_eq!(seg("ALU SECP GFA").unsupported_by(&host), None);
+
+        let superset = seg("ALU GFA SECP STR");
+        assert!(!superset.is_supported_by(&host));
+        assert_eq!(superset.unsupported_by(&host), Some(&IsaId::from("STR")));
+        assert!(host.is_supported_by(&superset));
+
+        let overlap = seg("GFA BPDIGEST ALU");
+        assert!(!overlap.is_supported_by(&host));
+        assert!(!host.is_supported_by(&overlap));
+        assert_eq!(overlap.unsupported_by(&host), Some(&IsaId::from("BPDIGEST")));
+
+        assert!(!seg("GFA").is_supported_by(&seg("")));
+    }
+
+    #[test]
+    fn isa_seg_canonical() {
+        let seg1 = seg("GFA ALU GFA");
+        assert_eq!(seg1, seg("alu gfa"));
+        assert_eq!(seg1, IsaId::canonical_set([IsaId::from("ALU"), IsaId::from("GFA")]));
+        assert_eq!(seg1.to_string(), "ALU GFA");
+        assert_eq!(seg(&seg1.to_string()), seg1);
+        assert_eq!(seg("  Alu\tgfa\n").to_string(), "ALU GFA");
+        assert_eq!(seg("").to_string(), "");
+        assert_eq!(seg(""), IsaSeg::new());
+    }
+
+    #[test]
+    fn isa_seg_invalid() {
+        for id in ["A-B", "ALU_1", "ÄLU", "A.B", "ABCDEFGHIJKLMNOPQ"] {
+            assert_eq!(
+                IsaSeg::from_str(&format!("ALU {id}")),
+                Err(IsaSegError::InvalidId(id.to_string()))
+            );
+        }
+        assert_eq!(IsaSeg::parse_ids([""]), Err(IsaSegError::InvalidId(s!(""))));
+        assert_eq!(
+            IsaSeg::parse_ids(["ALU", "GFA SECP"]),
+            Err(IsaSegError::InvalidId(s!("GFA SECP")))
+        );
+        assert!(IsaSeg::parse_ids(["ABCDEFGHIJKLMNOP"]).is_ok());
+
+        let ids = (0..256).map(|no| format!("ISA{no}")).collect::<Vec<_>>();
+        assert_eq!(IsaSeg::parse_ids(ids.iter().map(String::as_str)), Err(IsaSegError::TooMany));
+        assert_eq!(
+            IsaSeg::parse_ids(ids[..255].iter().map(String::as_str))
+                .unwrap()
+                .len(),
+            255
+        );
+    }
+}
@@ -0,0 +1,529 @@
+// Reference rust implementation of AluVM (arithmetic logic unit virtual machine).
+// To find more on AluVM please check <https://aluvm.org>
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Designed in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+// Written in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+//
+// Copyright (C) 2021-2024 LNP/BP Standards Association, Switzerland.
+// Copyright (C) 2024-2025 Laboratories for Ubiquitous Deterministic Computing (UBIDECO),
+//                         Institute for Distributed and Cognitive Systems (InDCS), Switzerland.
+// Copyright (C) 2021-2025 Dr Maxim Orlovsky.
+// All rights under the above copyrights are reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use this file except
+// in compliance with the License. You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed under the License
+// is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express
+// or implied. See the License for the specific language governing permissions and limitations under
+// the License.
+
+//! Parser of the textual assembly form of the instructions.
+//!
+//! The parser accepts the instructions in the exact form produced by their [`Display`]
+//! implementations, with a flexible amount of whitespace between the mnemonic and the operands and
+//! around the operand separators. This allows re-assembling the disassembled code:
+//!
+//! ```
+//! # extern crate alloc;
+//! use aluvm::isa::{parse_asm, Instr};
+//! use aluvm::{aluasm, Lib, LibId};
+//!
+//! const MAIN: u16 = 0;
+//!
+//! let code = aluasm! {
+//!    routine  MAIN:
+//!     not     CO;
+//!     jif     CO, +2;
+//!     call    MAIN;
+//!     stop;
+//! };
+//! let lib = Lib::assemble::<Instr<LibId>>(&code).unwrap();
+//!
+//! let mut text = Vec::new();
+//! lib.print_disassemble::<Instr<LibId>>(&mut text).unwrap();
+//! let code = parse_asm::<LibId>(&String::from_utf8(text).unwrap()).unwrap();
+//! assert_eq!(Lib::assemble::<Instr<LibId>>(&code).unwrap(), lib);
+//! ```
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::str::FromStr;
+
+use super::{CtrlInstr, Instr, ReservedInstr};
+use crate::core::{parse_site_offset, SiteId};
+use crate::Site;
+
+/// Errors parsing the textual assembly form of an instruction.
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum AsmParseError {
+    /// empty instruction.
+    Empty,
+
+    /// unknown instruction mnemonic `{0}`.
+    UnknownMnemonic(String),
+
+    /// invalid operands `{1}` of the `{0}` instruction.
+    InvalidOperands(String, String),
+
+    /// the `{mnemonic}` instruction takes {expected} operand(s), while {provided} are given.
+    OperandCount {
+        /// Instruction mnemonic.
+        mnemonic: String,
+        /// Number of operands taken by the instruction.
+        expected: usize,
+        /// Number of operands given.
+        provided: usize,
+    },
+
+    /// invalid code offset `{0}`.
+    InvalidOffset(String),
+
+    /// invalid program identifier in the site `{0}`.
+    InvalidSite(String),
+
+    /// invalid reserved instruction opcode `{0}`.
+    InvalidOpcode(String),
+
+    /// invalid register mask `{0}`.
+    InvalidMask(String),
+}
+
+/// Splits an instruction into a mnemonic and a list of operands.
+fn split(s: &str) -> Result<(&str, Vec<&str>), AsmParseError> {
+    let s = s.trim();
+    if s.is_empty() {
+        return Err(AsmParseError::Empty);
+    }
+    let (mnemonic, operands) = s.split_once(char::is_whitespace).unwrap_or((s, ""));
+    let operands = operands.trim();
+    let operands =
+        if operands.is_empty() { Vec::new() } else { operands.split(',').map(str::trim).collect() };
+    Ok((mnemonic, operands))
+}
+
+/// Returns the number of operands taken by a control flow instruction with the given mnemonic,
+/// or `None` if the mnemonic is unknown.
+fn arity(mnemonic: &str) -> Option<usize> {
+    match mnemonic {
+        "nop" | "ret" | "stop" => Some(0),
+        "chk" | "not" | "fail" | "jmp" | "jmp.w" | "call" | "save" => Some(1),
+        "mov" | "jif" | "jif.w" => Some(2),
+        _ => None,
+    }
+}
+
+/// Reports operands not matching the instruction, distinguishing a wrong number of operands.
+fn operands_error(mnemonic: &str, operands: &[&str], expected: usize) -> AsmParseError {
+    if operands.len() != expected {
+        AsmParseError::OperandCount {
+            mnemonic: mnemonic.to_string(),
+            expected,
+            provided: operands.len(),
+        }
+    } else {
+        AsmParseError::InvalidOperands(mnemonic.to_string(), operands.join(", "))
+    }
+}
+
+fn parse_pos(s: &str) -> Result<u16, AsmParseError> {
+    if !s.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(AsmParseError::InvalidOffset(s.to_string()));
+    }
+    u16::from_str(s).map_err(|_| AsmParseError::InvalidOffset(s.to_string()))
+}
+
+fn parse_shift<T: FromStr>(s: &str) -> Result<T, AsmParseError> {
+    T::from_str(s).map_err(|_| AsmParseError::InvalidOffset(s.to_string()))
+}
+
+fn parse_mask(s: &str) -> Result<u16, AsmParseError> {
+    s.strip_prefix("0x")
+        .and_then(|hex| hex.strip_suffix(".h"))
+        .filter(|hex| !hex.is_empty() && hex.bytes().all(|b| b.is_ascii_hexdigit()))
+        .and_then(|hex| u16::from_str_radix(hex, 16).ok())
+        .ok_or_else(|| AsmParseError::InvalidMask(s.to_string()))
+}
+
+fn parse_site<Id: SiteId>(s: &str) -> Result<Site<Id>, AsmParseError> {
+    let (id, offset) = s
+        .rsplit_once('@')
+        .ok_or_else(|| AsmParseError::InvalidSite(s.to_string()))?;
+    let prog_id = Id::from_str(id).map_err(|_| AsmParseError::InvalidSite(s.to_string()))?;
+    // Besides the canonical hexadecimal form produced by `Site` display, a decimal offset is
+    // accepted
+    let offset = if offset.ends_with("#h") {
+        parse_site_offset(offset).ok_or_else(|| AsmParseError::InvalidOffset(offset.to_string()))?
+    } else {
+        parse_pos(offset)?
+    };
+    Ok(Site::new(prog_id, offset))
+}
+
+/// Kind of the jump target operand.
+enum Target<'s> {
+    Shift(&'s str),
+    Site(&'s str),
+    Pos(&'s str),
+}
+
+impl<'s> Target<'s> {
+    fn classify(s: &'s str) -> Self {
+        if s.starts_with(['+', '-']) {
+            Target::Shift(s)
+        } else if s.contains('@') {
+            Target::Site(s)
+        } else {
+            Target::Pos(s)
+        }
+    }
+}
+
+impl<Id: SiteId> FromStr for CtrlInstr<Id> {
+    type Err = AsmParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (mnemonic, operands) = split(s)?;
+        let invalid = || AsmParseError::InvalidOperands(mnemonic.to_string(), operands.join(", "));
+        Ok(match (mnemonic, operands.as_slice()) {
+            ("nop", []) => CtrlInstr::Nop,
+            ("chk", ["CO"]) => CtrlInstr::ChkCo,
+            ("chk", ["CK"]) => CtrlInstr::ChkCk,
+            ("not", ["CO"]) => CtrlInstr::NotCo,
+            ("fail", ["CK"]) => CtrlInstr::FailCk,
+            ("mov", ["CO", "CK"]) => CtrlInstr::RsetCk,
+            ("ret", []) => CtrlInstr::Ret,
+            ("stop", []) => CtrlInstr::Stop,
+            ("jmp", [target]) => match Target::classify(target) {
+                Target::Shift(shift) => CtrlInstr::Sh { shift: parse_shift(shift)? },
+                Target::Site(site) => CtrlInstr::Exec { site: parse_site(site)? },
+                Target::Pos(pos) => CtrlInstr::Jmp { pos: parse_pos(pos)? },
+            },
+            ("jif", [reg @ ("CO" | "CK"), target]) => {
+                let co = *reg == "CO";
+                match Target::classify(target) {
+                    Target::Shift(shift) if co => CtrlInstr::ShOvfl { shift: parse_shift(shift)? },
+                    Target::Shift(shift) => CtrlInstr::ShFail { shift: parse_shift(shift)? },
+                    Target::Pos(pos) if co => CtrlInstr::JiOvfl { pos: parse_pos(pos)? },
+                    Target::Pos(pos) => CtrlInstr::JiFail { pos: parse_pos(pos)? },
+                    Target::Site(_) => return Err(invalid()),
+                }
+            }
+            ("jmp.w", [target]) => match Target::classify(target) {
+                Target::Shift(shift) => CtrlInstr::ShW { shift: parse_shift(shift)? },
+                Target::Site(_) | Target::Pos(_) => return Err(invalid()),
+            },
+            ("jif.w", [reg @ ("CO" | "CK"), target]) => match Target::classify(target) {
+                Target::Shift(shift) if *reg == "CO" => {
+                    CtrlInstr::ShWOvfl { shift: parse_shift(shift)? }
+                }
+                Target::Shift(shift) => CtrlInstr::ShWFail { shift: parse_shift(shift)? },
+                Target::Site(_) | Target::Pos(_) => return Err(invalid()),
+            },
+            ("call", [target]) => match Target::classify(target) {
+                Target::Site(site) => CtrlInstr::Call { site: parse_site(site)? },
+                Target::Pos(pos) => CtrlInstr::Fn { pos: parse_pos(pos)? },
+                Target::Shift(_) => return Err(invalid()),
+            },
+            ("save", [mask]) => CtrlInstr::Save { mask: parse_mask(mask)? },
+            _ => {
+                return Err(match arity(mnemonic) {
+                    Some(expected) => operands_error(mnemonic, &operands, expected),
+                    None => AsmParseError::UnknownMnemonic(mnemonic.to_string()),
+                })
+            }
+        })
+    }
+}
+
+impl FromStr for ReservedInstr {
+    type Err = AsmParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (mnemonic, operands) = split(s)?;
+        match (mnemonic, operands.as_slice()) {
+            ("halt", [opcode]) => {
+                let hex = opcode
+                    .strip_prefix("0x")
+                    .and_then(|hex| hex.strip_suffix(".h"))
+                    .filter(|hex| hex.bytes().all(|b| b.is_ascii_hexdigit()))
+                    .ok_or_else(|| AsmParseError::InvalidOpcode(opcode.to_string()))?;
+                u8::from_str_radix(hex, 16)
+                    .map(ReservedInstr)
+                    .map_err(|_| AsmParseError::InvalidOpcode(opcode.to_string()))
+            }
+            ("halt", _) => Err(operands_error(mnemonic, &operands, 1)),
+            _ => Err(AsmParseError::UnknownMnemonic(mnemonic.to_string())),
+        }
+    }
+}
+
+impl<Id: SiteId> FromStr for Instr<Id> {
+    type Err = AsmParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match CtrlInstr::from_str(s) {
+            Err(AsmParseError::UnknownMnemonic(_)) => ReservedInstr::from_str(s).map(Instr::from),
+            res => res.map(Instr::from),
+        }
+    }
+}
+
+/// Parses a program in the textual assembly form, one instruction per line.
+///
+/// Empty lines and comments, starting with `;`, are skipped. Each line may be prefixed with the
+/// instruction offset in the form of `offset NNNNNN:`, as it is done by
+/// [`crate::Lib::print_disassemble`]; the offsets are not checked.
+///
+/// # Errors
+///
+/// The number of the line (starting from 1) which can't be parsed, together with the parsing
+/// error.
+pub fn parse_asm<Id: SiteId>(text: &str) -> Result<Vec<Instr<Id>>, (usize, AsmParseError)> {
+    let mut code = Vec::new();
+    for (no, line) in text.lines().enumerate() {
+        let line = line.split_once(';').map_or(line, |(instr, _)| instr).trim();
+        let line = match line.strip_prefix("offset") {
+            Some(rest) => rest
+                .split_once(':')
+                .filter(|(offset, _)| offset.trim().bytes().all(|b| b.is_ascii_digit()))
+                .map_or(line, |(_, instr)| instr.trim()),
+            None => line,
+        };
+        if line.is_empty() {
+            continue;
+        }
+        code.push(Instr::from_str(line).map_err(|err| (no + 1, err))?);
+    }
+    Ok(code)
+}
+
+#[cfg(test)]
+mod test {
+    #![cfg_attr(coverage_nightly, coverage(off))]
+
+    use super::*;
+    use crate::isa::Bytecode;
+    use crate::{aluasm, Lib, LibId};
+
+    fn lib_id(byte: u8) -> LibId { LibId::from([byte; 32]) }
+
+    fn roundtrip(instr: impl Into<Instr<LibId>>) {
+        let instr = instr.into();
+        let text = instr.to_string();
+        assert_eq!(Instr::from_str(&text), Ok(instr), "{text}");
+    }
+
+    #[test]
+    fn ctrl_roundtrip() {
+        for instr in [
+            CtrlInstr::Nop,
+            CtrlInstr::ChkCo,
+            CtrlInstr::ChkCk,
+            CtrlInstr::NotCo,
+            CtrlInstr::FailCk,
+            CtrlInstr::RsetCk,
+            CtrlInstr::Ret,
+            CtrlInstr::Stop,
+        ] {
+            roundtrip(instr);
+        }
+        for pos in [0, 1, 9, 10, 999, 1000, 9999, 10000, u16::MAX] {
+            roundtrip(CtrlInstr::Jmp { pos });
+            roundtrip(CtrlInstr::JiOvfl { pos });
+            roundtrip(CtrlInstr::JiFail { pos });
+            roundtrip(CtrlInstr::Fn { pos });
+            for byte in [0u8, 0xA5, 0xFF] {
+                let site = Site::new(lib_id(byte), pos);
+                roundtrip(CtrlInstr::Exec { site });
+                roundtrip(CtrlInstr::Call { site });
+            }
+        }
+        for shift in i8::MIN..=i8::MAX {
+            roundtrip(CtrlInstr::Sh { shift });
+            roundtrip(CtrlInstr::ShOvfl { shift });
+            roundtrip(CtrlInstr::ShFail { shift });
+        }
+        for shift in [i16::MIN, -0x1234, -129, -1, 0, 1, 128, 0x1234, i16::MAX] {
+            roundtrip(CtrlInstr::ShW { shift });
+            roundtrip(CtrlInstr::ShWOvfl { shift });
+            roundtrip(CtrlInstr::ShWFail { shift });
+        }
+        for mask in [0, 1, 0xA5, 0x8000, u16::MAX] {
+            roundtrip(CtrlInstr::Save { mask });
+        }
+    }
+
+    #[test]
+    fn reserved_roundtrip() {
+        for opcode in 0..=u8::MAX {
+            roundtrip(ReservedInstr(opcode));
+        }
+    }
+
+    #[test]
+    fn whitespace() {
+        let site = Site::new(lib_id(1), 5);
+        for (text, instr) in [
+            ("nop", CtrlInstr::Nop),
+            ("  nop  ", CtrlInstr::Nop),
+            ("chk CO", CtrlInstr::ChkCo),
+            ("chk\tCK", CtrlInstr::ChkCk),
+            ("mov CO,CK", CtrlInstr::RsetCk),
+            ("mov   CO ,  CK", CtrlInstr::RsetCk),
+            ("jif CK,-3", CtrlInstr::ShFail { shift: -3 }),
+            ("jif  CO ,  12", CtrlInstr::JiOvfl { pos: 12 }),
+            ("jmp +0", CtrlInstr::Sh { shift: 0 }),
+            ("jmp.w\t-300", CtrlInstr::ShW { shift: -300 }),
+            ("jif.w CO ,+300", CtrlInstr::ShWOvfl { shift: 300 }),
+        ] {
+            assert_eq!(CtrlInstr::<LibId>::from_str(text), Ok(instr), "{text}");
+        }
+        let text = format!("call\t {site}");
+        assert_eq!(CtrlInstr::from_str(&text), Ok(CtrlInstr::Call { site }));
+        let text = format!("call {}@5", site.prog_id);
+        assert_eq!(CtrlInstr::from_str(&text), Ok(CtrlInstr::Call { site }));
+        assert_eq!(Instr::<LibId>::from_str("halt 0xFF.h"), Ok(ReservedInstr(0xFF).into()));
+    }
+
+    #[test]
+    fn errors() {
+        let parse = |s: &str| Instr::<LibId>::from_str(s);
+        assert_eq!(parse(" "), Err(AsmParseError::Empty));
+        assert_eq!(parse("add A, B"), Err(AsmParseError::UnknownMnemonic(s!("add"))));
+        assert_eq!(parse("NOP"), Err(AsmParseError::UnknownMnemonic(s!("NOP"))));
+        assert_eq!(
+            parse("nop CO"),
+            Err(AsmParseError::OperandCount { mnemonic: s!("nop"), expected: 0, provided: 1 })
+        );
+        assert_eq!(
+            parse("jif CO"),
+            Err(AsmParseError::OperandCount { mnemonic: s!("jif"), expected: 2, provided: 1 })
+        );
+        assert_eq!(
+            parse("jmp 1, 2"),
+            Err(AsmParseError::OperandCount { mnemonic: s!("jmp"), expected: 1, provided: 2 })
+        );
+        assert_eq!(
+            parse("halt"),
+            Err(AsmParseError::OperandCount { mnemonic: s!("halt"), expected: 1, provided: 0 })
+        );
+        assert_eq!(parse("chk CH"), Err(AsmParseError::InvalidOperands(s!("chk"), s!("CH"))));
+        assert_eq!(
+            parse("mov CK, CO"),
+            Err(AsmParseError::InvalidOperands(s!("mov"), s!("CK, CO")))
+        );
+        assert_eq!(parse("call +1"), Err(AsmParseError::InvalidOperands(s!("call"), s!("+1"))));
+        assert_eq!(parse("jmp 65536"), Err(AsmParseError::InvalidOffset(s!("65536"))));
+        assert_eq!(parse("jmp 0x10"), Err(AsmParseError::InvalidOffset(s!("0x10"))));
+        assert_eq!(parse("jmp +128"), Err(AsmParseError::InvalidOffset(s!("+128"))));
+        assert_eq!(parse("jmp.w +32768"), Err(AsmParseError::InvalidOffset(s!("+32768"))));
+        assert_eq!(parse("jmp.w 12"), Err(AsmParseError::InvalidOperands(s!("jmp.w"), s!("12"))));
+        assert_eq!(
+            parse("jif.w CK, 12"),
+            Err(AsmParseError::InvalidOperands(s!("jif.w"), s!("CK, 12")))
+        );
+        assert_eq!(parse("jmp x@0001"), Err(AsmParseError::InvalidSite(s!("x@0001"))));
+        let id = lib_id(1);
+        assert_eq!(
+            parse(&format!("jmp {id}@00X1#h")),
+            Err(AsmParseError::InvalidOffset(s!("00X1#h")))
+        );
+        assert_eq!(parse("halt 0xFF"), Err(AsmParseError::InvalidOpcode(s!("0xFF"))));
+        assert_eq!(parse("halt 0x100.h"), Err(AsmParseError::InvalidOpcode(s!("0x100.h"))));
+    }
+
+    #[test]
+    fn reassemble() {
+        const MAIN: u16 = 0;
+        const SUB: u16 = 1;
+        let ext = lib_id(7);
+        let code = aluasm! {
+           routine MAIN:
+            chk     CO;
+            chk     CK;
+            jif     CO, MAIN;
+            jif     CK, -1;
+            fail    CK;
+            mov     CO, CK;
+            not     CO;
+            jmp     +5;
+            jif.w   CO, +300;
+            jif.w   CK, -2;
+            jmp.w   -1;
+            call    SUB;
+            call    ext, 1234;
+            jmp     ext, 1234;
+            stop;
+           routine  SUB:
+            ret;
+        };
+        let mut code = code;
+        code.push(ReservedInstr(0xFF).into());
+        let lib = Lib::assemble::<Instr<LibId>>(&code).unwrap();
+
+        let text = lib
+            .disassemble::<Instr<LibId>>()
+            .unwrap()
+            .iter()
+            .map(Instr::to_string)
+            .collect::<Vec<_>>()
+            .join("\n");
+        let parsed = parse_asm::<LibId>(&text).unwrap();
+        assert_eq!(Lib::assemble::<Instr<LibId>>(&parsed).unwrap(), lib);
+
+        let mut printed = Vec::new();
+        lib.print_disassemble::<Instr<LibId>>(&mut printed).unwrap();
+        let printed = String::from_utf8(printed).unwrap();
+        let parsed = parse_asm::<LibId>(&printed).unwrap();
+        assert_eq!(Lib::assemble::<Instr<LibId>>(&parsed).unwrap(), lib);
+        assert_eq!(
+            parsed.iter().map(Bytecode::code_byte_len).sum::<u16>() as usize,
+            lib.code().len()
+        );
+    }
+
+    #[test]
+    fn macro_matches_parser() {
+        let ext = lib_id(7);
+        let code = aluasm! {
+            nop;
+            jmp     0x75AE;
+            jmp     -5;
+            jmp     +5;
+            jif     CO, 0x75AE;
+            jif     CK, -5;
+            jmp.w   -0x1234;
+            call    0x75AE;
+            call    ext@0x69AB;
+            jmp     ext@0x69AB;
+            save    0x80F1;
+            ret;
+            stop;
+        };
+        let text = code
+            .iter()
+            .map(Instr::to_string)
+            .collect::<Vec<_>>()
+            .join("\n");
+        assert_eq!(parse_asm::<LibId>(&text), Ok(code));
+    }
+
+    #[test]
+    fn parse_lines() {
+        let text = "\n; comment\noffset 000000: nop ; trailing comment\n   not CO\n\nstop\n";
+        assert_eq!(
+            parse_asm::<LibId>(text),
+            Ok(vec![CtrlInstr::Nop.into(), CtrlInstr::NotCo.into(), CtrlInstr::Stop.into()])
+        );
+        assert_eq!(
+            parse_asm::<LibId>("nop\nnop\nnot CK"),
+            Err((3, AsmParseError::InvalidOperands(s!("not"), s!("CK"))))
+        );
+    }
+}
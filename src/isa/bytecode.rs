@@ -22,6 +22,7 @@
 // or implied. See the License for the specific language governing permissions and limitations under
 // the License.
 
+use alloc::vec::Vec;
 use core::fmt::Debug;
 use core::ops::RangeInclusive;
 
@@ -39,6 +40,13 @@ pub trait Bytecode<Id: SiteId> {
     /// Returns the range of instruction bytecodes covered by a set of operations.
     fn op_range() -> RangeInclusive<u8>;
 
+    /// Checks whether the set of operations owns the opcode, i.e. whether it can decode an
+    /// instruction with it.
+    ///
+    /// Defaults to checking whether the opcode is inside [`Self::op_range`]. Sets of operations
+    /// composed of several members with non-adjacent opcode ranges must override it.
+    fn owns_opcode(opcode: u8) -> bool { Self::op_range().contains(&opcode) }
+
     /// Returns byte representing instruction code (without its arguments).
     fn opcode_byte(&self) -> u8;
 
@@ -52,10 +60,10 @@ pub trait Bytecode<Id: SiteId> {
     /// Write an instruction as bytecode.
     fn encode_instr<W>(&self, writer: &mut W) -> Result<(), W::Error>
     where W: BytecodeWrite<Id> {
+        let instr_offset = writer.pos();
         writer.write_byte(self.opcode_byte())?;
         self.encode_operands(writer)?;
-        writer.check_aligned();
-        Ok(())
+        writer.check_aligned(instr_offset)
     }
 
     /// Writes an instruction operands as bytecode, omitting opcode byte.
@@ -70,23 +78,87 @@ pub trait Bytecode<Id: SiteId> {
     {
         let opcode = reader.read_byte()?;
         let instr = Self::decode_operands(reader, opcode)?;
-        reader.check_aligned();
+        reader.check_aligned()?;
         Ok(instr)
     }
 
     /// Reads an instruction operands from bytecode, provided the opcode byte.
+    ///
+    /// Since the bytecode is untrusted, implementations must never panic: malformed operands, as
+    /// well as an opcode which doesn't belong to the instruction set, are reported as errors.
     fn decode_operands<R>(reader: &mut R, opcode: u8) -> Result<Self, CodeEofError>
     where
         Self: Sized,
         R: BytecodeRead<Id>;
 }
 
+/// Static description of the opcodes of an instruction set, which doesn't require constructing the
+/// instructions.
+///
+/// Used to produce ISA reference documentation and to check the validity of opcodes in tooling.
+pub trait OpcodeTable<Id: SiteId>: Bytecode<Id> {
+    /// Describes the opcode, if it belongs to the instruction set.
+    fn opcode_info(opcode: u8) -> Option<OpcodeInfo>;
+
+    /// Returns the description of every opcode from [`Bytecode::op_range`] belonging to the
+    /// instruction set, ordered by the opcode value.
+    fn opcode_table() -> Vec<OpcodeInfo> {
+        Self::op_range().filter_map(Self::opcode_info).collect()
+    }
+}
+
+/// Description of a single opcode of an instruction set (see [`OpcodeTable`]).
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct OpcodeInfo {
+    /// Opcode byte.
+    pub opcode: u8,
+    /// Instruction mnemonic, as used by the assembler.
+    ///
+    /// Several opcodes may share the same mnemonic, differing by their operands.
+    pub mnemonic: &'static str,
+    /// Operands of the instruction, in the form of a syntax template (like `CO, pos`).
+    pub operands: &'static str,
+    /// Number of bytes used by the instruction operands, without the opcode byte.
+    pub operand_len: u16,
+    /// Whether the opcode is reserved for a future use.
+    pub reserved: bool,
+}
+
 /// Error indicating that an end-of-code segment boundary is reached during read or write operation.
 #[derive(Clone, Copy, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Display, Error)]
 #[display("attempt to read or write outside of a code segment (i.e., at position > 0xFFFF)")]
 pub struct CodeEofError;
 
+/// Errors reading a variable-length byte string from the data segment.
+#[derive(Clone, Copy, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum DataReadError {
+    /// attempt to read byte string offset or length outside of a code segment.
+    #[from(CodeEofError)]
+    CodeEof,
+
+    /// byte string at offset {offset} with length {len} doesn't fit the data segment of {seg_len}
+    /// bytes.
+    OutOfDataSegment {
+        /// Offset of the byte string in the data segment.
+        offset: u16,
+        /// Length of the byte string.
+        len: u16,
+        /// Length of the data segment.
+        seg_len: usize,
+    },
+}
+
+impl From<DataReadError> for CodeEofError {
+    fn from(_: DataReadError) -> Self { CodeEofError }
+}
+
 /// Reader from a bytecode for instruction deserialization.
+///
+/// Operands shorter than a byte are packed starting from the least significant bit: the first
+/// operand read from a byte takes its lowest bits, and an operand crossing a byte boundary takes
+/// its remaining high bits from the lowest bits of the next byte. Each instruction must end at a
+/// byte boundary.
 pub trait BytecodeRead<Id: SiteId> {
     /// Return the current byte offset of the cursor. Does not account for bits.
     /// If the position is exactly at EOF, returns `None`.
@@ -136,31 +208,44 @@ pub trait BytecodeRead<Id: SiteId> {
         f: impl FnOnce([u8; LEN]) -> N,
     ) -> Result<N, CodeEofError>;
 
-    /// Read variable-length byte string.
+    /// Read variable-length byte string from the data segment, using its offset and length read
+    /// from the code.
     ///
-    /// # Returns
+    /// # Errors
     ///
-    /// Resulting data type and a flag for `CK` registry indicating whether it was possible to read
-    /// all the data.
-    fn read_bytes(&mut self) -> Result<(SmallBlob, bool), CodeEofError>;
+    /// If the code ends before the offset and the length are read, or if the byte string doesn't
+    /// fit the data segment.
+    fn read_bytes(&mut self) -> Result<SmallBlob, DataReadError>;
 
     /// Read external reference id.
+    ///
+    /// # Errors
+    ///
+    /// If the code segment ends, or if the reference can't be resolved (for instance, since it
+    /// points outside of the libs segment).
     fn read_ref(&mut self) -> Result<Id, CodeEofError>
     where Id: Sized;
 
     /// Check if the current cursor position is aligned to the next byte.
     ///
-    /// # Panics
+    /// # Errors
     ///
-    /// If the position is not aligned, panics.
-    fn check_aligned(&self);
+    /// If the position is not aligned, meaning that the instruction operands didn't end at a byte
+    /// boundary.
+    fn check_aligned(&self) -> Result<(), CodeEofError>;
 }
 
 /// Writer converting instructions into a bytecode.
+///
+/// Operands shorter than a byte are packed using the same bit order as the one used by
+/// [`BytecodeRead`], i.e. starting from the least significant bit of a byte.
 pub trait BytecodeWrite<Id: SiteId> {
     /// Error type returned during writing procedures.
     type Error: Debug;
 
+    /// Return the current byte offset of the cursor. Does not account for bits.
+    fn pos(&self) -> u16;
+
     /// Write a single bit from a bool value.
     fn write_bool(&mut self, data: bool) -> Result<(), Self::Error> {
         self.write_1bit(if data { u1::ONE } else { u1::ZERO })
@@ -189,16 +274,26 @@ pub trait BytecodeWrite<Id: SiteId> {
     /// Write data representable as a fixed-length byte array.
     fn write_fixed<const LEN: usize>(&mut self, data: [u8; LEN]) -> Result<(), Self::Error>;
 
-    /// Write variable-length byte string.
-    fn write_bytes(&mut self, data: &[u8]) -> Result<(), Self::Error>;
+    /// Write variable-length byte string into the data segment, and its offset and length into
+    /// the code.
+    ///
+    /// Byte strings already present in the data segment are not duplicated. An empty byte string
+    /// is always written with a zero offset.
+    ///
+    /// # Returns
+    ///
+    /// Offset of the byte string in the data segment and its length.
+    fn write_bytes(&mut self, data: &[u8]) -> Result<(u16, u16), Self::Error>;
 
     /// Write external reference id.
     fn write_ref(&mut self, id: Id) -> Result<(), Self::Error>;
 
-    /// Check if the current cursor position is aligned to the next byte.
+    /// Check if the current cursor position is aligned to the next byte, which must be the case
+    /// after all operands of an instruction are written.
     ///
-    /// # Panics
+    /// # Errors
     ///
-    /// If the position is not aligned, panics.
-    fn check_aligned(&self);
+    /// If the position is not aligned, returns an error referencing the instruction which started
+    /// at `instr_offset`.
+    fn check_aligned(&self, instr_offset: u16) -> Result<(), Self::Error>;
 }
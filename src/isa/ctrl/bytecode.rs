@@ -27,7 +27,10 @@ use core::ops::RangeInclusive;
 use super::CtrlInstr;
 use crate::core::SiteId;
 use crate::isa::bytecode::CodeEofError;
-use crate::isa::{Bytecode, BytecodeRead, BytecodeWrite, Instr, ReservedInstr};
+use crate::isa::{
+    Bytecode, BytecodeRead, BytecodeWrite, Instr, IsaMember, OpcodeInfo, OpcodeTable,
+    ReservedInstr, OPCODE_TABLE,
+};
 use crate::Site;
 
 impl<Id: SiteId> Bytecode<Id> for Instr<Id> {
@@ -67,11 +70,20 @@ impl<Id: SiteId> Bytecode<Id> for Instr<Id> {
         Self: Sized,
         R: BytecodeRead<Id>,
     {
-        match opcode {
-            op if CtrlInstr::<Id>::op_range().contains(&op) => {
-                CtrlInstr::<Id>::decode_operands(reader, op).map(Self::Ctrl)
+        match OPCODE_TABLE[opcode as usize] {
+            IsaMember::Ctrl => CtrlInstr::<Id>::decode_operands(reader, opcode).map(Self::Ctrl),
+            IsaMember::Reserved => {
+                ReservedInstr::decode_operands(reader, opcode).map(Self::Reserved)
             }
-            _ => ReservedInstr::decode_operands(reader, opcode).map(Self::Reserved),
+        }
+    }
+}
+
+impl<Id: SiteId> OpcodeTable<Id> for Instr<Id> {
+    fn opcode_info(opcode: u8) -> Option<OpcodeInfo> {
+        match OPCODE_TABLE[opcode as usize] {
+            IsaMember::Ctrl => CtrlInstr::<Id>::opcode_info(opcode),
+            IsaMember::Reserved => <ReservedInstr as OpcodeTable<Id>>::opcode_info(opcode),
         }
     }
 }
@@ -99,10 +111,22 @@ impl<Id: SiteId> Bytecode<Id> for ReservedInstr {
     }
 }
 
+impl<Id: SiteId> OpcodeTable<Id> for ReservedInstr {
+    fn opcode_info(opcode: u8) -> Option<OpcodeInfo> {
+        Some(OpcodeInfo {
+            opcode,
+            mnemonic: "halt",
+            operands: "",
+            operand_len: 0,
+            reserved: true,
+        })
+    }
+}
+
 #[allow(missing_docs)]
 impl<Id: SiteId> CtrlInstr<Id> {
-    const START: u8 = 0;
-    const END: u8 = Self::START + Self::STOP;
+    pub(crate) const START: u8 = 0;
+    pub(crate) const END: u8 = Self::START + Self::SAVE;
 
     pub const NOP: u8 = 0;
     pub const NOCO: u8 = 1;
@@ -121,6 +145,10 @@ impl<Id: SiteId> CtrlInstr<Id> {
     pub const CALL: u8 = 14;
     pub const RET: u8 = 15;
     pub const STOP: u8 = 16;
+    pub const SHW: u8 = 17;
+    pub const SHWNE: u8 = 18;
+    pub const SHWFAIL: u8 = 19;
+    pub const SAVE: u8 = 20;
 }
 
 impl<Id: SiteId> Bytecode<Id> for CtrlInstr<Id> {
@@ -145,6 +173,10 @@ impl<Id: SiteId> Bytecode<Id> for CtrlInstr<Id> {
             CtrlInstr::Call { .. } => Self::CALL,
             CtrlInstr::Ret => Self::RET,
             CtrlInstr::Stop => Self::STOP,
+            CtrlInstr::ShW { .. } => Self::SHW,
+            CtrlInstr::ShWOvfl { .. } => Self::SHWNE,
+            CtrlInstr::ShWFail { .. } => Self::SHWFAIL,
+            CtrlInstr::Save { .. } => Self::SAVE,
         }
     }
 
@@ -163,8 +195,12 @@ impl<Id: SiteId> Bytecode<Id> for CtrlInstr<Id> {
             CtrlInstr::Sh { shift: _ }
             | CtrlInstr::ShOvfl { shift: _ }
             | CtrlInstr::ShFail { shift: _ } => 1,
+            CtrlInstr::ShW { shift: _ }
+            | CtrlInstr::ShWOvfl { shift: _ }
+            | CtrlInstr::ShWFail { shift: _ } => 2,
             CtrlInstr::Exec { site: _ } | CtrlInstr::Call { site: _ } => 3,
             CtrlInstr::Ret | CtrlInstr::Stop => 0,
+            CtrlInstr::Save { mask: _ } => 2,
         };
         arg_bytes + 1
     }
@@ -178,7 +214,8 @@ impl<Id: SiteId> Bytecode<Id> for CtrlInstr<Id> {
             | CtrlInstr::RsetCk
             | CtrlInstr::NotCo
             | CtrlInstr::Ret
-            | CtrlInstr::Stop => None,
+            | CtrlInstr::Stop
+            | CtrlInstr::Save { mask: _ } => None,
 
             CtrlInstr::Jmp { pos: _ }
             | CtrlInstr::JiOvfl { pos: _ }
@@ -186,7 +223,10 @@ impl<Id: SiteId> Bytecode<Id> for CtrlInstr<Id> {
             | CtrlInstr::Fn { pos: _ } => None,
             CtrlInstr::Sh { shift: _ }
             | CtrlInstr::ShOvfl { shift: _ }
-            | CtrlInstr::ShFail { shift: _ } => None,
+            | CtrlInstr::ShFail { shift: _ }
+            | CtrlInstr::ShW { shift: _ }
+            | CtrlInstr::ShWOvfl { shift: _ }
+            | CtrlInstr::ShWFail { shift: _ } => None,
             CtrlInstr::Call { site } | CtrlInstr::Exec { site } => Some(site.prog_id),
         }
     }
@@ -210,6 +250,10 @@ impl<Id: SiteId> Bytecode<Id> for CtrlInstr<Id> {
             CtrlInstr::Sh { shift } | CtrlInstr::ShOvfl { shift } | CtrlInstr::ShFail { shift } => {
                 writer.write_byte(shift.to_le_bytes()[0])?
             }
+            CtrlInstr::ShW { shift }
+            | CtrlInstr::ShWOvfl { shift }
+            | CtrlInstr::ShWFail { shift } => writer.write_word(shift as u16)?,
+            CtrlInstr::Save { mask } => writer.write_word(mask)?,
             CtrlInstr::Call { site } | CtrlInstr::Exec { site } => {
                 let site = Site::new(site.prog_id, site.offset);
                 writer.write_ref(site.prog_id)?;
@@ -243,6 +287,12 @@ impl<Id: SiteId> Bytecode<Id> for CtrlInstr<Id> {
             Self::SHNE => CtrlInstr::ShOvfl { shift: i8::from_le_bytes([reader.read_byte()?]) },
             Self::SHFAIL => CtrlInstr::ShFail { shift: i8::from_le_bytes([reader.read_byte()?]) },
 
+            Self::SHW => CtrlInstr::ShW { shift: reader.read_word()? as i16 },
+            Self::SHWNE => CtrlInstr::ShWOvfl { shift: reader.read_word()? as i16 },
+            Self::SHWFAIL => CtrlInstr::ShWFail { shift: reader.read_word()? as i16 },
+
+            Self::SAVE => CtrlInstr::Save { mask: reader.read_word()? },
+
             Self::CALL => {
                 let prog_id = reader.read_ref()?;
                 let offset = reader.read_word()?;
@@ -256,11 +306,41 @@ impl<Id: SiteId> Bytecode<Id> for CtrlInstr<Id> {
                 CtrlInstr::Exec { site }
             }
 
-            _ => unreachable!(),
+            _ => return Err(CodeEofError),
         })
     }
 }
 
+impl<Id: SiteId> OpcodeTable<Id> for CtrlInstr<Id> {
+    fn opcode_info(opcode: u8) -> Option<OpcodeInfo> {
+        let (mnemonic, operands, operand_len) = match opcode {
+            Self::NOP => ("nop", "", 0),
+            Self::NOCO => ("not", "CO", 0),
+            Self::CHCO => ("chk", "CO", 0),
+            Self::CHCK => ("chk", "CK", 0),
+            Self::FAIL => ("fail", "CK", 0),
+            Self::RSET => ("mov", "CO, CK", 0),
+            Self::JMP => ("jmp", "pos", 2),
+            Self::JINE => ("jif", "CO, pos", 2),
+            Self::JIFAIL => ("jif", "CK, pos", 2),
+            Self::SH => ("jmp", "shift", 1),
+            Self::SHNE => ("jif", "CO, shift", 1),
+            Self::SHFAIL => ("jif", "CK, shift", 1),
+            Self::EXEC => ("jmp", "site", 3),
+            Self::FN => ("call", "pos", 2),
+            Self::CALL => ("call", "site", 3),
+            Self::RET => ("ret", "", 0),
+            Self::STOP => ("stop", "", 0),
+            Self::SHW => ("jmp.w", "shift", 2),
+            Self::SHWNE => ("jif.w", "CO, shift", 2),
+            Self::SHWFAIL => ("jif.w", "CK, shift", 2),
+            Self::SAVE => ("save", "mask", 2),
+            _ => return None,
+        };
+        Some(OpcodeInfo { opcode, mnemonic, operands, operand_len, reserved: false })
+    }
+}
+
 #[cfg(test)]
 mod test {
     #![cfg_attr(coverage_nightly, coverage(off))]
@@ -269,7 +349,9 @@ mod test {
     use amplify::confinement::SmallBlob;
 
     use super::*;
-    use crate::library::{LibId, LibsSeg, Marshaller};
+    use crate::aluasm;
+    use crate::isa::Instruction;
+    use crate::library::{LibId, LibsSeg, MarshallError, Marshaller};
 
     const LIB_ID: &str = "5iMb1eHJ-bN5BOe6-9RvBjYL-jF1ELjj-VV7c8Bm-WvFen1Q";
 
@@ -282,7 +364,7 @@ mod test {
         let (code, data) = marshaller.finish();
         assert_eq!(code.len(), instr.code_byte_len() as usize);
         assert_eq!(code.as_slice(), bytecode.as_ref());
-        let mut marshaller = Marshaller::with(code, data, &libs);
+        let mut marshaller = Marshaller::with(code, data, &libs).unwrap();
         let decoded = Instr::<LibId>::decode_instr(&mut marshaller).unwrap();
         assert_eq!(decoded, instr);
         marshaller.into_code_data().1
@@ -290,7 +372,7 @@ mod test {
 
     #[test]
     fn nop() {
-        let instr = Instr::<LibId>::Ctrl(CtrlInstr::Nop);
+        let instr = aluasm! { nop; }[0];
         roundtrip(instr, [CtrlInstr::<LibId>::NOP]);
         assert_eq!(instr.code_byte_len(), 1);
         assert_eq!(instr.opcode_byte(), CtrlInstr::<LibId>::NOP);
@@ -299,7 +381,7 @@ mod test {
 
     #[test]
     fn chk() {
-        let instr = Instr::<LibId>::Ctrl(CtrlInstr::ChkCk);
+        let instr = aluasm! { chk CK; }[0];
         roundtrip(instr, [CtrlInstr::<LibId>::CHCK]);
         assert_eq!(instr.code_byte_len(), 1);
         assert_eq!(instr.opcode_byte(), CtrlInstr::<LibId>::CHCK);
@@ -308,7 +390,7 @@ mod test {
 
     #[test]
     fn not_co() {
-        let instr = Instr::<LibId>::Ctrl(CtrlInstr::NotCo);
+        let instr = aluasm! { not CO; }[0];
         roundtrip(instr, [CtrlInstr::<LibId>::NOCO]);
         assert_eq!(instr.code_byte_len(), 1);
         assert_eq!(instr.opcode_byte(), CtrlInstr::<LibId>::NOCO);
@@ -317,7 +399,7 @@ mod test {
 
     #[test]
     fn fail_ck() {
-        let instr = Instr::<LibId>::Ctrl(CtrlInstr::FailCk);
+        let instr = aluasm! { fail CK; }[0];
         roundtrip(instr, [CtrlInstr::<LibId>::FAIL]);
         assert_eq!(instr.code_byte_len(), 1);
         assert_eq!(instr.opcode_byte(), CtrlInstr::<LibId>::FAIL);
@@ -326,7 +408,7 @@ mod test {
 
     #[test]
     fn reset_ck() {
-        let instr = Instr::<LibId>::Ctrl(CtrlInstr::RsetCk);
+        let instr = aluasm! { mov CO, CK; }[0];
         roundtrip(instr, [CtrlInstr::<LibId>::RSET]);
         assert_eq!(instr.code_byte_len(), 1);
         assert_eq!(instr.opcode_byte(), CtrlInstr::<LibId>::RSET);
@@ -335,7 +417,7 @@ mod test {
 
     #[test]
     fn jmp() {
-        let instr = Instr::<LibId>::Ctrl(CtrlInstr::Jmp { pos: 0x75AE });
+        let instr = aluasm! { jmp 0x75AE; }[0];
         roundtrip(instr, [CtrlInstr::<LibId>::JMP, 0xAE, 0x75]);
         assert_eq!(instr.code_byte_len(), 3);
         assert_eq!(instr.opcode_byte(), CtrlInstr::<LibId>::JMP);
@@ -344,7 +426,7 @@ mod test {
 
     #[test]
     fn jine() {
-        let instr = Instr::<LibId>::Ctrl(CtrlInstr::JiOvfl { pos: 0x75AE });
+        let instr = aluasm! { jif CO, 0x75AE; }[0];
         roundtrip(instr, [CtrlInstr::<LibId>::JINE, 0xAE, 0x75]);
         assert_eq!(instr.code_byte_len(), 3);
         assert_eq!(instr.opcode_byte(), CtrlInstr::<LibId>::JINE);
@@ -353,7 +435,7 @@ mod test {
 
     #[test]
     fn jifail() {
-        let instr = Instr::<LibId>::Ctrl(CtrlInstr::JiFail { pos: 0x75AE });
+        let instr = aluasm! { jif CK, 0x75AE; }[0];
         roundtrip(instr, [CtrlInstr::<LibId>::JIFAIL, 0xAE, 0x75]);
         assert_eq!(instr.code_byte_len(), 3);
         assert_eq!(instr.opcode_byte(), CtrlInstr::<LibId>::JIFAIL);
@@ -362,7 +444,7 @@ mod test {
 
     #[test]
     fn sh() {
-        let instr = Instr::<LibId>::Ctrl(CtrlInstr::Sh { shift: -0x5 });
+        let instr = aluasm! { jmp -0x5; }[0];
         roundtrip(instr, [CtrlInstr::<LibId>::SH, 255 - 5 + 1]);
         assert_eq!(instr.code_byte_len(), 2);
         assert_eq!(instr.opcode_byte(), CtrlInstr::<LibId>::SH);
@@ -371,7 +453,7 @@ mod test {
 
     #[test]
     fn shne() {
-        let instr = Instr::<LibId>::Ctrl(CtrlInstr::ShOvfl { shift: -0x5 });
+        let instr = aluasm! { jif CO, -0x5; }[0];
         roundtrip(instr, [CtrlInstr::<LibId>::SHNE, 255 - 5 + 1]);
         assert_eq!(instr.code_byte_len(), 2);
         assert_eq!(instr.opcode_byte(), CtrlInstr::<LibId>::SHNE);
@@ -380,17 +462,53 @@ mod test {
 
     #[test]
     fn shfail() {
-        let instr = Instr::<LibId>::Ctrl(CtrlInstr::ShFail { shift: -0x5 });
+        let instr = aluasm! { jif CK, -0x5; }[0];
         roundtrip(instr, [CtrlInstr::<LibId>::SHFAIL, 255 - 5 + 1]);
         assert_eq!(instr.code_byte_len(), 2);
         assert_eq!(instr.opcode_byte(), CtrlInstr::<LibId>::SHFAIL);
         assert_eq!(instr.external_ref(), None);
     }
 
+    #[test]
+    fn shw() {
+        let code = aluasm! {
+            jmp.w   -0x1234;
+            jif.w   CO, -0x1234;
+            jif.w   CK, -0x1234;
+        };
+        let opcodes =
+            [CtrlInstr::<LibId>::SHW, CtrlInstr::<LibId>::SHWNE, CtrlInstr::<LibId>::SHWFAIL];
+        for (instr, opcode) in code.into_iter().zip(opcodes) {
+            roundtrip(instr, [opcode, 0xCC, 0xED]);
+            assert_eq!(instr.code_byte_len(), 3);
+            assert_eq!(instr.opcode_byte(), opcode);
+            assert_eq!(instr.external_ref(), None);
+        }
+        roundtrip(CtrlInstr::ShW { shift: i16::MAX }, [CtrlInstr::<LibId>::SHW, 0xFF, 0x7F]);
+        roundtrip(CtrlInstr::ShW { shift: i16::MIN }, [CtrlInstr::<LibId>::SHW, 0x00, 0x80]);
+    }
+
+    #[test]
+    fn boundary_opcodes() {
+        let libs = LibsSeg::new();
+        let decode = |opcode: u8| {
+            let mut marshaller = Marshaller::with([opcode, 0x01, 0x00], [], &libs).unwrap();
+            Instr::<LibId>::decode_instr(&mut marshaller).unwrap()
+        };
+        assert_eq!(CtrlInstr::<LibId>::END, CtrlInstr::<LibId>::SAVE);
+        assert_eq!(decode(CtrlInstr::<LibId>::STOP), CtrlInstr::Stop.into());
+        assert_eq!(decode(CtrlInstr::<LibId>::SHW), CtrlInstr::ShW { shift: 1 }.into());
+        assert_eq!(decode(CtrlInstr::<LibId>::SHWFAIL), CtrlInstr::ShWFail { shift: 1 }.into());
+        assert_eq!(decode(CtrlInstr::<LibId>::SAVE), CtrlInstr::Save { mask: 1 }.into());
+        let reserved = CtrlInstr::<LibId>::END + 1;
+        assert_eq!(decode(reserved), ReservedInstr(reserved).into());
+        assert_eq!(OPCODE_TABLE[reserved as usize], IsaMember::Reserved);
+    }
+
     #[test]
     fn exec() {
         let lib_id = LibId::from_str(LIB_ID).unwrap();
-        let instr = Instr::<LibId>::Ctrl(CtrlInstr::Exec { site: Site::new(lib_id, 0x69AB) });
+        let instr = aluasm! { jmp lib_id@0x69AB; }[0];
         roundtrip(instr, [CtrlInstr::<LibId>::EXEC, 0x00, 0xAB, 0x69]);
         assert_eq!(instr.code_byte_len(), 4);
         assert_eq!(instr.opcode_byte(), CtrlInstr::<LibId>::EXEC);
@@ -399,7 +517,7 @@ mod test {
 
     #[test]
     fn func() {
-        let instr = Instr::<LibId>::Ctrl(CtrlInstr::Fn { pos: 0x75AE });
+        let instr = aluasm! { call 0x75AE; }[0];
         roundtrip(instr, [CtrlInstr::<LibId>::FN, 0xAE, 0x75]);
         assert_eq!(instr.code_byte_len(), 3);
         assert_eq!(instr.opcode_byte(), CtrlInstr::<LibId>::FN);
@@ -409,16 +527,32 @@ mod test {
     #[test]
     fn call() {
         let lib_id = LibId::from_str(LIB_ID).unwrap();
-        let instr = Instr::<LibId>::Ctrl(CtrlInstr::Call { site: Site::new(lib_id, 0x69AB) });
+        let instr = aluasm! { call lib_id@0x69AB; }[0];
         roundtrip(instr, [CtrlInstr::<LibId>::CALL, 0x00, 0xAB, 0x69]);
         assert_eq!(instr.code_byte_len(), 4);
         assert_eq!(instr.opcode_byte(), CtrlInstr::<LibId>::CALL);
         assert_eq!(instr.external_ref(), Some(lib_id));
     }
 
+    #[test]
+    fn call_lib_ref_out_of_bounds() {
+        let mut libs = LibsSeg::new();
+        libs.push(LibId::from_str(LIB_ID).unwrap()).unwrap();
+        for opcode in [CtrlInstr::<LibId>::CALL, CtrlInstr::<LibId>::EXEC] {
+            let mut marshaller = Marshaller::with([opcode, 0x01, 0xAB, 0x69], [], &libs).unwrap();
+            assert_eq!(Instr::<LibId>::decode_instr(&mut marshaller), Err(CodeEofError));
+            assert_eq!(marshaller.lib_ref(1), Err(MarshallError::LibRefOutOfBounds(1)));
+
+            let no_libs = LibsSeg::new();
+            let mut marshaller =
+                Marshaller::with([opcode, 0x00, 0xAB, 0x69], [], &no_libs).unwrap();
+            assert_eq!(Instr::<LibId>::decode_instr(&mut marshaller), Err(CodeEofError));
+        }
+    }
+
     #[test]
     fn ret() {
-        let instr = Instr::<LibId>::Ctrl(CtrlInstr::Ret);
+        let instr = aluasm! { ret; }[0];
         roundtrip(instr, [CtrlInstr::<LibId>::RET]);
         assert_eq!(instr.code_byte_len(), 1);
         assert_eq!(instr.opcode_byte(), CtrlInstr::<LibId>::RET);
@@ -427,20 +561,107 @@ mod test {
 
     #[test]
     fn stop() {
-        let instr = Instr::<LibId>::Ctrl(CtrlInstr::Stop);
+        let instr = aluasm! { stop; }[0];
         roundtrip(instr, [CtrlInstr::<LibId>::STOP]);
         assert_eq!(instr.code_byte_len(), 1);
         assert_eq!(instr.opcode_byte(), CtrlInstr::<LibId>::STOP);
         assert_eq!(instr.external_ref(), None);
     }
 
+    #[test]
+    fn save() {
+        let instr = aluasm! { save 0x80F1; }[0];
+        roundtrip(instr, [CtrlInstr::<LibId>::SAVE, 0xF1, 0x80]);
+        assert_eq!(instr.code_byte_len(), 3);
+        assert_eq!(instr.opcode_byte(), CtrlInstr::<LibId>::SAVE);
+        assert_eq!(instr.external_ref(), None);
+        assert_eq!(instr.to_string(), "save    0x80F1.h");
+    }
+
     #[test]
     fn reserved() {
-        let instr = Instr::<LibId>::Reserved(default!());
+        let instr = aluasm! { halt; }[0];
         roundtrip(instr, [0xFF]);
 
         assert_eq!(instr.code_byte_len(), 1);
         assert_eq!(instr.opcode_byte(), 0xFF);
         assert_eq!(instr.external_ref(), None);
     }
+
+    #[test]
+    fn opcode_table() {
+        for op in 0..=0xFFu8 {
+            let member = OPCODE_TABLE[op as usize];
+            if CtrlInstr::<LibId>::op_range().contains(&op) {
+                assert_eq!(member, IsaMember::Ctrl);
+            } else {
+                assert_eq!(member, IsaMember::Reserved);
+            }
+        }
+    }
+
+    #[test]
+    fn opcode_table_decode() {
+        fn decode_range_chain(
+            reader: &mut impl BytecodeRead<LibId>,
+            opcode: u8,
+        ) -> Result<Instr<LibId>, CodeEofError> {
+            match opcode {
+                op if CtrlInstr::<LibId>::op_range().contains(&op) => {
+                    CtrlInstr::decode_operands(reader, op).map(Instr::Ctrl)
+                }
+                _ => ReservedInstr::decode_operands(reader, opcode).map(Instr::Reserved),
+            }
+        }
+
+        let libs = LibsSeg::new();
+        for op in 0..=0xFFu8 {
+            let mut table = Marshaller::with([0u8; 8], [], &libs).unwrap();
+            let mut chain = Marshaller::with([0u8; 8], [], &libs).unwrap();
+            assert_eq!(
+                Instr::<LibId>::decode_operands(&mut table, op),
+                decode_range_chain(&mut chain, op)
+            );
+            assert_eq!(table.offset(), chain.offset());
+        }
+    }
+
+    #[test]
+    fn foreign_opcodes() {
+        let libs = LibsSeg::new();
+        for op in CtrlInstr::<LibId>::END + 1..=0xFF {
+            let mut marshaller = Marshaller::with([0u8; 8], [], &libs).unwrap();
+            assert_eq!(CtrlInstr::<LibId>::decode_operands(&mut marshaller, op), Err(CodeEofError));
+        }
+    }
+
+    #[test]
+    fn opcode_info() {
+        let table = <Instr<LibId> as OpcodeTable<LibId>>::opcode_table();
+        assert_eq!(table.len(), 256);
+        assert_eq!(CtrlInstr::<LibId>::opcode_table().len(), 21);
+        assert_eq!(<ReservedInstr as OpcodeTable<LibId>>::opcode_table().len(), 0x80);
+
+        let mut libs = LibsSeg::new();
+        libs.push(LibId::from_str(LIB_ID).unwrap()).unwrap();
+        for (op, info) in table.into_iter().enumerate() {
+            assert_eq!(info.opcode as usize, op);
+            let mut code = [0u8; 8];
+            code[0] = info.opcode;
+            let mut marshaller = Marshaller::with(code, [], &libs).unwrap();
+            let instr = Instr::<LibId>::decode_instr(&mut marshaller).unwrap();
+            assert_eq!(instr.opcode_byte(), info.opcode);
+            assert_eq!(instr.code_byte_len(), info.operand_len + 1, "{info:?}");
+            assert_eq!(marshaller.pos(), info.operand_len + 1, "{info:?}");
+            assert_eq!(instr.is_reserved(), info.reserved);
+            let text = instr.to_string();
+            let (mnemonic, operands) = text.split_once(' ').unwrap_or((&text, ""));
+            assert_eq!(mnemonic, info.mnemonic);
+            // Reserved instructions have no operands, but display their opcode
+            if !info.reserved {
+                assert_eq!(operands.is_empty(), info.operands.is_empty(), "{info:?}");
+            }
+            assert_eq!(CtrlInstr::<LibId>::opcode_info(info.opcode).is_some(), !info.reserved);
+        }
+    }
 }
@@ -25,8 +25,13 @@
 use alloc::collections::BTreeSet;
 
 use super::CtrlInstr;
-use crate::core::{Core, NoExt, NoRegs, Site, SiteId, Status};
-use crate::isa::{ExecStep, GotoTarget, Instr, Instruction, ReservedInstr};
+use crate::core::{
+    Core, CoreExt, FailureReason, NoExt, NoRegs, Site, SiteId, Status, TerminationCause,
+    UnknownInstrPolicy,
+};
+use crate::isa::{
+    ComplexityClass, ExecStep, FlowKind, GotoTarget, Instr, Instruction, ReservedInstr,
+};
 
 impl<Id: SiteId> Instruction<Id> for Instr<Id> {
     const ISA_EXT: &'static [&'static str] = &[];
@@ -34,6 +39,8 @@ impl<Id: SiteId> Instruction<Id> for Instr<Id> {
     type Core = NoExt;
     type Context<'ctx> = ();
 
+    fn is_reserved(&self) -> bool { matches!(self, Instr::Reserved(_)) }
+
     fn is_goto_target(&self) -> bool {
         match self {
             Instr::Ctrl(instr) => instr.is_goto_target(),
@@ -41,7 +48,7 @@ impl<Id: SiteId> Instruction<Id> for Instr<Id> {
         }
     }
 
-    fn local_goto_pos(&mut self) -> GotoTarget {
+    fn local_goto_pos(&mut self) -> GotoTarget<'_> {
         match self {
             Instr::Ctrl(instr) => instr.local_goto_pos(),
             Instr::Reserved(instr) => Instruction::<Id>::local_goto_pos(instr),
@@ -55,6 +62,20 @@ impl<Id: SiteId> Instruction<Id> for Instr<Id> {
         }
     }
 
+    fn localize_goto(&self, pos: u16) -> Option<Self> {
+        match self {
+            Instr::Ctrl(instr) => instr.localize_goto(pos).map(Instr::Ctrl),
+            Instr::Reserved(_) => None,
+        }
+    }
+
+    fn flow_kind(&self) -> FlowKind {
+        match self {
+            Instr::Ctrl(instr) => instr.flow_kind(),
+            Instr::Reserved(instr) => Instruction::<Id>::flow_kind(instr),
+        }
+    }
+
     fn src_regs(&self) -> BTreeSet<NoRegs> {
         match self {
             Instr::Ctrl(instr) => instr.src_regs(),
@@ -83,17 +104,17 @@ impl<Id: SiteId> Instruction<Id> for Instr<Id> {
         }
     }
 
-    fn complexity(&self) -> u64 {
+    fn complexity_class(&self) -> ComplexityClass {
         match self {
-            Instr::Ctrl(instr) => instr.complexity(),
-            Instr::Reserved(instr) => Instruction::<Id>::complexity(instr),
+            Instr::Ctrl(instr) => instr.complexity_class(),
+            Instr::Reserved(instr) => Instruction::<Id>::complexity_class(instr),
         }
     }
 
-    fn exec(
+    fn exec<const CALL_STACK_SIZE: usize>(
         &self,
         site: Site<Id>,
-        core: &mut Core<Id, Self::Core>,
+        core: &mut Core<Id, Self::Core, CALL_STACK_SIZE>,
         _: &Self::Context<'_>,
     ) -> ExecStep<Site<Id>> {
         match self {
@@ -109,12 +130,16 @@ impl<Id: SiteId> Instruction<Id> for ReservedInstr {
     type Core = NoExt;
     type Context<'ctx> = ();
 
+    fn is_reserved(&self) -> bool { true }
+
     fn is_goto_target(&self) -> bool { false }
 
-    fn local_goto_pos(&mut self) -> GotoTarget { GotoTarget::None }
+    fn local_goto_pos(&mut self) -> GotoTarget<'_> { GotoTarget::None }
 
     fn remote_goto_pos(&mut self) -> Option<&mut Site<Id>> { None }
 
+    fn flow_kind(&self) -> FlowKind { FlowKind::Continue }
+
     fn src_regs(&self) -> BTreeSet<NoRegs> { none!() }
 
     fn dst_regs(&self) -> BTreeSet<NoRegs> { none!() }
@@ -123,15 +148,31 @@ impl<Id: SiteId> Instruction<Id> for ReservedInstr {
 
     fn ext_data_bytes(&self) -> u16 { none!() }
 
-    fn complexity(&self) -> u64 { u64::MAX }
+    fn complexity_class(&self) -> ComplexityClass { ComplexityClass::Custom(u64::MAX) }
 
-    fn exec(
+    fn exec<const CALL_STACK_SIZE: usize>(
         &self,
         _: Site<Id>,
-        _: &mut Core<Id, Self::Core>,
+        core: &mut Core<Id, Self::Core, CALL_STACK_SIZE>,
         _: &Self::Context<'_>,
     ) -> ExecStep<Site<Id>> {
-        ExecStep::Fail
+        Self::exec_step(core)
+    }
+}
+
+impl ReservedInstr {
+    /// Returns the result of a reserved instruction execution under the core policy.
+    pub(crate) fn exec_step<Id: SiteId, Cx: CoreExt, const CALL_STACK_SIZE: usize>(
+        core: &mut Core<Id, Cx, CALL_STACK_SIZE>,
+    ) -> ExecStep<Site<Id>> {
+        match core.unknown_instr() {
+            UnknownInstrPolicy::Fail => {
+                core.set_failure(FailureReason::ReservedInstr);
+                ExecStep::Fail
+            }
+            UnknownInstrPolicy::Nop => ExecStep::Next,
+            UnknownInstrPolicy::Halt => ExecStep::Stop,
+        }
     }
 }
 
@@ -151,12 +192,14 @@ impl<Id: SiteId> Instruction<Id> for CtrlInstr<Id> {
             | CtrlInstr::RsetCk => false,
             CtrlInstr::Jmp { .. } | CtrlInstr::JiOvfl { .. } | CtrlInstr::JiFail { .. } => false,
             CtrlInstr::Sh { .. } | CtrlInstr::ShOvfl { .. } | CtrlInstr::ShFail { .. } => false,
+            CtrlInstr::ShW { .. } | CtrlInstr::ShWOvfl { .. } | CtrlInstr::ShWFail { .. } => false,
             CtrlInstr::Exec { .. } | CtrlInstr::Fn { .. } | CtrlInstr::Call { .. } => false,
             CtrlInstr::Ret | CtrlInstr::Stop => false,
+            CtrlInstr::Save { .. } => false,
         }
     }
 
-    fn local_goto_pos(&mut self) -> GotoTarget {
+    fn local_goto_pos(&mut self) -> GotoTarget<'_> {
         match self {
             CtrlInstr::Nop
             | CtrlInstr::ChkCo
@@ -171,8 +214,12 @@ impl<Id: SiteId> Instruction<Id> for CtrlInstr<Id> {
             CtrlInstr::Sh { shift } | CtrlInstr::ShOvfl { shift } | CtrlInstr::ShFail { shift } => {
                 GotoTarget::Relative(shift)
             }
+            CtrlInstr::ShW { shift }
+            | CtrlInstr::ShWOvfl { shift }
+            | CtrlInstr::ShWFail { shift } => GotoTarget::RelativeWide(shift),
             CtrlInstr::Exec { site: _ } | CtrlInstr::Call { site: _ } => GotoTarget::None,
             CtrlInstr::Ret | CtrlInstr::Stop => GotoTarget::None,
+            CtrlInstr::Save { mask: _ } => GotoTarget::None,
         }
     }
 
@@ -191,8 +238,44 @@ impl<Id: SiteId> Instruction<Id> for CtrlInstr<Id> {
             CtrlInstr::Sh { shift: _ }
             | CtrlInstr::ShOvfl { shift: _ }
             | CtrlInstr::ShFail { shift: _ } => None,
+            CtrlInstr::ShW { shift: _ }
+            | CtrlInstr::ShWOvfl { shift: _ }
+            | CtrlInstr::ShWFail { shift: _ } => None,
             CtrlInstr::Exec { site } | CtrlInstr::Call { site } => Some(site),
             CtrlInstr::Ret | CtrlInstr::Stop => None,
+            CtrlInstr::Save { mask: _ } => None,
+        }
+    }
+
+    fn localize_goto(&self, pos: u16) -> Option<Self> {
+        match self {
+            CtrlInstr::Exec { site: _ } => Some(CtrlInstr::Jmp { pos }),
+            CtrlInstr::Call { site: _ } => Some(CtrlInstr::Fn { pos }),
+            _ => None,
+        }
+    }
+
+    fn flow_kind(&self) -> FlowKind {
+        match self {
+            CtrlInstr::Nop
+            | CtrlInstr::ChkCo
+            | CtrlInstr::ChkCk
+            | CtrlInstr::NotCo
+            | CtrlInstr::FailCk
+            | CtrlInstr::RsetCk
+            | CtrlInstr::Save { .. } => FlowKind::Continue,
+            CtrlInstr::Jmp { .. }
+            | CtrlInstr::Sh { .. }
+            | CtrlInstr::ShW { .. }
+            | CtrlInstr::Exec { .. } => FlowKind::Jump,
+            CtrlInstr::JiOvfl { .. }
+            | CtrlInstr::JiFail { .. }
+            | CtrlInstr::ShOvfl { .. }
+            | CtrlInstr::ShFail { .. }
+            | CtrlInstr::ShWOvfl { .. }
+            | CtrlInstr::ShWFail { .. } => FlowKind::Branch,
+            CtrlInstr::Fn { .. } | CtrlInstr::Call { .. } => FlowKind::Call,
+            CtrlInstr::Ret | CtrlInstr::Stop => FlowKind::Halt,
         }
     }
 
@@ -210,10 +293,12 @@ impl<Id: SiteId> Instruction<Id> for CtrlInstr<Id> {
             | CtrlInstr::RsetCk => 0,
             CtrlInstr::Jmp { .. } | CtrlInstr::JiOvfl { .. } | CtrlInstr::JiFail { .. } => 2,
             CtrlInstr::Sh { .. } | CtrlInstr::ShOvfl { .. } | CtrlInstr::ShFail { .. } => 1,
+            CtrlInstr::ShW { .. } | CtrlInstr::ShWOvfl { .. } | CtrlInstr::ShWFail { .. } => 2,
             CtrlInstr::Exec { .. } => 2,
             CtrlInstr::Fn { .. } => 2,
             CtrlInstr::Call { .. } => 2,
             CtrlInstr::Ret | CtrlInstr::Stop => 0,
+            CtrlInstr::Save { .. } => 2,
         }
     }
 
@@ -227,43 +312,50 @@ impl<Id: SiteId> Instruction<Id> for CtrlInstr<Id> {
             | CtrlInstr::RsetCk => 0,
             CtrlInstr::Jmp { .. } | CtrlInstr::JiOvfl { .. } | CtrlInstr::JiFail { .. } => 0,
             CtrlInstr::Sh { .. } | CtrlInstr::ShOvfl { .. } | CtrlInstr::ShFail { .. } => 0,
+            CtrlInstr::ShW { .. } | CtrlInstr::ShWOvfl { .. } | CtrlInstr::ShWFail { .. } => 0,
+            // The one-byte operand is resolved into a 32-byte library id from the libs segment
             CtrlInstr::Exec { .. } => 32,
             CtrlInstr::Fn { .. } => 0,
             CtrlInstr::Call { .. } => 32,
             CtrlInstr::Ret | CtrlInstr::Stop => 0,
+            CtrlInstr::Save { .. } => 0,
         }
     }
 
-    fn complexity(&self) -> u64 {
-        let complexity = match self {
-            CtrlInstr::Nop => 0,
+    fn complexity_class(&self) -> ComplexityClass {
+        match self {
+            CtrlInstr::Nop => ComplexityClass::Trivial,
             CtrlInstr::ChkCo
             | CtrlInstr::ChkCk
             | CtrlInstr::NotCo
             | CtrlInstr::FailCk
-            | CtrlInstr::RsetCk => 2,
-            CtrlInstr::Jmp { .. } | CtrlInstr::Sh { .. } => 10,
+            | CtrlInstr::RsetCk => ComplexityClass::Light,
+            CtrlInstr::Jmp { .. } | CtrlInstr::Sh { .. } | CtrlInstr::ShW { .. } => {
+                ComplexityClass::Medium
+            }
             CtrlInstr::JiOvfl { .. }
             | CtrlInstr::JiFail { .. }
             | CtrlInstr::ShOvfl { .. }
-            | CtrlInstr::ShFail { .. } => 20,
-            CtrlInstr::Exec { .. } => return self.base_complexity() + 20_000,
-            CtrlInstr::Fn { .. } => 30,
-            CtrlInstr::Call { .. } => return self.base_complexity() + 20_000,
-            CtrlInstr::Ret => 20,
-            CtrlInstr::Stop => 0,
-        };
-        complexity * 1000
+            | CtrlInstr::ShFail { .. }
+            | CtrlInstr::ShWOvfl { .. }
+            | CtrlInstr::ShWFail { .. } => ComplexityClass::Heavy,
+            CtrlInstr::Exec { .. } | CtrlInstr::Call { .. } => ComplexityClass::External,
+            CtrlInstr::Fn { .. } => ComplexityClass::Call,
+            CtrlInstr::Ret => ComplexityClass::Heavy,
+            CtrlInstr::Stop => ComplexityClass::Trivial,
+            CtrlInstr::Save { .. } => ComplexityClass::Heavy,
+        }
     }
 
-    fn exec(
+    fn exec<const CALL_STACK_SIZE: usize>(
         &self,
         cursor: Site<Id>,
-        core: &mut Core<Id, Self::Core>,
+        core: &mut Core<Id, Self::Core, CALL_STACK_SIZE>,
         _: &Self::Context<'_>,
     ) -> ExecStep<Site<Id>> {
-        let shift_jump = |shift: i8| {
-            let Some(pos) = cursor.offset.checked_add_signed(shift as i16) else {
+        let shift_jump = |core: &mut Core<Id, NoExt, CALL_STACK_SIZE>, shift: i16| {
+            let Some(pos) = cursor.offset.checked_add_signed(shift) else {
+                core.set_failure(FailureReason::JumpOffsetOverflow);
                 return ExecStep::Fail;
             };
             ExecStep::Jump(pos)
@@ -273,16 +365,19 @@ impl<Id: SiteId> Instruction<Id> for CtrlInstr<Id> {
             CtrlInstr::Nop => {}
             CtrlInstr::ChkCo => {
                 if !core.co().is_ok() {
+                    core.set_failure(FailureReason::CheckFailed);
                     return ExecStep::Fail;
                 }
             }
             CtrlInstr::ChkCk => {
                 if !core.ck().is_ok() {
+                    core.set_termination(TerminationCause::CheckFailed { site: cursor });
                     return ExecStep::Stop;
                 }
             }
             CtrlInstr::FailCk => {
-                if core.fail_ck() {
+                if core.fail_ck_with(FailureReason::Explicit) {
+                    core.set_termination(TerminationCause::Halted { site: cursor });
                     return ExecStep::Stop;
                 }
             }
@@ -303,38 +398,69 @@ impl<Id: SiteId> Instruction<Id> for CtrlInstr<Id> {
                 }
             }
             CtrlInstr::Sh { shift } => {
-                return shift_jump(shift);
+                return shift_jump(core, shift as i16);
             }
             CtrlInstr::ShOvfl { shift } => {
                 if core.co() == Status::Fail {
-                    return shift_jump(shift);
+                    return shift_jump(core, shift as i16);
                 }
             }
             CtrlInstr::ShFail { shift } => {
                 if core.ck() == Status::Fail {
-                    return shift_jump(shift);
+                    return shift_jump(core, shift as i16);
+                }
+            }
+            CtrlInstr::ShW { shift } => {
+                return shift_jump(core, shift);
+            }
+            CtrlInstr::ShWOvfl { shift } => {
+                if core.co() == Status::Fail {
+                    return shift_jump(core, shift);
+                }
+            }
+            CtrlInstr::ShWFail { shift } => {
+                if core.ck() == Status::Fail {
+                    return shift_jump(core, shift);
                 }
             }
             CtrlInstr::Exec { site } => return ExecStep::Call(site),
             CtrlInstr::Fn { pos } => {
                 return match core.push_cs(cursor) {
                     Some(_) => ExecStep::Jump(pos),
-                    None => ExecStep::Fail,
+                    None => {
+                        core.set_failure(FailureReason::CallStackOverflow);
+                        ExecStep::Fail
+                    }
                 }
             }
             CtrlInstr::Call { site } => {
-                return match core.push_cs(cursor) {
+                return match core.push_xcs(cursor, site.prog_id) {
                     Some(_) => ExecStep::Call(site),
-                    None => ExecStep::Fail,
+                    None => {
+                        core.set_failure(FailureReason::CallStackOverflow);
+                        ExecStep::Fail
+                    }
                 }
             }
             CtrlInstr::Ret => {
-                return match core.pop_cs() {
+                return match core.pop_xcs(cursor.prog_id) {
                     Some(site) => ExecStep::Ret(site),
-                    None => ExecStep::Stop,
+                    None => {
+                        core.set_termination(TerminationCause::ReturnFromMain);
+                        ExecStep::Stop
+                    }
+                }
+            }
+            CtrlInstr::Stop => {
+                core.set_termination(TerminationCause::ExplicitStop);
+                return ExecStep::Stop;
+            }
+            CtrlInstr::Save { mask } => {
+                if core.push_ss(mask).is_none() {
+                    core.set_failure(FailureReason::SaveStackOverflow);
+                    return ExecStep::Fail;
                 }
             }
-            CtrlInstr::Stop => return ExecStep::Stop,
         }
         ExecStep::Next
     }
@@ -347,7 +473,8 @@ mod test {
     use core::str::FromStr;
 
     use super::*;
-    use crate::core::Site;
+    use crate::core::{CoreConfig, Site};
+    use crate::isa::ComplexityModel;
     use crate::LibId;
 
     const LIB_ID: &str = "5iMb1eHJ-bN5BOe6-9RvBjYL-jF1ELjj-VV7c8Bm-WvFen1Q";
@@ -529,6 +656,54 @@ mod test {
         assert_eq!(instr.complexity(), 20_000);
     }
 
+    #[test]
+    fn shw() {
+        let mut instr = Instr::<LibId>::Ctrl(CtrlInstr::ShW { shift: -0x1234 });
+        assert_eq!(instr.is_goto_target(), false);
+        assert_eq!(instr.local_goto_pos(), GotoTarget::RelativeWide(&mut -0x1234));
+        assert_eq!(instr.remote_goto_pos(), None);
+        assert_eq!(instr.regs(), none!());
+        assert_eq!(instr.src_regs(), none!());
+        assert_eq!(instr.dst_regs(), none!());
+        assert_eq!(instr.src_reg_bytes(), 0);
+        assert_eq!(instr.dst_reg_bytes(), 0);
+        assert_eq!(instr.op_data_bytes(), 2);
+        assert_eq!(instr.ext_data_bytes(), 0);
+        assert_eq!(instr.complexity(), 10_000);
+    }
+
+    #[test]
+    fn shwne() {
+        let mut instr = Instr::<LibId>::Ctrl(CtrlInstr::ShWOvfl { shift: -0x1234 });
+        assert_eq!(instr.is_goto_target(), false);
+        assert_eq!(instr.local_goto_pos(), GotoTarget::RelativeWide(&mut -0x1234));
+        assert_eq!(instr.remote_goto_pos(), None);
+        assert_eq!(instr.regs(), none!());
+        assert_eq!(instr.src_regs(), none!());
+        assert_eq!(instr.dst_regs(), none!());
+        assert_eq!(instr.src_reg_bytes(), 0);
+        assert_eq!(instr.dst_reg_bytes(), 0);
+        assert_eq!(instr.op_data_bytes(), 2);
+        assert_eq!(instr.ext_data_bytes(), 0);
+        assert_eq!(instr.complexity(), 20_000);
+    }
+
+    #[test]
+    fn shwfail() {
+        let mut instr = Instr::<LibId>::Ctrl(CtrlInstr::ShWFail { shift: -0x1234 });
+        assert_eq!(instr.is_goto_target(), false);
+        assert_eq!(instr.local_goto_pos(), GotoTarget::RelativeWide(&mut -0x1234));
+        assert_eq!(instr.remote_goto_pos(), None);
+        assert_eq!(instr.regs(), none!());
+        assert_eq!(instr.src_regs(), none!());
+        assert_eq!(instr.dst_regs(), none!());
+        assert_eq!(instr.src_reg_bytes(), 0);
+        assert_eq!(instr.dst_reg_bytes(), 0);
+        assert_eq!(instr.op_data_bytes(), 2);
+        assert_eq!(instr.ext_data_bytes(), 0);
+        assert_eq!(instr.complexity(), 20_000);
+    }
+
     #[test]
     fn exec() {
         let lib_id = LibId::from_str(LIB_ID).unwrap();
@@ -613,6 +788,23 @@ mod test {
         assert_eq!(instr.complexity(), 0);
     }
 
+    #[test]
+    fn save() {
+        let mut instr = Instr::<LibId>::Ctrl(CtrlInstr::Save { mask: 0x00FF });
+        assert_eq!(instr.is_goto_target(), false);
+        assert_eq!(instr.local_goto_pos(), GotoTarget::None);
+        assert_eq!(instr.remote_goto_pos(), None);
+        assert_eq!(instr.flow_kind(), FlowKind::Continue);
+        assert_eq!(instr.regs(), none!());
+        assert_eq!(instr.src_regs(), none!());
+        assert_eq!(instr.dst_regs(), none!());
+        assert_eq!(instr.src_reg_bytes(), 0);
+        assert_eq!(instr.dst_reg_bytes(), 0);
+        assert_eq!(instr.op_data_bytes(), 2);
+        assert_eq!(instr.ext_data_bytes(), 0);
+        assert_eq!(instr.complexity(), 20_000);
+    }
+
     #[test]
     fn reserved() {
         let mut instr = Instr::<LibId>::Reserved(default!());
@@ -628,4 +820,137 @@ mod test {
         assert_eq!(instr.ext_data_bytes(), 0);
         assert_eq!(instr.complexity(), u64::MAX);
     }
+
+    #[test]
+    fn failure_reasons() {
+        let site = Site::new(LibId::from_str(LIB_ID).unwrap(), 0);
+        let failed = |instr: Instr<LibId>, core: &mut Core<LibId, NoExt, 1>| {
+            assert_eq!(instr.exec(site, core, &()), ExecStep::Fail, "{instr}");
+            assert!(!core.fail_ck());
+            core.last_failure().cloned()
+        };
+
+        let mut core = Core::<LibId, NoExt, 1>::with(CoreConfig { halt: false, ..default!() }, ());
+        core.set_co(Status::Fail);
+        let reason = failed(Instr::Ctrl(CtrlInstr::ChkCo), &mut core);
+        assert_eq!(reason, Some(FailureReason::CheckFailed));
+        let reason = failed(Instr::Ctrl(CtrlInstr::Sh { shift: -1 }), &mut core);
+        assert_eq!(reason, Some(FailureReason::JumpOffsetOverflow));
+        let reason = failed(Instr::Reserved(default!()), &mut core);
+        assert_eq!(reason, Some(FailureReason::ReservedInstr));
+
+        // `FailCk` fails `CK` on its own, such that the reason gets recorded immediately
+        assert_eq!(Instr::Ctrl(CtrlInstr::FailCk).exec(site, &mut core, &()), ExecStep::Next);
+        assert_eq!(core.last_failure(), Some(&FailureReason::Explicit));
+
+        assert!(core.push_cs(site).is_some());
+        let reason = failed(Instr::Ctrl(CtrlInstr::Fn { pos: 0 }), &mut core);
+        assert_eq!(reason, Some(FailureReason::CallStackOverflow));
+
+        // The save stack has the same capacity as the call stack
+        let save = Instr::Ctrl(CtrlInstr::Save { mask: 1 });
+        assert_eq!(save.exec(site, &mut core, &()), ExecStep::Next);
+        assert_eq!(core.save_stack(), &[(1, 1)]);
+        let reason = failed(save, &mut core);
+        assert_eq!(reason, Some(FailureReason::SaveStackOverflow));
+        assert_eq!(core.save_stack(), &[(1, 1)]);
+        assert!(core.pop_cs().is_some());
+        assert_eq!(core.save_stack(), &[]);
+    }
+
+    #[test]
+    fn complexity_classes() {
+        let lib_id = LibId::from_str(LIB_ID).unwrap();
+        let site = Site::new(lib_id, 0x69AB);
+        let corpus: [(CtrlInstr<LibId>, ComplexityClass, u64); 21] = [
+            (CtrlInstr::Nop, ComplexityClass::Trivial, 0),
+            (CtrlInstr::ChkCo, ComplexityClass::Light, 2_000),
+            (CtrlInstr::ChkCk, ComplexityClass::Light, 2_000),
+            (CtrlInstr::NotCo, ComplexityClass::Light, 2_000),
+            (CtrlInstr::FailCk, ComplexityClass::Light, 2_000),
+            (CtrlInstr::RsetCk, ComplexityClass::Light, 2_000),
+            (CtrlInstr::Jmp { pos: 0 }, ComplexityClass::Medium, 10_000),
+            (CtrlInstr::JiOvfl { pos: 0 }, ComplexityClass::Heavy, 20_000),
+            (CtrlInstr::JiFail { pos: 0 }, ComplexityClass::Heavy, 20_000),
+            (CtrlInstr::Sh { shift: 0 }, ComplexityClass::Medium, 10_000),
+            (CtrlInstr::ShOvfl { shift: 0 }, ComplexityClass::Heavy, 20_000),
+            (CtrlInstr::ShFail { shift: 0 }, ComplexityClass::Heavy, 20_000),
+            (CtrlInstr::ShW { shift: 0 }, ComplexityClass::Medium, 10_000),
+            (CtrlInstr::ShWOvfl { shift: 0 }, ComplexityClass::Heavy, 20_000),
+            (CtrlInstr::ShWFail { shift: 0 }, ComplexityClass::Heavy, 20_000),
+            (CtrlInstr::Exec { site }, ComplexityClass::External, 548_000),
+            (CtrlInstr::Fn { pos: 0 }, ComplexityClass::Call, 30_000),
+            (CtrlInstr::Call { site }, ComplexityClass::External, 548_000),
+            (CtrlInstr::Ret, ComplexityClass::Heavy, 20_000),
+            (CtrlInstr::Stop, ComplexityClass::Trivial, 0),
+            (CtrlInstr::Save { mask: 0 }, ComplexityClass::Heavy, 20_000),
+        ];
+        for (instr, class, complexity) in corpus {
+            let instr = Instr::<LibId>::Ctrl(instr);
+            assert_eq!(instr.complexity_class(), class, "{instr}");
+            assert_eq!(instr.complexity(), complexity, "{instr}");
+        }
+        let reserved = Instr::<LibId>::Reserved(default!());
+        assert_eq!(reserved.complexity_class(), ComplexityClass::Custom(u64::MAX));
+    }
+
+    #[test]
+    fn base_complexity() {
+        let lib_id = LibId::from_str(LIB_ID).unwrap();
+        let site = Site::new(lib_id, 0x69AB);
+        let corpus: [(CtrlInstr<LibId>, u16, u16, u64); 21] = [
+            (CtrlInstr::Nop, 0, 0, 0),
+            (CtrlInstr::ChkCo, 0, 0, 0),
+            (CtrlInstr::ChkCk, 0, 0, 0),
+            (CtrlInstr::NotCo, 0, 0, 0),
+            (CtrlInstr::FailCk, 0, 0, 0),
+            (CtrlInstr::RsetCk, 0, 0, 0),
+            (CtrlInstr::Jmp { pos: 0 }, 2, 0, 16_000),
+            (CtrlInstr::JiOvfl { pos: 0 }, 2, 0, 16_000),
+            (CtrlInstr::JiFail { pos: 0 }, 2, 0, 16_000),
+            (CtrlInstr::Sh { shift: 0 }, 1, 0, 8_000),
+            (CtrlInstr::ShOvfl { shift: 0 }, 1, 0, 8_000),
+            (CtrlInstr::ShFail { shift: 0 }, 1, 0, 8_000),
+            (CtrlInstr::ShW { shift: 0 }, 2, 0, 16_000),
+            (CtrlInstr::ShWOvfl { shift: 0 }, 2, 0, 16_000),
+            (CtrlInstr::ShWFail { shift: 0 }, 2, 0, 16_000),
+            (CtrlInstr::Exec { site }, 2, 32, 528_000),
+            (CtrlInstr::Fn { pos: 0 }, 2, 0, 16_000),
+            (CtrlInstr::Call { site }, 2, 32, 528_000),
+            (CtrlInstr::Ret, 0, 0, 0),
+            (CtrlInstr::Stop, 0, 0, 0),
+            (CtrlInstr::Save { mask: 0 }, 2, 0, 16_000),
+        ];
+        for (instr, op_data, ext_data, complexity) in corpus {
+            let instr = Instr::<LibId>::Ctrl(instr);
+            assert_eq!(instr.op_data_bytes(), op_data, "{instr}");
+            assert_eq!(instr.ext_data_bytes(), ext_data, "{instr}");
+            assert_eq!(instr.base_complexity(), complexity, "{instr}");
+        }
+        let reserved = Instr::<LibId>::Reserved(default!());
+        assert_eq!(reserved.base_complexity(), 0);
+    }
+
+    #[test]
+    fn complexity_model() {
+        let model = ComplexityModel {
+            trivial: 1,
+            light: 10,
+            medium: 100,
+            heavy: 1000,
+            call: 10_000,
+            external: 100_000,
+            ..ComplexityModel::DEFAULT
+        };
+        assert_eq!(Instr::<LibId>::Ctrl(CtrlInstr::Nop).complexity_in(&model), 1);
+        assert_eq!(Instr::<LibId>::Ctrl(CtrlInstr::NotCo).complexity_in(&model), 10);
+        assert_eq!(Instr::<LibId>::Ctrl(CtrlInstr::Jmp { pos: 0 }).complexity_in(&model), 100);
+        assert_eq!(Instr::<LibId>::Ctrl(CtrlInstr::Ret).complexity_in(&model), 1000);
+        assert_eq!(Instr::<LibId>::Ctrl(CtrlInstr::Fn { pos: 0 }).complexity_in(&model), 10_000);
+        let site = Site::new(LibId::from([0xA5u8; 32]), 0);
+        assert_eq!(Instr::<LibId>::Ctrl(CtrlInstr::Call { site }).complexity_in(&model), 100_000);
+        assert_eq!(Instr::<LibId>::Ctrl(CtrlInstr::Exec { site }).complexity_in(&model), 100_000);
+        assert_eq!(Instr::<LibId>::Reserved(default!()).complexity_in(&model), u64::MAX);
+        assert_eq!(ComplexityModel::default(), ComplexityModel::DEFAULT);
+    }
 }
@@ -123,4 +123,67 @@ pub enum CtrlInstr<Id: SiteId> {
     /// Stop the program.
     #[display("stop")]
     Stop,
+
+    /// Wide relative jump, reaching offsets beyond the range of [`CtrlInstr::Sh`].
+    #[display("jmp.w   {shift:+}")]
+    ShW {
+        /** Number of bytes for the relative shift */
+        shift: i16,
+    },
+
+    /// Wide relative jump if `CO` is in a failed state.
+    #[display("jif.w   CO, {shift:+}")]
+    ShWOvfl {
+        /** Number of bytes for the relative shift */
+        shift: i16,
+    },
+
+    /// Wide relative jump if `CK` is in a failed state.
+    #[display("jif.w   CK, {shift:+}")]
+    ShWFail {
+        /** Number of bytes for the relative shift */
+        shift: i16,
+    },
+
+    /// Save ISA extension registers, restoring them once the current subroutine returns.
+    ///
+    /// Bit `n` of the mask selects the `n`-th register of the ISA extension. Fails `CK` if the
+    /// register save stack is full.
+    #[display("save    {mask:#06X}.h")]
+    Save {
+        /** Mask of the registers to save */
+        mask: u16,
+    },
+}
+
+#[cfg(any(test, feature = "fuzzing"))]
+impl crate::fuzzing::Arbitrary for CtrlInstr<crate::LibId> {
+    fn arbitrary(bytes: &mut impl Iterator<Item = u8>) -> Option<Self> {
+        use crate::fuzzing::operand;
+
+        Some(match bytes.next()? % (Self::END - Self::START + 1) + Self::START {
+            Self::NOP => CtrlInstr::Nop,
+            Self::NOCO => CtrlInstr::NotCo,
+            Self::CHCO => CtrlInstr::ChkCo,
+            Self::CHCK => CtrlInstr::ChkCk,
+            Self::FAIL => CtrlInstr::FailCk,
+            Self::RSET => CtrlInstr::RsetCk,
+            Self::JMP => CtrlInstr::Jmp { pos: operand(bytes) },
+            Self::JINE => CtrlInstr::JiOvfl { pos: operand(bytes) },
+            Self::JIFAIL => CtrlInstr::JiFail { pos: operand(bytes) },
+            Self::SH => CtrlInstr::Sh { shift: operand(bytes) },
+            Self::SHNE => CtrlInstr::ShOvfl { shift: operand(bytes) },
+            Self::SHFAIL => CtrlInstr::ShFail { shift: operand(bytes) },
+            Self::EXEC => CtrlInstr::Exec { site: operand(bytes) },
+            Self::FN => CtrlInstr::Fn { pos: operand(bytes) },
+            Self::CALL => CtrlInstr::Call { site: operand(bytes) },
+            Self::RET => CtrlInstr::Ret,
+            Self::STOP => CtrlInstr::Stop,
+            Self::SHW => CtrlInstr::ShW { shift: operand(bytes) },
+            Self::SHWNE => CtrlInstr::ShWOvfl { shift: operand(bytes) },
+            Self::SHWFAIL => CtrlInstr::ShWFail { shift: operand(bytes) },
+            Self::SAVE => CtrlInstr::Save { mask: operand(bytes) },
+            _ => unreachable!(),
+        })
+    }
 }
@@ -22,14 +22,12 @@
 // or implied. See the License for the specific language governing permissions and limitations under
 // the License.
 
-use alloc::collections::BTreeSet;
+use alloc::collections::{BTreeMap, BTreeSet};
 use core::fmt::{Debug, Display};
 
-use amplify::confinement::TinyOrdSet;
-
 use crate::core::{Core, Register, Site, SiteId};
 use crate::isa::Bytecode;
-use crate::{CoreExt, IsaId};
+use crate::{CoreExt, IsaId, IsaSeg};
 
 /// Turing machine movement after instruction execution
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
@@ -54,6 +52,35 @@ pub enum ExecStep<Site> {
 }
 
 /// A local goto position for the jump instructions.
+///
+/// Tools enumerating or relocating jump targets must handle absolute and relative targets, as well
+/// as the jump tables; the relative shift is counted from the offset of the jump instruction
+/// itself.
+///
+/// # Example
+///
+/// ```
+/// use aluvm::isa::{CtrlInstr, GotoTarget, Instruction};
+/// use aluvm::LibId;
+///
+/// /// Resolves the offset targeted by an instruction located at `pos`.
+/// fn target(instr: &mut CtrlInstr<LibId>, pos: u16) -> Option<u16> {
+///     match instr.local_goto_pos() {
+///         GotoTarget::None => None,
+///         GotoTarget::Absolute(target) => Some(*target),
+///         GotoTarget::Relative(shift) => pos.checked_add_signed(*shift as i16),
+///         GotoTarget::RelativeWide(shift) => pos.checked_add_signed(*shift),
+///         // A jump table has multiple targets
+///         GotoTarget::Table(_) => None,
+///     }
+/// }
+///
+/// assert_eq!(target(&mut CtrlInstr::Jmp { pos: 12 }, 4), Some(12));
+/// assert_eq!(target(&mut CtrlInstr::ShFail { shift: -3 }, 4), Some(1));
+/// assert_eq!(target(&mut CtrlInstr::Sh { shift: -5 }, 4), None);
+/// assert_eq!(target(&mut CtrlInstr::ShW { shift: 0x100 }, 4), Some(0x104));
+/// assert_eq!(target(&mut CtrlInstr::Ret, 4), None);
+/// ```
 #[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
 pub enum GotoTarget<'a> {
     /// The instruction does not perform a local jump.
@@ -67,6 +94,178 @@ pub enum GotoTarget<'a> {
 
     /// An offset relative to the current position.
     Relative(&'a mut i8),
+
+    /// A wide (16-bit) offset relative to the current position.
+    RelativeWide(&'a mut i16),
+
+    /// A table of absolute offsets in the code segment of the library, one of which is selected
+    /// by the instruction at runtime.
+    Table(&'a mut [u16]),
+}
+
+/// Kind of the control transfer performed by an instruction, used by the static control flow
+/// analysis.
+///
+/// See [`Instruction::flow_kind`].
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Display)]
+pub enum FlowKind {
+    /// The instruction always proceeds to the next instruction (unless it fails and `CH` is set).
+    #[display("continue")]
+    Continue,
+
+    /// The instruction either jumps to its goto target or proceeds to the next instruction.
+    #[display("branch")]
+    Branch,
+
+    /// The instruction unconditionally jumps to its (local or remote) goto target.
+    #[display("jump")]
+    Jump,
+
+    /// The instruction calls a subroutine at its (local or remote) goto target, and the execution
+    /// proceeds to the next instruction once the subroutine returns.
+    #[display("call")]
+    Call,
+
+    /// The instruction terminates the program or returns from a subroutine.
+    #[display("halt")]
+    Halt,
+}
+
+/// Complexity class of an instruction.
+///
+/// Classes allow instruction sets to declare how expensive an instruction is without hard-coding
+/// the exact number of "CPU ticks"; the mapping from a class to the cost is provided by
+/// [`ComplexityModel`].
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Display)]
+pub enum ComplexityClass {
+    /// Instruction which doesn't perform any computation (like `nop`).
+    #[display("trivial")]
+    Trivial,
+
+    /// Instruction operating only with the flag registers.
+    #[display("light")]
+    Light,
+
+    /// Unconditional control-flow instruction, or a simple register-to-register operation.
+    #[display("medium")]
+    Medium,
+
+    /// Conditional control-flow instruction, or an operation involving multiple registers.
+    #[display("heavy")]
+    Heavy,
+
+    /// Call of a subroutine in the same library, which pushes a call stack frame.
+    #[display("call")]
+    Call,
+
+    /// Control transfer into another library, which resolves the library id from the libs
+    /// segment and may require loading the library.
+    #[display("external")]
+    External,
+
+    /// Instruction with a custom complexity value, which is not adjusted by the
+    /// [`ComplexityModel`].
+    #[display("custom({0})")]
+    Custom(u64),
+}
+
+/// Model mapping [`ComplexityClass`]es to the complexity values.
+///
+/// The model may additionally adjust the costs of the instructions belonging to specific ISA
+/// extensions or having specific opcodes: the instruction is first priced by its complexity class,
+/// and then the rule for its opcode, or, if none, the rule for its ISA extension (see
+/// [`Instruction::isa_ext_id`]) is applied to the class cost.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct ComplexityModel {
+    /// Cost of [`ComplexityClass::Trivial`] instructions.
+    pub trivial: u64,
+    /// Cost of [`ComplexityClass::Light`] instructions.
+    pub light: u64,
+    /// Cost of [`ComplexityClass::Medium`] instructions.
+    pub medium: u64,
+    /// Cost of [`ComplexityClass::Heavy`] instructions.
+    pub heavy: u64,
+    /// Cost of [`ComplexityClass::Call`] instructions.
+    pub call: u64,
+    /// Cost of [`ComplexityClass::External`] instructions.
+    pub external: u64,
+    /// Cost rules for the instructions belonging to ISA extensions.
+    pub isa: BTreeMap<IsaId, Cost>,
+    /// Cost rules for the instructions with specific opcodes, taking precedence over the rules
+    /// in [`Self::isa`].
+    pub opcodes: BTreeMap<u8, Cost>,
+}
+
+impl Default for ComplexityModel {
+    fn default() -> Self { Self::DEFAULT }
+}
+
+impl ComplexityModel {
+    /// Default complexity model used by [`Instruction::complexity`] and by new cores (see
+    /// [`crate::Core::complexity_model`]).
+    pub const DEFAULT: Self = Self {
+        trivial: 0,
+        light: 2_000,
+        medium: 10_000,
+        heavy: 20_000,
+        call: 30_000,
+        external: 548_000,
+        isa: BTreeMap::new(),
+        opcodes: BTreeMap::new(),
+    };
+
+    /// Returns complexity value for the provided complexity class.
+    pub const fn cost(&self, class: ComplexityClass) -> u64 {
+        match class {
+            ComplexityClass::Trivial => self.trivial,
+            ComplexityClass::Light => self.light,
+            ComplexityClass::Medium => self.medium,
+            ComplexityClass::Heavy => self.heavy,
+            ComplexityClass::Call => self.call,
+            ComplexityClass::External => self.external,
+            ComplexityClass::Custom(cost) => cost,
+        }
+    }
+
+    /// Returns complexity value for the instruction: the cost of its complexity class, adjusted
+    /// by the matching cost rule, if any.
+    pub fn instr_cost<Id: SiteId, I: Instruction<Id>>(&self, instr: &I) -> u64 {
+        let rule = self.opcodes.get(&instr.opcode_byte()).or_else(|| {
+            instr
+                .isa_ext_id()
+                .and_then(|ext| self.isa.get(&IsaId::from(ext)))
+        });
+        let complexity = self.cost(instr.complexity_class());
+        rule.map_or(complexity, |cost| cost.apply(complexity))
+    }
+}
+
+/// Cost rule of an instruction under a [`ComplexityModel`].
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Display)]
+pub enum Cost {
+    /// The cost of the instruction complexity class multiplied by the provided factor.
+    #[display("x{0}")]
+    Multiplier(u64),
+
+    /// Fixed cost replacing the cost of the instruction complexity class.
+    #[display("{0}")]
+    Absolute(u64),
+}
+
+impl Default for Cost {
+    /// Multiplier of 1, leaving the instruction cost unchanged.
+    fn default() -> Self { Cost::Multiplier(1) }
+}
+
+impl Cost {
+    /// Applies the cost rule to the cost of the instruction complexity class, saturating at
+    /// `u64::MAX`.
+    pub const fn apply(self, complexity: u64) -> u64 {
+        match self {
+            Cost::Multiplier(factor) => complexity.saturating_mul(factor),
+            Cost::Absolute(cost) => cost,
+        }
+    }
 }
 
 /// Trait for instructions
@@ -79,23 +278,58 @@ pub trait Instruction<Id: SiteId>: Display + Debug + Bytecode<Id> + Clone + Eq {
     /// Context: external data which are accessible to the ISA.
     type Context<'ctx>;
 
-    /// Convert the set of ISA extensions from [`Self::ISA_EXT`] into a set of [`IsaId`].
-    fn isa_ext() -> TinyOrdSet<IsaId> {
-        let iter = Self::ISA_EXT.iter().copied().map(IsaId::from);
-        TinyOrdSet::from_iter_checked(iter)
-    }
+    /// Convert the set of ISA extensions from [`Self::ISA_EXT`] into an [`IsaSeg`].
+    ///
+    /// The set is constructed in the canonical form (see [`IsaId::canonical_set`]), such that
+    /// instruction sets composed out of the same members produce the same set irrespectively of
+    /// the order in which the members list their extensions, and of extensions shared by several
+    /// members.
+    fn isa_ext() -> IsaSeg { IsaId::canonical_set(Self::ISA_EXT.iter().copied().map(IsaId::from)) }
+
+    /// Returns the ISA extension (one of [`Self::ISA_EXT`]) declaring the instruction, or `None`
+    /// if the instruction belongs to the core AluVM ISA.
+    fn isa_ext_id(&self) -> Option<&'static str> { None }
+
+    /// Whether the instruction is a reserved one, i.e. its opcode is not assigned to any operation.
+    fn is_reserved(&self) -> bool { false }
 
     /// Whether the instruction can be used as a goto-target.
     fn is_goto_target(&self) -> bool;
 
     /// If an instruction is a jump operation inside the library, it should return its goto target
-    /// position number.
-    fn local_goto_pos(&mut self) -> GotoTarget;
+    /// position: either an absolute offset, or a shift relative to the instruction offset.
+    ///
+    /// Instruction sets must report relative jumps as [`GotoTarget::Relative`] or
+    /// [`GotoTarget::RelativeWide`], and computed jumps as [`GotoTarget::Table`], rather than
+    /// [`GotoTarget::None`], since the library validation, control flow analysis and code
+    /// relocation rely on this method to discover all local jumps.
+    fn local_goto_pos(&mut self) -> GotoTarget<'_>;
 
     /// If an instruction is a jump operation into an external library, it should return its remote
     /// target.
     fn remote_goto_pos(&mut self) -> Option<&mut Site<Id>>;
 
+    /// If an instruction is a jump operation into an external library, returns its equivalent
+    /// jumping to the `pos` offset of the local code segment, if the instruction set has one.
+    ///
+    /// Used by [`crate::Lib::merge`] to turn calls into the merged libraries into local calls.
+    /// The default implementation returns `None`.
+    fn localize_goto(&self, _pos: u16) -> Option<Self> { None }
+
+    /// Returns the kind of the control transfer performed by the instruction.
+    ///
+    /// The default implementation is conservative: it returns [`FlowKind::Branch`] for any
+    /// instruction having a local or remote goto target, and [`FlowKind::Continue`] otherwise.
+    /// Instruction sets should override it to make the control flow analysis precise.
+    fn flow_kind(&self) -> FlowKind {
+        let mut instr = self.clone();
+        if instr.local_goto_pos() != GotoTarget::None || instr.remote_goto_pos().is_some() {
+            FlowKind::Branch
+        } else {
+            FlowKind::Continue
+        }
+    }
+
     /// Lists all registers which are used by the instruction.
     fn regs(&self) -> BTreeSet<<Self::Core as CoreExt>::Reg> {
         let mut regs = self.src_regs();
@@ -135,7 +369,7 @@ pub trait Instruction<Id: SiteId>: Display + Debug + Bytecode<Id> + Clone + Eq {
 
     /// Computes base (non-adjusted) complexity of the instruction.
     ///
-    /// Called by the default [`Self::complexity`] implementation. See it for more details.
+    /// Called by the default [`Self::complexity_class`] implementation. See it for more details.
     fn base_complexity(&self) -> u64 {
         (self.op_data_bytes() as u64
             + self.src_reg_bytes() as u64
@@ -145,25 +379,42 @@ pub trait Instruction<Id: SiteId>: Display + Debug + Bytecode<Id> + Clone + Eq {
             * 1000 // by default use large unit
     }
 
+    /// Returns complexity class of the instruction.
+    ///
+    /// Defaults to [`ComplexityClass::Custom`] with the value of [`Self::base_complexity`], i.e.
+    /// the complexity derived from the number of bytes processed by the instruction.
+    fn complexity_class(&self) -> ComplexityClass {
+        ComplexityClass::Custom(self.base_complexity())
+    }
+
+    /// Returns computational complexity of the instruction under a given complexity model.
+    ///
+    /// See [`ComplexityModel::instr_cost`] for the details.
+    fn complexity_in(&self, model: &ComplexityModel) -> u64 { model.instr_cost(self) }
+
     /// Returns computational complexity of the instruction.
     ///
     /// Computational complexity is the number of "CPU ticks" required to process the instruction.
-    fn complexity(&self) -> u64 { self.base_complexity() }
+    /// Computed from [`Self::complexity_class`] using the [`ComplexityModel::DEFAULT`] model; the
+    /// program execution accounts the complexity under the model of the core instead (see
+    /// [`crate::Core::complexity_model`]).
+    fn complexity(&self) -> u64 { self.complexity_in(&ComplexityModel::DEFAULT) }
 
     /// Executes the given instruction taking all registers as input and output.
     ///
     /// # Arguments
     ///
     /// The method is provided with the current code position which may be used by the instruction
-    /// for constructing call stack.
+    /// for constructing call stack. The core may have any call stack size, as chosen for the
+    /// virtual machine (see [`crate::Vm`]).
     ///
     /// # Returns
     ///
     /// Returns whether further execution should be stopped.
-    fn exec(
+    fn exec<const CALL_STACK_SIZE: usize>(
         &self,
         site: Site<Id>,
-        core: &mut Core<Id, Self::Core>,
+        core: &mut Core<Id, Self::Core, CALL_STACK_SIZE>,
         context: &Self::Context<'_>,
     ) -> ExecStep<Site<Id>>;
 }
@@ -24,10 +24,16 @@
 
 /// Macro compiler for AluVM assembler.
 ///
+/// Accepts the mnemonics in the form produced by the [`Display`](core::fmt::Display)
+/// implementation of the instructions. Sites in other libraries may be written either as
+/// `lib, offset` or as `lib@offset`, where `lib` is a variable holding the library id.
+///
 /// # Example
 ///
 /// ```
 /// ##![cfg_attr(coverage_nightly, feature(coverage_attribute), coverage(off))]
+/// extern crate alloc;
+///
 /// use aluvm::isa::Instr;
 /// use aluvm::regs::Status;
 /// use aluvm::{aluasm, Lib, LibId, LibSite, Vm};
@@ -91,6 +97,16 @@ macro_rules! aluasm_inner {
         $code.push(instr!{ $op });
         $crate::aluasm_inner! { $code => $( $tt )* }
     };
+    // operand is a shift; goes before the literals, since negative literals match the
+    // `literal` fragment
+    { $code:ident => $op:ident + $pos:literal ; $($tt:tt)* } => {
+        $code.push(instr!{ $op + $pos });
+        $crate::aluasm_inner! { $code => $( $tt )* }
+    };
+    { $code:ident => $op:ident - $pos:literal ; $($tt:tt)* } => {
+        $code.push(instr!{ $op - $pos });
+        $crate::aluasm_inner! { $code => $( $tt )* }
+    };
     // operands are all literals
     { $code:ident => $op:ident $( $arg:literal ),+ ; $($tt:tt)* } => {
         $code.push(instr!{ $op $( $arg ),+ });
@@ -101,24 +117,40 @@ macro_rules! aluasm_inner {
         $code.push(instr!{ $op $( $arg ),+ });
         $crate::aluasm_inner! { $code => $( $tt )* }
     };
-    // operand is a positive shift
-    { $code:ident => $op:ident + $pos:literal ; $($tt:tt)* } => {
-        $code.push(instr!{ $op + $pos });
-        $crate::aluasm_inner! { $code => $( $tt )* }
-    };
     { $code:ident => $op:ident $arg:ident, + $pos:literal ; $($tt:tt)* } => {
         $code.push(instr!{ $op $arg, + $pos });
         $crate::aluasm_inner! { $code => $( $tt )* }
     };
-    // operand is a negative shift
-    { $code:ident => $op:ident - $pos:literal ; $($tt:tt)* } => {
-        $code.push(instr!{ $op - $pos });
-        $crate::aluasm_inner! { $code => $( $tt )* }
-    };
     { $code:ident => $op:ident $arg:ident, - $pos:literal ; $($tt:tt)* } => {
         $code.push(instr!{ $op $arg, - $pos });
         $crate::aluasm_inner! { $code => $( $tt )* }
     };
+    // wide shifts
+    { $code:ident => $op:ident . $w:ident + $pos:literal ; $($tt:tt)* } => {
+        $code.push(instr!{ $op.$w + $pos });
+        $crate::aluasm_inner! { $code => $( $tt )* }
+    };
+    { $code:ident => $op:ident . $w:ident - $pos:literal ; $($tt:tt)* } => {
+        $code.push(instr!{ $op.$w - $pos });
+        $crate::aluasm_inner! { $code => $( $tt )* }
+    };
+    { $code:ident => $op:ident . $w:ident $arg:ident, + $pos:literal ; $($tt:tt)* } => {
+        $code.push(instr!{ $op.$w $arg, + $pos });
+        $crate::aluasm_inner! { $code => $( $tt )* }
+    };
+    { $code:ident => $op:ident . $w:ident $arg:ident, - $pos:literal ; $($tt:tt)* } => {
+        $code.push(instr!{ $op.$w $arg, - $pos });
+        $crate::aluasm_inner! { $code => $( $tt )* }
+    };
+    // operand is an external site
+    { $code:ident => $op:ident $lib:ident @ $pos:literal ; $($tt:tt)* } => {
+        $code.push(instr!{ $op $lib @ $pos });
+        $crate::aluasm_inner! { $code => $( $tt )* }
+    };
+    { $code:ident => $op:ident $lib:ident @ $pos:ident ; $($tt:tt)* } => {
+        $code.push(instr!{ $op $lib @ $pos });
+        $crate::aluasm_inner! { $code => $( $tt )* }
+    };
     // operands are indent followed by a literal
     { $code:ident => $op:ident $arg:ident, $val:literal ; $($tt:tt)* } => {
         $code.push(instr!{ $op $arg, $val });
@@ -185,7 +217,13 @@ macro_rules! instr {
         $crate::isa::CtrlInstr::Stop.into()
     };
 
-    // Jumps
+    // Jumps; relative ones go first, since negative literals match the `literal` fragment
+    (jmp + $shift:literal) => {
+        $crate::isa::CtrlInstr::Sh { shift: $shift }.into()
+    };
+    (jmp - $shift:literal) => {
+        $crate::isa::CtrlInstr::Sh { shift: -$shift }.into()
+    };
     (jmp $pos:literal) => {
         $crate::isa::CtrlInstr::Jmp { pos: $pos }.into()
     };
@@ -217,11 +255,23 @@ macro_rules! instr {
     (jif CK, $pos:ident) => {
         $crate::isa::CtrlInstr::JiFail { pos: $pos }.into()
     };
-    (jmp + $shift:literal) => {
-        $crate::isa::CtrlInstr::Sh { shift: $shift }.into()
+    (jmp.w + $shift:literal) => {
+        $crate::isa::CtrlInstr::ShW { shift: $shift }.into()
     };
-    (jmp - $shift:literal) => {
-        $crate::isa::CtrlInstr::Sh { shift: -$shift }.into()
+    (jmp.w - $shift:literal) => {
+        $crate::isa::CtrlInstr::ShW { shift: -$shift }.into()
+    };
+    (jif.w CO, + $shift:literal) => {
+        $crate::isa::CtrlInstr::ShWOvfl { shift: $shift }.into()
+    };
+    (jif.w CO, - $shift:literal) => {
+        $crate::isa::CtrlInstr::ShWOvfl { shift: -$shift }.into()
+    };
+    (jif.w CK, + $shift:literal) => {
+        $crate::isa::CtrlInstr::ShWFail { shift: $shift }.into()
+    };
+    (jif.w CK, - $shift:literal) => {
+        $crate::isa::CtrlInstr::ShWFail { shift: -$shift }.into()
     };
 
     // Calls
@@ -237,6 +287,18 @@ macro_rules! instr {
     (call $lib:ident, $pos:ident) => {
         $crate::isa::CtrlInstr::Call { site: $crate::Site::new($lib, $pos).into() }.into()
     };
+    (jmp $lib:ident @ $pos:literal) => {
+        $crate::isa::CtrlInstr::Exec { site: $crate::Site::new($lib, $pos).into() }.into()
+    };
+    (jmp $lib:ident @ $pos:ident) => {
+        $crate::isa::CtrlInstr::Exec { site: $crate::Site::new($lib, $pos).into() }.into()
+    };
+    (call $lib:ident @ $pos:literal) => {
+        $crate::isa::CtrlInstr::Call { site: $crate::Site::new($lib, $pos).into() }.into()
+    };
+    (call $lib:ident @ $pos:ident) => {
+        $crate::isa::CtrlInstr::Call { site: $crate::Site::new($lib, $pos).into() }.into()
+    };
     (call $pos:literal) => {
         $crate::isa::CtrlInstr::Fn { pos: $pos }.into()
     };
@@ -244,6 +306,14 @@ macro_rules! instr {
         $crate::isa::CtrlInstr::Fn { pos: $pos }.into()
     };
 
+    // Register save
+    (save $mask:literal) => {
+        $crate::isa::CtrlInstr::Save { mask: $mask }.into()
+    };
+    (save $mask:ident) => {
+        $crate::isa::CtrlInstr::Save { mask: $mask }.into()
+    };
+
     // Halt
     (halt) => {
         $crate::isa::ReservedInstr::default().into()
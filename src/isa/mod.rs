@@ -28,10 +28,25 @@ mod instr;
 mod bytecode;
 mod arch;
 
+mod alu;
 mod ctrl;
+#[cfg(feature = "secp256k1")]
+pub mod secp;
 mod masm;
+mod multi;
+mod asm;
+mod strict;
 
-pub use arch::{Instr, IsaId, ReservedInstr, ISA_ID_MAX_LEN};
-pub use bytecode::{Bytecode, BytecodeRead, BytecodeWrite, CodeEofError};
+pub use alu::{Alu64Core, Alu64Instr, ArithmInstr, RegA, RegIndexError, SelectInstr, TableInstr};
+pub use arch::{
+    Instr, IsaId, IsaMember, IsaSeg, IsaSegError, ReservedInstr, ISA_ID_MAX_LEN, OPCODE_TABLE,
+};
+pub use asm::{parse_asm, AsmParseError};
+pub use bytecode::{
+    Bytecode, BytecodeRead, BytecodeWrite, CodeEofError, DataReadError, OpcodeInfo, OpcodeTable,
+};
 pub use ctrl::CtrlInstr;
-pub use instr::{ExecStep, GotoTarget, Instruction};
+pub use instr::{
+    ComplexityClass, ComplexityModel, Cost, ExecStep, FlowKind, GotoTarget, Instruction,
+};
+pub use multi::MultiIsa;
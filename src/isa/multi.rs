@@ -0,0 +1,334 @@
+// Reference rust implementation of AluVM (arithmetic logic unit virtual machine).
+// To find more on AluVM please check <https://aluvm.org>
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Designed in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+// Written in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+//
+// Copyright (C) 2021-2024 LNP/BP Standards Association, Switzerland.
+// Copyright (C) 2024-2025 Laboratories for Ubiquitous Deterministic Computing (UBIDECO),
+//                         Institute for Distributed and Cognitive Systems (InDCS), Switzerland.
+// Copyright (C) 2021-2025 Dr Maxim Orlovsky.
+// All rights under the above copyrights are reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use this file except
+// in compliance with the License. You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed under the License
+// is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express
+// or implied. See the License for the specific language governing permissions and limitations under
+// the License.
+
+use alloc::collections::BTreeSet;
+use core::fmt::{self, Display, Formatter};
+use core::ops::RangeInclusive;
+
+use super::{
+    Bytecode, BytecodeRead, BytecodeWrite, CodeEofError, ComplexityClass, ExecStep, FlowKind,
+    GotoTarget, Instruction, IsaId, IsaSeg, ReservedInstr,
+};
+use crate::core::{Core, CoreExt, Site, SiteId, Supercore};
+
+/// Instruction set dispatching instructions to one of two member instruction sets by the opcode.
+///
+/// Each of the members declares the opcodes it owns with [`Bytecode::owns_opcode`]; opcodes not
+/// owned by any of the members are decoded as [`ReservedInstr`]. If both members own an opcode,
+/// the primary member takes precedence; use [`MultiIsa::opcode_overlap`] to check that the
+/// members are disjoint. Dispatchers can be nested to compose more than two members.
+///
+/// Instructions of the primary member are executed directly by the core, while the secondary
+/// member instructions are executed by its subcore (see [`Supercore`]), which requires the
+/// registers of the secondary member to be convertible into the registers of the primary one.
+/// Both members must use the same context.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub enum MultiIsa<A, B> {
+    /// Instruction of the primary member instruction set.
+    Primary(A),
+
+    /// Instruction of the secondary member instruction set.
+    Secondary(B),
+
+    /// Reserved instruction, which opcode is not owned by any of the members.
+    Reserved(ReservedInstr),
+}
+
+impl<A: Display, B: Display> Display for MultiIsa<A, B> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            MultiIsa::Primary(instr) => Display::fmt(instr, f),
+            MultiIsa::Secondary(instr) => Display::fmt(instr, f),
+            MultiIsa::Reserved(instr) => Display::fmt(instr, f),
+        }
+    }
+}
+
+impl<A, B> MultiIsa<A, B> {
+    /// Returns the lowest opcode owned by both members, if any.
+    pub fn opcode_overlap<Id: SiteId>() -> Option<u8>
+    where
+        A: Bytecode<Id>,
+        B: Bytecode<Id>,
+    {
+        (0..=0xFF).find(|opcode| A::owns_opcode(*opcode) && B::owns_opcode(*opcode))
+    }
+}
+
+impl<Id: SiteId, A: Bytecode<Id>, B: Bytecode<Id>> Bytecode<Id> for MultiIsa<A, B> {
+    fn op_range() -> RangeInclusive<u8> { 0..=0xFF }
+
+    fn owns_opcode(opcode: u8) -> bool { A::owns_opcode(opcode) || B::owns_opcode(opcode) }
+
+    fn opcode_byte(&self) -> u8 {
+        match self {
+            MultiIsa::Primary(instr) => instr.opcode_byte(),
+            MultiIsa::Secondary(instr) => instr.opcode_byte(),
+            MultiIsa::Reserved(instr) => Bytecode::<Id>::opcode_byte(instr),
+        }
+    }
+
+    fn code_byte_len(&self) -> u16 {
+        match self {
+            MultiIsa::Primary(instr) => instr.code_byte_len(),
+            MultiIsa::Secondary(instr) => instr.code_byte_len(),
+            MultiIsa::Reserved(instr) => Bytecode::<Id>::code_byte_len(instr),
+        }
+    }
+
+    fn external_ref(&self) -> Option<Id> {
+        match self {
+            MultiIsa::Primary(instr) => instr.external_ref(),
+            MultiIsa::Secondary(instr) => instr.external_ref(),
+            MultiIsa::Reserved(instr) => Bytecode::<Id>::external_ref(instr),
+        }
+    }
+
+    fn encode_operands<W>(&self, writer: &mut W) -> Result<(), W::Error>
+    where W: BytecodeWrite<Id> {
+        match self {
+            MultiIsa::Primary(instr) => instr.encode_operands(writer),
+            MultiIsa::Secondary(instr) => instr.encode_operands(writer),
+            MultiIsa::Reserved(instr) => instr.encode_operands(writer),
+        }
+    }
+
+    fn decode_operands<R>(reader: &mut R, opcode: u8) -> Result<Self, CodeEofError>
+    where
+        Self: Sized,
+        R: BytecodeRead<Id>,
+    {
+        if A::owns_opcode(opcode) {
+            A::decode_operands(reader, opcode).map(Self::Primary)
+        } else if B::owns_opcode(opcode) {
+            B::decode_operands(reader, opcode).map(Self::Secondary)
+        } else {
+            ReservedInstr::decode_operands(reader, opcode).map(Self::Reserved)
+        }
+    }
+}
+
+impl<Id: SiteId, A, B> Instruction<Id> for MultiIsa<A, B>
+where
+    A: Instruction<Id>,
+    A::Core: Supercore<B::Core>,
+    <A::Core as CoreExt>::Reg: From<<B::Core as CoreExt>::Reg>,
+    B: for<'ctx> Instruction<Id, Context<'ctx> = A::Context<'ctx>>,
+{
+    /// The dispatcher doesn't cover extensions on its own: [`Self::isa_ext`] returns the
+    /// extensions of both members.
+    const ISA_EXT: &'static [&'static str] = &[];
+
+    type Core = A::Core;
+    type Context<'ctx> = A::Context<'ctx>;
+
+    fn isa_ext() -> IsaSeg {
+        IsaId::canonical_set(A::isa_ext().iter().chain(B::isa_ext().iter()).cloned())
+    }
+
+    fn isa_ext_id(&self) -> Option<&'static str> {
+        match self {
+            MultiIsa::Primary(instr) => instr.isa_ext_id(),
+            MultiIsa::Secondary(instr) => instr.isa_ext_id(),
+            MultiIsa::Reserved(_) => None,
+        }
+    }
+
+    fn is_reserved(&self) -> bool {
+        match self {
+            MultiIsa::Primary(instr) => instr.is_reserved(),
+            MultiIsa::Secondary(instr) => instr.is_reserved(),
+            MultiIsa::Reserved(_) => true,
+        }
+    }
+
+    fn is_goto_target(&self) -> bool {
+        match self {
+            MultiIsa::Primary(instr) => instr.is_goto_target(),
+            MultiIsa::Secondary(instr) => instr.is_goto_target(),
+            MultiIsa::Reserved(instr) => Instruction::<Id>::is_goto_target(instr),
+        }
+    }
+
+    fn local_goto_pos(&mut self) -> GotoTarget<'_> {
+        match self {
+            MultiIsa::Primary(instr) => instr.local_goto_pos(),
+            MultiIsa::Secondary(instr) => instr.local_goto_pos(),
+            MultiIsa::Reserved(instr) => Instruction::<Id>::local_goto_pos(instr),
+        }
+    }
+
+    fn remote_goto_pos(&mut self) -> Option<&mut Site<Id>> {
+        match self {
+            MultiIsa::Primary(instr) => instr.remote_goto_pos(),
+            MultiIsa::Secondary(instr) => instr.remote_goto_pos(),
+            MultiIsa::Reserved(instr) => Instruction::<Id>::remote_goto_pos(instr),
+        }
+    }
+
+    fn localize_goto(&self, pos: u16) -> Option<Self> {
+        match self {
+            MultiIsa::Primary(instr) => instr.localize_goto(pos).map(MultiIsa::Primary),
+            MultiIsa::Secondary(instr) => instr.localize_goto(pos).map(MultiIsa::Secondary),
+            MultiIsa::Reserved(_) => None,
+        }
+    }
+
+    fn flow_kind(&self) -> FlowKind {
+        match self {
+            MultiIsa::Primary(instr) => instr.flow_kind(),
+            MultiIsa::Secondary(instr) => instr.flow_kind(),
+            MultiIsa::Reserved(instr) => Instruction::<Id>::flow_kind(instr),
+        }
+    }
+
+    fn src_regs(&self) -> BTreeSet<<Self::Core as CoreExt>::Reg> {
+        match self {
+            MultiIsa::Primary(instr) => instr.src_regs(),
+            MultiIsa::Secondary(instr) => instr.src_regs().into_iter().map(From::from).collect(),
+            MultiIsa::Reserved(_) => none!(),
+        }
+    }
+
+    fn dst_regs(&self) -> BTreeSet<<Self::Core as CoreExt>::Reg> {
+        match self {
+            MultiIsa::Primary(instr) => instr.dst_regs(),
+            MultiIsa::Secondary(instr) => instr.dst_regs().into_iter().map(From::from).collect(),
+            MultiIsa::Reserved(_) => none!(),
+        }
+    }
+
+    fn op_data_bytes(&self) -> u16 {
+        match self {
+            MultiIsa::Primary(instr) => instr.op_data_bytes(),
+            MultiIsa::Secondary(instr) => instr.op_data_bytes(),
+            MultiIsa::Reserved(instr) => Instruction::<Id>::op_data_bytes(instr),
+        }
+    }
+
+    fn ext_data_bytes(&self) -> u16 {
+        match self {
+            MultiIsa::Primary(instr) => instr.ext_data_bytes(),
+            MultiIsa::Secondary(instr) => instr.ext_data_bytes(),
+            MultiIsa::Reserved(instr) => Instruction::<Id>::ext_data_bytes(instr),
+        }
+    }
+
+    fn complexity_class(&self) -> ComplexityClass {
+        match self {
+            MultiIsa::Primary(instr) => instr.complexity_class(),
+            MultiIsa::Secondary(instr) => instr.complexity_class(),
+            MultiIsa::Reserved(instr) => Instruction::<Id>::complexity_class(instr),
+        }
+    }
+
+    fn exec<const CALL_STACK_SIZE: usize>(
+        &self,
+        site: Site<Id>,
+        core: &mut Core<Id, Self::Core, CALL_STACK_SIZE>,
+        context: &Self::Context<'_>,
+    ) -> ExecStep<Site<Id>> {
+        match self {
+            MultiIsa::Primary(instr) => instr.exec(site, core, context),
+            MultiIsa::Secondary(instr) => {
+                let mut subcore: Core<Id, B::Core, CALL_STACK_SIZE> = core.subcore();
+                let step = instr.exec(site, &mut subcore, context);
+                core.merge_subcore(subcore);
+                step
+            }
+            MultiIsa::Reserved(_) => ReservedInstr::exec_step(core),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![cfg_attr(coverage_nightly, coverage(off))]
+
+    use super::*;
+    use crate::core::Status;
+    use crate::isa::{Alu64Instr, ArithmInstr, CtrlInstr, RegA};
+    use crate::{CoreConfig, Lib, LibId, LibSite, Vm};
+
+    type AluIsa = MultiIsa<ArithmInstr, CtrlInstr<LibId>>;
+
+    fn put(dst: RegA, val: u64) -> ArithmInstr { ArithmInstr::Put { dst, val } }
+
+    fn add(dst: RegA, src: RegA) -> ArithmInstr { ArithmInstr::Add { wrap: false, dst, src } }
+
+    #[test]
+    fn opcode_ownership() {
+        assert_eq!(AluIsa::opcode_overlap::<LibId>(), None);
+        assert_eq!(
+            MultiIsa::<Alu64Instr<LibId>, CtrlInstr<LibId>>::opcode_overlap::<LibId>(),
+            Some(0)
+        );
+        for opcode in 0..=0xFF {
+            let owned = CtrlInstr::<LibId>::owns_opcode(opcode)
+                || <ArithmInstr as Bytecode<LibId>>::owns_opcode(opcode);
+            assert_eq!(<AluIsa as Bytecode<LibId>>::owns_opcode(opcode), owned);
+        }
+        assert_eq!(AluIsa::isa_ext(), Alu64Instr::<LibId>::isa_ext());
+    }
+
+    #[test]
+    fn same_as_alu64() {
+        let code = [
+            AluIsa::Primary(put(RegA::A0, 2)),
+            AluIsa::Primary(add(RegA::A0, RegA::A0)),
+            AluIsa::Secondary(CtrlInstr::Stop),
+            AluIsa::Reserved(ReservedInstr(0xFE)),
+        ];
+        let alu64 = [
+            Alu64Instr::<LibId>::Arithm(put(RegA::A0, 2)),
+            Alu64Instr::Arithm(add(RegA::A0, RegA::A0)),
+            Alu64Instr::Ctrl(CtrlInstr::Stop),
+            Alu64Instr::Reserved(ReservedInstr(0xFE)),
+        ];
+        let lib = Lib::assemble(&code).unwrap();
+        assert_eq!(lib, Lib::assemble(&alu64).unwrap());
+        assert_eq!(lib.disassemble::<AluIsa>().unwrap(), code);
+        assert!(code[3].is_reserved());
+        assert_eq!(code[3].to_string(), alu64[3].to_string());
+        assert_eq!(code[0].dst_regs(), alu64[0].dst_regs());
+    }
+
+    #[test]
+    fn exec() {
+        let code = [
+            AluIsa::Primary(put(RegA::A0, u64::MAX)),
+            AluIsa::Primary(put(RegA::A1, 1)),
+            AluIsa::Primary(ArithmInstr::Add { wrap: true, dst: RegA::A0, src: RegA::A1 }),
+            // `CO` is set by the primary member and checked by the secondary one
+            AluIsa::Secondary(CtrlInstr::ChkCo),
+            AluIsa::Primary(put(RegA::A2, 1)),
+        ];
+        let lib = Lib::assemble(&code).unwrap();
+        let mut vm = Vm::<AluIsa>::with(CoreConfig::default(), ());
+        let status = vm.exec(LibSite::new(lib.lib_id(), 0), &(), |_| Some(&lib));
+        assert_eq!(status, Status::Fail);
+        assert_eq!(vm.core.get(RegA::A0), Some(0));
+        assert_eq!(vm.core.get(RegA::A2), None);
+    }
+}
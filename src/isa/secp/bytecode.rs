@@ -0,0 +1,333 @@
+// Reference rust implementation of AluVM (arithmetic logic unit virtual machine).
+// To find more on AluVM please check <https://aluvm.org>
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Designed in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+// Written in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+//
+// Copyright (C) 2021-2024 LNP/BP Standards Association, Switzerland.
+// Copyright (C) 2024-2025 Laboratories for Ubiquitous Deterministic Computing (UBIDECO),
+//                         Institute for Distributed and Cognitive Systems (InDCS), Switzerland.
+// Copyright (C) 2021-2025 Dr Maxim Orlovsky.
+// All rights under the above copyrights are reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use this file except
+// in compliance with the License. You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed under the License
+// is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express
+// or implied. See the License for the specific language governing permissions and limitations under
+// the License.
+
+use core::ops::RangeInclusive;
+
+use amplify::num::u4;
+
+use super::{RegSecp, SecpInstr, SecpIsa, SecpKind, SecpValue};
+use crate::core::SiteId;
+use crate::isa::bytecode::CodeEofError;
+use crate::isa::{Bytecode, BytecodeRead, BytecodeWrite, CtrlInstr, ReservedInstr, TableInstr};
+use crate::LibId;
+
+const _: () = assert!(
+    SecpInstr::START > CtrlInstr::<LibId>::END,
+    "SECP256K opcodes overlap with the control flow instructions"
+);
+const _: () = assert!(
+    SecpInstr::START > TableInstr::END,
+    "SECP256K opcodes overlap with the JMPTBL instructions"
+);
+
+impl<Id: SiteId> Bytecode<Id> for SecpIsa<Id> {
+    fn op_range() -> RangeInclusive<u8> { 0..=0xFF }
+
+    fn opcode_byte(&self) -> u8 {
+        match self {
+            SecpIsa::Ctrl(instr) => instr.opcode_byte(),
+            SecpIsa::Secp(instr) => Bytecode::<Id>::opcode_byte(instr),
+            SecpIsa::Reserved(instr) => Bytecode::<Id>::opcode_byte(instr),
+        }
+    }
+
+    fn code_byte_len(&self) -> u16 {
+        match self {
+            SecpIsa::Ctrl(instr) => instr.code_byte_len(),
+            SecpIsa::Secp(instr) => Bytecode::<Id>::code_byte_len(instr),
+            SecpIsa::Reserved(instr) => Bytecode::<Id>::code_byte_len(instr),
+        }
+    }
+
+    fn external_ref(&self) -> Option<Id> {
+        match self {
+            SecpIsa::Ctrl(instr) => instr.external_ref(),
+            SecpIsa::Secp(instr) => Bytecode::<Id>::external_ref(instr),
+            SecpIsa::Reserved(instr) => Bytecode::<Id>::external_ref(instr),
+        }
+    }
+
+    fn encode_operands<W>(&self, writer: &mut W) -> Result<(), W::Error>
+    where W: BytecodeWrite<Id> {
+        match self {
+            SecpIsa::Ctrl(instr) => instr.encode_operands(writer),
+            SecpIsa::Secp(instr) => instr.encode_operands(writer),
+            SecpIsa::Reserved(instr) => instr.encode_operands(writer),
+        }
+    }
+
+    fn decode_operands<R>(reader: &mut R, opcode: u8) -> Result<Self, CodeEofError>
+    where
+        Self: Sized,
+        R: BytecodeRead<Id>,
+    {
+        if CtrlInstr::<Id>::op_range().contains(&opcode) {
+            CtrlInstr::<Id>::decode_operands(reader, opcode).map(Self::Ctrl)
+        } else if <SecpInstr as Bytecode<Id>>::op_range().contains(&opcode) {
+            SecpInstr::decode_operands(reader, opcode).map(Self::Secp)
+        } else {
+            ReservedInstr::decode_operands(reader, opcode).map(Self::Reserved)
+        }
+    }
+}
+
+#[allow(missing_docs)]
+impl SecpInstr {
+    pub(crate) const START: u8 = 0x60;
+    pub(crate) const END: u8 = Self::MUL;
+
+    pub const LD: u8 = 0x60;
+    pub const VERIFY: u8 = 0x61;
+    pub const ADD: u8 = 0x62;
+    pub const MUL: u8 = 0x63;
+}
+
+fn write_regs<Id: SiteId, W: BytecodeWrite<Id>>(
+    writer: &mut W,
+    reg1: RegSecp,
+    reg2: RegSecp,
+) -> Result<(), W::Error> {
+    writer.write_4bits(u4::with(reg1.index()))?;
+    writer.write_4bits(u4::with(reg2.index()))
+}
+
+/// Reads a register, checking that it holds the values of the expected kind.
+fn read_reg<Id: SiteId, R: BytecodeRead<Id>>(
+    reader: &mut R,
+    kind: SecpKind,
+) -> Result<RegSecp, CodeEofError> {
+    let reg = RegSecp::from(reader.read_4bits()?);
+    if reg.kind() != kind {
+        return Err(CodeEofError);
+    }
+    Ok(reg)
+}
+
+impl<Id: SiteId> Bytecode<Id> for SecpInstr {
+    fn op_range() -> RangeInclusive<u8> { Self::START..=Self::END }
+
+    fn opcode_byte(&self) -> u8 {
+        match self {
+            SecpInstr::Ld { .. } => Self::LD,
+            SecpInstr::Verify { .. } => Self::VERIFY,
+            SecpInstr::Add { .. } => Self::ADD,
+            SecpInstr::Mul { .. } => Self::MUL,
+        }
+    }
+
+    fn code_byte_len(&self) -> u16 {
+        let arg_bytes = match self {
+            // register and an offset-length pair of the value in the data segment
+            SecpInstr::Ld { .. } => 5,
+            SecpInstr::Verify { .. } => 2,
+            SecpInstr::Add { .. } | SecpInstr::Mul { .. } => 1,
+        };
+        arg_bytes + 1
+    }
+
+    fn external_ref(&self) -> Option<Id> { None }
+
+    fn encode_operands<W>(&self, writer: &mut W) -> Result<(), W::Error>
+    where W: BytecodeWrite<Id> {
+        match *self {
+            SecpInstr::Ld { dst, val } => {
+                writer.write_4bits(u4::with(dst.index()))?;
+                writer.write_4bits(u4::ZERO)?;
+                writer.write_bytes(val.as_slice())?;
+            }
+            SecpInstr::Verify { sig, msg, key } => {
+                write_regs(writer, sig, msg)?;
+                writer.write_4bits(u4::with(key.index()))?;
+                writer.write_4bits(u4::ZERO)?;
+            }
+            SecpInstr::Add { dst, src } | SecpInstr::Mul { dst, src } => {
+                write_regs(writer, dst, src)?
+            }
+        }
+        Ok(())
+    }
+
+    fn decode_operands<R>(reader: &mut R, opcode: u8) -> Result<Self, CodeEofError>
+    where
+        Self: Sized,
+        R: BytecodeRead<Id>,
+    {
+        Ok(match opcode {
+            Self::LD => {
+                let dst = RegSecp::from(reader.read_4bits()?);
+                let _ = reader.read_4bits()?;
+                let data = reader.read_bytes()?;
+                let val = SecpValue::from_slice(dst.kind(), &data).ok_or(CodeEofError)?;
+                SecpInstr::Ld { dst, val }
+            }
+            Self::VERIFY => {
+                let sig = read_reg(reader, SecpKind::Sig)?;
+                let msg = read_reg(reader, SecpKind::Scalar)?;
+                let key = read_reg(reader, SecpKind::Point)?;
+                let _ = reader.read_4bits()?;
+                SecpInstr::Verify { sig, msg, key }
+            }
+            Self::ADD => {
+                let dst = read_reg(reader, SecpKind::Point)?;
+                let src = read_reg(reader, SecpKind::Point)?;
+                SecpInstr::Add { dst, src }
+            }
+            Self::MUL => {
+                let dst = read_reg(reader, SecpKind::Point)?;
+                let src = read_reg(reader, SecpKind::Scalar)?;
+                SecpInstr::Mul { dst, src }
+            }
+            _ => return Err(CodeEofError),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![cfg_attr(coverage_nightly, coverage(off))]
+
+    use super::*;
+    use crate::library::{LibsSeg, Marshaller};
+
+    fn roundtrip(instr: impl Into<SecpIsa<LibId>>, bytecode: impl AsRef<[u8]>) {
+        let instr = instr.into();
+        let libs = LibsSeg::new();
+        let mut marshaller = Marshaller::new(&libs);
+        instr.encode_instr(&mut marshaller).unwrap();
+        let (code, data) = marshaller.finish();
+        assert_eq!(code.len(), instr.code_byte_len() as usize);
+        assert_eq!(code.as_slice(), bytecode.as_ref());
+        let mut marshaller = Marshaller::with(code, data, &libs).unwrap();
+        let decoded = SecpIsa::<LibId>::decode_instr(&mut marshaller).unwrap();
+        assert_eq!(decoded, instr);
+    }
+
+    fn decode(code: &[u8], data: &[u8]) -> Result<SecpIsa<LibId>, CodeEofError> {
+        let libs = LibsSeg::new();
+        let mut marshaller = Marshaller::with(code, data, &libs).unwrap();
+        SecpIsa::<LibId>::decode_instr(&mut marshaller)
+    }
+
+    #[test]
+    fn ld() {
+        let instr = SecpInstr::Ld { dst: RegSecp::P2, val: SecpValue::Point([0x02; 33]) };
+        roundtrip(instr, [SecpInstr::LD, 0x0A, 0x00, 0x00, 33, 0x00]);
+        assert_eq!(instr.to_string(), format!("ld      P2, {}", "02".repeat(33)));
+        roundtrip(SecpInstr::Ld { dst: RegSecp::S7, val: SecpValue::Scalar([0xFF; 32]) }, [
+            SecpInstr::LD,
+            0x07,
+            0x00,
+            0x00,
+            32,
+            0x00,
+        ]);
+        roundtrip(SecpInstr::Ld { dst: RegSecp::G3, val: SecpValue::Sig([0x01; 64]) }, [
+            SecpInstr::LD,
+            0x0F,
+            0x00,
+            0x00,
+            64,
+            0x00,
+        ]);
+    }
+
+    #[test]
+    fn ld_invalid() {
+        // Length of the value in the data segment doesn't match the register kind
+        let data = [0x02; 64];
+        for (reg, len) in [(0x00, 33), (0x08, 32), (0x0C, 33), (0x0C, 0)] {
+            assert_eq!(
+                decode(&[SecpInstr::LD, reg, 0x00, 0x00, len, 0x00], &data),
+                Err(CodeEofError)
+            );
+        }
+    }
+
+    #[test]
+    fn ops() {
+        let cases = [
+            (
+                SecpInstr::Verify { sig: RegSecp::G1, msg: RegSecp::S3, key: RegSecp::P2 },
+                &[SecpInstr::VERIFY, 0x3D, 0x0A][..],
+                "verify  G1, S3, P2",
+            ),
+            (
+                SecpInstr::Add { dst: RegSecp::P0, src: RegSecp::P3 },
+                &[SecpInstr::ADD, 0xB8],
+                "add     P0, P3",
+            ),
+            (
+                SecpInstr::Mul { dst: RegSecp::P1, src: RegSecp::S5 },
+                &[SecpInstr::MUL, 0x59],
+                "mul     P1, S5",
+            ),
+        ];
+        for (instr, bytecode, display) in cases {
+            roundtrip(instr, bytecode);
+            assert_eq!(instr.to_string(), display);
+        }
+    }
+
+    #[test]
+    fn ops_invalid_regs() {
+        for code in [
+            // Signature is not in a `G`-register
+            [SecpInstr::VERIFY, 0x3B, 0x0A],
+            // Message is not in an `S`-register
+            [SecpInstr::VERIFY, 0x8D, 0x0A],
+            // Key is not in a `P`-register
+            [SecpInstr::VERIFY, 0x3D, 0x03],
+        ] {
+            assert_eq!(decode(&code, &[]), Err(CodeEofError));
+        }
+        for code in [[SecpInstr::ADD, 0x38], [SecpInstr::ADD, 0x83], [SecpInstr::MUL, 0x98], [
+            SecpInstr::MUL,
+            0x50,
+        ]] {
+            assert_eq!(decode(&code, &[]), Err(CodeEofError));
+        }
+    }
+
+    #[test]
+    fn opcode_ranges() {
+        roundtrip(CtrlInstr::Stop, [CtrlInstr::<LibId>::STOP]);
+        roundtrip(ReservedInstr(SecpInstr::END + 1), [SecpInstr::END + 1]);
+        roundtrip(ReservedInstr(SecpInstr::START - 1), [SecpInstr::START - 1]);
+        assert_eq!(<SecpInstr as Bytecode<LibId>>::op_range(), 0x60..=0x63);
+    }
+
+    #[test]
+    fn foreign_opcodes() {
+        let libs = LibsSeg::new();
+        for opcode in
+            (0..=0xFF).filter(|opcode| !(SecpInstr::START..=SecpInstr::END).contains(opcode))
+        {
+            let mut marshaller = Marshaller::with([0u8; 8], [0u8; 8], &libs).unwrap();
+            assert_eq!(
+                <SecpInstr as Bytecode<LibId>>::decode_operands(&mut marshaller, opcode),
+                Err(CodeEofError)
+            );
+        }
+    }
+}
@@ -0,0 +1,528 @@
+// Reference rust implementation of AluVM (arithmetic logic unit virtual machine).
+// To find more on AluVM please check <https://aluvm.org>
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Designed in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+// Written in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+//
+// Copyright (C) 2021-2024 LNP/BP Standards Association, Switzerland.
+// Copyright (C) 2024-2025 Laboratories for Ubiquitous Deterministic Computing (UBIDECO),
+//                         Institute for Distributed and Cognitive Systems (InDCS), Switzerland.
+// Copyright (C) 2021-2025 Dr Maxim Orlovsky.
+// All rights under the above copyrights are reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use this file except
+// in compliance with the License. You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed under the License
+// is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express
+// or implied. See the License for the specific language governing permissions and limitations under
+// the License.
+
+use alloc::collections::BTreeSet;
+
+use secp256k1::{ecdsa, Message, PublicKey, Scalar, Secp256k1};
+
+use super::{RegSecp, SecpCore, SecpInstr, SecpIsa, SecpValue};
+use crate::core::{Core, FailureReason, NoExt, Site, SiteId, Status, Supercore};
+use crate::isa::{ComplexityClass, ExecStep, FlowKind, GotoTarget, Instruction};
+use crate::IsaId;
+
+/// Complexity of the signature verification and of the point multiplication, which take several
+/// orders of magnitude more time than the 64-bit arithmetic.
+const CURVE_MUL_COMPLEXITY: u64 = 2_000_000;
+
+impl<Id: SiteId> Instruction<Id> for SecpIsa<Id> {
+    const ISA_EXT: &'static [&'static str] = &["SECP256K"];
+
+    type Core = SecpCore;
+    type Context<'ctx> = ();
+
+    fn isa_ext_id(&self) -> Option<&'static str> {
+        match self {
+            SecpIsa::Ctrl(_) | SecpIsa::Reserved(_) => None,
+            SecpIsa::Secp(instr) => Instruction::<Id>::isa_ext_id(instr),
+        }
+    }
+
+    fn is_reserved(&self) -> bool { matches!(self, SecpIsa::Reserved(_)) }
+
+    fn is_goto_target(&self) -> bool {
+        match self {
+            SecpIsa::Ctrl(instr) => instr.is_goto_target(),
+            SecpIsa::Secp(instr) => Instruction::<Id>::is_goto_target(instr),
+            SecpIsa::Reserved(instr) => Instruction::<Id>::is_goto_target(instr),
+        }
+    }
+
+    fn local_goto_pos(&mut self) -> GotoTarget<'_> {
+        match self {
+            SecpIsa::Ctrl(instr) => instr.local_goto_pos(),
+            SecpIsa::Secp(instr) => Instruction::<Id>::local_goto_pos(instr),
+            SecpIsa::Reserved(instr) => Instruction::<Id>::local_goto_pos(instr),
+        }
+    }
+
+    fn remote_goto_pos(&mut self) -> Option<&mut Site<Id>> {
+        match self {
+            SecpIsa::Ctrl(instr) => instr.remote_goto_pos(),
+            SecpIsa::Secp(instr) => Instruction::<Id>::remote_goto_pos(instr),
+            SecpIsa::Reserved(instr) => Instruction::<Id>::remote_goto_pos(instr),
+        }
+    }
+
+    fn localize_goto(&self, pos: u16) -> Option<Self> {
+        match self {
+            SecpIsa::Ctrl(instr) => instr.localize_goto(pos).map(SecpIsa::Ctrl),
+            SecpIsa::Secp(_) | SecpIsa::Reserved(_) => None,
+        }
+    }
+
+    fn flow_kind(&self) -> FlowKind {
+        match self {
+            SecpIsa::Ctrl(instr) => instr.flow_kind(),
+            SecpIsa::Secp(instr) => Instruction::<Id>::flow_kind(instr),
+            SecpIsa::Reserved(instr) => Instruction::<Id>::flow_kind(instr),
+        }
+    }
+
+    fn src_regs(&self) -> BTreeSet<RegSecp> {
+        match self {
+            SecpIsa::Ctrl(_) | SecpIsa::Reserved(_) => none!(),
+            SecpIsa::Secp(instr) => Instruction::<Id>::src_regs(instr),
+        }
+    }
+
+    fn dst_regs(&self) -> BTreeSet<RegSecp> {
+        match self {
+            SecpIsa::Ctrl(_) | SecpIsa::Reserved(_) => none!(),
+            SecpIsa::Secp(instr) => Instruction::<Id>::dst_regs(instr),
+        }
+    }
+
+    fn op_data_bytes(&self) -> u16 {
+        match self {
+            SecpIsa::Ctrl(instr) => instr.op_data_bytes(),
+            SecpIsa::Secp(instr) => Instruction::<Id>::op_data_bytes(instr),
+            SecpIsa::Reserved(instr) => Instruction::<Id>::op_data_bytes(instr),
+        }
+    }
+
+    fn ext_data_bytes(&self) -> u16 {
+        match self {
+            SecpIsa::Ctrl(instr) => instr.ext_data_bytes(),
+            SecpIsa::Secp(instr) => Instruction::<Id>::ext_data_bytes(instr),
+            SecpIsa::Reserved(instr) => Instruction::<Id>::ext_data_bytes(instr),
+        }
+    }
+
+    fn complexity_class(&self) -> ComplexityClass {
+        match self {
+            SecpIsa::Ctrl(instr) => instr.complexity_class(),
+            SecpIsa::Secp(instr) => Instruction::<Id>::complexity_class(instr),
+            SecpIsa::Reserved(instr) => Instruction::<Id>::complexity_class(instr),
+        }
+    }
+
+    fn exec<const CALL_STACK_SIZE: usize>(
+        &self,
+        site: Site<Id>,
+        core: &mut Core<Id, Self::Core, CALL_STACK_SIZE>,
+        _: &Self::Context<'_>,
+    ) -> ExecStep<Site<Id>> {
+        match self {
+            SecpIsa::Ctrl(instr) => {
+                let mut subcore: Core<Id, NoExt, CALL_STACK_SIZE> = core.subcore();
+                let step = instr.exec(site, &mut subcore, &());
+                core.merge_subcore(subcore);
+                step
+            }
+            SecpIsa::Secp(instr) => instr.exec(site, core, &()),
+            SecpIsa::Reserved(instr) => {
+                let mut subcore: Core<Id, NoExt, CALL_STACK_SIZE> = core.subcore();
+                let step = instr.exec(site, &mut subcore, &());
+                core.merge_subcore(subcore);
+                step
+            }
+        }
+    }
+}
+
+impl<Id: SiteId> Instruction<Id> for SecpInstr {
+    const ISA_EXT: &'static [&'static str] = &["SECP256K"];
+
+    type Core = SecpCore;
+    type Context<'ctx> = ();
+
+    fn isa_ext_id(&self) -> Option<&'static str> { Some("SECP256K") }
+
+    fn is_goto_target(&self) -> bool { false }
+
+    fn local_goto_pos(&mut self) -> GotoTarget<'_> { GotoTarget::None }
+
+    fn remote_goto_pos(&mut self) -> Option<&mut Site<Id>> { None }
+
+    fn flow_kind(&self) -> FlowKind { FlowKind::Continue }
+
+    fn src_regs(&self) -> BTreeSet<RegSecp> {
+        match *self {
+            SecpInstr::Ld { .. } => none!(),
+            SecpInstr::Verify { sig, msg, key } => bset![sig, msg, key],
+            SecpInstr::Add { dst, src } | SecpInstr::Mul { dst, src } => bset![dst, src],
+        }
+    }
+
+    fn dst_regs(&self) -> BTreeSet<RegSecp> {
+        match *self {
+            SecpInstr::Ld { dst, .. } | SecpInstr::Add { dst, .. } | SecpInstr::Mul { dst, .. } => {
+                bset![dst]
+            }
+            SecpInstr::Verify { .. } => none!(),
+        }
+    }
+
+    fn op_data_bytes(&self) -> u16 {
+        match self {
+            SecpInstr::Ld { .. } => 4,
+            SecpInstr::Verify { .. } | SecpInstr::Add { .. } | SecpInstr::Mul { .. } => 0,
+        }
+    }
+
+    fn ext_data_bytes(&self) -> u16 {
+        match self {
+            SecpInstr::Ld { val, .. } => val.kind().bytes(),
+            SecpInstr::Verify { .. } | SecpInstr::Add { .. } | SecpInstr::Mul { .. } => 0,
+        }
+    }
+
+    fn complexity_class(&self) -> ComplexityClass {
+        match self {
+            SecpInstr::Ld { .. } => ComplexityClass::Medium,
+            SecpInstr::Add { .. } => ComplexityClass::Heavy,
+            SecpInstr::Verify { .. } | SecpInstr::Mul { .. } => {
+                ComplexityClass::Custom(CURVE_MUL_COMPLEXITY)
+            }
+        }
+    }
+
+    fn exec<const CALL_STACK_SIZE: usize>(
+        &self,
+        _: Site<Id>,
+        core: &mut Core<Id, Self::Core, CALL_STACK_SIZE>,
+        _: &Self::Context<'_>,
+    ) -> ExecStep<Site<Id>> {
+        match *self {
+            SecpInstr::Ld { dst, val } => {
+                if val.kind() != dst.kind() {
+                    return fail(core, dst, Some(SecpInstr::FAIL_KIND_MISMATCH));
+                }
+                core.set(dst, val);
+            }
+            SecpInstr::Verify { sig, msg, key } => {
+                let (Some(sig), Some(msg), Some(key)) =
+                    (core.get(sig), core.get(msg), core.get(key))
+                else {
+                    core.set_co(Status::Fail);
+                    core.set_failure(FailureReason::NoValue);
+                    return ExecStep::Fail;
+                };
+                core.set_co(if verify(sig, msg, key) { Status::Ok } else { Status::Fail });
+            }
+            SecpInstr::Add { dst, src } => {
+                return point_op(core, dst, src, |point1, val| {
+                    point1
+                        .combine(&point(val)?)
+                        .map_err(|_| SecpInstr::FAIL_INFINITY)
+                })
+            }
+            SecpInstr::Mul { dst, src } => {
+                return point_op(core, dst, src, |point, val| {
+                    point
+                        .mul_tweak(&Secp256k1::verification_only(), &scalar(val)?)
+                        .map_err(|_| SecpInstr::FAIL_INFINITY)
+                })
+            }
+        }
+        ExecStep::Next
+    }
+}
+
+/// Applies an operation to the destination register point and the source register value, putting
+/// the resulting point into the destination register.
+fn point_op<Id: SiteId, const CALL_STACK_SIZE: usize>(
+    core: &mut Core<Id, SecpCore, CALL_STACK_SIZE>,
+    dst: RegSecp,
+    src: RegSecp,
+    op: impl FnOnce(PublicKey, SecpValue) -> Result<PublicKey, u16>,
+) -> ExecStep<Site<Id>> {
+    let (Some(val1), Some(val2)) = (core.get(dst), core.get(src)) else {
+        return fail(core, dst, None);
+    };
+    match point(val1).and_then(|point| op(point, val2)) {
+        Ok(point) => {
+            core.set(dst, SecpValue::Point(point.serialize()));
+            ExecStep::Next
+        }
+        Err(code) => fail(core, dst, Some(code)),
+    }
+}
+
+/// Clears the destination register and fails `CK` with the given [`FailureReason::Custom`] code,
+/// or with [`FailureReason::NoValue`] if there is no code.
+fn fail<Id: SiteId, const CALL_STACK_SIZE: usize>(
+    core: &mut Core<Id, SecpCore, CALL_STACK_SIZE>,
+    dst: RegSecp,
+    code: Option<u16>,
+) -> ExecStep<Site<Id>> {
+    core.clr(dst);
+    core.set_failure(match code {
+        Some(code) => FailureReason::Custom(IsaId::from("SECP256K"), code),
+        None => FailureReason::NoValue,
+    });
+    ExecStep::Fail
+}
+
+fn point(val: SecpValue) -> Result<PublicKey, u16> {
+    match val {
+        SecpValue::Point(bytes) => {
+            PublicKey::from_slice(&bytes).map_err(|_| SecpInstr::FAIL_INVALID_POINT)
+        }
+        _ => Err(SecpInstr::FAIL_INVALID_POINT),
+    }
+}
+
+fn scalar(val: SecpValue) -> Result<Scalar, u16> {
+    match val {
+        SecpValue::Scalar(bytes) => {
+            Scalar::from_be_bytes(bytes).map_err(|_| SecpInstr::FAIL_INVALID_SCALAR)
+        }
+        _ => Err(SecpInstr::FAIL_INVALID_SCALAR),
+    }
+}
+
+fn verify(sig: SecpValue, msg: SecpValue, key: SecpValue) -> bool {
+    let (SecpValue::Sig(sig), SecpValue::Scalar(msg)) = (sig, msg) else {
+        return false;
+    };
+    let (Ok(sig), Ok(key)) = (ecdsa::Signature::from_compact(&sig), point(key)) else {
+        return false;
+    };
+    Secp256k1::verification_only()
+        .verify_ecdsa(&Message::from_digest(msg), &sig, &key)
+        .is_ok()
+}
+
+#[cfg(test)]
+mod test {
+    #![cfg_attr(coverage_nightly, coverage(off))]
+
+    use amplify::hex::FromHex;
+
+    use super::*;
+    use crate::isa::CtrlInstr;
+    use crate::{CoreConfig, Lib, LibId, LibSite, Vm};
+
+    /// Public key for the secret key `0x1111..11`.
+    const KEY: &str = "034f355bdcb7cc0af728ef3cceb9615d90684bb5b2ca5f859ab0f0b704075871aa";
+    /// RFC6979 signature of [`digest`] with the secret key `0x1111..11`.
+    const SIG: &str = "45c66894f81a0c55d809102082de99d030ca1c9e2a9ce413b27f38f3c0ffa9d7\
+                       1420f34d854f3fafc3d50f9d9b448a42579601a981281f0d8daa5abff84d2322";
+    /// Generator point and its multiples.
+    const G1: &str = "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798";
+    const G2: &str = "02c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5";
+    const G3: &str = "02f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9";
+
+    fn site() -> Site<LibId> { Site::new(LibId::from([0xA5u8; 32]), 0) }
+
+    fn point(hex: &str) -> SecpValue { SecpValue::Point(<[u8; 33]>::from_hex(hex).unwrap()) }
+
+    fn sig(hex: &str) -> SecpValue { SecpValue::Sig(<[u8; 64]>::from_hex(hex).unwrap()) }
+
+    fn scalar(val: u8) -> SecpValue {
+        let mut bytes = [0u8; 32];
+        bytes[31] = val;
+        SecpValue::Scalar(bytes)
+    }
+
+    fn digest() -> SecpValue { SecpValue::Scalar(core::array::from_fn(|i| i as u8)) }
+
+    fn exec(core: &mut Core<LibId, SecpCore>, instr: SecpInstr) -> ExecStep<Site<LibId>> {
+        SecpIsa::from(instr).exec(site(), core, &())
+    }
+
+    fn failure(core: &mut Core<LibId, SecpCore>) -> Option<FailureReason> {
+        let _ = core.fail_ck();
+        core.last_failure().cloned()
+    }
+
+    fn custom(code: u16) -> Option<FailureReason> {
+        Some(FailureReason::Custom(IsaId::from("SECP256K"), code))
+    }
+
+    fn verify_with(sig: SecpValue, msg: SecpValue, key: SecpValue) -> Status {
+        let mut core = Core::<LibId, SecpCore>::new();
+        core.set(RegSecp::G0, sig);
+        core.set(RegSecp::S0, msg);
+        core.set(RegSecp::P0, key);
+        let instr = SecpInstr::Verify { sig: RegSecp::G0, msg: RegSecp::S0, key: RegSecp::P0 };
+        assert_eq!(exec(&mut core, instr), ExecStep::Next);
+        core.co()
+    }
+
+    #[test]
+    fn verify_vectors() {
+        assert_eq!(verify_with(sig(SIG), digest(), point(KEY)), Status::Ok);
+        // Different message
+        assert_eq!(verify_with(sig(SIG), scalar(1), point(KEY)), Status::Fail);
+        // Different key
+        assert_eq!(verify_with(sig(SIG), digest(), point(G1)), Status::Fail);
+        // Tampered signature
+        let mut tampered = <[u8; 64]>::from_hex(SIG).unwrap();
+        tampered[63] ^= 1;
+        assert_eq!(verify_with(SecpValue::Sig(tampered), digest(), point(KEY)), Status::Fail);
+        // Non-normalized signature, with `S` replaced by `n - S`
+        let high_s = "45c66894f81a0c55d809102082de99d030ca1c9e2a9ce413b27f38f3c0ffa9d7\
+                      ebdf0cb27ab0c0503c2af06264bb75bc6318db3d2e20812e322803ccd7e91e1f";
+        assert_eq!(verify_with(sig(high_s), digest(), point(KEY)), Status::Fail);
+        // Invalid encodings
+        assert_eq!(verify_with(SecpValue::Sig([0xFF; 64]), digest(), point(KEY)), Status::Fail);
+        assert_eq!(verify_with(sig(SIG), digest(), SecpValue::Point([0x05; 33])), Status::Fail);
+    }
+
+    #[test]
+    fn verify_unset() {
+        let mut core = Core::<LibId, SecpCore>::new();
+        core.set(RegSecp::G0, sig(SIG));
+        core.set(RegSecp::P0, point(KEY));
+        let instr = SecpInstr::Verify { sig: RegSecp::G0, msg: RegSecp::S0, key: RegSecp::P0 };
+        assert_eq!(exec(&mut core, instr), ExecStep::Fail);
+        assert_eq!(core.co(), Status::Fail);
+        assert_eq!(failure(&mut core), Some(FailureReason::NoValue));
+    }
+
+    #[test]
+    fn point_ops() {
+        let mut core = Core::<LibId, SecpCore>::new();
+        core.set(RegSecp::P0, point(G1));
+        core.set(RegSecp::P1, point(G1));
+        let add = SecpInstr::Add { dst: RegSecp::P0, src: RegSecp::P1 };
+        assert_eq!(exec(&mut core, add), ExecStep::Next);
+        assert_eq!(core.get(RegSecp::P0), Some(point(G2)));
+
+        core.set(RegSecp::P0, point(G1));
+        core.set(RegSecp::S0, scalar(3));
+        let mul = SecpInstr::Mul { dst: RegSecp::P0, src: RegSecp::S0 };
+        assert_eq!(exec(&mut core, mul), ExecStep::Next);
+        assert_eq!(core.get(RegSecp::P0), Some(point(G3)));
+        assert_eq!(core.get(RegSecp::S0), Some(scalar(3)));
+        assert_eq!(core.ck(), Status::Ok);
+    }
+
+    #[test]
+    fn point_ops_fail() {
+        let add = SecpInstr::Add { dst: RegSecp::P0, src: RegSecp::P1 };
+        let mul = SecpInstr::Mul { dst: RegSecp::P0, src: RegSecp::S0 };
+        // `G` with the flipped parity is `-G`
+        let neg_g1 = format!("03{}", &G1[2..]);
+        let order = SecpValue::Scalar(
+            <[u8; 32]>::from_hex(
+                "fffffffffffffffffffffffffffffffebaaedce6af48a03bbfd25e8cd0364141",
+            )
+            .unwrap(),
+        );
+        // The `x` coordinate exceeds the field size
+        let mut off_curve = [0xFF; 33];
+        off_curve[0] = 0x02;
+        let off_curve = SecpValue::Point(off_curve);
+        let cases = [
+            (add, point(G1), Some(point(&neg_g1)), custom(SecpInstr::FAIL_INFINITY)),
+            (
+                add,
+                point(G1),
+                Some(SecpValue::Point([0x05; 33])),
+                custom(SecpInstr::FAIL_INVALID_POINT),
+            ),
+            (add, off_curve, Some(point(G1)), custom(SecpInstr::FAIL_INVALID_POINT)),
+            (add, point(G1), None, Some(FailureReason::NoValue)),
+            (mul, point(G1), Some(scalar(0)), custom(SecpInstr::FAIL_INFINITY)),
+            (mul, point(G1), Some(order), custom(SecpInstr::FAIL_INVALID_SCALAR)),
+            (mul, point(G1), None, Some(FailureReason::NoValue)),
+        ];
+        for (instr, dst, src, reason) in cases {
+            let mut core = Core::<LibId, SecpCore>::new();
+            core.set(RegSecp::P0, dst);
+            let src_reg = if instr == add { RegSecp::P1 } else { RegSecp::S0 };
+            core.put(src_reg, src);
+            assert_eq!(exec(&mut core, instr), ExecStep::Fail, "{instr}");
+            assert_eq!(core.get(RegSecp::P0), None, "{instr}");
+            assert_eq!(failure(&mut core), reason, "{instr}");
+        }
+    }
+
+    #[test]
+    fn ld() {
+        let mut core = Core::<LibId, SecpCore>::new();
+        let instr = SecpInstr::Ld { dst: RegSecp::G2, val: sig(SIG) };
+        assert_eq!(exec(&mut core, instr), ExecStep::Next);
+        assert_eq!(core.get(RegSecp::G2), Some(sig(SIG)));
+
+        let instr = SecpInstr::Ld { dst: RegSecp::G2, val: point(G1) };
+        assert_eq!(exec(&mut core, instr), ExecStep::Fail);
+        assert_eq!(core.get(RegSecp::G2), None);
+        assert_eq!(failure(&mut core), custom(SecpInstr::FAIL_KIND_MISMATCH));
+    }
+
+    #[test]
+    fn props() {
+        let mut instr = SecpIsa::<LibId>::from(SecpInstr::Ld { dst: RegSecp::P1, val: point(G1) });
+        assert_eq!(instr.isa_ext_id(), Some("SECP256K"));
+        assert_eq!(instr.local_goto_pos(), GotoTarget::None);
+        assert_eq!(instr.dst_regs(), bset![RegSecp::P1]);
+        assert_eq!(instr.src_regs(), none!());
+        assert_eq!(instr.op_data_bytes(), 4);
+        assert_eq!(instr.ext_data_bytes(), 33);
+        assert_eq!(instr.complexity(), 10_000);
+
+        let instr = SecpIsa::<LibId>::from(SecpInstr::Verify {
+            sig: RegSecp::G0,
+            msg: RegSecp::S1,
+            key: RegSecp::P2,
+        });
+        assert_eq!(instr.src_regs(), bset![RegSecp::S1, RegSecp::P2, RegSecp::G0]);
+        assert_eq!(instr.src_reg_bytes(), 32 + 33 + 64);
+        assert_eq!(instr.dst_regs(), none!());
+        assert_eq!(instr.complexity(), CURVE_MUL_COMPLEXITY);
+        assert_eq!(SecpIsa::<LibId>::isa_ext(), IsaId::canonical_set([IsaId::from("SECP256K")]));
+        assert_eq!(SecpIsa::<LibId>::from(CtrlInstr::Stop).isa_ext_id(), None);
+    }
+
+    #[test]
+    fn program() {
+        let code: [SecpIsa<LibId>; 6] = [
+            SecpInstr::Ld { dst: RegSecp::G0, val: sig(SIG) }.into(),
+            SecpInstr::Ld { dst: RegSecp::S0, val: digest() }.into(),
+            SecpInstr::Ld { dst: RegSecp::P0, val: point(KEY) }.into(),
+            SecpInstr::Verify { sig: RegSecp::G0, msg: RegSecp::S0, key: RegSecp::P0 }.into(),
+            CtrlInstr::ChkCo.into(),
+            CtrlInstr::Stop.into(),
+        ];
+        let lib = Lib::assemble(&code).unwrap();
+        assert_eq!(lib.disassemble::<SecpIsa<LibId>>().unwrap(), code);
+
+        let mut vm = Vm::<SecpIsa<LibId>>::with(CoreConfig::default(), ());
+        let status = vm.exec(LibSite::new(lib.lib_id(), 0), &(), |_| Some(&lib));
+        assert_eq!(status, Status::Ok);
+
+        // A different message fails the check
+        let mut code = code;
+        code[1] = SecpInstr::Ld { dst: RegSecp::S0, val: scalar(1) }.into();
+        let lib = Lib::assemble(&code).unwrap();
+        let mut vm = Vm::<SecpIsa<LibId>>::with(CoreConfig::default(), ());
+        let status = vm.exec(LibSite::new(lib.lib_id(), 0), &(), |_| Some(&lib));
+        assert_eq!(status, Status::Fail);
+        assert_eq!(vm.last_failure(), Some(&FailureReason::CheckFailed));
+    }
+}
@@ -0,0 +1,133 @@
+// Reference rust implementation of AluVM (arithmetic logic unit virtual machine).
+// To find more on AluVM please check <https://aluvm.org>
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Designed in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+// Written in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+//
+// Copyright (C) 2021-2024 LNP/BP Standards Association, Switzerland.
+// Copyright (C) 2024-2025 Laboratories for Ubiquitous Deterministic Computing (UBIDECO),
+//                         Institute for Distributed and Cognitive Systems (InDCS), Switzerland.
+// Copyright (C) 2021-2025 Dr Maxim Orlovsky.
+// All rights under the above copyrights are reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use this file except
+// in compliance with the License. You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed under the License
+// is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express
+// or implied. See the License for the specific language governing permissions and limitations under
+// the License.
+
+use core::fmt::{self, Display, Formatter};
+
+use super::{RegSecp, SecpValue};
+use crate::core::SiteId;
+use crate::isa::{CtrlInstr, ReservedInstr};
+
+/// Elliptic curve instructions over secp256k1 (`SECP256K` ISA extension).
+///
+/// Each operand must be a register of the kind expected by the instruction (see
+/// [`RegSecp::kind`]); instructions with mismatching operands are not decoded from the bytecode.
+/// Failures caused by invalid register values set `CK` to a failure with one of the
+/// [`crate::FailureReason::Custom`] codes defined by this type.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum SecpInstr {
+    /// Load a value from the library data segment into a register.
+    ///
+    /// If the value kind doesn't match the register kind, clears the register and sets `CK` to a
+    /// failure.
+    Ld {
+        /** Destination register */
+        dst: RegSecp,
+        /** Value to load */
+        val: SecpValue,
+    },
+
+    /// Verify an ECDSA signature of a message digest against a public key.
+    ///
+    /// Sets `CO` to a non-failed state if the signature is valid, and to a failed state otherwise,
+    /// including the cases when the signature or the key are not validly encoded. Signatures with
+    /// a high `S` value are not valid. If any of the registers is not set, sets both `CO` and `CK`
+    /// to a failure.
+    Verify {
+        /** Register holding the signature */
+        sig: RegSecp,
+        /** Register holding the message digest */
+        msg: RegSecp,
+        /** Register holding the public key */
+        key: RegSecp,
+    },
+
+    /// Add the source register point to the destination register point.
+    ///
+    /// If any of the registers is not set or doesn't hold a valid point, or if the sum is the
+    /// point at infinity, clears the destination register and sets `CK` to a failure.
+    Add {
+        /** Destination register, which is also the first operand */
+        dst: RegSecp,
+        /** Source register */
+        src: RegSecp,
+    },
+
+    /// Multiply the destination register point by the source register scalar.
+    ///
+    /// If any of the registers is not set, doesn't hold a valid point or scalar (which must be
+    /// less than the curve order), or if the scalar is zero, clears the destination register and
+    /// sets `CK` to a failure.
+    Mul {
+        /** Destination register, which is also the point operand */
+        dst: RegSecp,
+        /** Register holding the scalar */
+        src: RegSecp,
+    },
+}
+
+impl SecpInstr {
+    /// Code of the [`crate::FailureReason::Custom`] failure when a register doesn't hold a valid
+    /// curve point.
+    pub const FAIL_INVALID_POINT: u16 = 1;
+
+    /// Code of the [`crate::FailureReason::Custom`] failure when a register doesn't hold a valid
+    /// scalar.
+    pub const FAIL_INVALID_SCALAR: u16 = 2;
+
+    /// Code of the [`crate::FailureReason::Custom`] failure when the result of an operation is the
+    /// point at infinity.
+    pub const FAIL_INFINITY: u16 = 3;
+
+    /// Code of the [`crate::FailureReason::Custom`] failure when a loaded value doesn't match the
+    /// register kind.
+    pub const FAIL_KIND_MISMATCH: u16 = 4;
+}
+
+impl Display for SecpInstr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match *self {
+            SecpInstr::Ld { dst, val } => write!(f, "ld      {dst}, {val}"),
+            SecpInstr::Verify { sig, msg, key } => write!(f, "verify  {sig}, {msg}, {key}"),
+            SecpInstr::Add { dst, src } => write!(f, "add     {dst}, {src}"),
+            SecpInstr::Mul { dst, src } => write!(f, "mul     {dst}, {src}"),
+        }
+    }
+}
+
+/// Instruction set composed of the control flow instructions and the `SECP256K` ISA extension.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Display, From)]
+#[display(inner)]
+pub enum SecpIsa<Id: SiteId> {
+    /// Control flow instructions.
+    #[from]
+    Ctrl(CtrlInstr<Id>),
+
+    /// Elliptic curve instructions.
+    #[from]
+    Secp(SecpInstr),
+
+    /// Reserved instruction for future use.
+    #[from]
+    Reserved(ReservedInstr),
+}
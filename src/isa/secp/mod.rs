@@ -0,0 +1,268 @@
+// Reference rust implementation of AluVM (arithmetic logic unit virtual machine).
+// To find more on AluVM please check <https://aluvm.org>
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Designed in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+// Written in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+//
+// Copyright (C) 2021-2024 LNP/BP Standards Association, Switzerland.
+// Copyright (C) 2024-2025 Laboratories for Ubiquitous Deterministic Computing (UBIDECO),
+//                         Institute for Distributed and Cognitive Systems (InDCS), Switzerland.
+// Copyright (C) 2021-2025 Dr Maxim Orlovsky.
+// All rights under the above copyrights are reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use this file except
+// in compliance with the License. You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed under the License
+// is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express
+// or implied. See the License for the specific language governing permissions and limitations under
+// the License.
+
+//! Elliptic curve operations over secp256k1 (`SECP256K` ISA extension): ECDSA signature
+//! verification and arithmetic on the curve points.
+//!
+//! The extension is available with the `secp256k1` feature.
+
+mod regs;
+mod instr;
+mod bytecode;
+mod exec;
+
+use core::fmt::{self, Display, Formatter};
+
+use amplify::num::u4;
+pub use instr::{SecpInstr, SecpIsa};
+pub use regs::SecpCore;
+
+use crate::core::{NoRegs, Register};
+use crate::isa::RegIndexError;
+
+/// Kind of the value held by a register of the `SECP256K` ISA extension.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Display)]
+#[display(lowercase)]
+pub enum SecpKind {
+    /// 256-bit big-endian scalar, which may also be a message digest.
+    Scalar,
+    /// Curve point in a compressed SEC1 encoding.
+    Point,
+    /// ECDSA signature in a compact encoding.
+    Sig,
+}
+
+impl SecpKind {
+    /// Number of bytes taken by a value of this kind.
+    pub const fn bytes(self) -> u16 {
+        match self {
+            SecpKind::Scalar => 32,
+            SecpKind::Point => 33,
+            SecpKind::Sig => 64,
+        }
+    }
+}
+
+/// Registers provided by the `SECP256K` ISA extension.
+///
+/// The registers are grouped by the kind of value they hold (see [`SecpKind`]): `S`-registers
+/// hold scalars, `P`-registers hold curve points, and `G`-registers hold signatures.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug)]
+#[repr(u8)]
+#[allow(missing_docs)]
+pub enum RegSecp {
+    S0 = 0,
+    S1,
+    S2,
+    S3,
+    S4,
+    S5,
+    S6,
+    S7,
+    P0,
+    P1,
+    P2,
+    P3,
+    G0,
+    G1,
+    G2,
+    G3,
+}
+
+impl RegSecp {
+    /// All registers, ordered by their index.
+    pub const ALL: [RegSecp; 16] = [
+        RegSecp::S0,
+        RegSecp::S1,
+        RegSecp::S2,
+        RegSecp::S3,
+        RegSecp::S4,
+        RegSecp::S5,
+        RegSecp::S6,
+        RegSecp::S7,
+        RegSecp::P0,
+        RegSecp::P1,
+        RegSecp::P2,
+        RegSecp::P3,
+        RegSecp::G0,
+        RegSecp::G1,
+        RegSecp::G2,
+        RegSecp::G3,
+    ];
+
+    /// Returns the register with the given index, or `None` if the index is not less than 16.
+    ///
+    /// The [`TryFrom`] implementation does the same, reporting the out-of-range index as
+    /// [`RegIndexError`]; the index is never wrapped around.
+    pub const fn with(index: u8) -> Option<Self> {
+        if index as usize >= Self::ALL.len() {
+            return None;
+        }
+        Some(Self::ALL[index as usize])
+    }
+
+    /// Index of the register (from 0 to 15).
+    pub const fn index(self) -> u8 { self as u8 }
+
+    /// Kind of the value held by the register.
+    pub const fn kind(self) -> SecpKind {
+        match self.index() {
+            0..=7 => SecpKind::Scalar,
+            8..=11 => SecpKind::Point,
+            _ => SecpKind::Sig,
+        }
+    }
+}
+
+impl From<u4> for RegSecp {
+    fn from(index: u4) -> Self { Self::ALL[index.to_u8() as usize] }
+}
+
+impl TryFrom<u8> for RegSecp {
+    type Error = RegIndexError;
+
+    fn try_from(index: u8) -> Result<Self, Self::Error> {
+        Self::with(index).ok_or(RegIndexError(index))
+    }
+}
+
+impl Display for RegSecp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.kind() {
+            SecpKind::Scalar => write!(f, "S{}", self.index()),
+            SecpKind::Point => write!(f, "P{}", self.index() - 8),
+            SecpKind::Sig => write!(f, "G{}", self.index() - 12),
+        }
+    }
+}
+
+impl Register for RegSecp {
+    type Value = SecpValue;
+
+    fn bytes(self) -> u16 { self.kind().bytes() }
+
+    fn enumerate() -> impl Iterator<Item = Self> { Self::ALL.into_iter() }
+}
+
+impl From<NoRegs> for RegSecp {
+    fn from(regs: NoRegs) -> Self { match regs {} }
+}
+
+/// Value of a register of the `SECP256K` ISA extension.
+///
+/// The value is kept in its serialized form and is checked for being a valid scalar, point or
+/// signature only when an instruction uses it.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum SecpValue {
+    /// 256-bit big-endian scalar.
+    Scalar([u8; 32]),
+    /// Curve point in a compressed SEC1 encoding.
+    Point([u8; 33]),
+    /// ECDSA signature in a compact encoding.
+    Sig([u8; 64]),
+}
+
+impl SecpValue {
+    /// Constructs a value of the given kind from its serialized form.
+    ///
+    /// Returns `None` if the number of bytes doesn't match the kind.
+    pub fn from_slice(kind: SecpKind, bytes: &[u8]) -> Option<Self> {
+        Some(match kind {
+            SecpKind::Scalar => SecpValue::Scalar(bytes.try_into().ok()?),
+            SecpKind::Point => SecpValue::Point(bytes.try_into().ok()?),
+            SecpKind::Sig => SecpValue::Sig(bytes.try_into().ok()?),
+        })
+    }
+
+    /// Kind of the value.
+    pub const fn kind(&self) -> SecpKind {
+        match self {
+            SecpValue::Scalar(_) => SecpKind::Scalar,
+            SecpValue::Point(_) => SecpKind::Point,
+            SecpValue::Sig(_) => SecpKind::Sig,
+        }
+    }
+
+    /// Serialized form of the value.
+    pub fn as_slice(&self) -> &[u8] {
+        match self {
+            SecpValue::Scalar(bytes) => bytes,
+            SecpValue::Point(bytes) => bytes,
+            SecpValue::Sig(bytes) => bytes,
+        }
+    }
+}
+
+impl Display for SecpValue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.as_slice()
+            .iter()
+            .try_for_each(|byte| write!(f, "{byte:02x}"))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![cfg_attr(coverage_nightly, coverage(off))]
+
+    use super::*;
+
+    #[test]
+    fn regs() {
+        let names = RegSecp::ALL.map(|reg| reg.to_string());
+        assert_eq!(names, [
+            "S0", "S1", "S2", "S3", "S4", "S5", "S6", "S7", "P0", "P1", "P2", "P3", "G0", "G1",
+            "G2", "G3"
+        ]);
+        for (index, reg) in RegSecp::ALL.into_iter().enumerate() {
+            assert_eq!(reg.index() as usize, index);
+            assert_eq!(RegSecp::from(u4::with(index as u8)), reg);
+            assert_eq!(RegSecp::with(index as u8), Some(reg));
+            assert_eq!(RegSecp::try_from(index as u8), Ok(reg));
+        }
+        for index in [16u8, 17, 31, 255] {
+            assert_eq!(RegSecp::with(index), None);
+            assert_eq!(RegSecp::try_from(index), Err(RegIndexError(index)));
+        }
+        assert_eq!(RegSecp::S7.kind(), SecpKind::Scalar);
+        assert_eq!(RegSecp::P0.kind(), SecpKind::Point);
+        assert_eq!(RegSecp::G3.kind(), SecpKind::Sig);
+        assert_eq!(RegSecp::P3.bytes(), 33);
+        assert_eq!(RegSecp::G0.bytes(), 64);
+    }
+
+    #[test]
+    fn value() {
+        let val = SecpValue::from_slice(SecpKind::Scalar, &[0xA5; 32]).unwrap();
+        assert_eq!(val, SecpValue::Scalar([0xA5; 32]));
+        assert_eq!(val.kind(), SecpKind::Scalar);
+        assert_eq!(val.to_string(), "a5".repeat(32));
+        assert_eq!(SecpValue::from_slice(SecpKind::Point, &[0xA5; 32]), None);
+        assert_eq!(SecpValue::from_slice(SecpKind::Sig, &[0; 64]), Some(SecpValue::Sig([0; 64])));
+        for kind in [SecpKind::Scalar, SecpKind::Point, SecpKind::Sig] {
+            let bytes = vec![1u8; kind.bytes() as usize];
+            assert_eq!(SecpValue::from_slice(kind, &bytes).unwrap().as_slice(), bytes);
+        }
+    }
+}
@@ -0,0 +1,120 @@
+// Reference rust implementation of AluVM (arithmetic logic unit virtual machine).
+// To find more on AluVM please check <https://aluvm.org>
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Designed in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+// Written in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+//
+// Copyright (C) 2021-2024 LNP/BP Standards Association, Switzerland.
+// Copyright (C) 2024-2025 Laboratories for Ubiquitous Deterministic Computing (UBIDECO),
+//                         Institute for Distributed and Cognitive Systems (InDCS), Switzerland.
+// Copyright (C) 2021-2025 Dr Maxim Orlovsky.
+// All rights under the above copyrights are reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use this file except
+// in compliance with the License. You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed under the License
+// is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express
+// or implied. See the License for the specific language governing permissions and limitations under
+// the License.
+
+use super::{RegSecp, SecpValue};
+use crate::core::{CoreExt, NoExt, Supercore};
+
+/// Extension of the AluVM core with the registers used by the `SECP256K` ISA extension.
+///
+/// All registers are initialized to `None`.
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct SecpCore {
+    regs: [Option<SecpValue>; 16],
+}
+
+impl CoreExt for SecpCore {
+    type Reg = RegSecp;
+    type Config = ();
+    type Snapshot = [Option<SecpValue>; 16];
+
+    fn with(_config: Self::Config) -> Self { default!() }
+
+    fn get(&self, reg: Self::Reg) -> Option<SecpValue> { self.regs[reg.index() as usize] }
+
+    fn clr(&mut self, reg: Self::Reg) { self.regs[reg.index() as usize] = None }
+
+    /// # Panics
+    ///
+    /// If the kind of the value doesn't match the kind of the register.
+    fn put(&mut self, reg: Self::Reg, val: Option<SecpValue>) {
+        if let Some(val) = val {
+            assert_eq!(val.kind(), reg.kind(), "value kind doesn't match register {reg}");
+        }
+        self.regs[reg.index() as usize] = val
+    }
+
+    fn reset(&mut self) { *self = default!() }
+
+    fn snapshot(&self) -> Self::Snapshot { self.regs }
+
+    fn restore(&mut self, snapshot: Self::Snapshot) { self.regs = snapshot }
+
+    fn set_arbitrary(&mut self, bytes: &mut impl Iterator<Item = u8>) {
+        for reg in RegSecp::ALL {
+            let val = match bytes.next() {
+                Some(flag) if flag & 1 == 1 => {
+                    let buf = bytes
+                        .by_ref()
+                        .chain(core::iter::repeat(0))
+                        .take(reg.kind().bytes() as usize)
+                        .collect::<alloc::vec::Vec<_>>();
+                    SecpValue::from_slice(reg.kind(), &buf)
+                }
+                _ => None,
+            };
+            self.regs[reg.index() as usize] = val;
+        }
+    }
+}
+
+impl Supercore<NoExt> for SecpCore {
+    fn subcore(&self) -> NoExt { NoExt }
+
+    fn merge_subcore(&mut self, _subcore: NoExt) {}
+}
+
+#[cfg(test)]
+mod test {
+    #![cfg_attr(coverage_nightly, coverage(off))]
+
+    use super::*;
+
+    #[test]
+    fn core_ext() {
+        let mut core = SecpCore::with(());
+        assert_eq!(core.get(RegSecp::P1), None);
+        core.set(RegSecp::P1, SecpValue::Point([2; 33]));
+        assert_eq!(core.get(RegSecp::P1), Some(SecpValue::Point([2; 33])));
+        core.clr(RegSecp::P1);
+        assert_eq!(core.get(RegSecp::P1), None);
+        core.set(RegSecp::S0, SecpValue::Scalar([1; 32]));
+        let snapshot = core.snapshot();
+        core.reset();
+        assert_eq!(core, SecpCore::default());
+        core.restore(snapshot);
+        assert_eq!(core.get(RegSecp::S0), Some(SecpValue::Scalar([1; 32])));
+
+        let mut bytes = [1u8, 7].into_iter().chain([0; 15]);
+        core.set_arbitrary(&mut bytes);
+        let mut expected = [0u8; 32];
+        expected[0] = 7;
+        assert_eq!(core.get(RegSecp::S0), Some(SecpValue::Scalar(expected)));
+        assert_eq!(core.get(RegSecp::S1), None);
+        assert_eq!(core.get(RegSecp::G3), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "value kind doesn't match register G0")]
+    fn kind_mismatch() { SecpCore::default().set(RegSecp::G0, SecpValue::Scalar([1; 32])); }
+}
@@ -0,0 +1,345 @@
+// Reference rust implementation of AluVM (arithmetic logic unit virtual machine).
+// To find more on AluVM please check <https://aluvm.org>
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Designed in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+// Written in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+//
+// Copyright (C) 2021-2024 LNP/BP Standards Association, Switzerland.
+// Copyright (C) 2024-2025 Laboratories for Ubiquitous Deterministic Computing (UBIDECO),
+//                         Institute for Distributed and Cognitive Systems (InDCS), Switzerland.
+// Copyright (C) 2021-2025 Dr Maxim Orlovsky.
+// All rights under the above copyrights are reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use this file except
+// in compliance with the License. You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed under the License
+// is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express
+// or implied. See the License for the specific language governing permissions and limitations under
+// the License.
+
+//! Strict encoding of the instructions using [`LibId`] as the program identifier.
+//!
+//! The instructions are generic over the program identifier, which doesn't have to be strict
+//! encodable; thus, the strict encoding is implemented for the concrete [`LibId`] identifier only.
+//! Variant tags match the instruction opcodes.
+
+use std::io;
+
+use strict_encoding::{
+    DecodeError, DefineStruct, DefineUnion, ReadStruct, ReadUnion, StrictDecode, StrictDeserialize,
+    StrictDumb, StrictEncode, StrictSerialize, StrictSum, StrictType, StrictUnion, TypeName,
+    TypedRead, TypedWrite, WriteStruct, WriteUnion,
+};
+
+use super::{Bytecode, CtrlInstr, Instr, ReservedInstr};
+use crate::{LibId, Site, LIB_NAME_ALUVM};
+
+impl StrictDumb for CtrlInstr<LibId> {
+    fn strict_dumb() -> Self { CtrlInstr::Nop }
+}
+
+impl StrictType for CtrlInstr<LibId> {
+    const STRICT_LIB_NAME: &'static str = LIB_NAME_ALUVM;
+    fn strict_name() -> Option<TypeName> { Some(tn!("CtrlInstr")) }
+}
+
+impl StrictSum for CtrlInstr<LibId> {
+    const ALL_VARIANTS: &'static [(u8, &'static str)] = &[
+        (Self::NOP, "nop"),
+        (Self::NOCO, "notCo"),
+        (Self::CHCO, "chkCo"),
+        (Self::CHCK, "chkCk"),
+        (Self::FAIL, "failCk"),
+        (Self::RSET, "rsetCk"),
+        (Self::JMP, "jmp"),
+        (Self::JINE, "jiOvfl"),
+        (Self::JIFAIL, "jiFail"),
+        (Self::SH, "sh"),
+        (Self::SHNE, "shOvfl"),
+        (Self::SHFAIL, "shFail"),
+        (Self::EXEC, "exec"),
+        (Self::FN, "fn"),
+        (Self::CALL, "call"),
+        (Self::RET, "ret"),
+        (Self::STOP, "stop"),
+        (Self::SHW, "shW"),
+        (Self::SHWNE, "shWOvfl"),
+        (Self::SHWFAIL, "shWFail"),
+        (Self::SAVE, "save"),
+    ];
+
+    fn variant_name(&self) -> &'static str {
+        let opcode = Bytecode::<LibId>::opcode_byte(self);
+        Self::ALL_VARIANTS
+            .iter()
+            .find(|(tag, _)| *tag == opcode)
+            .map(|(_, name)| *name)
+            .expect("all opcodes are listed")
+    }
+}
+
+impl StrictUnion for CtrlInstr<LibId> {}
+
+impl StrictEncode for CtrlInstr<LibId> {
+    fn strict_encode<W: TypedWrite>(&self, writer: W) -> io::Result<W> {
+        writer.write_union::<Self>(|definer| {
+            let pos = |d: <W::UnionDefiner as DefineUnion>::StructDefiner| {
+                d.define_field::<u16>(fname!("pos")).complete()
+            };
+            let shift = |d: <W::UnionDefiner as DefineUnion>::StructDefiner| {
+                d.define_field::<i8>(fname!("shift")).complete()
+            };
+            let shift_wide = |d: <W::UnionDefiner as DefineUnion>::StructDefiner| {
+                d.define_field::<i16>(fname!("shift")).complete()
+            };
+            let site = |d: <W::UnionDefiner as DefineUnion>::StructDefiner| {
+                d.define_field::<Site<LibId>>(fname!("site")).complete()
+            };
+            let mask = |d: <W::UnionDefiner as DefineUnion>::StructDefiner| {
+                d.define_field::<u16>(fname!("mask")).complete()
+            };
+            let writer = definer
+                .define_unit(vname!("nop"))
+                .define_unit(vname!("notCo"))
+                .define_unit(vname!("chkCo"))
+                .define_unit(vname!("chkCk"))
+                .define_unit(vname!("failCk"))
+                .define_unit(vname!("rsetCk"))
+                .define_struct(vname!("jmp"), pos)
+                .define_struct(vname!("jiOvfl"), pos)
+                .define_struct(vname!("jiFail"), pos)
+                .define_struct(vname!("sh"), shift)
+                .define_struct(vname!("shOvfl"), shift)
+                .define_struct(vname!("shFail"), shift)
+                .define_struct(vname!("exec"), site)
+                .define_struct(vname!("fn"), pos)
+                .define_struct(vname!("call"), site)
+                .define_unit(vname!("ret"))
+                .define_unit(vname!("stop"))
+                .define_struct(vname!("shW"), shift_wide)
+                .define_struct(vname!("shWOvfl"), shift_wide)
+                .define_struct(vname!("shWFail"), shift_wide)
+                .define_struct(vname!("save"), mask)
+                .complete();
+
+            let name = vname!(self.variant_name());
+            Ok(match self {
+                CtrlInstr::Nop
+                | CtrlInstr::NotCo
+                | CtrlInstr::ChkCo
+                | CtrlInstr::ChkCk
+                | CtrlInstr::FailCk
+                | CtrlInstr::RsetCk
+                | CtrlInstr::Ret
+                | CtrlInstr::Stop => writer.write_unit(name)?,
+                CtrlInstr::Jmp { pos }
+                | CtrlInstr::JiOvfl { pos }
+                | CtrlInstr::JiFail { pos }
+                | CtrlInstr::Fn { pos } => writer
+                    .write_struct(name, |w| Ok(w.write_field(fname!("pos"), pos)?.complete()))?,
+                CtrlInstr::Sh { shift }
+                | CtrlInstr::ShOvfl { shift }
+                | CtrlInstr::ShFail { shift } => writer.write_struct(name, |w| {
+                    Ok(w.write_field(fname!("shift"), shift)?.complete())
+                })?,
+                CtrlInstr::ShW { shift }
+                | CtrlInstr::ShWOvfl { shift }
+                | CtrlInstr::ShWFail { shift } => writer.write_struct(name, |w| {
+                    Ok(w.write_field(fname!("shift"), shift)?.complete())
+                })?,
+                CtrlInstr::Exec { site } | CtrlInstr::Call { site } => writer
+                    .write_struct(name, |w| Ok(w.write_field(fname!("site"), site)?.complete()))?,
+                CtrlInstr::Save { mask } => writer
+                    .write_struct(name, |w| Ok(w.write_field(fname!("mask"), mask)?.complete()))?,
+            }
+            .complete())
+        })
+    }
+}
+
+impl StrictDecode for CtrlInstr<LibId> {
+    fn strict_decode(reader: &mut impl TypedRead) -> Result<Self, DecodeError> {
+        reader.read_union(|name, r| match name.as_str() {
+            "nop" => Ok(CtrlInstr::Nop),
+            "notCo" => Ok(CtrlInstr::NotCo),
+            "chkCo" => Ok(CtrlInstr::ChkCo),
+            "chkCk" => Ok(CtrlInstr::ChkCk),
+            "failCk" => Ok(CtrlInstr::FailCk),
+            "rsetCk" => Ok(CtrlInstr::RsetCk),
+            "jmp" => r.read_struct(|r| Ok(CtrlInstr::Jmp { pos: r.read_field(fname!("pos"))? })),
+            "jiOvfl" => {
+                r.read_struct(|r| Ok(CtrlInstr::JiOvfl { pos: r.read_field(fname!("pos"))? }))
+            }
+            "jiFail" => {
+                r.read_struct(|r| Ok(CtrlInstr::JiFail { pos: r.read_field(fname!("pos"))? }))
+            }
+            "sh" => r.read_struct(|r| Ok(CtrlInstr::Sh { shift: r.read_field(fname!("shift"))? })),
+            "shOvfl" => {
+                r.read_struct(|r| Ok(CtrlInstr::ShOvfl { shift: r.read_field(fname!("shift"))? }))
+            }
+            "shFail" => {
+                r.read_struct(|r| Ok(CtrlInstr::ShFail { shift: r.read_field(fname!("shift"))? }))
+            }
+            "exec" => {
+                r.read_struct(|r| Ok(CtrlInstr::Exec { site: r.read_field(fname!("site"))? }))
+            }
+            "fn" => r.read_struct(|r| Ok(CtrlInstr::Fn { pos: r.read_field(fname!("pos"))? })),
+            "call" => {
+                r.read_struct(|r| Ok(CtrlInstr::Call { site: r.read_field(fname!("site"))? }))
+            }
+            "ret" => Ok(CtrlInstr::Ret),
+            "stop" => Ok(CtrlInstr::Stop),
+            "shW" => {
+                r.read_struct(|r| Ok(CtrlInstr::ShW { shift: r.read_field(fname!("shift"))? }))
+            }
+            "shWOvfl" => {
+                r.read_struct(|r| Ok(CtrlInstr::ShWOvfl { shift: r.read_field(fname!("shift"))? }))
+            }
+            "shWFail" => {
+                r.read_struct(|r| Ok(CtrlInstr::ShWFail { shift: r.read_field(fname!("shift"))? }))
+            }
+            "save" => {
+                r.read_struct(|r| Ok(CtrlInstr::Save { mask: r.read_field(fname!("mask"))? }))
+            }
+            _ => unreachable!(),
+        })
+    }
+}
+
+impl StrictDumb for Instr<LibId> {
+    fn strict_dumb() -> Self { Instr::Ctrl(strict_dumb!()) }
+}
+
+impl StrictType for Instr<LibId> {
+    const STRICT_LIB_NAME: &'static str = LIB_NAME_ALUVM;
+    fn strict_name() -> Option<TypeName> { Some(tn!("Instr")) }
+}
+
+impl StrictSum for Instr<LibId> {
+    const ALL_VARIANTS: &'static [(u8, &'static str)] =
+        &[(CtrlInstr::<LibId>::START, "ctrl"), (0xFF, "reserved")];
+
+    fn variant_name(&self) -> &'static str {
+        match self {
+            Instr::Ctrl(_) => "ctrl",
+            Instr::Reserved(_) => "reserved",
+        }
+    }
+}
+
+impl StrictUnion for Instr<LibId> {}
+impl StrictSerialize for Instr<LibId> {}
+impl StrictDeserialize for Instr<LibId> {}
+
+impl StrictEncode for Instr<LibId> {
+    fn strict_encode<W: TypedWrite>(&self, writer: W) -> io::Result<W> {
+        writer.write_union::<Self>(|definer| {
+            let writer = definer
+                .define_newtype::<CtrlInstr<LibId>>(vname!("ctrl"))
+                .define_newtype::<ReservedInstr>(vname!("reserved"))
+                .complete();
+            Ok(match self {
+                Instr::Ctrl(instr) => writer.write_newtype(vname!("ctrl"), instr)?,
+                Instr::Reserved(instr) => writer.write_newtype(vname!("reserved"), instr)?,
+            }
+            .complete())
+        })
+    }
+}
+
+impl StrictDecode for Instr<LibId> {
+    fn strict_decode(reader: &mut impl TypedRead) -> Result<Self, DecodeError> {
+        reader.read_union(|name, r| match name.as_str() {
+            "ctrl" => r.read_newtype::<Self, CtrlInstr<LibId>>(),
+            "reserved" => r.read_newtype::<Self, ReservedInstr>(),
+            _ => unreachable!(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![cfg_attr(coverage_nightly, coverage(off))]
+
+    use amplify::confinement::Confined;
+
+    use super::*;
+    use crate::fuzzing::Arbitrary;
+
+    const MAX: usize = 0xFF;
+
+    fn roundtrip(instr: impl Into<Instr<LibId>>) -> Vec<u8> {
+        let instr = instr.into();
+        let data = instr.to_strict_serialized::<MAX>().unwrap();
+        assert_eq!(Instr::from_strict_serialized::<MAX>(data.clone()).unwrap(), instr);
+        data.release()
+    }
+
+    #[test]
+    fn tags_match_opcodes() {
+        let lib_id = LibId::from([0xAC; 32]);
+        for instr in [
+            CtrlInstr::Nop,
+            CtrlInstr::NotCo,
+            CtrlInstr::ChkCo,
+            CtrlInstr::ChkCk,
+            CtrlInstr::FailCk,
+            CtrlInstr::RsetCk,
+            CtrlInstr::Jmp { pos: 0x1234 },
+            CtrlInstr::JiOvfl { pos: 0x1234 },
+            CtrlInstr::JiFail { pos: 0x1234 },
+            CtrlInstr::Sh { shift: -5 },
+            CtrlInstr::ShOvfl { shift: -5 },
+            CtrlInstr::ShFail { shift: -5 },
+            CtrlInstr::Exec { site: Site::new(lib_id, 0x1234) },
+            CtrlInstr::Fn { pos: 0x1234 },
+            CtrlInstr::Call { site: Site::new(lib_id, 0x1234) },
+            CtrlInstr::Ret,
+            CtrlInstr::Stop,
+            CtrlInstr::ShW { shift: -5 },
+            CtrlInstr::ShWOvfl { shift: -5 },
+            CtrlInstr::ShWFail { shift: -5 },
+            CtrlInstr::Save { mask: 0x1234 },
+        ] {
+            let data = roundtrip(instr);
+            assert_eq!(data[..2], [CtrlInstr::<LibId>::START, instr.opcode_byte()], "{instr}");
+            let operands = match instr {
+                CtrlInstr::Exec { .. } | CtrlInstr::Call { .. } => {
+                    [&[0xAC; 32][..], &[0x34, 0x12]].concat()
+                }
+                CtrlInstr::Sh { .. } | CtrlInstr::ShOvfl { .. } | CtrlInstr::ShFail { .. } => {
+                    vec![0xFB]
+                }
+                CtrlInstr::ShW { .. } | CtrlInstr::ShWOvfl { .. } | CtrlInstr::ShWFail { .. } => {
+                    vec![0xFB, 0xFF]
+                }
+                _ if data.len() > 2 => vec![0x34, 0x12],
+                _ => vec![],
+            };
+            assert_eq!(data[2..], operands, "{instr}");
+        }
+        assert_eq!(roundtrip(ReservedInstr(0x80)), [0xFF, 0x80]);
+    }
+
+    #[test]
+    fn arbitrary_roundtrip() {
+        let mut bytes = (0..0x4000u32).map(|i| (i.wrapping_mul(0x9E37_79B9) >> 13) as u8);
+        while let Some(instr) = Instr::<LibId>::arbitrary(&mut bytes) {
+            roundtrip(instr);
+        }
+    }
+
+    #[test]
+    fn invalid_tag() {
+        let data = Confined::try_from(vec![CtrlInstr::<LibId>::START, CtrlInstr::<LibId>::END + 1])
+            .unwrap();
+        assert!(Instr::<LibId>::from_strict_serialized::<MAX>(data).is_err());
+        let data = Confined::try_from(vec![0x01, 0x00]).unwrap();
+        assert!(Instr::<LibId>::from_strict_serialized::<MAX>(data).is_err());
+    }
+}
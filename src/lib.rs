@@ -150,30 +150,52 @@ extern crate commit_verify;
 extern crate serde;
 
 mod core;
+pub mod fmt;
 #[macro_use]
 pub mod isa;
 mod library;
 mod vm;
 #[cfg(feature = "stl")]
 pub mod stl;
+#[cfg(any(test, feature = "fuzzing"))]
+pub mod fuzzing;
+#[cfg(any(test, feature = "tests"))]
+pub mod testing;
 
 /// Module providing register information
 pub mod regs {
-    pub use crate::core::{Status, CALL_STACK_SIZE_MAX};
+    pub use crate::core::{Status, CALL_STACK_SIZE_MAX, CALL_STACK_SIZE_SMALL};
 }
 
-pub use isa::{ExecStep, IsaId, ISA_ID_MAX_LEN};
+pub use isa::{ExecStep, IsaId, IsaSeg, IsaSegError, ISA_ID_MAX_LEN};
 #[cfg(feature = "armor")]
 pub use library::armor::LibArmorError;
 pub use library::{
-    AssemblerError, CompiledLib, CompilerError, Lib, LibId, LibSite, LibsSeg, MarshallError,
-    Marshaller,
+    AssemblerError, BasicBlock, BoundaryIndex, BytecodeMigration, CodeEdit, CompatError,
+    CompiledLib, CompilerError, ControlFlowGraph, DataExtendError, DataRange, Edge, EdgeKind,
+    EntryPoint, EntryPointError, ExecHook, HookAction, InstrIter, InvalidJump,
+    IsaConsistencyReport, Lib, LibAssembler, LibBudget, LibBuilder, LibBuilderError, LibGraph,
+    LibGraphError, LibId, LibLimit, LibMetrics, LibModifyError, LibOp, LibRepo, LibSite,
+    LibSymbols, LibValidationError, LibsSeg, MarshallError, Marshaller, MergeError, MergeReport,
+    MigrationError, MigrationReport, NoHook, PatchError, PrecompiledLib, Program, ProgramError,
+    RelocationError, ResumeAfterError, RewriteError, RoutineBuilder, SourceError, StackDepth,
+    SymbolError, SymbolName, UnsupportedIsaError, CORE_STATE_TAG, SYMBOL_NAME_MAX_LEN,
 };
+#[cfg(feature = "std")]
+pub use library::{LibContainerError, LIB_CONTAINER_MAGIC, LIB_CONTAINER_VERSION};
 #[doc(hidden)]
 pub use paste::paste;
-pub use vm::Vm;
+pub use vm::{
+    CallError, ExecError, ExecReport, ExecStats, ExecSuspension, HaltReason, SmallStackVm,
+    StepError, StepOutcome, Stepper, SuspendedVm, Vm, VmRun,
+};
 
-pub use self::core::{Core, CoreConfig, CoreExt, NoExt, NoRegs, Register, Site, SiteId, Supercore};
+pub use self::core::{
+    CallStackFault, Core, CoreConfig, CoreDump, CoreExt, CoreSnapshot, FailureReason,
+    InvariantViolation, JumpFault, NoExt, NoRegs, Profile, ProfileData, RegDump, Register, Site,
+    SiteId, SiteParseError, SiteStats, SnapshotError, Supercore, TerminationCause,
+    UnknownInstrPolicy,
+};
 
 /// Name of the strict types library for AluVM.
 pub const LIB_NAME_ALUVM: &str = "AluVM";
@@ -57,7 +57,7 @@ impl AsciiArmor for Lib {
             ArmorHeader::new(ASCII_ARMOR_ID, self.lib_id().to_string()),
             ArmorHeader::new(ASCII_ARMOR_ISAE, self.isae_string()),
         ];
-        for dep in &self.libs {
+        for dep in self.libs() {
             headers.push(ArmorHeader::new(ASCII_ARMOR_DEPENDENCY, dep.to_string()));
         }
         headers
@@ -22,57 +22,777 @@
 // or implied. See the License for the specific language governing permissions and limitations under
 // the License.
 
-use amplify::confinement::{self, TinyOrdSet};
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt::Write;
+use core::iter::FusedIterator;
+use core::marker::PhantomData;
+use core::str::FromStr;
 
-use super::{Lib, LibId, MarshallError, Marshaller};
-use crate::isa::{BytecodeRead, CodeEofError, Instruction};
+use amplify::confinement::{self, SmallBlob, TinyOrdSet};
+use amplify::num::{u1, u2, u3, u4, u5, u6, u7};
+
+use super::flow::local_targets;
+use super::{
+    BoundaryIndex, DataRange, Lib, LibId, LibSymbols, LibValidationError, LibsSeg, MarshallError,
+    Marshaller, PatchError, SymbolError, SymbolName,
+};
+use crate::isa::{
+    AsmParseError, Bytecode, BytecodeRead, BytecodeWrite, CodeEofError, GotoTarget, Instr,
+    Instruction,
+};
 
 /// Errors while assembling lib-old from the instruction set.
-#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug, Display, Error, From)]
-#[display(inner)]
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Display, Error, From)]
+#[display(doc_comments)]
+#[non_exhaustive]
 pub enum AssemblerError {
-    /// Error assembling code and data segments.
+    /// {0}
     #[from]
     Bytecode(MarshallError),
 
-    /// Error assembling a library segment.
+    /// {0}
     #[from]
     LibSegOverflow(confinement::Error),
+
+    /// instruction #{index} `{mnemonic}` at offset {offset:#06X} can't be encoded: {source}
+    Instr {
+        /// Zero-based index of the instruction.
+        index: usize,
+        /// Code offset at which the instruction was placed.
+        offset: u16,
+        /// Text representation of the instruction.
+        mnemonic: String,
+        /// Error encoding the instruction.
+        source: MarshallError,
+    },
+
+    /// instruction #{index} `{mnemonic}` at offset {offset:#06X} references library {lib_id},
+    /// which exceeds the maximal number of libraries in the libs segment.
+    LibsOverflow {
+        /// Zero-based index of the instruction.
+        index: usize,
+        /// Code offset at which the instruction was placed.
+        offset: u16,
+        /// Text representation of the instruction.
+        mnemonic: String,
+        /// Identifier of the library which doesn't fit the libs segment.
+        lib_id: LibId,
+    },
+
+    /// {0}
+    #[from]
+    Validation(LibValidationError),
+}
+
+/// Errors assembling a library from the source text with [`Lib::assemble_from_source`].
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum SourceError {
+    /// line {0}: {1}
+    Parse(usize, AsmParseError),
+
+    /// line {line}: invalid label name `{label}`.
+    InvalidLabel {
+        /// Line number, starting from 1.
+        line: usize,
+        /// Label name.
+        label: String,
+    },
+
+    /// line {line}: label `{label}` is already defined at line {defined}.
+    DuplicateLabel {
+        /// Line number, starting from 1.
+        line: usize,
+        /// Label name.
+        label: String,
+        /// Line at which the label was defined first.
+        defined: usize,
+    },
+
+    /// line {line}: label `{label}` is not defined.
+    UndefinedLabel {
+        /// Line number, starting from 1.
+        line: usize,
+        /// Label name.
+        label: String,
+    },
+
+    /// line {line}: offset of the label `{label}` exceeds the maximal code segment size.
+    OffsetOverflow {
+        /// Line number, starting from 1.
+        line: usize,
+        /// Label name.
+        label: String,
+    },
+
+    /// line {line}: library alias `{alias}` is not declared.
+    UnknownAlias {
+        /// Line number, starting from 1.
+        line: usize,
+        /// Library alias.
+        alias: String,
+    },
+
+    /// {0}
+    #[from]
+    Assemble(AssemblerError),
+}
+
+/// Streaming library assembler, encoding instructions into bytecode as they are appended.
+///
+/// Unlike [`Lib::assemble`], the assembler doesn't require the whole program to be present in
+/// memory as a list of instructions: each appended instruction is encoded right away, and only the
+/// code and data segment buffers are kept. Since the libs segment is a sorted set, which isn't
+/// known until the last instruction is appended, references to external libraries are encoded as
+/// placeholders, and only their bit offsets are retained. They are patched with the final
+/// positions in the libs segment by [`LibAssembler::finish`].
+///
+/// Thus, the peak memory usage is approximately the size of the final library plus four bytes per
+/// external reference and one bit per code byte, used to track instruction boundaries (not
+/// counting spare capacity of the growing buffers, which is released by
+/// [`LibAssembler::shrink_to_fit`] and [`LibAssembler::finish`]). The produced library is
+/// byte-identical to the one produced by [`Lib::assemble`] from the same sequence of instructions.
+///
+/// Equal data used by different instructions are deduplicated, sharing the same place in the data
+/// segment. Jumps to the code which is not yet appended can be encoded with a placeholder target,
+/// and patched once the target offset becomes known with [`LibAssembler::patch_jump`].
+///
+/// Named constants can be placed into the data segment with [`LibAssembler::define_symbol`], such
+/// that the instructions appended afterwards can use their offsets in the operands. The symbols are
+/// returned in a [`LibSymbols`] table by [`LibAssembler::finish_with_symbols`].
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct LibAssembler<Isa: Instruction<LibId>> {
+    code: Vec<u8>,
+    data: Vec<u8>,
+    /// External libraries in the order of their first reference.
+    libs: Vec<LibId>,
+    /// Bit offsets of external library references in the code segment.
+    patches: Vec<u32>,
+    /// Offsets of the appended instructions.
+    boundaries: BoundaryIndex,
+    /// Constants in the data segment named by symbols.
+    symbols: BTreeMap<SymbolName, DataRange>,
+    _phantom: PhantomData<Isa>,
+}
+
+impl<Isa: Instruction<LibId>> Default for LibAssembler<Isa> {
+    fn default() -> Self { Self::new() }
+}
+
+impl<Isa: Instruction<LibId>> LibAssembler<Isa> {
+    /// Constructs an empty assembler.
+    pub const fn new() -> Self {
+        Self {
+            code: Vec::new(),
+            data: Vec::new(),
+            libs: Vec::new(),
+            patches: Vec::new(),
+            boundaries: BoundaryIndex::new(),
+            symbols: BTreeMap::new(),
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Returns the length of the code segment produced so far.
+    #[inline]
+    pub fn code_len(&self) -> usize { self.code.len() }
+
+    /// Returns the length of the data segment produced so far.
+    #[inline]
+    pub fn data_len(&self) -> usize { self.data.len() }
+
+    /// Returns the code offset at which the next appended instruction will be placed.
+    #[inline]
+    pub fn offset(&self) -> u16 { self.code.len() as u16 }
+
+    /// Encodes an instruction, appending it to the code and data segments.
+    ///
+    /// # Errors
+    ///
+    /// If the instruction doesn't fit the code or data segment, or if it references a library
+    /// which doesn't fit the libs segment. In this case, the assembler state is left as it was
+    /// before the call.
+    pub fn append(&mut self, instr: &Isa) -> Result<(), AssemblerError> {
+        // References are written by `RefPatcher`, thus the marshaller doesn't need the libs
+        let no_libs = LibsSeg::new();
+        let lens = (self.code.len(), self.data.len(), self.libs.len(), self.patches.len());
+        let code = core::mem::take(&mut self.code);
+        let data = core::mem::take(&mut self.data);
+        let mut writer = RefPatcher {
+            marshaller: Marshaller::resume(code, data, &no_libs),
+            libs: &mut self.libs,
+            patches: &mut self.patches,
+        };
+        let res = instr.encode_instr(&mut writer);
+        (self.code, self.data) = writer.marshaller.into_buffers();
+        match res {
+            Ok(()) => {
+                self.boundaries.insert(lens.0 as u16);
+                Ok(())
+            }
+            Err(err) => {
+                self.code.truncate(lens.0);
+                self.data.truncate(lens.1);
+                self.libs.truncate(lens.2);
+                self.patches.truncate(lens.3);
+                let index = self.boundaries.count();
+                Err(err.with_context(index, lens.0 as u16, instr))
+            }
+        }
+    }
+
+    /// Rewrites the target of a previously appended local jump instruction.
+    ///
+    /// For the jumps with a relative shift, the shift is recomputed from the instruction offset.
+    ///
+    /// # Errors
+    ///
+    /// If there is no instruction appended at the offset, the instruction is not a local jump (jump
+    /// tables, having multiple targets, are not patched either), the target is not reachable with
+    /// a relative shift, or the instruction references an external library. In this case, the
+    /// assembler state is not changed.
+    pub fn patch_jump(&mut self, offset: u16, target: u16) -> Result<(), PatchError> {
+        if !self.boundaries.is_boundary(offset) {
+            return Err(PatchError::NoInstruction(offset));
+        }
+        // References hold positions in the list of the referenced libraries rather than in the
+        // libs segment; the instructions having them are not patched, thus it is sufficient for
+        // them to be decodable
+        let libs = LibsSeg::from_iter_checked(self.libs.iter().copied());
+        let mut reader = Marshaller::with_unchecked(&self.code, &self.data, &libs);
+        let mut instr = reader
+            .seek(offset)
+            .and_then(|_| Isa::decode_instr(&mut reader))
+            .map_err(|_| PatchError::NoInstruction(offset))?;
+        let len = reader.offset().0 - offset;
+        match instr.local_goto_pos() {
+            GotoTarget::None | GotoTarget::Table(_) => return Err(PatchError::NoJump(offset)),
+            GotoTarget::Absolute(pos) => *pos = target,
+            GotoTarget::Relative(shift) => {
+                *shift = i8::try_from(target as i32 - offset as i32)
+                    .map_err(|_| PatchError::ShiftOverflow { offset, target })?;
+            }
+            GotoTarget::RelativeWide(shift) => {
+                *shift = i16::try_from(target as i32 - offset as i32)
+                    .map_err(|_| PatchError::ShiftOverflow { offset, target })?;
+            }
+        }
+
+        let no_libs = LibsSeg::new();
+        let mut writer = Marshaller::resume(Vec::new(), core::mem::take(&mut self.data), &no_libs);
+        let res = instr.encode_instr(&mut writer);
+        let (patch, data) = writer.into_buffers();
+        self.data = data;
+        res?;
+        debug_assert_eq!(patch.len(), len as usize, "jump patch changes the instruction length");
+        self.code[offset as usize..offset as usize + patch.len()].copy_from_slice(&patch);
+        Ok(())
+    }
+
+    /// Places a constant into the data segment, naming it with a symbol.
+    ///
+    /// The constant shares the place with equal data already present in the data segment. The
+    /// returned range can be used in the operands of the instructions appended afterwards.
+    ///
+    /// # Errors
+    ///
+    /// If the symbol is already defined, the constant doesn't fit the data segment, or the number
+    /// of symbols exceeds 255. In this case, the assembler state is not changed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use aluvm::isa::{CtrlInstr, Instr};
+    /// use aluvm::{LibAssembler, LibId, SymbolName};
+    ///
+    /// let mut asm = LibAssembler::<Instr<LibId>>::new();
+    /// let key = asm.define_symbol(SymbolName::from("key"), &[0xA5; 32])?;
+    /// // Equal constants are placed into the data segment only once
+    /// assert_eq!(asm.define_symbol(SymbolName::from("alias"), &[0xA5; 32])?, key);
+    /// asm.append(&CtrlInstr::Save { mask: key.offset }.into())?;
+    /// let lib = asm.finish()?;
+    /// assert_eq!(lib.data().as_slice(), &[0xA5; 32]);
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn define_symbol(
+        &mut self,
+        name: SymbolName,
+        bytes: &[u8],
+    ) -> Result<DataRange, SymbolError> {
+        if self.symbols.contains_key(&name) {
+            return Err(SymbolError::Duplicate(name));
+        }
+        if self.symbols.len() >= u8::MAX as usize {
+            return Err(SymbolError::TooMany);
+        }
+        let no_libs = LibsSeg::new();
+        let mut writer = Marshaller::resume(Vec::new(), core::mem::take(&mut self.data), &no_libs);
+        let res = writer.write_data_dedup(bytes);
+        (_, self.data) = writer.into_buffers();
+        let offset = res.map_err(|_| SymbolError::DataOverflow(name.clone()))?;
+        let range = DataRange::new(offset, bytes.len() as u16);
+        self.symbols.insert(name, range);
+        Ok(range)
+    }
+
+    /// Returns the data segment range of a previously defined symbol.
+    #[inline]
+    pub fn symbol(&self, name: &SymbolName) -> Option<DataRange> { self.symbols.get(name).copied() }
+
+    /// Releases spare capacity of the code and data segment buffers and of the reference patch
+    /// list.
+    pub fn shrink_to_fit(&mut self) {
+        self.code.shrink_to_fit();
+        self.data.shrink_to_fit();
+        self.libs.shrink_to_fit();
+        self.patches.shrink_to_fit();
+        self.boundaries.shrink_to_fit();
+    }
+
+    /// Completes assembly, patching external library references and producing the library.
+    ///
+    /// The produced code and data segments do not retain any spare capacity.
+    pub fn finish(mut self) -> Result<Lib, AssemblerError> {
+        let libs = TinyOrdSet::try_from_iter(self.libs.iter().copied())?;
+        // Maps the order of the first reference to the position in the sorted libs segment
+        let positions = self
+            .libs
+            .iter()
+            .map(|id| {
+                libs.iter()
+                    .position(|lib| lib == id)
+                    .expect("libs segment contains all references") as u8
+            })
+            .collect::<Vec<_>>();
+        for offset in self.patches.drain(..) {
+            patch_byte(&mut self.code, offset, |idx| positions[idx as usize]);
+        }
+        self.shrink_to_fit();
+
+        Ok(Lib::with(
+            Isa::isa_ext(),
+            SmallBlob::from_checked(self.code),
+            SmallBlob::from_checked(self.data),
+            libs,
+        ))
+    }
+
+    /// Completes assembly like [`Self::finish`], also producing the symbol table of the library.
+    pub fn finish_with_symbols(mut self) -> Result<(Lib, LibSymbols), AssemblerError> {
+        let symbols = core::mem::take(&mut self.symbols);
+        let lib = self.finish()?;
+        let symbols =
+            LibSymbols::with(&lib, symbols).expect("symbols are checked when they are defined");
+        Ok((lib, symbols))
+    }
+}
+
+/// Replaces a byte starting at a given bit offset in the bytecode.
+fn patch_byte(code: &mut [u8], bit_offset: u32, f: impl FnOnce(u8) -> u8) {
+    let pos = (bit_offset / 8) as usize;
+    let shift = bit_offset % 8;
+    if shift == 0 {
+        code[pos] = f(code[pos]);
+        return;
+    }
+    let word = u16::from_le_bytes([code[pos], code[pos + 1]]);
+    let mask = 0xFFu16 << shift;
+    let byte = f((word >> shift) as u8);
+    let word = (word & !mask) | ((byte as u16) << shift);
+    [code[pos], code[pos + 1]] = word.to_le_bytes();
+}
+
+/// Bytecode writer used by [`LibAssembler`], which writes the order of the first reference to a
+/// library instead of its position in the libs segment, recording the reference offset.
+struct RefPatcher<'a, 'libs> {
+    marshaller: Marshaller<'libs, Vec<u8>, Vec<u8>>,
+    libs: &'a mut Vec<LibId>,
+    patches: &'a mut Vec<u32>,
+}
+
+impl BytecodeWrite<LibId> for RefPatcher<'_, '_> {
+    type Error = AssemblerError;
+
+    fn pos(&self) -> u16 { BytecodeWrite::pos(&self.marshaller) }
+
+    fn write_1bit(&mut self, data: u1) -> Result<(), Self::Error> {
+        self.marshaller
+            .write_1bit(data)
+            .map_err(AssemblerError::from)
+    }
+
+    fn write_2bits(&mut self, data: u2) -> Result<(), Self::Error> {
+        self.marshaller
+            .write_2bits(data)
+            .map_err(AssemblerError::from)
+    }
+
+    fn write_3bits(&mut self, data: u3) -> Result<(), Self::Error> {
+        self.marshaller
+            .write_3bits(data)
+            .map_err(AssemblerError::from)
+    }
+
+    fn write_4bits(&mut self, data: u4) -> Result<(), Self::Error> {
+        self.marshaller
+            .write_4bits(data)
+            .map_err(AssemblerError::from)
+    }
+
+    fn write_5bits(&mut self, data: u5) -> Result<(), Self::Error> {
+        self.marshaller
+            .write_5bits(data)
+            .map_err(AssemblerError::from)
+    }
+
+    fn write_6bits(&mut self, data: u6) -> Result<(), Self::Error> {
+        self.marshaller
+            .write_6bits(data)
+            .map_err(AssemblerError::from)
+    }
+
+    fn write_7bits(&mut self, data: u7) -> Result<(), Self::Error> {
+        self.marshaller
+            .write_7bits(data)
+            .map_err(AssemblerError::from)
+    }
+
+    fn write_byte(&mut self, data: u8) -> Result<(), Self::Error> {
+        self.marshaller
+            .write_byte(data)
+            .map_err(AssemblerError::from)
+    }
+
+    fn write_word(&mut self, data: u16) -> Result<(), Self::Error> {
+        self.marshaller
+            .write_word(data)
+            .map_err(AssemblerError::from)
+    }
+
+    fn write_fixed<const LEN: usize>(&mut self, data: [u8; LEN]) -> Result<(), Self::Error> {
+        self.marshaller
+            .write_fixed(data)
+            .map_err(AssemblerError::from)
+    }
+
+    fn write_bytes(&mut self, data: &[u8]) -> Result<(u16, u16), Self::Error> {
+        self.marshaller
+            .write_bytes(data)
+            .map_err(AssemblerError::from)
+    }
+
+    fn write_ref(&mut self, id: LibId) -> Result<(), Self::Error> {
+        let idx = match self.libs.iter().position(|lib| *lib == id) {
+            Some(idx) => idx,
+            None if self.libs.len() >= u8::MAX as usize => {
+                return Err(confinement::Error::Oversize {
+                    len: self.libs.len() + 1,
+                    max_len: u8::MAX as usize,
+                }
+                .into())
+            }
+            None => {
+                self.libs.push(id);
+                self.libs.len() - 1
+            }
+        };
+        let (byte_pos, bit_pos) = self.marshaller.offset();
+        self.patches
+            .push(byte_pos as u32 * 8 + bit_pos.to_u8() as u32);
+        self.write_byte(idx as u8)
+    }
+
+    fn check_aligned(&self, instr_offset: u16) -> Result<(), Self::Error> {
+        BytecodeWrite::check_aligned(&self.marshaller, instr_offset).map_err(AssemblerError::from)
+    }
+}
+
+impl AssemblerError {
+    /// Attaches the context of the instruction which has failed to encode.
+    fn with_context<Isa: Instruction<LibId>>(self, index: usize, offset: u16, instr: &Isa) -> Self {
+        match self {
+            AssemblerError::Bytecode(source) => {
+                AssemblerError::Instr { index, offset, mnemonic: instr.to_string(), source }
+            }
+            AssemblerError::LibSegOverflow(_) => match instr.external_ref() {
+                Some(lib_id) => AssemblerError::LibsOverflow {
+                    index,
+                    offset,
+                    mnemonic: instr.to_string(),
+                    lib_id,
+                },
+                None => self,
+            },
+            err => err,
+        }
+    }
 }
 
 impl Lib {
     /// Assembles a library from the provided instructions by encoding them into bytecode.
+    ///
+    /// # Errors
+    ///
+    /// If an instruction can't be encoded, or if the instructions reference more libraries than
+    /// fit the libs segment. The error specifies the index and code offset of the instruction.
     pub fn assemble<Isa>(code: &[Isa]) -> Result<Lib, AssemblerError>
     where Isa: Instruction<LibId> {
-        let call_sites = code.iter().filter_map(|instr| instr.external_ref());
-        let libs_segment = TinyOrdSet::try_from_iter(call_sites)?;
+        let mut libs_segment = LibsSeg::new();
+        let mut offset = 0u16;
+        for (index, instr) in code.iter().enumerate() {
+            if let Some(lib_id) = instr.external_ref() {
+                if libs_segment.push(lib_id).is_err() {
+                    return Err(AssemblerError::LibsOverflow {
+                        index,
+                        offset,
+                        mnemonic: instr.to_string(),
+                        lib_id,
+                    });
+                }
+            }
+            offset = offset.saturating_add(instr.code_byte_len());
+        }
 
         let mut writer = Marshaller::new(&libs_segment);
-        for instr in code.iter() {
-            instr.encode_instr(&mut writer)?;
+        for (index, instr) in code.iter().enumerate() {
+            let offset = BytecodeWrite::pos(&writer);
+            instr
+                .encode_instr(&mut writer)
+                .map_err(|err| AssemblerError::from(err).with_context(index, offset, instr))?;
         }
         let (code_segment, data_segment) = writer.finish();
 
-        Ok(Lib {
-            isae: Isa::isa_ext(),
-            libs: libs_segment,
-            code: code_segment,
-            data: data_segment,
-        })
+        Ok(Lib::with(Isa::isa_ext(), code_segment, data_segment, libs_segment))
+    }
+
+    /// Assembles a library from the provided instructions and validates its code with
+    /// [`Lib::validate_code`].
+    ///
+    /// # Errors
+    ///
+    /// If the library can't be assembled (see [`Lib::assemble`]), or if some of its local jumps
+    /// target an offset outside the code segment, in the middle of an instruction, or an
+    /// instruction which can't be used as a goto target.
+    pub fn assemble_validated<Isa>(code: &[Isa]) -> Result<Lib, AssemblerError>
+    where Isa: Instruction<LibId> {
+        let lib = Self::assemble(code)?;
+        lib.validate_code::<Isa>()?;
+        Ok(lib)
+    }
+
+    /// Assembles a library from the source text, resolving label references to code offsets.
+    ///
+    /// The source contains one instruction per line, in the form accepted by
+    /// [`crate::isa::parse_asm`]. Any line may start with a label definition (`loop:`), which
+    /// designates the offset of the instruction following it. Labels may be used instead of the
+    /// code offsets in `jmp`, `jif` and `call` instructions (`jmp loop`), including the references
+    /// to labels defined further in the code. External sites may be written as `@alias:offset`
+    /// (`call @lib:4`), where the aliases are mapped to the library ids by `aliases`. Empty lines
+    /// and comments, starting with `;`, are skipped.
+    ///
+    /// # Errors
+    ///
+    /// If a line can't be parsed, a label is undefined or defined twice, an alias is not
+    /// declared, or a label offset doesn't fit the code segment; in all these cases the error
+    /// specifies the line number. Also, if the library can't be assembled.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate alloc;
+    /// use aluvm::isa::Instr;
+    /// use aluvm::regs::Status;
+    /// use aluvm::{Lib, LibId, LibSite, Vm};
+    ///
+    /// let lib = Lib::assemble_from_source(
+    ///     "
+    ///         jmp     start
+    ///     fail:
+    ///         fail    CK
+    ///     start:
+    ///         jif     CO, fail    ; not taken, since `CO` is not failed
+    ///         stop
+    ///     ",
+    ///     &[],
+    /// )
+    /// .unwrap();
+    /// let mut vm = Vm::<Instr<LibId>>::new();
+    /// assert_eq!(vm.exec(LibSite::new(lib.lib_id(), 0), &(), |_| Some(&lib)), Status::Ok);
+    /// ```
+    pub fn assemble_from_source(
+        source: &str,
+        aliases: &[(&str, LibId)],
+    ) -> Result<Lib, SourceError> {
+        let mut code = Vec::<Instr<LibId>>::new();
+        let mut labels = BTreeMap::<&str, (usize, usize)>::new();
+        let mut refs = Vec::new();
+        let mut offset = 0usize;
+        for (no, line) in source.lines().enumerate() {
+            let line_no = no + 1;
+            let mut line = line.split_once(';').map_or(line, |(line, _)| line).trim();
+            if let Some((label, rest)) = line.split_once(':') {
+                if !label.contains(char::is_whitespace) {
+                    if !is_label(label) {
+                        return Err(SourceError::InvalidLabel {
+                            line: line_no,
+                            label: label.to_string(),
+                        });
+                    }
+                    if let Some((_, defined)) = labels.insert(label, (offset, line_no)) {
+                        return Err(SourceError::DuplicateLabel {
+                            line: line_no,
+                            label: label.to_string(),
+                            defined,
+                        });
+                    }
+                    line = rest.trim();
+                }
+            }
+            if line.is_empty() {
+                continue;
+            }
+            let (instr, label) = parse_source_instr(line, line_no, aliases)?;
+            if let Some(label) = label {
+                refs.push((code.len(), label, line_no));
+            }
+            offset += instr.code_byte_len() as usize;
+            code.push(instr);
+        }
+        for (idx, label, line) in refs {
+            let (pos, _) = labels
+                .get(label)
+                .ok_or_else(|| SourceError::UndefinedLabel { line, label: label.to_string() })?;
+            let pos = u16::try_from(*pos)
+                .map_err(|_| SourceError::OffsetOverflow { line, label: label.to_string() })?;
+            match code[idx].local_goto_pos() {
+                GotoTarget::Absolute(target) => *target = pos,
+                _ => unreachable!("label references are parsed only for local jumps"),
+            }
+        }
+        Ok(Lib::assemble(&code)?)
     }
 
     /// Disassembles the library into a set of instructions.
     pub fn disassemble<Isa>(&self) -> Result<Vec<Isa>, CodeEofError>
     where Isa: Instruction<LibId> {
-        let mut code = Vec::new();
-        let mut reader = Marshaller::with(&self.code, &self.data, &self.libs);
-        while !reader.is_eof() {
-            code.push(Isa::decode_instr(&mut reader)?);
+        self.disassemble_with_offsets()
+            .map(|code| code.into_iter().map(|(_, instr)| instr).collect())
+    }
+
+    /// Disassembles the library into a set of instructions, each with its offset in the code
+    /// segment.
+    ///
+    /// The offsets are increasing, thus the instruction covering an arbitrary code offset can be
+    /// found with a binary search, like [`slice::partition_point`].
+    pub fn disassemble_with_offsets<Isa>(&self) -> Result<Vec<(u16, Isa)>, CodeEofError>
+    where Isa: Instruction<LibId> {
+        self.instr_iter().collect()
+    }
+
+    /// Returns an iterator lazily decoding the library instructions, each with its offset in the
+    /// code segment.
+    ///
+    /// Unlike [`Lib::disassemble`], the iterator doesn't allocate, and thus should be preferred
+    /// when only a part of the code has to be scanned.
+    pub fn instr_iter<Isa>(&self) -> InstrIter<'_, Isa>
+    where Isa: Instruction<LibId> {
+        InstrIter {
+            lib: self,
+            reader: Marshaller::with_unchecked(self.code(), self.data(), self.libs()),
+            code_len: self.code().len() as u16,
+            failed: false,
+            _isa: PhantomData,
+        }
+    }
+
+    /// Returns an iterator decoding the library instructions, each with its offset in the code
+    /// segment, which doesn't stop at the decoding errors.
+    ///
+    /// Unlike [`Lib::instr_iter`], a byte sequence which can't be decoded is reported with its
+    /// offset, and the decoding resumes from the next byte, such that all the code segment is
+    /// covered. This allows mapping the site of a failure back to the instruction even for a
+    /// library with invalid code. This is a shorthand for [`InstrIter::resume_after_error`].
+    pub fn disassemble_iter<Isa>(&self) -> ResumeAfterError<'_, Isa>
+    where Isa: Instruction<LibId> {
+        self.instr_iter().resume_after_error()
+    }
+
+    /// Decodes a single instruction starting at the given offset of the code segment.
+    ///
+    /// The offset is not checked to be an instruction boundary: decoding from the middle of an
+    /// instruction may produce a valid, but a different instruction. Use [`Lib::boundary_index`]
+    /// to check the offset first, if it is not known to be a boundary.
+    pub fn instr_at<Isa>(&self, offset: u16) -> Result<Isa, CodeEofError>
+    where Isa: Instruction<LibId> {
+        let mut reader = Marshaller::with_unchecked(self.code(), self.data(), self.libs());
+        reader.seek(offset)?;
+        Isa::decode_instr(&mut reader)
+    }
+
+    /// Disassembles the library into a text listing, with each instruction prefixed by its offset
+    /// in the code segment.
+    ///
+    /// Instructions which can't be decoded are marked in the listing as `<incomplete
+    /// instruction>` comments. The listing can be parsed back with [`crate::isa::parse_asm`].
+    ///
+    /// # Errors
+    ///
+    /// If the instruction set fails to decode an instruction without consuming any of its bytes,
+    /// such that the disassembly can't proceed.
+    pub fn disassemble_to_string<Isa>(&self) -> Result<String, CodeEofError>
+    where Isa: Instruction<LibId> {
+        self.write_disassemble::<Isa>(false)
+    }
+
+    /// Disassembles the library into a text listing, like [`Lib::disassemble_to_string`], adding
+    /// the hex encoding of each instruction as a trailing comment.
+    ///
+    /// # Errors
+    ///
+    /// If the instruction set fails to decode an instruction without consuming any of its bytes,
+    /// such that the disassembly can't proceed.
+    pub fn disassemble_to_string_with_bytes<Isa>(&self) -> Result<String, CodeEofError>
+    where Isa: Instruction<LibId> {
+        self.write_disassemble::<Isa>(true)
+    }
+
+    fn write_disassemble<Isa>(&self, bytes: bool) -> Result<String, CodeEofError>
+    where Isa: Instruction<LibId> {
+        let mut listing = String::new();
+        let code = self.code().as_ref();
+        let mut iter = self.instr_iter::<Isa>();
+        loop {
+            let pos = iter.pos();
+            let text = match iter.next() {
+                None => break,
+                Some(Ok((_, instr))) => instr.to_string(),
+                Some(Err(_)) if iter.pos() == pos => return Err(CodeEofError),
+                Some(Err(_)) => s!("; <incomplete instruction>"),
+            };
+            if !bytes {
+                writeln!(listing, "offset {pos:06}: {text}")
+            } else {
+                let end = iter.pos() as usize;
+                let hex = code[pos as usize..end]
+                    .iter()
+                    .map(|byte| format!("{byte:02x}"))
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                // The bytes of an incomplete instruction are already in a comment
+                let sep = if text.starts_with(';') { ' ' } else { ';' };
+                writeln!(listing, "offset {pos:06}: {text:<32}{sep} {hex}")
+            }
+            .expect("writing to a string never fails");
         }
-        Ok(code)
+        Ok(listing)
     }
 
     /// Disassembles the library into a set of instructions and offsets and prints it to the writer.
+    ///
+    /// The printed listing is the same as produced by [`Lib::disassemble_to_string`].
     pub fn print_disassemble<Isa>(
         &self,
         mut writer: impl std::io::Write,
@@ -80,15 +800,715 @@ impl Lib {
     where
         Isa: Instruction<LibId>,
     {
-        let mut reader = Marshaller::with(&self.code, &self.data, &self.libs);
-        while !reader.is_eof() {
-            let pos = reader.offset().0 as usize;
-            write!(writer, "offset {pos:06}: ")?;
-            match Isa::decode_instr(&mut reader) {
-                Ok(instr) => writeln!(writer, "{instr}")?,
-                Err(_) => writeln!(writer, "; <incomplete instruction>")?,
+        let listing = self
+            .disassemble_to_string::<Isa>()
+            .map_err(std::io::Error::other)?;
+        writer.write_all(listing.as_bytes())
+    }
+
+    /// Prints the library listing to the writer, like [`Lib::print_disassemble`], making the
+    /// control flow easier to follow.
+    ///
+    /// Each offset targeted by a local jump is marked with a `loc_XXXX:` label line, `XXXX` being
+    /// the hexadecimal offset, and the jumps are annotated with the labels of their targets.
+    /// External library ids are replaced by their names from `symbols`, if present there.
+    pub fn pretty_print<Isa>(
+        &self,
+        mut writer: impl std::io::Write,
+        symbols: &BTreeMap<LibId, String>,
+    ) -> Result<(), std::io::Error>
+    where
+        Isa: Instruction<LibId>,
+    {
+        // Only the instruction boundaries and the end of the code can be labeled
+        let mut boundaries = BTreeSet::from([self.code().len() as u16]);
+        let mut targets = BTreeSet::new();
+        for (pos, mut instr) in self.instr_iter::<Isa>().map_while(Result::ok) {
+            boundaries.insert(pos);
+            targets.extend(local_targets(&mut instr, pos).into_iter().flatten());
+        }
+        let labels = targets
+            .intersection(&boundaries)
+            .copied()
+            .collect::<BTreeSet<_>>();
+
+        let mut iter = self.instr_iter::<Isa>();
+        loop {
+            let pos = iter.pos();
+            if labels.contains(&pos) {
+                writeln!(writer, "loc_{pos:04X}:")?;
+            }
+            let mut instr = match iter.next() {
+                None => break,
+                Some(Ok((_, instr))) => instr,
+                Some(Err(err)) if iter.pos() == pos => return Err(std::io::Error::other(err)),
+                Some(Err(_)) => {
+                    writeln!(writer, "offset {pos:06}: ; <incomplete instruction>")?;
+                    continue;
+                }
+            };
+            let mut text = instr.to_string();
+            if let Some(lib_id) = instr.external_ref() {
+                if let Some(name) = symbols.get(&lib_id) {
+                    text = text.replace(&lib_id.to_string(), name);
+                }
+            }
+            let refs = local_targets(&mut instr, pos)
+                .into_iter()
+                .flatten()
+                .filter(|target| labels.contains(target))
+                .map(|target| format!("loc_{target:04X}"))
+                .collect::<Vec<_>>();
+            if refs.is_empty() {
+                writeln!(writer, "offset {pos:06}: {text}")?;
+            } else {
+                writeln!(writer, "offset {pos:06}: {text:<32}; {}", refs.join(", "))?;
             }
         }
         Ok(())
     }
 }
+
+/// Iterator lazily decoding library instructions, returned by [`Lib::instr_iter`].
+///
+/// Yields each instruction together with its offset in the code segment. The iteration stops at
+/// the end of the code segment, or after yielding the first decoding error, unless the iterator is
+/// turned into [`ResumeAfterError`].
+pub struct InstrIter<'lib, Isa: Instruction<LibId>> {
+    lib: &'lib Lib,
+    reader: Marshaller<'lib, &'lib SmallBlob, &'lib SmallBlob>,
+    code_len: u16,
+    failed: bool,
+    _isa: PhantomData<Isa>,
+}
+
+impl<'lib, Isa: Instruction<LibId>> InstrIter<'lib, Isa> {
+    /// Returns the number of code segment bytes which were not decoded yet.
+    ///
+    /// After a decoding error, these are the bytes following the part of the failed instruction
+    /// which was consumed before the error.
+    pub fn remaining_bytes(&self) -> u16 { self.code_len - self.pos() }
+
+    /// Turns the iterator into the one which doesn't stop at the decoding errors, resuming the
+    /// decoding from the byte following the offset of the failed instruction.
+    pub fn resume_after_error(self) -> ResumeAfterError<'lib, Isa> { ResumeAfterError(self) }
+
+    fn pos(&self) -> u16 { self.reader.pos().min(self.code_len) }
+
+    /// Restarts decoding from the provided offset after a decoding error, which may have left the
+    /// reader in the middle of a byte.
+    fn restart_at(&mut self, pos: u16) {
+        let lib = self.lib;
+        self.reader = Marshaller::with_unchecked(lib.code(), lib.data(), lib.libs());
+        self.failed = self.reader.seek(pos).is_err();
+    }
+}
+
+impl<Isa: Instruction<LibId>> Iterator for InstrIter<'_, Isa> {
+    type Item = Result<(u16, Isa), CodeEofError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed || self.reader.is_eof() {
+            return None;
+        }
+        let pos = self.reader.pos();
+        let res = Isa::decode_instr(&mut self.reader).map(|instr| (pos, instr));
+        self.failed = res.is_err();
+        Some(res)
+    }
+}
+
+impl<Isa: Instruction<LibId>> FusedIterator for InstrIter<'_, Isa> {}
+
+/// Iterator decoding library instructions which doesn't stop at the decoding errors, returned by
+/// [`InstrIter::resume_after_error`] and [`Lib::disassemble_iter`].
+///
+/// Yields each instruction or a decoding error together with the offset in the code segment the
+/// decoding has started from. After an error, the decoding resumes from the byte following that
+/// offset; the iteration stops at the end of the code segment.
+pub struct ResumeAfterError<'lib, Isa: Instruction<LibId>>(InstrIter<'lib, Isa>);
+
+impl<Isa: Instruction<LibId>> Iterator for ResumeAfterError<'_, Isa> {
+    type Item = (u16, Result<Isa, CodeEofError>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let pos = self.0.pos();
+        let res = self.0.next()?;
+        if res.is_err() {
+            match pos.checked_add(1) {
+                Some(next) => self.0.restart_at(next),
+                None => self.0.failed = true,
+            }
+        }
+        Some((pos, res.map(|(_, instr)| instr)))
+    }
+}
+
+impl<Isa: Instruction<LibId>> FusedIterator for ResumeAfterError<'_, Isa> {}
+
+fn is_label(s: &str) -> bool {
+    let mut chars = s.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Parses a single instruction of the source text, substituting the external site aliases and
+/// returning the referenced label, if any.
+fn parse_source_instr<'s>(
+    instr: &'s str,
+    line: usize,
+    aliases: &[(&str, LibId)],
+) -> Result<(Instr<LibId>, Option<&'s str>), SourceError> {
+    let (mnemonic, operands) = instr.split_once(char::is_whitespace).unwrap_or((instr, ""));
+    let operands = operands.split(',').map(str::trim).collect::<Vec<_>>();
+    let target_no = match (mnemonic, operands.len()) {
+        ("jmp" | "call", 1) => Some(0),
+        ("jif", 2) => Some(1),
+        _ => None,
+    };
+    let mut label = None;
+    let mut substituted = operands.iter().map(ToString::to_string).collect::<Vec<_>>();
+    if let Some(no) = target_no {
+        let target = operands[no];
+        if let Some((alias, offset)) = target.strip_prefix('@').and_then(|s| s.split_once(':')) {
+            let (_, lib_id) = aliases
+                .iter()
+                .find(|(name, _)| *name == alias)
+                .ok_or_else(|| SourceError::UnknownAlias { line, alias: alias.to_string() })?;
+            substituted[no] = format!("{lib_id}@{offset}");
+        } else if is_label(target) {
+            label = Some(target);
+            substituted[no] = s!("0");
+        }
+    }
+    let instr = format!("{mnemonic} {}", substituted.join(", "));
+    let instr = Instr::from_str(&instr).map_err(|err| SourceError::Parse(line, err))?;
+    Ok((instr, label))
+}
+
+#[cfg(test)]
+mod test {
+    #![cfg_attr(coverage_nightly, coverage(off))]
+
+    use super::*;
+    use crate::isa::{parse_asm, CtrlInstr, Instr};
+    use crate::{InvalidJump, Site};
+
+    /// Generates a program of `count` instructions referencing libraries in a non-sorted order.
+    fn program(count: usize) -> impl Iterator<Item = Instr<LibId>> {
+        let libs = [7u8, 3, 250, 1, 42].map(|b| LibId::from([b; 32]));
+        (0..count).map(move |i| {
+            let pos = (i * 31) as u16;
+            match i % 6 {
+                0 => CtrlInstr::Call { site: Site::new(libs[i / 6 % libs.len()], pos) },
+                1 => CtrlInstr::Nop,
+                2 => CtrlInstr::Jmp { pos },
+                3 => CtrlInstr::Exec { site: Site::new(libs[i / 3 % libs.len()], pos) },
+                4 => CtrlInstr::Fn { pos },
+                _ => CtrlInstr::ChkCo,
+            }
+            .into()
+        })
+    }
+
+    #[test]
+    fn streaming_matches_one_shot() {
+        let mut asm = LibAssembler::new();
+        let mut code = vec![];
+        for instr in program(usize::MAX) {
+            if asm.append(&instr).is_err() {
+                break;
+            }
+            code.push(instr);
+        }
+        assert!(asm.code_len() > 0xFF00);
+        let lib = asm.finish().unwrap();
+        assert_eq!(lib.libs().len(), 5);
+        assert_eq!(lib.code().capacity(), lib.code().len());
+        assert_eq!(lib, Lib::assemble(&code).unwrap());
+        assert_eq!(lib.disassemble::<Instr<LibId>>().unwrap(), code);
+    }
+
+    #[test]
+    fn streaming_rollback() {
+        let mut asm = LibAssembler::new();
+        for instr in program(10) {
+            asm.append(&instr).unwrap();
+        }
+        let before = asm.clone();
+        let mut full = asm.clone();
+        while full.append(&CtrlInstr::Nop.into()).is_ok() {}
+        let len = full.code_len();
+        assert!(full
+            .append(&CtrlInstr::Call { site: Site::new(LibId::from([9u8; 32]), 0) }.into())
+            .is_err());
+        assert_eq!(full.code_len(), len);
+        assert_eq!(full.libs, before.libs);
+        assert_eq!(full.patches.len(), before.patches.len());
+        assert_eq!(asm, before);
+    }
+
+    #[test]
+    fn streaming_libs_overflow() {
+        let mut asm = LibAssembler::<Instr<LibId>>::new();
+        for b in 0..u8::MAX {
+            let site = Site::new(LibId::from([b; 32]), 0);
+            asm.append(&CtrlInstr::Exec { site }.into()).unwrap();
+        }
+        let lib_id = LibId::from([u8::MAX; 32]);
+        let instr = CtrlInstr::Exec { site: Site::new(lib_id, 0) }.into();
+        assert_eq!(
+            asm.append(&instr),
+            Err(AssemblerError::LibsOverflow {
+                index: 255,
+                offset: 255 * 4,
+                mnemonic: instr.to_string(),
+                lib_id
+            })
+        );
+        assert_eq!(asm.libs.len(), u8::MAX as usize);
+        assert_eq!(asm.finish().unwrap().libs().len(), u8::MAX as usize);
+    }
+
+    #[test]
+    fn assemble_libs_overflow() {
+        let mut code = (0..=u8::MAX)
+            .map(|b| CtrlInstr::Call { site: Site::new(LibId::from([b; 32]), 0) }.into())
+            .collect::<Vec<Instr<LibId>>>();
+        // References to already known libraries don't overflow the segment
+        code.insert(1, CtrlInstr::Call { site: Site::new(LibId::from([0u8; 32]), 8) }.into());
+        let err = Lib::assemble(&code).unwrap_err();
+        assert_eq!(err, AssemblerError::LibsOverflow {
+            index: 256,
+            offset: 256 * 4,
+            mnemonic: code[256].to_string(),
+            lib_id: LibId::from([u8::MAX; 32]),
+        });
+        assert!(err.to_string().starts_with("instruction #256 `call "));
+    }
+
+    #[test]
+    fn assemble_code_overflow() {
+        let mut code = vec![Instr::<LibId>::from(CtrlInstr::Nop); 0xFFFD];
+        code.push(CtrlInstr::Jmp { pos: 0 }.into());
+        code.push(CtrlInstr::Stop.into());
+        let err = Lib::assemble(&code).unwrap_err();
+        assert_eq!(err, AssemblerError::Instr {
+            index: 0xFFFD,
+            offset: 0xFFFD,
+            mnemonic: code[0xFFFD].to_string(),
+            source: MarshallError::CodeNotFittingSegment,
+        });
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "instruction #65533 `{}` at offset 0xFFFD can't be encoded: attempt to read or \
+                 write outside of code segment (i.e. at position > 0xFF).",
+                code[0xFFFD]
+            )
+        );
+
+        let mut asm = LibAssembler::new();
+        for instr in &code[..0xFFFD] {
+            asm.append(instr).unwrap();
+        }
+        assert_eq!(asm.append(&code[0xFFFD]), Err(err));
+    }
+
+    #[test]
+    fn streaming_patch_jump() {
+        let mut asm = LibAssembler::<Instr<LibId>>::new();
+        asm.append(&CtrlInstr::ChkCo.into()).unwrap();
+        let jif = asm.offset();
+        asm.append(&CtrlInstr::JiFail { pos: 0 }.into()).unwrap();
+        let sh = asm.offset();
+        asm.append(&CtrlInstr::ShFail { shift: 0 }.into()).unwrap();
+        asm.append(&CtrlInstr::Call { site: Site::new(LibId::from([9u8; 32]), 0) }.into())
+            .unwrap();
+        let target = asm.offset();
+        asm.append(&CtrlInstr::Stop.into()).unwrap();
+
+        asm.patch_jump(jif, target).unwrap();
+        asm.patch_jump(sh, target).unwrap();
+        let before = asm.clone();
+        assert_eq!(asm.patch_jump(2, target), Err(PatchError::NoInstruction(2)));
+        assert_eq!(asm.patch_jump(0, target), Err(PatchError::NoJump(0)));
+        assert_eq!(asm.patch_jump(6, target), Err(PatchError::NoJump(6)));
+        assert_eq!(asm.patch_jump(target + 1, 0), Err(PatchError::NoInstruction(target + 1)));
+        assert_eq!(
+            asm.patch_jump(sh, 0x100),
+            Err(PatchError::ShiftOverflow { offset: sh, target: 0x100 })
+        );
+        assert_eq!(asm, before);
+
+        let code: [Instr<LibId>; 5] = [
+            CtrlInstr::ChkCo.into(),
+            CtrlInstr::JiFail { pos: target }.into(),
+            CtrlInstr::ShFail { shift: (target - sh) as i8 }.into(),
+            CtrlInstr::Call { site: Site::new(LibId::from([9u8; 32]), 0) }.into(),
+            CtrlInstr::Stop.into(),
+        ];
+        assert_eq!(asm.finish().unwrap(), Lib::assemble(&code).unwrap());
+    }
+
+    #[test]
+    fn assemble_validated() {
+        let code: [Instr<LibId>; 3] =
+            [CtrlInstr::Nop.into(), CtrlInstr::Jmp { pos: 0 }.into(), CtrlInstr::Stop.into()];
+        assert_eq!(Lib::assemble_validated(&code), Lib::assemble(&code));
+
+        let code: [Instr<LibId>; 3] =
+            [CtrlInstr::Nop.into(), CtrlInstr::Jmp { pos: 4 }.into(), CtrlInstr::Stop.into()];
+        assert!(Lib::assemble(&code).is_ok());
+        assert_eq!(
+            Lib::assemble_validated(&code),
+            Err(AssemblerError::Validation(LibValidationError::InvalidJump(
+                InvalidJump::NotGotoTarget { source: 1, target: 4 }
+            )))
+        );
+    }
+
+    #[test]
+    fn disassemble_offsets() {
+        let ext = Site::new(LibId::from([9u8; 32]), 4);
+        let code: [Instr<LibId>; 6] = [
+            CtrlInstr::Nop.into(),
+            CtrlInstr::Call { site: ext }.into(),
+            CtrlInstr::Sh { shift: -5 }.into(),
+            CtrlInstr::Jmp { pos: 7 }.into(),
+            CtrlInstr::ChkCo.into(),
+            CtrlInstr::Stop.into(),
+        ];
+        let lib = Lib::assemble(&code).unwrap();
+        let listing = lib.disassemble_with_offsets::<Instr<LibId>>().unwrap();
+        let offsets = listing.iter().map(|(pos, _)| *pos).collect::<Vec<_>>();
+        assert_eq!(offsets, [0, 1, 5, 7, 10, 11]);
+        assert!(listing.iter().map(|(_, instr)| instr).eq(&code));
+        for (pos, instr) in offsets.iter().zip(&code) {
+            assert_eq!(lib.instr_at::<Instr<LibId>>(*pos).as_ref(), Ok(instr));
+        }
+
+        // The jump target can be mapped back to the instruction index
+        let idx = offsets.partition_point(|pos| *pos <= 7) - 1;
+        assert_eq!(code[idx], CtrlInstr::Jmp { pos: 7 }.into());
+        assert_eq!(lib.instr_at::<Instr<LibId>>(12), Err(CodeEofError));
+    }
+
+    #[test]
+    fn instr_iter() {
+        let lib = Lib::assemble(&program(120).collect::<Vec<_>>()).unwrap();
+        let listing = lib.disassemble_with_offsets::<Instr<LibId>>().unwrap();
+        let mut iter = lib.instr_iter::<Instr<LibId>>();
+        assert_eq!(iter.remaining_bytes() as usize, lib.code().len());
+        assert!(iter
+            .by_ref()
+            .map(Result::unwrap)
+            .eq(listing.iter().copied()));
+        assert_eq!(iter.remaining_bytes(), 0);
+        assert_eq!(iter.next(), None);
+
+        // The iteration can be stopped early
+        let mut iter = lib.instr_iter::<Instr<LibId>>();
+        let (pos, _) = iter
+            .find_map(|res| res.ok().filter(|(_, instr)| instr.is_goto_target()))
+            .unwrap();
+        assert_eq!(pos, listing[1].0);
+        assert_eq!(iter.remaining_bytes() as usize, lib.code().len() - listing[2].0 as usize);
+    }
+
+    #[test]
+    fn disassemble_iter() {
+        let lib = Lib::assemble(&program(120).collect::<Vec<_>>()).unwrap();
+        let listing = lib.disassemble_with_offsets::<Instr<LibId>>().unwrap();
+        assert!(lib
+            .disassemble_iter::<Instr<LibId>>()
+            .map(|(pos, res)| (pos, res.unwrap()))
+            .eq(listing.iter().copied()));
+    }
+
+    #[test]
+    fn disassemble_iter_resumes() {
+        const NOP: u8 = CtrlInstr::<LibId>::NOP;
+        const CALL: u8 = CtrlInstr::<LibId>::CALL;
+        const JMP: u8 = CtrlInstr::<LibId>::JMP;
+        const STOP: u8 = CtrlInstr::<LibId>::STOP;
+
+        // Library reference outside of the (empty) libs segment, followed by a truncated jump
+        let mut lib = Lib::assemble::<Instr<LibId>>(&[]).unwrap();
+        *lib.code_mut() = SmallBlob::from_checked(vec![NOP, CALL, NOP, STOP, STOP, NOP, JMP, STOP]);
+        let mut iter = lib.disassemble_iter::<Instr<LibId>>();
+        assert_eq!(iter.next(), Some((0, Ok(CtrlInstr::Nop.into()))));
+        assert_eq!(iter.next(), Some((1, Err(CodeEofError))));
+        assert_eq!(iter.next(), Some((2, Ok(CtrlInstr::Nop.into()))));
+        assert_eq!(iter.next(), Some((3, Ok(CtrlInstr::Stop.into()))));
+        assert_eq!(iter.next(), Some((4, Ok(CtrlInstr::Stop.into()))));
+        assert_eq!(iter.next(), Some((5, Ok(CtrlInstr::Nop.into()))));
+        assert_eq!(iter.next(), Some((6, Err(CodeEofError))));
+        assert_eq!(iter.next(), Some((7, Ok(CtrlInstr::Stop.into()))));
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.next(), None);
+
+        // The plain iterator stops after the first error, unless it is resumed
+        let mut iter = lib.instr_iter::<Instr<LibId>>();
+        assert_eq!(iter.next(), Some(Ok((0, CtrlInstr::Nop.into()))));
+        let mut iter = iter.resume_after_error();
+        assert_eq!(iter.next(), Some((1, Err(CodeEofError))));
+        assert_eq!(iter.next(), Some((2, Ok(CtrlInstr::Nop.into()))));
+        assert_eq!(iter.count(), 5);
+
+        // An error at the last byte ends the iteration
+        *lib.code_mut() = SmallBlob::from_checked(vec![NOP, JMP]);
+        let items = lib.disassemble_iter::<Instr<LibId>>().collect::<Vec<_>>();
+        assert_eq!(items, vec![(0, Ok(CtrlInstr::Nop.into())), (1, Err(CodeEofError))]);
+    }
+
+    #[test]
+    fn instr_iter_truncated() {
+        let code: [Instr<LibId>; 3] =
+            [CtrlInstr::Nop.into(), CtrlInstr::ChkCo.into(), CtrlInstr::Jmp { pos: 5 }.into()];
+        let mut lib = Lib::assemble(&code).unwrap();
+        *lib.code_mut() = SmallBlob::from_checked(lib.code()[..4].to_vec());
+
+        let mut iter = lib.instr_iter::<Instr<LibId>>();
+        assert_eq!(iter.next(), Some(Ok((0, code[0]))));
+        assert_eq!(iter.next(), Some(Ok((1, code[1]))));
+        assert_eq!(iter.remaining_bytes(), 2);
+        assert_eq!(iter.next(), Some(Err(CodeEofError)));
+        assert_eq!(iter.remaining_bytes(), 0);
+        // The iterator is fused after the error
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.next(), None);
+
+        let collected = lib
+            .instr_iter::<Instr<LibId>>()
+            .collect::<Result<Vec<_>, _>>();
+        assert_eq!(collected, lib.disassemble_with_offsets::<Instr<LibId>>());
+        assert_eq!(collected, Err(CodeEofError));
+        assert_eq!(lib.disassemble::<Instr<LibId>>(), Err(CodeEofError));
+    }
+
+    #[test]
+    fn disassemble_listing() {
+        let code: [Instr<LibId>; 5] = [
+            CtrlInstr::Nop.into(),
+            CtrlInstr::ChkCo.into(),
+            CtrlInstr::Jmp { pos: 5 }.into(),
+            CtrlInstr::Sh { shift: -2 }.into(),
+            CtrlInstr::Stop.into(),
+        ];
+        let mut lib = Lib::assemble(&code).unwrap();
+        assert_eq!(
+            lib.disassemble_to_string::<Instr<LibId>>().unwrap(),
+            "offset 000000: nop
+offset 000001: chk     CO
+offset 000002: jmp     5
+offset 000005: jmp     -2
+offset 000007: stop
+"
+        );
+        assert_eq!(
+            lib.disassemble_to_string_with_bytes::<Instr<LibId>>()
+                .unwrap(),
+            "offset 000000: nop                             ; 00
+offset 000001: chk     CO                      ; 02
+offset 000002: jmp     5                       ; 06 05 00
+offset 000005: jmp     -2                      ; 09 fe
+offset 000007: stop                            ; 10
+"
+        );
+        let listing = lib
+            .disassemble_to_string_with_bytes::<Instr<LibId>>()
+            .unwrap();
+        assert_eq!(parse_asm::<LibId>(&listing).unwrap(), code);
+
+        // Truncated jump
+        *lib.code_mut() = SmallBlob::from_checked(lib.code()[..4].to_vec());
+        assert_eq!(
+            lib.disassemble_to_string::<Instr<LibId>>().unwrap(),
+            "offset 000000: nop
+offset 000001: chk     CO
+offset 000002: ; <incomplete instruction>
+"
+        );
+        assert_eq!(
+            lib.disassemble_to_string_with_bytes::<Instr<LibId>>()
+                .unwrap(),
+            "offset 000000: nop                             ; 00
+offset 000001: chk     CO                      ; 02
+offset 000002: ; <incomplete instruction>        06 05
+"
+        );
+    }
+
+    #[test]
+    fn patch_unaligned() {
+        let mut code = [0b1010_1111, 0b0101_0110];
+        patch_byte(&mut code, 4, |b| {
+            assert_eq!(b, 0b0110_1010);
+            0b1001_0101
+        });
+        assert_eq!(code, [0b0101_1111, 0b0101_1001]);
+        patch_byte(&mut code, 8, |b| b.wrapping_add(1));
+        assert_eq!(code, [0b0101_1111, 0b0101_1010]);
+    }
+
+    #[test]
+    fn source_labels() {
+        let ext = LibId::from([7u8; 32]);
+        let lib = Lib::assemble_from_source(
+            "
+            ; forward and backward references
+            start:  jmp     end
+                    not     CO
+            loop:
+                    jif     CO, loop
+                    jif     CK,loop     ; no space after the comma
+                    call    @ext:12
+                    jmp     @ext:0
+                    call    sub
+            end:    stop
+            sub:    ret
+            ",
+            &[("ext", ext)],
+        )
+        .unwrap();
+        let loop_pos = 4;
+        let end = loop_pos + 3 + 3 + 4 + 4 + 3;
+        let code: [Instr<LibId>; 9] = [
+            CtrlInstr::Jmp { pos: end }.into(),
+            CtrlInstr::NotCo.into(),
+            CtrlInstr::JiOvfl { pos: loop_pos }.into(),
+            CtrlInstr::JiFail { pos: loop_pos }.into(),
+            CtrlInstr::Call { site: Site::new(ext, 12) }.into(),
+            CtrlInstr::Exec { site: Site::new(ext, 0) }.into(),
+            CtrlInstr::Fn { pos: end + 1 }.into(),
+            CtrlInstr::Stop.into(),
+            CtrlInstr::Ret.into(),
+        ];
+        assert_eq!(lib, Lib::assemble(&code).unwrap());
+    }
+
+    #[test]
+    fn source_end_label() {
+        let lib = Lib::assemble_from_source("jmp end\nend:", &[]).unwrap();
+        assert_eq!(lib.disassemble::<Instr<LibId>>().unwrap(), [CtrlInstr::Jmp { pos: 3 }.into()]);
+        assert_eq!(lib.validate::<Instr<LibId>>(), Ok(()));
+        assert_eq!(lib.validate_code::<Instr<LibId>>(), Ok(()));
+    }
+
+    #[test]
+    fn source_errors() {
+        let asm = |s: &str| Lib::assemble_from_source(s, &[("ext", LibId::from([7u8; 32]))]);
+        assert_eq!(
+            asm("nop\njmp missing"),
+            Err(SourceError::UndefinedLabel { line: 2, label: s!("missing") })
+        );
+        assert_eq!(
+            asm("a: nop\n\na: nop"),
+            Err(SourceError::DuplicateLabel { line: 3, label: s!("a"), defined: 1 })
+        );
+        assert_eq!(asm("1a: nop"), Err(SourceError::InvalidLabel { line: 1, label: s!("1a") }));
+        assert_eq!(
+            asm("call @lib:1"),
+            Err(SourceError::UnknownAlias { line: 1, alias: s!("lib") })
+        );
+        assert_eq!(
+            asm("nop\nadd A, B"),
+            Err(SourceError::Parse(2, AsmParseError::UnknownMnemonic(s!("add"))))
+        );
+        assert_eq!(
+            asm("call @ext:x"),
+            Err(SourceError::Parse(1, AsmParseError::InvalidOffset(s!("x"))))
+        );
+        assert!(matches!(asm("jif CO, @ext:1"), Err(SourceError::Parse(1, _))));
+    }
+
+    #[test]
+    fn source_offset_overflow() {
+        let mut source = s!("jmp end\n");
+        for _ in 0..u16::MAX {
+            source.push_str("nop\n");
+        }
+        source.push_str("end:");
+        assert_eq!(
+            Lib::assemble_from_source(&source, &[]),
+            Err(SourceError::OffsetOverflow { line: 1, label: s!("end") })
+        );
+    }
+
+    #[test]
+    fn streaming_symbols() {
+        let mut asm = LibAssembler::<Instr<LibId>>::new();
+        let root = asm
+            .define_symbol(SymbolName::from("root"), b"merkle")
+            .unwrap();
+        assert_eq!(root, DataRange::new(0, 6));
+        // Constants share the place with the equal data
+        let tail = asm.define_symbol(SymbolName::from("tail"), b"kle").unwrap();
+        assert_eq!(tail, DataRange::new(3, 3));
+        assert_eq!(asm.data_len(), 6);
+        assert_eq!(asm.symbol(&SymbolName::from("tail")), Some(tail));
+        assert_eq!(asm.symbol(&SymbolName::from("absent")), None);
+
+        let before = asm.clone();
+        assert_eq!(
+            asm.define_symbol(SymbolName::from("root"), b"other"),
+            Err(SymbolError::Duplicate(SymbolName::from("root")))
+        );
+        assert_eq!(
+            asm.define_symbol(SymbolName::from("huge"), &[0xA5; 0xFFFA]),
+            Err(SymbolError::DataOverflow(SymbolName::from("huge")))
+        );
+        assert_eq!(asm, before);
+
+        asm.append(&CtrlInstr::Save { mask: root.offset }.into())
+            .unwrap();
+        let (lib, symbols) = asm.finish_with_symbols().unwrap();
+        assert_eq!(symbols.lib_id(), lib.lib_id());
+        assert_eq!(lib.symbol(&symbols, "root"), Some(&b"merkle"[..]));
+        assert_eq!(lib.symbol(&symbols, "tail"), Some(&b"kle"[..]));
+    }
+
+    #[test]
+    fn streaming_symbols_too_many() {
+        let mut asm = LibAssembler::<Instr<LibId>>::new();
+        for no in 0..u8::MAX {
+            let name = SymbolName::from_str(&format!("s{no}")).unwrap();
+            asm.define_symbol(name, &[no]).unwrap();
+        }
+        assert_eq!(asm.define_symbol(SymbolName::from("last"), b""), Err(SymbolError::TooMany));
+        assert_eq!(asm.finish_with_symbols().unwrap().1.len(), 255);
+    }
+
+    #[test]
+    fn shared_constant() {
+        let hash = [0xA5u8; 32];
+        let mut asm = LibAssembler::<Instr<LibId>>::new();
+        asm.define_symbol(SymbolName::from("prefix"), b"alu")
+            .unwrap();
+        let first = asm.define_symbol(SymbolName::from("hash"), &hash).unwrap();
+        let second = asm.define_symbol(SymbolName::from("same"), &hash).unwrap();
+        assert_eq!(first, DataRange::new(3, 32));
+        assert_eq!(second, first);
+
+        let code: [Instr<LibId>; 2] = [
+            CtrlInstr::Save { mask: first.offset }.into(),
+            CtrlInstr::Save { mask: second.offset }.into(),
+        ];
+        for instr in &code {
+            asm.append(instr).unwrap();
+        }
+        let (lib, symbols) = asm.finish_with_symbols().unwrap();
+        assert_eq!(lib.data().len(), 35);
+        assert_eq!(
+            lib.data()
+                .windows(32)
+                .filter(|window| *window == hash)
+                .count(),
+            1
+        );
+        assert_eq!(lib.symbol(&symbols, "hash"), Some(&hash[..]));
+        assert_eq!(lib.symbol(&symbols, "same"), Some(&hash[..]));
+        assert_eq!(lib.disassemble::<Instr<LibId>>().unwrap(), code);
+    }
+}
@@ -0,0 +1,127 @@
+// Reference rust implementation of AluVM (arithmetic logic unit virtual machine).
+// To find more on AluVM please check <https://aluvm.org>
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Designed in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+// Written in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+//
+// Copyright (C) 2021-2024 LNP/BP Standards Association, Switzerland.
+// Copyright (C) 2024-2025 Laboratories for Ubiquitous Deterministic Computing (UBIDECO),
+//                         Institute for Distributed and Cognitive Systems (InDCS), Switzerland.
+// Copyright (C) 2021-2025 Dr Maxim Orlovsky.
+// All rights under the above copyrights are reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use this file except
+// in compliance with the License. You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed under the License
+// is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express
+// or implied. See the License for the specific language governing permissions and limitations under
+// the License.
+
+use alloc::vec::Vec;
+
+use super::{Lib, LibId, Marshaller};
+use crate::isa::{BytecodeRead, Instruction};
+
+/// Index of instruction boundaries in a library code segment.
+///
+/// An offset is an instruction boundary if the code segment, decoded sequentially from the start,
+/// has an instruction starting at that offset. If the code segment contains a byte sequence which
+/// can't be decoded, none of the offsets starting from that sequence are boundaries.
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct BoundaryIndex(Vec<u8>);
+
+impl BoundaryIndex {
+    /// Checks whether an instruction starts at the provided offset.
+    #[inline]
+    pub fn is_boundary(&self, pos: u16) -> bool {
+        let pos = pos as usize;
+        self.0
+            .get(pos / 8)
+            .is_some_and(|byte| byte & (1 << (pos % 8)) != 0)
+    }
+
+    /// Returns the number of instruction boundaries in the index.
+    #[inline]
+    pub fn count(&self) -> usize { self.0.iter().map(|byte| byte.count_ones() as usize).sum() }
+
+    pub(super) const fn new() -> Self { Self(Vec::new()) }
+
+    pub(super) fn shrink_to_fit(&mut self) { self.0.shrink_to_fit() }
+
+    pub(super) fn insert(&mut self, pos: u16) {
+        let pos = pos as usize;
+        if self.0.len() <= pos / 8 {
+            self.0.resize(pos / 8 + 1, 0);
+        }
+        self.0[pos / 8] |= 1 << (pos % 8);
+    }
+}
+
+impl Lib {
+    /// Computes the index of instruction boundaries in the library code segment by decoding it
+    /// sequentially.
+    pub fn boundary_index<Isa>(&self) -> BoundaryIndex
+    where Isa: Instruction<LibId> {
+        let mut index = BoundaryIndex::default();
+        let mut reader = Marshaller::with_unchecked(self.code(), self.data(), self.libs());
+        while !reader.is_eof() {
+            let pos = reader.pos();
+            if Isa::decode_instr(&mut reader).is_err() {
+                break;
+            }
+            index.insert(pos);
+        }
+        index
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![cfg_attr(coverage_nightly, coverage(off))]
+
+    use amplify::confinement::SmallBlob;
+    use strict_encoding::StrictDumb;
+
+    use super::*;
+    use crate::isa::{CtrlInstr, Instr};
+
+    #[test]
+    fn boundaries() {
+        let code: [Instr<LibId>; 4] = [
+            CtrlInstr::Nop.into(),
+            CtrlInstr::Jmp { pos: 0 }.into(),
+            CtrlInstr::ChkCo.into(),
+            CtrlInstr::Fn { pos: 0 }.into(),
+        ];
+        let lib = Lib::assemble(&code).unwrap();
+        let index = lib.boundary_index::<Instr<LibId>>();
+        assert_eq!(index.count(), 4);
+        for pos in 0..=u16::MAX {
+            assert_eq!(index.is_boundary(pos), [0, 1, 4, 5].contains(&pos), "{pos}");
+        }
+    }
+
+    #[test]
+    fn boundaries_truncated() {
+        let code: [Instr<LibId>; 2] = [CtrlInstr::Nop.into(), CtrlInstr::Jmp { pos: 0 }.into()];
+        let mut lib = Lib::assemble(&code).unwrap();
+        // Nop repeated twice, followed by a jump missing its last byte
+        *lib.code_mut() = SmallBlob::try_from_slice(&[
+            lib.code()[0],
+            lib.code()[0],
+            lib.code()[1],
+            lib.code()[2],
+        ])
+        .unwrap();
+        let index = lib.boundary_index::<Instr<LibId>>();
+        assert_eq!(index.count(), 2);
+        assert!(index.is_boundary(1));
+        assert!(!index.is_boundary(2));
+        assert_eq!(Lib::strict_dumb().boundary_index::<Instr<LibId>>().count(), 0);
+    }
+}
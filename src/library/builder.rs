@@ -0,0 +1,358 @@
+// Reference rust implementation of AluVM (arithmetic logic unit virtual machine).
+// To find more on AluVM please check <https://aluvm.org>
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Designed in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+// Written in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+//
+// Copyright (C) 2021-2024 LNP/BP Standards Association, Switzerland.
+// Copyright (C) 2024-2025 Laboratories for Ubiquitous Deterministic Computing (UBIDECO),
+//                         Institute for Distributed and Cognitive Systems (InDCS), Switzerland.
+// Copyright (C) 2021-2025 Dr Maxim Orlovsky.
+// All rights under the above copyrights are reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use this file except
+// in compliance with the License. You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed under the License
+// is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express
+// or implied. See the License for the specific language governing permissions and limitations under
+// the License.
+
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+
+use crate::isa::{CtrlInstr, GotoTarget, Instruction};
+use crate::{AssemblerError, Lib, LibId, Site};
+
+/// Errors building a library with [`LibBuilder`].
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum LibBuilderError {
+    /// routine `{0}` is already defined.
+    DuplicateRoutine(String),
+
+    /// routine `{0}` starts at an offset exceeding the maximal code segment size.
+    OffsetOverflow(String),
+
+    /// routine `{0}` is not defined.
+    UnresolvedRoutine(String),
+
+    /// external symbol `{0}` is not defined.
+    UnresolvedSymbol(String),
+
+    /// {0}
+    #[from]
+    Assemble(AssemblerError),
+}
+
+/// Reference by name, which is resolved once all the routines are known.
+#[derive(Clone, Eq, PartialEq, Debug)]
+enum NameRef {
+    Routine(String),
+    Extern(String),
+}
+
+/// Builder of libraries consisting of named routines, which may call each other and the external
+/// libraries by name.
+///
+/// Each routine starts with a `nop` instruction, making its entry point a goto target, in the same
+/// way as `routine` labels do in [`crate::aluasm`]. The references to the routines, including the
+/// ones defined further in the code, and to the external symbols are resolved by
+/// [`LibBuilder::finish`], which then assembles the library with [`Lib::assemble`].
+///
+/// # Example
+///
+/// ```
+/// # extern crate alloc;
+/// use aluvm::isa::{CtrlInstr, Instr};
+/// use aluvm::regs::Status;
+/// use aluvm::{LibBuilder, LibId, LibSite, Vm};
+///
+/// let mut builder = LibBuilder::<Instr<LibId>>::new();
+/// builder
+///     .routine("main", |code| {
+///         code.call("check").push(CtrlInstr::Stop);
+///     })?
+///     .routine("check", |code| {
+///         code.push(CtrlInstr::NotCo).push(CtrlInstr::Ret);
+///     })?;
+/// let lib = builder.finish()?;
+/// let mut vm = Vm::<Instr<LibId>>::new();
+/// assert_eq!(vm.exec(LibSite::new(lib.lib_id(), 0), &(), |_| Some(&lib)), Status::Ok);
+/// # Ok::<_, aluvm::LibBuilderError>(())
+/// ```
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct LibBuilder<Isa: Instruction<LibId>> {
+    code: Vec<Isa>,
+    /// Offset of the next instruction, which may exceed the code segment size.
+    offset: usize,
+    routines: BTreeMap<String, u16>,
+    externals: BTreeMap<String, Site<LibId>>,
+    refs: Vec<(usize, NameRef)>,
+}
+
+impl<Isa: Instruction<LibId> + From<CtrlInstr<LibId>>> Default for LibBuilder<Isa> {
+    fn default() -> Self { Self::new() }
+}
+
+impl<Isa: Instruction<LibId> + From<CtrlInstr<LibId>>> LibBuilder<Isa> {
+    /// Constructs a builder without any external symbols.
+    pub fn new() -> Self {
+        Self {
+            code: vec![],
+            offset: 0,
+            routines: none!(),
+            externals: none!(),
+            refs: vec![],
+        }
+    }
+
+    /// Constructs a builder resolving the external symbols to the provided sites in other
+    /// libraries.
+    pub fn with_externals<S: ToString>(
+        externals: impl IntoIterator<Item = (S, Site<LibId>)>,
+    ) -> Self {
+        let mut builder = Self::new();
+        builder.externals = externals
+            .into_iter()
+            .map(|(name, site)| (name.to_string(), site))
+            .collect();
+        builder
+    }
+
+    /// Defines a routine, appending its code produced by `f` to the library.
+    ///
+    /// # Errors
+    ///
+    /// If a routine with the same name is already defined, or if the routine doesn't start within
+    /// the maximal code segment size. In this case, `f` is not called and the builder state is not
+    /// changed.
+    pub fn routine(
+        &mut self,
+        name: &str,
+        f: impl FnOnce(&mut RoutineBuilder<Isa>),
+    ) -> Result<&mut Self, LibBuilderError> {
+        if self.routines.contains_key(name) {
+            return Err(LibBuilderError::DuplicateRoutine(name.to_string()));
+        }
+        let offset = u16::try_from(self.offset)
+            .map_err(|_| LibBuilderError::OffsetOverflow(name.to_string()))?;
+        self.routines.insert(name.to_string(), offset);
+        let mut routine = RoutineBuilder { builder: self, _phantom: PhantomData };
+        routine.push(CtrlInstr::Nop);
+        f(&mut routine);
+        Ok(self)
+    }
+
+    /// Returns the code offset of a previously defined routine.
+    pub fn routine_offset(&self, name: &str) -> Option<u16> { self.routines.get(name).copied() }
+
+    /// Resolves the references to the routines and the external symbols and assembles the
+    /// library.
+    ///
+    /// # Errors
+    ///
+    /// If a referenced routine or external symbol is not defined, or if the library can't be
+    /// assembled.
+    pub fn finish(mut self) -> Result<Lib, LibBuilderError> {
+        for (idx, name_ref) in self.refs {
+            let instr = &mut self.code[idx];
+            match name_ref {
+                NameRef::Routine(name) => {
+                    let Some(offset) = self.routines.get(&name) else {
+                        return Err(LibBuilderError::UnresolvedRoutine(name));
+                    };
+                    match instr.local_goto_pos() {
+                        GotoTarget::Absolute(pos) => *pos = *offset,
+                        _ => unreachable!("routine references are made only by local jumps"),
+                    }
+                }
+                NameRef::Extern(name) => {
+                    let Some(site) = self.externals.get(&name) else {
+                        return Err(LibBuilderError::UnresolvedSymbol(name));
+                    };
+                    *instr
+                        .remote_goto_pos()
+                        .expect("external references are made only by remote jumps") = *site;
+                }
+            }
+        }
+        Ok(Lib::assemble(&self.code)?)
+    }
+}
+
+/// Code of a routine defined with [`LibBuilder::routine`].
+pub struct RoutineBuilder<'builder, Isa: Instruction<LibId>> {
+    builder: &'builder mut LibBuilder<Isa>,
+    _phantom: PhantomData<Isa>,
+}
+
+impl<Isa: Instruction<LibId> + From<CtrlInstr<LibId>>> RoutineBuilder<'_, Isa> {
+    /// Appends an instruction to the routine.
+    pub fn push(&mut self, instr: impl Into<Isa>) -> &mut Self {
+        let instr = instr.into();
+        self.builder.offset += instr.code_byte_len() as usize;
+        self.builder.code.push(instr);
+        self
+    }
+
+    /// Appends a call of a routine of the same library, which may be defined later.
+    pub fn call(&mut self, routine: &str) -> &mut Self {
+        self.push_ref(CtrlInstr::Fn { pos: 0 }, NameRef::Routine(routine.to_string()))
+    }
+
+    /// Appends a jump to the start of a routine of the same library, which may be defined later.
+    pub fn jmp(&mut self, routine: &str) -> &mut Self {
+        self.push_ref(CtrlInstr::Jmp { pos: 0 }, NameRef::Routine(routine.to_string()))
+    }
+
+    /// Appends a call of an external symbol (see [`LibBuilder::with_externals`]).
+    pub fn call_extern(&mut self, symbol: &str) -> &mut Self {
+        let site = Site::new(LibId::default(), 0);
+        self.push_ref(CtrlInstr::Call { site }, NameRef::Extern(symbol.to_string()))
+    }
+
+    /// Appends a jump to an external symbol (see [`LibBuilder::with_externals`]).
+    pub fn exec_extern(&mut self, symbol: &str) -> &mut Self {
+        let site = Site::new(LibId::default(), 0);
+        self.push_ref(CtrlInstr::Exec { site }, NameRef::Extern(symbol.to_string()))
+    }
+
+    fn push_ref(&mut self, instr: CtrlInstr<LibId>, name_ref: NameRef) -> &mut Self {
+        self.builder.refs.push((self.builder.code.len(), name_ref));
+        self.push(instr)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![cfg_attr(coverage_nightly, coverage(off))]
+
+    use super::*;
+    use crate::isa::Instr;
+    use crate::regs::Status;
+    use crate::{aluasm, LibSite, Vm};
+
+    #[test]
+    fn forward_ref() {
+        let mut builder = LibBuilder::<Instr<LibId>>::new();
+        builder
+            .routine("main", |code| {
+                code.call("sub")
+                    .push(CtrlInstr::ChkCo)
+                    .push(CtrlInstr::Stop);
+            })
+            .unwrap()
+            .routine("sub", |code| {
+                code.push(CtrlInstr::NotCo).push(CtrlInstr::Ret);
+            })
+            .unwrap();
+        assert_eq!(builder.routine_offset("main"), Some(0));
+        assert_eq!(builder.routine_offset("sub"), Some(6));
+        let lib = builder.finish().unwrap();
+
+        const SUB: u16 = 6;
+        let expected = Lib::assemble::<Instr<LibId>>(&aluasm! {
+            nop;
+            call    SUB;
+            chk     CO;
+            stop;
+            nop;
+            not     CO;
+            ret;
+        })
+        .unwrap();
+        assert_eq!(lib.code(), expected.code());
+        assert_eq!(lib.lib_id(), expected.lib_id());
+        assert_eq!(lib.validate_code::<Instr<LibId>>(), Ok(()));
+
+        let mut vm = Vm::<Instr<LibId>>::new();
+        assert_eq!(vm.exec(LibSite::new(lib.lib_id(), 0), &(), |_| Some(&lib)), Status::Fail);
+    }
+
+    #[test]
+    fn externals() {
+        let mut dep = LibBuilder::<Instr<LibId>>::new();
+        dep.routine("skip", |code| {
+            code.push(CtrlInstr::Ret);
+        })
+        .unwrap()
+        .routine("fail", |code| {
+            code.push(CtrlInstr::FailCk).push(CtrlInstr::Ret);
+        })
+        .unwrap();
+        let fail = dep.routine_offset("fail").unwrap();
+        let dep = dep.finish().unwrap();
+        let dep_id = dep.lib_id();
+
+        let mut builder =
+            LibBuilder::<Instr<LibId>>::with_externals([("fail", Site::new(dep_id, fail))]);
+        builder
+            .routine("main", |code| {
+                code.jmp("end").call_extern("fail");
+            })
+            .unwrap()
+            .routine("end", |code| {
+                code.exec_extern("fail");
+            })
+            .unwrap();
+        let lib = builder.finish().unwrap();
+        assert_eq!(lib.libs().iter().copied().collect::<Vec<_>>(), vec![dep_id]);
+        assert_eq!(lib.disassemble::<Instr<LibId>>().unwrap(), aluasm! {
+            nop;
+            jmp     8;
+            call    dep_id, fail;
+            nop;
+            jmp     dep_id, fail;
+        });
+
+        let resolver = |id| [&lib, &dep].into_iter().find(|lib| lib.lib_id() == id);
+        let mut vm = Vm::<Instr<LibId>>::new();
+        assert_eq!(vm.exec(LibSite::new(lib.lib_id(), 0), &(), resolver), Status::Fail);
+    }
+
+    #[test]
+    fn errors() {
+        let mut builder = LibBuilder::<Instr<LibId>>::new();
+        builder.routine("main", |_| {}).unwrap();
+        assert_eq!(
+            builder.routine("main", |_| unreachable!()).unwrap_err(),
+            LibBuilderError::DuplicateRoutine(s!("main"))
+        );
+
+        let mut unresolved = builder.clone();
+        unresolved
+            .routine("sub", |code| {
+                code.call("missing");
+            })
+            .unwrap();
+        assert_eq!(unresolved.finish(), Err(LibBuilderError::UnresolvedRoutine(s!("missing"))));
+
+        let mut unresolved = builder.clone();
+        unresolved
+            .routine("sub", |code| {
+                code.call_extern("missing");
+            })
+            .unwrap();
+        assert_eq!(unresolved.finish(), Err(LibBuilderError::UnresolvedSymbol(s!("missing"))));
+
+        builder
+            .routine("large", |code| {
+                for _ in 0..u16::MAX {
+                    code.push(CtrlInstr::Nop);
+                }
+            })
+            .unwrap();
+        assert_eq!(
+            builder.routine("beyond", |_| unreachable!()).unwrap_err(),
+            LibBuilderError::OffsetOverflow(s!("beyond"))
+        );
+        assert!(matches!(builder.finish(), Err(LibBuilderError::Assemble(_))));
+    }
+}
@@ -0,0 +1,319 @@
+// Reference rust implementation of AluVM (arithmetic logic unit virtual machine).
+// To find more on AluVM please check <https://aluvm.org>
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Designed in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+// Written in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+//
+// Copyright (C) 2021-2024 LNP/BP Standards Association, Switzerland.
+// Copyright (C) 2024-2025 Laboratories for Ubiquitous Deterministic Computing (UBIDECO),
+//                         Institute for Distributed and Cognitive Systems (InDCS), Switzerland.
+// Copyright (C) 2021-2025 Dr Maxim Orlovsky.
+// All rights under the above copyrights are reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use this file except
+// in compliance with the License. You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed under the License
+// is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express
+// or implied. See the License for the specific language governing permissions and limitations under
+// the License.
+
+//! Decoding of the legacy (2021-era) AluVM bytecode into the current instruction model.
+//!
+//! The legacy bytecode used a different opcode layout, kept the library references as indexes into
+//! the libs segment *after* the jump offset, and had a status-register model which doesn't exist
+//! anymore. The decoder reads the legacy code instruction by instruction, passing each opcode to
+//! the translators of the legacy ISA extensions, and assembles the translated instructions into a
+//! new library, fixing up the local jump offsets for the changed instruction lengths.
+//!
+//! Only the control flow subset is translated now; the other legacy extensions can be supported by
+//! adding a translator to [`TRANSLATORS`].
+
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+
+use super::{AssemblerError, Lib, LibId, LibsSeg, MarshallError};
+use crate::core::Site;
+use crate::isa::{Bytecode, CtrlInstr, GotoTarget, Instr, Instruction};
+
+/// Errors decoding legacy bytecode.
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum CompatError {
+    /// legacy instruction with opcode {opcode:#04X} at offset {offset:#06X} has no equivalent in
+    /// the current instruction set.
+    Untranslatable {
+        /// Offset of the instruction in the legacy code segment.
+        offset: u16,
+        /// Legacy opcode of the instruction.
+        opcode: u8,
+    },
+
+    /// legacy instruction at offset {0:#06X} is truncated by the end of the code segment.
+    CodeEof(u16),
+
+    /// legacy instruction at offset {0:#06X} refers to the library #{1}, which is absent from the
+    /// libs segment.
+    UnknownLib(u16, u8),
+
+    /// legacy instruction at offset {0:#06X} jumps to offset {1:#06X}, which is not an
+    /// instruction boundary.
+    InvalidJump(u16, u16),
+
+    /// Error assembling the translated library (see [`AssemblerError`] for the details).
+    #[from]
+    #[display(inner)]
+    Assemble(AssemblerError),
+}
+
+/// Reader of the legacy bytecode.
+///
+/// Legacy instructions are byte-aligned, and all multibyte values are little-endian.
+struct LegacyReader<'a> {
+    code: &'a [u8],
+    #[allow(dead_code)]
+    data: &'a [u8],
+    libs: &'a LibsSeg,
+    /// Offset of the instruction being read.
+    start: u16,
+    pos: usize,
+}
+
+impl<'a> LegacyReader<'a> {
+    fn new(code: &'a [u8], data: &'a [u8], libs: &'a LibsSeg) -> Self {
+        Self { code, data, libs, start: 0, pos: 0 }
+    }
+
+    fn is_eof(&self) -> bool { self.pos >= self.code.len() }
+
+    fn pos(&self) -> u16 { self.pos as u16 }
+
+    fn untranslatable(&self, opcode: u8) -> CompatError {
+        CompatError::Untranslatable { offset: self.start, opcode }
+    }
+
+    fn read_u8(&mut self) -> Result<u8, CompatError> {
+        let byte = *self
+            .code
+            .get(self.pos)
+            .ok_or(CompatError::CodeEof(self.start))?;
+        self.pos += 1;
+        Ok(byte)
+    }
+
+    fn read_u16(&mut self) -> Result<u16, CompatError> {
+        let lo = self.read_u8()?;
+        let hi = self.read_u8()?;
+        Ok(u16::from_le_bytes([lo, hi]))
+    }
+
+    /// Reads a library reference, encoded as an index into the libs segment.
+    fn read_lib(&mut self) -> Result<LibId, CompatError> {
+        let idx = self.read_u8()?;
+        self.libs
+            .iter()
+            .nth(idx as usize)
+            .copied()
+            .ok_or(CompatError::UnknownLib(self.start, idx))
+    }
+
+    /// Reads an external site, encoded as a code offset followed by a library reference.
+    fn read_site(&mut self) -> Result<Site<LibId>, CompatError> {
+        let offset = self.read_u16()?;
+        let prog_id = self.read_lib()?;
+        Ok(Site::new(prog_id, offset))
+    }
+}
+
+/// Translator of a legacy ISA extension.
+///
+/// Returns `Ok(None)` if the opcode doesn't belong to the extension. Local jumps of the produced
+/// instructions must refer to the offsets in the legacy code; they are fixed up afterwards.
+type Translator = fn(u8, &mut LegacyReader) -> Result<Option<Instr<LibId>>, CompatError>;
+
+/// Translators of the supported legacy ISA extensions, tried in order.
+const TRANSLATORS: &[Translator] = &[translate_ctrl];
+
+/// Legacy control flow opcodes.
+mod ctrl_op {
+    pub const FAIL: u8 = 0x00;
+    pub const SUCC: u8 = 0x01;
+    pub const JMP: u8 = 0x02;
+    pub const JIF: u8 = 0x03;
+    pub const ROUTINE: u8 = 0x04;
+    pub const CALL: u8 = 0x05;
+    pub const EXEC: u8 = 0x06;
+    pub const RET: u8 = 0x07;
+    pub const NOP: u8 = 0xFF;
+}
+
+/// Translates the legacy control flow instructions.
+///
+/// `fail`, `succ` and `jif` operated on the legacy `st0` status register, which was set by the
+/// instructions themselves and halted the program; the current `CK`/`CO` registers have different
+/// semantics, so these instructions are reported as untranslatable.
+fn translate_ctrl(
+    opcode: u8,
+    reader: &mut LegacyReader,
+) -> Result<Option<Instr<LibId>>, CompatError> {
+    let instr = match opcode {
+        ctrl_op::FAIL | ctrl_op::SUCC | ctrl_op::JIF => return Err(reader.untranslatable(opcode)),
+        ctrl_op::JMP => CtrlInstr::Jmp { pos: reader.read_u16()? },
+        ctrl_op::ROUTINE => CtrlInstr::Fn { pos: reader.read_u16()? },
+        ctrl_op::CALL => CtrlInstr::Call { site: reader.read_site()? },
+        ctrl_op::EXEC => CtrlInstr::Exec { site: reader.read_site()? },
+        ctrl_op::RET => CtrlInstr::Ret,
+        ctrl_op::NOP => CtrlInstr::Nop,
+        _ => return Ok(None),
+    };
+    Ok(Some(instr.into()))
+}
+
+impl Lib {
+    /// Decodes a library from the legacy (2021-era) bytecode.
+    ///
+    /// The `code`, `data` and `libs` are the segments of the legacy library. The instructions are
+    /// translated into the current instruction set one by one, the local jump targets are fixed up
+    /// for the changed instruction lengths, and the result is assembled into a new library (thus,
+    /// having a different id). External sites are kept as is, so the libraries called from the
+    /// translated one must be translated with the same code offsets.
+    ///
+    /// # Errors
+    ///
+    /// If the code contains a legacy instruction which has no equivalent in the current
+    /// instruction set or which is not supported yet, if an instruction is truncated or refers to
+    /// an unknown library, or if it jumps to the middle of an instruction.
+    pub fn from_legacy_bytecode(
+        code: &[u8],
+        data: &[u8],
+        libs: &LibsSeg,
+    ) -> Result<Lib, CompatError> {
+        let mut reader = LegacyReader::new(code, data, libs);
+        let mut instrs = Vec::new();
+        let mut offsets = Vec::new();
+        let mut positions = BTreeMap::new();
+        let mut new_pos = 0u16;
+        while !reader.is_eof() {
+            reader.start = reader.pos();
+            let opcode = reader.read_u8()?;
+            let mut instr = None;
+            for translate in TRANSLATORS {
+                instr = translate(opcode, &mut reader)?;
+                if instr.is_some() {
+                    break;
+                }
+            }
+            let instr = instr.ok_or_else(|| reader.untranslatable(opcode))?;
+            positions.insert(reader.start, new_pos);
+            offsets.push(reader.start);
+            new_pos = new_pos
+                .checked_add(instr.code_byte_len())
+                .ok_or(AssemblerError::Bytecode(MarshallError::CodeNotFittingSegment))?;
+            instrs.push(instr);
+        }
+        positions.insert(reader.pos(), new_pos);
+
+        for (instr, old_pos) in instrs.iter_mut().zip(offsets) {
+            // Legacy instructions have only absolute local jumps
+            if let GotoTarget::Absolute(pos) = instr.local_goto_pos() {
+                *pos = *positions
+                    .get(pos)
+                    .ok_or(CompatError::InvalidJump(old_pos, *pos))?;
+            }
+        }
+
+        Ok(Lib::assemble(&instrs)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![cfg_attr(coverage_nightly, coverage(off))]
+
+    use amplify::confinement::TinyOrdSet;
+
+    use super::*;
+    use crate::core::Status;
+    use crate::{LibSite, Vm};
+
+    fn lib_id(byte: u8) -> LibId { LibId::from([byte; 32]) }
+
+    fn translate(code: &[u8]) -> Result<Lib, CompatError> {
+        Lib::from_legacy_bytecode(code, &[], &none!())
+    }
+
+    #[test]
+    fn ctrl() {
+        // routine 4; ret; nop; ret
+        let lib = translate(&[0x04, 0x04, 0x00, 0x07, 0xFF, 0x07]).unwrap();
+        assert_eq!(lib.disassemble::<Instr<LibId>>().unwrap(), vec![
+            CtrlInstr::Fn { pos: 4 }.into(),
+            CtrlInstr::Ret.into(),
+            CtrlInstr::Nop.into(),
+            CtrlInstr::Ret.into(),
+        ]);
+
+        let lib_id = lib.lib_id();
+        let mut vm = Vm::<Instr<LibId>>::new();
+        let resolver = |id: LibId| if id == lib_id { Some(&lib) } else { None };
+        assert_eq!(vm.exec(LibSite::new(lib_id, 0), &(), resolver), Status::Ok);
+    }
+
+    #[test]
+    fn jumps_remapped() {
+        // nop; jmp 6; nop; nop; ret
+        let lib = translate(&[0xFF, 0x02, 0x06, 0x00, 0xFF, 0xFF, 0x07]).unwrap();
+        let code = lib.disassemble::<Instr<LibId>>().unwrap();
+        let nop_len = CtrlInstr::<LibId>::Nop.code_byte_len();
+        let jmp_len = CtrlInstr::<LibId>::Jmp { pos: 0 }.code_byte_len();
+        assert_eq!(code[1], CtrlInstr::Jmp { pos: 3 * nop_len + jmp_len }.into());
+    }
+
+    #[test]
+    fn external() {
+        let libs = TinyOrdSet::try_from_iter([lib_id(1), lib_id(2)]).unwrap();
+        // call 0x1234@#1; exec 0x0010@#0
+        let code = [0x05, 0x34, 0x12, 0x01, 0x06, 0x10, 0x00, 0x00];
+        let lib = Lib::from_legacy_bytecode(&code, &[], &libs).unwrap();
+        assert_eq!(lib.disassemble::<Instr<LibId>>().unwrap(), vec![
+            CtrlInstr::Call { site: Site::new(lib_id(2), 0x1234) }.into(),
+            CtrlInstr::Exec { site: Site::new(lib_id(1), 0x0010) }.into(),
+        ]);
+        assert_eq!(lib.libs(), &libs);
+    }
+
+    #[test]
+    fn untranslatable() {
+        for (code, offset, opcode) in [
+            (&[0xFF, 0x00][..], 1, 0x00),
+            (&[0x01], 0, 0x01),
+            (&[0xFF, 0x03, 0x00, 0x00], 1, 0x03),
+            // Legacy register instruction
+            (&[0x07, 0x08, 0x00], 1, 0x08),
+        ] {
+            assert_eq!(translate(code).unwrap_err(), CompatError::Untranslatable {
+                offset,
+                opcode
+            });
+        }
+    }
+
+    #[test]
+    fn invalid() {
+        assert_eq!(translate(&[0xFF, 0x02, 0x00]).unwrap_err(), CompatError::CodeEof(1));
+        assert_eq!(translate(&[0x05, 0x00, 0x00]).unwrap_err(), CompatError::CodeEof(0));
+        assert_eq!(
+            translate(&[0x06, 0x00, 0x00, 0x00]).unwrap_err(),
+            CompatError::UnknownLib(0, 0)
+        );
+        assert_eq!(
+            translate(&[0x02, 0x02, 0x00, 0x07]).unwrap_err(),
+            CompatError::InvalidJump(0, 2)
+        );
+        assert_eq!(translate(&[]).unwrap(), Lib::assemble::<Instr<LibId>>(&[]).unwrap());
+    }
+}
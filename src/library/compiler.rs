@@ -49,6 +49,18 @@ pub enum CompilerError<Isa: Instruction<LibId>> {
     InvalidLib(Isa, usize, u16, LibId),
 }
 
+/// Replaces the routine number with the routine code offset; returns `false` if there is no
+/// routine with this number.
+fn resolve_routine(goto_pos: &mut u16, routines: &[u16]) -> bool {
+    match routines.get(*goto_pos as usize) {
+        Some(pos) => {
+            *goto_pos = *pos;
+            true
+        }
+        None => false,
+    }
+}
+
 /// The compiled AluVM library containing information about the routines.
 pub struct CompiledLib {
     id: LibId,
@@ -81,11 +93,15 @@ impl CompiledLib {
         }
         let mut cursor = 0u16;
         for (no, instr) in code.iter_mut().enumerate() {
-            if let GotoTarget::Absolute(goto_pos) = instr.local_goto_pos() {
-                let Some(pos) = routines.get(*goto_pos as usize) else {
-                    return Err(CompilerError::InvalidRef(instr.clone(), no, cursor, routines));
-                };
-                *goto_pos = *pos;
+            let valid = match instr.local_goto_pos() {
+                GotoTarget::Absolute(goto_pos) => resolve_routine(goto_pos, &routines),
+                GotoTarget::Table(table) => table
+                    .iter_mut()
+                    .all(|goto_pos| resolve_routine(goto_pos, &routines)),
+                GotoTarget::None | GotoTarget::Relative(_) | GotoTarget::RelativeWide(_) => true,
+            };
+            if !valid {
+                return Err(CompilerError::InvalidRef(instr.clone(), no, cursor, routines));
             }
             let cloned_instr = instr.clone();
             if let Some(remote_pos) = instr.remote_goto_pos() {
@@ -0,0 +1,286 @@
+// Reference rust implementation of AluVM (arithmetic logic unit virtual machine).
+// To find more on AluVM please check <https://aluvm.org>
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Designed in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+// Written in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+//
+// Copyright (C) 2021-2024 LNP/BP Standards Association, Switzerland.
+// Copyright (C) 2024-2025 Laboratories for Ubiquitous Deterministic Computing (UBIDECO),
+//                         Institute for Distributed and Cognitive Systems (InDCS), Switzerland.
+// Copyright (C) 2021-2025 Dr Maxim Orlovsky.
+// All rights under the above copyrights are reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use this file except
+// in compliance with the License. You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed under the License
+// is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express
+// or implied. See the License for the specific language governing permissions and limitations under
+// the License.
+
+use std::io;
+
+use amplify::confinement::{SmallBlob, TinyOrdSet};
+
+use super::{Lib, LibId};
+use crate::isa::Instruction;
+use crate::{IsaId, ISA_ID_MAX_LEN};
+
+/// Magic bytes starting a library container produced by [`Lib::serialize_to`].
+pub const LIB_CONTAINER_MAGIC: [u8; 8] = *b"ALUVMLIB";
+
+/// Version of the library container format produced by [`Lib::serialize_to`].
+pub const LIB_CONTAINER_VERSION: u8 = 1;
+
+/// Errors reading a library container with [`Lib::deserialize_from`].
+#[derive(Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum LibContainerError {
+    /// I/O error reading the library container: {0}
+    #[from]
+    Io(io::Error),
+
+    /// library container data end unexpectedly.
+    Truncated,
+
+    /// data are not an AluVM library container.
+    InvalidMagic,
+
+    /// library container version {0} is not supported.
+    UnsupportedVersion(u8),
+
+    /// library container contains an invalid ISA extension identifier.
+    InvalidIsaId,
+
+    /// library requires ISA extension {0}, which is not supported by the instruction set.
+    UnsupportedIsa(IsaId),
+
+    /// library container has its {0} segment not in the canonical form.
+    NonCanonical(&'static str),
+}
+
+impl Lib {
+    /// Writes the library into a portable binary container.
+    ///
+    /// The container starts with [`LIB_CONTAINER_MAGIC`] and [`LIB_CONTAINER_VERSION`] bytes,
+    /// followed by the ISA extension identifiers, the libs, code and data segments, each prefixed
+    /// with its length (little-endian for the segments longer than 255 bytes).
+    pub fn serialize_to(&self, mut writer: impl io::Write) -> io::Result<()> {
+        writer.write_all(&LIB_CONTAINER_MAGIC)?;
+        writer.write_all(&[LIB_CONTAINER_VERSION])?;
+
+        writer.write_all(&[self.isae().len() as u8])?;
+        for isa in self.isae() {
+            writer.write_all(&[isa.len() as u8])?;
+            writer.write_all(isa.as_bytes())?;
+        }
+
+        writer.write_all(&[self.libs().len() as u8])?;
+        for lib_id in self.libs() {
+            writer.write_all(lib_id.as_slice())?;
+        }
+
+        for segment in [self.code(), self.data()] {
+            writer.write_all(&(segment.len() as u16).to_le_bytes())?;
+            writer.write_all(segment.as_slice())?;
+        }
+        Ok(())
+    }
+
+    /// Reads a library from a binary container produced by [`Lib::serialize_to`].
+    ///
+    /// # Errors
+    ///
+    /// If the data are not a library container of a supported version, are truncated or contain
+    /// non-canonical segments; also if the library requires ISA extensions not provided by `Isa`.
+    pub fn deserialize_from<Isa>(mut reader: impl io::Read) -> Result<Lib, LibContainerError>
+    where Isa: Instruction<LibId> {
+        let mut magic = [0u8; 8];
+        read_exact(&mut reader, &mut magic)?;
+        if magic != LIB_CONTAINER_MAGIC {
+            return Err(LibContainerError::InvalidMagic);
+        }
+        let version = read_u8(&mut reader)?;
+        if version != LIB_CONTAINER_VERSION {
+            return Err(LibContainerError::UnsupportedVersion(version));
+        }
+
+        let supported = Isa::isa_ext();
+        let mut isae = TinyOrdSet::new();
+        for _ in 0..read_u8(&mut reader)? {
+            let len = read_u8(&mut reader)? as usize;
+            if len > ISA_ID_MAX_LEN {
+                return Err(LibContainerError::InvalidIsaId);
+            }
+            let mut buf = [0u8; ISA_ID_MAX_LEN];
+            read_exact(&mut reader, &mut buf[..len])?;
+            let isa = core::str::from_utf8(&buf[..len])
+                .ok()
+                .and_then(|s| s.parse::<IsaId>().ok())
+                .ok_or(LibContainerError::InvalidIsaId)?;
+            if !supported.contains(&isa) {
+                return Err(LibContainerError::UnsupportedIsa(isa));
+            }
+            if isae.last().is_some_and(|last| *last >= isa) {
+                return Err(LibContainerError::NonCanonical("ISA extensions"));
+            }
+            isae.push(isa).expect("the number of items is read as u8");
+        }
+
+        let mut libs = TinyOrdSet::new();
+        for _ in 0..read_u8(&mut reader)? {
+            let mut id = [0u8; 32];
+            read_exact(&mut reader, &mut id)?;
+            let lib_id = LibId::from(id);
+            if libs.last().is_some_and(|last| *last >= lib_id) {
+                return Err(LibContainerError::NonCanonical("libs"));
+            }
+            libs.push(lib_id)
+                .expect("the number of items is read as u8");
+        }
+
+        let code = read_segment(&mut reader)?;
+        let data = read_segment(&mut reader)?;
+
+        Ok(Lib::with(isae.into(), code, data, libs))
+    }
+}
+
+fn read_exact(reader: &mut impl io::Read, buf: &mut [u8]) -> Result<(), LibContainerError> {
+    reader.read_exact(buf).map_err(|err| match err.kind() {
+        io::ErrorKind::UnexpectedEof => LibContainerError::Truncated,
+        _ => LibContainerError::Io(err),
+    })
+}
+
+fn read_u8(reader: &mut impl io::Read) -> Result<u8, LibContainerError> {
+    let mut buf = [0u8; 1];
+    read_exact(reader, &mut buf)?;
+    Ok(buf[0])
+}
+
+fn read_segment(reader: &mut impl io::Read) -> Result<SmallBlob, LibContainerError> {
+    let mut len = [0u8; 2];
+    read_exact(reader, &mut len)?;
+    let mut segment = vec![0u8; u16::from_le_bytes(len) as usize];
+    read_exact(reader, &mut segment)?;
+    Ok(SmallBlob::try_from(segment).expect("the segment length is read as u16"))
+}
+
+#[cfg(test)]
+mod test {
+    #![cfg_attr(coverage_nightly, coverage(off))]
+
+    use strict_encoding::StrictDumb;
+
+    use super::*;
+    use crate::core::Site;
+    use crate::isa::{CtrlInstr, Instr};
+
+    fn sample() -> Lib {
+        let code: [Instr<LibId>; 5] = [
+            CtrlInstr::Call { site: Site::new(LibId::from([2u8; 32]), 12) }.into(),
+            CtrlInstr::Exec { site: Site::new(LibId::from([1u8; 32]), 4) }.into(),
+            CtrlInstr::Call { site: Site::new(LibId::from([2u8; 32]), 0) }.into(),
+            CtrlInstr::Jmp { pos: 0 }.into(),
+            CtrlInstr::Stop.into(),
+        ];
+        Lib::assemble(&code).unwrap()
+    }
+
+    fn serialize(lib: &Lib) -> Vec<u8> {
+        let mut data = Vec::new();
+        lib.serialize_to(&mut data).unwrap();
+        data
+    }
+
+    #[test]
+    fn roundtrip() {
+        let lib = sample();
+        assert_eq!(lib.libs().len(), 2);
+        let data = serialize(&lib);
+        assert_eq!(&data[..10], b"ALUVMLIB\x01\x00");
+        let decoded = Lib::deserialize_from::<Instr<LibId>>(data.as_slice()).unwrap();
+        assert_eq!(decoded, lib);
+        assert_eq!(decoded.lib_id(), lib.lib_id());
+
+        let dumb = Lib::strict_dumb();
+        let decoded = Lib::deserialize_from::<Instr<LibId>>(serialize(&dumb).as_slice()).unwrap();
+        assert_eq!(decoded, dumb);
+    }
+
+    #[test]
+    fn truncated() {
+        let data = serialize(&sample());
+        for len in 0..data.len() {
+            assert!(
+                matches!(
+                    Lib::deserialize_from::<Instr<LibId>>(&data[..len]),
+                    Err(LibContainerError::Truncated)
+                ),
+                "{len}"
+            );
+        }
+    }
+
+    #[test]
+    fn unsupported_isa() {
+        let mut lib = sample();
+        *lib.isae_mut() = IsaId::canonical_set([IsaId::from("JMPX")]);
+        let err = Lib::deserialize_from::<Instr<LibId>>(serialize(&lib).as_slice()).unwrap_err();
+        assert!(
+            matches!(err, LibContainerError::UnsupportedIsa(ref isa) if isa.as_str() == "JMPX")
+        );
+        assert_eq!(
+            err.to_string(),
+            "library requires ISA extension JMPX, which is not supported by the instruction set."
+        );
+    }
+
+    #[test]
+    fn malformed() {
+        let data = serialize(&sample());
+
+        let mut wrong = data.clone();
+        wrong[0] = b'X';
+        assert!(matches!(
+            Lib::deserialize_from::<Instr<LibId>>(wrong.as_slice()),
+            Err(LibContainerError::InvalidMagic)
+        ));
+
+        let mut wrong = data.clone();
+        wrong[8] = 2;
+        assert!(matches!(
+            Lib::deserialize_from::<Instr<LibId>>(wrong.as_slice()),
+            Err(LibContainerError::UnsupportedVersion(2))
+        ));
+
+        let mut wrong = data[..9].to_vec();
+        wrong.extend([1, 17]);
+        wrong.extend([b'A'; 17]);
+        assert!(matches!(
+            Lib::deserialize_from::<Instr<LibId>>(wrong.as_slice()),
+            Err(LibContainerError::InvalidIsaId)
+        ));
+
+        let mut wrong = data[..9].to_vec();
+        wrong.extend([1, 2, b'a', b'b']);
+        assert!(matches!(
+            Lib::deserialize_from::<Instr<LibId>>(wrong.as_slice()),
+            Err(LibContainerError::InvalidIsaId)
+        ));
+
+        // Swap the two library ids in the libs segment
+        let mut wrong = data.clone();
+        wrong[11..43].fill(2);
+        wrong[43..75].fill(1);
+        assert!(matches!(
+            Lib::deserialize_from::<Instr<LibId>>(wrong.as_slice()),
+            Err(LibContainerError::NonCanonical("libs"))
+        ));
+    }
+}
@@ -0,0 +1,207 @@
+// Reference rust implementation of AluVM (arithmetic logic unit virtual machine).
+// To find more on AluVM please check <https://aluvm.org>
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Designed in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+// Written in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+//
+// Copyright (C) 2021-2024 LNP/BP Standards Association, Switzerland.
+// Copyright (C) 2024-2025 Laboratories for Ubiquitous Deterministic Computing (UBIDECO),
+//                         Institute for Distributed and Cognitive Systems (InDCS), Switzerland.
+// Copyright (C) 2021-2025 Dr Maxim Orlovsky.
+// All rights under the above copyrights are reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use this file except
+// in compliance with the License. You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed under the License
+// is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express
+// or implied. See the License for the specific language governing permissions and limitations under
+// the License.
+
+use alloc::vec::Vec;
+
+use amplify::confinement::TinyVec;
+use strict_encoding::{StrictDeserialize, StrictSerialize};
+
+use super::LibSite;
+use crate::core::Register;
+use crate::LIB_NAME_ALUVM;
+
+/// Descriptor of a routine which can be called by the host with [`crate::Vm::call_entry`].
+///
+/// Defines the calling convention of the routine: its entry site, and the ISA extension registers
+/// which are taken as the routine arguments and returned as its results. The registers are stored
+/// by their index in the [`Register::enumerate`] order, such that the descriptor doesn't depend on
+/// the instruction set and can be kept alongside the library, like [`crate::LibSymbols`].
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+#[derive(StrictType, StrictDumb, StrictEncode, StrictDecode)]
+#[strict_type(lib = LIB_NAME_ALUVM)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(rename_all = "camelCase"))]
+pub struct EntryPoint {
+    site: LibSite,
+    inputs: TinyVec<u8>,
+    outputs: TinyVec<u8>,
+}
+
+impl StrictSerialize for EntryPoint {}
+impl StrictDeserialize for EntryPoint {}
+
+impl EntryPoint {
+    /// Constructs a descriptor of the routine starting at the `site`, taking its arguments from
+    /// the `inputs` registers and returning the values of the `outputs` registers.
+    ///
+    /// # Errors
+    ///
+    /// If an input register is listed twice, if the number of inputs or outputs exceeds 255, or if
+    /// the register index doesn't fit a byte.
+    pub fn with<R: Register>(
+        site: LibSite,
+        inputs: impl IntoIterator<Item = R>,
+        outputs: impl IntoIterator<Item = R>,
+    ) -> Result<Self, EntryPointError> {
+        let inputs = Self::encode_regs(inputs)?;
+        for (no, index) in inputs.iter().enumerate() {
+            if inputs[..no].contains(index) {
+                return Err(EntryPointError::DuplicateInput(*index));
+            }
+        }
+        let outputs = Self::encode_regs(outputs)?;
+        Ok(Self { site, inputs, outputs })
+    }
+
+    fn encode_regs<R: Register>(
+        regs: impl IntoIterator<Item = R>,
+    ) -> Result<TinyVec<u8>, EntryPointError> {
+        let mut indexes = TinyVec::new();
+        for reg in regs {
+            let index = R::enumerate()
+                .position(|r| r == reg)
+                .and_then(|pos| u8::try_from(pos).ok())
+                .ok_or(EntryPointError::TooManyRegs)?;
+            indexes
+                .push(index)
+                .map_err(|_| EntryPointError::TooManyRegs)?;
+        }
+        Ok(indexes)
+    }
+
+    fn decode_regs<R: Register>(indexes: &[u8]) -> Result<Vec<R>, EntryPointError> {
+        indexes
+            .iter()
+            .map(|index| {
+                R::enumerate()
+                    .nth(*index as usize)
+                    .ok_or(EntryPointError::UnknownReg(*index))
+            })
+            .collect()
+    }
+
+    /// Returns the entry site of the routine.
+    #[inline]
+    pub fn site(&self) -> LibSite { self.site }
+
+    /// Returns the number of the routine arguments.
+    #[inline]
+    pub fn input_count(&self) -> usize { self.inputs.len() }
+
+    /// Returns the number of the routine results.
+    #[inline]
+    pub fn output_count(&self) -> usize { self.outputs.len() }
+
+    /// Returns the registers taking the routine arguments, in the order of the arguments.
+    ///
+    /// # Errors
+    ///
+    /// If the descriptor refers to a register index which is not known to the register set `R`
+    /// (which may be the case for a descriptor decoded from an untrusted source).
+    pub fn inputs<R: Register>(&self) -> Result<Vec<R>, EntryPointError> {
+        Self::decode_regs(&self.inputs)
+    }
+
+    /// Returns the registers holding the routine results, in the order of the results.
+    ///
+    /// # Errors
+    ///
+    /// If the descriptor refers to a register index which is not known to the register set `R`.
+    pub fn outputs<R: Register>(&self) -> Result<Vec<R>, EntryPointError> {
+        Self::decode_regs(&self.outputs)
+    }
+}
+
+/// Errors defining or resolving registers of an [`EntryPoint`].
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum EntryPointError {
+    /// input register with index {0} is listed more than once.
+    DuplicateInput(u8),
+
+    /// register with index {0} is not known to the instruction set.
+    UnknownReg(u8),
+
+    /// the number of entry point registers or a register index exceeds 255.
+    TooManyRegs,
+}
+
+#[cfg(test)]
+mod test {
+    #![cfg_attr(coverage_nightly, coverage(off))]
+
+    use alloc::vec;
+
+    use strict_encoding::StrictDumb;
+
+    use super::*;
+    use crate::isa::RegA;
+    use crate::LibId;
+
+    fn site() -> LibSite { LibSite::new(LibId::strict_dumb(), 4) }
+
+    #[test]
+    fn regs() {
+        let entry = EntryPoint::with(site(), [RegA::A3, RegA::A0], [RegA::A15]).unwrap();
+        assert_eq!(entry.site(), site());
+        assert_eq!(entry.input_count(), 2);
+        assert_eq!(entry.output_count(), 1);
+        assert_eq!(entry.inputs::<RegA>(), Ok(vec![RegA::A3, RegA::A0]));
+        assert_eq!(entry.outputs::<RegA>(), Ok(vec![RegA::A15]));
+
+        // Outputs may repeat the inputs
+        let entry = EntryPoint::with(site(), [RegA::A0, RegA::A1], [RegA::A0]).unwrap();
+        assert_eq!(entry.outputs::<RegA>(), Ok(vec![RegA::A0]));
+    }
+
+    #[test]
+    fn duplicate_input() {
+        assert_eq!(
+            EntryPoint::with(site(), [RegA::A1, RegA::A2, RegA::A1], []),
+            Err(EntryPointError::DuplicateInput(1))
+        );
+    }
+
+    #[test]
+    fn unknown_reg() {
+        let mut entry = EntryPoint::with(site(), [RegA::A1], [RegA::A2]).unwrap();
+        entry.outputs.push(16).unwrap();
+        assert_eq!(entry.inputs::<RegA>(), Ok(vec![RegA::A1]));
+        assert_eq!(entry.outputs::<RegA>(), Err(EntryPointError::UnknownReg(16)));
+    }
+
+    #[test]
+    fn too_many_regs() {
+        assert_eq!(
+            EntryPoint::with(site(), [], [RegA::A0; 256]),
+            Err(EntryPointError::TooManyRegs)
+        );
+    }
+
+    #[test]
+    fn strict_roundtrip() {
+        let entry = EntryPoint::with(site(), [RegA::A3, RegA::A0], [RegA::A15]).unwrap();
+        let data = entry.to_strict_serialized::<0xFFFF>().unwrap();
+        assert_eq!(EntryPoint::from_strict_serialized::<0xFFFF>(data).unwrap(), entry);
+    }
+}
@@ -22,13 +22,49 @@
 // or implied. See the License for the specific language governing permissions and limitations under
 // the License.
 
+use amplify::confinement::SmallBlob;
 use amplify::num::u3;
 #[cfg(feature = "log")]
 use baid64::DisplayBaid64;
 
-use super::{Lib, Marshaller};
+use super::{ExecHook, HookAction, Lib, Marshaller, NoHook};
+use crate::core::CALL_STACK_SIZE_MAX;
 use crate::isa::{Bytecode, BytecodeRead, ExecStep, Instruction};
-use crate::{Core, LibId, Site, SiteId};
+#[cfg(feature = "paranoid")]
+use crate::JumpFault;
+use crate::{
+    Core, FailureReason, IsaId, LibId, Site, SiteId, StepError, TerminationCause,
+    UnknownInstrPolicy,
+};
+
+/// Returns the complexity of the instruction execution by the core under the core complexity
+/// model.
+///
+/// Reserved instructions have the complexity defined by the ISA only under the
+/// [`UnknownInstrPolicy::Fail`] policy; otherwise they are accounted as trivial instructions.
+pub(super) fn exec_complexity<Instr, const CALL_STACK_SIZE: usize>(
+    instr: &Instr,
+    core: &Core<LibId, Instr::Core, CALL_STACK_SIZE>,
+) -> u64
+where
+    Instr: Instruction<LibId>,
+{
+    if instr.is_reserved() && core.unknown_instr() != UnknownInstrPolicy::Fail {
+        return core.complexity_model().trivial;
+    }
+    instr.complexity_in(core.complexity_model())
+}
+
+/// Error indicating that a library requires an ISA extension which is not supported by the
+/// instruction set used for its execution.
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Display, Error)]
+#[display("library {lib_id} requires ISA extension {isa} not supported by the instruction set")]
+pub struct UnsupportedIsaError {
+    /// Library requiring the extension.
+    pub lib_id: LibId,
+    /// Unsupported ISA extension.
+    pub isa: IsaId,
+}
 
 #[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Display)]
 pub enum Jump<Id: SiteId> {
@@ -40,58 +76,201 @@ pub enum Jump<Id: SiteId> {
 
     #[display(">{0}")]
     Next(Site<Id>),
+
+    #[display("!{0}")]
+    Break(Site<Id>),
+
+    #[display("^{0}")]
+    OutOfCode(Site<Id>),
+
+    #[display("#{0}")]
+    InstrLimit(Site<Id>),
+
+    #[display("*{0}")]
+    Hook(Site<Id>),
+}
+
+/// Library code executable by [`crate::Vm`], either decoded from the bytecode during the execution
+/// ([`Lib`]) or decoded ahead of it ([`super::PrecompiledLib`]).
+pub(crate) trait ExecCode<Instr: Instruction<LibId>> {
+    /// Executes the code starting at the entrypoint, stopping before the execution of an
+    /// instruction at an offset for which `is_break` returns `true` (see [`Lib::exec_until`]).
+    fn exec_code<const CALL_STACK_SIZE: usize>(
+        &self,
+        entrypoint: u16,
+        skip_first: bool,
+        core: &mut Core<LibId, Instr::Core, CALL_STACK_SIZE>,
+        context: &Instr::Context<'_>,
+        is_break: impl Fn(u16) -> bool,
+        hook: &mut impl ExecHook<LibId>,
+    ) -> Result<Jump<LibId>, UnsupportedIsaError>;
+}
+
+impl<Instr: Instruction<LibId>> ExecCode<Instr> for Lib {
+    fn exec_code<const CALL_STACK_SIZE: usize>(
+        &self,
+        entrypoint: u16,
+        skip_first: bool,
+        core: &mut Core<LibId, Instr::Core, CALL_STACK_SIZE>,
+        context: &Instr::Context<'_>,
+        is_break: impl Fn(u16) -> bool,
+        hook: &mut impl ExecHook<LibId>,
+    ) -> Result<Jump<LibId>, UnsupportedIsaError> {
+        self.exec_until::<Instr, CALL_STACK_SIZE>(
+            entrypoint, skip_first, core, context, is_break, hook,
+        )
+    }
 }
 
 impl Lib {
+    /// Checks whether all ISA extensions declared by the library are supported by the instruction
+    /// set (see [`Instruction::isa_ext`]).
+    ///
+    /// # Errors
+    ///
+    /// If the library declares an extension which is not supported. In case of several such
+    /// extensions, the first of them in the lexicographic order is reported.
+    pub fn check_isae<Instr>(&self) -> Result<(), UnsupportedIsaError>
+    where Instr: Instruction<LibId> {
+        match self.isae().unsupported_by(&Instr::isa_ext()) {
+            Some(isa) => Err(UnsupportedIsaError { lib_id: self.lib_id(), isa: isa.clone() }),
+            None => Ok(()),
+        }
+    }
+
     /// Execute library code starting at the entrypoint.
     ///
+    /// The core must have the default call stack size; cores with a different call stack size are
+    /// run by [`crate::Vm`].
+    ///
     /// # Returns
     ///
     /// Location for the external code jump, if any.
+    ///
+    /// # Errors
+    ///
+    /// If the library requires ISA extensions which are not supported by the instruction set (see
+    /// [`Lib::check_isae`]). In this case, no code is executed, and the core is left intact.
     pub fn exec<Instr>(
         &self,
         entrypoint: u16,
         skip_first: bool,
         core: &mut Core<LibId, Instr::Core>,
         context: &Instr::Context<'_>,
+    ) -> Result<Jump<LibId>, UnsupportedIsaError>
+    where
+        Instr: Instruction<LibId> + Bytecode<LibId>,
+    {
+        self.exec_with_hook::<Instr>(entrypoint, skip_first, core, context, &mut NoHook)
+    }
+
+    /// Execute library code starting at the entrypoint, invoking the instrumentation hook for
+    /// each executed instruction (see [`ExecHook`]).
+    ///
+    /// # Returns
+    ///
+    /// Location for the external code jump, if any, or the site at which the hook has terminated
+    /// the execution.
+    ///
+    /// # Errors
+    ///
+    /// If the library requires ISA extensions which are not supported by the instruction set (see
+    /// [`Lib::check_isae`]). In this case, no code is executed, and the core is left intact.
+    pub fn exec_with_hook<Instr>(
+        &self,
+        entrypoint: u16,
+        skip_first: bool,
+        core: &mut Core<LibId, Instr::Core>,
+        context: &Instr::Context<'_>,
+        hook: &mut impl ExecHook<LibId>,
+    ) -> Result<Jump<LibId>, UnsupportedIsaError>
+    where
+        Instr: Instruction<LibId> + Bytecode<LibId>,
+    {
+        self.exec_until::<Instr, { CALL_STACK_SIZE_MAX as usize }>(
+            entrypoint,
+            skip_first,
+            core,
+            context,
+            |_| false,
+            hook,
+        )
+    }
+
+    /// Execute library code starting at the entrypoint, stopping before the execution of an
+    /// instruction at an offset for which `is_break` returns `true`.
+    ///
+    /// # Returns
+    ///
+    /// Location for the external code jump, if any, or the breakpoint site.
+    ///
+    /// # Errors
+    ///
+    /// If the library requires ISA extensions which are not supported by the instruction set.
+    pub(crate) fn exec_until<Instr, const CALL_STACK_SIZE: usize>(
+        &self,
+        entrypoint: u16,
+        skip_first: bool,
+        core: &mut Core<LibId, Instr::Core, CALL_STACK_SIZE>,
+        context: &Instr::Context<'_>,
+        is_break: impl Fn(u16) -> bool,
+        hook: &mut impl ExecHook<LibId>,
+    ) -> Result<Jump<LibId>, UnsupportedIsaError>
+    where
+        Instr: Instruction<LibId> + Bytecode<LibId>,
+    {
+        self.check_isae::<Instr>()?;
+        Ok(self.exec_lib::<Instr, CALL_STACK_SIZE>(
+            entrypoint, skip_first, core, context, is_break, hook,
+        ))
+    }
+
+    fn exec_lib<Instr, const CALL_STACK_SIZE: usize>(
+        &self,
+        entrypoint: u16,
+        skip_first: bool,
+        core: &mut Core<LibId, Instr::Core, CALL_STACK_SIZE>,
+        context: &Instr::Context<'_>,
+        is_break: impl Fn(u16) -> bool,
+        hook: &mut impl ExecHook<LibId>,
     ) -> Jump<LibId>
     where
         Instr: Instruction<LibId> + Bytecode<LibId>,
     {
         #[cfg(feature = "log")]
-        let (m, w, d, g, r, y, z) = (
-            "\x1B[0;35m",
-            "\x1B[1;1m",
-            "\x1B[0;37;2m",
-            "\x1B[0;32m",
-            "\x1B[0;31m",
-            "\x1B[0;33m",
-            "\x1B[0m",
-        );
+        let (y, r, z) = ("\x1B[0;33m", "\x1B[0;31m", "\x1B[0m");
 
-        let mut marshaller = Marshaller::with(&self.code, &self.data, &self.libs);
+        let mut marshaller = Marshaller::with_unchecked(self.code(), self.data(), self.libs());
         let lib_id = self.lib_id();
 
-        #[cfg(feature = "log")]
-        let lib_mnemonic = lib_id.to_baid64_mnemonic();
-        #[cfg(feature = "log")]
-        let lib_ref = lib_mnemonic.split_at(5).0;
-
         if marshaller.seek(entrypoint).is_err() {
-            let _ = core.fail_ck();
+            let _ = core.fail_ck_with(FailureReason::InvalidJump);
             #[cfg(feature = "log")]
             eprintln!("jump to non-existing offset; halting, {y}CK{z} is set to {r}false{z}");
-            return Jump::Halt;
+            return Jump::OutOfCode(Site::new(lib_id, entrypoint));
         }
 
-        #[cfg(feature = "log")]
-        let mut ck0 = core.ck();
-        #[cfg(feature = "log")]
-        let mut co0 = core.co();
-
-        if marshaller.is_eof() {
+        // Boundary index is computed only once the first non-trivial control transfer happens
+        #[cfg(feature = "paranoid")]
+        let mut boundaries = None;
+        #[cfg(feature = "paranoid")]
+        let mut is_boundary = |pos: u16| {
+            boundaries
+                .get_or_insert_with(|| self.boundary_index::<Instr>())
+                .is_boundary(pos)
+        };
+        #[cfg(not(feature = "paranoid"))]
+        let mut is_boundary = |_: u16| true;
+        #[cfg(feature = "paranoid")]
+        if entrypoint != 0 && !is_boundary(entrypoint) {
+            core.fail_jump(JumpFault { source: None, target: Site::new(lib_id, entrypoint) });
+            #[cfg(feature = "log")]
+            eprintln!(
+                "entering in the middle of an instruction; halting, {y}CK{z} is set to {r}false{z}"
+            );
             return Jump::Halt;
         }
+
         // Skip instruction if required
         if skip_first {
             if Instr::decode_instr(&mut marshaller).is_err() {
@@ -102,6 +281,7 @@ impl Lib {
                         "; unable to decode instruction at byte pos {byte:06X}.h, bit pos {bit}",
                     );
                 }
+                core.terminate_with(TerminationCause::EndOfCode);
                 return Jump::Halt;
             };
             let next_pos = marshaller.offset();
@@ -109,164 +289,549 @@ impl Lib {
             #[cfg(feature = "log")]
             eprintln!("; return to the caller offset {:06X}.h", next_pos.0);
         }
+        if marshaller.is_eof() {
+            core.terminate_with(TerminationCause::EndOfCode);
+            return Jump::Halt;
+        }
 
-        while !marshaller.is_eof() {
-            let pos = marshaller.pos();
-
-            let Ok(instr) = Instr::decode_instr(&mut marshaller) else {
-                #[cfg(feature = "log")]
-                {
-                    let (byte, bit) = marshaller.offset();
-                    eprintln!(
-                        "unable to decode instruction at byte pos {byte:06X}.h, bit pos {bit}",
-                    );
-                }
-                return Jump::Halt;
+        #[cfg(any(feature = "paranoid", debug_assertions))]
+        let mut held = core.check_invariants().is_ok();
+        loop {
+            #[cfg(any(feature = "paranoid", debug_assertions))]
+            {
+                held = core.assert_invariants(held);
+            }
+            let site = Site::new(lib_id, marshaller.pos());
+            let step = match self.exec_instr::<Instr, CALL_STACK_SIZE>(
+                &mut marshaller,
+                core,
+                context,
+                &is_break,
+                hook,
+            ) {
+                InstrOutcome::Executed(step) => step,
+                InstrOutcome::Break => return Jump::Break(site),
+                InstrOutcome::Unwound(Some(site)) => return Jump::Next(site),
+                InstrOutcome::InstrLimit => return Jump::InstrLimit(site),
+                InstrOutcome::Hook => return Jump::Hook(site),
+                InstrOutcome::Unwound(None)
+                | InstrOutcome::Undecodable
+                | InstrOutcome::ComplexityOverflow
+                | InstrOutcome::JumpOverflow => return Jump::Halt,
             };
+            if let Some(jump) = self.advance::<Instr, CALL_STACK_SIZE>(
+                &mut marshaller,
+                site.offset,
+                step,
+                core,
+                &mut is_boundary,
+            ) {
+                return jump;
+            }
+        }
+    }
 
+    /// Executes a single instruction at the marshaller position, leaving the marshaller right
+    /// after the instruction.
+    ///
+    /// This is the part of the program execution shared by [`Lib::exec`] and [`Lib::exec_step`]:
+    /// the instruction is decoded, executed and accounted in the `CI`, `CA` and `CY` registers,
+    /// and a halting [`ExecStep::Stop`] or [`ExecStep::Fail`] is applied to the core.
+    fn exec_instr<Instr, const CALL_STACK_SIZE: usize>(
+        &self,
+        marshaller: &mut LibReader,
+        core: &mut Core<LibId, Instr::Core, CALL_STACK_SIZE>,
+        context: &Instr::Context<'_>,
+        is_break: impl Fn(u16) -> bool,
+        hook: &mut impl ExecHook<LibId>,
+    ) -> InstrOutcome
+    where
+        Instr: Instruction<LibId> + Bytecode<LibId>,
+    {
+        #[cfg(feature = "log")]
+        let (m, w, d, g, r, y, z) = (
+            "\x1B[0;35m",
+            "\x1B[1;1m",
+            "\x1B[0;37;2m",
+            "\x1B[0;32m",
+            "\x1B[0;31m",
+            "\x1B[0;33m",
+            "\x1B[0m",
+        );
+
+        let lib_id = self.lib_id();
+
+        #[cfg(feature = "log")]
+        let lib_mnemonic = lib_id.to_baid64_mnemonic();
+        #[cfg(feature = "log")]
+        let lib_ref = lib_mnemonic.split_at(5).0;
+
+        if let Some(depth) = core.exhausted_frame() {
             #[cfg(feature = "log")]
-            let mut prev = bmap![];
+            eprintln!(
+                "call frame complexity budget exhausted; {y}CK{z} is set to {r}fail{z}, returning \
+                 to the caller"
+            );
+            core.set_failure(FailureReason::BudgetExhausted);
+            let site = core.unwind_cs(depth, lib_id);
+            if site.is_none() {
+                core.terminate_with(TerminationCause::ComplexityExceeded);
+            }
+            return InstrOutcome::Unwound(site);
+        }
 
+        let pos = marshaller.pos();
+        let site = Site::new(lib_id, pos);
+        if is_break(pos) {
             #[cfg(feature = "log")]
-            // Stupid compiler can't reason between `cfg` blocks
-            #[allow(unused_assignments)]
-            let mut src_empty = true;
+            eprintln!("site {m}{}@{pos:06}:{z} {y}breakpoint{z}", lib_ref);
+            return InstrOutcome::Break;
+        }
+
+        let Ok(instr) = Instr::decode_instr(marshaller) else {
             #[cfg(feature = "log")]
             {
-                for reg in instr.dst_regs() {
-                    prev.insert(reg, core.get(reg));
-                }
-                eprint!("site {m}{}@{pos:06}:{z} {: <32}; ", lib_ref, instr.to_string());
-                let src_regs = instr.src_regs();
-                src_empty = src_regs.is_empty();
-                let mut iter = src_regs.into_iter().peekable();
-                while let Some(reg) = iter.next() {
-                    eprint!("{d}{reg}{z} ");
-                    if let Some(val) = core.get(reg) {
-                        eprint!("{w}{}{z}", val);
-                    } else {
-                        eprint!("{d}~{z}");
-                    }
-                    if iter.peek().is_some() {
-                        eprint!(", ");
-                    }
-                }
+                let (byte, bit) = marshaller.offset();
+                eprintln!("unable to decode instruction at byte pos {byte:06X}.h, bit pos {bit}",);
             }
+            core.terminate_with(TerminationCause::EndOfCode);
+            return InstrOutcome::Undecodable;
+        };
 
-            let next = instr.exec(Site::new(lib_id, pos), core, context);
+        if hook.before_instr(site, &instr) == HookAction::Abort {
+            #[cfg(feature = "log")]
+            eprintln!("site {m}{}@{pos:06}:{z} {y}terminated by the hook{z}", lib_ref);
+            return InstrOutcome::Hook;
+        }
 
+        if !core.acc_instr() {
+            let _ = core.fail_ck_with(FailureReason::InstrLimit);
             #[cfg(feature = "log")]
-            {
-                if !src_empty {
-                    if !prev.is_empty() {
-                        eprint!(" => ");
-                    } else if ck0 != core.ck() || co0 != core.co() || next != ExecStep::Next {
-                        eprint!("; ");
-                    }
-                }
+            eprintln!(
+                "site {m}{}@{pos:06}:{z} instruction limit reached: unconditionally halting; \
+                 {y}CK{z} is set to {r}fail{z}",
+                lib_ref
+            );
+            return InstrOutcome::InstrLimit;
+        }
 
-                let mut iter = instr.dst_regs().into_iter().peekable();
-                while let Some(reg) = iter.next() {
-                    eprint!("{g}{reg}{z} ");
-                    if let Some(val) = prev.get(&reg).unwrap() {
-                        eprint!("{y}{}{z}", val);
-                    } else {
-                        eprint!("{d}~{z}");
-                    }
-                    eprint!(" -> ");
-                    if let Some(val) = core.get(reg) {
-                        eprint!("{y}{}{z}", val);
-                    } else {
-                        eprint!("{d}~{z}");
-                    }
-                    if iter.peek().is_some() {
-                        eprint!(", ");
-                    }
+        #[cfg(feature = "log")]
+        let (ck0, co0) = (core.ck(), core.co());
+        #[cfg(feature = "log")]
+        let mut prev = bmap![];
+
+        #[cfg(feature = "log")]
+        // Stupid compiler can't reason between `cfg` blocks
+        #[allow(unused_assignments)]
+        let mut src_empty = true;
+        #[cfg(feature = "log")]
+        {
+            for reg in instr.dst_regs() {
+                prev.insert(reg, core.get(reg));
+            }
+            eprint!("site {m}{}@{pos:06}:{z} {: <32}; ", lib_ref, instr.to_string());
+            let src_regs = instr.src_regs();
+            src_empty = src_regs.is_empty();
+            let mut iter = src_regs.into_iter().peekable();
+            while let Some(reg) = iter.next() {
+                eprint!("{d}{reg}{z} ");
+                if let Some(val) = core.get(reg) {
+                    eprint!("{w}{}{z}", val);
+                } else {
+                    eprint!("{d}~{z}");
                 }
-                if !prev.is_empty() && (ck0 != core.ck() || co0 != core.co()) {
+                if iter.peek().is_some() {
                     eprint!(", ");
                 }
-                if ck0 != core.ck() {
-                    let p = if ck0.is_ok() { g } else { r };
-                    let c = if core.ck().is_ok() { g } else { r };
-                    eprint!("{y}CK{z} {p}{ck0}{z} -> {c}{}{z}", core.ck());
+            }
+        }
+
+        let next = instr.exec(site, core, context);
+
+        #[cfg(feature = "log")]
+        {
+            if !src_empty {
+                if !prev.is_empty() {
+                    eprint!(" => ");
+                } else if ck0 != core.ck() || co0 != core.co() || next != ExecStep::Next {
+                    eprint!("; ");
                 }
-                if ck0 != core.ck() && co0 != core.co() {
-                    eprint!(", ");
+            }
+
+            let mut iter = instr.dst_regs().into_iter().peekable();
+            while let Some(reg) = iter.next() {
+                eprint!("{g}{reg}{z} ");
+                if let Some(val) = prev.get(&reg).unwrap() {
+                    eprint!("{y}{}{z}", val);
+                } else {
+                    eprint!("{d}~{z}");
                 }
-                if co0 != core.co() {
-                    let p = if co0.is_ok() { g } else { r };
-                    let c = if core.co().is_ok() { g } else { r };
-                    eprint!("{y}CO{z} {p}{co0}{z} -> {c}{}{z}", core.co());
+                eprint!(" -> ");
+                if let Some(val) = core.get(reg) {
+                    eprint!("{y}{}{z}", val);
+                } else {
+                    eprint!("{d}~{z}");
                 }
-                if (!prev.is_empty() || ck0 != core.ck() || co0 != core.co())
-                    && next != ExecStep::Next
-                {
+                if iter.peek().is_some() {
                     eprint!(", ");
                 }
-
-                ck0 = core.ck();
-                co0 = core.co();
             }
+            if !prev.is_empty() && (ck0 != core.ck() || co0 != core.co()) {
+                eprint!(", ");
+            }
+            if ck0 != core.ck() {
+                let p = if ck0.is_ok() { g } else { r };
+                let c = if core.ck().is_ok() { g } else { r };
+                eprint!("{y}CK{z} {p}{ck0}{z} -> {c}{}{z}", core.ck());
+            }
+            if ck0 != core.ck() && co0 != core.co() {
+                eprint!(", ");
+            }
+            if co0 != core.co() {
+                let p = if co0.is_ok() { g } else { r };
+                let c = if core.co().is_ok() { g } else { r };
+                eprint!("{y}CO{z} {p}{co0}{z} -> {c}{}{z}", core.co());
+            }
+            if (!prev.is_empty() || ck0 != core.ck() || co0 != core.co()) && next != ExecStep::Next
+            {
+                eprint!(", ");
+            }
+        }
 
-            if !core.acc_complexity(instr.complexity()) {
-                let _ = core.fail_ck();
-                #[cfg(feature = "log")]
-                {
-                    if !src_empty || !prev.is_empty() {
-                        eprint!(", ");
-                    }
-                    eprintln!("halting, complexity overflow");
+        #[cfg(feature = "log")]
+        let warned = core.cw_site().is_some();
+        let complexity = exec_complexity(&instr, core);
+        let jumped = matches!(next, ExecStep::Jump(_));
+        core.record_profile(site, instr.opcode_byte(), complexity, jumped);
+        let within_limit = core.acc_complexity_at(site, complexity);
+        #[cfg(feature = "log")]
+        if !warned && core.cw_site().is_some() {
+            eprint!("{y}complexity warning limit crossed{z}; ");
+        }
+        if !within_limit {
+            let _ = core.fail_ck_with(FailureReason::ComplexityLimit);
+            #[cfg(feature = "log")]
+            {
+                if !src_empty || !prev.is_empty() {
+                    eprint!(", ");
                 }
-                return Jump::Halt;
+                eprintln!("halting, complexity overflow");
             }
-            match next {
-                ExecStep::Stop => {
-                    return Jump::Halt;
-                }
-                ExecStep::Fail => {
+            core.terminate_with(TerminationCause::ComplexityExceeded);
+            return InstrOutcome::ComplexityOverflow;
+        }
+        if matches!(next, ExecStep::Jump(_) | ExecStep::Call(_) | ExecStep::Ret(_))
+            && !core.acc_jump()
+        {
+            #[cfg(feature = "log")]
+            eprint!("{y}CY{z} overflow: {y}CK{z} {g}success{z} -> {r}fail{z}");
+            if core.fail_ck_with(FailureReason::JumpLimit) {
+                #[cfg(feature = "log")]
+                eprintln!(", {y}CH{z} is {g}true{z}: halting");
+                core.terminate_with(TerminationCause::Halted { site });
+                return InstrOutcome::JumpOverflow;
+            }
+            #[cfg(feature = "log")]
+            eprint!(", {y}CH{z} is {r}false{z}: continuing; ");
+        }
+        if hook.after_instr(site, &instr, core) == HookAction::Abort {
+            #[cfg(feature = "log")]
+            eprintln!("{y}terminated by the hook{z}");
+            return InstrOutcome::Hook;
+        }
+        match next {
+            ExecStep::Stop => core.terminate(),
+            ExecStep::Fail => {
+                #[cfg(feature = "log")]
+                eprint!("{y}CK{z} {g}success{z} -> {r}fail{z}");
+                if core.fail_ck() {
                     #[cfg(feature = "log")]
-                    eprint!("{y}CK{z} {g}success{z} -> {r}fail{z}");
-                    if core.fail_ck() {
-                        #[cfg(feature = "log")]
-                        eprintln!(", {y}CH{z} is {g}true{z}: halting");
-                        return Jump::Halt;
-                    }
+                    eprintln!(", {y}CH{z} is {g}true{z}: halting");
+                    core.terminate_with(TerminationCause::Halted { site });
+                } else {
                     #[cfg(feature = "log")]
                     eprintln!(", {y}CH{z} is {r}false{z}: continuing");
-                    continue;
                 }
-                ExecStep::Next => {
-                    #[cfg(feature = "log")]
-                    eprintln!();
-                    continue;
-                }
-                ExecStep::Jump(pos) => {
-                    #[cfg(feature = "log")]
-                    eprintln!("{d}jumping to{z} {m}{pos:06}{z}");
-                    if marshaller.seek(pos).is_err() {
-                        let _ = core.fail_ck();
-                        #[cfg(feature = "log")]
-                        eprintln!(
-                            "jump to non-existing offset: unconditionally halting; {y}CK{z} is \
-                             set to {r}fail{z}"
-                        );
-                        return Jump::Halt;
-                    }
-                }
-                ExecStep::Call(site) => {
+            }
+            _ => {}
+        }
+        InstrOutcome::Executed(next)
+    }
+
+    /// Moves the marshaller to the instruction executed after the instruction at the offset `pos`
+    /// has produced the `step`, which has already been applied to the core by
+    /// [`Self::exec_instr`]. For the steps proceeding to the next instruction, the marshaller
+    /// must be positioned right after the instruction.
+    ///
+    /// Moving past the end of the code, either by proceeding from the last instruction or by
+    /// jumping right past it, terminates the program with [`TerminationCause::EndOfCode`].
+    ///
+    /// # Returns
+    ///
+    /// `None` if the execution continues with the instruction at the marshaller position, or the
+    /// control transfer out of the library code otherwise.
+    fn advance<Instr, const CALL_STACK_SIZE: usize>(
+        &self,
+        marshaller: &mut LibReader,
+        pos: u16,
+        step: ExecStep<Site<LibId>>,
+        core: &mut Core<LibId, Instr::Core, CALL_STACK_SIZE>,
+        is_boundary: &mut impl FnMut(u16) -> bool,
+    ) -> Option<Jump<LibId>>
+    where
+        Instr: Instruction<LibId> + Bytecode<LibId>,
+    {
+        #[cfg(feature = "log")]
+        let (m, d, r, y, z) = ("\x1B[0;35m", "\x1B[0;37;2m", "\x1B[0;31m", "\x1B[0;33m", "\x1B[0m");
+        #[cfg(not(feature = "paranoid"))]
+        let _ = (pos, &is_boundary);
+
+        match step {
+            ExecStep::Stop => return Some(Jump::Halt),
+            ExecStep::Fail if core.ch() => return Some(Jump::Halt),
+            ExecStep::Fail => {}
+            ExecStep::Next => {
+                #[cfg(feature = "log")]
+                eprintln!();
+            }
+            ExecStep::Jump(pos) if pos as usize == self.code().len() => {
+                #[cfg(feature = "log")]
+                eprintln!("{d}jumping to{z} {m}{pos:06}{z}");
+                // Jumping right past the last instruction is the same as falling off the end
+                core.terminate_with(TerminationCause::EndOfCode);
+                return Some(Jump::Halt);
+            }
+            ExecStep::Jump(target) => {
+                #[cfg(feature = "log")]
+                eprintln!("{d}jumping to{z} {m}{target:06}{z}");
+                let lib_id = self.lib_id();
+                #[cfg(feature = "paranoid")]
+                if (target as usize) < self.code().len() && !is_boundary(target) {
+                    core.fail_jump(JumpFault {
+                        source: Some(Site::new(lib_id, pos)),
+                        target: Site::new(lib_id, target),
+                    });
                     #[cfg(feature = "log")]
-                    eprintln!("{d}calling{z} {m}{site}{z}");
-                    return Jump::Instr(site);
+                    eprintln!(
+                        "jump to the middle of an instruction: unconditionally halting; {y}CK{z} \
+                         is set to {r}fail{z}"
+                    );
+                    return Some(Jump::Halt);
                 }
-                ExecStep::Ret(site) => {
+                if marshaller.seek(target).is_err() {
+                    let _ = core.fail_ck_with(FailureReason::InvalidJump);
                     #[cfg(feature = "log")]
-                    eprintln!("{d}returning to{z} {m}{site}{z}");
-                    return Jump::Next(site);
+                    eprintln!(
+                        "jump to non-existing offset: unconditionally halting; {y}CK{z} is set to \
+                         {r}fail{z}"
+                    );
+                    return Some(Jump::OutOfCode(Site::new(lib_id, target)));
                 }
             }
+            ExecStep::Call(site) => {
+                #[cfg(feature = "log")]
+                eprintln!("{d}calling{z} {m}{site}{z}");
+                return Some(Jump::Instr(site));
+            }
+            ExecStep::Ret(site) => {
+                #[cfg(feature = "log")]
+                eprintln!("{d}returning to{z} {m}{site}{z}");
+                return Some(Jump::Next(site));
+            }
+        }
+        if marshaller.is_eof() {
+            core.terminate_with(TerminationCause::EndOfCode);
+            return Some(Jump::Halt);
+        }
+        None
+    }
+
+    /// Executes a single instruction located at the offset `pos`, accounting its complexity and
+    /// failures in the same way as [`Lib::exec`].
+    ///
+    /// The `is_boundary` check is applied to the offset by the `paranoid` runtime, unless it is the
+    /// start of the code.
+    #[cfg_attr(not(feature = "paranoid"), allow(unused_variables))]
+    pub(crate) fn exec_step<Instr, const CALL_STACK_SIZE: usize>(
+        &self,
+        pos: u16,
+        core: &mut Core<LibId, Instr::Core, CALL_STACK_SIZE>,
+        context: &Instr::Context<'_>,
+        is_boundary: &mut impl FnMut(u16) -> bool,
+    ) -> Result<ExecStep<Site<LibId>>, StepError>
+    where
+        Instr: Instruction<LibId> + Bytecode<LibId>,
+    {
+        let site = Site::new(self.lib_id(), pos);
+        if self.check_isae::<Instr>().is_err() {
+            let _ = core.fail_ck_with(FailureReason::Vm);
+            core.terminate_with(TerminationCause::Aborted);
+            return Err(StepError::UnsupportedIsa(site.into()));
+        }
+        let mut marshaller = Marshaller::with_unchecked(self.code(), self.data(), self.libs());
+        if marshaller.seek(pos).is_err() {
+            let _ = core.fail_ck_with(FailureReason::InvalidJump);
+            core.terminate_with(TerminationCause::Aborted);
+            return Err(StepError::OutOfCode(site.into()));
+        }
+        #[cfg(feature = "paranoid")]
+        if pos != 0 && !is_boundary(pos) {
+            core.fail_jump(JumpFault { source: None, target: site });
+            return Err(StepError::JumpFault(site.into()));
+        }
+
+        match self.exec_instr::<Instr, CALL_STACK_SIZE>(
+            &mut marshaller,
+            core,
+            context,
+            |_| false,
+            &mut NoHook,
+        ) {
+            InstrOutcome::Executed(step) => Ok(step),
+            InstrOutcome::Unwound(Some(site)) => Ok(ExecStep::Ret(site)),
+            InstrOutcome::Unwound(None) => Ok(ExecStep::Stop),
+            InstrOutcome::Undecodable => Err(StepError::Decode(site.into())),
+            InstrOutcome::InstrLimit => {
+                core.terminate_with(TerminationCause::Aborted);
+                Err(StepError::InstrLimit(site.into()))
+            }
+            InstrOutcome::ComplexityOverflow => Err(StepError::ComplexityOverflow(site.into())),
+            InstrOutcome::JumpOverflow => Err(StepError::JumpOverflow(site.into())),
+            InstrOutcome::Break | InstrOutcome::Hook => {
+                unreachable!("single steps have neither breakpoints nor hooks")
+            }
+        }
+    }
+
+    /// Resolves the offset of the instruction executed after the instruction at the offset `pos`
+    /// has produced the `step`, in the same way as [`Lib::exec`], terminating the program if the
+    /// execution moves past the end of the code.
+    ///
+    /// # Errors
+    ///
+    /// With the control transfer out of the library code, if the execution doesn't continue in
+    /// this library.
+    pub(crate) fn next_pos<Instr, const CALL_STACK_SIZE: usize>(
+        &self,
+        pos: u16,
+        step: ExecStep<Site<LibId>>,
+        core: &mut Core<LibId, Instr::Core, CALL_STACK_SIZE>,
+        is_boundary: &mut impl FnMut(u16) -> bool,
+    ) -> Result<u16, Jump<LibId>>
+    where
+        Instr: Instruction<LibId> + Bytecode<LibId>,
+    {
+        let mut marshaller = Marshaller::with_unchecked(self.code(), self.data(), self.libs());
+        if matches!(step, ExecStep::Next | ExecStep::Fail)
+            && (marshaller.seek(pos).is_err() || Instr::decode_instr(&mut marshaller).is_err())
+        {
+            core.terminate_with(TerminationCause::EndOfCode);
+            return Err(Jump::Halt);
+        }
+        match self.advance::<Instr, CALL_STACK_SIZE>(&mut marshaller, pos, step, core, is_boundary)
+        {
+            None => Ok(marshaller.pos()),
+            Some(jump) => Err(jump),
         }
+    }
+}
+
+/// Reader of the library code used by the program execution.
+type LibReader<'lib> = Marshaller<'lib, &'lib SmallBlob, &'lib SmallBlob>;
+
+/// Outcome of the execution of a single instruction by [`Lib::exec_instr`].
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+enum InstrOutcome {
+    /// The instruction has been executed, producing the step. If the step halts the program, the
+    /// core is already terminated.
+    Executed(ExecStep<Site<LibId>>),
+
+    /// The instruction is at a breakpoint and has not been executed.
+    Break,
+
+    /// The complexity budget of a call stack frame is exhausted, and the frame is unwound to the
+    /// returned site, or the program halts if the call stack is empty.
+    Unwound(Option<Site<LibId>>),
+
+    /// The instruction can't be decoded, and the program halts.
+    Undecodable,
+
+    /// The instruction limit is reached; `CK` is set to a failure.
+    InstrLimit,
+
+    /// The complexity limit is exceeded, and the program halts.
+    ComplexityOverflow,
+
+    /// The jump limit is exceeded while `CH` is set, and the program halts.
+    JumpOverflow,
+
+    /// The instrumentation hook has terminated the execution.
+    Hook,
+}
+
+#[cfg(test)]
+mod test {
+    #![cfg_attr(coverage_nightly, coverage(off))]
+
+    use super::*;
+    use crate::isa::{CtrlInstr, Instr};
+    use crate::regs::Status;
+    use crate::{CoreConfig, LibSite, Vm};
+
+    /// Library jumping to the middle of the `fn` instruction. Constructed from raw segments, as
+    /// the static validation would reject it.
+    fn patched_lib() -> Lib {
+        let code: [Instr<LibId>; 3] = [
+            CtrlInstr::Jmp { pos: 3 }.into(),
+            CtrlInstr::Fn { pos: 0x0101 }.into(),
+            CtrlInstr::Stop.into(),
+        ];
+        let mut lib = Lib::assemble(&code).unwrap();
+        lib.code_mut()[1] = 4;
+        lib
+    }
+
+    fn run(offset: u16) -> Vm<Instr<LibId>> {
+        let lib = patched_lib();
+        let mut vm = Vm::<Instr<LibId>>::with(CoreConfig::default(), ());
+        vm.exec(LibSite::new(lib.lib_id(), offset), &(), |_| Some(&lib));
+        vm
+    }
+
+    #[test]
+    #[cfg(feature = "paranoid")]
+    fn paranoid_jump() {
+        let lib_id = patched_lib().lib_id();
+        let vm = run(0);
+        assert_eq!(vm.core.ck(), Status::Fail);
+        assert_eq!(
+            vm.core.jump_fault(),
+            Some(JumpFault {
+                source: Some(Site::new(lib_id, 0)),
+                target: Site::new(lib_id, 4)
+            })
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "paranoid")]
+    fn paranoid_entry() {
+        let lib_id = patched_lib().lib_id();
+        let vm = run(1);
+        assert_eq!(vm.core.ck(), Status::Fail);
+        assert_eq!(
+            vm.core.jump_fault(),
+            Some(JumpFault { source: None, target: Site::new(lib_id, 1) })
+        );
+        assert_eq!(run(3).core.jump_fault(), None);
+    }
 
-        Jump::Halt
+    #[test]
+    #[cfg(not(feature = "paranoid"))]
+    fn unchecked_jump() {
+        // Without the check, the execution continues decoding from the middle of `fn`
+        let vm = run(0);
+        assert_eq!(vm.core.ck(), Status::Ok);
+        assert_eq!(vm.core.jump_fault(), None);
     }
 }
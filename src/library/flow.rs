@@ -0,0 +1,499 @@
+// Reference rust implementation of AluVM (arithmetic logic unit virtual machine).
+// To find more on AluVM please check <https://aluvm.org>
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Designed in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+// Written in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+//
+// Copyright (C) 2021-2024 LNP/BP Standards Association, Switzerland.
+// Copyright (C) 2024-2025 Laboratories for Ubiquitous Deterministic Computing (UBIDECO),
+//                         Institute for Distributed and Cognitive Systems (InDCS), Switzerland.
+// Copyright (C) 2021-2025 Dr Maxim Orlovsky.
+// All rights under the above copyrights are reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use this file except
+// in compliance with the License. You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed under the License
+// is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express
+// or implied. See the License for the specific language governing permissions and limitations under
+// the License.
+
+use alloc::collections::BTreeSet;
+use alloc::vec::Vec;
+
+use super::{Lib, LibId, Marshaller};
+use crate::core::Site;
+use crate::isa::{BytecodeRead, FlowKind, GotoTarget, Instruction};
+
+/// Invalid jump found by the control flow analysis.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum InvalidJump {
+    /// instruction at offset {source:#06x} jumps to offset {target:#06x}, which is in the middle
+    /// of another instruction.
+    MidInstruction {
+        /// Offset of the jump instruction.
+        source: u16,
+        /// Offset of the jump target.
+        target: u16,
+    },
+
+    /// instruction at offset {source:#06x} jumps outside the library code segment.
+    OutOfCode {
+        /// Offset of the jump instruction.
+        source: u16,
+    },
+
+    /// instruction at offset {source:#06x} jumps to offset {target:#06x}, which holds an
+    /// instruction that can't be used as a goto target.
+    NotGotoTarget {
+        /// Offset of the jump instruction.
+        source: u16,
+        /// Offset of the jump target.
+        target: u16,
+    },
+}
+
+/// Kind of a control flow graph edge.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Display)]
+pub enum EdgeKind {
+    /// The execution proceeds from the last instruction of a block to the next block.
+    #[display("fallthrough")]
+    Fallthrough,
+
+    /// The last instruction of a block jumps to another block.
+    #[display("jump")]
+    Jump,
+
+    /// The last instruction of a block calls a subroutine starting another block.
+    #[display("call")]
+    Call,
+}
+
+/// Basic block: a sequence of instructions which is entered only through its first instruction
+/// and left only after its last instruction.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct BasicBlock {
+    /// Offset of the first instruction of the block.
+    pub start: u16,
+    /// Offset following the last instruction of the block (exclusive).
+    pub end: u16,
+    /// Offset of the last instruction of the block.
+    pub last: u16,
+    /// Number of instructions in the block.
+    pub len: u16,
+}
+
+/// Control flow graph edge between two basic blocks, identified by their start offsets.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct Edge {
+    /// Start offset of the source block.
+    pub source: u16,
+    /// Start offset of the target block.
+    pub target: u16,
+    /// Kind of the control transfer.
+    pub kind: EdgeKind,
+}
+
+/// Control flow graph of a library code segment.
+///
+/// Produced by [`Lib::control_flow_graph`].
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct ControlFlowGraph {
+    /// Basic blocks, ordered by their start offset.
+    pub blocks: Vec<BasicBlock>,
+
+    /// Edges between the basic blocks, ordered by the source block offset.
+    pub edges: Vec<Edge>,
+
+    /// Jumps and calls into other libraries, each with the offset of the instruction performing
+    /// it.
+    pub external: Vec<(u16, Site<LibId>)>,
+
+    /// Local jumps which can't be followed.
+    pub invalid_jumps: Vec<InvalidJump>,
+
+    /// Offset of the code segment data which can't be decoded as an instruction, if any.
+    ///
+    /// The graph covers only the code preceding this offset.
+    pub invalid_code: Option<u16>,
+}
+
+impl ControlFlowGraph {
+    /// Checks whether the graph has no invalid jumps.
+    #[inline]
+    pub fn is_valid(&self) -> bool { self.invalid_jumps.is_empty() }
+
+    /// Returns the basic block containing an instruction at the provided offset.
+    pub fn block_at(&self, pos: u16) -> Option<&BasicBlock> {
+        let index = self.blocks.partition_point(|block| block.start <= pos);
+        self.blocks[..index].last().filter(|block| pos < block.end)
+    }
+
+    /// Iterates over the edges leaving the block starting at the provided offset.
+    pub fn successors(&self, start: u16) -> impl Iterator<Item = &Edge> {
+        self.edges.iter().filter(move |edge| edge.source == start)
+    }
+
+    /// Iterates over the edges entering the block starting at the provided offset.
+    pub fn predecessors(&self, start: u16) -> impl Iterator<Item = &Edge> {
+        self.edges.iter().filter(move |edge| edge.target == start)
+    }
+}
+
+/// Decoded instruction with its control flow properties.
+struct Node {
+    pos: u16,
+    next: u16,
+    kind: FlowKind,
+    targets: Vec<Result<u16, InvalidJump>>,
+    is_goto_target: bool,
+}
+
+/// Resolves the local jump targets of an instruction located at `pos`.
+///
+/// Relative targets falling before the start of the code segment are reported as
+/// [`InvalidJump::OutOfCode`]; the other targets are not checked.
+pub(super) fn local_targets<Isa>(instr: &mut Isa, pos: u16) -> Vec<Result<u16, InvalidJump>>
+where Isa: Instruction<LibId> {
+    match instr.local_goto_pos() {
+        GotoTarget::None => vec![],
+        GotoTarget::Absolute(target) => vec![Ok(*target)],
+        GotoTarget::Relative(shift) => vec![pos
+            .checked_add_signed(*shift as i16)
+            .ok_or(InvalidJump::OutOfCode { source: pos })],
+        GotoTarget::RelativeWide(shift) => vec![pos
+            .checked_add_signed(*shift)
+            .ok_or(InvalidJump::OutOfCode { source: pos })],
+        GotoTarget::Table(table) => table.iter().copied().map(Ok).collect(),
+    }
+}
+
+impl Lib {
+    /// Decodes the library code segment and builds its control flow graph.
+    ///
+    /// Local jump targets are checked to point to an instruction boundary; the jumps failing the
+    /// check are listed in [`ControlFlowGraph::invalid_jumps`] and do not produce edges. Jumps to
+    /// the offset right past the end of the code segment are valid, but don't produce edges
+    /// either, since they halt the program. The
+    /// precision of the graph depends on [`Instruction::flow_kind`] implementation by the
+    /// instruction set.
+    pub fn control_flow_graph<Isa>(&self) -> ControlFlowGraph
+    where Isa: Instruction<LibId> {
+        let mut cfg = ControlFlowGraph::default();
+        let mut nodes = Vec::new();
+        let mut reader = Marshaller::with_unchecked(self.code(), self.data(), self.libs());
+        while !reader.is_eof() {
+            let pos = reader.pos();
+            let Ok(mut instr) = Isa::decode_instr(&mut reader) else {
+                cfg.invalid_code = Some(pos);
+                break;
+            };
+            let targets = local_targets(&mut instr, pos);
+            if let Some(site) = instr.remote_goto_pos() {
+                cfg.external.push((pos, *site));
+            }
+            nodes.push(Node {
+                pos,
+                next: reader.pos(),
+                kind: instr.flow_kind(),
+                targets,
+                is_goto_target: instr.is_goto_target(),
+            });
+        }
+
+        let boundaries = nodes.iter().map(|node| node.pos).collect::<BTreeSet<_>>();
+        for node in &mut nodes {
+            for item in &mut node.targets {
+                if let Ok(target) = *item {
+                    if target as usize > self.code().len() {
+                        *item = Err(InvalidJump::OutOfCode { source: node.pos });
+                    } else if target as usize != self.code().len() && !boundaries.contains(&target)
+                    {
+                        *item = Err(InvalidJump::MidInstruction { source: node.pos, target });
+                    }
+                }
+                if let Err(err) = *item {
+                    cfg.invalid_jumps.push(err);
+                }
+            }
+        }
+
+        let mut leaders = BTreeSet::new();
+        for node in &nodes {
+            if node.pos == 0 || node.is_goto_target {
+                leaders.insert(node.pos);
+            }
+            leaders.extend(node.targets.iter().copied().filter_map(Result::ok));
+            if node.kind != FlowKind::Continue {
+                leaders.insert(node.next);
+            }
+        }
+
+        let mut iter = nodes.iter().peekable();
+        while let Some(first) = iter.next() {
+            let mut last = first;
+            let mut len = 1u16;
+            while let Some(node) = iter.next_if(|node| !leaders.contains(&node.pos)) {
+                last = node;
+                len += 1;
+            }
+            let start = first.pos;
+            cfg.blocks
+                .push(BasicBlock { start, end: last.next, last: last.pos, len });
+
+            let falls_through =
+                matches!(last.kind, FlowKind::Continue | FlowKind::Branch | FlowKind::Call);
+            let jump = match last.kind {
+                FlowKind::Branch | FlowKind::Jump => Some(EdgeKind::Jump),
+                FlowKind::Call => Some(EdgeKind::Call),
+                FlowKind::Continue | FlowKind::Halt => None,
+            };
+            if let Some(kind) = jump {
+                // Jump tables may list the same target multiple times
+                let targets = last
+                    .targets
+                    .iter()
+                    .copied()
+                    .filter_map(Result::ok)
+                    .filter(|target| (*target as usize) < self.code().len())
+                    .collect::<BTreeSet<_>>();
+                cfg.edges.extend(targets.into_iter().map(|target| Edge {
+                    source: start,
+                    target,
+                    kind,
+                }));
+            }
+            if falls_through && iter.peek().is_some() {
+                cfg.edges.push(Edge {
+                    source: start,
+                    target: last.next,
+                    kind: EdgeKind::Fallthrough,
+                });
+            }
+        }
+        cfg
+    }
+
+    /// Checks that all local jumps in the library code segment target instruction boundaries
+    /// within the code segment, or the end of the code segment.
+    ///
+    /// # Errors
+    ///
+    /// Lists all invalid jumps found by [`Lib::control_flow_graph`].
+    pub fn validate_jumps<Isa>(&self) -> Result<(), Vec<InvalidJump>>
+    where Isa: Instruction<LibId> {
+        let cfg = self.control_flow_graph::<Isa>();
+        if cfg.invalid_jumps.is_empty() {
+            Ok(())
+        } else {
+            Err(cfg.invalid_jumps)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![cfg_attr(coverage_nightly, coverage(off))]
+
+    use alloc::collections::BTreeSet;
+    use core::fmt::{self, Display, Formatter};
+    use core::ops::RangeInclusive;
+
+    use amplify::confinement::SmallBlob;
+    use strict_encoding::StrictDumb;
+
+    use super::*;
+    use crate::core::{Core, NoExt, NoRegs};
+    use crate::isa::{Bytecode, BytecodeWrite, CodeEofError, CtrlInstr, ExecStep, Instr};
+
+    /// Instruction set relying on the default [`Instruction::flow_kind`] implementation.
+    #[derive(Clone, PartialEq, Eq, Debug)]
+    struct OpaqueInstr(Instr<LibId>);
+
+    impl Display for OpaqueInstr {
+        fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result { Display::fmt(&self.0, f) }
+    }
+
+    impl Bytecode<LibId> for OpaqueInstr {
+        fn op_range() -> RangeInclusive<u8> { Instr::<LibId>::op_range() }
+        fn opcode_byte(&self) -> u8 { self.0.opcode_byte() }
+        fn code_byte_len(&self) -> u16 { self.0.code_byte_len() }
+        fn external_ref(&self) -> Option<LibId> { self.0.external_ref() }
+        fn encode_operands<W>(&self, writer: &mut W) -> Result<(), W::Error>
+        where W: BytecodeWrite<LibId> {
+            self.0.encode_operands(writer)
+        }
+        fn decode_operands<R>(reader: &mut R, opcode: u8) -> Result<Self, CodeEofError>
+        where R: BytecodeRead<LibId> {
+            Instr::decode_operands(reader, opcode).map(Self)
+        }
+    }
+
+    impl Instruction<LibId> for OpaqueInstr {
+        const ISA_EXT: &'static [&'static str] = &[];
+        type Core = NoExt;
+        type Context<'ctx> = ();
+
+        fn is_goto_target(&self) -> bool { self.0.is_goto_target() }
+        fn local_goto_pos(&mut self) -> GotoTarget<'_> { self.0.local_goto_pos() }
+        fn remote_goto_pos(&mut self) -> Option<&mut Site<LibId>> { self.0.remote_goto_pos() }
+        fn src_regs(&self) -> BTreeSet<NoRegs> { self.0.src_regs() }
+        fn dst_regs(&self) -> BTreeSet<NoRegs> { self.0.dst_regs() }
+        fn op_data_bytes(&self) -> u16 { self.0.op_data_bytes() }
+        fn ext_data_bytes(&self) -> u16 { self.0.ext_data_bytes() }
+        fn exec<const CALL_STACK_SIZE: usize>(
+            &self,
+            site: Site<LibId>,
+            core: &mut Core<LibId, NoExt, CALL_STACK_SIZE>,
+            context: &(),
+        ) -> ExecStep<Site<LibId>> {
+            self.0.exec(site, core, context)
+        }
+    }
+
+    fn block(start: u16, end: u16, last: u16, len: u16) -> BasicBlock {
+        BasicBlock { start, end, last, len }
+    }
+
+    fn edge(source: u16, target: u16, kind: EdgeKind) -> Edge { Edge { source, target, kind } }
+
+    fn sample() -> Lib {
+        let code: [Instr<LibId>; 6] = [
+            CtrlInstr::ChkCo.into(),             // 0
+            CtrlInstr::JiFail { pos: 9 }.into(), // 1
+            CtrlInstr::Fn { pos: 8 }.into(),     // 4
+            CtrlInstr::Stop.into(),              // 7
+            CtrlInstr::Ret.into(),               // 8
+            CtrlInstr::Sh { shift: -9 }.into(),  // 9
+        ];
+        Lib::assemble(&code).unwrap()
+    }
+
+    #[test]
+    fn graph() {
+        let lib = sample();
+        let cfg = lib.control_flow_graph::<Instr<LibId>>();
+        assert!(cfg.is_valid());
+        assert_eq!(cfg.invalid_code, None);
+        assert!(cfg.external.is_empty());
+        assert_eq!(cfg.blocks, [
+            block(0, 4, 1, 2),
+            block(4, 7, 4, 1),
+            block(7, 8, 7, 1),
+            block(8, 9, 8, 1),
+            block(9, 11, 9, 1),
+        ]);
+        assert_eq!(cfg.edges, [
+            edge(0, 9, EdgeKind::Jump),
+            edge(0, 4, EdgeKind::Fallthrough),
+            edge(4, 8, EdgeKind::Call),
+            edge(4, 7, EdgeKind::Fallthrough),
+            edge(9, 0, EdgeKind::Jump),
+        ]);
+        assert_eq!(cfg.block_at(2), Some(&cfg.blocks[0]));
+        assert_eq!(cfg.block_at(10), Some(&cfg.blocks[4]));
+        assert_eq!(cfg.block_at(11), None);
+        assert_eq!(cfg.successors(4).count(), 2);
+        assert_eq!(cfg.predecessors(0).count(), 1);
+        assert_eq!(lib.validate_jumps::<Instr<LibId>>(), Ok(()));
+    }
+
+    #[test]
+    fn graph_default_flow_kind() {
+        let lib = sample();
+        let cfg = lib.control_flow_graph::<OpaqueInstr>();
+        assert!(cfg.is_valid());
+        assert_eq!(cfg.blocks, lib.control_flow_graph::<Instr<LibId>>().blocks);
+        // Without the precise flow kinds, calls are seen as branches and halts as no-ops
+        assert_eq!(cfg.edges, [
+            edge(0, 9, EdgeKind::Jump),
+            edge(0, 4, EdgeKind::Fallthrough),
+            edge(4, 8, EdgeKind::Jump),
+            edge(4, 7, EdgeKind::Fallthrough),
+            edge(7, 8, EdgeKind::Fallthrough),
+            edge(8, 9, EdgeKind::Fallthrough),
+            edge(9, 0, EdgeKind::Jump),
+        ]);
+    }
+
+    #[test]
+    fn graph_goto_targets_and_external() {
+        let site = Site::new(LibId::strict_dumb(), 0x10);
+        let code: [Instr<LibId>; 4] = [
+            CtrlInstr::ChkCk.into(),
+            CtrlInstr::Nop.into(),
+            CtrlInstr::Call { site }.into(),
+            CtrlInstr::Exec { site }.into(),
+        ];
+        let lib = Lib::assemble(&code).unwrap();
+        let cfg = lib.control_flow_graph::<Instr<LibId>>();
+        assert_eq!(cfg.external, [(2, site), (6, site)]);
+        assert_eq!(cfg.blocks.len(), 3);
+        assert_eq!(cfg.blocks[1].start, 1);
+        assert_eq!(cfg.blocks[2].start, 6);
+        assert_eq!(cfg.edges, [
+            edge(0, 1, EdgeKind::Fallthrough),
+            edge(1, 6, EdgeKind::Fallthrough)
+        ]);
+    }
+
+    #[test]
+    fn jump_to_end() {
+        let code: [Instr<LibId>; 2] =
+            [CtrlInstr::JiOvfl { pos: 5 }.into(), CtrlInstr::Sh { shift: 2 }.into()];
+        let lib = Lib::assemble(&code).unwrap();
+        let cfg = lib.control_flow_graph::<Instr<LibId>>();
+        assert!(cfg.is_valid());
+        assert_eq!(cfg.blocks.len(), 2);
+        assert_eq!(cfg.edges, [edge(0, 3, EdgeKind::Fallthrough)]);
+        assert_eq!(lib.validate_jumps::<Instr<LibId>>(), Ok(()));
+    }
+
+    #[test]
+    fn invalid_jumps() {
+        let code: [Instr<LibId>; 4] = [
+            CtrlInstr::Jmp { pos: 2 }.into(),
+            CtrlInstr::JiOvfl { pos: 100 }.into(),
+            CtrlInstr::ShFail { shift: -7 }.into(),
+            CtrlInstr::Stop.into(),
+        ];
+        let lib = Lib::assemble(&code).unwrap();
+        let cfg = lib.control_flow_graph::<Instr<LibId>>();
+        assert!(!cfg.is_valid());
+        assert!(cfg
+            .edges
+            .iter()
+            .all(|edge| edge.kind == EdgeKind::Fallthrough));
+        let errs = vec![
+            InvalidJump::MidInstruction { source: 0, target: 2 },
+            InvalidJump::OutOfCode { source: 3 },
+            InvalidJump::OutOfCode { source: 6 },
+        ];
+        assert_eq!(cfg.invalid_jumps, errs);
+        assert_eq!(lib.validate_jumps::<Instr<LibId>>(), Err(errs));
+        assert_eq!(
+            InvalidJump::MidInstruction { source: 0, target: 2 }.to_string(),
+            "instruction at offset 0x0000 jumps to offset 0x0002, which is in the middle of \
+             another instruction."
+        );
+    }
+
+    #[test]
+    fn invalid_code() {
+        let code: [Instr<LibId>; 2] = [CtrlInstr::Nop.into(), CtrlInstr::Jmp { pos: 0 }.into()];
+        let mut lib = Lib::assemble(&code).unwrap();
+        // Nop followed by a jump missing its last byte
+        *lib.code_mut() =
+            SmallBlob::try_from_slice(&[lib.code()[0], lib.code()[1], lib.code()[2]]).unwrap();
+        let cfg = lib.control_flow_graph::<Instr<LibId>>();
+        assert_eq!(cfg.invalid_code, Some(1));
+        assert_eq!(cfg.blocks, [block(0, 1, 0, 1)]);
+        assert!(cfg.edges.is_empty());
+        assert_eq!(Lib::strict_dumb().control_flow_graph::<Instr<LibId>>(), none!());
+    }
+}
@@ -0,0 +1,233 @@
+// Reference rust implementation of AluVM (arithmetic logic unit virtual machine).
+// To find more on AluVM please check <https://aluvm.org>
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Designed in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+// Written in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+//
+// Copyright (C) 2021-2024 LNP/BP Standards Association, Switzerland.
+// Copyright (C) 2024-2025 Laboratories for Ubiquitous Deterministic Computing (UBIDECO),
+//                         Institute for Distributed and Cognitive Systems (InDCS), Switzerland.
+// Copyright (C) 2021-2025 Dr Maxim Orlovsky.
+// All rights under the above copyrights are reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use this file except
+// in compliance with the License. You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed under the License
+// is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express
+// or implied. See the License for the specific language governing permissions and limitations under
+// the License.
+
+use alloc::vec::Vec;
+
+use crate::isa::{BytecodeRead, Instruction};
+use crate::{Lib, LibId, LibRepo, LibSite, Marshaller};
+
+/// Errors building a [`LibGraph`].
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum LibGraphError {
+    /// library {0} depends on library {1}, which can't be resolved.
+    MissingLib(LibId, LibId),
+
+    /// library {0} has invalid code at offset {1:#06x}.
+    InvalidCode(LibId, u16),
+
+    /// instruction at offset {pos:#06x} of library {lib_id} references offset {target} outside
+    /// of the code segment of the target library.
+    SiteOutOfBounds {
+        /// Library containing the instruction.
+        lib_id: LibId,
+        /// Offset of the instruction in the library code segment.
+        pos: u16,
+        /// Site referenced by the instruction.
+        target: LibSite,
+    },
+}
+
+/// Graph of a library and all its transitive dependencies, verified to be complete and
+/// consistent.
+///
+/// The graph is constructed with [`LibGraph::resolve`], which guarantees that
+/// - all libraries reachable from the root library via their libs segments are resolved;
+/// - each external site referenced from the code of the libraries lies within the code segment of
+///   the target library.
+///
+/// The libraries are kept in a [`LibRepo`], thus their dependencies never form cycles.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct LibGraph {
+    repo: LibRepo,
+    order: Vec<LibId>,
+}
+
+impl LibGraph {
+    /// Resolves the root library dependencies, transitively, using the `resolver`, and verifies
+    /// them against the instruction set.
+    ///
+    /// A dependency is resolved only if the `resolver` returns a library with the requested id.
+    ///
+    /// # Errors
+    ///
+    /// If a dependency can't be resolved, the code of some library can't be decoded, or some of
+    /// the libraries reference a site outside the code segment of the target library. The first
+    /// detected error is reported.
+    pub fn resolve<Isa>(
+        root: Lib,
+        resolver: impl Fn(LibId) -> Option<Lib>,
+    ) -> Result<Self, LibGraphError>
+    where
+        Isa: Instruction<LibId>,
+    {
+        let mut repo = LibRepo::new();
+        let root_id = repo.insert(root);
+        while let Err(missing) = repo.check_dependencies(root_id) {
+            for dep in missing {
+                match resolver(dep) {
+                    Some(lib) if lib.lib_id() == dep => repo.insert(lib),
+                    _ => {
+                        let lib_id = repo
+                            .libs()
+                            .find(|lib| lib.libs().contains(&dep))
+                            .map(Lib::lib_id)
+                            .expect("missing libraries are always referenced by some library");
+                        return Err(LibGraphError::MissingLib(lib_id, dep));
+                    }
+                };
+            }
+        }
+        let order = repo.dependency_order(root_id);
+        let graph = Self { repo, order };
+        for lib_id in &graph.order {
+            graph.check_sites::<Isa>(*lib_id)?;
+        }
+        Ok(graph)
+    }
+
+    /// Returns the id of the root library.
+    pub fn root(&self) -> LibId { *self.order.last().expect("graph always contains the root") }
+
+    /// Returns the ids of all libraries of the graph, such that each library goes after all of
+    /// its dependencies (see [`LibRepo::dependency_order`]).
+    ///
+    /// The root library is always the last one.
+    pub fn order(&self) -> &[LibId] { &self.order }
+
+    /// Returns a library which is a part of the graph.
+    pub fn lib(&self, lib_id: LibId) -> Option<&Lib> { self.repo.get(lib_id) }
+
+    /// Iterates over all libraries of the graph in the dependency order (see [`Self::order`]).
+    pub fn libs(&self) -> impl Iterator<Item = &Lib> {
+        self.order
+            .iter()
+            .filter_map(|lib_id| self.repo.get(*lib_id))
+    }
+
+    /// Converts the graph into a library repository, which can be used for the execution.
+    pub fn into_repo(self) -> LibRepo { self.repo }
+
+    fn check_sites<Isa>(&self, lib_id: LibId) -> Result<(), LibGraphError>
+    where Isa: Instruction<LibId> {
+        let lib = self
+            .repo
+            .get(lib_id)
+            .expect("graph contains all the ordered libraries");
+        let mut reader = Marshaller::with_unchecked(lib.code(), lib.data(), lib.libs());
+        while !reader.is_eof() {
+            let pos = reader.pos();
+            let mut instr = Isa::decode_instr(&mut reader)
+                .map_err(|_| LibGraphError::InvalidCode(lib_id, pos))?;
+            let Some(target) = instr.remote_goto_pos().copied().map(LibSite::from) else {
+                continue;
+            };
+            match self.repo.get(target.lib_id) {
+                Some(dst) if (target.offset as usize) < dst.code().len() => {}
+                _ => return Err(LibGraphError::SiteOutOfBounds { lib_id, pos, target }),
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![cfg_attr(coverage_nightly, coverage(off))]
+
+    use super::*;
+    use crate::isa::Instr;
+    use crate::regs::Status;
+    use crate::{aluasm, CompiledLib, Vm};
+
+    fn libs() -> (Lib, Lib, Lib) {
+        let c = CompiledLib::compile(aluasm! { nop; ret; }, &[]).unwrap();
+        let c_id = c.as_lib().lib_id();
+        let b = CompiledLib::compile(aluasm! { nop; call c_id, 0; ret; }, &[&c]).unwrap();
+        let b_id = b.as_lib().lib_id();
+        let a = CompiledLib::compile(aluasm! { call b_id, 0; stop; }, &[&b]).unwrap();
+        (a.into_lib(), b.into_lib(), c.into_lib())
+    }
+
+    fn resolver<'a>(libs: &'a [&'a Lib]) -> impl Fn(LibId) -> Option<Lib> + 'a {
+        |lib_id| {
+            libs.iter()
+                .find(|lib| lib.lib_id() == lib_id)
+                .map(|lib| (*lib).clone())
+        }
+    }
+
+    #[test]
+    fn chain() {
+        let (a, b, c) = libs();
+        let (a_id, b_id, c_id) = (a.lib_id(), b.lib_id(), c.lib_id());
+        let graph = LibGraph::resolve::<Instr<LibId>>(a, resolver(&[&b, &c])).unwrap();
+        assert_eq!(graph.root(), a_id);
+        assert_eq!(graph.order(), &[c_id, b_id, a_id]);
+        assert_eq!(graph.lib(b_id), Some(&b));
+        assert_eq!(graph.libs().map(Lib::lib_id).collect::<Vec<_>>(), vec![c_id, b_id, a_id]);
+
+        let repo = graph.into_repo();
+        let mut vm = Vm::<Instr<LibId>>::new();
+        assert_eq!(vm.exec_repo(LibSite::new(a_id, 0), &(), &repo), Status::Ok);
+    }
+
+    #[test]
+    fn leaf() {
+        let (.., c) = libs();
+        let c_id = c.lib_id();
+        let graph = LibGraph::resolve::<Instr<LibId>>(c, |_| None).unwrap();
+        assert_eq!(graph.order(), &[c_id]);
+    }
+
+    #[test]
+    fn missing() {
+        let (a, b, c) = libs();
+        let err = LibGraph::resolve::<Instr<LibId>>(a, resolver(&[&b])).unwrap_err();
+        assert_eq!(err, LibGraphError::MissingLib(b.lib_id(), c.lib_id()));
+    }
+
+    #[test]
+    fn wrong_lib() {
+        let (a, b, _) = libs();
+        // A resolver returning a wrong library for the id, such that the library would refer back
+        // to itself
+        let err = LibGraph::resolve::<Instr<LibId>>(a.clone(), |_| Some(a.clone())).unwrap_err();
+        assert_eq!(err, LibGraphError::MissingLib(a.lib_id(), b.lib_id()));
+    }
+
+    #[test]
+    fn out_of_bounds() {
+        let (_, b, c) = libs();
+        let c_id = c.lib_id();
+        let a = Lib::assemble::<Instr<LibId>>(&aluasm! { call c_id, 2; stop; }).unwrap();
+        let a_id = a.lib_id();
+        let err = LibGraph::resolve::<Instr<LibId>>(a, resolver(&[&b, &c])).unwrap_err();
+        assert_eq!(err, LibGraphError::SiteOutOfBounds {
+            lib_id: a_id,
+            pos: 0,
+            target: LibSite::new(c_id, 2)
+        });
+    }
+}
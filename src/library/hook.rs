@@ -0,0 +1,86 @@
+// Reference rust implementation of AluVM (arithmetic logic unit virtual machine).
+// To find more on AluVM please check <https://aluvm.org>
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Designed in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+// Written in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+//
+// Copyright (C) 2021-2024 LNP/BP Standards Association, Switzerland.
+// Copyright (C) 2024-2025 Laboratories for Ubiquitous Deterministic Computing (UBIDECO),
+//                         Institute for Distributed and Cognitive Systems (InDCS), Switzerland.
+// Copyright (C) 2021-2025 Dr Maxim Orlovsky.
+// All rights under the above copyrights are reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use this file except
+// in compliance with the License. You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed under the License
+// is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express
+// or implied. See the License for the specific language governing permissions and limitations under
+// the License.
+
+use crate::core::{Core, Site, SiteId};
+use crate::isa::Instruction;
+
+/// Decision of an [`ExecHook`] on whether to proceed with the program execution.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default, Display)]
+pub enum HookAction {
+    /// Continue the program execution.
+    #[default]
+    #[display("continue")]
+    Continue,
+
+    /// Terminate the program execution.
+    ///
+    /// The termination is reported to the caller as [`crate::ExecError::HookAbort`], and `CK` is
+    /// set to a failure independently of the `CH` register value.
+    #[display("abort")]
+    Abort,
+}
+
+/// Instrumentation callbacks invoked by the program execution for each instruction, used to
+/// collect execution statistics without the `log` feature.
+///
+/// The hook is a generic parameter of the execution methods (see [`crate::Vm::exec_with_hook`]
+/// and [`crate::Lib::exec_with_hook`]), such that the execution without instrumentation, which
+/// uses [`NoHook`], is not affected by it. All the methods default to doing nothing and
+/// continuing the execution.
+pub trait ExecHook<Id: SiteId> {
+    /// Called before the execution of the instruction at `site`.
+    ///
+    /// Returning [`HookAction::Abort`] terminates the execution without executing the instruction
+    /// and accounting it in the instruction counter.
+    fn before_instr<Instr>(&mut self, site: Site<Id>, instr: &Instr) -> HookAction
+    where Instr: Instruction<Id> {
+        let _ = (site, instr);
+        HookAction::Continue
+    }
+
+    /// Called after the execution of the instruction at `site` once its complexity is
+    /// accounted, providing the state of the core registers after the instruction.
+    ///
+    /// Not called if the execution halts due to the complexity or jump limits. Returning
+    /// [`HookAction::Abort`] terminates the execution instead of proceeding to the next
+    /// instruction.
+    fn after_instr<Instr, const CALL_STACK_SIZE: usize>(
+        &mut self,
+        site: Site<Id>,
+        instr: &Instr,
+        core: &Core<Id, Instr::Core, CALL_STACK_SIZE>,
+    ) -> HookAction
+    where
+        Instr: Instruction<Id>,
+    {
+        let _ = (site, instr, core);
+        HookAction::Continue
+    }
+}
+
+/// Execution hook which does nothing, used by the execution methods without instrumentation.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct NoHook;
+
+impl<Id: SiteId> ExecHook<Id> for NoHook {}
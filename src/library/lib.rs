@@ -22,21 +22,32 @@
 // or implied. See the License for the specific language governing permissions and limitations under
 // the License.
 
+use core::cmp::Ordering;
 use core::fmt;
-use core::fmt::{Display, Formatter};
+use core::fmt::{Debug, Display, Formatter};
+use core::hash::{Hash, Hasher};
 use core::str::FromStr;
+use std::io;
+use std::sync::OnceLock;
 
 use amplify::confinement::{SmallBlob, TinyOrdSet};
 use amplify::Bytes32;
 use baid64::{Baid64ParseError, DisplayBaid64, FromBaid64Str};
-use commit_verify::{CommitId, CommitmentId, Digest, Sha256};
-use strict_encoding::{StrictDeserialize, StrictSerialize};
+use commit_verify::{CommitId, CommitmentId, Digest, DigestExt, Sha256};
+use strict_encoding::{
+    DecodeError, ReadStruct, StrictDecode, StrictDeserialize, StrictDumb, StrictEncode,
+    StrictProduct, StrictSerialize, StrictStruct, StrictType, TypeName, TypedRead, TypedWrite,
+    WriteStruct,
+};
 
-use crate::core::SiteId;
-use crate::{IsaId, Site, LIB_NAME_ALUVM};
+use crate::core::{CoreExt, SiteId, SiteParseError, Status};
+use crate::{Core, CoreSnapshot, IsaSeg, Site, UnknownInstrPolicy, LIB_NAME_ALUVM};
 
 pub const LIB_ID_TAG: &str = "urn:ubideco:aluvm:lib:v01#241020";
 
+/// Tag of the tagged SHA-256 hash used by [`Core::state_digest`].
+pub const CORE_STATE_TAG: &str = "urn:ubideco:aluvm:core-state:v01#261016";
+
 /// Unique identifier for an AluVM library.
 #[derive(Wrapper, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Default, Debug, From)]
 #[wrapper(Deref, BorrowSlice, Hex, Index, RangeOps)]
@@ -77,11 +88,14 @@ impl From<Sha256> for LibId {
 }
 
 /// Location inside the instruction sequence which can be executed by the core.
+///
+/// The canonical textual form of the site is the same as of [`Site`]: `<lib_id>@<offset>#h`, with
+/// the offset written as four hexadecimal digits. Human-readable serde formats use this textual
+/// form, such that library sites can be used as keys in JSON maps.
 #[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Display)]
-#[display("{lib_id}@{offset:04}")]
+#[display("{lib_id}@{offset:04X}#h")]
 #[derive(StrictType, StrictDumb, StrictEncode, StrictDecode)]
 #[strict_type(lib = LIB_NAME_ALUVM)]
-#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(rename_all = "camelCase"))]
 pub struct LibSite {
     /// The identifier of the library.
     pub lib_id: LibId,
@@ -99,26 +113,270 @@ impl LibSite {
     pub fn new(lib_id: LibId, offset: u16) -> Self { LibSite { lib_id, offset } }
 }
 
+impl FromStr for LibSite {
+    type Err = SiteParseError<Baid64ParseError>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> { Site::<LibId>::from_str(s).map(Self::from) }
+}
+
+// Sites are generic over the program identifier, which doesn't have to be strict encodable, so the
+// strict encoding is implemented for the library sites only.
+impl StrictDumb for Site<LibId> {
+    fn strict_dumb() -> Self { Site::new(strict_dumb!(), 0) }
+}
+
+impl StrictType for Site<LibId> {
+    const STRICT_LIB_NAME: &'static str = LIB_NAME_ALUVM;
+    fn strict_name() -> Option<TypeName> { Some(tn!("Site")) }
+}
+
+impl StrictProduct for Site<LibId> {}
+
+impl StrictStruct for Site<LibId> {
+    const ALL_FIELDS: &'static [&'static str] = &["progId", "offset"];
+}
+
+impl StrictEncode for Site<LibId> {
+    fn strict_encode<W: TypedWrite>(&self, writer: W) -> io::Result<W> {
+        writer.write_struct::<Self>(|w| {
+            Ok(w.write_field(fname!("progId"), &self.prog_id)?
+                .write_field(fname!("offset"), &self.offset)?
+                .complete())
+        })
+    }
+}
+
+impl StrictDecode for Site<LibId> {
+    fn strict_decode(reader: &mut impl TypedRead) -> Result<Self, DecodeError> {
+        reader.read_struct(|r| {
+            let prog_id = r.read_field(fname!("progId"))?;
+            let offset = r.read_field(fname!("offset"))?;
+            Ok(Site::new(prog_id, offset))
+        })
+    }
+}
+
+// Snapshots are generic over the program identifier in the same way as the sites, thus the strict
+// encoding is implemented for the library sites only.
+impl<S: StrictDumb> StrictDumb for CoreSnapshot<LibId, S> {
+    fn strict_dumb() -> Self {
+        CoreSnapshot {
+            ch: true,
+            ck: Status::Ok,
+            cf: 0,
+            co: Status::Ok,
+            cy: 0,
+            cy_lim: None,
+            ci: 0,
+            ci_lim: None,
+            ca: 0,
+            cl: None,
+            cw: None,
+            cw_site: None,
+            cs: none!(),
+            cs_integrity: false,
+            cs_budgets: none!(),
+            cs_budget_next: None,
+            cs_high_water: 0,
+            xcp: 0,
+            xcs_high_water: 0,
+            unknown_instr: UnknownInstrPolicy::Fail,
+            cx: S::strict_dumb(),
+        }
+    }
+}
+
+impl<S: StrictType> StrictType for CoreSnapshot<LibId, S> {
+    const STRICT_LIB_NAME: &'static str = LIB_NAME_ALUVM;
+    fn strict_name() -> Option<TypeName> { Some(tn!("CoreSnapshot")) }
+}
+
+impl<S: StrictType + StrictDumb> StrictProduct for CoreSnapshot<LibId, S> {}
+
+impl<S: StrictType + StrictDumb> StrictStruct for CoreSnapshot<LibId, S> {
+    const ALL_FIELDS: &'static [&'static str] = &[
+        "ch",
+        "ck",
+        "cf",
+        "co",
+        "cy",
+        "cyLim",
+        "ci",
+        "ciLim",
+        "ca",
+        "cl",
+        "cw",
+        "cwSite",
+        "cs",
+        "csIntegrity",
+        "csBudgets",
+        "csBudgetNext",
+        "csHighWater",
+        "xcp",
+        "xcsHighWater",
+        "unknownInstr",
+        "cx",
+    ];
+}
+
+impl<S: StrictEncode + StrictDumb> StrictEncode for CoreSnapshot<LibId, S> {
+    fn strict_encode<W: TypedWrite>(&self, writer: W) -> io::Result<W> {
+        writer.write_struct::<Self>(|w| {
+            Ok(w.write_field(fname!("ch"), &self.ch)?
+                .write_field(fname!("ck"), &self.ck)?
+                .write_field(fname!("cf"), &self.cf)?
+                .write_field(fname!("co"), &self.co)?
+                .write_field(fname!("cy"), &self.cy)?
+                .write_field(fname!("cyLim"), &self.cy_lim)?
+                .write_field(fname!("ci"), &self.ci)?
+                .write_field(fname!("ciLim"), &self.ci_lim)?
+                .write_field(fname!("ca"), &self.ca)?
+                .write_field(fname!("cl"), &self.cl)?
+                .write_field(fname!("cw"), &self.cw)?
+                .write_field(fname!("cwSite"), &self.cw_site)?
+                .write_field(fname!("cs"), &self.cs)?
+                .write_field(fname!("csIntegrity"), &self.cs_integrity)?
+                .write_field(fname!("csBudgets"), &self.cs_budgets)?
+                .write_field(fname!("csBudgetNext"), &self.cs_budget_next)?
+                .write_field(fname!("csHighWater"), &self.cs_high_water)?
+                .write_field(fname!("xcp"), &self.xcp)?
+                .write_field(fname!("xcsHighWater"), &self.xcs_high_water)?
+                .write_field(fname!("unknownInstr"), &self.unknown_instr)?
+                .write_field(fname!("cx"), &self.cx)?
+                .complete())
+        })
+    }
+}
+
+impl<S: StrictEncode + StrictDumb> StrictSerialize for CoreSnapshot<LibId, S> {}
+impl<S: StrictDecode + StrictDumb> StrictDeserialize for CoreSnapshot<LibId, S> {}
+
+impl<S: StrictDecode + StrictDumb> StrictDecode for CoreSnapshot<LibId, S> {
+    fn strict_decode(reader: &mut impl TypedRead) -> Result<Self, DecodeError> {
+        reader.read_struct(|r| {
+            Ok(CoreSnapshot {
+                ch: r.read_field(fname!("ch"))?,
+                ck: r.read_field(fname!("ck"))?,
+                cf: r.read_field(fname!("cf"))?,
+                co: r.read_field(fname!("co"))?,
+                cy: r.read_field(fname!("cy"))?,
+                cy_lim: r.read_field(fname!("cyLim"))?,
+                ci: r.read_field(fname!("ci"))?,
+                ci_lim: r.read_field(fname!("ciLim"))?,
+                ca: r.read_field(fname!("ca"))?,
+                cl: r.read_field(fname!("cl"))?,
+                cw: r.read_field(fname!("cw"))?,
+                cw_site: r.read_field(fname!("cwSite"))?,
+                cs: r.read_field(fname!("cs"))?,
+                cs_integrity: r.read_field(fname!("csIntegrity"))?,
+                cs_budgets: r.read_field(fname!("csBudgets"))?,
+                cs_budget_next: r.read_field(fname!("csBudgetNext"))?,
+                cs_high_water: r.read_field(fname!("csHighWater"))?,
+                xcp: r.read_field(fname!("xcp"))?,
+                xcs_high_water: r.read_field(fname!("xcsHighWater"))?,
+                unknown_instr: r.read_field(fname!("unknownInstr"))?,
+                cx: r.read_field(fname!("cx"))?,
+            })
+        })
+    }
+}
+
+impl<Cx: CoreExt, const CALL_STACK_SIZE: usize> Core<LibId, Cx, CALL_STACK_SIZE>
+where Cx::Snapshot: StrictEncode + StrictDumb
+{
+    /// Computes a digest of the core state, which can be used to compare the results of program
+    /// execution across different implementations of AluVM (for instance, in the test vectors).
+    ///
+    /// The digest is a SHA-256 hash, tagged with [`CORE_STATE_TAG`], of the strict-serialized
+    /// [`Core::snapshot`]. Thus, it commits to the following registers, in this order:
+    /// - `CH`, `CK`, `CF`, `CO`, `CY` and the jump limit, `CI` and the instruction limit, `CA`,
+    ///   `CL`, `CW` and the complexity warning site;
+    /// - the call stack `CS`, the call stack integrity mode (but not the shadow call stack itself,
+    ///   which is derived from `CS`), the complexity budgets of the frames and the budget for the
+    ///   next frame, and the call stack high-water mark;
+    /// - `XCP` and its high-water mark;
+    /// - the policy for the reserved instructions;
+    /// - the ISA extension registers, as provided by [`CoreExt::snapshot`].
+    ///
+    /// The configuration values (`CH`, `CL`, `CW` and the limits) participate in the digest, since
+    /// they affect the program execution. The register save stack, the diagnostic reports and the
+    /// execution profile are not included.
+    ///
+    /// The composition of the digest is a part of the consensus and is not changed without
+    /// updating the tag.
+    pub fn state_digest(&self) -> Bytes32 {
+        let data = self
+            .snapshot()
+            .to_strict_serialized::<{ usize::MAX }>()
+            .expect("in-memory serialization can't fail");
+        let mut hasher = Sha256::from_tag(CORE_STATE_TAG);
+        hasher.input_raw(&data);
+        Bytes32::from_byte_array(hasher.finish())
+    }
+}
+
 /// Library segment inside AluVM library which stores references to the external library ids for the
 /// external calls made within the library.
 pub type LibsSeg = TinyOrdSet<LibId>;
 
 /// An AluVM library, which can be executed on a VM instance.
-#[derive(Clone, PartialEq, Eq, Ord, PartialOrd, Hash, Debug)]
+///
+/// The library identity is defined by its [`LibId`]: libraries are compared, ordered and hashed
+/// by their ids. The id is computed once, on the first request, and is cached afterwards; the
+/// segments can be modified only through the methods resetting the cache.
+#[derive(Clone)]
 #[derive(StrictType, StrictDumb, StrictEncode, StrictDecode)]
 #[strict_type(lib = LIB_NAME_ALUVM)]
 #[derive(CommitEncode)]
 #[commit_encode(id = LibId, strategy = strict)]
-#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", derive(Serialize))]
 pub struct Lib {
     /// ISA extension segment.
-    pub isae: TinyOrdSet<IsaId>,
+    ///
+    /// The segment is always kept in the canonical form (see [`IsaSeg`]), which is also enforced
+    /// when the library is strict-decoded.
+    isae: IsaSeg,
     /// Code segment.
-    pub code: SmallBlob,
+    #[cfg_attr(feature = "serde", serde(with = "serde_blob"))]
+    code: SmallBlob,
     /// Data segment.
-    pub data: SmallBlob,
+    #[cfg_attr(feature = "serde", serde(with = "serde_blob"))]
+    data: SmallBlob,
     /// Library segment keeping external library references.
-    pub libs: LibsSeg,
+    libs: LibsSeg,
+    /// Cached library id.
+    #[strict_type(skip, dumb = OnceLock::new())]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    id: OnceLock<LibId>,
+}
+
+impl PartialEq for Lib {
+    fn eq(&self, other: &Self) -> bool { self.lib_id() == other.lib_id() }
+}
+
+impl Eq for Lib {}
+
+impl PartialOrd for Lib {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> { Some(self.cmp(other)) }
+}
+
+impl Ord for Lib {
+    fn cmp(&self, other: &Self) -> Ordering { self.lib_id().cmp(&other.lib_id()) }
+}
+
+impl Hash for Lib {
+    fn hash<H: Hasher>(&self, state: &mut H) { self.lib_id().hash(state) }
+}
+
+impl Debug for Lib {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Lib")
+            .field("isae", &self.isae)
+            .field("code", &self.code)
+            .field("data", &self.data)
+            .field("libs", &self.libs)
+            .finish()
+    }
 }
 
 impl StrictSerialize for Lib {}
@@ -129,18 +387,64 @@ impl AsRef<Lib> for Lib {
 }
 
 impl Lib {
-    /// Compute a library identifier, which serves as a cryptographic commitment to the library
+    /// Constructs a library from its segments.
+    pub fn with(isae: IsaSeg, code: SmallBlob, data: SmallBlob, libs: LibsSeg) -> Self {
+        Self { isae, code, data, libs, id: OnceLock::new() }
+    }
+
+    /// Returns a library identifier, which serves as a cryptographic commitment to the library
     /// contents.
-    pub fn lib_id(&self) -> LibId { self.commit_id() }
+    ///
+    /// The identifier is computed on the first call and is cached afterwards.
+    pub fn lib_id(&self) -> LibId { *self.id.get_or_init(|| self.commit_id()) }
 
-    /// String containing all ISA extensions used by the library, enumerated with spaces.
-    pub fn isae_string(&self) -> String {
-        self.isae
-            .iter()
-            .map(IsaId::to_string)
-            .collect::<Vec<_>>()
-            .join(" ")
+    /// ISA extension segment.
+    #[inline]
+    pub fn isae(&self) -> &IsaSeg { &self.isae }
+
+    /// Code segment.
+    #[inline]
+    pub fn code(&self) -> &SmallBlob { &self.code }
+
+    /// Data segment.
+    #[inline]
+    pub fn data(&self) -> &SmallBlob { &self.data }
+
+    /// Library segment keeping external library references.
+    #[inline]
+    pub fn libs(&self) -> &LibsSeg { &self.libs }
+
+    /// Mutable access to the ISA extension segment, resetting the cached library id.
+    pub fn isae_mut(&mut self) -> &mut IsaSeg {
+        self.id.take();
+        &mut self.isae
+    }
+
+    /// Mutable access to the code segment, resetting the cached library id.
+    pub fn code_mut(&mut self) -> &mut SmallBlob {
+        self.id.take();
+        &mut self.code
     }
+
+    /// Mutable access to the data segment, resetting the cached library id.
+    pub fn data_mut(&mut self) -> &mut SmallBlob {
+        self.id.take();
+        &mut self.data
+    }
+
+    /// Mutable access to the libs segment, resetting the cached library id.
+    pub fn libs_mut(&mut self) -> &mut LibsSeg {
+        self.id.take();
+        &mut self.libs
+    }
+
+    /// Decomposes the library into its ISA extension, code, data and libs segments.
+    pub fn into_segments(self) -> (IsaSeg, SmallBlob, SmallBlob, LibsSeg) {
+        (self.isae, self.code, self.data, self.libs)
+    }
+
+    /// String containing all ISA extensions used by the library, enumerated with spaces.
+    pub fn isae_string(&self) -> String { self.isae.to_string() }
 }
 
 impl Display for Lib {
@@ -164,12 +468,162 @@ impl Display for Lib {
     }
 }
 
+/// Serialization of the library sites as strings in their canonical textual form in
+/// human-readable formats, and as a tuple of the library id and the offset otherwise.
+#[cfg(feature = "serde")]
+mod serde_site {
+    use alloc::string::String;
+
+    use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::*;
+
+    impl Serialize for LibSite {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            if serializer.is_human_readable() {
+                serializer.collect_str(self)
+            } else {
+                (self.lib_id, self.offset).serialize(serializer)
+            }
+        }
+    }
+
+    impl<'de> Deserialize<'de> for LibSite {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            if deserializer.is_human_readable() {
+                let s = String::deserialize(deserializer)?;
+                LibSite::from_str(&s).map_err(de::Error::custom)
+            } else {
+                let (lib_id, offset) = <(LibId, u16)>::deserialize(deserializer)?;
+                Ok(LibSite::new(lib_id, offset))
+            }
+        }
+    }
+}
+
+/// Deserialization of the libraries, which in human-readable formats may be accompanied by the
+/// library id, verified against the deserialized segments.
+#[cfg(feature = "serde")]
+mod serde_lib {
+    use serde::{de, Deserialize, Deserializer};
+
+    use super::*;
+
+    // The libs segment is deserialized as a vector, since the confined set doesn't check its size
+    // when deserialized.
+    #[derive(Deserialize)]
+    struct Segments {
+        isae: IsaSeg,
+        #[serde(with = "serde_blob")]
+        code: SmallBlob,
+        #[serde(with = "serde_blob")]
+        data: SmallBlob,
+        libs: Vec<LibId>,
+    }
+
+    #[derive(Deserialize)]
+    struct IdentifiedSegments {
+        #[serde(default)]
+        id: Option<LibId>,
+        isae: IsaSeg,
+        #[serde(with = "serde_blob")]
+        code: SmallBlob,
+        #[serde(with = "serde_blob")]
+        data: SmallBlob,
+        libs: Vec<LibId>,
+    }
+
+    fn libs_seg<E: de::Error>(libs: Vec<LibId>) -> Result<LibsSeg, E> {
+        LibsSeg::try_from_iter(libs).map_err(E::custom)
+    }
+
+    impl<'de> Deserialize<'de> for Lib {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            if !deserializer.is_human_readable() {
+                let Segments { isae, code, data, libs } = Segments::deserialize(deserializer)?;
+                return Ok(Lib::with(isae, code, data, libs_seg(libs)?));
+            }
+            let IdentifiedSegments { id, isae, code, data, libs } =
+                IdentifiedSegments::deserialize(deserializer)?;
+            let lib = Lib::with(isae, code, data, libs_seg(libs)?);
+            match id {
+                Some(id) if id != lib.lib_id() => Err(de::Error::custom(format!(
+                    "library id {id} doesn't match the library segments, which have id {}",
+                    lib.lib_id()
+                ))),
+                _ => Ok(lib),
+            }
+        }
+    }
+}
+
+/// Serialization of the library segments as hex strings in human-readable formats, and as raw
+/// bytes otherwise.
+#[cfg(feature = "serde")]
+mod serde_blob {
+    use amplify::confinement::SmallBlob;
+    use amplify::hex::{FromHex, ToHex};
+    use serde::de::{self, SeqAccess, Visitor};
+    use serde::{Deserializer, Serializer};
+
+    use super::*;
+
+    pub fn serialize<S: Serializer>(blob: &SmallBlob, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&blob.to_hex())
+        } else {
+            serializer.serialize_bytes(blob.as_slice())
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<SmallBlob, D::Error> {
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_str(BlobVisitor)
+        } else {
+            deserializer.deserialize_byte_buf(BlobVisitor)
+        }
+    }
+
+    struct BlobVisitor;
+
+    impl<'de> Visitor<'de> for BlobVisitor {
+        type Value = SmallBlob;
+
+        fn expecting(&self, f: &mut Formatter) -> fmt::Result {
+            f.write_str("a hex string or a byte string of at most 65535 bytes")
+        }
+
+        fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+            let bytes = Vec::<u8>::from_hex(v).map_err(E::custom)?;
+            self.visit_byte_buf(bytes)
+        }
+
+        fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+            SmallBlob::try_from_slice(v).map_err(E::custom)
+        }
+
+        fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+            SmallBlob::try_from(v).map_err(E::custom)
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+            let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or_default());
+            while let Some(byte) = seq.next_element()? {
+                bytes.push(byte);
+            }
+            self.visit_byte_buf(bytes)
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     #![cfg_attr(coverage_nightly, coverage(off))]
+    use amplify::confinement::Confined;
     use strict_encoding::StrictDumb;
 
     use super::*;
+    use crate::IsaId;
 
     #[test]
     fn lib_id_display() {
@@ -186,6 +640,36 @@ mod test {
         assert_eq!(format!("{id:-#}"), "uZkzX1J9-i5EvGTf-J1TB79p-OBvKq5x-1U2n4qd-8Nso3Ag");
     }
 
+    #[test]
+    fn isae_canonical() {
+        const MAX: usize = u16::MAX as usize;
+
+        let isae = IsaId::canonical_set([IsaId::from("B"), IsaId::from("A"), IsaId::from("B")]);
+        assert_eq!(isae, IsaId::canonical_set([IsaId::from("A"), IsaId::from("B")]));
+        assert_eq!(isae.iter().map(IsaId::to_string).collect::<Vec<_>>(), ["A", "B"]);
+
+        let lib1 = Lib { isae, ..Lib::strict_dumb() };
+        let lib2 = Lib {
+            isae: IsaId::canonical_set([IsaId::from("A"), IsaId::from("B"), IsaId::from("A")]),
+            ..Lib::strict_dumb()
+        };
+        assert_eq!(lib1.lib_id(), lib2.lib_id());
+
+        let data = lib1.to_strict_serialized::<MAX>().unwrap();
+        assert_eq!(&data[..5], &[2, 1, b'A', 1, b'B']);
+        assert_eq!(Lib::from_strict_serialized::<MAX>(data.clone()).unwrap(), lib1);
+
+        let mut unordered = data.clone().release();
+        unordered[..5].copy_from_slice(&[2, 1, b'B', 1, b'A']);
+        let unordered = Confined::try_from(unordered).unwrap();
+        assert!(Lib::from_strict_serialized::<MAX>(unordered).is_err());
+
+        let mut repeated = data.release();
+        repeated[..5].copy_from_slice(&[2, 1, b'A', 1, b'A']);
+        let repeated = Confined::try_from(repeated).unwrap();
+        assert!(Lib::from_strict_serialized::<MAX>(repeated).is_err());
+    }
+
     #[test]
     fn lib_id_from_str() {
         let id = Lib::strict_dumb().lib_id();
@@ -207,4 +691,184 @@ mod test {
 
         assert_eq!(id, LibId::from_str("uZkzX1J9i5EvGTfJ1TB79pOBvKq5x1U2n4qd8Nso3Ag").unwrap());
     }
+
+    #[test]
+    fn lib_site_display_from_str() {
+        let id = Lib::strict_dumb().lib_id();
+        let site = LibSite::new(id, 0x20);
+        let s = "alu:uZkzX1J9-i5EvGTf-J1TB79p-OBvKq5x-1U2n4qd-8Nso3Ag#reunion-cable-tractor@0020#h";
+        assert_eq!(site.to_string(), s);
+        assert_eq!(Site::new(id, 0x20).to_string(), s);
+        assert_eq!(LibSite::from_str(s).unwrap(), site);
+        assert_eq!(Site::<LibId>::from_str(s).unwrap(), Site::new(id, 0x20));
+
+        let site = LibSite::new(id, 0xABCD);
+        assert_eq!(LibSite::from_str(&site.to_string()).unwrap(), site);
+        for offset in ["abcd#h", "AbCd#h"] {
+            let s = format!("alu:uZkzX1J9i5EvGTfJ1TB79pOBvKq5x1U2n4qd8Nso3Ag@{offset}");
+            assert_eq!(LibSite::from_str(&s).unwrap(), site);
+        }
+        assert_eq!(LibSite::from_str(&format!("{id}@7#h")).unwrap(), LibSite::new(id, 7));
+    }
+
+    #[test]
+    fn lib_site_from_str_invalid() {
+        let id = Lib::strict_dumb().lib_id();
+        for s in [s!(""), s!("0020#h"), id.to_string()] {
+            assert!(
+                matches!(LibSite::from_str(&s), Err(SiteParseError::NoSeparator(err)) if err == s)
+            );
+        }
+        for s in ["@0020#h", "alu:invalid@0020#h", "x@0020#h"] {
+            assert!(
+                matches!(LibSite::from_str(s), Err(SiteParseError::InvalidId(err, _)) if err == s)
+            );
+        }
+        for offset in [
+            "", "#h", "0020", "0020#H", "0020h", "+020#h", "10000#h", "00200#h", "0x20#h",
+            "g020#h", "0020#h ",
+        ] {
+            let s = format!("{id}@{offset}");
+            assert!(
+                matches!(LibSite::from_str(&s), Err(SiteParseError::InvalidOffset(err)) if err == s)
+            );
+        }
+        assert_eq!(
+            SiteParseError::<Baid64ParseError>::NoSeparator(s!("x")).to_string(),
+            "code site `x` has no `@` separator between the program identifier and the offset."
+        );
+    }
+
+    #[test]
+    fn identity() {
+        use std::collections::{BTreeSet, HashSet};
+
+        let lib = || {
+            Lib::with(
+                IsaId::canonical_set([IsaId::from("ALU")]),
+                SmallBlob::from_checked(vec![0x00, 0x41, 0x02]),
+                SmallBlob::from_checked(vec![0xDE, 0xAD]),
+                none!(),
+            )
+        };
+        let (lib1, lib2) = (lib(), lib());
+        assert_eq!(lib1.id.get(), None);
+        assert_eq!(lib1, lib2);
+        assert_eq!(lib1.id.get(), Some(&lib1.commit_id()));
+        assert_eq!(lib1.lib_id(), lib2.lib_id());
+        assert_eq!(lib1.clone().id.get(), Some(&lib1.lib_id()));
+
+        let mut lib3 = lib1.clone();
+        lib3.data_mut().push(0xBE).unwrap();
+        assert_eq!(lib3.id.get(), None);
+        assert_ne!(lib3, lib1);
+        assert_eq!(lib3.lib_id(), lib3.commit_id());
+        assert_eq!(lib3.cmp(&lib1), lib3.lib_id().cmp(&lib1.lib_id()));
+
+        assert_eq!(HashSet::<_>::from_iter([lib1.clone(), lib2.clone(), lib3.clone()]).len(), 2);
+        assert_eq!(BTreeSet::from_iter([lib1, lib2, lib3]).len(), 2);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn serde_roundtrip() {
+        use alloc::collections::BTreeMap;
+
+        let lib = Lib::with(
+            IsaId::canonical_set([IsaId::from("ALU"), IsaId::from("GFA")]),
+            SmallBlob::try_from(vec![0x00, 0x41, 0x02]).unwrap(),
+            SmallBlob::try_from(vec![0xDE, 0xAD, 0xBE, 0xEF]).unwrap(),
+            TinyOrdSet::try_from_iter([LibId::from([1u8; 32]), LibId::from([2u8; 32])]).unwrap(),
+        );
+
+        let (id1, id2, id3) = ("01".repeat(32), "02".repeat(32), "03".repeat(32));
+        let json = serde_json::to_string(&lib).unwrap();
+        assert_eq!(
+            json,
+            format!(
+                r#"{{"isae":["ALU","GFA"],"code":"004102","data":"deadbeef","libs":["{id1}","{id2}"]}}"#
+            )
+        );
+        assert_eq!(serde_json::from_str::<Lib>(&json).unwrap(), lib);
+
+        let bin = bincode::serialize(&lib).unwrap();
+        // Binary formats keep the segments as raw bytes
+        assert!(bin.windows(4).any(|w| w == [0xDE, 0xAD, 0xBE, 0xEF]));
+        assert_eq!(bincode::deserialize::<Lib>(&bin).unwrap(), lib);
+
+        let site = LibSite::new(LibId::from([3u8; 32]), 0x1234);
+        let json = serde_json::to_string(&site).unwrap();
+        assert_eq!(json, format!(r#""{site}""#));
+        assert_eq!(serde_json::from_str::<LibSite>(&json).unwrap(), site);
+        let map = BTreeMap::from([(site, 1u8)]);
+        let json = serde_json::to_string(&map).unwrap();
+        assert_eq!(json, format!(r#"{{"{site}":1}}"#));
+        assert_eq!(serde_json::from_str::<BTreeMap<LibSite, u8>>(&json).unwrap(), map);
+        assert!(serde_json::from_str::<LibSite>(r#""x@1234#h""#).is_err());
+        let bin = bincode::serialize(&site).unwrap();
+        assert_eq!(bin.len(), 34);
+        assert_eq!(bincode::deserialize::<LibSite>(&bin).unwrap(), site);
+        let site = Site::new(site.lib_id, site.offset);
+        let json = serde_json::to_string(&site).unwrap();
+        assert_eq!(json, format!(r#"{{"progId":"{id3}","offset":4660}}"#));
+        assert_eq!(serde_json::from_str::<Site<LibId>>(&json).unwrap(), site);
+        let bin = bincode::serialize(&site).unwrap();
+        assert_eq!(bincode::deserialize::<Site<LibId>>(&bin).unwrap(), site);
+
+        assert!(
+            serde_json::from_str::<Lib>(r#"{"isae":[],"code":"0x","data":"","libs":[]}"#).is_err()
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn serde_lib_id() {
+        let lib = Lib::with(
+            IsaSeg::new(),
+            SmallBlob::try_from(vec![0x00, 0x41, 0x02]).unwrap(),
+            none!(),
+            none!(),
+        );
+        let json = |id: LibId| {
+            format!(r#"{{"id":"{id:x}","isae":[],"code":"004102","data":"","libs":[]}}"#)
+        };
+        assert_eq!(serde_json::from_str::<Lib>(&json(lib.lib_id())).unwrap(), lib);
+        let err = serde_json::from_str::<Lib>(&json(LibId::from([1u8; 32]))).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("doesn't match the library segments"));
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn serde_oversized() {
+        let code = "00".repeat(u16::MAX as usize + 1);
+        let json = format!(r#"{{"isae":[],"code":"{code}","data":"","libs":[]}}"#);
+        assert!(serde_json::from_str::<Lib>(&json).is_err());
+        let json = format!(r#"{{"isae":[],"code":"{}","data":"","libs":[]}}"#, &code[2..]);
+        assert_eq!(serde_json::from_str::<Lib>(&json).unwrap().code().len(), u16::MAX as usize);
+
+        let isae = (0..256)
+            .map(|no| format!(r#""X{no}""#))
+            .collect::<Vec<_>>()
+            .join(",");
+        let json = format!(r#"{{"isae":[{isae}],"code":"","data":"","libs":[]}}"#);
+        assert!(serde_json::from_str::<Lib>(&json).is_err());
+        let libs = (0..256u16)
+            .map(|no| {
+                let mut id = [0u8; 32];
+                id[..2].copy_from_slice(&no.to_le_bytes());
+                format!(r#""{:x}""#, LibId::from(id))
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+        let json = format!(r#"{{"isae":[],"code":"","data":"","libs":[{libs}]}}"#);
+        assert!(serde_json::from_str::<Lib>(&json).is_err());
+
+        // Binary formats enforce the same limits
+        let mut bin = bincode::serialize(&Lib::strict_dumb()).unwrap();
+        bin[8..16].copy_from_slice(&(u16::MAX as u64 + 1).to_le_bytes());
+        bin.extend(vec![0u8; u16::MAX as usize + 1]);
+        assert!(bincode::deserialize::<Lib>(&bin).is_err());
+    }
 }
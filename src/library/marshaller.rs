@@ -28,7 +28,7 @@ use amplify::confinement::SmallBlob;
 use amplify::num::{u1, u2, u3, u4, u5, u6, u7};
 
 use super::{LibId, LibsSeg};
-use crate::isa::{BytecodeRead, BytecodeWrite, CodeEofError};
+use crate::isa::{BytecodeRead, BytecodeWrite, CodeEofError, DataReadError};
 
 /// Errors write operations
 #[derive(Clone, Copy, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Display, Error, From)]
@@ -38,15 +38,30 @@ pub enum MarshallError {
     #[from(CodeEofError)]
     CodeNotFittingSegment,
 
-    /// data size {0} exceeds limit of 0xFF bytes.
+    /// code size {0} exceeds limit of 0xFFFF bytes.
+    CodeExceedsLimit(usize),
+
+    /// data size {0} exceeds limit of 0xFFFF bytes.
     DataExceedsLimit(usize),
 
-    /// attempt to write data which does not fit code segment.
+    /// attempt to write data which does not fit data segment.
     DataNotFittingSegment,
 
     /// attempt to write library reference for the lib id {0} which is not a part of program
     /// segment.
     LibAbsent(LibId),
+
+    /// library reference {0} points outside of the libs segment.
+    LibRefOutOfBounds(u8),
+
+    /// operands of the instruction at offset {instr_offset:#06X} don't end at a byte boundary,
+    /// leaving {dangling_bits} dangling bits.
+    UnalignedOpcode {
+        /// Offset of the instruction opcode in the code segment.
+        instr_offset: u16,
+        /// Number of bits written into the last, partially filled byte.
+        dangling_bits: u8,
+    },
 }
 
 /// Marshals instructions to and from bytecode representation.
@@ -96,6 +111,27 @@ where Self: 'a
         }
     }
 
+    /// Creates a marshaller continuing to write after the end of already produced code and data
+    /// segments.
+    ///
+    /// The bytecode must end at a byte margin, which is always the case for a bytecode produced
+    /// by encoding complete instructions.
+    #[inline]
+    pub(crate) fn resume(bytecode: Vec<u8>, data: Vec<u8>, libs: &'a LibsSeg) -> Self {
+        debug_assert!(bytecode.len() <= u16::MAX as usize);
+        Self {
+            byte_pos: bytecode.len() as u16,
+            bit_pos: u3::MIN,
+            bytecode,
+            data,
+            libs,
+        }
+    }
+
+    /// Returns raw code and data buffers without the confinement check.
+    #[inline]
+    pub(crate) fn into_buffers(self) -> (Vec<u8>, Vec<u8>) { (self.bytecode, self.data) }
+
     /// Completes marshalling, returning produced data segment.
     ///
     /// # Panics
@@ -126,17 +162,57 @@ where
 {
     /// Create marshaller from byte string utilizing existing bytecode.
     ///
-    /// # Panics
+    /// The data and libs segments must be the ones of the library containing the bytecode: while
+    /// the references outside of the segments are detected on decoding, the references inside them
+    /// can't be checked to correspond to the bytecode.
+    ///
+    /// # Errors
     ///
-    /// If the length of the bytecode or data segment exceeds 0xFF.
+    /// If the length of the bytecode or data segment exceeds 0xFFFF bytes.
+    pub fn with(bytecode: C, data: D, libs: &'a LibsSeg) -> Result<Self, MarshallError> {
+        let code_len = bytecode.as_ref().len();
+        if code_len > u16::MAX as usize {
+            return Err(MarshallError::CodeExceedsLimit(code_len));
+        }
+        let data_len = data.as_ref().len();
+        if data_len > u16::MAX as usize {
+            return Err(MarshallError::DataExceedsLimit(data_len));
+        }
+        Ok(Self::with_unchecked(bytecode, data, libs))
+    }
+
+    /// Create marshaller from segments which are known to fit the limits, for instance the ones of
+    /// a [`super::Lib`].
     #[inline]
-    pub fn with(bytecode: C, data: D, libs: &'a LibsSeg) -> Self {
+    pub(crate) fn with_unchecked(bytecode: C, data: D, libs: &'a LibsSeg) -> Self {
+        debug_assert!(bytecode.as_ref().len() <= u16::MAX as usize);
+        debug_assert!(data.as_ref().len() <= u16::MAX as usize);
         Self { bytecode, byte_pos: 0, bit_pos: u3::MIN, data, libs }
     }
 
-    /// Returns the current offset of the marshaller
+    /// Returns the current offset of the marshaller, as a byte position in the code segment and
+    /// a bit position within that byte.
+    ///
+    /// Once an instruction is completely read or written, the bit position is always zero.
     pub const fn offset(&self) -> (u16, u3) { (self.byte_pos, self.bit_pos) }
 
+    /// Returns whether the end of the code segment is reached.
+    #[inline]
+    pub fn is_eof(&self) -> bool { self.byte_pos as usize >= self.bytecode.as_ref().len() }
+
+    /// Resolves the library reference, i.e. a position in the libs segment, into the library id.
+    ///
+    /// # Errors
+    ///
+    /// If the position is outside of the libs segment.
+    pub fn lib_ref(&self, pos: u8) -> Result<LibId, MarshallError> {
+        self.libs
+            .iter()
+            .nth(pos as usize)
+            .copied()
+            .ok_or(MarshallError::LibRefOutOfBounds(pos))
+    }
+
     fn read(&mut self, bit_count: u5) -> Result<u32, CodeEofError> {
         let mut ret = 0u32;
         let mut cnt = bit_count.to_u8();
@@ -228,25 +304,39 @@ where
     D: AsRef<[u8]> + AsMut<[u8]> + Extend<u8>,
     Self: 'a,
 {
-    fn write_unique(&mut self, bytes: &[u8]) -> Result<u16, MarshallError> {
-        // We write the value only if the value is not yet present in the data segment
+    /// Writes a byte string to the data segment, reusing the data already present in it.
+    ///
+    /// If the byte string is already present in the data segment, including as a part of some
+    /// other data, its offset is returned and the data segment is not changed. Otherwise, the
+    /// byte string is appended to the data segment.
+    ///
+    /// A byte string is never placed such that it overlaps the end of the data segment, even if
+    /// the segment ends with a prefix of it: this keeps the data segment layout, and thus ids of
+    /// the assembled libraries, stable.
+    ///
+    /// # Returns
+    ///
+    /// Offset of the byte string in the data segment.
+    ///
+    /// # Errors
+    ///
+    /// If the byte string doesn't fit the data segment. In this case, the data segment is not
+    /// changed.
+    pub fn write_data_dedup(&mut self, bytes: &[u8]) -> Result<u16, MarshallError> {
+        let data = self.data.as_ref();
         let len = bytes.len();
-        let offset = self.data.as_ref().len();
         if len == 0 {
-            Ok(offset as u16)
-        } else if let Some(offset) = self
-            .data
-            .as_ref()
-            .windows(len)
-            .position(|window| window == bytes)
-        {
-            Ok(offset as u16)
-        } else if offset + len > u16::MAX as usize {
-            Err(MarshallError::DataNotFittingSegment)
-        } else {
-            self.data.extend(bytes.iter().copied());
-            Ok(offset as u16)
+            return Ok(data.len() as u16);
         }
+        if let Some(offset) = data.windows(len).position(|window| window == bytes) {
+            return Ok(offset as u16);
+        }
+        let offset = data.len();
+        if offset + len > u16::MAX as usize {
+            return Err(MarshallError::DataNotFittingSegment);
+        }
+        self.data.extend(bytes.iter().copied());
+        Ok(offset as u16)
     }
 }
 
@@ -270,7 +360,7 @@ where
     }
 
     #[inline]
-    fn is_eof(&self) -> bool { self.byte_pos as usize >= self.bytecode.as_ref().len() }
+    fn is_eof(&self) -> bool { Marshaller::is_eof(self) }
 
     fn peek_byte(&self) -> Result<u8, CodeEofError> {
         if self.is_eof() {
@@ -340,22 +430,28 @@ where
         Ok(f(buf))
     }
 
-    fn read_bytes(&mut self) -> Result<(SmallBlob, bool), CodeEofError> {
-        let pos = self.read_word()? as usize;
-        let end = pos + self.read_word()? as usize;
-        let ck = end >= self.data.as_ref().len();
-        let data = &self.data.as_ref()[pos.min(0xFF)..end.min(0xFF)];
-        Ok((SmallBlob::from_slice_checked(data), ck))
+    fn read_bytes(&mut self) -> Result<SmallBlob, DataReadError> {
+        let offset = self.read_word()?;
+        let len = self.read_word()?;
+        let data = self.data.as_ref();
+        let end = offset as usize + len as usize;
+        if end > data.len() {
+            return Err(DataReadError::OutOfDataSegment { offset, len, seg_len: data.len() });
+        }
+        Ok(SmallBlob::from_checked(data[offset as usize..end].to_vec()))
     }
 
     fn read_ref(&mut self) -> Result<LibId, CodeEofError>
     where LibId: Sized {
-        let pos = self.read_byte()? as usize;
-        Ok(self.libs.iter().nth(pos).copied().unwrap_or_default())
+        let pos = self.read_byte()?;
+        self.lib_ref(pos).map_err(|_| CodeEofError)
     }
 
-    fn check_aligned(&self) {
-        debug_assert_eq!(self.bit_pos, u3::ZERO, "not all instruction operands are read")
+    fn check_aligned(&self) -> Result<(), CodeEofError> {
+        if self.bit_pos != u3::ZERO {
+            return Err(CodeEofError);
+        }
+        Ok(())
     }
 }
 
@@ -367,6 +463,9 @@ where
 {
     type Error = MarshallError;
 
+    #[inline]
+    fn pos(&self) -> u16 { self.byte_pos }
+
     fn write_1bit(&mut self, data: u1) -> Result<(), MarshallError> {
         self.write(data.into_u8() as u32, u5::with(1))
             .map_err(MarshallError::from)
@@ -416,18 +515,19 @@ where
         if LEN >= u16::MAX as usize {
             return Err(MarshallError::DataExceedsLimit(LEN));
         }
-        let offset = self.write_unique(&data)?;
+        let offset = self.write_data_dedup(&data)?;
         self.write_word(offset)
     }
 
-    fn write_bytes(&mut self, data: &[u8]) -> Result<(), Self::Error> {
+    fn write_bytes(&mut self, data: &[u8]) -> Result<(u16, u16), Self::Error> {
         let len = data.len();
-        if len >= u16::MAX as usize {
+        if len > u16::MAX as usize {
             return Err(MarshallError::DataExceedsLimit(len));
         }
-        let offset = self.write_unique(data)?;
+        let offset = if len == 0 { 0 } else { self.write_data_dedup(data)? };
         self.write_word(offset)?;
-        self.write_word(len as u16)
+        self.write_word(len as u16)?;
+        Ok((offset, len as u16))
     }
 
     fn write_ref(&mut self, id: LibId) -> Result<(), Self::Error> {
@@ -439,43 +539,52 @@ where
         self.write_byte(pos as u8)
     }
 
-    fn check_aligned(&self) {
-        debug_assert_eq!(self.bit_pos, u3::ZERO, "not all instruction operands are written")
+    fn check_aligned(&self, instr_offset: u16) -> Result<(), MarshallError> {
+        if self.bit_pos != u3::ZERO {
+            return Err(MarshallError::UnalignedOpcode {
+                instr_offset,
+                dangling_bits: self.bit_pos.to_u8(),
+            });
+        }
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
     #![cfg_attr(coverage_nightly, coverage(off))]
+    use core::ops::RangeInclusive;
+
     use super::*;
+    use crate::isa::Bytecode;
 
     #[test]
     fn read() {
         let libseg = LibsSeg::default();
-        let mut marshaller = Marshaller::with([0b01010111, 0b00001001], [], &libseg);
+        let mut marshaller = Marshaller::with([0b01010111, 0b00001001], [], &libseg).unwrap();
         assert_eq!(marshaller.read_2bits().unwrap().to_u8(), 0b00000011);
         assert_eq!(marshaller.read_2bits().unwrap().to_u8(), 0b00000001);
         assert_eq!(marshaller.read_byte().unwrap(), 0b10010101);
 
-        let mut marshaller = Marshaller::with([0b01010111, 0b00001001], [], &libseg);
+        let mut marshaller = Marshaller::with([0b01010111, 0b00001001], [], &libseg).unwrap();
         assert_eq!(marshaller.read_2bits().unwrap().to_u8(), 0b00000011);
         assert_eq!(marshaller.read_3bits().unwrap().to_u8(), 0b00000101);
         assert_eq!(marshaller.read_byte().unwrap(), 0b01001010);
 
-        let mut marshaller = Marshaller::with([0b01110111, 0b00001111], [], &libseg);
+        let mut marshaller = Marshaller::with([0b01110111, 0b00001111], [], &libseg).unwrap();
         assert_eq!(marshaller.read_byte().unwrap(), 0b01110111);
         assert_eq!(marshaller.read_3bits().unwrap().to_u8(), 0b00000111);
         assert_eq!(marshaller.read_5bits().unwrap().to_u8(), 0b00000001);
 
         let bytes = 0b11101011_11110000_01110111;
-        let mut marshaller = Marshaller::with(u32::to_le_bytes(bytes), [], &libseg);
+        let mut marshaller = Marshaller::with(u32::to_le_bytes(bytes), [], &libseg).unwrap();
         assert_eq!(marshaller.read(u5::with(24)).unwrap(), bytes);
     }
 
     #[test]
     fn read_eof() {
         let libseg = LibsSeg::default();
-        let mut marshaller = Marshaller::with([0b01010111], [], &libseg);
+        let mut marshaller = Marshaller::with([0b01010111], [], &libseg).unwrap();
         assert_eq!(marshaller.read_2bits().unwrap().to_u8(), 0b00000011);
         assert_eq!(marshaller.read_2bits().unwrap().to_u8(), 0b00000001);
         assert!(marshaller.read_byte().is_err());
@@ -484,7 +593,7 @@ mod tests {
     #[test]
     fn write() {
         let libseg = LibsSeg::default();
-        let mut marshaller = Marshaller::with(vec![], vec![], &libseg);
+        let mut marshaller = Marshaller::with(vec![], vec![], &libseg).unwrap();
         marshaller.write_2bits(u2::with(0b00000011)).unwrap();
         marshaller.write_3bits(u3::with(0b00000101)).unwrap();
         marshaller.write_7bits(u7::with(0b01011111)).unwrap();
@@ -497,7 +606,7 @@ mod tests {
         marshaller.write_fixed(255u8.to_le_bytes()).unwrap();
         let (code, data) = marshaller.finish();
 
-        let mut marshaller = Marshaller::with(code, data, &libseg);
+        let mut marshaller = Marshaller::with(code, data, &libseg).unwrap();
         assert_eq!(marshaller.read_2bits().unwrap().to_u8(), 0b00000011);
         assert_eq!(marshaller.read_3bits().unwrap().to_u8(), 0b00000101);
         assert_eq!(marshaller.read_7bits().unwrap().to_u8(), 0b01011111);
@@ -511,15 +620,102 @@ mod tests {
     #[test]
     fn write_data() {
         let libseg = LibsSeg::default();
-        let mut marshaller = Marshaller::with(vec![], vec![], &libseg);
+        let mut marshaller = Marshaller::with(vec![], vec![], &libseg).unwrap();
         marshaller.write_fixed(256u16.to_le_bytes()).unwrap();
         assert_eq!(marshaller.data, vec![0, 1]);
     }
 
+    #[test]
+    fn write_bytes() {
+        let libseg = LibsSeg::default();
+        let mut marshaller = Marshaller::with(vec![], vec![], &libseg).unwrap();
+        assert_eq!(marshaller.write_bytes(b"alu").unwrap(), (0, 3));
+        assert_eq!(marshaller.write_bytes(b"").unwrap(), (0, 0));
+        assert_eq!(marshaller.write_bytes(b"vm").unwrap(), (3, 2));
+        // Substrings of the already written data are not duplicated
+        assert_eq!(marshaller.write_bytes(b"luv").unwrap(), (1, 3));
+        assert_eq!(marshaller.data, b"aluvm");
+        let (code, data) = marshaller.finish();
+        assert_eq!(code.len(), 16);
+
+        let mut marshaller = Marshaller::with(code, data, &libseg).unwrap();
+        assert_eq!(marshaller.read_bytes().unwrap().as_slice(), b"alu");
+        assert_eq!(marshaller.read_bytes().unwrap().as_slice(), b"");
+        assert_eq!(marshaller.read_bytes().unwrap().as_slice(), b"vm");
+        assert_eq!(marshaller.read_bytes().unwrap().as_slice(), b"luv");
+        assert_eq!(marshaller.read_bytes(), Err(DataReadError::CodeEof));
+    }
+
+    #[test]
+    fn write_data_dedup() {
+        let libseg = LibsSeg::default();
+        let mut marshaller = Marshaller::with(vec![], vec![], &libseg).unwrap();
+        assert_eq!(marshaller.write_data_dedup(b"alu").unwrap(), 0);
+        assert_eq!(marshaller.write_data_dedup(b"alu").unwrap(), 0);
+        assert_eq!(marshaller.write_data_dedup(b"lu").unwrap(), 1);
+        // Matches may overlap each other, but not the end of the data segment
+        assert_eq!(marshaller.write_data_dedup(b"luvm").unwrap(), 3);
+        assert_eq!(marshaller.write_data_dedup(b"ulu").unwrap(), 2);
+        assert_eq!(marshaller.write_data_dedup(b"").unwrap(), 7);
+        assert_eq!(marshaller.data, b"aluluvm");
+    }
+
+    #[test]
+    fn write_data_dedup_limits() {
+        let libseg = LibsSeg::default();
+        let mut data = vec![0xA5; 0xFFFE];
+        data.push(0x5A);
+        let mut marshaller = Marshaller::with(vec![], data, &libseg).unwrap();
+        assert_eq!(
+            marshaller.write_data_dedup(&[0x5A, 0x5A]),
+            Err(MarshallError::DataNotFittingSegment)
+        );
+        assert_eq!(marshaller.data.len(), 0xFFFF);
+        // Existing data is still reused when the data segment is full
+        assert_eq!(marshaller.write_data_dedup(&[0xA5, 0x5A]).unwrap(), 0xFFFD);
+        assert_eq!(marshaller.write_data_dedup(&[0xA5; 0x100]).unwrap(), 0);
+        assert_eq!(marshaller.data.len(), 0xFFFF);
+    }
+
+    #[test]
+    fn read_bytes_out_of_bounds() {
+        let libseg = LibsSeg::default();
+        let code = [0x03, 0x00, 0x03, 0x00, 0x05, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+        let mut marshaller = Marshaller::with(code, *b"aluvm", &libseg).unwrap();
+        assert_eq!(
+            marshaller.read_bytes(),
+            Err(DataReadError::OutOfDataSegment { offset: 3, len: 3, seg_len: 5 })
+        );
+        // Empty byte string at the end of the data segment
+        assert_eq!(marshaller.read_bytes().unwrap().as_slice(), b"");
+        assert_eq!(marshaller.read_bytes().unwrap().as_slice(), b"");
+        assert_eq!(marshaller.read_bytes(), Err(DataReadError::CodeEof));
+
+        let mut marshaller = Marshaller::with([0xFF, 0xFF, 0xFF, 0xFF], [], &libseg).unwrap();
+        assert_eq!(
+            marshaller.read_bytes(),
+            Err(DataReadError::OutOfDataSegment { offset: 0xFFFF, len: 0xFFFF, seg_len: 0 })
+        );
+    }
+
+    #[test]
+    fn write_bytes_limits() {
+        let libseg = LibsSeg::default();
+        let mut marshaller = Marshaller::with(vec![], vec![], &libseg).unwrap();
+        assert_eq!(
+            marshaller.write_bytes(&[0xA5; 0x10000]),
+            Err(MarshallError::DataExceedsLimit(0x10000))
+        );
+        assert_eq!(marshaller.write_bytes(&[0xA5; 0xFFFF]).unwrap(), (0, 0xFFFF));
+        assert_eq!(marshaller.write_bytes(&[0xA5; 0x10]).unwrap(), (0, 0x10));
+        assert_eq!(marshaller.write_bytes(&[0x5A]), Err(MarshallError::DataNotFittingSegment));
+        assert_eq!(marshaller.write_bytes(&[]).unwrap(), (0, 0));
+    }
+
     #[test]
     fn write_eof() {
         let libseg = LibsSeg::default();
-        let mut marshaller = Marshaller::with(vec![0x00; 0xFFFD], vec![], &libseg);
+        let mut marshaller = Marshaller::with(vec![0x00; 0xFFFD], vec![], &libseg).unwrap();
         marshaller.seek(0xFFFD).unwrap_err();
         marshaller.byte_pos = 0xFFFD;
         marshaller.write_2bits(u2::with(0b00000011)).unwrap();
@@ -533,7 +729,7 @@ mod tests {
     #[should_panic(expected = "incomplete marshalling")]
     fn incomplete_byte() {
         let libseg = LibsSeg::default();
-        let mut marshaller = Marshaller::with(vec![], vec![], &libseg);
+        let mut marshaller = Marshaller::with(vec![], vec![], &libseg).unwrap();
         marshaller.write_2bits(u2::with(0b00000011)).unwrap();
         marshaller.write_3bits(u3::with(0b00000101)).unwrap();
         marshaller.write_7bits(u7::with(0b00000000)).unwrap();
@@ -543,7 +739,7 @@ mod tests {
     #[test]
     fn bit_order() {
         let libseg = LibsSeg::default();
-        let mut marshaller = Marshaller::with(vec![], vec![], &libseg);
+        let mut marshaller = Marshaller::with(vec![], vec![], &libseg).unwrap();
         marshaller.write_2bits(u2::with(0b00000011)).unwrap();
         marshaller.write_3bits(u3::with(0b00000101)).unwrap();
         marshaller.write_3bits(u3::with(0b00000001)).unwrap();
@@ -551,4 +747,89 @@ mod tests {
         assert_eq!(code.release(), vec![0b0011_0111u8]);
         assert!(data.is_empty());
     }
+
+    /// Instruction with a three-bit operand, which doesn't end at a byte boundary.
+    #[derive(Debug, PartialEq, Eq)]
+    struct Unaligned;
+
+    impl Bytecode<LibId> for Unaligned {
+        fn op_range() -> RangeInclusive<u8> { 0..=0 }
+
+        fn opcode_byte(&self) -> u8 { 0 }
+
+        fn code_byte_len(&self) -> u16 { 2 }
+
+        fn external_ref(&self) -> Option<LibId> { None }
+
+        fn encode_operands<W>(&self, writer: &mut W) -> Result<(), W::Error>
+        where W: BytecodeWrite<LibId> {
+            writer.write_3bits(u3::with(0b101))
+        }
+
+        fn decode_operands<R>(reader: &mut R, _: u8) -> Result<Self, CodeEofError>
+        where R: BytecodeRead<LibId> {
+            reader.read_3bits()?;
+            Ok(Unaligned)
+        }
+    }
+
+    #[test]
+    fn unaligned_encode() {
+        let libseg = LibsSeg::default();
+        let mut marshaller = Marshaller::with(vec![], vec![], &libseg).unwrap();
+        marshaller.write_word(0xFFFF).unwrap();
+        assert_eq!(
+            Unaligned.encode_instr(&mut marshaller),
+            Err(MarshallError::UnalignedOpcode { instr_offset: 2, dangling_bits: 3 })
+        );
+        assert_eq!(
+            MarshallError::UnalignedOpcode { instr_offset: 2, dangling_bits: 3 }.to_string(),
+            "operands of the instruction at offset 0x0002 don't end at a byte boundary, leaving 3 \
+             dangling bits."
+        );
+    }
+
+    #[test]
+    fn segment_limits() {
+        let libseg = LibsSeg::default();
+        let max = vec![0u8; 0xFFFF];
+        let over = vec![0u8; 0x10000];
+        assert!(Marshaller::with(&max, &max, &libseg).is_ok());
+        assert_eq!(
+            Marshaller::with(&over, &max, &libseg).unwrap_err(),
+            MarshallError::CodeExceedsLimit(0x10000)
+        );
+        assert_eq!(
+            Marshaller::with(&max, &over, &libseg).unwrap_err(),
+            MarshallError::DataExceedsLimit(0x10000)
+        );
+    }
+
+    #[test]
+    fn read_ref() {
+        let lib_id = LibId::from([0xA5u8; 32]);
+        let mut libseg = LibsSeg::default();
+        libseg.push(lib_id).unwrap();
+        let mut marshaller = Marshaller::with([0x00, 0x01, 0xFF], [], &libseg).unwrap();
+        assert_eq!(marshaller.read_ref(), Ok(lib_id));
+        assert_eq!(marshaller.read_ref(), Err(CodeEofError));
+        assert_eq!(marshaller.read_ref(), Err(CodeEofError));
+        assert!(marshaller.is_eof());
+        assert_eq!(marshaller.read_ref(), Err(CodeEofError));
+
+        assert_eq!(marshaller.lib_ref(0), Ok(lib_id));
+        assert_eq!(marshaller.lib_ref(0xFF), Err(MarshallError::LibRefOutOfBounds(0xFF)));
+        assert_eq!(
+            MarshallError::LibRefOutOfBounds(0xFF).to_string(),
+            "library reference 255 points outside of the libs segment."
+        );
+    }
+
+    #[test]
+    fn unaligned_decode() {
+        let libseg = LibsSeg::default();
+        let mut marshaller = Marshaller::with([0x00, 0b101], [], &libseg).unwrap();
+        assert_eq!(Unaligned::decode_instr(&mut marshaller), Err(CodeEofError));
+        assert_eq!(marshaller.offset(), (1, u3::with(3)));
+    }
 }
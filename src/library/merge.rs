@@ -0,0 +1,366 @@
+// Reference rust implementation of AluVM (arithmetic logic unit virtual machine).
+// To find more on AluVM please check <https://aluvm.org>
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Designed in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+// Written in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+//
+// Copyright (C) 2021-2024 LNP/BP Standards Association, Switzerland.
+// Copyright (C) 2024-2025 Laboratories for Ubiquitous Deterministic Computing (UBIDECO),
+//                         Institute for Distributed and Cognitive Systems (InDCS), Switzerland.
+// Copyright (C) 2021-2025 Dr Maxim Orlovsky.
+// All rights under the above copyrights are reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use this file except
+// in compliance with the License. You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed under the License
+// is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express
+// or implied. See the License for the specific language governing permissions and limitations under
+// the License.
+
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::vec::Vec;
+
+use amplify::confinement::SmallBlob;
+
+use super::{Lib, LibId, LibSite, LibsSeg, MarshallError, Marshaller};
+use crate::isa::{GotoTarget, Instruction};
+use crate::IsaId;
+
+/// Errors merging libraries with [`Lib::merge`].
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum MergeError {
+    /// no libraries to merge.
+    NoLibs,
+
+    /// library {0} is provided for merging more than once.
+    Duplicate(LibId),
+
+    /// code of library {0} can't be decoded under the instruction set.
+    InvalidCode(LibId),
+
+    /// instruction at offset {1:#06X} of library {0} jumps to offset {2:#06X}, which is not an
+    /// instruction boundary.
+    InvalidJump(LibId, u16, u16),
+
+    /// relative jump of the instruction at offset {1:#06X} of library {0} doesn't fit the merged
+    /// code.
+    JumpOutOfRange(LibId, u16),
+
+    /// merged code segment would have {0} bytes, exceeding the limit of 0xFFFF bytes.
+    CodeOversize(usize),
+
+    /// merged data segment would have {0} bytes, exceeding the limit of 0xFFFF bytes.
+    DataOversize(usize),
+
+    /// merged libs segment would reference {0} libraries, exceeding the limit of 255 libraries.
+    LibsOversize(usize),
+
+    /// Error encoding the merged code (see [`MarshallError`] for the details).
+    #[from]
+    #[display(inner)]
+    Encode(MarshallError),
+}
+
+/// Summary of a library merge performed by [`Lib::merge`].
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct MergeReport {
+    /// Identifier of the merged library.
+    pub lib_id: LibId,
+    /// Offsets in the merged code segment of each instruction of the merged libraries.
+    pub offsets: BTreeMap<LibSite, u16>,
+    /// Number of calls into the merged libraries replaced with local calls.
+    pub localized: usize,
+}
+
+impl MergeReport {
+    /// Returns the site in the merged library corresponding to an instruction of one of the
+    /// merged libraries.
+    pub fn site(&self, site: LibSite) -> Option<LibSite> {
+        self.offsets
+            .get(&site)
+            .map(|offset| LibSite::new(self.lib_id, *offset))
+    }
+}
+
+impl Lib {
+    /// Merges several libraries into a single one.
+    ///
+    /// The code segments are concatenated in the order of the libraries, with the local jump
+    /// targets rebased to the new offsets of the instructions. Data segments are also
+    /// concatenated, and references to the data are rebased by re-encoding the instructions. The
+    /// libs and ISA extensions segments are united.
+    ///
+    /// If `localize` is set, calls into the merged libraries are replaced with their local
+    /// equivalents (see [`Instruction::localize_goto`]), and the merged libraries which are no
+    /// longer referenced are excluded from the libs segment. Otherwise, such calls are kept, and
+    /// the original libraries remain required for the execution.
+    ///
+    /// Unlike the original library, whose execution halts at the end of its code segment, the
+    /// merged code proceeds to the code of the next library. Thus, the libraries must terminate
+    /// their execution explicitly, for instance with `stop` or `ret` instructions.
+    ///
+    /// # Returns
+    ///
+    /// The merged library and a report mapping the sites of the merged libraries into its code.
+    ///
+    /// # Errors
+    ///
+    /// If no libraries are provided or some of them are provided twice, if the code can't be
+    /// decoded or contains a jump to the middle of an instruction, or if any of the merged
+    /// segments exceeds its size limit.
+    pub fn merge<Isa>(libs: &[Lib], localize: bool) -> Result<(Lib, MergeReport), MergeError>
+    where Isa: Instruction<LibId> {
+        if libs.is_empty() {
+            return Err(MergeError::NoLibs);
+        }
+        let ids = libs.iter().map(Lib::lib_id).collect::<Vec<_>>();
+        let mut merged = BTreeSet::new();
+        for id in &ids {
+            if !merged.insert(*id) {
+                return Err(MergeError::Duplicate(*id));
+            }
+        }
+
+        // Instructions with their original sites, and the targets of localized calls together with
+        // the original call instructions
+        let mut code = Vec::<(LibSite, Isa, Option<(LibSite, Isa)>)>::new();
+        let mut offsets = BTreeMap::new();
+        let mut new_pos = 0usize;
+        for (lib, id) in libs.iter().zip(&ids) {
+            let lib_code = lib
+                .disassemble_with_offsets::<Isa>()
+                .map_err(|_| MergeError::InvalidCode(*id))?;
+            for (pos, mut instr) in lib_code {
+                let site = LibSite::new(*id, pos);
+                let mut target = None;
+                if let Some(remote) = instr.remote_goto_pos().filter(|_| localize) {
+                    let remote = LibSite::from(*remote);
+                    if merged.contains(&remote.lib_id) {
+                        if let Some(local) = instr.localize_goto(remote.offset) {
+                            target = Some((remote, instr));
+                            instr = local;
+                        }
+                    }
+                }
+                offsets.insert(site, new_pos as u16);
+                new_pos += instr.code_byte_len() as usize;
+                if new_pos > u16::MAX as usize {
+                    return Err(MergeError::CodeOversize(new_pos));
+                }
+                code.push((site, instr, target));
+            }
+        }
+
+        let mut localized = 0usize;
+        for (site, instr, target) in &mut code {
+            let new_pos = offsets[site];
+            if let Some((target, call)) = target {
+                let pos = *offsets.get(target).ok_or(MergeError::InvalidJump(
+                    target.lib_id,
+                    site.offset,
+                    target.offset,
+                ))?;
+                *instr = call
+                    .localize_goto(pos)
+                    .expect("the instruction was localized before");
+                localized += 1;
+                continue;
+            }
+            match instr.local_goto_pos() {
+                GotoTarget::None => {}
+                GotoTarget::Absolute(pos) => {
+                    *pos = *offsets
+                        .get(&LibSite::new(site.lib_id, *pos))
+                        .ok_or(MergeError::InvalidJump(site.lib_id, site.offset, *pos))?;
+                }
+                GotoTarget::Relative(shift) => {
+                    let target = site
+                        .offset
+                        .checked_add_signed(*shift as i16)
+                        .ok_or(MergeError::JumpOutOfRange(site.lib_id, site.offset))?;
+                    let target = *offsets
+                        .get(&LibSite::new(site.lib_id, target))
+                        .ok_or(MergeError::InvalidJump(site.lib_id, site.offset, target))?;
+                    *shift = i8::try_from(target as i32 - new_pos as i32)
+                        .map_err(|_| MergeError::JumpOutOfRange(site.lib_id, site.offset))?;
+                }
+                GotoTarget::RelativeWide(shift) => {
+                    let target = site
+                        .offset
+                        .checked_add_signed(*shift)
+                        .ok_or(MergeError::JumpOutOfRange(site.lib_id, site.offset))?;
+                    let target = *offsets
+                        .get(&LibSite::new(site.lib_id, target))
+                        .ok_or(MergeError::InvalidJump(site.lib_id, site.offset, target))?;
+                    *shift = i16::try_from(target as i32 - new_pos as i32)
+                        .map_err(|_| MergeError::JumpOutOfRange(site.lib_id, site.offset))?;
+                }
+                GotoTarget::Table(table) => {
+                    for pos in table {
+                        *pos = *offsets
+                            .get(&LibSite::new(site.lib_id, *pos))
+                            .ok_or(MergeError::InvalidJump(site.lib_id, site.offset, *pos))?;
+                    }
+                }
+            }
+        }
+
+        let data = libs
+            .iter()
+            .flat_map(|lib| lib.data().iter().copied())
+            .collect::<Vec<_>>();
+        if data.len() > u16::MAX as usize {
+            return Err(MergeError::DataOversize(data.len()));
+        }
+
+        let referenced = code
+            .iter()
+            .filter_map(|(_, instr, _)| instr.external_ref())
+            .collect::<BTreeSet<_>>();
+        let libs_segment = libs
+            .iter()
+            .flat_map(|lib| lib.libs().iter().copied())
+            .filter(|id| !merged.contains(id) || referenced.contains(id))
+            .collect::<BTreeSet<_>>();
+        let count = libs_segment.len();
+        let libs_segment =
+            LibsSeg::try_from_iter(libs_segment).map_err(|_| MergeError::LibsOversize(count))?;
+
+        let mut writer = Marshaller::resume(Vec::new(), data, &libs_segment);
+        for (_, instr, _) in &code {
+            instr.encode_instr(&mut writer)?;
+        }
+        let (code_segment, data_segment) = writer.into_buffers();
+
+        let lib = Lib::with(
+            IsaId::canonical_set(libs.iter().flat_map(|lib| lib.isae().iter().cloned())),
+            SmallBlob::from_checked(code_segment),
+            SmallBlob::from_checked(data_segment),
+            libs_segment,
+        );
+        let report = MergeReport { lib_id: lib.lib_id(), offsets, localized };
+        Ok((lib, report))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![cfg_attr(coverage_nightly, coverage(off))]
+
+    use super::*;
+    use crate::isa::{Alu64Instr, ArithmInstr, CtrlInstr, RegA};
+    use crate::regs::Status;
+    use crate::{CoreConfig, Site, Vm};
+
+    /// Library checking whether `A0` has the value of 42, failing `CK` otherwise.
+    fn callee() -> Lib {
+        let code: [Alu64Instr<LibId>; 7] = [
+            ArithmInstr::Put { dst: RegA::A2, val: 1 }.into(),
+            ArithmInstr::Put { dst: RegA::A1, val: 42 }.into(),
+            ArithmInstr::Eq { src1: RegA::A0, src2: RegA::A1 }.into(),
+            CtrlInstr::JiOvfl { pos: 14 }.into(),
+            CtrlInstr::Ret.into(),
+            // 14:
+            CtrlInstr::FailCk.into(),
+            CtrlInstr::Ret.into(),
+        ];
+        Lib::assemble(&code).unwrap()
+    }
+
+    fn caller(val: u64, callee: LibId) -> Lib {
+        let code: [Alu64Instr<LibId>; 5] = [
+            ArithmInstr::Put { dst: RegA::A0, val }.into(),
+            CtrlInstr::Call { site: Site::new(callee, 0) }.into(),
+            CtrlInstr::Sh { shift: 3 }.into(),
+            CtrlInstr::FailCk.into(),
+            CtrlInstr::Stop.into(),
+        ];
+        Lib::assemble(&code).unwrap()
+    }
+
+    fn exec(entry: LibSite, libs: &[&Lib]) -> (Status, Status) {
+        let mut vm = Vm::<Alu64Instr<LibId>>::with(CoreConfig::default(), ());
+        let status = vm.exec(entry, &(), |id| libs.iter().find(|lib| lib.lib_id() == id).copied());
+        (status, vm.core.co())
+    }
+
+    #[test]
+    fn merge() {
+        let callee = callee();
+        for val in [42, 7] {
+            let caller = caller(val, callee.lib_id());
+            let entry = LibSite::new(caller.lib_id(), 0);
+            let expected = exec(entry, &[&caller, &callee]);
+            assert_eq!(expected.0.is_ok(), val == 42);
+
+            let libs = [caller.clone(), callee.clone()];
+            let (merged, report) = Lib::merge::<Alu64Instr<LibId>>(&libs, false).unwrap();
+            assert_eq!(merged.code().len(), caller.code().len() + callee.code().len());
+            assert_eq!(&merged.data()[..caller.data().len()], caller.data().as_slice());
+            assert_eq!(merged.libs(), &LibsSeg::from_checked(bset![callee.lib_id()]));
+            assert_eq!(merged.isae(), callee.isae());
+            assert_eq!(report.localized, 0);
+            assert_eq!(report.site(entry), Some(LibSite::new(merged.lib_id(), 0)));
+            // The call still goes into the original library
+            assert_eq!(exec(report.site(entry).unwrap(), &[&merged, &callee]), expected);
+            assert_eq!(exec(report.site(entry).unwrap(), &[&merged]).0, Status::Fail);
+
+            let (merged, report) = Lib::merge::<Alu64Instr<LibId>>(&libs, true).unwrap();
+            assert_eq!(merged.code().len(), caller.code().len() + callee.code().len() - 1);
+            assert!(merged.libs().is_empty());
+            assert_eq!(report.localized, 1);
+            assert_eq!(
+                report.site(LibSite::new(callee.lib_id(), 14)),
+                Some(LibSite::new(merged.lib_id(), 11 + 14))
+            );
+            assert_eq!(report.site(LibSite::new(callee.lib_id(), 12)), None);
+            assert_eq!(exec(report.site(entry).unwrap(), &[&merged]), expected);
+        }
+    }
+
+    #[test]
+    fn merge_errors() {
+        let callee = callee();
+        assert_eq!(Lib::merge::<Alu64Instr<LibId>>(&[], false), Err(MergeError::NoLibs));
+        assert_eq!(
+            Lib::merge::<Alu64Instr<LibId>>(&[callee.clone(), callee.clone()], false),
+            Err(MergeError::Duplicate(callee.lib_id()))
+        );
+
+        let jump = Lib::assemble::<Alu64Instr<LibId>>(&[
+            CtrlInstr::Jmp { pos: 1 }.into(),
+            CtrlInstr::Stop.into(),
+        ])
+        .unwrap();
+        assert_eq!(
+            Lib::merge::<Alu64Instr<LibId>>(&[callee.clone(), jump.clone()], false),
+            Err(MergeError::InvalidJump(jump.lib_id(), 0, 1))
+        );
+
+        let nops = |stop: bool| {
+            let mut code = vec![Alu64Instr::<LibId>::Ctrl(CtrlInstr::Nop); 0x8000];
+            if stop {
+                code.push(CtrlInstr::Stop.into());
+            }
+            Lib::assemble(&code).unwrap()
+        };
+        assert_eq!(
+            Lib::merge::<Alu64Instr<LibId>>(&[nops(false), nops(true)], false),
+            Err(MergeError::CodeOversize(0x10000))
+        );
+
+        let (big, _) = callee.extend_data(&[0xAA; 0x8000]).unwrap();
+        let caller = caller(42, callee.lib_id());
+        let (big_caller, _) = caller.extend_data(&[0xAA; 0x8000]).unwrap();
+        assert_eq!(
+            Lib::merge::<Alu64Instr<LibId>>(&[big_caller, big], false),
+            Err(MergeError::DataOversize(0x10000 + callee.data().len() + caller.data().len()))
+        );
+    }
+}
@@ -0,0 +1,406 @@
+// Reference rust implementation of AluVM (arithmetic logic unit virtual machine).
+// To find more on AluVM please check <https://aluvm.org>
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Designed in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+// Written in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+//
+// Copyright (C) 2021-2024 LNP/BP Standards Association, Switzerland.
+// Copyright (C) 2024-2025 Laboratories for Ubiquitous Deterministic Computing (UBIDECO),
+//                         Institute for Distributed and Cognitive Systems (InDCS), Switzerland.
+// Copyright (C) 2021-2025 Dr Maxim Orlovsky.
+// All rights under the above copyrights are reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use this file except
+// in compliance with the License. You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed under the License
+// is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express
+// or implied. See the License for the specific language governing permissions and limitations under
+// the License.
+
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::vec::Vec;
+use core::fmt::{self, Display, Formatter};
+
+use super::{EdgeKind, Lib, LibId, Marshaller};
+use crate::isa::{BytecodeRead, FlowKind, Instruction};
+
+/// Maximal depth of the call stack reachable by a library.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Display)]
+pub enum StackDepth {
+    /// The call stack depth is bounded by the provided number of frames.
+    #[display(inner)]
+    Bounded(u16),
+
+    /// The library contains recursive calls, thus the depth of the call stack is bounded only by
+    /// the call stack size.
+    #[display("unbounded")]
+    Unbounded,
+}
+
+/// Size and complexity metrics of a library.
+///
+/// Produced by [`Lib::metrics`].
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct LibMetrics {
+    /// Size of the strict-serialized library, in bytes.
+    pub size: usize,
+
+    /// Number of instructions in the code segment.
+    pub instructions: u16,
+
+    /// Maximal number of call stack frames pushed by the nested calls made by the library.
+    ///
+    /// The depth is computed over the local subroutine calls; a call into another library
+    /// accounts for a single frame, since the depth of the calls made by the other library is
+    /// unknown.
+    pub stack_depth: StackDepth,
+
+    /// Sum of the worst-case complexities of all the instructions, saturating at `u64::MAX`.
+    pub complexity: u64,
+
+    /// Offset of the code segment data which can't be decoded as an instruction, if any.
+    ///
+    /// The metrics cover only the code preceding this offset.
+    pub invalid_code: Option<u16>,
+}
+
+impl Display for LibMetrics {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "size:         {} bytes", self.size)?;
+        writeln!(f, "instructions: {}", self.instructions)?;
+        writeln!(f, "stack depth:  {}", self.stack_depth)?;
+        write!(f, "complexity:   {}", self.complexity)?;
+        if let Some(pos) = self.invalid_code {
+            write!(f, "\ninvalid code: at offset {pos:#06x}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Library metric which can be limited by a [`LibBudget`].
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Display)]
+pub enum LibLimit {
+    /// Size of the strict-serialized library.
+    #[display("size")]
+    Size,
+
+    /// Number of instructions.
+    #[display("instructions")]
+    Instructions,
+
+    /// Call stack depth.
+    #[display("stack depth")]
+    StackDepth,
+
+    /// Complexity of the instructions.
+    #[display("complexity")]
+    Complexity,
+}
+
+/// Limits on the library metrics.
+///
+/// Limits which are `None` are not checked.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Default)]
+pub struct LibBudget {
+    /// Maximal size of the strict-serialized library, in bytes.
+    pub size: Option<usize>,
+    /// Maximal number of instructions.
+    pub instructions: Option<u16>,
+    /// Maximal call stack depth.
+    pub stack_depth: Option<u16>,
+    /// Maximal sum of the instruction complexities.
+    pub complexity: Option<u64>,
+}
+
+impl LibMetrics {
+    /// Returns the limits of the budget exceeded by the metrics.
+    ///
+    /// An unbounded stack depth exceeds any stack depth limit.
+    pub fn exceeded(&self, budget: &LibBudget) -> BTreeSet<LibLimit> {
+        let mut exceeded = BTreeSet::new();
+        if budget.size.is_some_and(|lim| self.size > lim) {
+            exceeded.insert(LibLimit::Size);
+        }
+        if budget
+            .instructions
+            .is_some_and(|lim| self.instructions > lim)
+        {
+            exceeded.insert(LibLimit::Instructions);
+        }
+        if budget
+            .stack_depth
+            .is_some_and(|lim| self.stack_depth > StackDepth::Bounded(lim))
+        {
+            exceeded.insert(LibLimit::StackDepth);
+        }
+        if budget.complexity.is_some_and(|lim| self.complexity > lim) {
+            exceeded.insert(LibLimit::Complexity);
+        }
+        exceeded
+    }
+
+    /// Checks whether the metrics fit into the budget.
+    #[inline]
+    pub fn fits(&self, budget: &LibBudget) -> bool { self.exceeded(budget).is_empty() }
+}
+
+impl Lib {
+    /// Computes size and complexity metrics of the library.
+    ///
+    /// The stack depth is computed with a conservative walk over the [`Lib::control_flow_graph`]:
+    /// each subroutine is assumed to perform all the calls reachable from its entry point, and
+    /// recursion makes the depth [`StackDepth::Unbounded`]. The library may be entered at its
+    /// start or at any of its subroutines, and the maximal depth among them is reported.
+    pub fn metrics<Isa>(&self) -> LibMetrics
+    where Isa: Instruction<LibId> {
+        let mut instructions = 0u16;
+        let mut complexity = 0u64;
+        let mut remote_calls = BTreeSet::new();
+        let mut invalid_code = None;
+        let mut reader = Marshaller::with_unchecked(self.code(), self.data(), self.libs());
+        while !reader.is_eof() {
+            let pos = reader.pos();
+            let Ok(mut instr) = Isa::decode_instr(&mut reader) else {
+                invalid_code = Some(pos);
+                break;
+            };
+            instructions += 1;
+            complexity = complexity.saturating_add(instr.complexity());
+            if instr.flow_kind() == FlowKind::Call && instr.remote_goto_pos().is_some() {
+                remote_calls.insert(pos);
+            }
+        }
+
+        LibMetrics {
+            size: self.serialized_len(),
+            instructions,
+            stack_depth: self.stack_depth::<Isa>(&remote_calls),
+            complexity,
+            invalid_code,
+        }
+    }
+
+    /// Computes the length of the strict serialization of the library.
+    fn serialized_len(&self) -> usize {
+        let isae = 1 + self.isae().iter().map(|isa| 1 + isa.len()).sum::<usize>();
+        let code = 2 + self.code().len();
+        let data = 2 + self.data().len();
+        let libs = 1 + self.libs().len() * 32;
+        isae + code + data + libs
+    }
+
+    fn stack_depth<Isa>(&self, remote_calls: &BTreeSet<u16>) -> StackDepth
+    where Isa: Instruction<LibId> {
+        let cfg = self.control_flow_graph::<Isa>();
+
+        // Subroutines with the local subroutines they call and whether they call other libraries
+        let mut subroutines = BTreeMap::<u16, (BTreeSet<u16>, bool)>::new();
+        let mut entries = BTreeSet::new();
+        if !cfg.blocks.is_empty() {
+            entries.insert(0u16);
+        }
+        entries.extend(
+            cfg.edges
+                .iter()
+                .filter(|edge| edge.kind == EdgeKind::Call)
+                .map(|e| e.target),
+        );
+        for entry in entries {
+            let mut callees = BTreeSet::new();
+            let mut remote = false;
+            let mut visited = BTreeSet::new();
+            let mut queue = Vec::from([entry]);
+            while let Some(start) = queue.pop() {
+                if !visited.insert(start) {
+                    continue;
+                }
+                let block = cfg.block_at(start).expect("edges connect existing blocks");
+                remote |= remote_calls.contains(&block.last);
+                for edge in cfg.successors(start) {
+                    match edge.kind {
+                        EdgeKind::Call => {
+                            callees.insert(edge.target);
+                        }
+                        EdgeKind::Jump | EdgeKind::Fallthrough => queue.push(edge.target),
+                    }
+                }
+            }
+            subroutines.insert(entry, (callees, remote));
+        }
+
+        // Depth-first post-order walk over the call graph; `None` marks subroutines in progress
+        let mut depths = BTreeMap::<u16, Option<u16>>::new();
+        for entry in subroutines.keys() {
+            let mut stack = Vec::from([(*entry, false)]);
+            while let Some((subroutine, expanded)) = stack.pop() {
+                let (callees, remote) = &subroutines[&subroutine];
+                if expanded {
+                    let depth = callees
+                        .iter()
+                        .map(|callee| {
+                            depths[callee]
+                                .expect("callee is processed")
+                                .saturating_add(1)
+                        })
+                        .max()
+                        .unwrap_or_default()
+                        .max(*remote as u16);
+                    depths.insert(subroutine, Some(depth));
+                    continue;
+                }
+                match depths.get(&subroutine) {
+                    Some(Some(_)) => continue,
+                    // The subroutine is called by one of the subroutines it calls
+                    Some(None) => return StackDepth::Unbounded,
+                    None => {}
+                }
+                depths.insert(subroutine, None);
+                stack.push((subroutine, true));
+                stack.extend(callees.iter().map(|callee| (*callee, false)));
+            }
+        }
+        StackDepth::Bounded(depths.into_values().flatten().max().unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![cfg_attr(coverage_nightly, coverage(off))]
+
+    use amplify::confinement::SmallBlob;
+    use strict_encoding::{StrictDumb, StrictSerialize};
+
+    use super::*;
+    use crate::isa::{CtrlInstr, Instr, ReservedInstr};
+    use crate::Site;
+
+    fn metrics(code: &[CtrlInstr<LibId>]) -> LibMetrics {
+        let code = code.iter().copied().map(Instr::from).collect::<Vec<_>>();
+        Lib::assemble(&code).unwrap().metrics::<Instr<LibId>>()
+    }
+
+    #[test]
+    fn lib_metrics() {
+        let site = Site::new(LibId::strict_dumb(), 0);
+        let code: [Instr<LibId>; 7] = [
+            CtrlInstr::ChkCo.into(),             // 0
+            CtrlInstr::JiFail { pos: 9 }.into(), // 1
+            CtrlInstr::Fn { pos: 8 }.into(),     // 4
+            CtrlInstr::Stop.into(),              // 7
+            CtrlInstr::Ret.into(),               // 8
+            CtrlInstr::Sh { shift: -9 }.into(),  // 9
+            CtrlInstr::Exec { site }.into(),     // 11
+        ];
+        let lib = Lib::assemble(&code).unwrap();
+        let metrics = lib.metrics::<Instr<LibId>>();
+        assert_eq!(metrics.size, lib.to_strict_serialized::<0xFFFFFF>().unwrap().len());
+        assert_eq!(metrics.instructions, 7);
+        assert_eq!(metrics.stack_depth, StackDepth::Bounded(1));
+        assert_eq!(
+            metrics.complexity,
+            code.iter()
+                .map(Instruction::<LibId>::complexity)
+                .sum::<u64>()
+        );
+        assert_eq!(metrics.invalid_code, None);
+        assert_eq!(
+            metrics.to_string(),
+            format!(
+                "size:         {} bytes\ninstructions: 7\nstack depth:  1\ncomplexity:   {}",
+                metrics.size, metrics.complexity
+            )
+        );
+    }
+
+    #[test]
+    fn stack_depth() {
+        assert_eq!(metrics(&[]).stack_depth, StackDepth::Bounded(0));
+        assert_eq!(metrics(&[CtrlInstr::Jmp { pos: 0 }]).stack_depth, StackDepth::Bounded(0));
+
+        let site = Site::new(LibId::strict_dumb(), 0);
+        let nested = [
+            CtrlInstr::Fn { pos: 4 },  // 0
+            CtrlInstr::Stop,           // 3
+            CtrlInstr::Fn { pos: 8 },  // 4
+            CtrlInstr::Ret,            // 7
+            CtrlInstr::Call { site },  // 8
+            CtrlInstr::Fn { pos: 15 }, // 12
+            CtrlInstr::Ret,            // 15
+        ];
+        assert_eq!(metrics(&nested).stack_depth, StackDepth::Bounded(3));
+        // A call is accounted for even if it is reached only through a jump
+        let jump = [
+            CtrlInstr::Jmp { pos: 4 }, // 0
+            CtrlInstr::Stop,           // 3
+            CtrlInstr::Fn { pos: 3 },  // 4
+            CtrlInstr::Ret,            // 7
+        ];
+        assert_eq!(metrics(&jump).stack_depth, StackDepth::Bounded(1));
+
+        let recursive = [CtrlInstr::Fn { pos: 0 }];
+        assert_eq!(metrics(&recursive).stack_depth, StackDepth::Unbounded);
+        let mutual = [
+            CtrlInstr::Fn { pos: 4 }, // 0
+            CtrlInstr::Ret,           // 3
+            CtrlInstr::Fn { pos: 8 }, // 4
+            CtrlInstr::Ret,           // 7
+            CtrlInstr::ChkCo,         // 8
+            CtrlInstr::Fn { pos: 4 }, // 9
+            CtrlInstr::Ret,           // 12
+        ];
+        assert_eq!(metrics(&mutual).stack_depth, StackDepth::Unbounded);
+    }
+
+    #[test]
+    fn saturation_and_invalid_code() {
+        let code: [Instr<LibId>; 2] = [ReservedInstr::default().into(), CtrlInstr::Stop.into()];
+        let metrics = Lib::assemble(&code).unwrap().metrics::<Instr<LibId>>();
+        assert_eq!(metrics.instructions, 2);
+        assert_eq!(metrics.complexity, u64::MAX);
+
+        let lib = Lib::with(
+            none!(),
+            SmallBlob::from_checked(vec![CtrlInstr::<LibId>::RET, CtrlInstr::<LibId>::JMP]),
+            none!(),
+            none!(),
+        );
+        let metrics = lib.metrics::<Instr<LibId>>();
+        assert_eq!(metrics.instructions, 1);
+        assert_eq!(metrics.invalid_code, Some(1));
+        assert!(metrics
+            .to_string()
+            .ends_with("\ninvalid code: at offset 0x0001"));
+    }
+
+    #[test]
+    fn budget() {
+        let metrics = metrics(&[CtrlInstr::Fn { pos: 0 }]);
+        assert!(metrics.fits(&LibBudget::default()));
+
+        let budget = LibBudget {
+            size: Some(metrics.size),
+            instructions: Some(1),
+            stack_depth: Some(u16::MAX),
+            complexity: Some(metrics.complexity),
+        };
+        assert_eq!(metrics.exceeded(&budget), bset![LibLimit::StackDepth]);
+
+        let budget = LibBudget {
+            size: Some(metrics.size - 1),
+            instructions: Some(0),
+            stack_depth: None,
+            complexity: Some(metrics.complexity - 1),
+        };
+        assert_eq!(metrics.exceeded(&budget), bset![
+            LibLimit::Size,
+            LibLimit::Instructions,
+            LibLimit::Complexity
+        ]);
+        assert!(!metrics.fits(&budget));
+    }
+}
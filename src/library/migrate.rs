@@ -0,0 +1,353 @@
+// Reference rust implementation of AluVM (arithmetic logic unit virtual machine).
+// To find more on AluVM please check <https://aluvm.org>
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Designed in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+// Written in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+//
+// Copyright (C) 2021-2024 LNP/BP Standards Association, Switzerland.
+// Copyright (C) 2024-2025 Laboratories for Ubiquitous Deterministic Computing (UBIDECO),
+//                         Institute for Distributed and Cognitive Systems (InDCS), Switzerland.
+// Copyright (C) 2021-2025 Dr Maxim Orlovsky.
+// All rights under the above copyrights are reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use this file except
+// in compliance with the License. You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed under the License
+// is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express
+// or implied. See the License for the specific language governing permissions and limitations under
+// the License.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use super::{AssemblerError, Lib, LibId, RewriteError};
+use crate::isa::{CodeEofError, Instruction};
+
+/// Errors migrating a library between bytecode versions.
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum MigrationError {
+    /// library code can't be decoded under the old instruction set.
+    #[from]
+    InvalidCode(CodeEofError),
+
+    /// instruction `{0}` has no equivalent in the new instruction set.
+    Unsupported(String),
+
+    /// instruction at offset {0:#06X} jumps to offset {1:#06X}, which is not an instruction
+    /// boundary.
+    InvalidJump(u16, u16),
+
+    /// relative jump of the instruction at offset {0:#06X} doesn't fit the migrated code.
+    JumpOutOfRange(u16),
+
+    /// Error assembling the migrated library (see [`AssemblerError`] for the details).
+    #[from]
+    #[display(inner)]
+    Assemble(AssemblerError),
+}
+
+impl MigrationError {
+    /// Converts an error rewriting the code under migration, which maps each instruction into a
+    /// single one and doesn't apply code edits.
+    fn from_rewrite(err: RewriteError) -> Self {
+        match err {
+            RewriteError::InvalidCode(err) => Self::InvalidCode(err),
+            RewriteError::InvalidJump(pos, target) | RewriteError::DeletedTarget(pos, target) => {
+                Self::InvalidJump(pos, target)
+            }
+            RewriteError::JumpOutOfRange(pos) => Self::JumpOutOfRange(pos),
+            RewriteError::Assemble(err) => Self::Assemble(err),
+            RewriteError::InvalidEdit(_) => unreachable!("migration doesn't apply code edits"),
+        }
+    }
+}
+
+/// Migration of instructions between two bytecode versions, preserving the instruction semantics.
+///
+/// The migration maps each instruction of the old version into a single instruction of the new
+/// version. The local jump targets of the produced instructions must still refer to the offsets in
+/// the old code; they are fixed up by [`Lib::migrate`] according to the new instruction lengths.
+pub trait BytecodeMigration<Old, New>
+where
+    Old: Instruction<LibId>,
+    New: Instruction<LibId>,
+{
+    /// Maps an instruction of the old bytecode version into the new one.
+    fn migrate_instr(&self, old: Old) -> Result<New, MigrationError>;
+}
+
+/// Summary of a library migration performed by [`Lib::migrate`].
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct MigrationReport {
+    /// Number of instructions whose bytecode has changed.
+    pub changed: usize,
+    /// Difference between the new and the old code segment lengths.
+    pub size_delta: i32,
+    /// Identifier of the migrated library.
+    pub lib_id: LibId,
+}
+
+impl Lib {
+    /// Migrates the library into a new bytecode version.
+    ///
+    /// The library is disassembled using the `Old` instruction set, each instruction is mapped
+    /// with the `migration`, and the code is rewritten with [`Lib::map_instrs`], which fixes up
+    /// the local jump targets to account for the changes in instruction lengths and assembles
+    /// the result using the `New` instruction set, recomputing the libs segment.
+    ///
+    /// # Errors
+    ///
+    /// If the code can't be decoded or migrated, if it contains a jump to the middle of an
+    /// instruction, or if a relative jump doesn't fit the migrated code.
+    pub fn migrate<Old, New>(
+        &self,
+        migration: &impl BytecodeMigration<Old, New>,
+    ) -> Result<(Lib, MigrationReport), MigrationError>
+    where
+        Old: Instruction<LibId>,
+        New: Instruction<LibId>,
+    {
+        let mut code = Vec::new();
+        // Offsets and lengths of the old instructions, and the lengths of the migrated ones
+        let mut spans = Vec::new();
+        for item in self.instr_iter::<Old>() {
+            let (pos, old) = item?;
+            let old_len = old.code_byte_len();
+            let new = migration.migrate_instr(old)?;
+            spans.push((pos, old_len, new.code_byte_len()));
+            code.push(new);
+        }
+
+        let mut code = code.into_iter();
+        let lib = self
+            .map_instrs(|_, _: Old| code.next())
+            .map_err(MigrationError::from_rewrite)?;
+        let mut new_pos = 0usize;
+        let mut changed = 0usize;
+        for (old_pos, old_len, new_len) in spans {
+            let old_pos = old_pos as usize;
+            let new_end = new_pos + new_len as usize;
+            if self.code()[old_pos..old_pos + old_len as usize] != lib.code()[new_pos..new_end] {
+                changed += 1;
+            }
+            new_pos = new_end;
+        }
+        let report = MigrationReport {
+            changed,
+            size_delta: lib.code().len() as i32 - self.code().len() as i32,
+            lib_id: lib.lib_id(),
+        };
+        Ok((lib, report))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![cfg_attr(coverage_nightly, coverage(off))]
+
+    use alloc::collections::BTreeSet;
+    use alloc::string::ToString;
+    use core::fmt::{self, Display, Formatter};
+    use core::ops::RangeInclusive;
+
+    use amplify::confinement::SmallBlob;
+    use strict_encoding::StrictDumb;
+
+    use super::*;
+    use crate::isa::{
+        Bytecode, BytecodeRead, BytecodeWrite, ComplexityClass, CtrlInstr, ExecStep, GotoTarget,
+        Instr,
+    };
+    use crate::{aluasm, CompiledLib, Core, CoreConfig, LibSite, NoExt, NoRegs, Site, Vm};
+
+    /// Previous version of the control flow ISA, which had opcodes shifted by 0x40 and encoded
+    /// `nop` with an additional padding byte.
+    #[derive(Clone, PartialEq, Eq, Debug)]
+    struct OldCtrl(Instr<LibId>);
+
+    impl OldCtrl {
+        const SHIFT: u8 = 0x40;
+
+        fn is_nop(&self) -> bool { self.0 == Instr::Ctrl(CtrlInstr::Nop) }
+    }
+
+    impl Display for OldCtrl {
+        fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result { Display::fmt(&self.0, f) }
+    }
+
+    impl Bytecode<LibId> for OldCtrl {
+        fn op_range() -> RangeInclusive<u8> { Instr::<LibId>::op_range() }
+        fn opcode_byte(&self) -> u8 { self.0.opcode_byte() ^ Self::SHIFT }
+        fn code_byte_len(&self) -> u16 { self.0.code_byte_len() + self.is_nop() as u16 }
+        fn external_ref(&self) -> Option<LibId> { self.0.external_ref() }
+        fn encode_operands<W>(&self, writer: &mut W) -> Result<(), W::Error>
+        where W: BytecodeWrite<LibId> {
+            self.0.encode_operands(writer)?;
+            if self.is_nop() {
+                writer.write_byte(0)?;
+            }
+            Ok(())
+        }
+        fn decode_operands<R>(reader: &mut R, opcode: u8) -> Result<Self, CodeEofError>
+        where R: BytecodeRead<LibId> {
+            let instr = Self(Instr::decode_operands(reader, opcode ^ Self::SHIFT)?);
+            if instr.is_nop() {
+                reader.read_byte()?;
+            }
+            Ok(instr)
+        }
+    }
+
+    impl Instruction<LibId> for OldCtrl {
+        const ISA_EXT: &'static [&'static str] = &[];
+        type Core = NoExt;
+        type Context<'ctx> = ();
+
+        fn is_reserved(&self) -> bool { self.0.is_reserved() }
+        fn is_goto_target(&self) -> bool { self.0.is_goto_target() }
+        fn local_goto_pos(&mut self) -> GotoTarget<'_> { self.0.local_goto_pos() }
+        fn remote_goto_pos(&mut self) -> Option<&mut Site<LibId>> { self.0.remote_goto_pos() }
+        fn src_regs(&self) -> BTreeSet<NoRegs> { self.0.src_regs() }
+        fn dst_regs(&self) -> BTreeSet<NoRegs> { self.0.dst_regs() }
+        fn op_data_bytes(&self) -> u16 { self.0.op_data_bytes() }
+        fn ext_data_bytes(&self) -> u16 { self.0.ext_data_bytes() }
+        fn complexity_class(&self) -> ComplexityClass { self.0.complexity_class() }
+        fn exec<const CALL_STACK_SIZE: usize>(
+            &self,
+            site: Site<LibId>,
+            core: &mut Core<LibId, NoExt, CALL_STACK_SIZE>,
+            context: &(),
+        ) -> ExecStep<Site<LibId>> {
+            self.0.exec(site, core, context)
+        }
+    }
+
+    /// Migration from [`OldCtrl`] to the current control flow ISA.
+    struct CtrlRenumbering;
+
+    impl BytecodeMigration<OldCtrl, Instr<LibId>> for CtrlRenumbering {
+        fn migrate_instr(&self, old: OldCtrl) -> Result<Instr<LibId>, MigrationError> {
+            if old.is_reserved() {
+                return Err(MigrationError::Unsupported(old.to_string()));
+            }
+            Ok(old.0)
+        }
+    }
+
+    fn corpus() -> [Vec<Instr<LibId>>; 3] {
+        const MAIN: u16 = 0;
+        const END: u16 = 1;
+        const SUB: u16 = 2;
+        const FAIL: u16 = 1;
+
+        [
+            aluasm! {
+               routine MAIN:
+                nop;
+                nop;
+                call    SUB;
+                jmp     END;
+                fail    CK;
+               label    END:
+                stop;
+               routine  SUB:
+                nop;
+                ret;
+            },
+            aluasm! {
+               routine MAIN:
+                not     CO;
+                jif     CO, FAIL;
+                stop;
+               label    FAIL:
+                nop;
+                fail    CK;
+                stop;
+            },
+            aluasm! {
+               routine MAIN:
+                chk     CO;
+                chk     CK;
+                jif     CK, MAIN;
+                not     CO;
+                jif     CO, MAIN;
+                stop;
+            },
+        ]
+    }
+
+    #[test]
+    fn ctrl_renumbering() {
+        for code in corpus() {
+            let old_code = code.iter().cloned().map(OldCtrl).collect::<Vec<_>>();
+            let old = CompiledLib::compile(old_code, &[]).unwrap().into_lib();
+            let new = CompiledLib::compile(code.clone(), &[]).unwrap().into_lib();
+            let nops = code
+                .iter()
+                .filter(|instr| **instr == CtrlInstr::Nop.into())
+                .count();
+
+            let (migrated, report) = old.migrate(&CtrlRenumbering).unwrap();
+            assert_eq!(migrated, new);
+            assert_eq!(report.lib_id, new.lib_id());
+            assert_eq!(report.size_delta, -(nops as i32));
+            assert_eq!(report.changed, code.len());
+
+            let mut old_vm = Vm::<OldCtrl>::with(CoreConfig::default(), ());
+            let old_status = old_vm.exec(LibSite::new(old.lib_id(), 0), &(), |_| Some(&old));
+            let mut new_vm = Vm::<Instr<LibId>>::with(CoreConfig::default(), ());
+            let new_status =
+                new_vm.exec(LibSite::new(migrated.lib_id(), 0), &(), |_| Some(&migrated));
+            assert_eq!(old_status, new_status);
+            assert_eq!(old_vm.core.co(), new_vm.core.co());
+            assert_eq!(old_vm.core.cy(), new_vm.core.cy());
+            assert_eq!(old_vm.core.ca(), new_vm.core.ca());
+        }
+    }
+
+    #[test]
+    fn relative_fixup() {
+        let code = [
+            OldCtrl(CtrlInstr::Nop.into()),
+            OldCtrl(CtrlInstr::Sh { shift: 4 }.into()),
+            OldCtrl(CtrlInstr::Nop.into()),
+            OldCtrl(CtrlInstr::Sh { shift: -6 }.into()),
+        ];
+        let old = Lib::assemble(&code).unwrap();
+        let (migrated, report) = old.migrate(&CtrlRenumbering).unwrap();
+        assert_eq!(migrated.disassemble::<Instr<LibId>>().unwrap(), [
+            CtrlInstr::Nop.into(),
+            CtrlInstr::Sh { shift: 3 }.into(),
+            CtrlInstr::Nop.into(),
+            CtrlInstr::Sh { shift: -4 }.into(),
+        ]);
+        assert_eq!(report.size_delta, -2);
+        assert_eq!(report.changed, 4);
+    }
+
+    #[test]
+    fn invalid_jump() {
+        let code = [OldCtrl(CtrlInstr::Jmp { pos: 4 }.into()), OldCtrl(CtrlInstr::Nop.into())];
+        let old = Lib::assemble(&code).unwrap();
+        assert_eq!(
+            old.migrate::<_, Instr<LibId>>(&CtrlRenumbering),
+            Err(MigrationError::InvalidJump(0, 4))
+        );
+    }
+
+    #[test]
+    fn unsupported() {
+        let mut lib = Lib::strict_dumb();
+        *lib.code_mut() = SmallBlob::from_checked(vec![0x80 ^ OldCtrl::SHIFT]);
+        assert!(matches!(
+            lib.migrate::<_, Instr<LibId>>(&CtrlRenumbering),
+            Err(MigrationError::Unsupported(_))
+        ));
+    }
+}
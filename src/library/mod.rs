@@ -26,12 +26,53 @@ mod lib;
 #[cfg(feature = "armor")]
 pub mod armor;
 mod assembler;
+mod boundary;
+mod builder;
+mod compat;
 mod compiler;
+#[cfg(feature = "std")]
+mod container;
+mod entry;
 mod marshaller;
+mod merge;
+mod metrics;
+mod migrate;
+mod modify;
 mod exec;
+mod flow;
+mod graph;
+mod hook;
+mod precompiled;
+mod program;
+mod relocate;
+mod rewrite;
+mod repo;
+mod symbols;
+mod validate;
 
-pub use assembler::AssemblerError;
+pub use assembler::{AssemblerError, InstrIter, LibAssembler, ResumeAfterError, SourceError};
+pub use boundary::BoundaryIndex;
+pub use builder::{LibBuilder, LibBuilderError, RoutineBuilder};
+pub use compat::CompatError;
 pub use compiler::{CompiledLib, CompilerError};
-pub use exec::Jump;
-pub use lib::{Lib, LibId, LibSite, LibsSeg};
+#[cfg(feature = "std")]
+pub use container::{LibContainerError, LIB_CONTAINER_MAGIC, LIB_CONTAINER_VERSION};
+pub use entry::{EntryPoint, EntryPointError};
+pub(crate) use exec::ExecCode;
+pub use exec::{Jump, UnsupportedIsaError};
+pub use flow::{BasicBlock, ControlFlowGraph, Edge, EdgeKind, InvalidJump};
+pub use graph::{LibGraph, LibGraphError};
+pub use hook::{ExecHook, HookAction, NoHook};
+pub use lib::{Lib, LibId, LibSite, LibsSeg, CORE_STATE_TAG};
 pub use marshaller::{MarshallError, Marshaller};
+pub use merge::{MergeError, MergeReport};
+pub use metrics::{LibBudget, LibLimit, LibMetrics, StackDepth};
+pub use migrate::{BytecodeMigration, MigrationError, MigrationReport};
+pub use modify::{DataExtendError, LibModifyError, LibOp, PatchError};
+pub use precompiled::PrecompiledLib;
+pub use program::{Program, ProgramError};
+pub use relocate::RelocationError;
+pub use repo::LibRepo;
+pub use rewrite::{CodeEdit, RewriteError};
+pub use symbols::{DataRange, LibSymbols, SymbolError, SymbolName, SYMBOL_NAME_MAX_LEN};
+pub use validate::{IsaConsistencyReport, LibValidationError};
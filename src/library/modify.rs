@@ -0,0 +1,291 @@
+// Reference rust implementation of AluVM (arithmetic logic unit virtual machine).
+// To find more on AluVM please check <https://aluvm.org>
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Designed in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+// Written in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+//
+// Copyright (C) 2021-2024 LNP/BP Standards Association, Switzerland.
+// Copyright (C) 2024-2025 Laboratories for Ubiquitous Deterministic Computing (UBIDECO),
+//                         Institute for Distributed and Cognitive Systems (InDCS), Switzerland.
+// Copyright (C) 2021-2025 Dr Maxim Orlovsky.
+// All rights under the above copyrights are reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use this file except
+// in compliance with the License. You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed under the License
+// is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express
+// or implied. See the License for the specific language governing permissions and limitations under
+// the License.
+
+use alloc::vec::Vec;
+
+use amplify::confinement::SmallBlob;
+
+use super::{Lib, LibId, MarshallError, Marshaller};
+use crate::isa::{BytecodeRead, Instruction};
+
+/// Errors extending library data segment.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum DataExtendError {
+    /// data segment would have {0} bytes, exceeding the limit of 0xFFFF bytes.
+    Oversize(usize),
+
+    /// data at offset {offset:#06X} overlap with the existing data segment of {len} bytes.
+    Overlap {
+        /// Offset at which the data were requested to be placed.
+        offset: u16,
+        /// Length of the existing data segment.
+        len: u16,
+    },
+}
+
+/// Errors patching library code.
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum PatchError {
+    /// offset {0:#06X} is not an instruction boundary.
+    NoInstruction(u16),
+
+    /// patch at offset {offset:#06X} changes the instruction length from {old} to {new} bytes.
+    LengthMismatch {
+        /// Offset of the patched instruction.
+        offset: u16,
+        /// Length of the original instruction.
+        old: u16,
+        /// Length of the patch.
+        new: u16,
+    },
+
+    /// instruction at offset {0:#06X} is not a local jump.
+    NoJump(u16),
+
+    /// jump at offset {offset:#06X} can't reach offset {target:#06X} with a relative shift.
+    ShiftOverflow {
+        /// Offset of the patched instruction.
+        offset: u16,
+        /// New jump target.
+        target: u16,
+    },
+
+    /// Error encoding the patch (see [`MarshallError`] for the details).
+    #[from]
+    #[display(inner)]
+    Encode(MarshallError),
+}
+
+/// Errors modifying a library with [`Lib::modify`].
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Display, Error, From)]
+#[display(inner)]
+pub enum LibModifyError {
+    /// Error extending data segment.
+    #[from]
+    Data(DataExtendError),
+
+    /// Error patching code segment.
+    #[from]
+    Patch(PatchError),
+}
+
+/// Single operation of the [`Lib::modify`] transaction.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub enum LibOp<Isa: Instruction<LibId>> {
+    /// Append data to the data segment (see [`Lib::extend_data`]).
+    ExtendData(Vec<u8>),
+
+    /// Place data at the given offset of the data segment (see [`Lib::extend_data_at`]).
+    ExtendDataAt(u16, Vec<u8>),
+
+    /// Replace an instruction at the given offset (see [`Lib::patch`]).
+    Patch(u16, Isa),
+}
+
+impl Lib {
+    /// Constructs a new library with `extra` bytes appended to the data segment.
+    ///
+    /// The code segment is not changed; the returned library has a different id unless `extra` is
+    /// empty.
+    ///
+    /// # Returns
+    ///
+    /// The new library and the offset of the appended data in its data segment.
+    pub fn extend_data(&self, extra: &[u8]) -> Result<(Lib, u16), DataExtendError> {
+        let offset = self.data().len() as u16;
+        Ok((self.extend_data_at(offset, extra)?, offset))
+    }
+
+    /// Constructs a new library with `bytes` placed at the given offset of the data segment.
+    ///
+    /// The offset must not be less than the current data segment length; if it is greater, the gap
+    /// is filled with zeros (and can't be used by other data afterwards).
+    ///
+    /// # Errors
+    ///
+    /// If the data overlap with the existing data segment or if the resulting segment exceeds
+    /// 0xFFFF bytes.
+    pub fn extend_data_at(&self, offset: u16, bytes: &[u8]) -> Result<Lib, DataExtendError> {
+        let len = self.data().len() as u16;
+        if offset < len {
+            return Err(DataExtendError::Overlap { offset, len });
+        }
+        let new_len = offset as usize + bytes.len();
+        if new_len > u16::MAX as usize {
+            return Err(DataExtendError::Oversize(new_len));
+        }
+        let mut data = self.data().to_vec();
+        data.resize(offset as usize, 0);
+        data.extend_from_slice(bytes);
+        Ok(Lib::with(
+            self.isae().clone(),
+            self.code().clone(),
+            SmallBlob::from_checked(data),
+            self.libs().clone(),
+        ))
+    }
+
+    /// Constructs a new library with the instruction at the given offset replaced.
+    ///
+    /// The new instruction must have the same length as the replaced one. If it uses data, they
+    /// are added to the data segment (unless already present there); if it references an external
+    /// library, the library must be already present in the libs segment.
+    pub fn patch<Isa>(&self, offset: u16, instr: &Isa) -> Result<Lib, PatchError>
+    where Isa: Instruction<LibId> {
+        if !self.boundary_index::<Isa>().is_boundary(offset) {
+            return Err(PatchError::NoInstruction(offset));
+        }
+        let mut reader = Marshaller::with_unchecked(self.code(), self.data(), self.libs());
+        reader
+            .seek(offset)
+            .and_then(|_| Isa::decode_instr(&mut reader))
+            .map_err(|_| PatchError::NoInstruction(offset))?;
+        let old = reader.offset().0 - offset;
+
+        let mut writer = Marshaller::resume(Vec::new(), self.data().to_vec(), self.libs());
+        instr.encode_instr(&mut writer)?;
+        let (patch, data) = writer.into_buffers();
+        let new = patch.len() as u16;
+        if new != old {
+            return Err(PatchError::LengthMismatch { offset, old, new });
+        }
+
+        let mut code = self.code().to_vec();
+        code[offset as usize..(offset + new) as usize].copy_from_slice(&patch);
+        Ok(Lib::with(
+            self.isae().clone(),
+            SmallBlob::from_checked(code),
+            SmallBlob::from_checked(data),
+            self.libs().clone(),
+        ))
+    }
+
+    /// Constructs a new library by applying a sequence of data extensions and code patches.
+    ///
+    /// The operations are applied in order, each to the result of the previous one. If any of the
+    /// operations fails, no library is produced.
+    pub fn modify<Isa>(
+        &self,
+        ops: impl IntoIterator<Item = LibOp<Isa>>,
+    ) -> Result<Lib, LibModifyError>
+    where
+        Isa: Instruction<LibId>,
+    {
+        let mut lib = self.clone();
+        for op in ops {
+            lib = match op {
+                LibOp::ExtendData(bytes) => lib.extend_data(&bytes)?.0,
+                LibOp::ExtendDataAt(offset, bytes) => lib.extend_data_at(offset, &bytes)?,
+                LibOp::Patch(offset, instr) => lib.patch(offset, &instr)?,
+            };
+        }
+        Ok(lib)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![cfg_attr(coverage_nightly, coverage(off))]
+
+    use super::*;
+    use crate::isa::{CtrlInstr, Instr};
+    use crate::regs::Status;
+    use crate::{CoreConfig, LibSite, Vm};
+
+    fn lib() -> Lib {
+        let code: [Instr<LibId>; 4] = [
+            CtrlInstr::Jmp { pos: 3 }.into(),
+            CtrlInstr::Stop.into(),
+            CtrlInstr::FailCk.into(),
+            CtrlInstr::Stop.into(),
+        ];
+        Lib::assemble(&code).unwrap()
+    }
+
+    fn exec(lib: &Lib) -> Status {
+        let mut vm = Vm::<Instr<LibId>>::with(CoreConfig::default(), ());
+        vm.exec(LibSite::new(lib.lib_id(), 0), &(), |_| Some(lib))
+    }
+
+    #[test]
+    fn extend_and_patch() {
+        let lib = lib();
+        assert_eq!(exec(&lib), Status::Ok);
+
+        let modified = lib
+            .modify([
+                LibOp::<Instr<LibId>>::ExtendData(b"table".to_vec()),
+                LibOp::ExtendDataAt(8, vec![0xFF]),
+                LibOp::Patch(0, CtrlInstr::Jmp { pos: 4 }.into()),
+            ])
+            .unwrap();
+        assert_ne!(modified.lib_id(), lib.lib_id());
+        assert_eq!(modified.data().as_slice(), b"table\0\0\0\xFF");
+        assert_eq!(modified.code().len(), lib.code().len());
+        assert_eq!(exec(&modified), Status::Fail);
+
+        let (extended, offset) = lib.extend_data(b"table").unwrap();
+        assert_eq!(offset, 0);
+        assert_eq!(extended.extend_data(b"!").unwrap().1, 5);
+        assert_eq!(extended.code(), lib.code());
+    }
+
+    #[test]
+    fn data_limits() {
+        let (lib, _) = lib().extend_data(&[0xAA; 0xFFF0]).unwrap();
+        assert_eq!(lib.extend_data(&[0; 0x20]), Err(DataExtendError::Oversize(0x10010)));
+        assert_eq!(lib.extend_data(&[0; 0x0F]).unwrap().0.data().len(), 0xFFFF);
+        assert_eq!(
+            lib.extend_data_at(0x10, &[0]),
+            Err(DataExtendError::Overlap { offset: 0x10, len: 0xFFF0 })
+        );
+        assert_eq!(
+            lib.modify([
+                LibOp::Patch(0, CtrlInstr::Jmp { pos: 4 }.into()),
+                LibOp::<Instr<LibId>>::ExtendData(vec![0; 0x20]),
+            ]),
+            Err(DataExtendError::Oversize(0x10010).into())
+        );
+    }
+
+    #[test]
+    fn patch_errors() {
+        let lib = lib();
+        assert_eq!(
+            lib.patch::<Instr<LibId>>(1, &CtrlInstr::Stop.into()),
+            Err(PatchError::NoInstruction(1))
+        );
+        assert_eq!(
+            lib.patch::<Instr<LibId>>(0, &CtrlInstr::Stop.into()),
+            Err(PatchError::LengthMismatch { offset: 0, old: 3, new: 1 })
+        );
+        assert_eq!(
+            lib.patch::<Instr<LibId>>(3, &CtrlInstr::FailCk.into())
+                .map(|lib| exec(&lib)),
+            Ok(Status::Fail)
+        );
+    }
+}
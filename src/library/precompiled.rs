@@ -0,0 +1,468 @@
+// Reference rust implementation of AluVM (arithmetic logic unit virtual machine).
+// To find more on AluVM please check <https://aluvm.org>
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Designed in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+// Written in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+//
+// Copyright (C) 2021-2024 LNP/BP Standards Association, Switzerland.
+// Copyright (C) 2024-2025 Laboratories for Ubiquitous Deterministic Computing (UBIDECO),
+//                         Institute for Distributed and Cognitive Systems (InDCS), Switzerland.
+// Copyright (C) 2021-2025 Dr Maxim Orlovsky.
+// All rights under the above copyrights are reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use this file except
+// in compliance with the License. You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed under the License
+// is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express
+// or implied. See the License for the specific language governing permissions and limitations under
+// the License.
+
+use alloc::vec::Vec;
+
+use super::exec::{exec_complexity, ExecCode};
+use super::{ExecHook, HookAction, Jump, Lib, LibId, Marshaller, UnsupportedIsaError};
+use crate::isa::{BytecodeRead, ExecStep, Instruction};
+use crate::{Core, FailureReason, JumpFault, Site, TerminationCause};
+
+/// Index value for the code offsets which are not instruction boundaries.
+///
+/// It can't be a valid instruction number: once there is an offset inside an instruction, the
+/// number of instructions is less than the code length, which doesn't exceed `u16::MAX`.
+const NO_INSTR: u16 = u16::MAX;
+
+/// Library code decoded into instructions ahead of the execution (see [`Lib::precompile`]).
+///
+/// The execution of a precompiled library by [`crate::Vm::exec_compiled`] doesn't decode the
+/// instructions from the bytecode each time they are executed, and has the same semantics as the
+/// execution of the library itself, except for a control transfer to an offset which is not an
+/// instruction boundary. Such a transfer always fails `CK` and halts the program, as with the
+/// `paranoid` feature (see [`Core::jump_fault`]).
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct PrecompiledLib<Isa: Instruction<LibId>> {
+    lib_id: LibId,
+    code_len: u16,
+    /// Decoded instructions with their offsets.
+    instrs: Vec<(u16, Isa)>,
+    /// Number of the instruction starting at each code offset, or [`NO_INSTR`].
+    index: Vec<u16>,
+}
+
+impl<Isa: Instruction<LibId>> AsRef<PrecompiledLib<Isa>> for PrecompiledLib<Isa> {
+    fn as_ref(&self) -> &PrecompiledLib<Isa> { self }
+}
+
+impl<Isa: Instruction<LibId>> PrecompiledLib<Isa> {
+    /// Identifier of the precompiled library.
+    #[inline]
+    pub fn lib_id(&self) -> LibId { self.lib_id }
+
+    /// Decoded instructions with their offsets in the library code.
+    ///
+    /// If the code contains a byte sequence which can't be decoded, only the instructions
+    /// preceding it are present.
+    #[inline]
+    pub fn instrs(&self) -> &[(u16, Isa)] { &self.instrs }
+
+    /// Returns the number of the instruction starting at the offset `pos`, or `None` if the offset
+    /// is not an instruction boundary.
+    #[inline]
+    fn instr_no(&self, pos: u16) -> Option<usize> {
+        match self.index.get(pos as usize) {
+            Some(&NO_INSTR) | None => None,
+            Some(no) => Some(*no as usize),
+        }
+    }
+}
+
+impl Lib {
+    /// Decodes the library code into instructions once, such that it can be executed without
+    /// decoding the instructions from the bytecode each time they are executed (see
+    /// [`crate::Vm::exec_compiled`]).
+    ///
+    /// # Errors
+    ///
+    /// If the library requires ISA extensions which are not supported by the instruction set (see
+    /// [`Lib::check_isae`]).
+    pub fn precompile<Isa>(&self) -> Result<PrecompiledLib<Isa>, UnsupportedIsaError>
+    where Isa: Instruction<LibId> {
+        self.check_isae::<Isa>()?;
+        let code_len = self.code().len() as u16;
+        let mut instrs = Vec::new();
+        let mut index = alloc::vec![NO_INSTR; code_len as usize];
+        let mut reader = Marshaller::with_unchecked(self.code(), self.data(), self.libs());
+        while !reader.is_eof() {
+            let pos = reader.pos();
+            // Like in the boundary index, the offsets starting from a byte sequence which can't be
+            // decoded are not instruction boundaries
+            let Ok(instr) = Isa::decode_instr(&mut reader) else {
+                break;
+            };
+            index[pos as usize] = instrs.len() as u16;
+            instrs.push((pos, instr));
+        }
+        Ok(PrecompiledLib { lib_id: self.lib_id(), code_len, instrs, index })
+    }
+}
+
+impl<Isa: Instruction<LibId>> ExecCode<Isa> for PrecompiledLib<Isa> {
+    fn exec_code<const CALL_STACK_SIZE: usize>(
+        &self,
+        entrypoint: u16,
+        skip_first: bool,
+        core: &mut Core<LibId, Isa::Core, CALL_STACK_SIZE>,
+        context: &Isa::Context<'_>,
+        is_break: impl Fn(u16) -> bool,
+        hook: &mut impl ExecHook<LibId>,
+    ) -> Result<Jump<LibId>, UnsupportedIsaError> {
+        let lib_id = self.lib_id;
+
+        if entrypoint >= self.code_len {
+            let _ = core.fail_ck_with(FailureReason::InvalidJump);
+            return Ok(Jump::OutOfCode(Site::new(lib_id, entrypoint)));
+        }
+        // Code which can't be decoded from its start halts the execution without the jump fault
+        let Some(mut no) = self.instr_no(entrypoint).or((entrypoint == 0).then_some(0)) else {
+            core.fail_jump(JumpFault { source: None, target: Site::new(lib_id, entrypoint) });
+            return Ok(Jump::Halt);
+        };
+        if skip_first {
+            no += 1;
+        }
+
+        #[cfg(any(feature = "paranoid", debug_assertions))]
+        let mut held = core.check_invariants().is_ok();
+        while let Some((pos, instr)) = self.instrs.get(no) {
+            let pos = *pos;
+            #[cfg(any(feature = "paranoid", debug_assertions))]
+            {
+                held = core.assert_invariants(held);
+            }
+
+            if let Some(depth) = core.exhausted_frame() {
+                core.set_failure(FailureReason::BudgetExhausted);
+                return Ok(match core.unwind_cs(depth, lib_id) {
+                    Some(site) => Jump::Next(site),
+                    None => {
+                        core.terminate_with(TerminationCause::ComplexityExceeded);
+                        Jump::Halt
+                    }
+                });
+            }
+
+            let site = Site::new(lib_id, pos);
+            if is_break(pos) {
+                return Ok(Jump::Break(site));
+            }
+            if hook.before_instr(site, instr) == HookAction::Abort {
+                return Ok(Jump::Hook(site));
+            }
+            if !core.acc_instr() {
+                let _ = core.fail_ck_with(FailureReason::InstrLimit);
+                return Ok(Jump::InstrLimit(site));
+            }
+
+            let next = instr.exec(site, core, context);
+
+            let complexity = exec_complexity(instr, core);
+            let jumped = matches!(next, ExecStep::Jump(_));
+            core.record_profile(site, instr.opcode_byte(), complexity, jumped);
+            if !core.acc_complexity_at(site, complexity) {
+                let _ = core.fail_ck_with(FailureReason::ComplexityLimit);
+                core.terminate_with(TerminationCause::ComplexityExceeded);
+                return Ok(Jump::Halt);
+            }
+            if matches!(next, ExecStep::Jump(_) | ExecStep::Call(_) | ExecStep::Ret(_))
+                && !core.acc_jump()
+                && core.fail_ck_with(FailureReason::JumpLimit)
+            {
+                core.terminate_with(TerminationCause::Halted { site });
+                return Ok(Jump::Halt);
+            }
+            if hook.after_instr(site, instr, core) == HookAction::Abort {
+                return Ok(Jump::Hook(site));
+            }
+            match next {
+                ExecStep::Stop => {
+                    core.terminate();
+                    return Ok(Jump::Halt);
+                }
+                ExecStep::Fail => {
+                    if core.fail_ck() {
+                        core.terminate_with(TerminationCause::Halted { site });
+                        return Ok(Jump::Halt);
+                    }
+                    no += 1;
+                }
+                ExecStep::Next => no += 1,
+                ExecStep::Jump(pos) => {
+                    if pos == self.code_len {
+                        core.terminate_with(TerminationCause::EndOfCode);
+                        return Ok(Jump::Halt);
+                    }
+                    if pos > self.code_len {
+                        let _ = core.fail_ck_with(FailureReason::InvalidJump);
+                        return Ok(Jump::OutOfCode(Site::new(lib_id, pos)));
+                    }
+                    let Some(target) = self.instr_no(pos) else {
+                        core.fail_jump(JumpFault {
+                            source: Some(site),
+                            target: Site::new(lib_id, pos),
+                        });
+                        return Ok(Jump::Halt);
+                    };
+                    no = target;
+                }
+                ExecStep::Call(site) => return Ok(Jump::Instr(site)),
+                ExecStep::Ret(site) => return Ok(Jump::Next(site)),
+            }
+        }
+
+        core.terminate_with(TerminationCause::EndOfCode);
+        Ok(Jump::Halt)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![cfg_attr(coverage_nightly, coverage(off))]
+
+    use alloc::vec;
+    use core::str::FromStr;
+
+    use super::*;
+    use crate::isa::{Alu64Instr, ArithmInstr, CtrlInstr, Instr, RegA};
+    use crate::regs::Status;
+    use crate::{aluasm, CompiledLib, CoreConfig, IsaSeg, LibSite, Vm};
+
+    /// Executes the program both from the bytecode and precompiled, checking that the results
+    /// match.
+    fn differential<Isa>(libs: &[&Lib], entry: LibSite, config: CoreConfig) -> Vm<Isa>
+    where Isa: for<'ctx> Instruction<LibId, Context<'ctx> = ()> {
+        let precompiled = libs
+            .iter()
+            .map(|lib| lib.precompile::<Isa>().unwrap())
+            .collect::<Vec<_>>();
+
+        let mut vm = Vm::<Isa>::with(config, default!());
+        let status = vm.exec(entry, &(), |id| libs.iter().find(|lib| lib.lib_id() == id));
+        let mut vm2 = Vm::<Isa>::with(config, default!());
+        let status2 =
+            vm2.exec_compiled(entry, &(), |id| precompiled.iter().find(|lib| lib.lib_id() == id));
+
+        assert_eq!(status, status2);
+        assert_eq!(vm.core.ck(), vm2.core.ck());
+        assert_eq!(vm.core.co(), vm2.core.co());
+        assert_eq!(vm.core.cy(), vm2.core.cy());
+        assert_eq!(vm.core.ca(), vm2.core.ca());
+        assert_eq!(vm.core.ci(), vm2.core.ci());
+        assert_eq!(vm.core.cp(), vm2.core.cp());
+        assert_eq!(vm.core.last_failure(), vm2.core.last_failure());
+        assert_eq!(vm.core.termination(), vm2.core.termination());
+        assert_eq!(vm.last_error(), vm2.last_error());
+        vm2
+    }
+
+    fn ctrl_corpus() -> Vec<Vec<Instr<LibId>>> {
+        const MAIN: u16 = 0;
+        const END: u16 = 1;
+        const SUB: u16 = 2;
+        const FAIL: u16 = 1;
+
+        vec![
+            aluasm! {
+               routine MAIN:
+                nop;
+                call    SUB;
+                jmp     END;
+                fail    CK;
+               label    END:
+                stop;
+               routine  SUB:
+                nop;
+                ret;
+            },
+            aluasm! {
+               routine MAIN:
+                not     CO;
+                jif     CO, FAIL;
+                stop;
+               label    FAIL:
+                nop;
+                fail    CK;
+                stop;
+            },
+            aluasm! {
+               routine MAIN:
+                chk     CO;
+                chk     CK;
+                jif     CK, MAIN;
+                not     CO;
+                jif     CO, MAIN;
+                stop;
+            },
+            aluasm! {
+               routine MAIN:
+                jmp     MAIN;
+            },
+            aluasm! {
+               routine MAIN:
+                fail    CK;
+                mov     CO, CK;
+                jif     CO, +3;
+                stop;
+                fail    CK;
+            },
+        ]
+    }
+
+    #[test]
+    fn ctrl() {
+        let configs = [
+            CoreConfig::default(),
+            CoreConfig { halt: false, instr_lim: Some(1000), ..default!() },
+            CoreConfig { jump_lim: Some(3), ..default!() },
+            CoreConfig { instr_lim: Some(4), ..default!() },
+            CoreConfig { complexity_lim: Some(40_000), ..default!() },
+        ];
+        for code in ctrl_corpus() {
+            let lib = CompiledLib::compile(code, &[]).unwrap().into_lib();
+            for config in configs {
+                differential::<Instr<LibId>>(&[&lib], LibSite::new(lib.lib_id(), 0), config);
+            }
+        }
+    }
+
+    #[test]
+    fn alu64() {
+        const LOOP: u16 = 16;
+        for n in [5, 20, 21] {
+            let code: Vec<Alu64Instr<LibId>> = vec![
+                ArithmInstr::Put { dst: RegA::A0, val: 1 }.into(),
+                ArithmInstr::Put { dst: RegA::A1, val: n }.into(),
+                ArithmInstr::Put { dst: RegA::A2, val: 1 }.into(),
+                ArithmInstr::Put { dst: RegA::A3, val: 0 }.into(),
+                ArithmInstr::Mul { wrap: false, dst: RegA::A0, src: RegA::A1 }.into(),
+                ArithmInstr::Sub { wrap: false, dst: RegA::A1, src: RegA::A2 }.into(),
+                ArithmInstr::Eq { src1: RegA::A1, src2: RegA::A3 }.into(),
+                CtrlInstr::JiOvfl { pos: LOOP }.into(),
+                CtrlInstr::Stop.into(),
+            ];
+            let lib = Lib::assemble(&code).unwrap();
+            let vm = differential::<Alu64Instr<LibId>>(
+                &[&lib],
+                LibSite::new(lib.lib_id(), 0),
+                default!(),
+            );
+            assert_eq!(vm.core.ck(), if n > 20 { Status::Fail } else { Status::Ok });
+        }
+    }
+
+    #[test]
+    fn remote_calls() {
+        const ENTRY: u16 = 0;
+        const INNER: u16 = 1;
+        let dep = CompiledLib::compile(
+            aluasm! {
+               routine ENTRY:
+                call    INNER;
+                ret;
+               routine INNER:
+                not     CO;
+                ret;
+            },
+            &[],
+        )
+        .unwrap();
+        let dep_id = dep.as_lib().lib_id();
+
+        const MAIN: u16 = 0;
+        let main = CompiledLib::compile(
+            aluasm! {
+               routine MAIN:
+                call    dep_id, ENTRY;
+                call    dep_id, ENTRY;
+                jmp     dep_id, ENTRY;
+            },
+            &[&dep],
+        )
+        .unwrap();
+        let entry = main.routine(MAIN);
+        let (main, dep) = (main.into_lib(), dep.into_lib());
+        let vm = differential::<Instr<LibId>>(&[&main, &dep], entry, default!());
+        assert_eq!(vm.core.cs_high_water(), 2);
+
+        // The dependency is absent
+        differential::<Instr<LibId>>(&[&main], entry, default!());
+    }
+
+    #[test]
+    fn truncated() {
+        let code: [Instr<LibId>; 3] =
+            [CtrlInstr::Nop.into(), CtrlInstr::NotCo.into(), CtrlInstr::Jmp { pos: 0 }.into()];
+        let mut lib = Lib::assemble(&code).unwrap();
+        lib.code_mut().pop();
+        let lib_id = lib.lib_id();
+        let precompiled = lib.precompile::<Instr<LibId>>().unwrap();
+        assert_eq!(precompiled.instrs().len(), 2);
+        // The execution halts once it reaches the code which can't be decoded
+        for offset in [0, 1, 4] {
+            differential::<Instr<LibId>>(&[&lib], LibSite::new(lib_id, offset), default!());
+        }
+
+        // Code which can't be decoded from its start
+        lib.code_mut().remove(0).unwrap();
+        lib.code_mut().remove(0).unwrap();
+        let vm = differential::<Instr<LibId>>(&[&lib], LibSite::new(lib.lib_id(), 0), default!());
+        assert_eq!(vm.core.ck(), Status::Ok);
+        assert_eq!(vm.core.ci(), 0);
+    }
+
+    #[test]
+    fn mid_instr_jump() {
+        let code: [Instr<LibId>; 3] = [
+            CtrlInstr::Jmp { pos: 3 }.into(),
+            CtrlInstr::Fn { pos: 0x0101 }.into(),
+            CtrlInstr::Stop.into(),
+        ];
+        let mut lib = Lib::assemble(&code).unwrap();
+        lib.code_mut()[1] = 4;
+        let lib_id = lib.lib_id();
+        let precompiled = lib.precompile::<Instr<LibId>>().unwrap();
+
+        let mut vm = Vm::<Instr<LibId>>::new();
+        assert_eq!(
+            vm.exec_compiled(LibSite::new(lib_id, 0), &(), |_| Some(&precompiled)),
+            Status::Fail
+        );
+        assert_eq!(
+            vm.core.jump_fault(),
+            Some(JumpFault {
+                source: Some(Site::new(lib_id, 0)),
+                target: Site::new(lib_id, 4)
+            })
+        );
+
+        let mut vm = Vm::<Instr<LibId>>::new();
+        assert_eq!(
+            vm.exec_compiled(LibSite::new(lib_id, 1), &(), |_| Some(&precompiled)),
+            Status::Fail
+        );
+        assert_eq!(
+            vm.core.jump_fault(),
+            Some(JumpFault { source: None, target: Site::new(lib_id, 1) })
+        );
+    }
+
+    #[test]
+    fn unsupported_isa() {
+        let mut lib = Lib::assemble::<Instr<LibId>>(&[CtrlInstr::Stop.into()]).unwrap();
+        *lib.isae_mut() = IsaSeg::from_str("ALU64").unwrap();
+        assert!(lib.precompile::<Instr<LibId>>().is_err());
+        assert!(lib.precompile::<Alu64Instr<LibId>>().is_ok());
+    }
+}
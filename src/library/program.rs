@@ -0,0 +1,331 @@
+// Reference rust implementation of AluVM (arithmetic logic unit virtual machine).
+// To find more on AluVM please check <https://aluvm.org>
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Designed in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+// Written in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+//
+// Copyright (C) 2021-2024 LNP/BP Standards Association, Switzerland.
+// Copyright (C) 2024-2025 Laboratories for Ubiquitous Deterministic Computing (UBIDECO),
+//                         Institute for Distributed and Cognitive Systems (InDCS), Switzerland.
+// Copyright (C) 2021-2025 Dr Maxim Orlovsky.
+// All rights under the above copyrights are reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use this file except
+// in compliance with the License. You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed under the License
+// is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express
+// or implied. See the License for the specific language governing permissions and limitations under
+// the License.
+
+use alloc::collections::{BTreeMap, BTreeSet};
+
+use crate::core::Status;
+use crate::isa::Instruction;
+use crate::{ExecSuspension, IsaId, Lib, LibId, LibSite, LibValidationError, SuspendedVm, Vm};
+
+/// Errors constructing or verifying a [`Program`].
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum ProgramError {
+    /// entry point offset {1:#06x} lies outside of the code segment of the main library {0}.
+    InvalidEntry(LibId, u16),
+
+    /// library {0} depends on library {1}, which is neither a part of the program nor marked as
+    /// external.
+    MissingDep(LibId, LibId),
+
+    /// library {0} requires ISA extension {1}, which is not supported by the instruction set.
+    UnsupportedIsa(LibId, IsaId),
+
+    /// library {0} is invalid: {1}
+    InvalidLib(LibId, LibValidationError),
+}
+
+/// Program consisting of the main library, an entry point in it, and all libraries it depends on.
+///
+/// # Example
+///
+/// ```
+/// # extern crate alloc;
+/// # use aluvm::isa::Instr;
+/// # use aluvm::regs::Status;
+/// # use aluvm::{aluasm, CompiledLib, LibId, Program, Vm};
+/// # let dep = CompiledLib::compile(aluasm! { nop; ret; }, &[]).unwrap();
+/// # let dep_id = dep.as_lib().lib_id();
+/// # let main = CompiledLib::compile(aluasm! { call dep_id, 0; stop; }, &[&dep]).unwrap();
+/// # let (main, dep) = (main.into_lib(), dep.into_lib());
+/// let program = Program::new(main, 0)?.with_dep(dep)?;
+/// let mut vm = Vm::<Instr<LibId>>::new();
+/// assert_eq!(program.run(&mut vm, &()), Ok(Status::Ok));
+/// # Ok::<_, aluvm::ProgramError>(())
+/// ```
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Program {
+    entry: LibSite,
+    libs: BTreeMap<LibId, Lib>,
+    external: BTreeSet<LibId>,
+}
+
+impl Program {
+    /// Constructs a program from the main library and an offset of the entry point in it.
+    ///
+    /// # Errors
+    ///
+    /// If the entry point lies outside the code segment of the library.
+    pub fn new(main: Lib, entry: u16) -> Result<Self, ProgramError> {
+        let lib_id = main.lib_id();
+        if entry as usize >= main.code().len() {
+            return Err(ProgramError::InvalidEntry(lib_id, entry));
+        }
+        Ok(Self {
+            entry: LibSite::new(lib_id, entry),
+            libs: bmap! { lib_id => main },
+            external: none!(),
+        })
+    }
+
+    /// Marks a library as external, i.e. such that it is not required to be a part of the
+    /// program.
+    ///
+    /// Calls into external libraries fail at runtime.
+    pub fn with_external(mut self, lib_id: LibId) -> Self {
+        self.external.insert(lib_id);
+        self
+    }
+
+    /// Adds a dependency library to the program.
+    ///
+    /// # Errors
+    ///
+    /// If the library depends on a library which is neither a part of the program nor marked as
+    /// external.
+    pub fn with_dep(self, lib: Lib) -> Result<Self, ProgramError> { self.with_deps([lib]) }
+
+    /// Adds multiple dependency libraries to the program.
+    ///
+    /// The libraries may depend on each other, irrespectively of the order they are provided in.
+    ///
+    /// # Errors
+    ///
+    /// If any of the libraries depends on a library which is neither a part of the program nor
+    /// marked as external.
+    pub fn with_deps(mut self, libs: impl IntoIterator<Item = Lib>) -> Result<Self, ProgramError> {
+        let ids = libs
+            .into_iter()
+            .map(|lib| {
+                let lib_id = lib.lib_id();
+                self.libs.insert(lib_id, lib);
+                lib_id
+            })
+            .collect::<Vec<_>>();
+        for lib_id in ids {
+            self.check_deps(lib_id)?;
+        }
+        Ok(self)
+    }
+
+    /// Returns the program entry point.
+    pub fn entry_point(&self) -> LibSite { self.entry }
+
+    /// Returns the main program library.
+    pub fn main(&self) -> &Lib { &self.libs[&self.entry.lib_id] }
+
+    /// Returns a library which is a part of the program.
+    pub fn lib(&self, lib_id: LibId) -> Option<&Lib> { self.libs.get(&lib_id) }
+
+    /// Iterates over all libraries of the program, including the main one.
+    pub fn libs(&self) -> impl Iterator<Item = &Lib> { self.libs.values() }
+
+    /// Checks that all libraries the program libraries depend on are either a part of the program
+    /// or marked as external.
+    pub fn check_closure(&self) -> Result<(), ProgramError> {
+        for lib_id in self.libs.keys() {
+            self.check_deps(*lib_id)?;
+        }
+        Ok(())
+    }
+
+    /// Verifies the program against an instruction set, checking that
+    /// - all dependencies are present (see [`Self::check_closure`]);
+    /// - all ISA extensions used by the libraries are supported by the instruction set;
+    /// - all libraries are valid (see [`Lib::validate`]).
+    pub fn verify<Isa>(&self) -> Result<(), ProgramError>
+    where Isa: Instruction<LibId> {
+        self.check_closure()?;
+        let isae = Isa::isa_ext();
+        for (lib_id, lib) in &self.libs {
+            if let Some(isa) = lib.isae().iter().find(|isa| !isae.contains(*isa)) {
+                return Err(ProgramError::UnsupportedIsa(*lib_id, isa.clone()));
+            }
+            lib.validate::<Isa>()
+                .map_err(|err| ProgramError::InvalidLib(*lib_id, err))?;
+        }
+        Ok(())
+    }
+
+    /// Runs the program on a virtual machine starting from its entry point.
+    ///
+    /// # Returns
+    ///
+    /// Value of the `CK` register at the end of the program execution.
+    ///
+    /// # Errors
+    ///
+    /// If some of the dependencies are missing (see [`Self::check_closure`]); in this case the
+    /// program is not run.
+    pub fn run<Isa>(
+        &self,
+        vm: &mut Vm<Isa>,
+        context: &Isa::Context<'_>,
+    ) -> Result<Status, ProgramError>
+    where
+        Isa: Instruction<LibId>,
+    {
+        self.check_closure()?;
+        Ok(vm.exec(self.entry, context, |lib_id| self.libs.get(&lib_id)))
+    }
+
+    /// Runs the program on a virtual machine starting from its entry point, taking the external
+    /// libraries from the `external` resolver.
+    ///
+    /// If an external library can't be resolved, the execution is suspended (see
+    /// [`Vm::exec_suspendable`]) and can be continued with [`Self::resume`] once the library
+    /// becomes available.
+    ///
+    /// # Errors
+    ///
+    /// If some of the dependencies are missing (see [`Self::check_closure`]); in this case the
+    /// program is not run.
+    pub fn exec_suspendable<'lib, Isa>(
+        &'lib self,
+        vm: Vm<Isa>,
+        context: &Isa::Context<'_>,
+        external: impl Fn(LibId) -> Option<&'lib Lib>,
+    ) -> Result<ExecSuspension<Isa>, ProgramError>
+    where
+        Isa: Instruction<LibId>,
+    {
+        self.check_closure()?;
+        Ok(vm.exec_suspendable(self.entry, context, |lib_id| {
+            self.libs.get(&lib_id).or_else(|| external(lib_id))
+        }))
+    }
+
+    /// Continues the program execution suspended by [`Self::exec_suspendable`].
+    pub fn resume<'lib, Isa>(
+        &'lib self,
+        state: SuspendedVm<Isa>,
+        context: &Isa::Context<'_>,
+        external: impl Fn(LibId) -> Option<&'lib Lib>,
+    ) -> ExecSuspension<Isa>
+    where
+        Isa: Instruction<LibId>,
+    {
+        Vm::resume_after_host_abort(state, context, |lib_id| {
+            self.libs.get(&lib_id).or_else(|| external(lib_id))
+        })
+    }
+
+    fn check_deps(&self, lib_id: LibId) -> Result<(), ProgramError> {
+        let lib = &self.libs[&lib_id];
+        match lib
+            .libs()
+            .iter()
+            .find(|dep| !self.libs.contains_key(*dep) && !self.external.contains(*dep))
+        {
+            Some(dep) => Err(ProgramError::MissingDep(lib_id, *dep)),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![cfg_attr(coverage_nightly, coverage(off))]
+
+    use super::*;
+    use crate::isa::Instr;
+    use crate::{aluasm, CompiledLib};
+
+    fn libs() -> (Lib, Lib, Lib) {
+        let leaf = CompiledLib::compile(aluasm! { nop; ret; }, &[]).unwrap();
+        let leaf_id = leaf.as_lib().lib_id();
+        let dep = CompiledLib::compile(aluasm! { nop; call leaf_id, 0; ret; }, &[&leaf]).unwrap();
+        let dep_id = dep.as_lib().lib_id();
+        let main = CompiledLib::compile(aluasm! { call dep_id, 0; stop; }, &[&dep]).unwrap();
+        (main.into_lib(), dep.into_lib(), leaf.into_lib())
+    }
+
+    #[test]
+    fn invalid_entry() {
+        let (main, ..) = libs();
+        let lib_id = main.lib_id();
+        let len = main.code().len() as u16;
+        assert_eq!(Program::new(main, len), Err(ProgramError::InvalidEntry(lib_id, len)));
+    }
+
+    #[test]
+    fn missing_dep() {
+        let (main, dep, leaf) = libs();
+        let dep_id = dep.lib_id();
+        let err = Program::new(main.clone(), 0)
+            .unwrap()
+            .with_dep(dep.clone())
+            .unwrap_err();
+        assert_eq!(err, ProgramError::MissingDep(dep_id, leaf.lib_id()));
+
+        let program = Program::new(main, 0).unwrap();
+        assert_eq!(
+            program.check_closure(),
+            Err(ProgramError::MissingDep(program.entry_point().lib_id, dep_id))
+        );
+        let mut vm = Vm::<Instr<LibId>>::new();
+        assert!(program.run(&mut vm, &()).is_err());
+    }
+
+    #[test]
+    fn external_dep() {
+        let (main, dep, leaf) = libs();
+        let program = Program::new(main, 0)
+            .unwrap()
+            .with_external(leaf.lib_id())
+            .with_dep(dep)
+            .unwrap();
+        assert_eq!(program.check_closure(), Ok(()));
+        let mut vm = Vm::<Instr<LibId>>::new();
+        assert_eq!(program.run(&mut vm, &()), Ok(Status::Fail));
+    }
+
+    #[test]
+    fn full_closure() {
+        let (main, dep, leaf) = libs();
+        let main_id = main.lib_id();
+        let program = Program::new(main, 0)
+            .unwrap()
+            .with_deps([dep, leaf])
+            .unwrap();
+        assert_eq!(program.main().lib_id(), main_id);
+        assert_eq!(program.libs().count(), 3);
+        assert_eq!(program.verify::<Instr<LibId>>(), Ok(()));
+        let mut vm = Vm::<Instr<LibId>>::new();
+        assert_eq!(program.run(&mut vm, &()), Ok(Status::Ok));
+    }
+
+    #[test]
+    fn unsupported_isa() {
+        let (mut main, ..) = libs();
+        *main.libs_mut() = none!();
+        *main.isae_mut() = IsaId::canonical_set([IsaId::from("GFA")]);
+        let lib_id = main.lib_id();
+        let program = Program::new(main, 0).unwrap();
+        assert_eq!(
+            program.verify::<Instr<LibId>>(),
+            Err(ProgramError::UnsupportedIsa(lib_id, IsaId::from("GFA")))
+        );
+    }
+}
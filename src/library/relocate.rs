@@ -0,0 +1,205 @@
+// Reference rust implementation of AluVM (arithmetic logic unit virtual machine).
+// To find more on AluVM please check <https://aluvm.org>
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Designed in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+// Written in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+//
+// Copyright (C) 2021-2024 LNP/BP Standards Association, Switzerland.
+// Copyright (C) 2024-2025 Laboratories for Ubiquitous Deterministic Computing (UBIDECO),
+//                         Institute for Distributed and Cognitive Systems (InDCS), Switzerland.
+// Copyright (C) 2021-2025 Dr Maxim Orlovsky.
+// All rights under the above copyrights are reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use this file except
+// in compliance with the License. You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed under the License
+// is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express
+// or implied. See the License for the specific language governing permissions and limitations under
+// the License.
+
+use alloc::collections::BTreeSet;
+use alloc::vec::Vec;
+
+use amplify::confinement::SmallBlob;
+
+use super::{Lib, LibId, LibsSeg, MarshallError, Marshaller};
+use crate::isa::Instruction;
+use crate::Site;
+
+/// Errors relocating a library dependency with [`Lib::relocate_dependency`].
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum RelocationError {
+    /// library code can't be decoded under the instruction set.
+    InvalidCode,
+
+    /// library doesn't depend on library {0}.
+    NoDependency(LibId),
+
+    /// instructions at offsets {offsets:?} reference offsets of library {old} which have no
+    /// mapping to the new library.
+    Unmapped {
+        /// Relocated dependency.
+        old: LibId,
+        /// Offsets of the instructions in the code segment, in increasing order.
+        offsets: Vec<u16>,
+    },
+
+    /// Error encoding the relocated code (see [`MarshallError`] for the details).
+    #[from]
+    #[display(inner)]
+    Encode(MarshallError),
+}
+
+impl Lib {
+    /// Replaces a dependency of the library with its new version, for instance, after the
+    /// dependency was recompiled.
+    ///
+    /// All the instructions referencing a site in the `old` library (like `call` or `exec`) are
+    /// rewritten to reference the `new` library, at the offset provided by `offset_map` for the
+    /// original offset. The libs segment is rebuilt to include `new` instead of `old`, while the
+    /// code and data segments are re-encoded; since the size of the instructions doesn't change,
+    /// the local jumps are kept as they are.
+    ///
+    /// The relocation is deterministic: the same library, dependencies and offset mapping always
+    /// produce the same library.
+    ///
+    /// # Errors
+    ///
+    /// If the code can't be decoded, the library doesn't reference `old`, or some of the offsets
+    /// in `old` have no mapping; in the latter case the error lists offsets of all the
+    /// instructions referencing such offsets.
+    pub fn relocate_dependency<Isa>(
+        &self,
+        old: LibId,
+        new: LibId,
+        offset_map: impl Fn(u16) -> Option<u16>,
+    ) -> Result<Lib, RelocationError>
+    where
+        Isa: Instruction<LibId>,
+    {
+        if !self.libs().contains(&old) {
+            return Err(RelocationError::NoDependency(old));
+        }
+        let mut code = self
+            .disassemble_with_offsets::<Isa>()
+            .map_err(|_| RelocationError::InvalidCode)?;
+
+        let mut unmapped = Vec::new();
+        for (pos, instr) in &mut code {
+            let Some(site) = instr.remote_goto_pos().filter(|site| site.prog_id == old) else {
+                continue;
+            };
+            match offset_map(site.offset) {
+                Some(offset) => *site = Site::new(new, offset),
+                None => unmapped.push(*pos),
+            }
+        }
+        if !unmapped.is_empty() {
+            return Err(RelocationError::Unmapped { old, offsets: unmapped });
+        }
+
+        let libs = self
+            .libs()
+            .iter()
+            .copied()
+            .filter(|id| *id != old)
+            .chain([new])
+            .collect::<BTreeSet<_>>();
+        let libs = LibsSeg::from_checked(libs);
+        let mut writer = Marshaller::resume(Vec::new(), self.data().to_vec(), &libs);
+        for (_, instr) in &code {
+            instr.encode_instr(&mut writer)?;
+        }
+        let (code_segment, data_segment) = writer.into_buffers();
+
+        Ok(Lib::with(
+            self.isae().clone(),
+            SmallBlob::from_checked(code_segment),
+            SmallBlob::from_checked(data_segment),
+            libs,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![cfg_attr(coverage_nightly, coverage(off))]
+
+    use super::*;
+    use crate::isa::{CtrlInstr, Instr};
+
+    fn code(first: Site<LibId>, second: Site<LibId>, third: Site<LibId>) -> [Instr<LibId>; 5] {
+        [
+            CtrlInstr::Call { site: first }.into(),
+            CtrlInstr::Call { site: second }.into(),
+            CtrlInstr::Jmp { pos: 13 }.into(),
+            CtrlInstr::Exec { site: third }.into(),
+            CtrlInstr::Stop.into(),
+        ]
+    }
+
+    #[test]
+    fn relocate() {
+        let (old, new, other) =
+            (LibId::from([1u8; 32]), LibId::from([9u8; 32]), LibId::from([5u8; 32]));
+        let lib = Lib::assemble(&code(Site::new(old, 0), Site::new(other, 4), Site::new(old, 8)))
+            .unwrap();
+        assert_eq!(lib.libs(), &LibsSeg::from_checked(bset![old, other]));
+
+        let relocated = lib
+            .relocate_dependency::<Instr<LibId>>(old, new, |pos| Some(pos + 0x100))
+            .unwrap();
+        // Only the sites in the relocated library are changed, while the library index of the
+        // other dependency is shifted in the libs segment
+        let expected = code(Site::new(new, 0x100), Site::new(other, 4), Site::new(new, 0x108));
+        assert_eq!(relocated, Lib::assemble(&expected).unwrap());
+        assert_eq!(relocated.libs(), &LibsSeg::from_checked(bset![other, new]));
+        assert_eq!(relocated.disassemble::<Instr<LibId>>().unwrap(), expected);
+
+        // The relocation is byte-stable and reversible
+        assert_eq!(
+            lib.relocate_dependency::<Instr<LibId>>(old, new, |pos| Some(pos + 0x100))
+                .unwrap(),
+            relocated
+        );
+        assert_eq!(
+            relocated
+                .relocate_dependency::<Instr<LibId>>(new, old, |pos| pos.checked_sub(0x100))
+                .unwrap(),
+            lib
+        );
+    }
+
+    #[test]
+    fn relocate_errors() {
+        let (old, new, other) =
+            (LibId::from([1u8; 32]), LibId::from([9u8; 32]), LibId::from([5u8; 32]));
+        let lib = Lib::assemble(&code(Site::new(old, 0), Site::new(other, 4), Site::new(old, 8)))
+            .unwrap();
+        assert_eq!(
+            lib.relocate_dependency::<Instr<LibId>>(new, old, Some),
+            Err(RelocationError::NoDependency(new))
+        );
+        assert_eq!(
+            lib.relocate_dependency::<Instr<LibId>>(old, new, |_| None),
+            Err(RelocationError::Unmapped { old, offsets: vec![0, 11] })
+        );
+        assert_eq!(
+            lib.relocate_dependency::<Instr<LibId>>(old, new, |pos| (pos == 0).then_some(pos)),
+            Err(RelocationError::Unmapped { old, offsets: vec![11] })
+        );
+
+        let mut broken = lib.clone();
+        *broken.code_mut() = SmallBlob::from_checked(lib.code()[..6].to_vec());
+        assert_eq!(
+            broken.relocate_dependency::<Instr<LibId>>(old, new, Some),
+            Err(RelocationError::InvalidCode)
+        );
+    }
+}
@@ -0,0 +1,196 @@
+// Reference rust implementation of AluVM (arithmetic logic unit virtual machine).
+// To find more on AluVM please check <https://aluvm.org>
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Designed in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+// Written in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+//
+// Copyright (C) 2021-2024 LNP/BP Standards Association, Switzerland.
+// Copyright (C) 2024-2025 Laboratories for Ubiquitous Deterministic Computing (UBIDECO),
+//                         Institute for Distributed and Cognitive Systems (InDCS), Switzerland.
+// Copyright (C) 2021-2025 Dr Maxim Orlovsky.
+// All rights under the above copyrights are reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use this file except
+// in compliance with the License. You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed under the License
+// is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express
+// or implied. See the License for the specific language governing permissions and limitations under
+// the License.
+
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::vec::Vec;
+
+use crate::{Lib, LibId};
+
+/// Collection of libraries indexed by their ids, used to resolve libraries during the program
+/// execution (see [`crate::Vm::exec_repo`]).
+///
+/// Libraries are always indexed by their own ids (see [`Lib::lib_id`]). Since the id of a library
+/// commits to its libs segment, a library can't depend on itself, directly or through its
+/// dependencies: the library dependencies always form an acyclic graph, and no cycle detection is
+/// required when walking them.
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct LibRepo {
+    libs: BTreeMap<LibId, Lib>,
+}
+
+impl LibRepo {
+    /// Constructs an empty repository.
+    pub fn new() -> Self { none!() }
+
+    /// Adds a library to the repository.
+    ///
+    /// # Returns
+    ///
+    /// Id of the added library.
+    pub fn insert(&mut self, lib: Lib) -> LibId {
+        let lib_id = lib.lib_id();
+        self.libs.insert(lib_id, lib);
+        lib_id
+    }
+
+    /// Returns a library from the repository.
+    pub fn get(&self, lib_id: LibId) -> Option<&Lib> { self.libs.get(&lib_id) }
+
+    /// Checks whether the repository contains a library.
+    pub fn contains(&self, lib_id: LibId) -> bool { self.libs.contains_key(&lib_id) }
+
+    /// Returns the number of libraries in the repository.
+    pub fn len(&self) -> usize { self.libs.len() }
+
+    /// Checks whether the repository has no libraries.
+    pub fn is_empty(&self) -> bool { self.libs.is_empty() }
+
+    /// Iterates over all libraries in the repository, ordered by their ids.
+    pub fn libs(&self) -> impl Iterator<Item = &Lib> { self.libs.values() }
+
+    /// Returns library resolver which can be provided to the [`crate::Vm`] execution methods.
+    pub fn resolver<'repo>(&'repo self) -> impl Fn(LibId) -> Option<&'repo Lib> + 'repo {
+        |lib_id| self.get(lib_id)
+    }
+
+    /// Checks that the library and all its transitive dependencies are present in the repository.
+    ///
+    /// # Errors
+    ///
+    /// Ids of all missing libraries reachable from the `entry` library, ordered by their value.
+    pub fn check_dependencies(&self, entry: LibId) -> Result<(), Vec<LibId>> {
+        let mut missing = BTreeSet::new();
+        self.visit(entry, &mut bset![], &mut vec![], &mut missing);
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(missing.into_iter().collect())
+        }
+    }
+
+    /// Returns the ids of the library and all its transitive dependencies present in the
+    /// repository, such that each library goes after all of its dependencies.
+    ///
+    /// The `entry` library, if present, is always the last one. Missing libraries are skipped
+    /// (see [`Self::check_dependencies`]).
+    pub fn dependency_order(&self, entry: LibId) -> Vec<LibId> {
+        let mut order = vec![];
+        self.visit(entry, &mut bset![], &mut order, &mut bset![]);
+        order
+    }
+
+    fn visit(
+        &self,
+        lib_id: LibId,
+        visited: &mut BTreeSet<LibId>,
+        order: &mut Vec<LibId>,
+        missing: &mut BTreeSet<LibId>,
+    ) {
+        if !visited.insert(lib_id) {
+            return;
+        }
+        let Some(lib) = self.libs.get(&lib_id) else {
+            missing.insert(lib_id);
+            return;
+        };
+        for dep in lib.libs() {
+            self.visit(*dep, visited, order, missing);
+        }
+        order.push(lib_id);
+    }
+}
+
+impl FromIterator<Lib> for LibRepo {
+    fn from_iter<T: IntoIterator<Item = Lib>>(iter: T) -> Self {
+        let mut repo = Self::new();
+        repo.extend(iter);
+        repo
+    }
+}
+
+impl Extend<Lib> for LibRepo {
+    fn extend<T: IntoIterator<Item = Lib>>(&mut self, iter: T) {
+        for lib in iter {
+            self.insert(lib);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![cfg_attr(coverage_nightly, coverage(off))]
+
+    use super::*;
+    use crate::isa::Instr;
+    use crate::regs::Status;
+    use crate::{aluasm, CompiledLib, LibSite, Vm};
+
+    fn libs() -> (Lib, Lib, Lib) {
+        let leaf = CompiledLib::compile(aluasm! { nop; ret; }, &[]).unwrap();
+        let leaf_id = leaf.as_lib().lib_id();
+        let dep = CompiledLib::compile(aluasm! { nop; call leaf_id, 0; ret; }, &[&leaf]).unwrap();
+        let dep_id = dep.as_lib().lib_id();
+        let main = CompiledLib::compile(aluasm! { call dep_id, 0; stop; }, &[&dep]).unwrap();
+        (main.into_lib(), dep.into_lib(), leaf.into_lib())
+    }
+
+    #[test]
+    fn missing_leaf() {
+        let (main, dep, leaf) = libs();
+        let (main_id, dep_id, leaf_id) = (main.lib_id(), dep.lib_id(), leaf.lib_id());
+        let mut repo = LibRepo::from_iter([main, dep]);
+        assert_eq!(repo.len(), 2);
+        assert_eq!(repo.check_dependencies(main_id), Err(vec![leaf_id]));
+        assert_eq!(repo.check_dependencies(leaf_id), Err(vec![leaf_id]));
+        assert_eq!(repo.dependency_order(main_id), vec![dep_id, main_id]);
+
+        let mut vm = Vm::<Instr<LibId>>::new();
+        assert_eq!(vm.exec_repo(LibSite::new(main_id, 0), &(), &repo), Status::Fail);
+
+        assert_eq!(repo.insert(leaf), leaf_id);
+        assert_eq!(repo.check_dependencies(main_id), Ok(()));
+        assert_eq!(repo.check_dependencies(dep_id), Ok(()));
+        assert_eq!(repo.dependency_order(main_id), vec![leaf_id, dep_id, main_id]);
+        assert_eq!(repo.dependency_order(dep_id), vec![leaf_id, dep_id]);
+
+        let mut vm = Vm::<Instr<LibId>>::new();
+        assert_eq!(vm.exec_repo(LibSite::new(main_id, 0), &(), &repo), Status::Ok);
+    }
+
+    #[test]
+    fn shared_dep() {
+        let leaf = CompiledLib::compile(aluasm! { nop; ret; }, &[]).unwrap();
+        let leaf_id = leaf.as_lib().lib_id();
+        let dep = CompiledLib::compile(aluasm! { nop; call leaf_id, 0; ret; }, &[&leaf]).unwrap();
+        let dep_id = dep.as_lib().lib_id();
+        let main = aluasm! { call leaf_id, 0; call dep_id, 0; stop; };
+        let main = CompiledLib::compile(main, &[&leaf, &dep]).unwrap();
+        let main_id = main.as_lib().lib_id();
+        let repo = LibRepo::from_iter([main.into_lib(), dep.into_lib(), leaf.into_lib()]);
+        assert_eq!(repo.check_dependencies(main_id), Ok(()));
+        assert_eq!(repo.dependency_order(main_id), vec![leaf_id, dep_id, main_id]);
+        assert!(repo.resolver()(main_id).is_some());
+        assert!(LibRepo::new().is_empty());
+    }
+}
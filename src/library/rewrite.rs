@@ -0,0 +1,487 @@
+// Reference rust implementation of AluVM (arithmetic logic unit virtual machine).
+// To find more on AluVM please check <https://aluvm.org>
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Designed in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+// Written in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+//
+// Copyright (C) 2021-2024 LNP/BP Standards Association, Switzerland.
+// Copyright (C) 2024-2025 Laboratories for Ubiquitous Deterministic Computing (UBIDECO),
+//                         Institute for Distributed and Cognitive Systems (InDCS), Switzerland.
+// Copyright (C) 2021-2025 Dr Maxim Orlovsky.
+// All rights under the above copyrights are reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use this file except
+// in compliance with the License. You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed under the License
+// is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express
+// or implied. See the License for the specific language governing permissions and limitations under
+// the License.
+
+use alloc::collections::BTreeMap;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use super::{AssemblerError, Lib, LibId, MarshallError};
+use crate::isa::{CodeEofError, GotoTarget, Instruction};
+
+/// Errors rewriting library code with [`Lib::map_instrs`].
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum RewriteError {
+    /// library code can't be decoded.
+    #[from]
+    InvalidCode(CodeEofError),
+
+    /// instruction at offset {0:#06X} jumps to offset {1:#06X}, which is not an instruction
+    /// boundary.
+    InvalidJump(u16, u16),
+
+    /// instruction at offset {0:#06X} jumps to offset {1:#06X}, which has been deleted by the
+    /// rewrite.
+    DeletedTarget(u16, u16),
+
+    /// relative jump of the instruction at offset {0:#06X} doesn't fit the rewritten code.
+    JumpOutOfRange(u16),
+
+    /// code edit at offset {0:#06X} doesn't point to an instruction boundary.
+    InvalidEdit(u16),
+
+    /// Error assembling the rewritten library (see [`AssemblerError`] for the details).
+    #[from]
+    #[display(inner)]
+    Assemble(AssemblerError),
+}
+
+/// Edit of the library code applied by [`Lib::relocate`].
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum CodeEdit<Isa: Instruction<LibId>> {
+    /// Insert instructions before the instruction at the offset, or at the end of the code, if
+    /// the offset equals to the code segment length.
+    ///
+    /// The jumps to the offset are redirected to the first inserted instruction.
+    Insert {
+        /// Offset of the insertion point in the original code.
+        offset: u16,
+        /// Inserted instructions.
+        code: Vec<Isa>,
+    },
+
+    /// Remove the instruction at the offset.
+    Remove {
+        /// Offset of the removed instruction in the original code.
+        offset: u16,
+    },
+}
+
+impl<Isa: Instruction<LibId>> CodeEdit<Isa> {
+    /// Returns the offset of the edit in the original code.
+    pub fn offset(&self) -> u16 {
+        match self {
+            CodeEdit::Insert { offset, .. } | CodeEdit::Remove { offset } => *offset,
+        }
+    }
+}
+
+impl Lib {
+    /// Inserts and removes instructions at the given offsets of the library code, adjusting the
+    /// local jump targets to the new offsets of the instructions (see [`Lib::map_instrs`]).
+    ///
+    /// The edits refer to the offsets in the original code. Multiple insertions at the same offset
+    /// are applied in their order; the local jump targets of the inserted instructions must also
+    /// refer to the offsets in the original code.
+    ///
+    /// # Errors
+    ///
+    /// If an edit doesn't point to an instruction boundary, and in all the cases described for
+    /// [`Lib::map_instrs`], including a jump to a removed instruction.
+    pub fn relocate<Isa>(&self, edits: &[CodeEdit<Isa>]) -> Result<Lib, RewriteError>
+    where Isa: Instruction<LibId> {
+        let end = self.code().len();
+        let mut applied = vec![false; edits.len()];
+        let mut tail = Vec::new();
+        for (edit, applied) in edits.iter().zip(&mut applied) {
+            if let CodeEdit::Insert { offset, code } = edit {
+                if *offset as usize == end {
+                    tail.extend(code.iter().cloned());
+                    *applied = true;
+                }
+            }
+        }
+        let lib = self.rewrite(
+            |pos, instr: Isa| {
+                let mut code = Vec::new();
+                let mut keep = true;
+                for (edit, applied) in edits.iter().zip(&mut applied) {
+                    match edit {
+                        CodeEdit::Insert { offset, code: inserted } if *offset == pos => {
+                            code.extend(inserted.iter().cloned());
+                        }
+                        CodeEdit::Remove { offset } if *offset == pos => keep = false,
+                        _ => continue,
+                    }
+                    *applied = true;
+                }
+                if keep {
+                    code.push(instr);
+                }
+                code
+            },
+            tail,
+        )?;
+        match edits.iter().zip(applied).find(|(_, applied)| !applied) {
+            Some((edit, _)) => Err(RewriteError::InvalidEdit(edit.offset())),
+            None => Ok(lib),
+        }
+    }
+
+    /// Rewrites the library code by mapping each instruction into a sequence of instructions,
+    /// which may be empty (deleting the instruction), or contain a single or multiple
+    /// instructions. The produced instructions may belong to a different instruction set than
+    /// the original ones (see [`Lib::migrate`]).
+    ///
+    /// The mapping function receives the offset of the instruction in the original code. The
+    /// local jump targets of the produced instructions must still refer to the offsets in the
+    /// original code; they are fixed up to point to the first instruction produced from the
+    /// target instruction. The result is assembled with [`Lib::assemble`], which recomputes the
+    /// ISA extension and libs segments.
+    ///
+    /// # Errors
+    ///
+    /// If the code can't be decoded, if it contains a jump to the middle of an instruction or to
+    /// a deleted instruction, if a relative jump doesn't fit the rewritten code, or if the
+    /// rewritten code doesn't fit the code segment.
+    pub fn map_instrs<Old, New, I>(&self, f: impl FnMut(u16, Old) -> I) -> Result<Lib, RewriteError>
+    where
+        Old: Instruction<LibId>,
+        New: Instruction<LibId>,
+        I: IntoIterator<Item = New>,
+    {
+        self.rewrite(f, [])
+    }
+
+    /// Rewrites the library code like [`Lib::map_instrs`], appending the `tail` instructions to
+    /// the end of the code. The jumps to the end of the original code are redirected to the first
+    /// tail instruction.
+    fn rewrite<Old, New, I>(
+        &self,
+        mut f: impl FnMut(u16, Old) -> I,
+        tail: impl IntoIterator<Item = New>,
+    ) -> Result<Lib, RewriteError>
+    where
+        Old: Instruction<LibId>,
+        New: Instruction<LibId>,
+        I: IntoIterator<Item = New>,
+    {
+        let mut code = Vec::new();
+        // Original offsets of the produced instructions, and their offsets in the rewritten code
+        let mut offsets = Vec::new();
+        // Offsets of the original instruction boundaries in the rewritten code; `None` for the
+        // deleted instructions
+        let mut positions = BTreeMap::new();
+        let mut new_pos = 0u16;
+        let mut push = |old_pos: u16, new: New, new_pos: &mut u16| {
+            offsets.push((old_pos, *new_pos));
+            *new_pos = new_pos
+                .checked_add(new.code_byte_len())
+                .ok_or(AssemblerError::Bytecode(MarshallError::CodeNotFittingSegment))?;
+            code.push(new);
+            Ok::<_, RewriteError>(())
+        };
+        for item in self.instr_iter::<Old>() {
+            let (old_pos, old) = item?;
+            let start = new_pos;
+            for new in f(old_pos, old) {
+                push(old_pos, new, &mut new_pos)?;
+            }
+            positions.insert(old_pos, (new_pos > start).then_some(start));
+        }
+        let end = self.code().len() as u16;
+        positions.insert(end, Some(new_pos));
+        for new in tail {
+            push(end, new, &mut new_pos)?;
+        }
+
+        let resolve = |old_pos: u16, target: u16| match positions.get(&target) {
+            Some(Some(pos)) => Ok(*pos),
+            Some(None) => Err(RewriteError::DeletedTarget(old_pos, target)),
+            None => Err(RewriteError::InvalidJump(old_pos, target)),
+        };
+        for (instr, (old_pos, new_pos)) in code.iter_mut().zip(&offsets) {
+            match instr.local_goto_pos() {
+                GotoTarget::None => {}
+                GotoTarget::Absolute(pos) => *pos = resolve(*old_pos, *pos)?,
+                GotoTarget::Relative(shift) => {
+                    let target = old_pos
+                        .checked_add_signed(*shift as i16)
+                        .ok_or(RewriteError::JumpOutOfRange(*old_pos))?;
+                    let target = resolve(*old_pos, target)?;
+                    *shift = i8::try_from(target as i32 - *new_pos as i32)
+                        .map_err(|_| RewriteError::JumpOutOfRange(*old_pos))?;
+                }
+                GotoTarget::RelativeWide(shift) => {
+                    let target = old_pos
+                        .checked_add_signed(*shift)
+                        .ok_or(RewriteError::JumpOutOfRange(*old_pos))?;
+                    let target = resolve(*old_pos, target)?;
+                    *shift = i16::try_from(target as i32 - *new_pos as i32)
+                        .map_err(|_| RewriteError::JumpOutOfRange(*old_pos))?;
+                }
+                GotoTarget::Table(table) => {
+                    for pos in table {
+                        *pos = resolve(*old_pos, *pos)?;
+                    }
+                }
+            }
+        }
+
+        Ok(Lib::assemble(&code)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![cfg_attr(coverage_nightly, coverage(off))]
+
+    use alloc::vec;
+
+    use super::*;
+    use crate::isa::{CtrlInstr, Instr};
+    use crate::{LibSite, Vm};
+
+    const NOP: Instr<LibId> = Instr::Ctrl(CtrlInstr::Nop);
+
+    fn lib(code: impl IntoIterator<Item = CtrlInstr<LibId>>) -> Lib {
+        Lib::assemble(&code.into_iter().map(Instr::from).collect::<Vec<_>>()).unwrap()
+    }
+
+    fn disassemble(lib: &Lib) -> Vec<CtrlInstr<LibId>> {
+        lib.disassemble::<Instr<LibId>>()
+            .unwrap()
+            .into_iter()
+            .map(|instr| match instr {
+                Instr::Ctrl(instr) => instr,
+                Instr::Reserved(_) => unreachable!(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn identity() {
+        let lib = lib([CtrlInstr::Nop, CtrlInstr::Jmp { pos: 4 }, CtrlInstr::Sh { shift: 2 }]);
+        assert_eq!(lib.map_instrs(|_, instr: Instr<LibId>| [instr]).unwrap(), lib);
+    }
+
+    #[test]
+    fn delete_nops() {
+        let lib = lib([
+            CtrlInstr::NotCo,
+            CtrlInstr::Nop,
+            CtrlInstr::JiOvfl { pos: 8 },
+            CtrlInstr::Nop,
+            CtrlInstr::FailCk,
+            CtrlInstr::Nop,
+            CtrlInstr::Fn { pos: 13 },
+            CtrlInstr::Stop,
+            CtrlInstr::Nop,
+            CtrlInstr::Ret,
+        ]);
+        let mut offsets = vec![];
+        let rewritten = lib
+            .map_instrs(|pos, instr: Instr<LibId>| {
+                offsets.push(pos);
+                (instr != NOP).then_some(instr)
+            })
+            .unwrap();
+        assert_eq!(offsets, [0, 1, 2, 5, 6, 7, 8, 11, 12, 13]);
+        assert_eq!(disassemble(&rewritten), [
+            CtrlInstr::NotCo,
+            CtrlInstr::JiOvfl { pos: 5 },
+            CtrlInstr::FailCk,
+            CtrlInstr::Fn { pos: 9 },
+            CtrlInstr::Stop,
+            CtrlInstr::Ret,
+        ]);
+
+        let mut vm = Vm::<Instr<LibId>>::new();
+        let status = vm.exec(LibSite::new(lib.lib_id(), 0), &(), |_| Some(&lib));
+        let mut vm2 = Vm::<Instr<LibId>>::new();
+        let status2 = vm2.exec(LibSite::new(rewritten.lib_id(), 0), &(), |_| Some(&rewritten));
+        assert_eq!(status, status2);
+        assert_eq!(vm.core.co(), vm2.core.co());
+        assert_eq!(vm.core.cy(), vm2.core.cy());
+        assert_eq!(vm.core.ci(), vm2.core.ci() + 1);
+    }
+
+    #[test]
+    fn expand() {
+        let lib = lib([CtrlInstr::Sh { shift: 3 }, CtrlInstr::Nop, CtrlInstr::Stop]);
+        let rewritten = lib
+            .map_instrs(
+                |_, instr: Instr<LibId>| if instr == NOP { vec![NOP; 3] } else { vec![instr] },
+            )
+            .unwrap();
+        assert_eq!(disassemble(&rewritten), [
+            CtrlInstr::Sh { shift: 5 },
+            CtrlInstr::Nop,
+            CtrlInstr::Nop,
+            CtrlInstr::Nop,
+            CtrlInstr::Stop,
+        ]);
+
+        assert_eq!(
+            lib.map_instrs(|_, instr: Instr<LibId>| if instr == NOP {
+                vec![NOP; 200]
+            } else {
+                vec![instr]
+            }),
+            Err(RewriteError::JumpOutOfRange(0))
+        );
+    }
+
+    #[test]
+    fn invalid_targets() {
+        let lib = lib([CtrlInstr::Jmp { pos: 3 }, CtrlInstr::Nop, CtrlInstr::Stop]);
+        assert_eq!(
+            lib.map_instrs(|_, instr: Instr<LibId>| (instr != NOP).then_some(instr)),
+            Err(RewriteError::DeletedTarget(0, 3))
+        );
+
+        let lib = self::lib([CtrlInstr::Jmp { pos: 1 }, CtrlInstr::Stop]);
+        assert_eq!(
+            lib.map_instrs(|_, instr: Instr<LibId>| [instr]),
+            Err(RewriteError::InvalidJump(0, 1))
+        );
+    }
+
+    #[test]
+    fn relocate() {
+        // A jump forward, a call backward, and a relative jump spanning the edit points
+        let lib = lib([
+            CtrlInstr::Jmp { pos: 8 },
+            CtrlInstr::Nop,
+            CtrlInstr::Sh { shift: 3 },
+            CtrlInstr::NotCo,
+            CtrlInstr::Ret,
+            CtrlInstr::Nop,
+            CtrlInstr::Fn { pos: 6 },
+            CtrlInstr::Stop,
+        ]);
+        let insert = |offset, code: &[CtrlInstr<LibId>]| CodeEdit::Insert {
+            offset,
+            code: code.iter().copied().map(Instr::from).collect(),
+        };
+
+        // Before the jump sources and targets
+        let relocated = lib.relocate(&[insert(0, &[CtrlInstr::FailCk])]).unwrap();
+        assert_eq!(disassemble(&relocated), [
+            CtrlInstr::FailCk,
+            CtrlInstr::Jmp { pos: 9 },
+            CtrlInstr::Nop,
+            CtrlInstr::Sh { shift: 3 },
+            CtrlInstr::NotCo,
+            CtrlInstr::Ret,
+            CtrlInstr::Nop,
+            CtrlInstr::Fn { pos: 7 },
+            CtrlInstr::Stop,
+        ]);
+
+        // Between the sources and the targets, and at the targets themselves
+        let relocated = lib
+            .relocate(&[
+                insert(6, &[CtrlInstr::Nop, CtrlInstr::Nop]),
+                insert(8, &[CtrlInstr::FailCk]),
+                insert(6, &[CtrlInstr::ChkCo]),
+                CodeEdit::Remove { offset: 3 },
+            ])
+            .unwrap();
+        assert_eq!(disassemble(&relocated), [
+            CtrlInstr::Jmp { pos: 10 },
+            CtrlInstr::Sh { shift: 6 },
+            CtrlInstr::Nop,
+            CtrlInstr::Nop,
+            CtrlInstr::ChkCo,
+            CtrlInstr::NotCo,
+            CtrlInstr::Ret,
+            CtrlInstr::FailCk,
+            CtrlInstr::Nop,
+            CtrlInstr::Fn { pos: 5 },
+            CtrlInstr::Stop,
+        ]);
+
+        // After the sources and the targets, including the end of the code
+        let relocated = lib
+            .relocate(&[insert(12, &[CtrlInstr::NotCo]), insert(13, &[CtrlInstr::Ret])])
+            .unwrap();
+        assert_eq!(disassemble(&relocated), [
+            CtrlInstr::Jmp { pos: 8 },
+            CtrlInstr::Nop,
+            CtrlInstr::Sh { shift: 3 },
+            CtrlInstr::NotCo,
+            CtrlInstr::Ret,
+            CtrlInstr::Nop,
+            CtrlInstr::Fn { pos: 6 },
+            CtrlInstr::NotCo,
+            CtrlInstr::Stop,
+            CtrlInstr::Ret,
+        ]);
+    }
+
+    #[test]
+    fn relocate_empty() {
+        let lib = lib([]);
+        let relocated = lib
+            .relocate(&[CodeEdit::Insert {
+                offset: 0,
+                code: vec![NOP, CtrlInstr::Jmp { pos: 0 }.into()],
+            }])
+            .unwrap();
+        assert_eq!(disassemble(&relocated), [CtrlInstr::Nop, CtrlInstr::Jmp { pos: 0 }]);
+        assert_eq!(
+            lib.relocate::<Instr<LibId>>(&[CodeEdit::Remove { offset: 0 }]),
+            Err(RewriteError::InvalidEdit(0))
+        );
+    }
+
+    #[test]
+    fn relocate_errors() {
+        let lib = lib([CtrlInstr::Jmp { pos: 4 }, CtrlInstr::Nop, CtrlInstr::Sh { shift: -1 }]);
+        assert_eq!(
+            lib.relocate::<Instr<LibId>>(&[CodeEdit::Remove { offset: 3 }]),
+            Err(RewriteError::DeletedTarget(4, 3))
+        );
+        assert_eq!(
+            lib.relocate::<Instr<LibId>>(&[CodeEdit::Remove { offset: 2 }]),
+            Err(RewriteError::InvalidEdit(2))
+        );
+        assert_eq!(
+            lib.relocate(&[CodeEdit::Insert { offset: 7, code: vec![NOP] }]),
+            Err(RewriteError::InvalidEdit(7))
+        );
+        assert_eq!(
+            lib.relocate(&[CodeEdit::Insert { offset: 3, code: vec![NOP; 200] }]),
+            Err(RewriteError::JumpOutOfRange(4))
+        );
+        assert_eq!(
+            lib.relocate(&[CodeEdit::Insert { offset: 0, code: vec![NOP; u16::MAX as usize] }]),
+            Err(RewriteError::Assemble(AssemblerError::Bytecode(
+                MarshallError::CodeNotFittingSegment
+            )))
+        );
+    }
+
+    #[test]
+    fn code_overflow() {
+        let lib = lib([CtrlInstr::Nop]);
+        assert_eq!(
+            lib.map_instrs(|_, _: Instr<LibId>| vec![NOP; u16::MAX as usize + 1]),
+            Err(RewriteError::Assemble(AssemblerError::Bytecode(
+                MarshallError::CodeNotFittingSegment
+            )))
+        );
+    }
+}
@@ -0,0 +1,296 @@
+// Reference rust implementation of AluVM (arithmetic logic unit virtual machine).
+// To find more on AluVM please check <https://aluvm.org>
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Designed in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+// Written in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+//
+// Copyright (C) 2021-2024 LNP/BP Standards Association, Switzerland.
+// Copyright (C) 2024-2025 Laboratories for Ubiquitous Deterministic Computing (UBIDECO),
+//                         Institute for Distributed and Cognitive Systems (InDCS), Switzerland.
+// Copyright (C) 2021-2025 Dr Maxim Orlovsky.
+// All rights under the above copyrights are reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use this file except
+// in compliance with the License. You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed under the License
+// is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express
+// or implied. See the License for the specific language governing permissions and limitations under
+// the License.
+
+use core::str::FromStr;
+
+use amplify::confinement::TinyOrdMap;
+use strict_encoding::stl::{AlphaLodash, AlphaNumLodash};
+use strict_encoding::{RString, StrictDeserialize, StrictDumb, StrictSerialize};
+
+use super::{Lib, LibId};
+use crate::LIB_NAME_ALUVM;
+
+/// Maximal length of a symbol name.
+pub const SYMBOL_NAME_MAX_LEN: usize = 32;
+
+/// Name of a constant in the library data segment (see [`LibSymbols`]).
+///
+/// Symbol name is an ASCII identifier, starting with a letter or an underscore and consisting of
+/// letters, digits and underscores, with a maximal length up to [`SYMBOL_NAME_MAX_LEN`].
+#[derive(Wrapper, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, From)]
+#[wrapper(Deref, Display, FromStr)]
+#[derive(StrictType, StrictEncode, StrictDecode)]
+#[strict_type(lib = LIB_NAME_ALUVM)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(transparent))]
+pub struct SymbolName(RString<AlphaLodash, AlphaNumLodash, 1, SYMBOL_NAME_MAX_LEN>);
+
+impl StrictDumb for SymbolName {
+    fn strict_dumb() -> Self { Self::from("dumb") }
+}
+
+impl From<&'static str> for SymbolName {
+    fn from(name: &'static str) -> Self { Self(RString::from(name)) }
+}
+
+/// Range of bytes in the library data segment.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default, Display)]
+#[display("{offset:#06X}..+{len}")]
+#[derive(StrictType, StrictEncode, StrictDecode)]
+#[strict_type(lib = LIB_NAME_ALUVM)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DataRange {
+    /// Offset of the first byte in the data segment.
+    pub offset: u16,
+    /// Number of bytes in the range.
+    pub len: u16,
+}
+
+impl DataRange {
+    /// Constructs a new data range.
+    #[inline]
+    pub const fn new(offset: u16, len: u16) -> Self { Self { offset, len } }
+
+    /// Returns the end of the range, i.e. the offset following its last byte.
+    #[inline]
+    pub const fn end(self) -> usize { self.offset as usize + self.len as usize }
+}
+
+/// Errors defining symbols of a library data segment.
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum SymbolError {
+    /// symbol '{0}' is already defined.
+    Duplicate(SymbolName),
+
+    /// symbol '{name}' at {range} exceeds the data segment of {data_len} bytes.
+    OutOfRange {
+        /// Name of the symbol.
+        name: SymbolName,
+        /// Range of the data segment given to the symbol.
+        range: DataRange,
+        /// Length of the data segment.
+        data_len: usize,
+    },
+
+    /// data of symbol '{0}' don't fit the data segment.
+    DataOverflow(SymbolName),
+
+    /// the number of symbols exceeds 255.
+    TooMany,
+}
+
+/// Symbol table of a library, naming immutable constants stored in its data segment.
+///
+/// The symbols are not a part of the library, and thus don't affect its id: the table is kept
+/// alongside the library, referring to it by its [`LibId`]. Use [`Lib::symbol`] to access the
+/// constants.
+///
+/// The table is produced by [`crate::LibAssembler::finish_with_symbols`], or constructed for an
+/// existing library with [`LibSymbols::with`].
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[derive(StrictType, StrictDumb, StrictEncode, StrictDecode)]
+#[strict_type(lib = LIB_NAME_ALUVM)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(rename_all = "camelCase"))]
+pub struct LibSymbols {
+    lib_id: LibId,
+    symbols: TinyOrdMap<SymbolName, DataRange>,
+}
+
+impl StrictSerialize for LibSymbols {}
+impl StrictDeserialize for LibSymbols {}
+
+impl LibSymbols {
+    /// Constructs a symbol table for the library.
+    ///
+    /// # Errors
+    ///
+    /// If a symbol is defined twice, if its range exceeds the library data segment, or if the
+    /// number of symbols exceeds 255.
+    pub fn with(
+        lib: &Lib,
+        symbols: impl IntoIterator<Item = (SymbolName, DataRange)>,
+    ) -> Result<Self, SymbolError> {
+        let data_len = lib.data().len();
+        let mut table = TinyOrdMap::new();
+        for (name, range) in symbols {
+            if range.end() > data_len {
+                return Err(SymbolError::OutOfRange { name, range, data_len });
+            }
+            if table.contains_key(&name) {
+                return Err(SymbolError::Duplicate(name));
+            }
+            table
+                .insert(name, range)
+                .map_err(|_| SymbolError::TooMany)?;
+        }
+        Ok(Self { lib_id: lib.lib_id(), symbols: table })
+    }
+
+    /// Returns the id of the library the symbols are defined for.
+    #[inline]
+    pub fn lib_id(&self) -> LibId { self.lib_id }
+
+    /// Returns the data segment range of the symbol, if it is defined.
+    pub fn get(&self, name: &str) -> Option<DataRange> {
+        let name = SymbolName::from_str(name).ok()?;
+        self.symbols.get(&name).copied()
+    }
+
+    /// Iterates over the symbols, ordered by their names.
+    pub fn iter(&self) -> impl Iterator<Item = (&SymbolName, DataRange)> {
+        self.symbols.iter().map(|(name, range)| (name, *range))
+    }
+
+    /// Returns the number of symbols.
+    #[inline]
+    pub fn len(&self) -> usize { self.symbols.len() }
+
+    /// Detects whether there are no symbols.
+    #[inline]
+    pub fn is_empty(&self) -> bool { self.symbols.is_empty() }
+}
+
+impl Lib {
+    /// Returns the constant named by the symbol from the library data segment.
+    ///
+    /// Returns `None` if the symbols are defined for another library, if there is no such symbol,
+    /// or if its range doesn't fit the data segment (which may be the case for a symbol table
+    /// decoded from an untrusted source).
+    pub fn symbol(&self, symbols: &LibSymbols, name: &str) -> Option<&[u8]> {
+        if symbols.lib_id != self.lib_id() {
+            return None;
+        }
+        let range = symbols.get(name)?;
+        self.data().get(range.offset as usize..range.end())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![cfg_attr(coverage_nightly, coverage(off))]
+
+    use super::*;
+    use crate::isa::Instr;
+
+    fn lib() -> Lib {
+        let lib = Lib::assemble::<Instr<LibId>>(&[]).unwrap();
+        lib.extend_data(b"modulus:root").unwrap().0
+    }
+
+    #[test]
+    fn lookup() {
+        let lib = lib();
+        let symbols = LibSymbols::with(&lib, [
+            (SymbolName::from("modulus"), DataRange::new(0, 7)),
+            (SymbolName::from("root"), DataRange::new(8, 4)),
+            (SymbolName::from("_empty"), DataRange::new(12, 0)),
+        ])
+        .unwrap();
+        assert_eq!(symbols.lib_id(), lib.lib_id());
+        assert_eq!(symbols.len(), 3);
+        assert_eq!(lib.symbol(&symbols, "modulus"), Some(&b"modulus"[..]));
+        assert_eq!(lib.symbol(&symbols, "root"), Some(&b"root"[..]));
+        assert_eq!(lib.symbol(&symbols, "_empty"), Some(&b""[..]));
+        assert_eq!(lib.symbol(&symbols, "absent"), None);
+        assert_eq!(lib.symbol(&symbols, "not a name"), None);
+        assert_eq!(
+            symbols
+                .iter()
+                .map(|(name, _)| name.to_string())
+                .collect::<Vec<_>>(),
+            ["_empty", "modulus", "root"]
+        );
+
+        // Symbols are bound to the library they are defined for
+        let other = lib.extend_data(b"!").unwrap().0;
+        assert_eq!(other.symbol(&symbols, "root"), None);
+    }
+
+    #[test]
+    fn duplicate() {
+        assert_eq!(
+            LibSymbols::with(&lib(), [
+                (SymbolName::from("root"), DataRange::new(8, 4)),
+                (SymbolName::from("root"), DataRange::new(0, 1)),
+            ]),
+            Err(SymbolError::Duplicate(SymbolName::from("root")))
+        );
+    }
+
+    #[test]
+    fn out_of_range() {
+        for range in
+            [DataRange::new(8, 5), DataRange::new(13, 0), DataRange::new(u16::MAX, u16::MAX)]
+        {
+            let err = LibSymbols::with(&lib(), [(SymbolName::from("root"), range)]).unwrap_err();
+            assert_eq!(err, SymbolError::OutOfRange {
+                name: SymbolName::from("root"),
+                range,
+                data_len: 12
+            });
+        }
+        assert_eq!(
+            SymbolError::OutOfRange {
+                name: SymbolName::from("root"),
+                range: DataRange::new(8, 5),
+                data_len: 12
+            }
+            .to_string(),
+            "symbol 'root' at 0x0008..+5 exceeds the data segment of 12 bytes."
+        );
+    }
+
+    #[test]
+    fn too_many() {
+        let (lib, _) = lib().extend_data(&[0; 256]).unwrap();
+        let names = (0..256).map(|no| SymbolName::from_str(&format!("s{no}")).unwrap());
+        let symbols = names.map(|name| (name, DataRange::new(0, 1)));
+        assert_eq!(
+            LibSymbols::with(&lib, symbols.clone().take(255))
+                .unwrap()
+                .len(),
+            255
+        );
+        assert_eq!(LibSymbols::with(&lib, symbols), Err(SymbolError::TooMany));
+    }
+
+    #[test]
+    fn invalid_names() {
+        for name in ["", "1st", "with space", "dash-ed", &"x".repeat(SYMBOL_NAME_MAX_LEN + 1)] {
+            assert!(SymbolName::from_str(name).is_err(), "{name}");
+        }
+        assert!(SymbolName::from_str(&"x".repeat(SYMBOL_NAME_MAX_LEN)).is_ok());
+    }
+
+    #[test]
+    fn strict_encoding() {
+        let lib = lib();
+        let symbols =
+            LibSymbols::with(&lib, [(SymbolName::from("root"), DataRange::new(8, 4))]).unwrap();
+        let data = symbols.to_strict_serialized::<0xFFFF>().unwrap();
+        let decoded = LibSymbols::from_strict_serialized::<0xFFFF>(data).unwrap();
+        assert_eq!(decoded, symbols);
+        assert_eq!(lib.symbol(&decoded, "root"), Some(&b"root"[..]));
+    }
+}
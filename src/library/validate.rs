@@ -0,0 +1,492 @@
+// Reference rust implementation of AluVM (arithmetic logic unit virtual machine).
+// To find more on AluVM please check <https://aluvm.org>
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Designed in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+// Written in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+//
+// Copyright (C) 2021-2024 LNP/BP Standards Association, Switzerland.
+// Copyright (C) 2024-2025 Laboratories for Ubiquitous Deterministic Computing (UBIDECO),
+//                         Institute for Distributed and Cognitive Systems (InDCS), Switzerland.
+// Copyright (C) 2021-2025 Dr Maxim Orlovsky.
+// All rights under the above copyrights are reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use this file except
+// in compliance with the License. You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed under the License
+// is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express
+// or implied. See the License for the specific language governing permissions and limitations under
+// the License.
+
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::vec::Vec;
+
+use amplify::confinement::SmallBlob;
+
+use super::flow::local_targets;
+use super::LibsSeg;
+use crate::isa::{BytecodeRead, Instruction};
+use crate::{InvalidJump, IsaId, IsaSeg, Lib, LibId, Marshaller};
+
+/// Errors in library validation.
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum LibValidationError {
+    /// library code segment contains data at offset {0:#06x} which can't be decoded as an
+    /// instruction.
+    InvalidCode(u16),
+
+    /// instruction at offset {1:#06x} belongs to ISA extension {0}, which is not declared by the
+    /// library.
+    UndeclaredIsa(IsaId, u16),
+
+    /// instruction at offset {1:#06x} refers to library {0}, which is not present in the libs
+    /// segment.
+    UnknownLib(LibId, u16),
+
+    /// {0}
+    InvalidJump(InvalidJump),
+}
+
+/// Report on consistency of the ISA extensions declared by a library with its code.
+///
+/// Produced by [`Lib::isa_consistency_report`].
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct IsaConsistencyReport {
+    /// ISA extensions used by the code but not declared by the library, each with an offset of
+    /// the first instruction using it.
+    ///
+    /// This is a hard inconsistency, causing [`Lib::validate`] to fail.
+    pub undeclared: BTreeMap<IsaId, u16>,
+
+    /// ISA extensions declared by the library, but not used by any of its instructions.
+    pub unused: BTreeSet<IsaId>,
+
+    /// Reserved opcodes present in the code, each with an offset of the instruction.
+    pub reserved: BTreeMap<u16, u8>,
+
+    /// Offset of the code segment data which can't be decoded as an instruction, if any.
+    ///
+    /// The report covers only the code preceding this offset.
+    pub invalid_code: Option<u16>,
+}
+
+impl IsaConsistencyReport {
+    /// Checks whether the report contains no hard inconsistencies.
+    pub fn is_consistent(&self) -> bool {
+        self.undeclared.is_empty() && self.invalid_code.is_none()
+    }
+
+    /// Converts the report into the first of the hard inconsistencies, if any.
+    pub fn into_result(self) -> Result<Self, LibValidationError> {
+        if let Some(offset) = self.invalid_code {
+            return Err(LibValidationError::InvalidCode(offset));
+        }
+        let first = self
+            .undeclared
+            .iter()
+            .min_by_key(|(_, offset)| **offset)
+            .map(|(isa, offset)| (isa.clone(), *offset));
+        match first {
+            Some((isa, offset)) => Err(LibValidationError::UndeclaredIsa(isa, offset)),
+            None => Ok(self),
+        }
+    }
+}
+
+impl Lib {
+    /// Decodes the library code segment and checks whether the ISA extensions used by the
+    /// instructions match the ones declared in [`Lib::isae`].
+    pub fn isa_consistency_report<Isa>(&self) -> IsaConsistencyReport
+    where Isa: Instruction<LibId> {
+        let mut report = IsaConsistencyReport::default();
+        let mut used = BTreeSet::new();
+        let mut reader = Marshaller::with_unchecked(self.code(), self.data(), self.libs());
+        while !reader.is_eof() {
+            let pos = reader.pos();
+            let Ok(instr) = Isa::decode_instr(&mut reader) else {
+                report.invalid_code = Some(pos);
+                break;
+            };
+            if instr.is_reserved() {
+                report.reserved.insert(pos, instr.opcode_byte());
+            }
+            if let Some(ext) = instr.isa_ext_id() {
+                let isa = IsaId::from(ext);
+                if !self.isae().contains(&isa) {
+                    report.undeclared.entry(isa.clone()).or_insert(pos);
+                }
+                used.insert(isa);
+            }
+        }
+        report.unused = self
+            .isae()
+            .iter()
+            .filter(|isa| !used.contains(*isa))
+            .cloned()
+            .collect();
+        report
+    }
+
+    /// Constructs a library from its segments, validating it against an instruction set.
+    ///
+    /// # Errors
+    ///
+    /// If the library doesn't pass [`Lib::validate`].
+    pub fn checked<Isa>(
+        isae: IsaSeg,
+        libs: LibsSeg,
+        code: SmallBlob,
+        data: SmallBlob,
+    ) -> Result<Lib, LibValidationError>
+    where
+        Isa: Instruction<LibId>,
+    {
+        let lib = Lib::with(isae, code, data, libs);
+        lib.validate::<Isa>()?;
+        Ok(lib)
+    }
+
+    /// Validates the library against an instruction set.
+    ///
+    /// # Errors
+    ///
+    /// If the code segment can't be decoded, which includes a truncated last instruction and
+    /// instructions referencing data outside the data segment, or uses an ISA extension not
+    /// declared by the library (see [`Lib::isa_consistency_report`]); if an instruction refers to
+    /// a library which is not present in the libs segment; also if some of the local jumps, either
+    /// absolute or relative, target an offset outside the code segment or in the middle of an
+    /// instruction (see [`Lib::validate_jumps`]). In the latter case, the first of the invalid
+    /// jumps is reported.
+    pub fn validate<Isa>(&self) -> Result<(), LibValidationError>
+    where Isa: Instruction<LibId> {
+        self.isa_consistency_report::<Isa>().into_result()?;
+        self.validate_refs::<Isa>()?;
+        self.validate_jumps::<Isa>()
+            .map_err(|errs| LibValidationError::InvalidJump(errs[0]))
+    }
+
+    /// Validates the code segment and the local jumps in it, requiring each jump to target an
+    /// instruction which can be used as a goto target.
+    ///
+    /// This is stricter than the jump validation performed by [`Lib::validate`], which only
+    /// requires the jump targets to be instruction boundaries: here the instruction at each target
+    /// must also return `true` from [`Instruction::is_goto_target`]. The ISA extensions and the
+    /// external library references are not checked.
+    ///
+    /// # Errors
+    ///
+    /// If the code segment can't be decoded, or if a local jump, either absolute or relative,
+    /// targets an offset outside the code segment, in the middle of an instruction, or an
+    /// instruction which is not a goto target. A jump to the offset right past the end of the
+    /// code segment is valid: it halts the program, like proceeding past the last instruction. The
+    /// first of the invalid jumps is reported, with the offset of the jump instruction and its
+    /// target.
+    pub fn validate_code<Isa>(&self) -> Result<(), LibValidationError>
+    where Isa: Instruction<LibId> {
+        let mut boundaries = BTreeSet::new();
+        let mut goto_targets = BTreeSet::new();
+        let mut jumps = Vec::new();
+        let mut reader = Marshaller::with_unchecked(self.code(), self.data(), self.libs());
+        while !reader.is_eof() {
+            let pos = reader.pos();
+            let mut instr =
+                Isa::decode_instr(&mut reader).map_err(|_| LibValidationError::InvalidCode(pos))?;
+            boundaries.insert(pos);
+            if instr.is_goto_target() {
+                goto_targets.insert(pos);
+            }
+            jumps.extend(
+                local_targets(&mut instr, pos)
+                    .into_iter()
+                    .map(|target| (pos, target)),
+            );
+        }
+        for (source, target) in jumps {
+            let target = target.map_err(LibValidationError::InvalidJump)?;
+            let err = if target as usize == self.code().len() {
+                continue;
+            } else if target as usize > self.code().len() {
+                InvalidJump::OutOfCode { source }
+            } else if !boundaries.contains(&target) {
+                InvalidJump::MidInstruction { source, target }
+            } else if !goto_targets.contains(&target) {
+                InvalidJump::NotGotoTarget { source, target }
+            } else {
+                continue;
+            };
+            return Err(LibValidationError::InvalidJump(err));
+        }
+        Ok(())
+    }
+
+    /// Checks that all the external library references are present in the libs segment.
+    ///
+    /// The decoder fails on a reference outside the libs segment, reporting it as invalid code;
+    /// this check covers the instruction sets which read the external references in other ways.
+    fn validate_refs<Isa>(&self) -> Result<(), LibValidationError>
+    where Isa: Instruction<LibId> {
+        let mut reader = Marshaller::with_unchecked(self.code(), self.data(), self.libs());
+        while !reader.is_eof() {
+            let pos = reader.pos();
+            let instr =
+                Isa::decode_instr(&mut reader).map_err(|_| LibValidationError::InvalidCode(pos))?;
+            match instr.external_ref() {
+                Some(lib_id) if !self.libs().contains(&lib_id) => {
+                    return Err(LibValidationError::UnknownLib(lib_id, pos))
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![cfg_attr(coverage_nightly, coverage(off))]
+
+    use alloc::collections::BTreeSet;
+    use core::fmt::{self, Display, Formatter};
+    use core::ops::RangeInclusive;
+
+    use super::*;
+    use crate::core::{Core, NoExt, NoRegs, Site};
+    use crate::isa::{
+        Bytecode, BytecodeWrite, CodeEofError, CtrlInstr, ExecStep, GotoTarget, Instr,
+    };
+
+    /// Instruction set attributing jumps to the `JMPX` extension.
+    #[derive(Clone, PartialEq, Eq, Debug)]
+    struct ExtInstr(Instr<LibId>);
+
+    impl Display for ExtInstr {
+        fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result { Display::fmt(&self.0, f) }
+    }
+
+    impl Bytecode<LibId> for ExtInstr {
+        fn op_range() -> RangeInclusive<u8> { Instr::<LibId>::op_range() }
+        fn opcode_byte(&self) -> u8 { self.0.opcode_byte() }
+        fn code_byte_len(&self) -> u16 { self.0.code_byte_len() }
+        fn external_ref(&self) -> Option<LibId> { self.0.external_ref() }
+        fn encode_operands<W>(&self, writer: &mut W) -> Result<(), W::Error>
+        where W: BytecodeWrite<LibId> {
+            self.0.encode_operands(writer)
+        }
+        fn decode_operands<R>(reader: &mut R, opcode: u8) -> Result<Self, CodeEofError>
+        where R: BytecodeRead<LibId> {
+            Instr::decode_operands(reader, opcode).map(Self)
+        }
+    }
+
+    impl Instruction<LibId> for ExtInstr {
+        const ISA_EXT: &'static [&'static str] = &["JMPX"];
+        type Core = NoExt;
+        type Context<'ctx> = ();
+
+        fn isa_ext_id(&self) -> Option<&'static str> {
+            matches!(self.0, Instr::Ctrl(CtrlInstr::Jmp { .. })).then_some("JMPX")
+        }
+        fn is_reserved(&self) -> bool { self.0.is_reserved() }
+        fn is_goto_target(&self) -> bool { self.0.is_goto_target() }
+        fn local_goto_pos(&mut self) -> GotoTarget<'_> { self.0.local_goto_pos() }
+        fn remote_goto_pos(&mut self) -> Option<&mut Site<LibId>> { self.0.remote_goto_pos() }
+        fn src_regs(&self) -> BTreeSet<NoRegs> { self.0.src_regs() }
+        fn dst_regs(&self) -> BTreeSet<NoRegs> { self.0.dst_regs() }
+        fn op_data_bytes(&self) -> u16 { self.0.op_data_bytes() }
+        fn ext_data_bytes(&self) -> u16 { self.0.ext_data_bytes() }
+        fn exec<const CALL_STACK_SIZE: usize>(
+            &self,
+            site: Site<LibId>,
+            core: &mut Core<LibId, NoExt, CALL_STACK_SIZE>,
+            context: &(),
+        ) -> ExecStep<Site<LibId>> {
+            self.0.exec(site, core, context)
+        }
+    }
+
+    fn lib(isae: &[&'static str], code: &[u8]) -> Lib {
+        Lib::with(
+            IsaId::canonical_set(isae.iter().copied().map(IsaId::from)),
+            SmallBlob::try_from_slice(code).unwrap(),
+            none!(),
+            none!(),
+        )
+    }
+
+    const NOP: u8 = CtrlInstr::<LibId>::NOP;
+    const JMP: u8 = CtrlInstr::<LibId>::JMP;
+    const STOP: u8 = CtrlInstr::<LibId>::STOP;
+
+    #[test]
+    fn consistent() {
+        let lib = lib(&["JMPX"], &[NOP, JMP, 0, 0, STOP]);
+        let report = lib.isa_consistency_report::<ExtInstr>();
+        assert!(report.is_consistent());
+        assert_eq!(report, IsaConsistencyReport::default());
+        assert_eq!(lib.validate::<ExtInstr>(), Ok(()));
+    }
+
+    #[test]
+    fn undeclared() {
+        let lib = lib(&[], &[NOP, NOP, JMP, 0, 0, JMP, 0, 0, STOP]);
+        let report = lib.isa_consistency_report::<ExtInstr>();
+        assert!(!report.is_consistent());
+        assert_eq!(report.undeclared, bmap! { IsaId::from("JMPX") => 2 });
+        assert!(report.unused.is_empty());
+        assert!(report.reserved.is_empty());
+        assert_eq!(
+            lib.validate::<ExtInstr>(),
+            Err(LibValidationError::UndeclaredIsa(IsaId::from("JMPX"), 2))
+        );
+    }
+
+    #[test]
+    fn unused() {
+        let lib = lib(&["JMPX", "OTHER"], &[NOP, STOP]);
+        let report = lib.isa_consistency_report::<ExtInstr>();
+        assert!(report.is_consistent());
+        assert_eq!(report.unused, bset! { IsaId::from("JMPX"), IsaId::from("OTHER") });
+        assert!(report.undeclared.is_empty());
+        assert_eq!(lib.validate::<ExtInstr>(), Ok(()));
+    }
+
+    #[test]
+    fn reserved() {
+        let lib = lib(&["JMPX"], &[NOP, 0xFF, JMP, 0, 0, 0x80]);
+        let report = lib.isa_consistency_report::<ExtInstr>();
+        assert!(report.is_consistent());
+        assert_eq!(report.reserved, bmap! { 1 => 0xFF, 5 => 0x80 });
+        assert_eq!(lib.validate::<ExtInstr>(), Ok(()));
+    }
+
+    #[test]
+    fn invalid_jumps() {
+        const SH: u8 = CtrlInstr::<LibId>::SH;
+        let mid = lib(&["JMPX"], &[NOP, JMP, 2, 0, STOP]);
+        assert!(mid.isa_consistency_report::<ExtInstr>().is_consistent());
+        assert_eq!(
+            mid.validate::<ExtInstr>(),
+            Err(LibValidationError::InvalidJump(InvalidJump::MidInstruction {
+                source: 1,
+                target: 2
+            }))
+        );
+        let out = lib(&["JMPX"], &[NOP, JMP, 0, 0, SH, 0xFB]);
+        assert_eq!(
+            out.validate::<ExtInstr>(),
+            Err(LibValidationError::InvalidJump(InvalidJump::OutOfCode { source: 4 }))
+        );
+        let start = lib(&["JMPX"], &[NOP, JMP, 0, 0, SH, 0xFC]);
+        assert_eq!(start.validate::<ExtInstr>(), Ok(()));
+    }
+
+    #[test]
+    fn validate_code() {
+        const SH: u8 = CtrlInstr::<LibId>::SH;
+        let valid = lib(&[], &[NOP, JMP, 0, 0, NOP, SH, 0xFF, STOP]);
+        assert_eq!(valid.validate_code::<ExtInstr>(), Ok(()));
+
+        // Passes the regular validation, which only checks for instruction boundaries
+        let not_target = lib(&["JMPX"], &[NOP, JMP, 4, 0, STOP]);
+        assert_eq!(not_target.validate::<ExtInstr>(), Ok(()));
+        assert_eq!(
+            not_target.validate_code::<ExtInstr>(),
+            Err(LibValidationError::InvalidJump(InvalidJump::NotGotoTarget {
+                source: 1,
+                target: 4
+            }))
+        );
+        let mid = lib(&[], &[NOP, JMP, 2, 0, STOP]);
+        assert_eq!(
+            mid.validate_code::<ExtInstr>(),
+            Err(LibValidationError::InvalidJump(InvalidJump::MidInstruction {
+                source: 1,
+                target: 2
+            }))
+        );
+        let to_end = lib(&[], &[NOP, JMP, 5, 0, STOP]);
+        assert_eq!(to_end.validate_code::<ExtInstr>(), Ok(()));
+        let past_end = lib(&[], &[NOP, JMP, 6, 0, STOP]);
+        assert_eq!(
+            past_end.validate_code::<ExtInstr>(),
+            Err(LibValidationError::InvalidJump(InvalidJump::OutOfCode { source: 1 }))
+        );
+
+        // Relative shifts leaving the code segment in both directions
+        let before_start = lib(&[], &[NOP, SH, 0xFE, STOP]);
+        assert_eq!(
+            before_start.validate_code::<ExtInstr>(),
+            Err(LibValidationError::InvalidJump(InvalidJump::OutOfCode { source: 1 }))
+        );
+        let after_end = lib(&[], &[NOP, SH, 0x10, STOP]);
+        assert_eq!(
+            after_end.validate_code::<ExtInstr>(),
+            Err(LibValidationError::InvalidJump(InvalidJump::OutOfCode { source: 1 }))
+        );
+
+        let truncated = lib(&[], &[NOP, JMP, 0]);
+        assert_eq!(truncated.validate_code::<ExtInstr>(), Err(LibValidationError::InvalidCode(1)));
+    }
+
+    #[test]
+    fn invalid_code() {
+        let lib = lib(&["JMPX"], &[NOP, JMP, 0]);
+        let report = lib.isa_consistency_report::<ExtInstr>();
+        assert_eq!(report.invalid_code, Some(1));
+        assert!(!report.is_consistent());
+        assert_eq!(lib.validate::<ExtInstr>(), Err(LibValidationError::InvalidCode(1)));
+    }
+
+    #[test]
+    fn unknown_lib() {
+        const CALL: u8 = CtrlInstr::<LibId>::CALL;
+        const EXEC: u8 = CtrlInstr::<LibId>::EXEC;
+        let ext = LibId::from([0xA5u8; 32]);
+
+        // Reference to an empty libs segment can't be decoded
+        let mut lib = lib(&["JMPX"], &[NOP, CALL, 0, 0, 0, STOP]);
+        assert_eq!(lib.isa_consistency_report::<ExtInstr>().invalid_code, Some(1));
+        assert_eq!(lib.validate::<ExtInstr>(), Err(LibValidationError::InvalidCode(1)));
+        *lib.libs_mut() = tiny_bset![ext];
+        assert_eq!(lib.validate::<ExtInstr>(), Ok(()));
+
+        // Reference past the end of the libs segment
+        *lib.code_mut() = SmallBlob::try_from_slice(&[CALL, 0, 0, 0, EXEC, 1, 0, 0]).unwrap();
+        assert_eq!(lib.validate::<ExtInstr>(), Err(LibValidationError::InvalidCode(4)));
+    }
+
+    #[test]
+    fn data_out_of_range() {
+        use crate::isa::{Alu64Instr, ArithmInstr};
+
+        const PUT: u8 = ArithmInstr::PUT;
+        let mut lib = lib(&["ALU64"], &[PUT, 0x00, 0x01, 0x00, STOP]);
+        *lib.data_mut() = SmallBlob::try_from_slice(&[0xA5; 8]).unwrap();
+        assert_eq!(lib.validate::<Alu64Instr<LibId>>(), Err(LibValidationError::InvalidCode(0)));
+        lib.data_mut().push(0xA5).unwrap();
+        assert_eq!(lib.validate::<Alu64Instr<LibId>>(), Ok(()));
+    }
+
+    #[test]
+    fn checked() {
+        let isae = IsaId::canonical_set([IsaId::from("JMPX")]);
+        let code = SmallBlob::try_from_slice(&[NOP, JMP, 0, 0, STOP]).unwrap();
+        let lib = Lib::checked::<ExtInstr>(isae.clone(), none!(), code.clone(), none!()).unwrap();
+        assert_eq!(lib, self::lib(&["JMPX"], &[NOP, JMP, 0, 0, STOP]));
+
+        let truncated = SmallBlob::try_from_slice(&[NOP, JMP, 0, 0, JMP, 0]).unwrap();
+        assert_eq!(
+            Lib::checked::<ExtInstr>(isae.clone(), none!(), truncated, none!()),
+            Err(LibValidationError::InvalidCode(4))
+        );
+        assert_eq!(
+            Lib::checked::<ExtInstr>(none!(), none!(), code, none!()),
+            Err(LibValidationError::UndeclaredIsa(IsaId::from("JMPX"), 1))
+        );
+    }
+}
@@ -27,11 +27,12 @@
 use strict_types::typelib::{CompileError, LibBuilder};
 use strict_types::TypeLib;
 
-use crate::{CoreConfig, Lib, LibSite, LIB_NAME_ALUVM};
+use crate::isa::{CtrlInstr, Instr, ReservedInstr};
+use crate::{CoreConfig, EntryPoint, Lib, LibId, LibSite, LibSymbols, Site, LIB_NAME_ALUVM};
 
 /// Strict type id for the lib-old providing data types from this crate.
 pub const LIB_ID_ALUVM: &str =
-    "stl:t1kptI_t-R8Ei0Wa-e0m53SK-toGi5AC-si8GK5F-MbQp588#reward-accent-swim";
+    "stl:JyzJswsC-T1ejwYx-FYwqnMP-rIidiU~-A_wzI1c-7toEWrU#courage-tuna-asia";
 
 #[allow(clippy::result_large_err)]
 fn _aluvm_stl() -> Result<TypeLib, CompileError> {
@@ -41,7 +42,13 @@ fn _aluvm_stl() -> Result<TypeLib, CompileError> {
     ])
     .transpile::<LibSite>()
     .transpile::<Lib>()
+    .transpile::<LibSymbols>()
+    .transpile::<EntryPoint>()
     .transpile::<CoreConfig>()
+    .transpile::<Site<LibId>>()
+    .transpile::<ReservedInstr>()
+    .transpile::<CtrlInstr<LibId>>()
+    .transpile::<Instr<LibId>>()
     .compile()
 }
 
@@ -58,4 +65,13 @@ mod test {
         let lib = aluvm_stl();
         assert_eq!(lib.id().to_string(), LIB_ID_ALUVM);
     }
+
+    #[test]
+    fn instr_types() {
+        let lib = aluvm_stl();
+        for name in ["Site", "ReservedInstr", "CtrlInstr", "Instr"] {
+            assert!(lib.types.contains_key(&tn!(name)), "type {name} is absent");
+        }
+        assert_eq!(aluvm_stl().id(), lib.id());
+    }
 }
@@ -0,0 +1,350 @@
+// Reference rust implementation of AluVM (arithmetic logic unit virtual machine).
+// To find more on AluVM please check <https://aluvm.org>
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Designed in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+// Written in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+//
+// Copyright (C) 2021-2024 LNP/BP Standards Association, Switzerland.
+// Copyright (C) 2024-2025 Laboratories for Ubiquitous Deterministic Computing (UBIDECO),
+//                         Institute for Distributed and Cognitive Systems (InDCS), Switzerland.
+// Copyright (C) 2021-2025 Dr Maxim Orlovsky.
+// All rights under the above copyrights are reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use this file except
+// in compliance with the License. You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed under the License
+// is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express
+// or implied. See the License for the specific language governing permissions and limitations under
+// the License.
+
+//! Mocks for the execution contexts of ISA extensions.
+//!
+//! ISA extensions depending on external data (digest providers, oracles, host tables etc.) access
+//! them through the [`crate::isa::Instruction::Context`] type. The intended pattern is to define a
+//! trait for each kind of external data, make the ISA context generic over (or hold a reference
+//! to) an implementation of the trait, and implement the trait for [`MockProvider`] in tests:
+//!
+//! ```
+//! # extern crate alloc;
+//! use aluvm::testing::context::{MockError, MockProvider};
+//!
+//! /// Provider of digests used by an ISA extension.
+//! pub trait DigestProvider {
+//!     fn digest(&self, data: &[u8]) -> Option<[u8; 4]>;
+//! }
+//!
+//! impl DigestProvider for MockProvider<Vec<u8>, [u8; 4]> {
+//!     fn digest(&self, data: &[u8]) -> Option<[u8; 4]> { self.call(data.to_vec()).ok() }
+//! }
+//!
+//! let mut provider = MockProvider::new();
+//! provider
+//!     .expect(b"abc".to_vec())
+//!     .returns([1, 2, 3, 4])
+//!     .times(1);
+//! provider.expect_any().returns([0; 4]);
+//! provider.fail_on_call(3);
+//!
+//! // The provider is used as (a part of) the context, which is passed to `Vm::exec`
+//! assert_eq!(provider.digest(b"abc"), Some([1, 2, 3, 4]));
+//! assert_eq!(provider.digest(b"abc"), Some([0; 4]));
+//! assert_eq!(provider.digest(b"def"), None);
+//!
+//! provider.assert_calls(&[b"abc".to_vec(), b"abc".to_vec(), b"def".to_vec()]);
+//! provider.verify();
+//! ```
+
+use alloc::collections::BTreeSet;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+use core::fmt::Debug;
+
+/// Errors returned by [`MockProvider::call`].
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum MockError<Req: Debug> {
+    /// unexpected call to a mock provider with request {0:?}.
+    Unexpected(Req),
+
+    /// mock provider is configured to fail on request {0:?}.
+    Failure(Req),
+
+    /// failure injected into the call number {0} to a mock provider.
+    Injected(usize),
+}
+
+/// Policy for handling calls to [`MockProvider`] which don't match any of the expectations.
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub enum UnexpectedCallPolicy<Resp> {
+    /// Panic on an unexpected call.
+    #[default]
+    Panic,
+
+    /// Return [`MockError::Unexpected`].
+    Error,
+
+    /// Respond with the provided default response.
+    Respond(Resp),
+}
+
+/// Expected call to [`MockProvider`], created by [`MockProvider::expect`] or
+/// [`MockProvider::expect_any`].
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct Expectation<Req, Resp> {
+    /// Request matched by the expectation; matches any request if `None`.
+    request: Option<Req>,
+    /// Response to a matched request; a failure if `None`.
+    response: Option<Resp>,
+    /// Number of calls the expectation must match; unlimited if `None`.
+    times: Option<usize>,
+}
+
+impl<Req: Eq, Resp> Expectation<Req, Resp> {
+    fn new(request: Option<Req>) -> Self { Self { request, response: None, times: None } }
+
+    /// Sets the response to the matched requests.
+    pub fn returns(&mut self, response: Resp) -> &mut Self {
+        self.response = Some(response);
+        self
+    }
+
+    /// Makes the matched requests fail with [`MockError::Failure`].
+    pub fn fails(&mut self) -> &mut Self {
+        self.response = None;
+        self
+    }
+
+    /// Limits the expectation to match exactly `n` calls.
+    ///
+    /// Once the expectation matched `n` calls, it doesn't match any further calls, and
+    /// [`MockProvider::verify`] panics if it matched less than `n` calls.
+    pub fn times(&mut self, n: usize) -> &mut Self {
+        self.times = Some(n);
+        self
+    }
+
+    fn matches(&self, request: &Req, matched: usize) -> bool {
+        self.times.map_or(true, |times| matched < times)
+            && self.request.as_ref().map_or(true, |req| req == request)
+    }
+}
+
+/// Generic mock of a provider of external data, recording calls and responding to them according
+/// to the configured expectations.
+///
+/// A call is answered by the first expectation (in the order they were added) which matches the
+/// request and is not yet exhausted. Calls not matching any expectation are handled according to
+/// the [`UnexpectedCallPolicy`].
+///
+/// The provider is configured using mutable references, but is called using a shared reference,
+/// such that it can be used as a part of an ISA context, which is passed to the instructions by a
+/// shared reference.
+#[derive(Clone, Debug)]
+pub struct MockProvider<Req: Clone + Eq + Debug, Resp: Clone> {
+    expectations: Vec<Expectation<Req, Resp>>,
+    policy: UnexpectedCallPolicy<Resp>,
+    /// Call numbers (starting from 1) which must fail.
+    failures: BTreeSet<usize>,
+    calls: RefCell<Vec<Req>>,
+    /// Number of calls matched by each of the expectations.
+    matched: RefCell<Vec<usize>>,
+}
+
+impl<Req: Clone + Eq + Debug, Resp: Clone> Default for MockProvider<Req, Resp> {
+    fn default() -> Self { Self::new() }
+}
+
+impl<Req: Clone + Eq + Debug, Resp: Clone> MockProvider<Req, Resp> {
+    /// Constructs a mock provider without expectations, panicking on any call.
+    pub fn new() -> Self { Self::with_policy(UnexpectedCallPolicy::Panic) }
+
+    /// Constructs a mock provider without expectations, handling calls according to the policy.
+    pub fn with_policy(policy: UnexpectedCallPolicy<Resp>) -> Self {
+        Self {
+            expectations: Vec::new(),
+            policy,
+            failures: BTreeSet::new(),
+            calls: RefCell::new(Vec::new()),
+            matched: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Constructs a mock provider expecting the recorded sequence of calls, each of them once.
+    ///
+    /// Since the expectations are matched in order, repeated requests receive the recorded
+    /// responses in the recorded order.
+    pub fn with_recording(calls: impl IntoIterator<Item = (Req, Resp)>) -> Self {
+        let mut mock = Self::new();
+        for (req, resp) in calls {
+            mock.expect(req).returns(resp).times(1);
+        }
+        mock
+    }
+
+    /// Sets the policy for handling unexpected calls.
+    pub fn set_policy(&mut self, policy: UnexpectedCallPolicy<Resp>) { self.policy = policy; }
+
+    /// Adds an expectation for a specific request.
+    pub fn expect(&mut self, request: Req) -> &mut Expectation<Req, Resp> {
+        self.expectations.push(Expectation::new(Some(request)));
+        self.expectations.last_mut().expect("just added")
+    }
+
+    /// Adds an expectation matching any request.
+    pub fn expect_any(&mut self) -> &mut Expectation<Req, Resp> {
+        self.expectations.push(Expectation::new(None));
+        self.expectations.last_mut().expect("just added")
+    }
+
+    /// Makes the call number `n` (starting from 1) fail with [`MockError::Injected`], regardless
+    /// of the expectations.
+    pub fn fail_on_call(&mut self, n: usize) { self.failures.insert(n); }
+
+    /// Calls the provider, recording the request.
+    ///
+    /// # Panics
+    ///
+    /// If the request doesn't match any expectation and the policy is
+    /// [`UnexpectedCallPolicy::Panic`].
+    pub fn call(&self, request: Req) -> Result<Resp, MockError<Req>> {
+        let mut calls = self.calls.borrow_mut();
+        calls.push(request.clone());
+        let no = calls.len();
+        drop(calls);
+
+        if self.failures.contains(&no) {
+            return Err(MockError::Injected(no));
+        }
+
+        let mut matched = self.matched.borrow_mut();
+        matched.resize(self.expectations.len(), 0);
+        let found = self
+            .expectations
+            .iter()
+            .enumerate()
+            .find(|(idx, exp)| exp.matches(&request, matched[*idx]));
+        match (found, &self.policy) {
+            (Some((idx, exp)), _) => {
+                matched[idx] += 1;
+                exp.response.clone().ok_or(MockError::Failure(request))
+            }
+            (None, UnexpectedCallPolicy::Panic) => {
+                panic!("unexpected call number {no} to a mock provider with request {request:?}")
+            }
+            (None, UnexpectedCallPolicy::Error) => Err(MockError::Unexpected(request)),
+            (None, UnexpectedCallPolicy::Respond(resp)) => Ok(resp.clone()),
+        }
+    }
+
+    /// Returns the number of calls made so far.
+    pub fn call_count(&self) -> usize { self.calls.borrow().len() }
+
+    /// Returns all requests received so far, in the order of the calls.
+    pub fn calls(&self) -> Vec<Req> { self.calls.borrow().clone() }
+
+    /// Asserts that the provider received exactly the given requests, in the given order.
+    ///
+    /// # Panics
+    ///
+    /// If the received requests don't match.
+    pub fn assert_calls(&self, requests: &[Req]) {
+        let calls = self.calls.borrow();
+        assert_eq!(
+            calls.as_slice(),
+            requests,
+            "mock provider received unexpected sequence of calls"
+        );
+    }
+
+    /// Verifies that all expectations limited with [`Expectation::times`] were fully matched.
+    ///
+    /// # Panics
+    ///
+    /// If some of the expectations matched fewer calls than required.
+    pub fn verify(&self) {
+        let matched = self.matched.borrow();
+        for (idx, exp) in self.expectations.iter().enumerate() {
+            let count = matched.get(idx).copied().unwrap_or_default();
+            if let Some(times) = exp.times {
+                assert_eq!(
+                    count, times,
+                    "mock expectation for {:?} matched {count} calls instead of {times}",
+                    exp.request
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![cfg_attr(coverage_nightly, coverage(off))]
+
+    use super::*;
+
+    #[test]
+    fn expectations_in_order() {
+        let mut mock = MockProvider::<u8, u16>::new();
+        mock.expect(1).returns(10).times(2);
+        mock.expect(1).returns(11);
+        mock.expect(2).fails();
+        mock.expect_any().returns(0).times(1);
+
+        assert_eq!(mock.call(1), Ok(10));
+        assert_eq!(mock.call(1), Ok(10));
+        assert_eq!(mock.call(1), Ok(11));
+        assert_eq!(mock.call(2), Err(MockError::Failure(2)));
+        assert_eq!(mock.call(3), Ok(0));
+        assert_eq!(mock.call_count(), 5);
+        mock.assert_calls(&[1, 1, 1, 2, 3]);
+        mock.verify();
+    }
+
+    #[test]
+    #[should_panic(expected = "unexpected call number 1 to a mock provider with request 5")]
+    fn unexpected_panic() {
+        let mock = MockProvider::<u8, u16>::new();
+        let _ = mock.call(5);
+    }
+
+    #[test]
+    fn unexpected_policy() {
+        let mut mock = MockProvider::<u8, u16>::with_policy(UnexpectedCallPolicy::Error);
+        assert_eq!(mock.call(5), Err(MockError::Unexpected(5)));
+        mock.set_policy(UnexpectedCallPolicy::Respond(7));
+        assert_eq!(mock.call(5), Ok(7));
+        mock.assert_calls(&[5, 5]);
+    }
+
+    #[test]
+    fn injected_failure() {
+        let mut mock = MockProvider::<u8, u16>::new();
+        mock.expect_any().returns(1);
+        mock.fail_on_call(2);
+        assert_eq!(mock.call(0), Ok(1));
+        assert_eq!(mock.call(0), Err(MockError::Injected(2)));
+        assert_eq!(mock.call(0), Ok(1));
+    }
+
+    #[test]
+    fn recording() {
+        let mock = MockProvider::with_recording([(1u8, 10u16), (2, 20), (1, 11)]);
+        assert_eq!(mock.call(1), Ok(10));
+        assert_eq!(mock.call(1), Ok(11));
+        assert_eq!(mock.call(2), Ok(20));
+        mock.verify();
+    }
+
+    #[test]
+    #[should_panic(expected = "matched 0 calls instead of 1")]
+    fn verify_unmatched() {
+        let mut mock = MockProvider::<u8, u16>::new();
+        mock.expect(1).returns(10).times(1);
+        mock.verify();
+    }
+}
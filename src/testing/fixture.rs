@@ -0,0 +1,166 @@
+// Reference rust implementation of AluVM (arithmetic logic unit virtual machine).
+// To find more on AluVM please check <https://aluvm.org>
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Designed in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+// Written in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+//
+// Copyright (C) 2021-2024 LNP/BP Standards Association, Switzerland.
+// Copyright (C) 2024-2025 Laboratories for Ubiquitous Deterministic Computing (UBIDECO),
+//                         Institute for Distributed and Cognitive Systems (InDCS), Switzerland.
+// Copyright (C) 2021-2025 Dr Maxim Orlovsky.
+// All rights under the above copyrights are reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use this file except
+// in compliance with the License. You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed under the License
+// is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express
+// or implied. See the License for the specific language governing permissions and limitations under
+// the License.
+
+//! Fixtures for constructing cores in arbitrary states.
+
+use alloc::vec::Vec;
+
+use crate::core::{Core, CoreConfig, CoreExt, Site, SiteId, Status};
+
+/// Builder of a [`Core`] in an arbitrary state.
+///
+/// The call stack frames are pushed as if by the call instructions, keeping the related registers
+/// consistent; the rest of the values are written into the registers as they are, after the
+/// frames are pushed. This allows constructing cores violating the invariants checked by
+/// [`Core::check_invariants`], which is useful for testing the code handling such cores.
+///
+/// ```
+/// # extern crate alloc;
+/// use aluvm::testing::fixture::CoreFixture;
+/// use aluvm::{Core, InvariantViolation, LibId, NoExt, Site};
+///
+/// let lib_id = LibId::from([0xA5u8; 32]);
+/// let core: Core<LibId, NoExt> = CoreFixture::new()
+///     .call(Site::new(lib_id, 10), lib_id)
+///     .cs_high_water(0)
+///     .build(());
+/// assert_eq!(
+///     core.check_invariants(),
+///     Err(InvariantViolation::CallStackAboveHighWater { depth: 1, high_water: 0 })
+/// );
+/// ```
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct CoreFixture<Id: SiteId> {
+    pub(crate) config: CoreConfig,
+    pub(crate) frames: Vec<(Site<Id>, Id)>,
+    pub(crate) ck: Option<Status>,
+    pub(crate) cf: Option<u64>,
+    pub(crate) co: Option<Status>,
+    pub(crate) ca: Option<u64>,
+    pub(crate) cw_site: Option<Site<Id>>,
+    pub(crate) cs_high_water: Option<u16>,
+    pub(crate) xcp: Option<u16>,
+    pub(crate) xcs_high_water: Option<u16>,
+    pub(crate) shadow_depth: Option<usize>,
+}
+
+impl<Id: SiteId> Default for CoreFixture<Id> {
+    fn default() -> Self { Self::with(CoreConfig::default()) }
+}
+
+impl<Id: SiteId> CoreFixture<Id> {
+    /// Constructs a fixture of a core with the default configuration.
+    pub fn new() -> Self { Self::default() }
+
+    /// Constructs a fixture of a core with the provided configuration.
+    pub fn with(config: CoreConfig) -> Self {
+        Self {
+            config,
+            frames: Vec::new(),
+            ck: None,
+            cf: None,
+            co: None,
+            ca: None,
+            cw_site: None,
+            cs_high_water: None,
+            xcp: None,
+            xcs_high_water: None,
+            shadow_depth: None,
+        }
+    }
+
+    /// Pushes a call stack frame for a call made from the site `from` into the program `to`.
+    pub fn call(mut self, from: Site<Id>, to: Id) -> Self {
+        self.frames.push((from, to));
+        self
+    }
+
+    /// Sets the value of the `CK` register.
+    pub fn ck(mut self, ck: Status) -> Self {
+        self.ck = Some(ck);
+        self
+    }
+
+    /// Sets the value of the `CF` register.
+    pub fn cf(mut self, cf: u64) -> Self {
+        self.cf = Some(cf);
+        self
+    }
+
+    /// Sets the value of the `CO` register.
+    pub fn co(mut self, co: Status) -> Self {
+        self.co = Some(co);
+        self
+    }
+
+    /// Sets the value of the `CA` register.
+    pub fn ca(mut self, ca: u64) -> Self {
+        self.ca = Some(ca);
+        self
+    }
+
+    /// Sets the site at which the complexity warning limit was crossed.
+    pub fn cw_site(mut self, site: Site<Id>) -> Self {
+        self.cw_site = Some(site);
+        self
+    }
+
+    /// Sets the maximal call stack depth reached during the execution.
+    pub fn cs_high_water(mut self, high_water: u16) -> Self {
+        self.cs_high_water = Some(high_water);
+        self
+    }
+
+    /// Sets the current number of cross-library frames in the call stack.
+    pub fn xcp(mut self, xcp: u16) -> Self {
+        self.xcp = Some(xcp);
+        self
+    }
+
+    /// Sets the maximal number of cross-library frames reached during the execution.
+    pub fn xcs_high_water(mut self, high_water: u16) -> Self {
+        self.xcs_high_water = Some(high_water);
+        self
+    }
+
+    /// Truncates the shadow call stack to the provided depth.
+    ///
+    /// Has no effect if the call stack integrity mode is off.
+    pub fn shadow_depth(mut self, depth: usize) -> Self {
+        self.shadow_depth = Some(depth);
+        self
+    }
+
+    /// Constructs the core.
+    ///
+    /// # Panics
+    ///
+    /// If the frames don't fit the call stack.
+    pub fn build<Cx: CoreExt, const CALL_STACK_SIZE: usize>(
+        self,
+        cx_config: Cx::Config,
+    ) -> Core<Id, Cx, CALL_STACK_SIZE> {
+        Core::from_fixture(self, cx_config)
+    }
+}
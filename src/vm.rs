@@ -24,43 +24,128 @@
 
 //! Alu virtual machine
 
+use alloc::collections::{BTreeMap, BTreeSet};
+use core::any::Any;
+use core::cell::{Cell, RefCell};
 use core::marker::PhantomData;
 
-use crate::core::{Core, CoreConfig, CoreExt, Status};
-use crate::isa::{Instr, Instruction};
-use crate::library::{Jump, Lib, LibId, LibSite};
+use crate::core::{
+    Core, CoreConfig, CoreExt, FailureReason, JumpFault, Register, Site, Status, TerminationCause,
+    CALL_STACK_SIZE_MAX, CALL_STACK_SIZE_SMALL,
+};
+use crate::isa::{ExecStep, Instr, Instruction};
+#[cfg(feature = "paranoid")]
+use crate::library::BoundaryIndex;
+use crate::library::{
+    EntryPoint, EntryPointError, ExecCode, ExecHook, HookAction, Jump, Lib, LibId, LibRepo,
+    LibSite, NoHook, PrecompiledLib,
+};
 
 /// Alu virtual machine providing single-core execution environment
 #[derive(Clone, Debug, Default)]
-pub struct Vm<Isa = Instr<LibId>>
+pub struct Vm<Isa = Instr<LibId>, const CALL_STACK_SIZE: usize = { CALL_STACK_SIZE_MAX as usize }>
 where Isa: Instruction<LibId>
 {
     /// A set of registers
-    pub core: Core<LibId, Isa::Core>,
+    pub core: Core<LibId, Isa::Core, CALL_STACK_SIZE>,
 
+    breakpoints: BTreeSet<Site<LibId>>,
+    paused: Option<LibSite>,
+    last_error: Option<ExecError>,
+    /// Instruction boundary indexes of the libraries executed with [`Self::step`], which are
+    /// checked on each control transfer by the `paranoid` runtime.
+    #[cfg(feature = "paranoid")]
+    boundaries: BTreeMap<LibId, BoundaryIndex>,
     phantom: PhantomData<Isa>,
 }
 
+/// Virtual machine with a call stack of [`CALL_STACK_SIZE_SMALL`] frames, for memory-constrained
+/// environments.
+pub type SmallStackVm<Isa = Instr<LibId>> = Vm<Isa, { CALL_STACK_SIZE_SMALL as usize }>;
+
 /// Runtime for program execution.
-impl<Isa> Vm<Isa>
+impl<Isa, const CALL_STACK_SIZE: usize> Vm<Isa, CALL_STACK_SIZE>
 where Isa: Instruction<LibId>
 {
+    /// Capacity of the call stack of the virtual machine core, i.e. the maximal depth of nested
+    /// calls (see [`Core::CALL_STACK_SIZE`]).
+    pub const CALL_STACK_SIZE: usize = CALL_STACK_SIZE;
+
     /// Constructs new virtual machine instance with default core configuration.
-    pub fn new() -> Self { Self { core: Core::new(), phantom: Default::default() } }
+    pub fn new() -> Self {
+        Self {
+            core: Core::new(),
+            breakpoints: BTreeSet::new(),
+            paused: None,
+            last_error: None,
+            #[cfg(feature = "paranoid")]
+            boundaries: BTreeMap::new(),
+            phantom: Default::default(),
+        }
+    }
 
     /// Constructs new virtual machine instance with default core configuration.
     pub fn with(config: CoreConfig, cx_config: <Isa::Core as CoreExt>::Config) -> Self {
         Self {
             core: Core::with(config, cx_config),
+            breakpoints: BTreeSet::new(),
+            paused: None,
+            last_error: None,
+            #[cfg(feature = "paranoid")]
+            boundaries: BTreeMap::new(),
             phantom: Default::default(),
         }
     }
 
     /// Resets all registers of the VM except those which were set up with the config object.
-    pub fn reset(&mut self) { self.core.reset(); }
+    ///
+    /// Discards the execution paused at a breakpoint, if any, and the last execution error, but
+    /// keeps the breakpoints.
+    pub fn reset(&mut self) {
+        self.core.reset();
+        self.paused = None;
+        self.last_error = None;
+    }
+
+    /// Registers a breakpoint at the `site`.
+    ///
+    /// Returns `false` if the breakpoint was already registered.
+    pub fn add_breakpoint(&mut self, site: Site<LibId>) -> bool { self.breakpoints.insert(site) }
+
+    /// Removes a breakpoint at the `site`.
+    ///
+    /// Returns `false` if there was no breakpoint at the site.
+    pub fn remove_breakpoint(&mut self, site: Site<LibId>) -> bool {
+        self.breakpoints.remove(&site)
+    }
+
+    /// Removes all registered breakpoints.
+    pub fn clear_breakpoints(&mut self) { self.breakpoints.clear() }
+
+    /// Returns the registered breakpoints in the order of their sites.
+    pub fn breakpoints(&self) -> impl Iterator<Item = Site<LibId>> + '_ {
+        self.breakpoints.iter().copied()
+    }
+
+    /// Returns the site at which the program execution is paused, if any.
+    ///
+    /// The instruction at the site is not executed yet.
+    pub fn paused_at(&self) -> Option<Site<LibId>> {
+        self.paused.map(|site| Site::new(site.lib_id, site.offset))
+    }
 
     /// Executes the program starting from the provided entry point.
     ///
+    /// The registered breakpoints are ignored; use [`Self::exec_until`] to stop at them.
+    ///
+    /// A library requiring ISA extensions not supported by the instruction set is not executed:
+    /// instead, `CK` is set to a failure, and the program halts (see [`Lib::check_isae`]). The
+    /// same happens when the library resolver fails to provide a library, the execution is
+    /// transferred outside the library code, or the instruction limit is reached, independently of
+    /// the `CH` register value. The reason
+    /// of such a halt is reported by [`Self::last_error`]; use [`Self::exec_full`] to learn the
+    /// reason of any program halt.
+    ///
     /// # Returns
     ///
     /// Value of the `CK` register at the end of the program execution.
@@ -70,13 +155,571 @@ where Isa: Instruction<LibId>
         context: &Isa::Context<'_>,
         lib_resolver: impl Fn(LibId) -> Option<L>,
     ) -> Status {
+        match self.run(entry_point, false, context, lib_resolver, RunMode::Exec, &mut NoHook) {
+            Halt::Complete(status) => status,
+            _ => unreachable!("execution is never suspended or paused unless requested"),
+        }
+    }
+
+    /// Executes the program starting from the provided entry point, like [`Self::exec`], invoking
+    /// the instrumentation hook before and after each executed instruction (see [`ExecHook`]).
+    ///
+    /// If the hook requests the execution termination, `CK` is set to a failure, and
+    /// [`ExecError::HookAbort`] is reported by [`Self::last_error`].
+    ///
+    /// # Returns
+    ///
+    /// Value of the `CK` register at the end of the program execution.
+    pub fn exec_with_hook<L: AsRef<Lib>>(
+        &mut self,
+        entry_point: LibSite,
+        context: &Isa::Context<'_>,
+        lib_resolver: impl Fn(LibId) -> Option<L>,
+        hook: &mut impl ExecHook<LibId>,
+    ) -> Status {
+        match self.run(entry_point, false, context, lib_resolver, RunMode::Exec, hook) {
+            Halt::Complete(status) => status,
+            _ => unreachable!("execution is never suspended or paused unless requested"),
+        }
+    }
+
+    /// Executes the program starting from the provided entry point, like [`Self::exec`], taking
+    /// the libraries precompiled with [`Lib::precompile`].
+    ///
+    /// The instructions are not decoded from the bytecode during the execution, which has the
+    /// same semantics as [`Self::exec`] except that a control transfer to an offset which is not
+    /// an instruction boundary always fails `CK` and halts the program (see [`PrecompiledLib`]).
+    ///
+    /// # Returns
+    ///
+    /// Value of the `CK` register at the end of the program execution.
+    pub fn exec_compiled<L: AsRef<PrecompiledLib<Isa>>>(
+        &mut self,
+        entry_point: LibSite,
+        context: &Isa::Context<'_>,
+        lib_resolver: impl Fn(LibId) -> Option<L>,
+    ) -> Status {
+        match self.run(entry_point, false, context, lib_resolver, RunMode::Exec, &mut NoHook) {
+            Halt::Complete(status) => status,
+            _ => unreachable!("execution is never suspended or paused unless requested"),
+        }
+    }
+
+    /// Executes the program starting from the provided entry point, like [`Self::exec`],
+    /// collecting the resource usage statistics (see [`ExecStats`]).
+    ///
+    /// # Returns
+    ///
+    /// Value of the `CK` register at the end of the program execution and the collected
+    /// statistics.
+    pub fn exec_with_stats<L: AsRef<Lib>>(
+        &mut self,
+        entry_point: LibSite,
+        context: &Isa::Context<'_>,
+        lib_resolver: impl Fn(LibId) -> Option<L>,
+    ) -> (Status, ExecStats) {
+        let (ci, cy) = (self.core.ci(), self.core.cy());
+        // The high water mark is tracked from the starting depth, and restored afterwards
+        let high_water = self.core.replace_cs_high_water(self.core.cp());
+        let mut hook = StatsHook { stats: ExecStats::default(), ca: self.core.ca() };
+        let status = self.exec_with_hook(entry_point, context, lib_resolver, &mut hook);
+        let mut stats = hook.stats;
+        stats.peak_depth = self.core.cs_high_water();
+        self.core
+            .replace_cs_high_water(stats.peak_depth.max(high_water));
+        stats.instructions = self.core.ci().saturating_sub(ci);
+        stats.jumps = self.core.cy().saturating_sub(cy);
+        stats.cy = self.core.cy();
+        stats.ca = self.core.ca();
+        (status, stats)
+    }
+
+    /// Executes the program starting from the provided entry point, like [`Self::exec`], reporting
+    /// the reason of the program halt and the resources used by the execution (see
+    /// [`ExecReport`]).
+    pub fn exec_full<L: AsRef<Lib>>(
+        &mut self,
+        entry_point: LibSite,
+        context: &Isa::Context<'_>,
+        lib_resolver: impl Fn(LibId) -> Option<L>,
+    ) -> ExecReport {
+        let (ci, ca) = (self.core.ci(), self.core.ca());
+        let status = self.exec(entry_point, context, lib_resolver);
+        ExecReport {
+            status,
+            halt_reason: self.halt_reason(),
+            steps: self.core.ci().saturating_sub(ci),
+            complexity_used: self.core.ca().saturating_sub(ca),
+        }
+    }
+
+    /// Returns the reason of the last program execution halt, combining the termination cause
+    /// (see [`Self::termination`]) with the details of the `CK` failure and of the virtual machine
+    /// error which have caused it.
+    ///
+    /// Returns `None` while the execution is suspended or paused at a breakpoint.
+    pub fn halt_reason(&self) -> Option<HaltReason> {
+        Some(match self.termination()? {
+            TerminationCause::ExplicitStop | TerminationCause::ReturnFromMain => {
+                HaltReason::OrderlyStop
+            }
+            TerminationCause::CheckFailed { site } => HaltReason::FailHalt(site),
+            TerminationCause::Halted { site } => match self.last_failure() {
+                Some(FailureReason::JumpLimit) => HaltReason::JumpLimit(site),
+                Some(FailureReason::CallStackOverflow) => HaltReason::CallStackOverflow(site),
+                _ => HaltReason::FailHalt(site),
+            },
+            TerminationCause::ComplexityExceeded => HaltReason::ComplexityLimit,
+            TerminationCause::EndOfCode => HaltReason::EndOfCode,
+            TerminationCause::Aborted => match self.last_error {
+                Some(ExecError::LibAbsent(lib_id, _)) => HaltReason::UnresolvedLib(lib_id),
+                Some(err) => HaltReason::Aborted(err),
+                None => HaltReason::InvalidJump(self.core.jump_fault()?),
+            },
+        })
+    }
+
+    /// Executes the program starting from the provided entry point, like [`Self::exec`], taking
+    /// the context as a type-erased reference.
+    ///
+    /// Allows hosts supporting multiple instruction sets with different [`Instruction::Context`]
+    /// types to execute all of them through the same code path, for instance keeping virtual
+    /// machines for the instruction sets registered at runtime behind trait objects.
+    ///
+    /// If the context is not of the type expected by the instruction set, the program is not
+    /// executed: instead, `CK` is set to a failure, and [`ExecError::ContextMismatch`] is reported
+    /// by [`Self::last_error`].
+    ///
+    /// # Returns
+    ///
+    /// Value of the `CK` register at the end of the program execution.
+    pub fn exec_dyn<L: AsRef<Lib>>(
+        &mut self,
+        entry_point: LibSite,
+        context: &dyn Any,
+        lib_resolver: impl Fn(LibId) -> Option<L>,
+    ) -> Status
+    where
+        Isa::Context<'static>: Any,
+    {
+        match context.downcast_ref::<Isa::Context<'static>>() {
+            Some(context) => self.exec(entry_point, context, lib_resolver),
+            None => {
+                #[cfg(feature = "log")]
+                eprintln!(">; execution halted: context type mismatch");
+                let _ = self.core.fail_ck_with(FailureReason::Vm);
+                self.last_error = Some(ExecError::ContextMismatch(entry_point));
+                self.core.reset_termination();
+                self.core.terminate_with(TerminationCause::Aborted);
+                self.core.ck()
+            }
+        }
+    }
+
+    /// Runs a closure over a copy of the virtual machine, leaving its registers, breakpoints and
+    /// paused execution intact.
+    ///
+    /// Allows a host to probe the execution of a routine in the middle of an ongoing program
+    /// execution, for instance one paused at a breakpoint (see [`Self::exec_until`]).
+    pub fn run_isolated<T>(&self, f: impl FnOnce(&mut Self) -> T) -> T { f(&mut self.clone()) }
+
+    /// Executes the program starting from the provided entry point, like [`Self::exec`], reporting
+    /// the reason of the program halt which is not caused by the program itself.
+    ///
+    /// # Returns
+    ///
+    /// Value of the `CK` register at the end of the program execution.
+    ///
+    /// # Errors
+    ///
+    /// If the program execution has halted since a library can't be resolved or executed, the
+    /// execution was transferred outside the library code, or the instruction limit was reached
+    /// (see [`crate::CoreConfig::instr_lim`]). In all cases `CK` is set to a failure.
+    pub fn exec_checked<L: AsRef<Lib>>(
+        &mut self,
+        entry_point: LibSite,
+        context: &Isa::Context<'_>,
+        lib_resolver: impl Fn(LibId) -> Option<L>,
+    ) -> Result<Status, ExecError> {
+        let status = self.exec(entry_point, context, lib_resolver);
+        match self.last_error {
+            Some(err) => Err(err),
+            None => Ok(status),
+        }
+    }
+
+    /// Executes the program starting from the provided entry point, like [`Self::exec`], refusing
+    /// to execute libraries which don't pass validation against the instruction set (see
+    /// [`Lib::validate`]).
+    ///
+    /// Each library is validated once per execution, when it is first provided by the library
+    /// resolver. The execution halts before entering an invalid library: `CK` is set to a failure,
+    /// and [`ExecError::InvalidLib`] is reported by [`Self::last_error`].
+    ///
+    /// # Returns
+    ///
+    /// Value of the `CK` register at the end of the program execution.
+    pub fn exec_validated<L: AsRef<Lib>>(
+        &mut self,
+        entry_point: LibSite,
+        context: &Isa::Context<'_>,
+        lib_resolver: impl Fn(LibId) -> Option<L>,
+    ) -> Status {
+        let validated = RefCell::new(BTreeMap::new());
+        let invalid = Cell::new(None);
+        let status = self.exec(entry_point, context, |lib_id| {
+            let lib = lib_resolver(lib_id)?;
+            let valid = *validated
+                .borrow_mut()
+                .entry(lib_id)
+                .or_insert_with(|| lib.as_ref().validate::<Isa>().is_ok());
+            if !valid {
+                invalid.set(Some(lib_id));
+                return None;
+            }
+            Some(lib)
+        });
+        // Invalid libraries are withheld from the execution, which reports them as absent
+        match self.last_error {
+            Some(ExecError::LibAbsent(lib_id, site)) if invalid.get() == Some(lib_id) => {
+                self.last_error = Some(ExecError::InvalidLib(site));
+            }
+            _ => {}
+        }
+        status
+    }
+
+    /// Executes the program starting from the provided entry point, like [`Self::exec`], taking
+    /// the libraries from a repository.
+    ///
+    /// Missing libraries are handled in the same way as by [`Self::exec`]; use
+    /// [`LibRepo::check_dependencies`] to check that the repository provides all of them before
+    /// the execution.
+    ///
+    /// # Returns
+    ///
+    /// Value of the `CK` register at the end of the program execution.
+    pub fn exec_repo(
+        &mut self,
+        entry_point: LibSite,
+        context: &Isa::Context<'_>,
+        repo: &LibRepo,
+    ) -> Status {
+        self.exec(entry_point, context, repo.resolver())
+    }
+
+    /// Calls the routine described by the entry point, passing it the arguments and returning its
+    /// results according to the routine calling convention (see [`EntryPoint`]).
+    ///
+    /// The virtual machine is reset before the call (see [`Self::reset`]), such that the routine
+    /// never observes the registers left by the previous calls. Then the arguments are written to
+    /// the input registers of the entry point, and the program is executed from its site with
+    /// [`Self::exec_checked`].
+    ///
+    /// # Returns
+    ///
+    /// Values of the output registers of the entry point, in their order.
+    ///
+    /// # Errors
+    ///
+    /// If the entry point registers are not known to the instruction set, if the number of the
+    /// arguments doesn't match the number of the entry point inputs, or if the execution has
+    /// halted with a failed `CK` register.
+    #[allow(clippy::type_complexity)]
+    pub fn call_entry<L: AsRef<Lib>>(
+        &mut self,
+        entry: &EntryPoint,
+        args: &[<<Isa::Core as CoreExt>::Reg as Register>::Value],
+        context: &Isa::Context<'_>,
+        lib_resolver: impl Fn(LibId) -> Option<L>,
+    ) -> Result<Vec<Option<<<Isa::Core as CoreExt>::Reg as Register>::Value>>, CallError> {
+        let inputs = entry.inputs::<<Isa::Core as CoreExt>::Reg>()?;
+        let outputs = entry.outputs::<<Isa::Core as CoreExt>::Reg>()?;
+        if args.len() != inputs.len() {
+            return Err(CallError::ArgCount { expected: inputs.len(), provided: args.len() });
+        }
+        self.reset();
+        for (reg, val) in inputs.into_iter().zip(args) {
+            self.core.set(reg, *val);
+        }
+        match self.exec_checked(entry.site(), context, lib_resolver)? {
+            Status::Ok => Ok(outputs.into_iter().map(|reg| self.core.get(reg)).collect()),
+            Status::Fail => {
+                Err(CallError::Failed(self.termination().unwrap_or(TerminationCause::Aborted)))
+            }
+        }
+    }
+
+    /// Returns the error which has halted the last program execution (see
+    /// [`Self::exec_checked`]), if any.
+    ///
+    /// The error is kept until the next program execution or its resumption is started, allowing
+    /// the hosts to log the libraries which they have failed to provide.
+    pub fn last_error(&self) -> Option<ExecError> { self.last_error }
+
+    /// Returns the reason of the last `CK` failure, if any (see [`Core::last_failure`]).
+    ///
+    /// Unlike [`Self::last_error`], the reason is kept until the core is reset.
+    pub fn last_failure(&self) -> Option<&FailureReason> { self.core.last_failure() }
+
+    /// Returns the cause of the last program execution termination (see [`Core::termination`]).
+    ///
+    /// Allows telling a program which has run to completion from a program which has halted on a
+    /// failure, independently of the final `CK` value. The cause is `None` while the execution is
+    /// suspended or paused at a breakpoint.
+    pub fn termination(&self) -> Option<TerminationCause<LibId>> { self.core.termination() }
+
+    /// Executes the program starting from the provided entry point, pausing the execution before
+    /// an instruction at one of the registered breakpoints (see [`Self::add_breakpoint`]).
+    ///
+    /// The paused execution keeps all the registers and can be continued with
+    /// [`Self::resume_from_breakpoint`]. The complexity limit applies to the whole program
+    /// execution, including all of its resumptions.
+    pub fn exec_until<L: AsRef<Lib>>(
+        &mut self,
+        entry_point: LibSite,
+        context: &Isa::Context<'_>,
+        lib_resolver: impl Fn(LibId) -> Option<L>,
+    ) -> VmRun {
+        self.paused = None;
+        self.run_until(entry_point, false, context, lib_resolver)
+    }
+
+    /// Continues the program execution paused at a breakpoint by [`Self::exec_until`] or a
+    /// previous call to this method, starting with the instruction at the breakpoint.
+    ///
+    /// If the execution is not paused, does nothing and returns the value of the `CK` register.
+    pub fn resume_from_breakpoint<L: AsRef<Lib>>(
+        &mut self,
+        context: &Isa::Context<'_>,
+        lib_resolver: impl Fn(LibId) -> Option<L>,
+    ) -> VmRun {
+        match self.paused.take() {
+            Some(site) => self.run_until(site, true, context, lib_resolver),
+            None => VmRun::Completed(self.core.ck()),
+        }
+    }
+
+    fn run_until<L: AsRef<Lib>>(
+        &mut self,
+        entry_point: LibSite,
+        resumed: bool,
+        context: &Isa::Context<'_>,
+        lib_resolver: impl Fn(LibId) -> Option<L>,
+    ) -> VmRun {
+        let mode = RunMode::Breakpoints { resumed };
+        match self.run(entry_point, false, context, lib_resolver, mode, &mut NoHook) {
+            Halt::Complete(status) => VmRun::Completed(status),
+            Halt::Breakpoint(site) => {
+                self.paused = Some(site.into());
+                VmRun::Breakpoint(site)
+            }
+            Halt::Suspended(..) => unreachable!("execution is never suspended unless requested"),
+        }
+    }
+
+    /// Executes the program starting from the provided entry point, suspending the execution if
+    /// the library resolver fails to provide a library.
+    ///
+    /// Unlike [`Self::exec`], a library which can't be resolved doesn't fail `CK`; instead, the
+    /// execution is aborted before the first instruction of the library, leaving all registers
+    /// intact. The execution may then be continued with [`Self::resume_after_host_abort`].
+    pub fn exec_suspendable<L: AsRef<Lib>>(
+        mut self,
+        entry_point: LibSite,
+        context: &Isa::Context<'_>,
+        lib_resolver: impl Fn(LibId) -> Option<L>,
+    ) -> ExecSuspension<Isa, CALL_STACK_SIZE> {
+        let mode = RunMode::Suspendable;
+        match self.run(entry_point, false, context, lib_resolver, mode, &mut NoHook) {
+            Halt::Complete(status) => ExecSuspension::Complete { vm: self, status },
+            Halt::Suspended(site, skip) => {
+                ExecSuspension::Suspended(SuspendedVm { vm: self, site, skip })
+            }
+            Halt::Breakpoint(_) => unreachable!("breakpoints are checked only when requested"),
+        }
+    }
+
+    /// Continues execution of a program suspended by [`Self::exec_suspendable`], re-executing the
+    /// aborted instruction.
+    ///
+    /// All registers, including `CY`, `CA` and `CF` counters, are inherited from the suspended
+    /// execution, such that the complexity and jump limits apply to the whole program execution
+    /// across all retries.
+    pub fn resume_after_host_abort<L: AsRef<Lib>>(
+        state: SuspendedVm<Isa, CALL_STACK_SIZE>,
+        context: &Isa::Context<'_>,
+        lib_resolver: impl Fn(LibId) -> Option<L>,
+    ) -> ExecSuspension<Isa, CALL_STACK_SIZE> {
+        let SuspendedVm { mut vm, site, skip } = state;
+        match vm.run(site, skip, context, lib_resolver, RunMode::Suspendable, &mut NoHook) {
+            Halt::Complete(status) => ExecSuspension::Complete { vm, status },
+            Halt::Suspended(site, skip) => {
+                ExecSuspension::Suspended(SuspendedVm { vm, site, skip })
+            }
+            Halt::Breakpoint(_) => unreachable!("breakpoints are checked only when requested"),
+        }
+    }
+
+    /// Executes a single instruction located at the `site`, for instance when single-stepping a
+    /// program in a debugger.
+    ///
+    /// The instruction complexity is accounted, and the returned [`ExecStep::Fail`] is applied to
+    /// the `CK` register, in the same way as in [`Self::exec`]. The program counter is not
+    /// advanced: the caller is responsible for choosing the next site to execute, usually with
+    /// [`Self::next_site`]. Stepping through a program in such a way leaves the core in the same
+    /// state as [`Self::exec`] does.
+    ///
+    /// If the complexity budget of a call stack frame is exhausted (see [`Core::push_budget`]),
+    /// the instruction is not executed; instead, the frame is unwound, and [`ExecStep::Ret`] to the
+    /// calling site is returned.
+    ///
+    /// Jumps, calls and returns are counted in the `CY` register; once the count reaches the
+    /// limit, `CK` is set to a failure, and if `CH` is set, [`StepError::JumpOverflow`] is
+    /// returned.
+    ///
+    /// # Errors
+    ///
+    /// If the instruction can't be executed, or its execution must halt the program. In all cases
+    /// except [`StepError::Decode`], `CK` is set to a failure. Errors aborting the program
+    /// execution are reported by [`Self::last_error`], as in [`Self::exec`].
+    pub fn step<L: AsRef<Lib>>(
+        &mut self,
+        site: LibSite,
+        context: &Isa::Context<'_>,
+        lib_resolver: impl Fn(LibId) -> Option<L>,
+    ) -> Result<ExecStep<Site<LibId>>, StepError> {
+        let Some(lib) = lib_resolver(site.lib_id) else {
+            let _ = self.core.fail_ck_with(FailureReason::Vm);
+            self.core.terminate_with(TerminationCause::Aborted);
+            self.last_error = Some(ExecError::LibAbsent(site.lib_id, site));
+            return Err(StepError::NoLib(site.lib_id));
+        };
+        let lib = lib.as_ref();
+        #[cfg(feature = "paranoid")]
+        let mut is_boundary = boundary_check::<Isa>(&mut self.boundaries, lib);
+        #[cfg(not(feature = "paranoid"))]
+        let mut is_boundary = |_: u16| true;
+        #[cfg(any(feature = "paranoid", debug_assertions))]
+        let held = self.core.check_invariants().is_ok();
+        let step = lib.exec_step::<Isa, CALL_STACK_SIZE>(
+            site.offset,
+            &mut self.core,
+            context,
+            &mut is_boundary,
+        );
+        #[cfg(any(feature = "paranoid", debug_assertions))]
+        self.core.assert_invariants(held);
+        step.inspect_err(|err| {
+            self.last_error = match *err {
+                StepError::OutOfCode(site) => Some(ExecError::CodeOverrun(site)),
+                StepError::UnsupportedIsa(site) => Some(ExecError::UnsupportedIsa(site)),
+                StepError::InstrLimit(site) => Some(ExecError::InstrLimit(site)),
+                _ => self.last_error,
+            }
+        })
+    }
+
+    /// Resolves the site of the instruction which is executed after the instruction at the `site`
+    /// has produced the `step`, as it is done by [`Self::exec`].
+    ///
+    /// Moving to the next instruction and returning from a call (which resumes the execution
+    /// after the calling instruction) require decoding the instruction at the `site` or at the
+    /// call site, and thus the library resolver.
+    ///
+    /// # Returns
+    ///
+    /// `None` if the program execution halts. Besides [`ExecStep::Stop`] and a failure with `CH`
+    /// set, this happens when the execution moves past the end of the library code, either by
+    /// proceeding from the last instruction or by jumping right past it, or when the instruction
+    /// to skip can't be decoded; the program is then terminated with
+    /// [`TerminationCause::EndOfCode`], as in [`Self::exec`]. The execution is also aborted, as in
+    /// [`Self::exec`], if the library to return into can't be resolved, or the jump target is
+    /// outside of the library code.
+    pub fn next_site<L: AsRef<Lib>>(
+        &mut self,
+        site: LibSite,
+        step: ExecStep<Site<LibId>>,
+        lib_resolver: impl Fn(LibId) -> Option<L>,
+    ) -> Option<LibSite> {
+        let (site, step) = match step {
+            ExecStep::Call(site) => return Some(site.into()),
+            // Returning resumes the execution after the calling instruction
+            ExecStep::Ret(site) => (site.into(), ExecStep::Next),
+            step => (site, step),
+        };
+        let Some(lib) = lib_resolver(site.lib_id) else {
+            let _ = self.core.fail_ck_with(FailureReason::Vm);
+            self.core.terminate_with(TerminationCause::Aborted);
+            self.last_error = Some(ExecError::LibAbsent(site.lib_id, site));
+            return None;
+        };
+        let lib = lib.as_ref();
+        #[cfg(feature = "paranoid")]
+        let mut is_boundary = boundary_check::<Isa>(&mut self.boundaries, lib);
+        #[cfg(not(feature = "paranoid"))]
+        let mut is_boundary = |_: u16| true;
+        match lib.next_pos::<Isa, CALL_STACK_SIZE>(
+            site.offset,
+            step,
+            &mut self.core,
+            &mut is_boundary,
+        ) {
+            Ok(pos) => Some(LibSite::new(site.lib_id, pos)),
+            Err(Jump::OutOfCode(site)) => {
+                self.core.terminate_with(TerminationCause::Aborted);
+                self.last_error = Some(ExecError::CodeOverrun(site.into()));
+                None
+            }
+            Err(_) => None,
+        }
+    }
+
+    fn run<L: AsRef<C>, C: ExecCode<Isa> + ?Sized>(
+        &mut self,
+        entry_point: LibSite,
+        skip: bool,
+        context: &Isa::Context<'_>,
+        lib_resolver: impl Fn(LibId) -> Option<L>,
+        mode: RunMode,
+        hook: &mut impl ExecHook<LibId>,
+    ) -> Halt {
         let mut site = entry_point;
-        let mut skip = false;
+        let mut skip = skip;
+        self.last_error = None;
+        self.core.reset_termination();
+        // The instruction at which the execution was paused must not pause it again
+        let ignore_break = Cell::new(matches!(mode, RunMode::Breakpoints { resumed: true }));
+        let breakpoints = &self.breakpoints;
         loop {
             if let Some(lib) = lib_resolver(site.lib_id) {
-                let jump = lib
-                    .as_ref()
-                    .exec::<Isa>(site.offset, skip, &mut self.core, context);
+                let lib_id = site.lib_id;
+                let is_break = |offset: u16| {
+                    matches!(mode, RunMode::Breakpoints { .. })
+                        && !ignore_break.replace(false)
+                        && breakpoints.contains(&Site::new(lib_id, offset))
+                };
+                #[cfg(any(feature = "paranoid", debug_assertions))]
+                let held = self.core.check_invariants().is_ok();
+                let jump = match lib.as_ref().exec_code::<CALL_STACK_SIZE>(
+                    site.offset,
+                    skip,
+                    &mut self.core,
+                    context,
+                    is_break,
+                    hook,
+                ) {
+                    Ok(jump) => jump,
+                    Err(_err) => {
+                        #[cfg(feature = "log")]
+                        eprintln!(">; execution halted: {_err}");
+                        let _ = self.core.fail_ck_with(FailureReason::Vm);
+                        self.last_error = Some(ExecError::UnsupportedIsa(site));
+                        break;
+                    }
+                };
+                #[cfg(any(feature = "paranoid", debug_assertions))]
+                self.core.assert_invariants(held);
                 match jump {
                     Jump::Halt => {
                         #[cfg(feature = "log")]
@@ -102,21 +745,392 @@ where Isa: Instruction<LibId>
                         skip = true;
                         site = new_site.into();
                     }
+                    Jump::Break(site) => return Halt::Breakpoint(site),
+                    Jump::OutOfCode(site) => {
+                        #[cfg(feature = "log")]
+                        eprintln!(
+                            ">; execution halted: site {site} is outside of the library code"
+                        );
+                        self.last_error = Some(ExecError::CodeOverrun(site.into()));
+                        break;
+                    }
+                    Jump::InstrLimit(site) => {
+                        self.last_error = Some(ExecError::InstrLimit(site.into()));
+                        break;
+                    }
+                    Jump::Hook(site) => {
+                        #[cfg(feature = "log")]
+                        eprintln!(">; execution terminated by the hook at site {site}");
+                        let _ = self.core.fail_ck_with(FailureReason::Vm);
+                        self.last_error = Some(ExecError::HookAbort(site.into()));
+                        break;
+                    }
                 }
+            } else if mode == RunMode::Suspendable {
+                #[cfg(feature = "log")]
+                eprintln!(">; execution suspended: library {} is not available", site.lib_id);
+                return Halt::Suspended(site, skip);
             } else {
-                let fail = self.core.fail_ck();
-                // We stop execution if the failure flag is set
-                if fail {
-                    break;
-                } else if let Some(pos) = site.offset.checked_add(1) {
-                    // Otherwise we just proceed
-                    site.offset = pos;
-                } else {
-                    // or we still stop if we reached the end of the code
-                    break;
-                }
+                #[cfg(feature = "log")]
+                eprintln!(">; execution halted: library {} is not available", site.lib_id);
+                // There is no code to proceed with, thus we stop even if `CH` is not set
+                let _ = self.core.fail_ck_with(FailureReason::Vm);
+                self.last_error = Some(ExecError::LibAbsent(site.lib_id, site));
+                break;
             };
         }
-        self.core.ck()
+        if self.last_error.is_some() {
+            self.core.terminate_with(TerminationCause::Aborted);
+        }
+        Halt::Complete(self.core.ck())
     }
 }
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+enum RunMode {
+    Exec,
+    Suspendable,
+    Breakpoints { resumed: bool },
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+enum Halt {
+    Complete(Status),
+    Suspended(LibSite, bool),
+    Breakpoint(Site<LibId>),
+}
+
+/// Result of a program execution which may be paused at a breakpoint.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum VmRun {
+    /// Program execution has completed.
+    ///
+    /// Contains the value of the `CK` register at the end of the program execution.
+    Completed(Status),
+
+    /// Program execution has been paused before executing the instruction at a breakpoint site.
+    Breakpoint(Site<LibId>),
+}
+
+/// Resource usage statistics of a program execution, collected by [`Vm::exec_with_stats`].
+///
+/// An instruction halting the program due to the complexity or jump limit is not accounted in
+/// [`Self::peak_depth`] and [`Self::max_complexity`].
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(rename_all = "camelCase"))]
+pub struct ExecStats {
+    /// Maximal depth of the call stack reached during the execution.
+    pub peak_depth: u16,
+    /// Complexity of the most expensive executed instruction.
+    pub max_complexity: u64,
+    /// Site of the first executed instruction with the [`Self::max_complexity`] complexity.
+    pub max_complexity_site: Option<Site<LibId>>,
+    /// Number of executed instructions.
+    pub instructions: u64,
+    /// Number of performed jumps, calls and returns.
+    ///
+    /// The jumps are counted by the `CY` register, thus the number is bounded by the jump limit
+    /// (see [`CoreConfig::jump_lim`]).
+    pub jumps: u16,
+    /// Value of the `CY` register at the end of the execution.
+    pub cy: u16,
+    /// Value of the `CA` register at the end of the execution.
+    pub ca: u64,
+}
+
+/// Reason of a program execution halt, reported by [`Vm::halt_reason`].
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Display)]
+#[display(doc_comments)]
+pub enum HaltReason {
+    /// program has explicitly stopped its execution or returned from its main routine.
+    OrderlyStop,
+
+    /// program has halted at {0} on a `CK` failure.
+    FailHalt(Site<LibId>),
+
+    /// program has exceeded its complexity limit or the complexity budget of a call stack frame.
+    ComplexityLimit,
+
+    /// program has halted at {0} on exceeding the jump limit.
+    JumpLimit(Site<LibId>),
+
+    /// program has halted at {0} on a call stack overflow.
+    CallStackOverflow(Site<LibId>),
+
+    /// library {0} is not available.
+    UnresolvedLib(LibId),
+
+    /// program execution has reached the end of the code segment, or a byte sequence which can't
+    /// be decoded as an instruction.
+    EndOfCode,
+
+    /// program execution was aborted on {0}.
+    InvalidJump(JumpFault<LibId>),
+
+    /// program execution was aborted: {0}
+    Aborted(ExecError),
+}
+
+/// Result of a program execution, reported by [`Vm::exec_full`].
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct ExecReport {
+    /// Value of the `CK` register at the end of the program execution.
+    pub status: Status,
+    /// Reason of the program execution halt, as reported by [`Vm::halt_reason`].
+    pub halt_reason: Option<HaltReason>,
+    /// Number of the executed instructions.
+    pub steps: u64,
+    /// Complexity accumulated by the execution.
+    pub complexity_used: u64,
+}
+
+/// Execution hook collecting [`ExecStats`].
+struct StatsHook {
+    stats: ExecStats,
+    ca: u64,
+}
+
+impl ExecHook<LibId> for StatsHook {
+    fn after_instr<Instr, const CALL_STACK_SIZE: usize>(
+        &mut self,
+        site: Site<LibId>,
+        _: &Instr,
+        core: &Core<LibId, Instr::Core, CALL_STACK_SIZE>,
+    ) -> HookAction
+    where
+        Instr: Instruction<LibId>,
+    {
+        // The charged complexity may differ from `Instruction::complexity`, for instance for the
+        // reserved instructions which are skipped
+        let complexity = core.ca().saturating_sub(self.ca);
+        self.ca = core.ca();
+        if complexity > self.stats.max_complexity || self.stats.max_complexity_site.is_none() {
+            self.stats.max_complexity = complexity;
+            self.stats.max_complexity_site = Some(site);
+        }
+        HookAction::Continue
+    }
+}
+
+/// Virtual machine with a program execution suspended due to a library which the host has failed
+/// to provide.
+#[derive(Clone, Debug)]
+pub struct SuspendedVm<
+    Isa = Instr<LibId>,
+    const CALL_STACK_SIZE: usize = { CALL_STACK_SIZE_MAX as usize },
+> where Isa: Instruction<LibId>
+{
+    vm: Vm<Isa, CALL_STACK_SIZE>,
+    site: LibSite,
+    skip: bool,
+}
+
+impl<Isa, const CALL_STACK_SIZE: usize> SuspendedVm<Isa, CALL_STACK_SIZE>
+where Isa: Instruction<LibId>
+{
+    /// Returns the registers of the suspended virtual machine.
+    pub fn core(&self) -> &Core<LibId, Isa::Core, CALL_STACK_SIZE> { &self.vm.core }
+
+    /// Returns the site at which the execution has been suspended.
+    pub fn site(&self) -> LibSite { self.site }
+
+    /// Returns the identifier of the library which the host has failed to provide.
+    pub fn missing_lib(&self) -> LibId { self.site.lib_id }
+}
+
+/// Returns the check of the library instruction boundaries, computing the boundary index once per
+/// library and caching it in the `boundaries`.
+#[cfg(feature = "paranoid")]
+fn boundary_check<'vm, Isa>(
+    boundaries: &'vm mut BTreeMap<LibId, BoundaryIndex>,
+    lib: &'vm Lib,
+) -> impl FnMut(u16) -> bool + 'vm
+where
+    Isa: Instruction<LibId>,
+{
+    move |pos| {
+        boundaries
+            .entry(lib.lib_id())
+            .or_insert_with(|| lib.boundary_index::<Isa>())
+            .is_boundary(pos)
+    }
+}
+
+/// Resumable state of a program executed one instruction at a time, for instance by a host which
+/// interleaves the program execution with other work.
+///
+/// The stepper keeps the site of the next instruction to execute, while the registers are kept by
+/// the virtual machine passed to [`Stepper::step`]. Each step executes the instruction with
+/// [`Vm::step`] and resolves the next site with [`Vm::next_site`], such that running a program to
+/// completion leaves the virtual machine in the same state as [`Vm::exec`].
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct Stepper {
+    site: Option<LibSite>,
+    started: bool,
+}
+
+impl Stepper {
+    /// Constructs a stepper starting the program execution from the `entry_point`.
+    pub fn new(entry_point: LibSite) -> Self { Self { site: Some(entry_point), started: false } }
+
+    /// Returns the site of the next instruction to execute, or `None` if the program has halted.
+    pub fn site(&self) -> Option<LibSite> { self.site }
+
+    /// Checks whether the program has halted.
+    pub fn is_halted(&self) -> bool { self.site.is_none() }
+
+    /// Executes the next instruction of the program and advances to the instruction following it.
+    ///
+    /// The first step discards the termination cause and the last error of the previous program
+    /// execution, as [`Vm::exec`] does. Once the program has halted, the step doesn't execute
+    /// anything and returns [`StepOutcome::Halted`] with the current value of the `CK` register.
+    ///
+    /// # Errors
+    ///
+    /// If [`Vm::step`] fails to execute the instruction. The program halts in this case.
+    pub fn step<Isa, const CALL_STACK_SIZE: usize, L: AsRef<Lib>>(
+        &mut self,
+        vm: &mut Vm<Isa, CALL_STACK_SIZE>,
+        context: &Isa::Context<'_>,
+        lib_resolver: impl Fn(LibId) -> Option<L>,
+    ) -> Result<StepOutcome, StepError>
+    where
+        Isa: Instruction<LibId>,
+    {
+        let Some(site) = self.site else {
+            return Ok(StepOutcome::Halted(vm.core.ck()));
+        };
+        // Like `Vm::exec`, the program execution starts without the outcome of a previous one
+        if !self.started {
+            self.started = true;
+            vm.last_error = None;
+            vm.core.reset_termination();
+        }
+        let step = vm
+            .step(site, context, &lib_resolver)
+            .inspect_err(|_| self.site = None)?;
+        self.site = vm.next_site(site, step, &lib_resolver);
+        Ok(match self.site {
+            None => StepOutcome::Halted(vm.core.ck()),
+            Some(next) if next.lib_id == site.lib_id => StepOutcome::Continue(next),
+            Some(next) => StepOutcome::Switch(next),
+        })
+    }
+}
+
+/// Outcome of a single [`Stepper::step`].
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum StepOutcome {
+    /// Program continues with the instruction at the site, located in the same library.
+    Continue(LibSite),
+
+    /// Program continues with the instruction at the site located in another library, due to a
+    /// call or a return.
+    Switch(LibSite),
+
+    /// Program has halted; the value of the `CK` register is provided.
+    Halted(Status),
+}
+
+/// Reasons for [`Vm::exec_checked`] to halt the program which are not caused by the program
+/// itself.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum ExecError {
+    /// library {0} required to execute site {1} is not available.
+    LibAbsent(LibId, LibSite),
+
+    /// site {0} is outside of the library code.
+    CodeOverrun(LibSite),
+
+    /// library containing site {0} requires ISA extensions not supported by the instruction set.
+    UnsupportedIsa(LibSite),
+
+    /// execution of the instruction at site {0} would exceed the instruction limit.
+    InstrLimit(LibSite),
+
+    /// context provided for the execution of site {0} is not of the type expected by the
+    /// instruction set.
+    ContextMismatch(LibSite),
+
+    /// execution has been terminated by the instrumentation hook at site {0}.
+    HookAbort(LibSite),
+
+    /// library containing site {0} doesn't pass validation against the instruction set.
+    InvalidLib(LibSite),
+}
+
+/// Errors calling a routine with [`Vm::call_entry`].
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum CallError {
+    /// invalid entry point: {0}
+    #[from]
+    EntryPoint(EntryPointError),
+
+    /// routine takes {expected} arguments, while {provided} arguments were provided.
+    ArgCount {
+        /// Number of the entry point inputs.
+        expected: usize,
+        /// Number of the provided arguments.
+        provided: usize,
+    },
+
+    /// routine execution has halted: {0}
+    #[from]
+    Exec(ExecError),
+
+    /// routine execution has failed, since {0}
+    Failed(TerminationCause<LibId>),
+}
+
+/// Reasons for [`Vm::step`] not to execute an instruction or to halt the program after its
+/// execution.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum StepError {
+    /// library {0} is not available.
+    NoLib(LibId),
+
+    /// site {0} is outside of the library code.
+    OutOfCode(LibSite),
+
+    /// unable to decode instruction at site {0}.
+    Decode(LibSite),
+
+    /// instruction at site {0} is not located at an instruction boundary.
+    #[cfg(feature = "paranoid")]
+    JumpFault(LibSite),
+
+    /// library containing site {0} requires ISA extensions not supported by the instruction set.
+    UnsupportedIsa(LibSite),
+
+    /// execution of the instruction at site {0} has exceeded the complexity limit.
+    ComplexityOverflow(LibSite),
+
+    /// control transfer by the instruction at site {0} has exceeded the jump count limit, and
+    /// `CH` is set.
+    JumpOverflow(LibSite),
+
+    /// execution of the instruction at site {0} would exceed the instruction limit.
+    InstrLimit(LibSite),
+}
+
+/// Result of a program execution which may be suspended by the host.
+#[derive(Clone, Debug)]
+pub enum ExecSuspension<
+    Isa = Instr<LibId>,
+    const CALL_STACK_SIZE: usize = { CALL_STACK_SIZE_MAX as usize },
+> where Isa: Instruction<LibId>
+{
+    /// Program execution has completed.
+    Complete {
+        /// Virtual machine after the program execution.
+        vm: Vm<Isa, CALL_STACK_SIZE>,
+        /// Value of the `CK` register at the end of the program execution.
+        status: Status,
+    },
+
+    /// Program execution has been suspended.
+    Suspended(SuspendedVm<Isa, CALL_STACK_SIZE>),
+}
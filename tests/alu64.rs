@@ -0,0 +1,602 @@
+// Reference rust implementation of AluVM (arithmetic logic unit virtual machine).
+// To find more on AluVM please check <https://aluvm.org>
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Designed in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+// Written in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+//
+// Copyright (C) 2021-2024 LNP/BP Standards Association, Switzerland.
+// Copyright (C) 2024-2025 Laboratories for Ubiquitous Deterministic Computing (UBIDECO),
+//                         Institute for Distributed and Cognitive Systems (InDCS), Switzerland.
+// Copyright (C) 2021-2025 Dr Maxim Orlovsky.
+// All rights under the above copyrights are reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use this file except
+// in compliance with the License. You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed under the License
+// is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express
+// or implied. See the License for the specific language governing permissions and limitations under
+// the License.
+
+use std::collections::BTreeMap;
+
+use aluvm::isa::{
+    Alu64Instr, ArithmInstr, Bytecode, ComplexityModel, Cost, CtrlInstr, Instruction, MultiIsa,
+    RegA, SelectInstr, TableInstr,
+};
+use aluvm::regs::Status;
+use aluvm::{
+    CallError, Core, CoreConfig, CoreSnapshot, EntryPoint, ExecError, ExecHook, HookAction, IsaId,
+    Lib, LibAssembler, LibId, LibSite, RegDump, Site, SymbolName, TerminationCause, Vm, VmRun,
+};
+
+const LOOP: u16 = 16;
+
+fn factorial(n: u64) -> Lib {
+    let code: Vec<Alu64Instr<LibId>> = vec![
+        ArithmInstr::Put { dst: RegA::A0, val: 1 }.into(),
+        ArithmInstr::Put { dst: RegA::A1, val: n }.into(),
+        ArithmInstr::Put { dst: RegA::A2, val: 1 }.into(),
+        ArithmInstr::Put { dst: RegA::A3, val: 0 }.into(),
+        // LOOP:
+        ArithmInstr::Mul { wrap: false, dst: RegA::A0, src: RegA::A1 }.into(),
+        ArithmInstr::Sub { wrap: false, dst: RegA::A1, src: RegA::A2 }.into(),
+        ArithmInstr::Eq { src1: RegA::A1, src2: RegA::A3 }.into(),
+        CtrlInstr::JiOvfl { pos: LOOP }.into(),
+        CtrlInstr::Stop.into(),
+    ];
+    Lib::assemble(&code).unwrap()
+}
+
+fn run(lib: &Lib) -> (Status, Vm<Alu64Instr<LibId>>) {
+    let mut vm = Vm::<Alu64Instr<LibId>>::with(CoreConfig::default(), ());
+    let status = vm.exec(LibSite::new(lib.lib_id(), 0), &(), |_| Some(lib));
+    (status, vm)
+}
+
+#[test]
+fn compute() {
+    let lib = factorial(20);
+    assert_eq!(lib.isae(), &IsaId::canonical_set([IsaId::from("ALU64")]));
+    assert_eq!(lib.disassemble::<Alu64Instr<LibId>>().unwrap()[4].to_string(), "mul     A0, A1");
+    let (status, vm) = run(&lib);
+    assert_eq!(status, Status::Ok);
+    assert_eq!(vm.core.get(RegA::A0), Some(2_432_902_008_176_640_000));
+    assert_eq!(vm.core.get(RegA::A1), Some(0));
+    assert_eq!(vm.core.cy(), 19);
+}
+
+#[test]
+fn overflow() {
+    let (status, vm) = run(&factorial(21));
+    assert_eq!(status, Status::Fail);
+    assert_eq!(vm.core.co(), Status::Fail);
+    assert_eq!(vm.core.get(RegA::A0), None);
+    assert_eq!(vm.core.get(RegA::A1), Some(3));
+    assert_eq!(vm.core.cf(), 1);
+}
+
+#[test]
+fn reset() {
+    let (_, mut vm) = run(&factorial(3));
+    assert_eq!(vm.core.get(RegA::A0), Some(6));
+    vm.core.reset();
+    assert_eq!(RegA::ALL.map(|reg| vm.core.get(reg)), [None; 16]);
+}
+
+#[test]
+fn dump_mid_execution() {
+    let lib = factorial(5);
+    let lib_id = lib.lib_id();
+    let mut vm = Vm::<Alu64Instr<LibId>>::with(CoreConfig::default(), ());
+    // Pause before the `eq` instruction of the second loop iteration
+    let brk = Site::new(lib_id, LOOP + 4);
+    vm.add_breakpoint(brk);
+    assert_eq!(vm.exec_until(LibSite::new(lib_id, 0), &(), |_| Some(&lib)), VmRun::Breakpoint(brk));
+    assert_eq!(vm.resume_from_breakpoint(&(), |_| Some(&lib)), VmRun::Breakpoint(brk));
+
+    let dump = vm.core.dump();
+    assert_eq!(dump.cy, 1);
+    assert_eq!(dump.ck, Status::Ok);
+    let regs = dump.regs.iter().take(5).cloned().collect::<Vec<_>>();
+    let reg =
+        |reg: &str, val: Option<&str>| RegDump { reg: reg.to_owned(), val: val.map(str::to_owned) };
+    assert_eq!(regs, [
+        reg("A0", Some("20")),
+        reg("A1", Some("3")),
+        reg("A2", Some("1")),
+        reg("A3", Some("0")),
+        reg("A4", None)
+    ]);
+    assert!(dump
+        .to_string()
+        .contains("\nA0 20, A1 3, A2 1, A3 0, A4 ~, "));
+
+    assert_eq!(vm.resume_from_breakpoint(&(), |_| Some(&lib)), VmRun::Breakpoint(brk));
+    assert_eq!(vm.core.dump().reg("A0").unwrap().val.as_deref(), Some("60"));
+}
+
+#[test]
+fn snapshot_resume() {
+    use strict_encoding::{StrictDeserialize, StrictSerialize};
+
+    let lib = factorial(20);
+    let lib_id = lib.lib_id();
+    let (status, expected) = run(&lib);
+
+    let mut vm = Vm::<Alu64Instr<LibId>>::with(CoreConfig::default(), ());
+    let brk = Site::new(lib_id, LOOP + 4);
+    vm.add_breakpoint(brk);
+    assert_eq!(vm.exec_until(LibSite::new(lib_id, 0), &(), |_| Some(&lib)), VmRun::Breakpoint(brk));
+    assert_eq!(vm.resume_from_breakpoint(&(), |_| Some(&lib)), VmRun::Breakpoint(brk));
+
+    let data = vm.core.snapshot().to_strict_serialized::<0xFFFF>().unwrap();
+    let snapshot = CoreSnapshot::<LibId, [Option<u64>; 16]>::from_strict_serialized(data).unwrap();
+
+    let mut resumed = Vm::<Alu64Instr<LibId>>::with(CoreConfig::default(), ());
+    resumed.core.restore(snapshot).unwrap();
+    assert_eq!(resumed.exec(brk.into(), &(), |_| Some(&lib)), status);
+    assert_eq!(resumed.core.dump(), expected.core.dump());
+}
+
+/// Main code calls the outer subroutine, which saves `A0` and calls the inner one, clobbering `A0`
+/// and `A1` after saving the registers selected by the `inner_mask`. The outer subroutine copies
+/// the value of `A0` it sees after the inner call into `A2`.
+fn nested_calls(inner_mask: u16) -> Lib {
+    let put = |dst: RegA, val: u64| Alu64Instr::from(ArithmInstr::Put { dst, val });
+    let main = |outer: u16| {
+        vec![
+            put(RegA::A0, 1),
+            put(RegA::A1, 1),
+            CtrlInstr::Fn { pos: outer }.into(),
+            CtrlInstr::Stop.into(),
+        ]
+    };
+    let outer = |inner: u16| {
+        vec![
+            CtrlInstr::Save { mask: 0b01 }.into(),
+            put(RegA::A0, 2),
+            CtrlInstr::Fn { pos: inner }.into(),
+            ArithmInstr::Mov { dst: RegA::A2, src: RegA::A0 }.into(),
+            CtrlInstr::Ret.into(),
+        ]
+    };
+    let inner = vec![
+        CtrlInstr::Save { mask: inner_mask }.into(),
+        put(RegA::A0, 3),
+        put(RegA::A1, 3),
+        CtrlInstr::Ret.into(),
+    ];
+    let len = |code: &[Alu64Instr<LibId>]| code.iter().map(|instr| instr.code_byte_len()).sum();
+    let outer_pos = len(&main(0));
+    let inner_pos = outer_pos + len(&outer(0));
+    Lib::assemble(&[main(outer_pos), outer(inner_pos), inner].concat()).unwrap()
+}
+
+#[test]
+fn saved_registers() {
+    let regs =
+        |vm: &Vm<Alu64Instr<LibId>>| [RegA::A0, RegA::A1, RegA::A2].map(|reg| vm.core.get(reg));
+
+    // Inner subroutine saves both registers it clobbers
+    let (status, vm) = run(&nested_calls(0b11));
+    assert_eq!(status, Status::Ok);
+    assert_eq!(regs(&vm), [Some(1), Some(1), Some(2)]);
+    assert_eq!(vm.core.save_stack(), &[]);
+
+    // Inner subroutine saves `A1` only, thus the outer one sees `A0` clobbered
+    let (status, vm) = run(&nested_calls(0b10));
+    assert_eq!(status, Status::Ok);
+    assert_eq!(regs(&vm), [Some(1), Some(1), Some(3)]);
+
+    // Nothing is saved by the inner subroutine, thus `A1` is clobbered for the main code as well
+    let (status, vm) = run(&nested_calls(0));
+    assert_eq!(status, Status::Ok);
+    assert_eq!(regs(&vm), [Some(1), Some(3), Some(3)]);
+}
+
+#[test]
+fn symbol_operands() {
+    const MODULUS: u64 = 0xFFFF_FFFF_0000_0001;
+    let root = [0x5Au8; 32];
+
+    let mut asm = LibAssembler::<Alu64Instr<LibId>>::new();
+    let root_range = asm.define_symbol(SymbolName::from("root"), &root).unwrap();
+    let modulus = asm
+        .define_symbol(SymbolName::from("modulus"), &MODULUS.to_le_bytes())
+        .unwrap();
+    // Operands referencing the constants are computed from the symbol table
+    for instr in [
+        ArithmInstr::Put { dst: RegA::A0, val: modulus.offset as u64 },
+        ArithmInstr::Put { dst: RegA::A1, val: root_range.len as u64 },
+        ArithmInstr::Put { dst: RegA::A2, val: MODULUS },
+    ] {
+        asm.append(&instr.into()).unwrap();
+    }
+    asm.append(&CtrlInstr::Stop.into()).unwrap();
+    let (lib, symbols) = asm.finish_with_symbols().unwrap();
+    // The `put` constants share the place with the symbol and with each other (both are 32)
+    assert_eq!(lib.data().len(), 32 + 8 + 8);
+    assert_eq!(lib.symbol(&symbols, "root"), Some(&root[..]));
+    assert_eq!(lib.symbol(&symbols, "modulus"), Some(&MODULUS.to_le_bytes()[..]));
+
+    let (status, vm) = run(&lib);
+    assert_eq!(status, Status::Ok);
+    assert_eq!(vm.core.get(RegA::A0), Some(32));
+    assert_eq!(vm.core.get(RegA::A1), Some(32));
+    assert_eq!(vm.core.get(RegA::A2), Some(MODULUS));
+}
+
+#[test]
+fn branchless_max() {
+    type SelIsa = MultiIsa<MultiIsa<ArithmInstr, SelectInstr>, CtrlInstr<LibId>>;
+
+    let arithm = |instr: ArithmInstr| SelIsa::Primary(MultiIsa::Primary(instr));
+    let select = |instr: SelectInstr| SelIsa::Primary(MultiIsa::Secondary(instr));
+    let code = |a: u64, b: u64| {
+        [
+            arithm(ArithmInstr::Put { dst: RegA::A0, val: a }),
+            arithm(ArithmInstr::Put { dst: RegA::A1, val: b }),
+            arithm(ArithmInstr::Mov { dst: RegA::A2, src: RegA::A0 }),
+            // `CO` fails if `a < b`
+            arithm(ArithmInstr::Sub { wrap: true, dst: RegA::A2, src: RegA::A1 }),
+            select(SelectInstr::MovCo { dst: RegA::A3 }),
+            select(SelectInstr::Sel { dst: RegA::A1, src: RegA::A0 }),
+        ]
+    };
+    for (a, b, max, less) in [(3, 5, 5, 1), (5, 3, 5, 0), (4, 4, 4, 0)] {
+        let lib = Lib::assemble(&code(a, b)).unwrap();
+        assert_eq!(lib.isae(), &IsaId::canonical_set([IsaId::from("ALU64"), IsaId::from("SEL64")]));
+        assert_eq!(lib.disassemble::<SelIsa>().unwrap()[5].to_string(), "sel     A1, A0");
+        let mut vm = Vm::<SelIsa>::with(CoreConfig::default(), ());
+        let status = vm.exec(LibSite::new(lib.lib_id(), 0), &(), |_| Some(&lib));
+        assert_eq!(status, Status::Ok);
+        assert_eq!(vm.core.get(RegA::A1), Some(max));
+        assert_eq!(vm.core.get(RegA::A3), Some(less));
+        assert_eq!(vm.core.cy(), 0);
+    }
+}
+
+type SwitchIsa = MultiIsa<MultiIsa<ArithmInstr, TableInstr>, CtrlInstr<LibId>>;
+
+fn switch(idx: u64, table: [u16; 4]) -> Lib {
+    let arithm = |instr: ArithmInstr| SwitchIsa::Primary(MultiIsa::Primary(instr));
+    let put = |val: u64| arithm(ArithmInstr::Put { dst: RegA::A1, val });
+    let ctrl = |instr: CtrlInstr<LibId>| SwitchIsa::Secondary(instr);
+    let code = [
+        arithm(ArithmInstr::Put { dst: RegA::A0, val: idx }),
+        SwitchIsa::Primary(MultiIsa::Secondary(TableInstr::Jmp {
+            idx: RegA::A0,
+            table: table.to_vec(),
+        })),
+        // index out of range
+        ctrl(CtrlInstr::Stop),
+        // 0x0B: case 0
+        put(100),
+        ctrl(CtrlInstr::Jmp { pos: 0x24 }),
+        // 0x12: case 1
+        put(101),
+        ctrl(CtrlInstr::Jmp { pos: 0x24 }),
+        // 0x19: case 2
+        put(102),
+        ctrl(CtrlInstr::Jmp { pos: 0x24 }),
+        // 0x20: case 3
+        put(103),
+        // 0x24: end
+        ctrl(CtrlInstr::Stop),
+    ];
+    Lib::assemble(&code).unwrap()
+}
+
+#[test]
+fn jump_table_switch() {
+    const CASES: [u16; 4] = [0x0B, 0x12, 0x19, 0x20];
+    for idx in 0..4 {
+        let lib = switch(idx, CASES);
+        assert_eq!(
+            lib.isae(),
+            &IsaId::canonical_set([IsaId::from("ALU64"), IsaId::from("JMPTBL")])
+        );
+        let mut vm = Vm::<SwitchIsa>::with(CoreConfig::default(), ());
+        let status = vm.exec(LibSite::new(lib.lib_id(), 0), &(), |_| Some(&lib));
+        assert_eq!(status, Status::Ok);
+        assert_eq!(vm.core.get(RegA::A1), Some(100 + idx));
+        // a single dispatch jump, plus the jump to the end for all cases but the last one
+        assert_eq!(vm.core.cy(), if idx == 3 { 1 } else { 2 });
+    }
+
+    let lib = switch(4, CASES);
+    let mut vm = Vm::<SwitchIsa>::with(CoreConfig::default(), ());
+    let status = vm.exec(LibSite::new(lib.lib_id(), 0), &(), |_| Some(&lib));
+    assert_eq!(status, Status::Fail);
+    assert_eq!(vm.core.get(RegA::A1), None);
+    assert_eq!(vm.core.cy(), 0);
+}
+
+#[test]
+fn jump_table_analysis() {
+    let lib = switch(0, [0x0B, 0x12, 0x19, 0x12]);
+    assert_eq!(
+        lib.disassemble::<SwitchIsa>().unwrap()[1].to_string(),
+        "jmp     A0, [11, 18, 25, 18]"
+    );
+    let cfg = lib.control_flow_graph::<SwitchIsa>();
+    assert!(cfg.is_valid());
+    let targets = cfg
+        .successors(0)
+        .map(|edge| edge.target)
+        .collect::<Vec<_>>();
+    // the duplicated table entry produces a single edge, followed by the fallthrough edge
+    assert_eq!(targets, [0x0B, 0x12, 0x19, 0x0A]);
+    assert_eq!(cfg.predecessors(0x20).count(), 0);
+
+    let lib = switch(0, [0x0B, 0x13, 0x19, 0x30]);
+    let errs = lib.validate_jumps::<SwitchIsa>().unwrap_err();
+    assert_eq!(errs.len(), 2);
+    assert_eq!(
+        errs[0].to_string(),
+        "instruction at offset 0x0004 jumps to offset 0x0013, which is in the middle of another \
+         instruction."
+    );
+    assert_eq!(
+        errs[1].to_string(),
+        "instruction at offset 0x0004 jumps outside the library code segment."
+    );
+}
+
+#[derive(Default)]
+struct Histogram {
+    opcodes: BTreeMap<u8, usize>,
+    offsets: BTreeMap<u16, usize>,
+    abort_at: Option<usize>,
+}
+
+impl ExecHook<LibId> for Histogram {
+    fn before_instr<Instr>(&mut self, site: Site<LibId>, instr: &Instr) -> HookAction
+    where Instr: Instruction<LibId> {
+        if Some(self.opcodes.values().sum()) == self.abort_at {
+            return HookAction::Abort;
+        }
+        *self.opcodes.entry(instr.opcode_byte()).or_default() += 1;
+        *self.offsets.entry(site.offset).or_default() += 1;
+        HookAction::Continue
+    }
+}
+
+#[test]
+fn exec_hook() {
+    let lib = factorial(5);
+    let mut hook = Histogram::default();
+    let mut vm = Vm::<Alu64Instr<LibId>>::with(CoreConfig::default(), ());
+    let status = vm.exec_with_hook(LibSite::new(lib.lib_id(), 0), &(), |_| Some(&lib), &mut hook);
+    assert_eq!(status, Status::Ok);
+    assert_eq!(vm.last_error(), None);
+    assert_eq!(vm.core.get(RegA::A0), Some(120));
+    assert_eq!(
+        hook.opcodes,
+        BTreeMap::from([
+            (ArithmInstr::PUT, 4),
+            (ArithmInstr::MUL, 5),
+            (ArithmInstr::SUB, 5),
+            (ArithmInstr::EQ, 5),
+            (CtrlInstr::<LibId>::JINE, 5),
+            (CtrlInstr::<LibId>::STOP, 1),
+        ])
+    );
+    assert_eq!(hook.offsets[&LOOP], 5);
+    assert_eq!(hook.opcodes.values().sum::<usize>(), vm.core.ci() as usize);
+}
+
+#[test]
+fn exec_hook_abort() {
+    let lib = factorial(5);
+    let lib_id = lib.lib_id();
+    let mut hook = Histogram { abort_at: Some(6), ..Histogram::default() };
+    let mut vm = Vm::<Alu64Instr<LibId>>::with(CoreConfig::default(), ());
+    let status = vm.exec_with_hook(LibSite::new(lib_id, 0), &(), |_| Some(&lib), &mut hook);
+    assert_eq!(status, Status::Fail);
+    assert_eq!(vm.last_error(), Some(ExecError::HookAbort(LibSite::new(lib_id, LOOP + 4))));
+    assert_eq!(vm.core.ci(), 6);
+    assert_eq!(vm.core.get(RegA::A1), Some(4));
+
+    struct AbortOnOverflow;
+    impl ExecHook<LibId> for AbortOnOverflow {
+        fn after_instr<Instr, const CALL_STACK_SIZE: usize>(
+            &mut self,
+            _: Site<LibId>,
+            _: &Instr,
+            core: &Core<LibId, Instr::Core, CALL_STACK_SIZE>,
+        ) -> HookAction
+        where
+            Instr: Instruction<LibId>,
+        {
+            if core.co().is_ok() {
+                HookAction::Continue
+            } else {
+                HookAction::Abort
+            }
+        }
+    }
+    let status =
+        vm.exec_with_hook(LibSite::new(lib_id, 0), &(), |_| Some(&lib), &mut AbortOnOverflow);
+    assert_eq!(status, Status::Fail);
+    // The instruction setting `CO` is executed before the abort
+    assert_eq!(vm.last_error(), Some(ExecError::HookAbort(LibSite::new(lib_id, LOOP + 4))));
+    assert_eq!(vm.core.co(), Status::Fail);
+}
+
+#[test]
+fn call_entry() {
+    const SUM: u16 = 0;
+    const SHIFT: u16 = 7;
+    let code: Vec<Alu64Instr<LibId>> = vec![
+        // SUM: A0 + A1, leaving the A8 scratch register set
+        ArithmInstr::Put { dst: RegA::A8, val: 100 }.into(),
+        ArithmInstr::Add { wrap: false, dst: RegA::A0, src: RegA::A1 }.into(),
+        CtrlInstr::Stop.into(),
+        // SHIFT: A2 + A8, failing if the scratch register is not set
+        ArithmInstr::Add { wrap: false, dst: RegA::A2, src: RegA::A8 }.into(),
+        CtrlInstr::Stop.into(),
+    ];
+    let lib = Lib::assemble(&code).unwrap();
+    let resolver = |_| Some(&lib);
+    assert_eq!(lib.disassemble::<Alu64Instr<LibId>>().unwrap()[3].to_string(), "add     A2, A8");
+    let sum = EntryPoint::with(LibSite::new(lib.lib_id(), SUM), [RegA::A0, RegA::A1], [RegA::A0])
+        .unwrap();
+    let shift =
+        EntryPoint::with(LibSite::new(lib.lib_id(), SHIFT), [RegA::A2], [RegA::A2]).unwrap();
+
+    let mut vm = Vm::<Alu64Instr<LibId>>::with(CoreConfig::default(), ());
+    assert_eq!(vm.call_entry(&sum, &[2, 3], &(), resolver), Ok(vec![Some(5)]));
+    assert_eq!(vm.core.get(RegA::A8), Some(100));
+    assert_eq!(vm.call_entry(&sum, &[40, 2], &(), resolver), Ok(vec![Some(42)]));
+
+    // The scratch register left by the previous call is not visible to the next one
+    assert_eq!(
+        vm.call_entry(&shift, &[1], &(), resolver),
+        Err(CallError::Failed(TerminationCause::Halted { site: Site::new(lib.lib_id(), SHIFT) }))
+    );
+    assert_eq!(vm.core.get(RegA::A8), None);
+    assert_eq!(vm.core.cf(), 1);
+
+    // Arguments are validated against the entry point
+    assert_eq!(
+        vm.call_entry(&sum, &[1], &(), resolver),
+        Err(CallError::ArgCount { expected: 2, provided: 1 })
+    );
+    let missing =
+        EntryPoint::with(LibSite::new(LibId::from([0xA5u8; 32]), 0), [], [RegA::A0]).unwrap();
+    let err = vm.call_entry(&missing, &[], &(), |id| (id == lib.lib_id()).then_some(&lib));
+    assert_eq!(
+        err,
+        Err(CallError::Exec(ExecError::LibAbsent(missing.site().lib_id, missing.site())))
+    );
+}
+
+#[test]
+fn cost_rules() {
+    let lib = factorial(5);
+    let run = |model: ComplexityModel| {
+        let config = CoreConfig { complexity_lim: Some(500_000), ..CoreConfig::default() };
+        let mut vm = Vm::<Alu64Instr<LibId>>::with(config, ());
+        vm.core.set_complexity_model(model);
+        let status = vm.exec(LibSite::new(lib.lib_id(), 0), &(), |_| Some(&lib));
+        (status, vm)
+    };
+
+    let (status, vm) = run(ComplexityModel::DEFAULT);
+    assert_eq!(status, Status::Ok);
+    assert_eq!(vm.core.ca(), 440_000);
+
+    // Arithmetic instructions get expensive, and the limit is hit by the first multiplication
+    let mut alu = ComplexityModel::DEFAULT;
+    alu.isa.insert(IsaId::from("ALU64"), Cost::Multiplier(10));
+    let (status, vm) = run(alu);
+    assert_eq!(status, Status::Fail);
+    assert_eq!(vm.core.ci(), 5);
+    assert_eq!(vm.termination(), Some(TerminationCause::ComplexityExceeded));
+
+    // Jumps get expensive, and the limit is hit by the second jump
+    let mut jumps = ComplexityModel::DEFAULT;
+    jumps
+        .opcodes
+        .insert(CtrlInstr::<LibId>::JINE, Cost::Absolute(200_000));
+    let (status, vm) = run(jumps);
+    assert_eq!(status, Status::Fail);
+    assert_eq!(vm.core.ci(), 12);
+    assert_eq!(vm.termination(), Some(TerminationCause::ComplexityExceeded));
+}
+
+#[test]
+fn complexity_model() {
+    let lib = factorial(5);
+    let config = CoreConfig { complexity_lim: Some(500_000), ..CoreConfig::default() };
+    let mut vm = Vm::<Alu64Instr<LibId>>::with(config, ());
+
+    // Doubling the class costs doubles the accounted complexity, which exceeds the limit
+    let model = ComplexityModel::DEFAULT;
+    vm.core.set_complexity_model(ComplexityModel {
+        trivial: model.trivial * 2,
+        light: model.light * 2,
+        medium: model.medium * 2,
+        heavy: model.heavy * 2,
+        call: model.call * 2,
+        external: model.external * 2,
+        ..ComplexityModel::DEFAULT
+    });
+    let status = vm.exec(LibSite::new(lib.lib_id(), 0), &(), |_| Some(&lib));
+    assert_eq!(status, Status::Fail);
+    assert_eq!(vm.termination(), Some(TerminationCause::ComplexityExceeded));
+
+    // The model is kept by the reset, and cheaper classes let the program complete
+    vm.reset();
+    vm.core
+        .set_complexity_model(ComplexityModel { medium: 5_000, ..ComplexityModel::DEFAULT });
+    let status = vm.exec(LibSite::new(lib.lib_id(), 0), &(), |_| Some(&lib));
+    assert_eq!(status, Status::Ok);
+    assert!(vm.core.ca() < 440_000);
+    assert_eq!(vm.core.complexity_model().medium, 5_000);
+}
+
+#[test]
+fn repeated_constants() {
+    const VAL: u64 = 0x0123_4567_89AB_CDEF;
+    let mut code: Vec<Alu64Instr<LibId>> = RegA::ALL[..10]
+        .iter()
+        .map(|dst| ArithmInstr::Put { dst: *dst, val: VAL }.into())
+        .collect();
+    code.push(CtrlInstr::Stop.into());
+    let lib = Lib::assemble(&code).unwrap();
+    assert_eq!(lib.data().as_slice(), &VAL.to_le_bytes());
+
+    let (status, vm) = run(&lib);
+    assert_eq!(status, Status::Ok);
+    assert!(RegA::ALL[..10]
+        .iter()
+        .all(|reg| vm.core.get(*reg) == Some(VAL)));
+}
+
+#[test]
+fn register_usage_and_complexity() {
+    let code: Vec<Alu64Instr<LibId>> = vec![
+        ArithmInstr::Put { dst: RegA::A0, val: 40 }.into(),
+        ArithmInstr::Put { dst: RegA::A1, val: 2 }.into(),
+        ArithmInstr::Add { wrap: false, dst: RegA::A0, src: RegA::A1 }.into(),
+        ArithmInstr::Mov { dst: RegA::A2, src: RegA::A0 }.into(),
+        ArithmInstr::Eq { src1: RegA::A0, src2: RegA::A2 }.into(),
+        CtrlInstr::Stop.into(),
+    ];
+    let lib = Lib::assemble(&code).unwrap();
+    let decoded = lib.disassemble::<Alu64Instr<LibId>>().unwrap();
+    assert_eq!(decoded, code);
+
+    let regs = decoded
+        .iter()
+        .map(|instr| {
+            (instr.src_regs().into_iter().collect(), instr.dst_regs().into_iter().collect())
+        })
+        .collect::<Vec<(Vec<_>, Vec<_>)>>();
+    assert_eq!(regs, vec![
+        (vec![], vec![RegA::A0]),
+        (vec![], vec![RegA::A1]),
+        (vec![RegA::A0, RegA::A1], vec![RegA::A0]),
+        (vec![RegA::A0], vec![RegA::A2]),
+        (vec![RegA::A0, RegA::A2], vec![]),
+        (vec![], vec![]),
+    ]);
+
+    let config = CoreConfig { complexity_lim: Some(u64::MAX), ..CoreConfig::default() };
+    let mut vm = Vm::<Alu64Instr<LibId>>::with(config, ());
+    let status = vm.exec(LibSite::new(lib.lib_id(), 0), &(), |_| Some(&lib));
+    assert_eq!(status, Status::Ok);
+    assert_eq!(vm.core.co(), Status::Ok);
+    assert_eq!(vm.core.get(RegA::A0), Some(42));
+    assert_eq!(vm.core.get(RegA::A2), Some(42));
+    assert_eq!(vm.core.get(RegA::A3), None);
+    assert_eq!(vm.core.ci(), code.len() as u64);
+    assert_eq!(vm.core.ca(), code.iter().map(Instruction::complexity).sum::<u64>());
+}
@@ -0,0 +1,99 @@
+// Reference rust implementation of AluVM (arithmetic logic unit virtual machine).
+// To find more on AluVM please check <https://aluvm.org>
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Designed in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+// Written in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+//
+// Copyright (C) 2021-2024 LNP/BP Standards Association, Switzerland.
+// Copyright (C) 2024-2025 Laboratories for Ubiquitous Deterministic Computing (UBIDECO),
+//                         Institute for Distributed and Cognitive Systems (InDCS), Switzerland.
+// Copyright (C) 2021-2025 Dr Maxim Orlovsky.
+// All rights under the above copyrights are reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use this file except
+// in compliance with the License. You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed under the License
+// is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express
+// or implied. See the License for the specific language governing permissions and limitations under
+// the License.
+
+//! Peak memory usage of the streaming assembly compared to the one-shot one. This is a separate
+//! test binary with a single test, since it replaces the global allocator.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use aluvm::isa::{CtrlInstr, Instr};
+use aluvm::{Lib, LibAssembler, LibId, Site};
+
+struct CountingAlloc;
+
+static CURRENT: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            let current = CURRENT.fetch_add(layout.size(), Ordering::SeqCst) + layout.size();
+            PEAK.fetch_max(current, Ordering::SeqCst);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        CURRENT.fetch_sub(layout.size(), Ordering::SeqCst);
+    }
+}
+
+#[global_allocator]
+static ALLOC: CountingAlloc = CountingAlloc;
+
+/// Generates a program close to the maximal code segment size.
+fn program() -> impl Iterator<Item = Instr<LibId>> {
+    let libs = [7u8, 3, 250, 1, 42].map(|b| LibId::from([b; 32]));
+    (0..16_000usize).map(move |i| {
+        let site = Site::new(libs[i % libs.len()], i as u16);
+        match i % 2 {
+            0 => CtrlInstr::Call { site },
+            _ => CtrlInstr::Exec { site },
+        }
+        .into()
+    })
+}
+
+/// Runs the closure, returning its result and the peak heap usage above the baseline.
+fn measure<T>(f: impl FnOnce() -> T) -> (T, usize) {
+    let base = CURRENT.load(Ordering::SeqCst);
+    PEAK.store(base, Ordering::SeqCst);
+    let res = f();
+    (res, PEAK.load(Ordering::SeqCst) - base)
+}
+
+#[test]
+fn streaming_peak_memory() {
+    let (one_shot, one_shot_peak) = measure(|| {
+        let code = program().collect::<Vec<_>>();
+        Lib::assemble(&code).unwrap()
+    });
+    let (streamed, streamed_peak) = measure(|| {
+        let mut asm = LibAssembler::new();
+        for instr in program() {
+            asm.append(&instr).unwrap();
+        }
+        asm.finish().unwrap()
+    });
+
+    assert_eq!(streamed, one_shot);
+    let lib_size = streamed.code().len() + streamed.data().len();
+    assert!(lib_size > 0xF000);
+    // Code buffer with a spare capacity and a patch list of four bytes per reference
+    assert!(streamed_peak <= lib_size * 2 + 16_000 * 4 * 2, "{streamed_peak} for {lib_size}");
+    assert!(streamed_peak * 2 < one_shot_peak, "{streamed_peak} vs {one_shot_peak}");
+}
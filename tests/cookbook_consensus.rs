@@ -0,0 +1,146 @@
+// Reference rust implementation of AluVM (arithmetic logic unit virtual machine).
+// To find more on AluVM please check <https://aluvm.org>
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Designed in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+// Written in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+//
+// Copyright (C) 2021-2024 LNP/BP Standards Association, Switzerland.
+// Copyright (C) 2024-2025 Laboratories for Ubiquitous Deterministic Computing (UBIDECO),
+//                         Institute for Distributed and Cognitive Systems (InDCS), Switzerland.
+// Copyright (C) 2021-2025 Dr Maxim Orlovsky.
+// All rights under the above copyrights are reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use this file except
+// in compliance with the License. You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed under the License
+// is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express
+// or implied. See the License for the specific language governing permissions and limitations under
+// the License.
+
+//! Consensus validator: loads a program bundle, verifies it and executes it deterministically.
+
+extern crate alloc;
+
+use aluvm::isa::Instr;
+use aluvm::regs::Status;
+use aluvm::{
+    aluasm, CompiledLib, CoreConfig, IsaId, Lib, LibId, ProfileData, Program, ProgramError, Site,
+    UnknownInstrPolicy, Vm,
+};
+
+fn bundle() -> (Lib, Lib) {
+    let dep = CompiledLib::compile(
+        aluasm! {
+            nop;
+            chk     CO;
+            not     CO;
+            not     CO;
+            ret;
+        },
+        &[],
+    )
+    .unwrap();
+    let dep_id = dep.as_lib().lib_id();
+    let main = CompiledLib::compile(
+        aluasm! {
+            nop;
+            call    dep_id, 0;
+            call    dep_id, 0;
+            chk     CO;
+            stop;
+        },
+        &[&dep],
+    )
+    .unwrap();
+    (main.into_lib(), dep.into_lib())
+}
+
+const CONFIG: CoreConfig = CoreConfig {
+    halt: true,
+    complexity_lim: Some(100_000_000),
+    complexity_warn: None,
+    jump_lim: None,
+    instr_lim: None,
+    cs_integrity: true,
+    unknown_instr: UnknownInstrPolicy::Fail,
+};
+
+#[test]
+fn consensus_validator() {
+    let (main, dep) = bundle();
+    let (main_id, dep_id) = (main.lib_id(), dep.lib_id());
+    let program = Program::new(main, 0).unwrap().with_dep(dep).unwrap();
+
+    // Libraries are addressed by ids committing to their content
+    assert_eq!(program.entry_point().lib_id, main_id);
+    for lib in program.libs() {
+        assert_eq!(program.lib(lib.lib_id()), Some(lib));
+        assert!(lib.isa_consistency_report::<Instr<LibId>>().is_consistent());
+    }
+    program.verify::<Instr<LibId>>().unwrap();
+
+    let run = || {
+        let mut vm = Vm::<Instr<LibId>>::with(CONFIG, ());
+        vm.core.start_profiling(64);
+        let status = program.run(&mut vm, &()).unwrap();
+        let core = &vm.core;
+        (status, core.ca(), core.cs_high_water(), core.cs_fault(), core.profile().cloned())
+    };
+    let first = run();
+    assert_eq!(first, run());
+
+    let (status, ca, cs_high_water, cs_fault, profile) = first;
+    assert_eq!(status, Status::Ok);
+    assert!(ca > 0 && ca < 100_000_000);
+    assert_eq!(cs_high_water, 1);
+    assert_eq!(cs_fault, None);
+    let profile = profile.unwrap();
+    assert_eq!(profile.site(Site::new(dep_id, 1)).unwrap().count, 2);
+    assert_eq!(
+        profile
+            .by_opcode()
+            .values()
+            .map(|stats| stats.count)
+            .sum::<u64>(),
+        15
+    );
+    assert_eq!(
+        profile
+            .by_opcode()
+            .values()
+            .map(|stats| stats.complexity)
+            .sum::<u64>(),
+        ca
+    );
+}
+
+#[test]
+fn consensus_rejects_unknown_isa() {
+    let (main, dep) = bundle();
+    let dep_id = dep.lib_id();
+    let mut foreign = dep;
+    *foreign.isae_mut() = IsaId::canonical_set([IsaId::from("FOREIGN")]);
+    let foreign_id = foreign.lib_id();
+    assert_ne!(foreign_id, dep_id);
+
+    // The main library references the original dependency, not the foreign one
+    let program = Program::new(main.clone(), 0)
+        .unwrap()
+        .with_dep(foreign.clone())
+        .unwrap();
+    assert_eq!(
+        program.verify::<Instr<LibId>>(),
+        Err(ProgramError::MissingDep(main.lib_id(), dep_id))
+    );
+
+    let program = Program::new(foreign, 0).unwrap();
+    assert_eq!(
+        program.verify::<Instr<LibId>>(),
+        Err(ProgramError::UnsupportedIsa(foreign_id, IsaId::from("FOREIGN")))
+    );
+}
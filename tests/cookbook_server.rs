@@ -0,0 +1,112 @@
+// Reference rust implementation of AluVM (arithmetic logic unit virtual machine).
+// To find more on AluVM please check <https://aluvm.org>
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Designed in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+// Written in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+//
+// Copyright (C) 2021-2024 LNP/BP Standards Association, Switzerland.
+// Copyright (C) 2024-2025 Laboratories for Ubiquitous Deterministic Computing (UBIDECO),
+//                         Institute for Distributed and Cognitive Systems (InDCS), Switzerland.
+// Copyright (C) 2021-2025 Dr Maxim Orlovsky.
+// All rights under the above copyrights are reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use this file except
+// in compliance with the License. You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed under the License
+// is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express
+// or implied. See the License for the specific language governing permissions and limitations under
+// the License.
+
+//! Metered server: runs a batch of requests on a program with lazily loaded external libraries,
+//! suspending the execution while a library is being fetched, and collecting metrics.
+
+extern crate alloc;
+
+use aluvm::isa::{CtrlInstr, Instr};
+use aluvm::regs::Status;
+use aluvm::{
+    aluasm, CompiledLib, CoreConfig, ExecSuspension, Lib, LibId, ProfileData, Program, Site,
+    UnknownInstrPolicy, Vm,
+};
+
+const CONFIG: CoreConfig = CoreConfig {
+    halt: true,
+    complexity_lim: Some(100_000_000),
+    complexity_warn: Some(1),
+    jump_lim: None,
+    instr_lim: None,
+    cs_integrity: false,
+    unknown_instr: UnknownInstrPolicy::Fail,
+};
+
+fn libs() -> (Lib, Lib) {
+    let ext = CompiledLib::compile(
+        aluasm! {
+            nop;
+            not     CO;
+            ret;
+        },
+        &[],
+    )
+    .unwrap();
+    let ext_id = ext.as_lib().lib_id();
+    let main = CompiledLib::compile(
+        aluasm! {
+            nop;
+            call    ext_id, 0;
+            not     CO;
+            chk     CO;
+            stop;
+        },
+        &[&ext],
+    )
+    .unwrap();
+    (main.into_lib(), ext.into_lib())
+}
+
+#[test]
+fn metered_server() {
+    let (main, ext) = libs();
+    let ext_id = ext.lib_id();
+    let program = Program::new(main, 0).unwrap().with_external(ext_id);
+    program.verify::<Instr<LibId>>().unwrap();
+
+    let mut metered = alloc::vec::Vec::new();
+    for request in 0..3 {
+        let mut vm = Vm::<Instr<LibId>>::with(CONFIG, ());
+        vm.core.start_profiling(16);
+
+        // The external library is fetched only after the first suspension
+        let ExecSuspension::Suspended(suspended) =
+            program.exec_suspendable(vm, &(), |_| None).unwrap()
+        else {
+            panic!("request {request} must be suspended");
+        };
+        assert_eq!(suspended.missing_lib(), ext_id);
+        assert_eq!(suspended.core().ck(), Status::Ok);
+        let ca_before = suspended.core().ca();
+
+        let ExecSuspension::Complete { vm, status } =
+            program.resume(suspended, &(), |id| (id == ext_id).then_some(&ext))
+        else {
+            panic!("request {request} must complete");
+        };
+        assert_eq!(status, Status::Ok);
+        assert!(vm.core.ca() > ca_before);
+        assert_eq!(vm.core.cw_site().map(|site| site.prog_id), Some(program.entry_point().lib_id));
+
+        // Metrics
+        let profile = vm.core.profile().unwrap();
+        assert_eq!(profile.site(Site::new(ext_id, 1)).unwrap().count, 1);
+        let nops = profile.by_opcode()[&CtrlInstr::<LibId>::NOP].count;
+        assert_eq!(nops, 2);
+        metered.push(vm.core.ca());
+    }
+    // Each request is billed the same amount
+    assert!(metered.windows(2).all(|w| w[0] == w[1]));
+}
@@ -0,0 +1,106 @@
+// Reference rust implementation of AluVM (arithmetic logic unit virtual machine).
+// To find more on AluVM please check <https://aluvm.org>
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Designed in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+// Written in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+//
+// Copyright (C) 2021-2024 LNP/BP Standards Association, Switzerland.
+// Copyright (C) 2024-2025 Laboratories for Ubiquitous Deterministic Computing (UBIDECO),
+//                         Institute for Distributed and Cognitive Systems (InDCS), Switzerland.
+// Copyright (C) 2021-2025 Dr Maxim Orlovsky.
+// All rights under the above copyrights are reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use this file except
+// in compliance with the License. You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed under the License
+// is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express
+// or implied. See the License for the specific language governing permissions and limitations under
+// the License.
+
+//! Toolchain: assembles and links libraries, patches the result, validates it and compares it with
+//! the original.
+
+extern crate alloc;
+
+use aluvm::isa::{CtrlInstr, Instr};
+use aluvm::regs::Status;
+use aluvm::{aluasm, CompiledLib, CoreConfig, Lib, LibAssembler, LibId, LibOp, LibSite, Vm};
+
+#[test]
+fn toolchain() {
+    const DONE: u16 = 1;
+
+    // Assemble and link
+    let dep = CompiledLib::compile(
+        aluasm! {
+            nop;
+            not     CO;
+            ret;
+        },
+        &[],
+    )
+    .unwrap();
+    let dep_id = dep.as_lib().lib_id();
+    let code = aluasm! {
+       routine  MAIN:
+        call    dep_id, 0;
+        chk     CO;
+        jmp     DONE;
+       label    DONE:
+        stop;
+    };
+    let main = CompiledLib::compile(code.clone(), &[&dep])
+        .unwrap()
+        .into_lib();
+    let dep = dep.into_lib();
+    assert_eq!(main.libs().iter().copied().collect::<Vec<_>>(), [dep_id]);
+    main.validate::<Instr<LibId>>().unwrap();
+
+    // Streaming re-assembly of the disassembled code produces the same library
+    let disasm = main.disassemble::<Instr<LibId>>().unwrap();
+    let mut asm = LibAssembler::new();
+    for instr in &disasm {
+        asm.append(instr).unwrap();
+    }
+    assert_eq!(asm.finish().unwrap(), main);
+
+    // Patch: invert the check and attach a data table
+    let chk_pos = 1 + 4;
+    let patched = main
+        .modify([
+            LibOp::Patch(chk_pos, CtrlInstr::NotCo.into()),
+            LibOp::<Instr<LibId>>::ExtendData(b"table".to_vec()),
+        ])
+        .unwrap();
+    patched.validate::<Instr<LibId>>().unwrap();
+    assert_eq!(patched.boundary_index::<Instr<LibId>>(), main.boundary_index::<Instr<LibId>>());
+
+    // Diff against the original
+    let patched_disasm = patched.disassemble::<Instr<LibId>>().unwrap();
+    let diff = disasm
+        .iter()
+        .zip(&patched_disasm)
+        .enumerate()
+        .filter(|(_, (a, b))| a != b)
+        .map(|(no, (_, b))| (no, *b))
+        .collect::<Vec<_>>();
+    assert_eq!(diff, [(2, CtrlInstr::NotCo.into())]);
+    assert_eq!(patched.data().as_slice(), b"table");
+    assert_ne!(patched.lib_id(), main.lib_id());
+
+    // Both versions execute, with the patch observable in the registers
+    let exec = |lib: &Lib| {
+        let mut vm = Vm::<Instr<LibId>>::with(CoreConfig::default(), ());
+        let status = vm.exec(LibSite::new(lib.lib_id(), 0), &(), |id| {
+            [lib, &dep].into_iter().find(|lib| lib.lib_id() == id)
+        });
+        (status, vm.core.co())
+    };
+    assert_eq!(exec(&main), (Status::Fail, Status::Fail));
+    assert_eq!(exec(&patched), (Status::Ok, Status::Ok));
+}
@@ -0,0 +1,190 @@
+// Reference rust implementation of AluVM (arithmetic logic unit virtual machine).
+// To find more on AluVM please check <https://aluvm.org>
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Designed in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+// Written in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+//
+// Copyright (C) 2021-2024 LNP/BP Standards Association, Switzerland.
+// Copyright (C) 2024-2025 Laboratories for Ubiquitous Deterministic Computing (UBIDECO),
+//                         Institute for Distributed and Cognitive Systems (InDCS), Switzerland.
+// Copyright (C) 2021-2025 Dr Maxim Orlovsky.
+// All rights under the above copyrights are reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use this file except
+// in compliance with the License. You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed under the License
+// is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express
+// or implied. See the License for the specific language governing permissions and limitations under
+// the License.
+
+//! Plugin-style host executing libraries of instruction sets with different context types through
+//! the same code path.
+
+extern crate alloc;
+
+use alloc::collections::{BTreeMap, BTreeSet};
+use core::any::Any;
+use core::fmt::{self, Display, Formatter};
+use core::ops::RangeInclusive;
+
+use aluvm::isa::{
+    Bytecode, BytecodeRead, BytecodeWrite, CodeEofError, ExecStep, GotoTarget, Instr, Instruction,
+};
+use aluvm::regs::Status;
+use aluvm::{aluasm, Core, ExecError, Lib, LibId, LibSite, NoExt, NoRegs, Site, SiteId, Vm};
+
+/// Context of the `QUOTA` instruction set.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+struct Quota {
+    limit: u8,
+}
+
+/// Instruction set spending an amount from the quota provided by the host.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+enum QuotaInstr {
+    /// Fail unless the amount fits the quota.
+    Spend(u8),
+}
+
+impl QuotaInstr {
+    const SPEND: u8 = 0x80;
+}
+
+impl Display for QuotaInstr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            QuotaInstr::Spend(amount) => write!(f, "spend   {amount}"),
+        }
+    }
+}
+
+impl<Id: SiteId> Bytecode<Id> for QuotaInstr {
+    fn op_range() -> RangeInclusive<u8> { Self::SPEND..=Self::SPEND }
+
+    fn opcode_byte(&self) -> u8 { Self::SPEND }
+
+    fn code_byte_len(&self) -> u16 { 2 }
+
+    fn external_ref(&self) -> Option<Id> { None }
+
+    fn encode_operands<W>(&self, writer: &mut W) -> Result<(), W::Error>
+    where W: BytecodeWrite<Id> {
+        match self {
+            QuotaInstr::Spend(amount) => writer.write_byte(*amount),
+        }
+    }
+
+    fn decode_operands<R>(reader: &mut R, _opcode: u8) -> Result<Self, CodeEofError>
+    where
+        Self: Sized,
+        R: BytecodeRead<Id>,
+    {
+        reader.read_byte().map(QuotaInstr::Spend)
+    }
+}
+
+impl<Id: SiteId> Instruction<Id> for QuotaInstr {
+    const ISA_EXT: &'static [&'static str] = &[];
+
+    type Core = NoExt;
+    type Context<'ctx> = Quota;
+
+    fn is_goto_target(&self) -> bool { false }
+
+    fn local_goto_pos(&mut self) -> GotoTarget<'_> { GotoTarget::None }
+
+    fn remote_goto_pos(&mut self) -> Option<&mut Site<Id>> { None }
+
+    fn src_regs(&self) -> BTreeSet<NoRegs> { BTreeSet::new() }
+
+    fn dst_regs(&self) -> BTreeSet<NoRegs> { BTreeSet::new() }
+
+    fn op_data_bytes(&self) -> u16 { 1 }
+
+    fn ext_data_bytes(&self) -> u16 { 0 }
+
+    fn exec<const CALL_STACK_SIZE: usize>(
+        &self,
+        _: Site<Id>,
+        _: &mut Core<Id, NoExt, CALL_STACK_SIZE>,
+        context: &Self::Context<'_>,
+    ) -> ExecStep<Site<Id>> {
+        match self {
+            QuotaInstr::Spend(amount) if *amount > context.limit => ExecStep::Fail,
+            QuotaInstr::Spend(_) => ExecStep::Next,
+        }
+    }
+}
+
+/// Virtual machine of any instruction set, as it is seen by the host.
+trait HostVm {
+    fn run(&mut self, entry_point: LibSite, context: &dyn Any, lib: &Lib) -> Status;
+
+    fn last_error(&self) -> Option<ExecError>;
+}
+
+impl<Isa> HostVm for Vm<Isa>
+where
+    Isa: Instruction<LibId>,
+    Isa::Context<'static>: Any,
+{
+    fn run(&mut self, entry_point: LibSite, context: &dyn Any, lib: &Lib) -> Status {
+        self.exec_dyn(entry_point, context, |_| Some(lib))
+    }
+
+    fn last_error(&self) -> Option<ExecError> { Vm::last_error(self) }
+}
+
+/// Host with the instruction sets registered at runtime.
+#[derive(Default)]
+struct Host {
+    vms: BTreeMap<&'static str, Box<dyn HostVm>>,
+}
+
+impl Host {
+    fn register(&mut self, isa: &'static str, vm: impl HostVm + 'static) {
+        self.vms.insert(isa, Box::new(vm));
+    }
+
+    fn run(&mut self, isa: &str, lib: &Lib, context: &dyn Any) -> (Status, Option<ExecError>) {
+        let vm = self.vms.get_mut(isa).expect("unknown instruction set");
+        let status = vm.run(LibSite::new(lib.lib_id(), 0), context, lib);
+        (status, vm.last_error())
+    }
+}
+
+#[test]
+fn host() {
+    let mut host = Host::default();
+    host.register("ctrl", Vm::<Instr<LibId>>::new());
+    host.register("quota", Vm::<QuotaInstr>::new());
+
+    let ctrl = Lib::assemble::<Instr<LibId>>(&aluasm! { not CO; chk CO; }).unwrap();
+    let quota = Lib::assemble(&[QuotaInstr::Spend(2), QuotaInstr::Spend(5)]).unwrap();
+
+    assert_eq!(host.run("ctrl", &ctrl, &()), (Status::Fail, None));
+    assert_eq!(host.run("quota", &quota, &Quota { limit: 5 }), (Status::Ok, None));
+    assert_eq!(host.run("quota", &quota, &Quota { limit: 4 }), (Status::Fail, None));
+}
+
+#[test]
+fn context_mismatch() {
+    let quota = Lib::assemble(&[QuotaInstr::Spend(0)]).unwrap();
+    let entry = LibSite::new(quota.lib_id(), 0);
+
+    let mut vm = Vm::<QuotaInstr>::new();
+    assert_eq!(vm.exec_dyn(entry, &(), |_| Some(&quota)), Status::Fail);
+    assert_eq!(vm.last_error(), Some(ExecError::ContextMismatch(entry)));
+    // No instruction is executed
+    assert_eq!(vm.core.ci(), 0);
+
+    vm.reset();
+    assert_eq!(vm.exec_dyn(entry, &Quota { limit: 0 }, |_| Some(&quota)), Status::Ok);
+    assert_eq!(vm.last_error(), None);
+    assert_eq!(vm.core.ci(), 1);
+}
@@ -24,9 +24,15 @@
 
 extern crate alloc;
 
-use aluvm::isa::{CtrlInstr, Instr};
-use aluvm::regs::Status;
-use aluvm::{aluasm, CompiledLib, CoreConfig, LibId, LibSite, Vm};
+use std::collections::BTreeMap;
+
+use aluvm::isa::{Bytecode, CtrlInstr, ExecStep, Instr, Instruction, ReservedInstr};
+use aluvm::regs::{Status, CALL_STACK_SIZE_MAX, CALL_STACK_SIZE_SMALL};
+use aluvm::{
+    aluasm, CompiledLib, CoreConfig, ExecError, ExecReport, ExecSuspension, FailureReason,
+    HaltReason, Lib, LibId, LibSite, ProfileData, Site, SmallStackVm, StepError, StepOutcome,
+    Stepper, TerminationCause, UnknownInstrPolicy, Vm, VmRun,
+};
 
 fn code() -> Vec<Instr<LibId>> {
     const MAIN: u16 = 0;
@@ -75,13 +81,469 @@ fn run() {
     disasm[17] = CtrlInstr::Jmp { pos: 2 }.into();
     assert_eq!(disasm, code);
 
-    let mut vm_main =
-        Vm::<Instr<LibId>>::with(CoreConfig { halt: false, complexity_lim: None }, ());
+    let mut vm_main = Vm::<Instr<LibId>>::with(
+        CoreConfig {
+            halt: false,
+            complexity_lim: None,
+            complexity_warn: None,
+            jump_lim: None,
+            instr_lim: None,
+            cs_integrity: false,
+            unknown_instr: UnknownInstrPolicy::Fail,
+        },
+        (),
+    );
     let resolver = |_: LibId| Some(&lib);
     let status = vm_main.exec(LibSite::new(lib.lib_id(), 0), &(), resolver);
     assert_eq!(status, Status::Ok);
 }
 
+#[test]
+fn complexity_warn() {
+    let code = aluasm! {
+        nop;
+        not     CO;
+        not     CO;
+        not     CO;
+        stop;
+    };
+    let lib = Lib::assemble(&code).unwrap();
+    let lib_id = lib.lib_id();
+    let resolver = |_: LibId| Some(&lib);
+
+    let config = CoreConfig {
+        halt: true,
+        complexity_lim: None,
+        complexity_warn: Some(4000),
+        jump_lim: None,
+        instr_lim: None,
+        cs_integrity: false,
+        unknown_instr: UnknownInstrPolicy::Fail,
+    };
+    let mut vm = Vm::<Instr<LibId>>::with(config, ());
+    let status = vm.exec(LibSite::new(lib_id, 0), &(), resolver);
+    assert_eq!(status, Status::Ok);
+    assert_eq!(vm.core.cw_site(), Some(Site::new(lib_id, 2)));
+
+    let config = CoreConfig {
+        halt: true,
+        complexity_lim: Some(4000),
+        complexity_warn: None,
+        jump_lim: None,
+        instr_lim: None,
+        cs_integrity: false,
+        unknown_instr: UnknownInstrPolicy::Fail,
+    };
+    let mut vm = Vm::<Instr<LibId>>::with(config, ());
+    let status = vm.exec(LibSite::new(lib_id, 0), &(), resolver);
+    assert_eq!(status, Status::Fail);
+    assert_eq!(vm.core.cw_site(), None);
+
+    let config = CoreConfig {
+        halt: true,
+        complexity_lim: Some(4000),
+        complexity_warn: Some(4000),
+        jump_lim: None,
+        instr_lim: None,
+        cs_integrity: false,
+        unknown_instr: UnknownInstrPolicy::Fail,
+    };
+    let mut vm = Vm::<Instr<LibId>>::with(config, ());
+    let status = vm.exec(LibSite::new(lib_id, 0), &(), resolver);
+    assert_eq!(status, Status::Fail);
+    assert_eq!(vm.core.cw_site(), Some(Site::new(lib_id, 2)));
+}
+
+#[test]
+fn call_stack_integrity() {
+    let lib = CompiledLib::compile(code(), &[]).unwrap().into_lib();
+    let config = CoreConfig {
+        halt: false,
+        complexity_lim: None,
+        complexity_warn: None,
+        jump_lim: None,
+        instr_lim: None,
+        cs_integrity: true,
+        unknown_instr: UnknownInstrPolicy::Fail,
+    };
+    let mut vm = Vm::<Instr<LibId>>::with(config, ());
+    let resolver = |_: LibId| Some(&lib);
+    let status = vm.exec(LibSite::new(lib.lib_id(), 0), &(), resolver);
+    assert_eq!(status, Status::Ok);
+    assert!(vm.core.cs_integrity());
+    assert_eq!(vm.core.cs_fault(), None);
+}
+
+#[test]
+fn resume_after_host_abort() {
+    let dep = CompiledLib::compile(
+        aluasm! {
+            nop;
+            not     CO;
+            fail    CK;
+            mov     CO, CK;
+            ret;
+        },
+        &[],
+    )
+    .unwrap();
+    let dep_id = dep.as_lib().lib_id();
+    let main_code = aluasm! {
+        nop;
+        not     CO;
+        call    dep_id, 0;
+        not     CO;
+        stop;
+    };
+    let main = CompiledLib::compile(main_code.clone(), &[&dep]).unwrap();
+    let (main, dep) = (main.into_lib(), dep.into_lib());
+    let main_id = main.lib_id();
+    let entry = LibSite::new(main_id, 0);
+    let config = CoreConfig { halt: false, ..CoreConfig::default() };
+
+    let resolver = |id: LibId| [&main, &dep].into_iter().find(|lib| lib.lib_id() == id);
+    let mut vm = Vm::<Instr<LibId>>::with(config, ());
+    let status = vm.exec(entry, &(), resolver);
+
+    let flaky_resolver = |id: LibId| (id == main_id).then_some(&main);
+    let ExecSuspension::Suspended(suspended) =
+        Vm::<Instr<LibId>>::with(config, ()).exec_suspendable(entry, &(), flaky_resolver)
+    else {
+        panic!("execution must be suspended");
+    };
+    assert_eq!(suspended.missing_lib(), dep_id);
+    assert_eq!(suspended.site(), LibSite::new(dep_id, 0));
+    // No effects of the aborted instruction must be applied
+    let ca = main_code[..3].iter().map(Instr::complexity).sum::<u64>();
+    assert_eq!(suspended.core().ca(), ca);
+    assert_eq!(suspended.core().cf(), 0);
+    assert_eq!(suspended.core().ck(), Status::Ok);
+    assert_eq!(suspended.core().cp(), 1);
+
+    let ExecSuspension::Complete { vm: resumed, status: resumed_status } =
+        Vm::resume_after_host_abort(suspended, &(), resolver)
+    else {
+        panic!("execution must complete");
+    };
+    assert_eq!(resumed_status, status);
+    assert_eq!(resumed.core.ca(), vm.core.ca());
+    assert_eq!(resumed.core.cy(), vm.core.cy());
+    assert_eq!(resumed.core.cf(), vm.core.cf());
+    assert_eq!(resumed.core.co(), vm.core.co());
+    assert_eq!(resumed.core.cp(), 0);
+}
+
+#[test]
+fn exec_checked() {
+    let missing = LibId::from([0xA5u8; 32]);
+    let code = vec![
+        CtrlInstr::Nop.into(),
+        CtrlInstr::Call { site: Site::new(missing, 0) }.into(),
+        CtrlInstr::NotCo.into(),
+        CtrlInstr::Stop.into(),
+    ];
+    let lib = Lib::assemble::<Instr<LibId>>(&code).unwrap();
+    let entry = LibSite::new(lib.lib_id(), 0);
+    let resolver = |id: LibId| (id == lib.lib_id()).then_some(&lib);
+    let err = ExecError::LibAbsent(missing, LibSite::new(missing, 0));
+
+    // The execution halts on an absent library independently of the `CH` register value
+    for halt in [true, false] {
+        let mut vm = Vm::<Instr<LibId>>::with(CoreConfig { halt, ..CoreConfig::default() }, ());
+        assert_eq!(vm.exec_checked(entry, &(), resolver), Err(err));
+        assert_eq!(vm.last_error(), Some(err));
+        assert_eq!(vm.core.ck(), Status::Fail);
+        assert_eq!(vm.core.cf(), 1);
+        // The instruction following the call is not executed
+        assert_eq!(vm.core.co(), Status::Ok);
+
+        vm.reset();
+        assert_eq!(vm.last_error(), None);
+    }
+
+    // Transfer of the execution outside the library code
+    let code = vec![CtrlInstr::Nop.into(), CtrlInstr::Jmp { pos: 0x1000 }.into()];
+    let lib = Lib::assemble::<Instr<LibId>>(&code).unwrap();
+    let site = LibSite::new(lib.lib_id(), 0x1000);
+    let mut vm = Vm::<Instr<LibId>>::new();
+    assert_eq!(
+        vm.exec_checked(LibSite::new(lib.lib_id(), 0), &(), |_| Some(&lib)),
+        Err(ExecError::CodeOverrun(site))
+    );
+    assert_eq!(vm.core.ck(), Status::Fail);
+
+    // A new execution clears the previous error, but not the registers
+    let lib = Lib::assemble::<Instr<LibId>>(&[CtrlInstr::Stop.into()]).unwrap();
+    let entry = LibSite::new(lib.lib_id(), 0);
+    assert_eq!(vm.exec_checked(entry, &(), |_| Some(&lib)), Ok(Status::Fail));
+    assert_eq!(vm.last_error(), None);
+}
+
+#[test]
+fn call_stack_high_water() {
+    const ENTRY: u16 = 0;
+    const INNER: u16 = 1;
+    let dep = CompiledLib::compile(
+        aluasm! {
+           routine ENTRY:
+            call    INNER;
+            ret;
+           routine INNER:
+            ret;
+        },
+        &[],
+    )
+    .unwrap();
+    let dep_id = dep.as_lib().lib_id();
+
+    const MAIN: u16 = 0;
+    const FIRST: u16 = 1;
+    const SECOND: u16 = 2;
+    let main = CompiledLib::compile(
+        aluasm! {
+           routine MAIN:
+            call    FIRST;
+            call    FIRST;
+            stop;
+           routine FIRST:
+            call    SECOND;
+            ret;
+           routine SECOND:
+            call    dep_id, ENTRY;
+            ret;
+        },
+        &[&dep],
+    )
+    .unwrap();
+    let entry = main.routine(MAIN);
+    let (main, dep) = (main.into_lib(), dep.into_lib());
+
+    let resolver = |id: LibId| [&main, &dep].into_iter().find(|lib| lib.lib_id() == id);
+    let mut vm = Vm::<Instr<LibId>>::new();
+    let status = vm.exec(entry, &(), resolver);
+    assert_eq!(status, Status::Ok);
+    assert_eq!(vm.core.cs_high_water(), 4);
+    assert_eq!(vm.core.xcs_high_water(), 1);
+    assert_eq!(vm.core.cp(), 0);
+    assert_eq!(vm.core.xcp(), 0);
+}
+
+#[test]
+fn exec_stats() {
+    const MAIN: u16 = 0;
+    const FIRST: u16 = 1;
+    const SECOND: u16 = 2;
+    const THIRD: u16 = 3;
+    let lib = CompiledLib::compile(
+        aluasm! {
+           routine MAIN:
+            call    FIRST;
+            call    FIRST;
+            stop;
+           routine FIRST:
+            call    SECOND;
+            ret;
+           routine SECOND:
+            call    THIRD;
+            ret;
+           routine THIRD:
+            ret;
+        },
+        &[],
+    )
+    .unwrap();
+    let entry = lib.routine(MAIN);
+    let third = lib.routine(THIRD);
+    let lib = lib.into_lib();
+
+    let mut vm = Vm::<Instr<LibId>>::new();
+    let (status, stats) = vm.exec_with_stats(entry, &(), |_| Some(&lib));
+    assert_eq!(status, Status::Ok);
+    assert_eq!(stats.peak_depth, 3);
+    assert_eq!(stats.peak_depth, vm.core.cs_high_water());
+    // Each routine starts with `nop`; the first routine is called twice, each time performing
+    // three calls and three returns
+    assert_eq!(stats.instructions, 20);
+    assert_eq!(stats.jumps, 12);
+    assert_eq!(stats.cy, 12);
+    assert_eq!(stats.ca, vm.core.ca());
+    assert!(stats.max_complexity > 0);
+    assert_eq!(stats.max_complexity_site, Some(Site::new(lib.lib_id(), 1)));
+
+    let mut plain = Vm::<Instr<LibId>>::new();
+    assert_eq!(plain.exec(entry, &(), |_| Some(&lib)), status);
+    assert_eq!(plain.core.ca(), stats.ca);
+
+    // Statistics don't include the execution preceding the call
+    let (_, again) = vm.exec_with_stats(entry, &(), |_| Some(&lib));
+    assert_eq!(again.instructions, 20);
+    assert_eq!(again.jumps, 12);
+    assert_eq!(again.cy, 24);
+    assert_eq!(again.ca, stats.ca * 2);
+    assert_eq!(again.peak_depth, 3);
+
+    // Peak depth doesn't include the depth reached by the preceding executions
+    let (_, shallow) = vm.exec_with_stats(third, &(), |_| Some(&lib));
+    assert_eq!(shallow.peak_depth, 0);
+    assert_eq!(vm.core.cs_high_water(), 3);
+}
+
+#[test]
+fn call_stack_overflow() {
+    // Each frame calls the next one and returns; the innermost frame stops the execution
+    let nested = |depth: u16| {
+        let mut code = Vec::<Instr<LibId>>::new();
+        for no in 0..depth {
+            code.push(CtrlInstr::Fn { pos: (no + 1) * 4 }.into());
+            code.push(CtrlInstr::Ret.into());
+        }
+        code.push(CtrlInstr::Stop.into());
+        Lib::assemble(&code).unwrap()
+    };
+
+    let lib = nested(CALL_STACK_SIZE_MAX);
+    let entry = LibSite::new(lib.lib_id(), 0);
+    let mut vm = Vm::<Instr<LibId>>::new();
+    assert_eq!(vm.exec(entry, &(), |_| Some(&lib)), Status::Ok);
+    assert_eq!(vm.core.cs_high_water(), CALL_STACK_SIZE_MAX);
+    assert_eq!(vm.core.cp(), CALL_STACK_SIZE_MAX);
+    assert_eq!(vm.core.call_stack().len(), CALL_STACK_SIZE_MAX as usize);
+    assert_eq!(vm.core.call_stack()[1], Site::new(lib.lib_id(), 4));
+    assert_eq!(vm.core.cs_overflow(), None);
+
+    let lib = nested(CALL_STACK_SIZE_MAX + 1);
+    let entry = LibSite::new(lib.lib_id(), 0);
+    let mut vm = Vm::<Instr<LibId>>::new();
+    assert_eq!(vm.exec(entry, &(), |_| Some(&lib)), Status::Fail);
+    assert_eq!(vm.core.cs_high_water(), CALL_STACK_SIZE_MAX);
+    assert_eq!(vm.core.cs_overflow(), Some(Site::new(lib.lib_id(), CALL_STACK_SIZE_MAX * 4)));
+    assert_eq!(vm.core.cf(), 1);
+}
+
+#[test]
+fn small_call_stack() {
+    // Each frame calls the next one and returns, such that the call stack unwinds fully
+    let nested = |depth: u16| {
+        let mut code = Vec::<Instr<LibId>>::new();
+        for no in 0..depth {
+            code.push(CtrlInstr::Fn { pos: (no + 1) * 4 }.into());
+            code.push(if no == 0 { CtrlInstr::Stop } else { CtrlInstr::Ret }.into());
+        }
+        code.push(CtrlInstr::Ret.into());
+        Lib::assemble(&code).unwrap()
+    };
+    assert_eq!(Vm::<Instr<LibId>, 2>::CALL_STACK_SIZE, 2);
+    assert_eq!(Vm::<Instr<LibId>>::CALL_STACK_SIZE, CALL_STACK_SIZE_MAX as usize);
+    assert_eq!(SmallStackVm::<Instr<LibId>>::CALL_STACK_SIZE, CALL_STACK_SIZE_SMALL as usize);
+
+    let lib = nested(2);
+    let entry = LibSite::new(lib.lib_id(), 0);
+    let mut vm = Vm::<Instr<LibId>, 2>::new();
+    assert_eq!(vm.exec(entry, &(), |_| Some(&lib)), Status::Ok);
+    assert_eq!(vm.core.cs_high_water(), 2);
+    assert_eq!(vm.core.cp(), 0);
+    assert_eq!(vm.core.cs_overflow(), None);
+
+    // The third nested call overflows the call stack
+    let lib = nested(3);
+    let entry = LibSite::new(lib.lib_id(), 0);
+    let mut vm = Vm::<Instr<LibId>, 2>::new();
+    assert_eq!(vm.exec(entry, &(), |_| Some(&lib)), Status::Fail);
+    assert_eq!(vm.core.cs_high_water(), 2);
+    assert_eq!(vm.core.cp(), 2);
+    assert_eq!(vm.core.cs_overflow(), Some(Site::new(lib.lib_id(), 8)));
+    assert_eq!(vm.core.cf(), 1);
+    assert_eq!(vm.last_error(), None);
+
+    // The same program fits the default call stack
+    let mut vm = Vm::<Instr<LibId>>::new();
+    assert_eq!(vm.exec(entry, &(), |_| Some(&lib)), Status::Ok);
+    assert_eq!(vm.core.cs_high_water(), 3);
+}
+
+/// Runs the program both with [`Vm::exec`] and instruction by instruction with [`Vm::step`],
+/// checking that both execute the same number of instructions.
+fn exec_and_step<'lib>(
+    entry: LibSite,
+    resolver: impl Fn(LibId) -> Option<&'lib Lib> + Copy,
+) -> Vm<Instr<LibId>> {
+    let mut vm = Vm::<Instr<LibId>>::new();
+    let status = vm.exec(entry, &(), resolver);
+
+    let mut stepped = Vm::<Instr<LibId>>::new();
+    let mut site = Some(entry);
+    while let Some(current) = site {
+        let step = stepped.step(current, &(), resolver).unwrap();
+        site = stepped.next_site(current, step, resolver);
+    }
+    assert_eq!(stepped.core.ck(), status);
+    assert_eq!(stepped.core.ci(), vm.core.ci());
+    assert_eq!(stepped.core.cy(), vm.core.cy());
+    assert_eq!(stepped.core.cp(), vm.core.cp());
+    vm
+}
+
+#[test]
+fn call_return() {
+    // Local call: the calling instruction is not executed again after the return
+    let code = vec![CtrlInstr::Fn { pos: 4 }.into(), CtrlInstr::Stop.into(), CtrlInstr::Ret.into()];
+    let lib = Lib::assemble::<Instr<LibId>>(&code).unwrap();
+    let vm = exec_and_step(LibSite::new(lib.lib_id(), 0), |_| Some(&lib));
+    assert_eq!(vm.core.ck(), Status::Ok);
+    assert_eq!(vm.core.ci(), 3);
+    assert_eq!(vm.core.cy(), 2);
+    assert_eq!(vm.core.cp(), 0);
+    assert_eq!(vm.termination(), Some(TerminationCause::ExplicitStop));
+
+    // Cross-library call returning into the middle of the caller code
+    let dep =
+        Lib::assemble::<Instr<LibId>>(&[CtrlInstr::Nop.into(), CtrlInstr::Ret.into()]).unwrap();
+    let code = vec![
+        CtrlInstr::Call { site: Site::new(dep.lib_id(), 0) }.into(),
+        CtrlInstr::Call { site: Site::new(dep.lib_id(), 1) }.into(),
+        CtrlInstr::Stop.into(),
+    ];
+    let main = Lib::assemble::<Instr<LibId>>(&code).unwrap();
+    let resolver = |id: LibId| [&main, &dep].into_iter().find(|lib| lib.lib_id() == id);
+    let vm = exec_and_step(LibSite::new(main.lib_id(), 0), resolver);
+    assert_eq!(vm.core.ck(), Status::Ok);
+    assert_eq!(vm.core.ci(), 6);
+    assert_eq!(vm.core.cy(), 4);
+    assert_eq!(vm.core.cp(), 0);
+    assert_eq!(vm.core.xcp(), 0);
+    assert_eq!(vm.core.xcs_high_water(), 1);
+    assert_eq!(vm.termination(), Some(TerminationCause::ExplicitStop));
+
+    // Return with an empty call stack stops the program
+    let lib =
+        Lib::assemble::<Instr<LibId>>(&[CtrlInstr::Ret.into(), CtrlInstr::Stop.into()]).unwrap();
+    let vm = exec_and_step(LibSite::new(lib.lib_id(), 0), |_| Some(&lib));
+    assert_eq!(vm.core.ck(), Status::Ok);
+    assert_eq!(vm.core.ci(), 1);
+    assert_eq!(vm.termination(), Some(TerminationCause::ReturnFromMain));
+}
+
+#[test]
+fn call_stack_unwinding() {
+    // Each frame calls the next one down to the maximal depth, and then all of them return
+    let mut code = Vec::<Instr<LibId>>::new();
+    for no in 0..CALL_STACK_SIZE_MAX {
+        code.push(CtrlInstr::Fn { pos: (no + 1) * 4 }.into());
+        code.push(if no == 0 { CtrlInstr::Stop } else { CtrlInstr::Ret }.into());
+    }
+    code.push(CtrlInstr::Ret.into());
+    let lib = Lib::assemble(&code).unwrap();
+
+    let vm = exec_and_step(LibSite::new(lib.lib_id(), 0), |_| Some(&lib));
+    assert_eq!(vm.core.ck(), Status::Ok);
+    assert_eq!(vm.core.cs_high_water(), CALL_STACK_SIZE_MAX);
+    assert_eq!(vm.core.cs_overflow(), None);
+    assert_eq!(vm.core.cp(), 0);
+    assert_eq!(vm.core.ci(), 2 * CALL_STACK_SIZE_MAX as u64 + 1);
+    assert_eq!(vm.core.cy(), 2 * CALL_STACK_SIZE_MAX);
+    assert_eq!(vm.termination(), Some(TerminationCause::ExplicitStop));
+}
+
 #[test]
 fn print_disassemble() {
     let lib = CompiledLib::compile(code(), &[]).unwrap().into_lib();
@@ -112,3 +574,1027 @@ offset 000032: ret
 "
     );
 }
+
+#[test]
+fn pretty_print() {
+    let verifier = LibId::from([0xA5u8; 32]);
+    let unknown = LibId::from([0x5Au8; 32]);
+    let lib = Lib::assemble::<Instr<LibId>>(&aluasm! {
+        nop;
+        chk     CO;
+        jif     CO, 13;
+        call    verifier, 16;
+        jmp     unknown, 0;
+        not     CO;
+        jmp     1;
+        ret;
+        jmp     12; // not an instruction boundary
+        jmp     24;
+    })
+    .unwrap();
+    let symbols = BTreeMap::from([(verifier, "verifier".to_string())]);
+    let mut buf = Vec::new();
+    lib.pretty_print::<Instr<_>>(&mut buf, &symbols).unwrap();
+    assert_eq!(
+        String::from_utf8(buf).unwrap(),
+        format!(
+            "offset 000000: nop
+loc_0001:
+offset 000001: chk     CO
+offset 000002: jif     CO, 13                  ; loc_000D
+offset 000005: call    verifier@0010#h
+offset 000009: jmp     {unknown}@0000#h
+loc_000D:
+offset 000013: not     CO
+offset 000014: jmp     1                       ; loc_0001
+offset 000017: ret
+offset 000018: jmp     12
+offset 000021: jmp     24                      ; loc_0018
+loc_0018:
+"
+        )
+    );
+}
+
+#[test]
+fn profile_loop() {
+    const LOOP: u16 = 1;
+
+    let code = aluasm! {
+        nop;
+        chk     CO;
+        chk     CK;
+       label    LOOP:
+        not     CO;
+        chk     CK;
+        jmp     LOOP;
+    };
+    let lib = CompiledLib::compile(code, &[]).unwrap().into_lib();
+    let lib_id = lib.lib_id();
+    let config = CoreConfig { complexity_lim: Some(1_000_000), ..CoreConfig::default() };
+    let run = || {
+        let mut vm = Vm::<Instr<LibId>>::with(config, ());
+        vm.core.start_profiling(16);
+        let status = vm.exec(LibSite::new(lib_id, 0), &(), |_| Some(&lib));
+        assert_eq!(status, Status::Fail);
+        vm.core.take_profile().unwrap()
+    };
+
+    let profile = run();
+    assert_eq!(profile, run());
+    assert!(!profile.is_capped());
+    let top = profile.top_n(3);
+    let loop_sites = [3, 4, 5, 6].map(|offset| Site::new(lib_id, offset));
+    assert!(top.iter().all(|(site, _)| loop_sites.contains(site)));
+    let jmp = profile.site(Site::new(lib_id, 6)).unwrap();
+    assert_eq!(jmp.jumps, jmp.count);
+    let prelude = profile.site(Site::new(lib_id, 0)).unwrap();
+    assert_eq!(prelude.count, 1);
+    assert!(top[0].1.complexity > 100 * prelude.complexity);
+}
+
+#[test]
+fn profile_site_cap() {
+    let mut code = vec![CtrlInstr::Nop.into(); 100];
+    code.push(CtrlInstr::Stop.into());
+    let lib = Lib::assemble::<Instr<LibId>>(&code).unwrap();
+    let mut vm = Vm::<Instr<LibId>>::with(CoreConfig::default(), ());
+    vm.core.start_profiling(10);
+    vm.exec(LibSite::new(lib.lib_id(), 0), &(), |_| Some(&lib));
+
+    let profile = vm.core.profile().unwrap();
+    assert!(profile.is_capped());
+    assert_eq!(profile.len(), 10);
+    assert_eq!(profile.overflow().count, 91);
+    let by_opcode = profile.by_opcode();
+    assert_eq!(by_opcode[&CtrlInstr::<LibId>::NOP].count, 100);
+    assert_eq!(by_opcode[&CtrlInstr::<LibId>::STOP].count, 1);
+}
+
+#[test]
+fn single_step() {
+    let dep = CompiledLib::compile(
+        aluasm! {
+            nop;
+            not     CO;
+            fail    CK;
+            mov     CO, CK;
+            ret;
+        },
+        &[],
+    )
+    .unwrap();
+    let dep_id = dep.as_lib().lib_id();
+    let main = CompiledLib::compile(
+        aluasm! {
+            nop;
+            not     CO;
+            call    dep_id, 0;
+            not     CO;
+            stop;
+        },
+        &[&dep],
+    )
+    .unwrap();
+    let local = CompiledLib::compile(code(), &[]).unwrap().into_lib();
+    let (main, dep) = (main.into_lib(), dep.into_lib());
+    let libs = [&main, &dep, &local].map(|lib| (lib.lib_id(), lib));
+    let resolver = |id: LibId| {
+        libs.iter()
+            .find(|(lib_id, _)| *lib_id == id)
+            .map(|(_, lib)| *lib)
+    };
+
+    for entry in [LibSite::new(main.lib_id(), 0), LibSite::new(local.lib_id(), 0)] {
+        for halt in [false, true] {
+            let config = CoreConfig { halt, ..CoreConfig::default() };
+            let mut vm = Vm::<Instr<LibId>>::with(config, ());
+            let status = vm.exec(entry, &(), resolver);
+
+            let mut stepped = Vm::<Instr<LibId>>::with(config, ());
+            let mut site = entry;
+            let mut steps = 0usize;
+            loop {
+                let step = stepped.step(site, &(), resolver).unwrap();
+                steps += 1;
+                let Some(next) = stepped.next_site(site, step, resolver) else {
+                    break;
+                };
+                site = next;
+            }
+            assert!(steps > 1);
+            assert_eq!(stepped.core.ck(), status);
+            assert_eq!(stepped.core.co(), vm.core.co());
+            assert_eq!(stepped.core.cf(), vm.core.cf());
+            assert_eq!(stepped.core.ca(), vm.core.ca());
+            assert_eq!(stepped.core.cp(), vm.core.cp());
+        }
+    }
+}
+
+#[test]
+fn single_step_errors() {
+    let lib = Lib::assemble::<Instr<LibId>>(&aluasm! {
+        not     CO;
+        stop;
+    })
+    .unwrap();
+    let lib_id = lib.lib_id();
+    let resolver = |id: LibId| (id == lib_id).then_some(&lib);
+
+    let mut vm = Vm::<Instr<LibId>>::new();
+    let step = vm.step(LibSite::new(lib_id, 0), &(), resolver).unwrap();
+    assert_eq!(step, ExecStep::Next);
+    assert_eq!(vm.core.co(), Status::Fail);
+    let next = vm.next_site(LibSite::new(lib_id, 0), step, resolver);
+    assert_eq!(next, Some(LibSite::new(lib_id, 1)));
+    // Moving past the end of the code halts the execution
+    assert_eq!(vm.next_site(LibSite::new(lib_id, 1), ExecStep::Next, resolver), None);
+    assert_eq!(vm.step(next.unwrap(), &(), resolver), Ok(ExecStep::Stop));
+    assert_eq!(vm.core.ck(), Status::Ok);
+
+    let site = LibSite::new(lib_id, 2);
+    assert_eq!(vm.step(site, &(), resolver), Err(StepError::OutOfCode(site)));
+    assert_eq!(vm.core.ck(), Status::Fail);
+
+    let missing = LibId::from([0xA5u8; 32]);
+    let mut vm = Vm::<Instr<LibId>>::new();
+    assert_eq!(vm.step(LibSite::new(missing, 0), &(), resolver), Err(StepError::NoLib(missing)));
+    assert_eq!(vm.core.ck(), Status::Fail);
+
+    let config = CoreConfig { complexity_lim: Some(1), ..CoreConfig::default() };
+    let mut vm = Vm::<Instr<LibId>>::with(config, ());
+    let site = LibSite::new(lib_id, 0);
+    assert_eq!(vm.step(site, &(), resolver), Err(StepError::ComplexityOverflow(site)));
+    assert_eq!(vm.core.ck(), Status::Fail);
+}
+
+#[test]
+fn stepper() {
+    let dep = CompiledLib::compile(
+        aluasm! {
+            nop;
+            not     CO;
+            ret;
+        },
+        &[],
+    )
+    .unwrap();
+    let dep_id = dep.as_lib().lib_id();
+    let main = CompiledLib::compile(
+        aluasm! {
+            call    dep_id, 0;
+            chk     CO;
+            stop;
+        },
+        &[&dep],
+    )
+    .unwrap();
+    let (main, dep) = (main.into_lib(), dep.into_lib());
+    let main_id = main.lib_id();
+    let resolver = |id: LibId| [&main, &dep].into_iter().find(|lib| lib.lib_id() == id);
+    let entry = LibSite::new(main_id, 0);
+
+    let mut vm = Vm::<Instr<LibId>>::new();
+    let status = vm.exec(entry, &(), resolver);
+    assert_eq!(status, Status::Fail);
+
+    let mut stepped = Vm::<Instr<LibId>>::new();
+    let mut stepper = Stepper::new(entry);
+    let mut outcomes = vec![];
+    while !stepper.is_halted() {
+        outcomes.push(stepper.step(&mut stepped, &(), resolver).unwrap());
+    }
+    assert_eq!(outcomes, vec![
+        StepOutcome::Switch(LibSite::new(dep_id, 0)),
+        StepOutcome::Continue(LibSite::new(dep_id, 1)),
+        StepOutcome::Continue(LibSite::new(dep_id, 2)),
+        StepOutcome::Switch(LibSite::new(main_id, 4)),
+        StepOutcome::Halted(status),
+    ]);
+    assert_eq!(stepper.site(), None);
+    assert_eq!(stepper.step(&mut stepped, &(), resolver), Ok(StepOutcome::Halted(status)));
+    assert_eq!(stepped.core.ci(), vm.core.ci());
+    assert_eq!(stepped.core.cy(), vm.core.cy());
+    assert_eq!(stepped.core.ca(), vm.core.ca());
+
+    // The stepper halts on an instruction which can't be executed
+    let missing = LibId::from([0xA5u8; 32]);
+    let mut vm = Vm::<Instr<LibId>>::new();
+    let mut stepper = Stepper::new(LibSite::new(missing, 0));
+    assert_eq!(stepper.step(&mut vm, &(), resolver), Err(StepError::NoLib(missing)));
+    assert!(stepper.is_halted());
+    assert_eq!(stepper.step(&mut vm, &(), resolver), Ok(StepOutcome::Halted(Status::Fail)));
+}
+
+#[test]
+fn stepper_matches_exec() {
+    let dep = Lib::assemble::<Instr<LibId>>(&aluasm! { not CO; ret; }).unwrap();
+    let dep_site = Site::new(dep.lib_id(), 0);
+    let programs: [Vec<Instr<LibId>>; 8] = [
+        // Falling off the end of the code
+        aluasm! { nop; not CO; },
+        // Jumping right past the last instruction
+        vec![CtrlInstr::Nop.into(), CtrlInstr::Jmp { pos: 4 }.into()],
+        aluasm! { nop; stop; nop; },
+        aluasm! { fail CK; nop; },
+        aluasm! { nop; ret; },
+        // Returning from a call placed at the end of the code
+        vec![CtrlInstr::Nop.into(), CtrlInstr::Call { site: dep_site }.into()],
+        vec![CtrlInstr::Call { site: dep_site }.into(), CtrlInstr::Stop.into()],
+        // Infinite loop, exceeding either the jump or the complexity limit
+        vec![CtrlInstr::Nop.into(), CtrlInstr::Jmp { pos: 0 }.into()],
+    ];
+    let configs = [
+        CoreConfig { complexity_lim: Some(1_000_000), ..CoreConfig::default() },
+        CoreConfig { complexity_lim: Some(100_000), ..CoreConfig::default() },
+        CoreConfig {
+            halt: false,
+            complexity_lim: Some(1_000_000),
+            jump_lim: Some(20),
+            ..CoreConfig::default()
+        },
+    ];
+    for code in programs {
+        let lib = Lib::assemble(&code).unwrap();
+        let resolver = |id: LibId| [&lib, &dep].into_iter().find(|lib| lib.lib_id() == id);
+        let entry = LibSite::new(lib.lib_id(), 0);
+        for config in configs {
+            let mut vm = Vm::<Instr<LibId>>::with(config, ());
+            let status = vm.exec(entry, &(), resolver);
+
+            let mut stepped = Vm::<Instr<LibId>>::with(config, ());
+            let mut stepper = Stepper::new(entry);
+            let mut outcome = stepper.step(&mut stepped, &(), resolver);
+            while !stepper.is_halted() {
+                outcome = stepper.step(&mut stepped, &(), resolver);
+            }
+            let details = format!("{code:?} with {config:?}");
+            assert!(vm.termination().is_some(), "{details}");
+            match outcome {
+                Ok(outcome) => assert_eq!(outcome, StepOutcome::Halted(status), "{details}"),
+                Err(_) => assert_eq!(stepped.core.ck(), status, "{details}"),
+            }
+            assert_eq!(stepped.termination(), vm.termination(), "{details}");
+            assert_eq!(stepped.halt_reason(), vm.halt_reason(), "{details}");
+            assert_eq!(stepped.core.ci(), vm.core.ci(), "{details}");
+            assert_eq!(stepped.core.ca(), vm.core.ca(), "{details}");
+            assert_eq!(stepped.core.cy(), vm.core.cy(), "{details}");
+            assert_eq!(stepped.core.co(), vm.core.co(), "{details}");
+        }
+    }
+}
+
+#[test]
+fn stepper_after_failed_exec() {
+    let missing = Site::new(LibId::from([0xA5u8; 32]), 0);
+    let failing = [
+        Lib::assemble::<Instr<LibId>>(&aluasm! { fail CK; }).unwrap(),
+        Lib::assemble::<Instr<LibId>>(&[CtrlInstr::Call { site: missing }.into()]).unwrap(),
+    ];
+    let lib = Lib::assemble::<Instr<LibId>>(&aluasm! { nop; stop; }).unwrap();
+    let entry = LibSite::new(lib.lib_id(), 0);
+    for failing in failing {
+        let resolver = |id: LibId| [&lib, &failing].into_iter().find(|lib| lib.lib_id() == id);
+        let failing = LibSite::new(failing.lib_id(), 0);
+
+        let mut vm = Vm::<Instr<LibId>>::new();
+        assert_eq!(vm.exec(failing, &(), resolver), Status::Fail);
+        vm.core.reset_ck();
+        assert_eq!(vm.exec(entry, &(), resolver), Status::Ok);
+
+        let mut stepped = Vm::<Instr<LibId>>::new();
+        assert_eq!(stepped.exec(failing, &(), resolver), Status::Fail);
+        stepped.core.reset_ck();
+        let mut stepper = Stepper::new(entry);
+        while !stepper.is_halted() {
+            stepper.step(&mut stepped, &(), resolver).unwrap();
+        }
+
+        assert_eq!(vm.termination(), Some(TerminationCause::ExplicitStop));
+        assert_eq!(stepped.termination(), vm.termination());
+        assert_eq!(stepped.last_error(), vm.last_error());
+        assert_eq!(stepped.halt_reason(), vm.halt_reason());
+    }
+}
+
+#[test]
+fn breakpoints() {
+    let dep = CompiledLib::compile(
+        aluasm! {
+            nop;
+            not     CO;
+            ret;
+        },
+        &[],
+    )
+    .unwrap();
+    let dep_id = dep.as_lib().lib_id();
+    const MAIN: u16 = 0;
+    const LOOP: u16 = 1;
+    let main = CompiledLib::compile(
+        aluasm! {
+           routine MAIN:
+            call    dep_id, 0;
+            not     CO;
+           label    LOOP:
+            not     CO;
+            jif     CO, LOOP;
+            stop;
+        },
+        &[&dep],
+    )
+    .unwrap();
+    let entry = main.routine(MAIN);
+    let (main, dep) = (main.into_lib(), dep.into_lib());
+    let main_id = main.lib_id();
+    let libs = [&main, &dep].map(|lib| (lib.lib_id(), lib));
+    let resolver = |id: LibId| {
+        libs.iter()
+            .find(|(lib_id, _)| *lib_id == id)
+            .map(|(_, lib)| *lib)
+    };
+
+    let mut vm = Vm::<Instr<LibId>>::new();
+    let status = vm.exec(entry, &(), resolver);
+
+    let disasm = main.disassemble::<Instr<_>>().unwrap();
+    let offset = |no: usize| {
+        disasm[..no]
+            .iter()
+            .map(|instr| instr.code_byte_len())
+            .sum::<u16>()
+    };
+    let after_call = Site::new(main_id, offset(2));
+    let in_loop = Site::new(main_id, offset(4));
+    let in_dep = Site::new(dep_id, 1);
+
+    let mut paused = Vm::<Instr<LibId>>::new();
+    assert!(paused.add_breakpoint(in_dep));
+    assert!(paused.add_breakpoint(after_call));
+    assert!(paused.add_breakpoint(in_loop));
+    assert!(!paused.add_breakpoint(in_loop));
+    assert_eq!(paused.breakpoints().count(), 3);
+
+    // Breakpoints are ignored by `exec`
+    let mut ignoring = paused.clone();
+    assert_eq!(ignoring.exec(entry, &(), resolver), status);
+    assert_eq!(ignoring.paused_at(), None);
+
+    assert_eq!(paused.exec_until(entry, &(), resolver), VmRun::Breakpoint(in_dep));
+    assert_eq!(paused.paused_at(), Some(in_dep));
+    assert_eq!(paused.core.cp(), 1);
+    assert_eq!(paused.resume_from_breakpoint(&(), resolver), VmRun::Breakpoint(after_call));
+    assert_eq!(paused.core.cp(), 0);
+    let co = paused.core.co();
+    // The loop body is executed twice
+    assert_eq!(paused.resume_from_breakpoint(&(), resolver), VmRun::Breakpoint(in_loop));
+    assert_eq!(paused.core.co(), !co);
+    assert_eq!(paused.resume_from_breakpoint(&(), resolver), VmRun::Breakpoint(in_loop));
+    assert!(paused.remove_breakpoint(in_loop));
+    assert!(!paused.remove_breakpoint(in_loop));
+    assert_eq!(paused.resume_from_breakpoint(&(), resolver), VmRun::Completed(status));
+    assert_eq!(paused.paused_at(), None);
+    assert_eq!(paused.core.co(), vm.core.co());
+    assert_eq!(paused.core.ca(), vm.core.ca());
+    assert_eq!(paused.core.cf(), vm.core.cf());
+    // Nothing to resume
+    assert_eq!(paused.resume_from_breakpoint(&(), resolver), VmRun::Completed(status));
+
+    // A breakpoint at the entry point pauses the execution before the first instruction
+    paused.clear_breakpoints();
+    paused.reset();
+    paused.add_breakpoint(Site::new(main_id, 0));
+    assert_eq!(paused.exec_until(entry, &(), resolver), VmRun::Breakpoint(Site::new(main_id, 0)));
+    assert_eq!(paused.core.ca(), 0);
+    assert_eq!(paused.resume_from_breakpoint(&(), resolver), VmRun::Completed(status));
+    assert_eq!(paused.core.ca(), vm.core.ca());
+
+    // Complexity limit is applied across the resumptions
+    let config = CoreConfig {
+        complexity_lim: Some(vm.core.ca() - 1),
+        ..CoreConfig::default()
+    };
+    let mut limited = Vm::<Instr<LibId>>::with(config, ());
+    limited.add_breakpoint(after_call);
+    assert_eq!(limited.exec_until(entry, &(), resolver), VmRun::Breakpoint(after_call));
+    assert_eq!(limited.resume_from_breakpoint(&(), resolver), VmRun::Completed(Status::Fail));
+}
+
+#[test]
+fn frame_budgets() {
+    const ENTRY: u16 = 0;
+    const INNER: u16 = 1;
+    // Untrusted library which never returns from a nested call
+    let dep = CompiledLib::compile(
+        aluasm! {
+           routine ENTRY:
+            call    INNER;
+            ret;
+           label    INNER:
+            jmp     INNER;
+        },
+        &[],
+    )
+    .unwrap();
+    let dep_id = dep.as_lib().lib_id();
+    let main = CompiledLib::compile(
+        aluasm! {
+            call    dep_id, ENTRY;
+            mov     CO, CK;
+            stop;
+        },
+        &[&dep],
+    )
+    .unwrap();
+    let entry = LibSite::new(main.as_lib().lib_id(), 0);
+    let (main, dep) = (main.into_lib(), dep.into_lib());
+    let libs = [&main, &dep].map(|lib| (lib.lib_id(), lib));
+    let resolver = |id: LibId| {
+        libs.iter()
+            .find(|(lib_id, _)| *lib_id == id)
+            .map(|(_, lib)| *lib)
+    };
+
+    let unit = Instr::<LibId>::from(CtrlInstr::Jmp { pos: 0 }).complexity();
+    let config = CoreConfig { complexity_lim: Some(1000 * unit), ..CoreConfig::default() };
+
+    // The inner frame exhausts its budget, the library returns to the program, which handles the
+    // failure and continues
+    let mut vm = Vm::<Instr<LibId>>::with(config, ());
+    let inner_call = Site::new(dep_id, 1);
+    vm.add_breakpoint(inner_call);
+    assert_eq!(vm.exec_until(entry, &(), resolver), VmRun::Breakpoint(inner_call));
+    vm.core.push_budget(20 * unit);
+    assert_eq!(vm.resume_from_breakpoint(&(), resolver), VmRun::Completed(Status::Ok));
+    assert_eq!(vm.core.co(), Status::Fail);
+    assert_eq!(vm.core.cf(), 1);
+    assert_eq!(vm.core.cp(), 0);
+    assert_eq!(vm.core.cs_high_water(), 2);
+    assert!(vm.core.ca() < 100 * unit);
+
+    // Budget of the outer frame caps the nested one, and all the frames are unwound at once
+    let mut vm = Vm::<Instr<LibId>>::with(config, ());
+    vm.core.push_budget(20 * unit);
+    assert_eq!(vm.exec(entry, &(), resolver), Status::Ok);
+    assert_eq!(vm.core.co(), Status::Fail);
+    assert_eq!(vm.core.cf(), 1);
+    assert_eq!(vm.core.cp(), 0);
+    assert_eq!(vm.core.xcp(), 0);
+    assert_eq!(vm.core.frame_budget(), None);
+    assert!(vm.core.ca() < 100 * unit);
+
+    // Stepping unwinds the frames in the same way
+    let mut stepped = Vm::<Instr<LibId>>::with(config, ());
+    stepped.core.push_budget(20 * unit);
+    let mut site = Some(entry);
+    while let Some(current) = site {
+        let step = stepped.step(current, &(), resolver).unwrap();
+        site = stepped.next_site(current, step, resolver);
+    }
+    assert_eq!(stepped.core.ck(), Status::Ok);
+    assert_eq!(stepped.core.co(), Status::Fail);
+    assert_eq!(stepped.core.ca(), vm.core.ca());
+
+    // The global complexity limit takes precedence over the frame budgets
+    let config = CoreConfig { complexity_lim: Some(10 * unit), ..CoreConfig::default() };
+    let mut vm = Vm::<Instr<LibId>>::with(config, ());
+    vm.core.push_budget(20 * unit);
+    assert_eq!(vm.exec(entry, &(), resolver), Status::Fail);
+    assert_eq!(vm.core.cf(), 1);
+    assert!(vm.core.cp() > 0);
+    assert!(vm.core.frame_budget().is_some());
+}
+
+#[test]
+fn jump_limit() {
+    // Infinite loop without a complexity limit is terminated by the jump counter
+    let code = vec![CtrlInstr::Nop.into(), CtrlInstr::Sh { shift: 0 }.into()];
+    let lib = Lib::assemble::<Instr<LibId>>(&code).unwrap();
+    let entry = LibSite::new(lib.lib_id(), 0);
+    let resolver = |_| Some(&lib);
+
+    let mut vm = Vm::<Instr<LibId>>::with(CoreConfig::default(), ());
+    assert_eq!(vm.exec(entry, &(), resolver), Status::Fail);
+    assert_eq!(vm.core.cy(), u16::MAX);
+    assert_eq!(vm.core.cf(), 1);
+
+    let config = CoreConfig { jump_lim: Some(10), ..CoreConfig::default() };
+    let mut vm = Vm::<Instr<LibId>>::with(config, ());
+    assert_eq!(vm.exec(entry, &(), resolver), Status::Fail);
+    assert_eq!(vm.core.cy(), 10);
+    assert_eq!(vm.core.cf(), 1);
+
+    // Stepping fails at the same jump
+    let mut stepped = Vm::<Instr<LibId>>::with(config, ());
+    let mut site = entry;
+    for _ in 0..11 {
+        let step = stepped.step(site, &(), resolver).unwrap();
+        site = stepped.next_site(site, step, resolver).unwrap();
+    }
+    assert_eq!(stepped.step(site, &(), resolver), Err(StepError::JumpOverflow(site)));
+    assert_eq!(stepped.core.cy(), 10);
+    assert_eq!(stepped.core.ck(), Status::Fail);
+
+    // Calls and returns are counted, while moving to the next instruction is not
+    let code = vec![
+        CtrlInstr::Nop.into(),
+        CtrlInstr::Fn { pos: 5 }.into(),
+        CtrlInstr::Stop.into(),
+        CtrlInstr::Ret.into(),
+    ];
+    let lib = Lib::assemble::<Instr<LibId>>(&code).unwrap();
+    let mut vm = Vm::<Instr<LibId>>::with(CoreConfig::default(), ());
+    assert_eq!(vm.exec(LibSite::new(lib.lib_id(), 0), &(), |_| Some(&lib)), Status::Ok);
+    assert_eq!(vm.core.cy(), 2);
+}
+
+#[test]
+fn jump_past_end() {
+    let lib = |shift: i8| {
+        let code = vec![CtrlInstr::Nop.into(), CtrlInstr::Sh { shift }.into()];
+        Lib::assemble::<Instr<LibId>>(&code).unwrap()
+    };
+
+    // Jumping right past the last instruction stops the program as if it ran off the end
+    let end = lib(2);
+    let entry = LibSite::new(end.lib_id(), 0);
+    let vm = exec_and_step(entry, |_| Some(&end));
+    assert_eq!(vm.core.ck(), Status::Ok);
+    assert_eq!(vm.termination(), Some(TerminationCause::EndOfCode));
+    assert_eq!(vm.last_error(), None);
+    let precompiled = end.precompile::<Instr<LibId>>().unwrap();
+    let mut vm = Vm::<Instr<LibId>>::new();
+    assert_eq!(vm.exec_compiled(entry, &(), |_| Some(&precompiled)), Status::Ok);
+    assert_eq!(vm.termination(), Some(TerminationCause::EndOfCode));
+
+    // Jumping further fails
+    let beyond = lib(3);
+    let entry = LibSite::new(beyond.lib_id(), 0);
+    let mut vm = Vm::<Instr<LibId>>::new();
+    assert_eq!(
+        vm.exec_checked(entry, &(), |_| Some(&beyond)),
+        Err(ExecError::CodeOverrun(LibSite::new(beyond.lib_id(), 4)))
+    );
+    assert_eq!(vm.core.ck(), Status::Fail);
+    let precompiled = beyond.precompile::<Instr<LibId>>().unwrap();
+    let mut vm = Vm::<Instr<LibId>>::new();
+    assert_eq!(vm.exec_compiled(entry, &(), |_| Some(&precompiled)), Status::Fail);
+
+    // Jumping before the start of the code fails
+    let before = lib(-2);
+    let entry = LibSite::new(before.lib_id(), 0);
+    let mut vm = Vm::<Instr<LibId>>::new();
+    assert_eq!(vm.exec(entry, &(), |_| Some(&before)), Status::Fail);
+    assert_eq!(vm.last_failure(), Some(&FailureReason::JumpOffsetOverflow));
+    assert_eq!(
+        vm.termination(),
+        Some(TerminationCause::Halted { site: Site::new(before.lib_id(), 1) })
+    );
+}
+
+#[test]
+fn jump_loop() {
+    // Tight loop of a goto target and an absolute jump back to it
+    let code = vec![CtrlInstr::Nop.into(), CtrlInstr::Jmp { pos: 0 }.into()];
+    let lib = Lib::assemble::<Instr<LibId>>(&code).unwrap();
+    let entry = LibSite::new(lib.lib_id(), 0);
+    let resolver = |_| Some(&lib);
+
+    for lim in [1, 5, u16::MAX] {
+        let config = CoreConfig { jump_lim: Some(lim), ..CoreConfig::default() };
+        let mut vm = Vm::<Instr<LibId>>::with(config, ());
+        assert_eq!(vm.exec(entry, &(), resolver), Status::Fail);
+        assert_eq!(vm.core.cy(), lim);
+        assert_eq!(vm.core.ci(), 2 * lim as u64 + 2);
+        assert_eq!(
+            vm.termination(),
+            Some(TerminationCause::Halted { site: Site::new(lib.lib_id(), 1) })
+        );
+    }
+}
+
+#[test]
+fn instr_limit() {
+    // Loop of trivial instructions which is not stopped by the jump counter
+    let code =
+        vec![CtrlInstr::Nop.into(), CtrlInstr::NotCo.into(), CtrlInstr::Sh { shift: -2 }.into()];
+    let lib = Lib::assemble::<Instr<LibId>>(&code).unwrap();
+    let entry = LibSite::new(lib.lib_id(), 0);
+    let resolver = |_| Some(&lib);
+
+    for halt in [true, false] {
+        let config = CoreConfig { halt, instr_lim: Some(10), ..CoreConfig::default() };
+        let mut vm = Vm::<Instr<LibId>>::with(config, ());
+        assert_eq!(
+            vm.exec_checked(entry, &(), resolver),
+            Err(ExecError::InstrLimit(LibSite::new(lib.lib_id(), 1)))
+        );
+        assert_eq!(vm.core.ci(), 10);
+        assert_eq!(vm.core.cy(), 3);
+        assert_eq!(vm.core.cf(), 1);
+        // The fourth `not CO` exceeding the limit is not executed
+        assert_eq!(vm.core.co(), Status::Fail);
+    }
+
+    // Stepping fails at the same instruction
+    let config = CoreConfig { instr_lim: Some(10), ..CoreConfig::default() };
+    let mut stepped = Vm::<Instr<LibId>>::with(config, ());
+    let mut site = entry;
+    for _ in 0..10 {
+        let step = stepped.step(site, &(), resolver).unwrap();
+        site = stepped.next_site(site, step, resolver).unwrap();
+    }
+    assert_eq!(stepped.step(site, &(), resolver), Err(StepError::InstrLimit(site)));
+    assert_eq!(stepped.core.ci(), 10);
+    assert_eq!(stepped.core.ck(), Status::Fail);
+
+    // Normal termination within the limit
+    let code = vec![CtrlInstr::Nop.into(), CtrlInstr::Stop.into()];
+    let lib = Lib::assemble::<Instr<LibId>>(&code).unwrap();
+    let config = CoreConfig { instr_lim: Some(2), ..CoreConfig::default() };
+    let mut vm = Vm::<Instr<LibId>>::with(config, ());
+    assert_eq!(vm.exec_checked(LibSite::new(lib.lib_id(), 0), &(), |_| Some(&lib)), Ok(Status::Ok));
+    assert_eq!(vm.core.ci(), 2);
+}
+
+#[test]
+fn host_control_regs() {
+    let code = aluasm! { fail CK; not CO; stop; };
+    let lib = Lib::assemble::<Instr<LibId>>(&code).unwrap();
+    let entry = LibSite::new(lib.lib_id(), 0);
+    let resolver = |_| Some(&lib);
+
+    // With `CH` set, the failed `CK` halts the program before `not CO`
+    let mut vm = Vm::<Instr<LibId>>::new();
+    assert!(vm.core.ch());
+    assert_eq!(vm.exec(entry, &(), resolver), Status::Fail);
+    assert_eq!(vm.core.co(), Status::Ok);
+    assert_eq!(vm.core.cf(), 1);
+    // The host failing `CK` gets the same halting decision as the program
+    assert!(vm.core.fail_ck());
+    assert_eq!(vm.core.cf(), 2);
+
+    let config = CoreConfig { halt: false, ..CoreConfig::default() };
+    let mut vm = Vm::<Instr<LibId>>::with(config, ());
+    vm.core.set_co(Status::Fail);
+    assert_eq!(vm.exec(entry, &(), resolver), Status::Fail);
+    assert_eq!(vm.core.co(), Status::Ok);
+    assert!(!vm.core.fail_ck());
+    assert_eq!(vm.core.ck(), Status::Fail);
+    assert_eq!(vm.core.cf(), 2);
+    vm.core.reset_ck();
+    assert_eq!(vm.core.ck(), Status::Ok);
+
+    // Probing doesn't perturb the control registers
+    let probe = vm.run_isolated(|vm| {
+        vm.core.set_co(Status::Fail);
+        let status = vm.exec(entry, &(), resolver);
+        (status, vm.core.co(), vm.core.cf())
+    });
+    assert_eq!(probe, (Status::Fail, Status::Ok, 3));
+    let probe = vm.core.run_isolated(|core| core.fail_ck());
+    assert!(!probe);
+    assert_eq!(vm.core.ck(), Status::Ok);
+    assert_eq!(vm.core.co(), Status::Ok);
+    assert_eq!(vm.core.cf(), 2);
+}
+
+#[test]
+fn wide_jumps() {
+    // Jumps over 200 bytes of `not CO`, which are out of reach for the one-byte shifts
+    let mut code = aluasm! { jmp.w +204; stop; };
+    code.extend([Instr::<LibId>::from(CtrlInstr::NotCo); 200]);
+    code.extend(aluasm! {
+        not     CO;
+        jif.w   CO, -202;
+        jif.w   CK, +2;
+        stop;
+    });
+    let lib = Lib::assemble::<Instr<LibId>>(&code).unwrap();
+    let mut vm = Vm::<Instr<LibId>>::new();
+    assert_eq!(vm.exec(LibSite::new(lib.lib_id(), 0), &(), |_| Some(&lib)), Status::Ok);
+    assert_eq!(vm.core.co(), Status::Fail);
+    assert_eq!(vm.core.ci(), 4);
+}
+
+#[test]
+fn unknown_instr_policy() {
+    let code = vec![
+        CtrlInstr::Nop.into(),
+        ReservedInstr::default().into(),
+        CtrlInstr::NotCo.into(),
+        CtrlInstr::Stop.into(),
+    ];
+    let lib = Lib::assemble::<Instr<LibId>>(&code).unwrap();
+    let entry = LibSite::new(lib.lib_id(), 0);
+    let run = |unknown_instr| {
+        let config = CoreConfig {
+            complexity_lim: Some(1_000_000),
+            unknown_instr,
+            ..CoreConfig::default()
+        };
+        let mut vm = Vm::<Instr<LibId>>::with(config, ());
+        let status = vm.exec(entry, &(), |_| Some(&lib));
+        (status, vm)
+    };
+
+    let (status, vm) = run(UnknownInstrPolicy::Fail);
+    assert_eq!(status, Status::Fail);
+    assert_eq!(vm.core.cf(), 1);
+    assert_eq!(vm.core.co(), Status::Ok);
+
+    // Reserved instruction is skipped without tripping the complexity limit
+    let (status, vm) = run(UnknownInstrPolicy::Nop);
+    assert_eq!(status, Status::Ok);
+    assert_eq!(vm.core.cf(), 0);
+    assert_eq!(vm.core.co(), Status::Fail);
+    assert_eq!(vm.core.ca(), Instr::<LibId>::from(CtrlInstr::NotCo).complexity());
+
+    let (status, vm) = run(UnknownInstrPolicy::Halt);
+    assert_eq!(status, Status::Ok);
+    assert_eq!(vm.core.cf(), 0);
+    assert_eq!(vm.core.co(), Status::Ok);
+    assert_eq!(vm.core.ca(), 0);
+
+    // Single-stepping honors the policy in the same way
+    let config = CoreConfig {
+        unknown_instr: UnknownInstrPolicy::Nop,
+        ..CoreConfig::default()
+    };
+    let mut vm = Vm::<Instr<LibId>>::with(config, ());
+    let site = LibSite::new(lib.lib_id(), 1);
+    assert_eq!(vm.step(site, &(), |_| Some(&lib)), Ok(ExecStep::Next));
+    assert_eq!(vm.core.ca(), 0);
+    assert_eq!(vm.core.ck(), Status::Ok);
+}
+
+#[test]
+fn exec_validated() {
+    let mut ext = Lib::assemble::<Instr<LibId>>(&[CtrlInstr::Nop.into()]).unwrap();
+    // The last instruction is truncated
+    ext.code_mut().extend([CtrlInstr::<LibId>::JMP, 0]).unwrap();
+    let code = vec![
+        CtrlInstr::NotCo.into(),
+        CtrlInstr::Call { site: Site::new(ext.lib_id(), 0) }.into(),
+        CtrlInstr::NotCo.into(),
+        CtrlInstr::Stop.into(),
+    ];
+    let main = Lib::assemble::<Instr<LibId>>(&code).unwrap();
+    let resolver = |id: LibId| [&main, &ext].into_iter().find(|lib| lib.lib_id() == id);
+    let entry = LibSite::new(main.lib_id(), 0);
+
+    let mut vm = Vm::<Instr<LibId>>::new();
+    assert_eq!(vm.exec_validated(entry, &(), resolver), Status::Fail);
+    assert_eq!(vm.last_error(), Some(ExecError::InvalidLib(LibSite::new(ext.lib_id(), 0))));
+    assert_eq!(vm.core.ci(), 2);
+
+    // Without validation, the library is entered, and the program silently halts on the truncated
+    // instruction
+    let mut vm = Vm::<Instr<LibId>>::new();
+    assert_eq!(vm.exec(entry, &(), resolver), Status::Ok);
+    assert_eq!(vm.last_error(), None);
+    assert_eq!(vm.core.ci(), 3);
+
+    let mut vm = Vm::<Instr<LibId>>::new();
+    let entry = LibSite::new(ext.lib_id(), 0);
+    assert_eq!(vm.exec_validated(entry, &(), resolver), Status::Fail);
+    assert_eq!(vm.last_error(), Some(ExecError::InvalidLib(entry)));
+    assert_eq!(vm.core.ci(), 0);
+
+    // Jumping right past the end of the code is an orderly stop, accepted by the validation
+    let to_end = Lib::assemble::<Instr<LibId>>(&[CtrlInstr::Jmp { pos: 3 }.into()]).unwrap();
+    let entry = LibSite::new(to_end.lib_id(), 0);
+    let mut vm = Vm::<Instr<LibId>>::new();
+    assert_eq!(vm.exec_validated(entry, &(), |_| Some(&to_end)), Status::Ok);
+    assert_eq!(vm.last_error(), None);
+    assert_eq!(vm.termination(), Some(TerminationCause::EndOfCode));
+}
+
+#[test]
+fn termination() {
+    fn exec(config: CoreConfig, code: &[Instr<LibId>]) -> (Vm<Instr<LibId>>, LibId) {
+        let lib = Lib::assemble::<Instr<LibId>>(code).unwrap();
+        let mut vm = Vm::<Instr<LibId>>::with(config, ());
+        let id = lib.lib_id();
+        let _ = vm.exec(LibSite::new(id, 0), &(), |lib_id| (lib_id == id).then_some(&lib));
+        (vm, id)
+    }
+    let no_halt = CoreConfig { halt: false, ..CoreConfig::default() };
+
+    let (vm, _) = exec(no_halt, &aluasm! { nop; stop; nop; });
+    assert_eq!(vm.termination(), Some(TerminationCause::ExplicitStop));
+    assert_eq!(vm.core.ck(), Status::Ok);
+
+    // The failure is reset, but the program has still bailed out on it
+    let (vm, id) = exec(no_halt, &aluasm! { fail CK; chk CK; mov CO, CK; stop; });
+    assert_eq!(vm.termination(), Some(TerminationCause::CheckFailed { site: Site::new(id, 1) }));
+    assert_eq!(vm.core.ck(), Status::Fail);
+
+    let (vm, _) = exec(no_halt, &aluasm! { nop; ret; stop; });
+    assert_eq!(vm.termination(), Some(TerminationCause::ReturnFromMain));
+
+    let (vm, _) = exec(no_halt, &aluasm! { nop; not CO; });
+    assert_eq!(vm.termination(), Some(TerminationCause::EndOfCode));
+    assert_eq!(vm.core.co(), Status::Fail);
+
+    // Explicit failure with `CH` set
+    let (vm, id) = exec(CoreConfig::default(), &aluasm! { nop; fail CK; stop; });
+    assert_eq!(vm.termination(), Some(TerminationCause::Halted { site: Site::new(id, 1) }));
+
+    // Failure of an instruction with `CH` set
+    let (vm, id) = exec(CoreConfig::default(), &aluasm! { not CO; chk CO; stop; });
+    assert_eq!(vm.termination(), Some(TerminationCause::Halted { site: Site::new(id, 1) }));
+
+    // Jump limit exceeded with `CH` set
+    let config = CoreConfig { jump_lim: Some(3), ..CoreConfig::default() };
+    let (vm, id) = exec(config, &[CtrlInstr::Nop.into(), CtrlInstr::Sh { shift: 0 }.into()]);
+    assert_eq!(vm.termination(), Some(TerminationCause::Halted { site: Site::new(id, 1) }));
+
+    // Complexity limit is exceeded independently of `CH`
+    let config = CoreConfig { complexity_lim: Some(4000), ..no_halt };
+    let (vm, _) = exec(config, &[
+        CtrlInstr::Nop.into(),
+        CtrlInstr::NotCo.into(),
+        CtrlInstr::Sh { shift: -1 }.into(),
+    ]);
+    assert_eq!(vm.termination(), Some(TerminationCause::ComplexityExceeded));
+    assert_eq!(vm.core.ck(), Status::Fail);
+
+    // The virtual machine aborts the execution on a missing library
+    let missing = Site::new(LibId::from([0xA5u8; 32]), 0);
+    let (vm, _) = exec(no_halt, &[CtrlInstr::Exec { site: missing }.into()]);
+    assert_eq!(vm.last_error(), Some(ExecError::LibAbsent(missing.prog_id, missing.into())));
+    assert_eq!(vm.termination(), Some(TerminationCause::Aborted));
+
+    // The cause is reset with each execution
+    let lib = Lib::assemble::<Instr<LibId>>(&aluasm! { nop; }).unwrap();
+    let mut vm = Vm::<Instr<LibId>>::with(no_halt, ());
+    let entry = LibSite::new(lib.lib_id(), 0);
+    assert_eq!(
+        vm.exec_checked(LibSite::new(missing.prog_id, 0), &(), |_| None::<&Lib>)
+            .ok(),
+        None
+    );
+    assert_eq!(vm.termination(), Some(TerminationCause::Aborted));
+    assert_eq!(vm.exec(entry, &(), |_| Some(&lib)), Status::Fail);
+    assert_eq!(vm.termination(), Some(TerminationCause::EndOfCode));
+
+    // Single-stepping records the same cause
+    let lib = Lib::assemble::<Instr<LibId>>(&aluasm! { fail CK; chk CK; }).unwrap();
+    let mut vm = Vm::<Instr<LibId>>::with(no_halt, ());
+    let site = LibSite::new(lib.lib_id(), 0);
+    assert_eq!(vm.step(site, &(), |_| Some(&lib)), Ok(ExecStep::Next));
+    assert_eq!(vm.termination(), None);
+    let site = vm.next_site(site, ExecStep::Next, |_| Some(&lib)).unwrap();
+    assert_eq!(vm.step(site, &(), |_| Some(&lib)), Ok(ExecStep::Stop));
+    let site = Site::new(lib.lib_id(), 1);
+    assert_eq!(vm.termination(), Some(TerminationCause::CheckFailed { site }));
+}
+
+#[test]
+fn monitor_control_registers() {
+    const SUB: u16 = 7;
+    let code = vec![
+        CtrlInstr::Fn { pos: SUB }.into(),
+        CtrlInstr::Fn { pos: SUB }.into(),
+        CtrlInstr::Stop.into(),
+        CtrlInstr::Ret.into(),
+    ];
+    let lib = Lib::assemble::<Instr<LibId>>(&code).unwrap();
+    let entry = LibSite::new(lib.lib_id(), 0);
+    let resolver = |_| Some(&lib);
+    let config = CoreConfig { complexity_lim: Some(1_000_000), ..CoreConfig::default() };
+
+    let mut vm = Vm::<Instr<LibId>>::with(config, ());
+    let sub = Site::new(lib.lib_id(), SUB);
+    vm.add_breakpoint(sub);
+    assert_eq!(vm.exec_until(entry, &(), resolver), VmRun::Breakpoint(sub));
+    assert_eq!(vm.core.call_stack(), &[Site::new(lib.lib_id(), 0)]);
+    assert_eq!(vm.core.cy(), 1);
+    vm.remove_breakpoint(sub);
+    assert_eq!(vm.resume_from_breakpoint(&(), resolver), VmRun::Completed(Status::Ok));
+
+    // Two calls and two returns
+    assert_eq!(vm.core.cy(), 4);
+    assert_eq!(vm.core.cf(), 0);
+    assert_eq!(vm.core.cl(), Some(1_000_000));
+    assert!(vm.core.ca() > 0);
+    assert!(vm.core.call_stack().is_empty());
+
+    let dump = vm.core.dump();
+    assert_eq!((dump.cy, dump.cf, dump.ca, dump.cl), (4, 0, vm.core.ca(), vm.core.cl()));
+    #[cfg(feature = "serde")]
+    {
+        let json = serde_json::to_value(&dump).unwrap();
+        assert_eq!(json["cy"], 4);
+        assert_eq!(json["cs"], serde_json::json!([]));
+    }
+}
+
+#[test]
+fn halt_reason() {
+    fn exec(config: CoreConfig, code: &[Instr<LibId>]) -> (ExecReport, LibId) {
+        let lib = Lib::assemble::<Instr<LibId>>(code).unwrap();
+        let mut vm = Vm::<Instr<LibId>>::with(config, ());
+        let id = lib.lib_id();
+        let report =
+            vm.exec_full(LibSite::new(id, 0), &(), |lib_id| (lib_id == id).then_some(&lib));
+        assert_eq!(report.complexity_used, vm.core.ca());
+        assert_eq!(vm.halt_reason(), report.halt_reason);
+        (report, id)
+    }
+    let no_halt = CoreConfig { halt: false, ..CoreConfig::default() };
+
+    let config = CoreConfig { complexity_lim: Some(1_000_000), ..CoreConfig::default() };
+    let (report, _) = exec(config, &aluasm! { not CO; stop; nop; });
+    assert_eq!(report.status, Status::Ok);
+    assert_eq!(report.halt_reason, Some(HaltReason::OrderlyStop));
+    assert_eq!(report.steps, 2);
+    assert!(report.complexity_used > 0);
+
+    let (report, _) = exec(CoreConfig::default(), &aluasm! { nop; ret; });
+    assert_eq!(report.halt_reason, Some(HaltReason::OrderlyStop));
+
+    let (report, id) = exec(CoreConfig::default(), &aluasm! { nop; fail CK; stop; });
+    assert_eq!(report.status, Status::Fail);
+    assert_eq!(report.halt_reason, Some(HaltReason::FailHalt(Site::new(id, 1))));
+    assert_eq!(report.steps, 2);
+
+    let (report, id) = exec(no_halt, &aluasm! { fail CK; chk CK; stop; });
+    assert_eq!(report.halt_reason, Some(HaltReason::FailHalt(Site::new(id, 1))));
+
+    let config = CoreConfig { complexity_lim: Some(4000), ..no_halt };
+    let (report, _) = exec(config, &[
+        CtrlInstr::Nop.into(),
+        CtrlInstr::NotCo.into(),
+        CtrlInstr::Sh { shift: -1 }.into(),
+    ]);
+    assert_eq!(report.halt_reason, Some(HaltReason::ComplexityLimit));
+
+    let config = CoreConfig { jump_lim: Some(3), ..CoreConfig::default() };
+    let (report, id) = exec(config, &[CtrlInstr::Nop.into(), CtrlInstr::Sh { shift: 0 }.into()]);
+    assert_eq!(report.halt_reason, Some(HaltReason::JumpLimit(Site::new(id, 1))));
+    assert_eq!(report.steps, 5);
+
+    let (report, id) = exec(CoreConfig::default(), &[CtrlInstr::Fn { pos: 0 }.into()]);
+    assert_eq!(report.halt_reason, Some(HaltReason::CallStackOverflow(Site::new(id, 0))));
+    assert_eq!(report.steps, CALL_STACK_SIZE_MAX as u64 + 1);
+
+    let missing = Site::new(LibId::from([0xA5u8; 32]), 0);
+    let (report, _) = exec(no_halt, &[CtrlInstr::Exec { site: missing }.into()]);
+    assert_eq!(report.status, Status::Fail);
+    assert_eq!(report.halt_reason, Some(HaltReason::UnresolvedLib(missing.prog_id)));
+
+    let (report, _) = exec(no_halt, &aluasm! { nop; not CO; });
+    assert_eq!(report.status, Status::Ok);
+    assert_eq!(report.halt_reason, Some(HaltReason::EndOfCode));
+
+    let config = CoreConfig { instr_lim: Some(1), ..no_halt };
+    let (report, id) = exec(config, &aluasm! { nop; nop; stop; });
+    assert_eq!(
+        report.halt_reason,
+        Some(HaltReason::Aborted(ExecError::InstrLimit(LibSite::new(id, 1))))
+    );
+    assert_eq!(report.steps, 1);
+}
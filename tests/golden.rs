@@ -0,0 +1,199 @@
+// Reference rust implementation of AluVM (arithmetic logic unit virtual machine).
+// To find more on AluVM please check <https://aluvm.org>
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Designed in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+// Written in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+//
+// Copyright (C) 2021-2024 LNP/BP Standards Association, Switzerland.
+// Copyright (C) 2024-2025 Laboratories for Ubiquitous Deterministic Computing (UBIDECO),
+//                         Institute for Distributed and Cognitive Systems (InDCS), Switzerland.
+// Copyright (C) 2021-2025 Dr Maxim Orlovsky.
+// All rights under the above copyrights are reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use this file except
+// in compliance with the License. You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed under the License
+// is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express
+// or implied. See the License for the specific language governing permissions and limitations under
+// the License.
+
+//! Golden test vectors for the core state digest.
+//!
+//! Each vector is a small program together with the digest of the core state after its
+//! execution. The bytecode of each program is pinned down by its library id, such that the same
+//! vectors can be used to check other implementations of AluVM.
+
+extern crate alloc;
+
+use aluvm::isa::{Alu64Instr, ArithmInstr, CtrlInstr, Instr, RegA};
+use aluvm::regs::Status;
+use aluvm::{
+    aluasm, CompiledLib, Core, CoreConfig, Lib, LibId, LibSite, NoExt, Site, UnknownInstrPolicy,
+    Vm, VmRun,
+};
+
+const CONFIG: CoreConfig = CoreConfig {
+    halt: false,
+    complexity_lim: Some(1_000_000),
+    complexity_warn: Some(1_000),
+    jump_lim: Some(100),
+    instr_lim: None,
+    cs_integrity: true,
+    unknown_instr: UnknownInstrPolicy::Fail,
+};
+
+fn ctrl_lib() -> Lib {
+    const SUB: u16 = 1;
+
+    let code: Vec<Instr<LibId>> = aluasm! {
+       routine MAIN:
+        not     CO;
+        call    SUB;
+        chk     CO;
+        fail    CK;
+        stop;
+
+       routine SUB:
+        not     CO;
+        ret;
+    };
+    CompiledLib::compile(code, &[]).unwrap().into_lib()
+}
+
+fn factorial(n: u64) -> Lib {
+    let code: Vec<Alu64Instr<LibId>> = vec![
+        ArithmInstr::Put { dst: RegA::A0, val: 1 }.into(),
+        ArithmInstr::Put { dst: RegA::A1, val: n }.into(),
+        ArithmInstr::Put { dst: RegA::A2, val: 1 }.into(),
+        ArithmInstr::Put { dst: RegA::A3, val: 0 }.into(),
+        ArithmInstr::Mul { wrap: false, dst: RegA::A0, src: RegA::A1 }.into(),
+        ArithmInstr::Sub { wrap: false, dst: RegA::A1, src: RegA::A2 }.into(),
+        ArithmInstr::Eq { src1: RegA::A1, src2: RegA::A3 }.into(),
+        CtrlInstr::JiOvfl { pos: 16 }.into(),
+        CtrlInstr::Stop.into(),
+    ];
+    Lib::assemble(&code).unwrap()
+}
+
+fn run_ctrl(config: CoreConfig) -> Vm<Instr<LibId>> {
+    let lib = ctrl_lib();
+    let mut vm = Vm::<Instr<LibId>>::with(config, ());
+    vm.exec(LibSite::new(lib.lib_id(), 0), &(), |_| Some(&lib));
+    vm
+}
+
+fn run_alu(n: u64) -> Vm<Alu64Instr<LibId>> {
+    let lib = factorial(n);
+    let mut vm = Vm::<Alu64Instr<LibId>>::with(CONFIG, ());
+    vm.exec(LibSite::new(lib.lib_id(), 0), &(), |_| Some(&lib));
+    vm
+}
+
+#[test]
+fn libraries() {
+    assert_eq!(
+        ctrl_lib().lib_id().to_string(),
+        "alu:GK4t0E~4-7gBIfZe-7O_Wxni-a~tpjUy-gKsGdm3-qz7l48s#elegant-zipper-example"
+    );
+    assert_eq!(
+        factorial(5).lib_id().to_string(),
+        "alu:TEeggaky-2w71AWn-g2CRH~E-fZ2etrz-cy6BBng-kRq5o58#lady-telecom-rocket"
+    );
+    assert_eq!(
+        factorial(21).lib_id().to_string(),
+        "alu:DmiCDpoM-M~j2ZzM-fSNMKoy-GZas3Tb-VjfrK8I-TFIAmxc#mozart-plato-battery"
+    );
+}
+
+#[test]
+fn initial() {
+    assert_eq!(
+        Core::<LibId, NoExt>::new().state_digest().to_string(),
+        "c0118d7b42850ce3ba65b3add67e0c4ea4f4417a299c510f5ca8fa8294fdfbad"
+    );
+    assert_eq!(
+        Vm::<Instr<LibId>>::with(CONFIG, ())
+            .core
+            .state_digest()
+            .to_string(),
+        "04ced83e544c100a680a1707a39426530b1cc04164fa93ef5f61aef75409a6ab"
+    );
+    assert_eq!(
+        Vm::<Alu64Instr<LibId>>::with(CONFIG, ())
+            .core
+            .state_digest()
+            .to_string(),
+        "7f4b0011d375e902ff5119e74ddaae52953b8b7e608102abbd1d903abe6f42a9"
+    );
+}
+
+#[test]
+fn ctrl() {
+    let vm = run_ctrl(CONFIG);
+    assert_eq!(vm.core.ck(), Status::Fail);
+    assert_eq!(
+        vm.core.state_digest().to_string(),
+        "d1213ad0755b4d25af28364d391901a41eb5184f42321b454b4d06dc694e9ce5"
+    );
+
+    // Configuration registers participate in the digest
+    let vm = run_ctrl(CoreConfig::default());
+    assert_eq!(
+        vm.core.state_digest().to_string(),
+        "690d0400f2c3fe1fe6156233f00a886267fd8b27d1a23ba1f443d5868cf33fc8"
+    );
+}
+
+#[test]
+fn ctrl_suspended() {
+    let lib = ctrl_lib();
+    let lib_id = lib.lib_id();
+    let mut vm = Vm::<Instr<LibId>>::with(CONFIG, ());
+    let brk = Site::new(lib_id, lib.code().len() as u16 - 1);
+    vm.add_breakpoint(brk);
+    assert_eq!(vm.exec_until(LibSite::new(lib_id, 0), &(), |_| Some(&lib)), VmRun::Breakpoint(brk));
+    assert_eq!(vm.core.cp(), 1);
+    assert_eq!(
+        vm.core.state_digest().to_string(),
+        "a77dbbaaeef7491f0897ea6072d07f4798754b042fd18af5585c96b96b9faaf9"
+    );
+}
+
+#[test]
+fn alu64() {
+    let vm = run_alu(5);
+    assert_eq!(vm.core.get(RegA::A0), Some(120));
+    assert_eq!(
+        vm.core.state_digest().to_string(),
+        "91060d733221183126263447f5baa4ebc8c5b9e653dba36d1812d6983a3cc173"
+    );
+
+    let vm = run_alu(21);
+    assert_eq!(vm.core.co(), Status::Fail);
+    assert_eq!(
+        vm.core.state_digest().to_string(),
+        "d336d226fd01a315b3766b96007bd6bb952f04ca2d7700358a8eab60f6b700df"
+    );
+}
+
+#[test]
+fn equality() {
+    let vm = run_alu(5);
+    assert!(vm.core == run_alu(5).core);
+    assert!(vm.core != run_alu(6).core);
+
+    let mut reset = vm.core.clone();
+    reset.reset();
+    assert!(reset != vm.core);
+    assert_ne!(reset.state_digest(), vm.core.state_digest());
+
+    // Restoring a snapshot reproduces the digest, even though diagnostic reports are dropped
+    let mut restored = Vm::<Alu64Instr<LibId>>::with(CONFIG, ()).core;
+    restored.restore(vm.core.snapshot()).unwrap();
+    assert_eq!(restored.state_digest(), vm.core.state_digest());
+}
@@ -0,0 +1,199 @@
+// Reference rust implementation of AluVM (arithmetic logic unit virtual machine).
+// To find more on AluVM please check <https://aluvm.org>
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Designed in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+// Written in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+//
+// Copyright (C) 2021-2024 LNP/BP Standards Association, Switzerland.
+// Copyright (C) 2024-2025 Laboratories for Ubiquitous Deterministic Computing (UBIDECO),
+//                         Institute for Distributed and Cognitive Systems (InDCS), Switzerland.
+// Copyright (C) 2021-2025 Dr Maxim Orlovsky.
+// All rights under the above copyrights are reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use this file except
+// in compliance with the License. You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed under the License
+// is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express
+// or implied. See the License for the specific language governing permissions and limitations under
+// the License.
+
+//! Runs a corpus of programs under different core configurations, checking that the engine keeps
+//! the core invariants. With the debug assertions enabled (or the `paranoid` feature on), the
+//! invariants are also checked after each executed instruction.
+
+extern crate alloc;
+
+use aluvm::isa::Instr;
+use aluvm::regs::Status;
+use aluvm::{
+    aluasm, CompiledLib, CoreConfig, InvariantViolation, Lib, LibId, LibSite, UnknownInstrPolicy,
+    Vm,
+};
+
+fn corpus() -> Vec<(&'static str, Vec<Lib>)> {
+    const MAIN: u16 = 0;
+    const SUB: u16 = 1;
+    const END: u16 = 2;
+
+    let control = CompiledLib::compile(
+        aluasm! {
+           routine MAIN:
+            chk     CO;
+            chk     CK;
+            jif     CO, MAIN;
+            jif     CK, MAIN;
+            fail    CK;
+            mov     CO, CK;
+            chk     CK;
+            not     CO;
+            chk     CO;
+            call    SUB;
+            stop;
+           routine  SUB:
+            jmp     END;
+           label    END:
+            ret;
+        },
+        &[],
+    )
+    .unwrap();
+
+    let dep = CompiledLib::compile(
+        aluasm! {
+            nop;
+            not     CO;
+            ret;
+        },
+        &[],
+    )
+    .unwrap();
+    let dep_id = dep.as_lib().lib_id();
+    let cross = CompiledLib::compile(
+        aluasm! {
+            call    dep_id, 0;
+            call    dep_id, 0;
+            fail    CK;
+            call    dep_id, 0;
+            stop;
+        },
+        &[&dep],
+    )
+    .unwrap();
+
+    let endless = CompiledLib::compile(
+        aluasm! {
+           routine MAIN:
+            not     CO;
+            jmp     MAIN;
+        },
+        &[],
+    )
+    .unwrap();
+
+    let recursion = CompiledLib::compile(
+        aluasm! {
+           routine MAIN:
+            call    MAIN;
+        },
+        &[],
+    )
+    .unwrap();
+
+    vec![
+        ("control", vec![control.into_lib()]),
+        ("cross-library", vec![cross.into_lib(), dep.into_lib()]),
+        ("missing library", vec![cross_only()]),
+        ("endless loop", vec![endless.into_lib()]),
+        ("recursion", vec![recursion.into_lib()]),
+    ]
+}
+
+fn cross_only() -> Lib {
+    let dep_id = LibId::from([0xA5u8; 32]);
+    Lib::assemble::<Instr<LibId>>(&aluasm! {
+        call    dep_id, 0;
+        not     CO;
+        stop;
+    })
+    .unwrap()
+}
+
+fn configs() -> impl Iterator<Item = CoreConfig> {
+    [false, true].into_iter().flat_map(|halt| {
+        [false, true].into_iter().flat_map(move |cs_integrity| {
+            [1_000_000, 100_000_000]
+                .into_iter()
+                .map(move |lim| CoreConfig {
+                    halt,
+                    complexity_lim: Some(lim),
+                    complexity_warn: Some(lim / 2),
+                    jump_lim: None,
+                    instr_lim: None,
+                    cs_integrity,
+                    unknown_instr: UnknownInstrPolicy::Fail,
+                })
+        })
+    })
+}
+
+#[test]
+fn corpus_keeps_invariants() {
+    for (name, libs) in corpus() {
+        let libs = libs
+            .iter()
+            .map(|lib| (lib.lib_id(), lib))
+            .collect::<Vec<_>>();
+        let entry = LibSite::new(libs[0].0, 0);
+        for config in configs() {
+            let mut vm = Vm::<Instr<LibId>>::with(config, ());
+            vm.exec(entry, &(), |id| {
+                libs.iter()
+                    .find(|(lib_id, _)| *lib_id == id)
+                    .map(|(_, lib)| *lib)
+            });
+            if let Err(violation) = vm.core.check_invariants() {
+                panic!("program `{name}` with {config:?} violates an invariant: {violation}");
+            }
+        }
+    }
+}
+
+#[test]
+fn host_reset_after_complexity_limit() {
+    const MAIN: u16 = 0;
+    let lib = CompiledLib::compile(
+        aluasm! {
+           routine MAIN:
+            nop;
+            jmp     MAIN;
+        },
+        &[],
+    )
+    .unwrap()
+    .into_lib();
+    let entry = LibSite::new(lib.lib_id(), 0);
+    let config = CoreConfig { complexity_lim: Some(50_000), ..CoreConfig::default() };
+
+    let mut vm = Vm::<Instr<LibId>>::with(config, ());
+    assert_eq!(vm.exec(entry, &(), |_| Some(&lib)), Status::Fail);
+    // The host is allowed to reset `CK`, putting the core into a state violating the invariants
+    vm.core.reset_ck();
+    assert_eq!(
+        vm.core.check_invariants(),
+        Err(InvariantViolation::ComplexityOverrun { ca: 50_000, cl: 50_000 })
+    );
+    // ... which is corrected by the engine during the further execution instead of panicking
+    assert_eq!(vm.exec(entry, &(), |_| Some(&lib)), Status::Fail);
+    assert_eq!(vm.core.check_invariants(), Ok(()));
+
+    let mut stepped = Vm::<Instr<LibId>>::with(config, ());
+    stepped.exec(entry, &(), |_| Some(&lib));
+    stepped.core.reset_ck();
+    assert!(stepped.step(entry, &(), |_| Some(&lib)).is_err());
+    assert_eq!(stepped.core.check_invariants(), Ok(()));
+}
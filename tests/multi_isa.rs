@@ -0,0 +1,271 @@
+// Reference rust implementation of AluVM (arithmetic logic unit virtual machine).
+// To find more on AluVM please check <https://aluvm.org>
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Designed in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+// Written in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+//
+// Copyright (C) 2021-2024 LNP/BP Standards Association, Switzerland.
+// Copyright (C) 2024-2025 Laboratories for Ubiquitous Deterministic Computing (UBIDECO),
+//                         Institute for Distributed and Cognitive Systems (InDCS), Switzerland.
+// Copyright (C) 2021-2025 Dr Maxim Orlovsky.
+// All rights under the above copyrights are reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use this file except
+// in compliance with the License. You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed under the License
+// is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express
+// or implied. See the License for the specific language governing permissions and limitations under
+// the License.
+
+//! Library using a custom `TICK` ISA extension, executed by a dispatcher combining it with the
+//! control flow instructions, and rejected by instruction sets not supporting the extension.
+
+extern crate alloc;
+
+use alloc::collections::BTreeSet;
+use core::fmt::{self, Display, Formatter};
+use core::ops::RangeInclusive;
+
+use aluvm::isa::{
+    Alu64Instr, ArithmInstr, Bytecode, BytecodeRead, BytecodeWrite, CodeEofError, CtrlInstr,
+    ExecStep, GotoTarget, Instr, Instruction, MultiIsa, RegA,
+};
+use aluvm::regs::Status;
+use aluvm::{
+    Core, CoreConfig, CoreExt, IsaId, Lib, LibId, LibSite, NoExt, NoRegs, Register, Site, SiteId,
+    StepError, Supercore, UnsupportedIsaError, Vm,
+};
+
+/// Tick counter register.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug)]
+struct TickReg;
+
+impl Display for TickReg {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result { f.write_str("TK") }
+}
+
+impl Register for TickReg {
+    type Value = u64;
+
+    fn bytes(self) -> u16 { 8 }
+
+    fn enumerate() -> impl Iterator<Item = Self> { core::iter::once(TickReg) }
+}
+
+impl From<NoRegs> for TickReg {
+    fn from(regs: NoRegs) -> Self { match regs {} }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+struct TickCore(Option<u64>);
+
+impl CoreExt for TickCore {
+    type Reg = TickReg;
+    type Config = ();
+    type Snapshot = Option<u64>;
+
+    fn with(_config: Self::Config) -> Self { Self::default() }
+
+    fn get(&self, _reg: TickReg) -> Option<u64> { self.0 }
+
+    fn clr(&mut self, _reg: TickReg) { self.0 = None }
+
+    fn put(&mut self, _reg: TickReg, val: Option<u64>) { self.0 = val }
+
+    fn reset(&mut self) { *self = Self::default() }
+
+    fn snapshot(&self) -> Self::Snapshot { self.0 }
+
+    fn restore(&mut self, snapshot: Self::Snapshot) { self.0 = snapshot }
+}
+
+impl Supercore<NoExt> for TickCore {
+    fn subcore(&self) -> NoExt { NoExt }
+
+    fn merge_subcore(&mut self, _subcore: NoExt) {}
+}
+
+/// Instructions of the `TICK` ISA extension, not including any of the control flow instructions.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+enum TickInstr {
+    /// Increment the tick counter.
+    Tick,
+    /// Set `CO` to a failure unless the tick counter has the provided value.
+    Chk(u8),
+}
+
+impl TickInstr {
+    const TICK: u8 = 0x80;
+    const CHK: u8 = 0x81;
+}
+
+impl Display for TickInstr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            TickInstr::Tick => f.write_str("tick"),
+            TickInstr::Chk(val) => write!(f, "chk     TK, {val}"),
+        }
+    }
+}
+
+impl<Id: SiteId> Bytecode<Id> for TickInstr {
+    fn op_range() -> RangeInclusive<u8> { Self::TICK..=Self::CHK }
+
+    fn opcode_byte(&self) -> u8 {
+        match self {
+            TickInstr::Tick => Self::TICK,
+            TickInstr::Chk(_) => Self::CHK,
+        }
+    }
+
+    fn code_byte_len(&self) -> u16 {
+        match self {
+            TickInstr::Tick => 1,
+            TickInstr::Chk(_) => 2,
+        }
+    }
+
+    fn external_ref(&self) -> Option<Id> { None }
+
+    fn encode_operands<W>(&self, writer: &mut W) -> Result<(), W::Error>
+    where W: BytecodeWrite<Id> {
+        match self {
+            TickInstr::Tick => Ok(()),
+            TickInstr::Chk(val) => writer.write_byte(*val),
+        }
+    }
+
+    fn decode_operands<R>(reader: &mut R, opcode: u8) -> Result<Self, CodeEofError>
+    where
+        Self: Sized,
+        R: BytecodeRead<Id>,
+    {
+        match opcode {
+            Self::TICK => Ok(TickInstr::Tick),
+            Self::CHK => reader.read_byte().map(TickInstr::Chk),
+            _ => unreachable!("opcode is not owned by the TICK extension"),
+        }
+    }
+}
+
+impl<Id: SiteId> Instruction<Id> for TickInstr {
+    const ISA_EXT: &'static [&'static str] = &["TICK"];
+
+    type Core = TickCore;
+    type Context<'ctx> = ();
+
+    fn isa_ext_id(&self) -> Option<&'static str> { Some("TICK") }
+
+    fn is_goto_target(&self) -> bool { false }
+
+    fn local_goto_pos(&mut self) -> GotoTarget<'_> { GotoTarget::None }
+
+    fn remote_goto_pos(&mut self) -> Option<&mut Site<Id>> { None }
+
+    fn src_regs(&self) -> BTreeSet<TickReg> { BTreeSet::from([TickReg]) }
+
+    fn dst_regs(&self) -> BTreeSet<TickReg> {
+        match self {
+            TickInstr::Tick => BTreeSet::from([TickReg]),
+            TickInstr::Chk(_) => BTreeSet::new(),
+        }
+    }
+
+    fn op_data_bytes(&self) -> u16 {
+        match self {
+            TickInstr::Tick => 0,
+            TickInstr::Chk(_) => 1,
+        }
+    }
+
+    fn ext_data_bytes(&self) -> u16 { 0 }
+
+    fn exec<const CALL_STACK_SIZE: usize>(
+        &self,
+        _: Site<Id>,
+        core: &mut Core<Id, TickCore, CALL_STACK_SIZE>,
+        _: &Self::Context<'_>,
+    ) -> ExecStep<Site<Id>> {
+        let ticks = core.get(TickReg).unwrap_or_default();
+        match self {
+            TickInstr::Tick => core.set(TickReg, ticks + 1),
+            TickInstr::Chk(val) if ticks == *val as u64 => core.set_co(Status::Ok),
+            TickInstr::Chk(_) => core.set_co(Status::Fail),
+        }
+        ExecStep::Next
+    }
+}
+
+type TickIsa = MultiIsa<TickInstr, CtrlInstr<LibId>>;
+
+fn tick_lib() -> Lib {
+    let code = [
+        TickIsa::Primary(TickInstr::Tick),
+        TickIsa::Primary(TickInstr::Tick),
+        TickIsa::Primary(TickInstr::Chk(2)),
+        TickIsa::Secondary(CtrlInstr::ChkCo),
+        TickIsa::Secondary(CtrlInstr::Stop),
+    ];
+    Lib::assemble(&code).unwrap()
+}
+
+#[test]
+fn dispatch() {
+    assert_eq!(TickIsa::opcode_overlap::<LibId>(), None);
+    assert_eq!(TickIsa::isa_ext(), IsaId::canonical_set([IsaId::from("TICK")]));
+
+    let lib = tick_lib();
+    assert_eq!(lib.isae(), &TickIsa::isa_ext());
+    assert_eq!(lib.validate::<TickIsa>(), Ok(()));
+
+    let mut vm = Vm::<TickIsa>::with(CoreConfig::default(), ());
+    let status = vm.exec(LibSite::new(lib.lib_id(), 0), &(), |_| Some(&lib));
+    assert_eq!(status, Status::Ok);
+    assert_eq!(vm.core.get(TickReg), Some(2));
+    assert_eq!(vm.core.co(), Status::Ok);
+}
+
+#[test]
+fn unsupported_isa() {
+    let lib = tick_lib();
+    let tick = UnsupportedIsaError { lib_id: lib.lib_id(), isa: IsaId::from("TICK") };
+    assert_eq!(lib.check_isae::<Instr<LibId>>(), Err(tick.clone()));
+    assert_eq!(lib.check_isae::<Alu64Instr<LibId>>(), Err(tick.clone()));
+
+    // The library is not executed, leaving the core intact
+    let mut core = Core::<LibId, NoExt>::with(CoreConfig::default(), ());
+    assert_eq!(lib.exec::<Instr<LibId>>(0, false, &mut core, &()), Err(tick));
+    assert_eq!(core.ck(), Status::Ok);
+    assert_eq!(core.ca(), 0);
+
+    let entry = LibSite::new(lib.lib_id(), 0);
+    let mut vm = Vm::<Instr<LibId>>::new();
+    assert_eq!(vm.exec(entry, &(), |_| Some(&lib)), Status::Fail);
+    assert_eq!(vm.core.cf(), 1);
+    assert_eq!(vm.core.ca(), 0);
+
+    let mut vm = Vm::<Instr<LibId>>::new();
+    assert_eq!(vm.step(entry, &(), |_| Some(&lib)), Err(StepError::UnsupportedIsa(entry)));
+    assert_eq!(vm.core.ck(), Status::Fail);
+}
+
+#[test]
+fn unsupported_by_dispatcher() {
+    let code = [
+        Alu64Instr::<LibId>::Arithm(ArithmInstr::Put { dst: RegA::A0, val: 0x81 }),
+        Alu64Instr::Ctrl(CtrlInstr::Stop),
+    ];
+    let lib = Lib::assemble(&code).unwrap();
+    let err = lib.check_isae::<TickIsa>().unwrap_err();
+    assert_eq!(err.isa, IsaId::from("ALU64"));
+
+    let mut vm = Vm::<TickIsa>::with(CoreConfig::default(), ());
+    let status = vm.exec(LibSite::new(lib.lib_id(), 0), &(), |_| Some(&lib));
+    assert_eq!(status, Status::Fail);
+    assert_eq!(vm.core.get(TickReg), None);
+}
@@ -0,0 +1,530 @@
+// Reference rust implementation of AluVM (arithmetic logic unit virtual machine).
+// To find more on AluVM please check <https://aluvm.org>
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Designed in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+// Written in 2021-2025 by Dr Maxim Orlovsky <orlovsky@ubideco.org>
+//
+// Copyright (C) 2021-2024 LNP/BP Standards Association, Switzerland.
+// Copyright (C) 2024-2025 Laboratories for Ubiquitous Deterministic Computing (UBIDECO),
+//                         Institute for Distributed and Cognitive Systems (InDCS), Switzerland.
+// Copyright (C) 2021-2025 Dr Maxim Orlovsky.
+// All rights under the above copyrights are reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use this file except
+// in compliance with the License. You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed under the License
+// is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express
+// or implied. See the License for the specific language governing permissions and limitations under
+// the License.
+
+//! Nested ISA extensions: a program running on the full `ALU64` core calls a library compiled for
+//! a narrower ISA, which executes on a subcore exposing only the first four `A`-registers.
+
+extern crate alloc;
+
+use alloc::collections::BTreeSet;
+use core::fmt::{self, Display, Formatter};
+use core::ops::RangeInclusive;
+
+use aluvm::isa::{
+    Alu64Core, Alu64Instr, ArithmInstr, Bytecode, BytecodeRead, BytecodeWrite, CodeEofError,
+    CtrlInstr, ExecStep, FlowKind, GotoTarget, Instruction, RegA, ReservedInstr,
+};
+use aluvm::regs::Status;
+use aluvm::{
+    Core, CoreConfig, CoreExt, FailureReason, IsaId, Lib, LibId, LibSite, NoExt, Site, SiteId,
+    Supercore, Vm,
+};
+use amplify::num::{u2, u6};
+
+/// Subcore exposing only registers `A0`-`A3`.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+struct QuadCore([Option<u64>; 4]);
+
+impl CoreExt for QuadCore {
+    type Reg = RegA;
+    type Config = ();
+    type Snapshot = [Option<u64>; 4];
+
+    fn with(_config: Self::Config) -> Self { Self::default() }
+
+    fn get(&self, reg: RegA) -> Option<u64> { self.0.get(reg.index() as usize).copied().flatten() }
+
+    fn clr(&mut self, reg: RegA) { self.put(reg, None) }
+
+    fn put(&mut self, reg: RegA, val: Option<u64>) {
+        let slot = self
+            .0
+            .get_mut(reg.index() as usize)
+            .expect("register is not exposed by the subcore");
+        *slot = val;
+    }
+
+    fn reset(&mut self) { *self = Self::default() }
+
+    fn snapshot(&self) -> Self::Snapshot { self.0 }
+
+    fn restore(&mut self, snapshot: Self::Snapshot) { self.0 = snapshot }
+}
+
+impl Supercore<NoExt> for QuadCore {
+    fn subcore(&self) -> NoExt { NoExt }
+
+    fn merge_subcore(&mut self, _subcore: NoExt) {}
+}
+
+impl Supercore<QuadCore> for Alu64Core {
+    fn subcore(&self) -> QuadCore { QuadCore([0, 1, 2, 3].map(|no| self.get(RegA::ALL[no]))) }
+
+    fn merge_subcore(&mut self, subcore: QuadCore) {
+        for (reg, val) in RegA::ALL.into_iter().zip(subcore.0) {
+            self.put(reg, val);
+        }
+    }
+}
+
+/// Instruction set of the libraries running on the [`QuadCore`].
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+enum QuadInstr<Id: SiteId> {
+    Ctrl(CtrlInstr<Id>),
+    /// Checked increment of a register.
+    Inc(RegA),
+    Reserved(ReservedInstr),
+}
+
+/// Code of the `QUAD` failure reason for the increment overflow.
+const QUAD_FAIL_INC: u16 = 1;
+
+impl<Id: SiteId> From<CtrlInstr<Id>> for QuadInstr<Id> {
+    fn from(instr: CtrlInstr<Id>) -> Self { Self::Ctrl(instr) }
+}
+
+impl<Id: SiteId> Display for QuadInstr<Id> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            QuadInstr::Ctrl(instr) => Display::fmt(instr, f),
+            QuadInstr::Inc(reg) => write!(f, "inc     {reg}"),
+            QuadInstr::Reserved(instr) => Display::fmt(instr, f),
+        }
+    }
+}
+
+impl<Id: SiteId> QuadInstr<Id> {
+    const INC: u8 = 0x80;
+}
+
+impl<Id: SiteId> Bytecode<Id> for QuadInstr<Id> {
+    fn op_range() -> RangeInclusive<u8> { 0..=0xFF }
+
+    fn opcode_byte(&self) -> u8 {
+        match self {
+            QuadInstr::Ctrl(instr) => instr.opcode_byte(),
+            QuadInstr::Inc(_) => Self::INC,
+            QuadInstr::Reserved(instr) => Bytecode::<Id>::opcode_byte(instr),
+        }
+    }
+
+    fn code_byte_len(&self) -> u16 {
+        match self {
+            QuadInstr::Ctrl(instr) => instr.code_byte_len(),
+            QuadInstr::Inc(_) => 2,
+            QuadInstr::Reserved(instr) => Bytecode::<Id>::code_byte_len(instr),
+        }
+    }
+
+    fn external_ref(&self) -> Option<Id> {
+        match self {
+            QuadInstr::Ctrl(instr) => instr.external_ref(),
+            QuadInstr::Inc(_) | QuadInstr::Reserved(_) => None,
+        }
+    }
+
+    fn encode_operands<W>(&self, writer: &mut W) -> Result<(), W::Error>
+    where W: BytecodeWrite<Id> {
+        match self {
+            QuadInstr::Ctrl(instr) => instr.encode_operands(writer),
+            QuadInstr::Inc(reg) => {
+                writer.write_2bits(u2::with(reg.index()))?;
+                writer.write_6bits(u6::ZERO)
+            }
+            QuadInstr::Reserved(instr) => instr.encode_operands(writer),
+        }
+    }
+
+    fn decode_operands<R>(reader: &mut R, opcode: u8) -> Result<Self, CodeEofError>
+    where
+        Self: Sized,
+        R: BytecodeRead<Id>,
+    {
+        if CtrlInstr::<Id>::op_range().contains(&opcode) {
+            CtrlInstr::decode_operands(reader, opcode).map(Self::Ctrl)
+        } else if opcode == Self::INC {
+            let reg = RegA::ALL[reader.read_2bits()?.to_u8() as usize];
+            reader.read_6bits()?;
+            Ok(Self::Inc(reg))
+        } else {
+            ReservedInstr::decode_operands(reader, opcode).map(Self::Reserved)
+        }
+    }
+}
+
+impl<Id: SiteId> Instruction<Id> for QuadInstr<Id> {
+    const ISA_EXT: &'static [&'static str] = &["QUAD"];
+
+    type Core = QuadCore;
+    type Context<'ctx> = ();
+
+    fn is_reserved(&self) -> bool { matches!(self, QuadInstr::Reserved(_)) }
+
+    fn is_goto_target(&self) -> bool {
+        matches!(self, QuadInstr::Ctrl(instr) if Instruction::<Id>::is_goto_target(instr))
+    }
+
+    fn local_goto_pos(&mut self) -> GotoTarget<'_> {
+        match self {
+            QuadInstr::Ctrl(instr) => instr.local_goto_pos(),
+            QuadInstr::Inc(_) | QuadInstr::Reserved(_) => GotoTarget::None,
+        }
+    }
+
+    fn remote_goto_pos(&mut self) -> Option<&mut Site<Id>> {
+        match self {
+            QuadInstr::Ctrl(instr) => instr.remote_goto_pos(),
+            QuadInstr::Inc(_) | QuadInstr::Reserved(_) => None,
+        }
+    }
+
+    fn flow_kind(&self) -> FlowKind {
+        match self {
+            QuadInstr::Ctrl(instr) => instr.flow_kind(),
+            QuadInstr::Inc(_) | QuadInstr::Reserved(_) => FlowKind::Continue,
+        }
+    }
+
+    fn src_regs(&self) -> BTreeSet<RegA> {
+        match self {
+            QuadInstr::Inc(reg) => BTreeSet::from([*reg]),
+            QuadInstr::Ctrl(_) | QuadInstr::Reserved(_) => BTreeSet::new(),
+        }
+    }
+
+    fn dst_regs(&self) -> BTreeSet<RegA> { self.src_regs() }
+
+    fn op_data_bytes(&self) -> u16 {
+        match self {
+            QuadInstr::Ctrl(instr) => instr.op_data_bytes(),
+            QuadInstr::Inc(_) | QuadInstr::Reserved(_) => 0,
+        }
+    }
+
+    fn ext_data_bytes(&self) -> u16 {
+        match self {
+            QuadInstr::Ctrl(instr) => instr.ext_data_bytes(),
+            QuadInstr::Inc(_) | QuadInstr::Reserved(_) => 0,
+        }
+    }
+
+    fn exec<const CALL_STACK_SIZE: usize>(
+        &self,
+        site: Site<Id>,
+        core: &mut Core<Id, QuadCore, CALL_STACK_SIZE>,
+        _: &Self::Context<'_>,
+    ) -> ExecStep<Site<Id>> {
+        match self {
+            QuadInstr::Ctrl(instr) => {
+                let mut subcore: Core<Id, NoExt, CALL_STACK_SIZE> = core.subcore();
+                let step = instr.exec(site, &mut subcore, &());
+                core.merge_subcore(subcore);
+                step
+            }
+            QuadInstr::Inc(reg) => match core.get(*reg).map(|val| val.checked_add(1)) {
+                Some(Some(val)) => {
+                    core.set(*reg, val);
+                    ExecStep::Next
+                }
+                Some(None) => {
+                    core.set_failure(FailureReason::Custom(IsaId::from("QUAD"), QUAD_FAIL_INC));
+                    ExecStep::Fail
+                }
+                None => {
+                    core.set_failure(FailureReason::NoValue);
+                    ExecStep::Fail
+                }
+            },
+            QuadInstr::Reserved(_) => {
+                core.set_failure(FailureReason::ReservedInstr);
+                ExecStep::Fail
+            }
+        }
+    }
+}
+
+/// Instruction set of the programs running on the full core, which includes the [`QuadInstr`]
+/// instructions executed on the [`QuadCore`] subcore.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+enum FullInstr<Id: SiteId> {
+    Alu(Alu64Instr<Id>),
+    Quad(QuadInstr<Id>),
+}
+
+impl<Id: SiteId> From<CtrlInstr<Id>> for FullInstr<Id> {
+    fn from(instr: CtrlInstr<Id>) -> Self { Self::Alu(instr.into()) }
+}
+
+impl<Id: SiteId> From<ArithmInstr> for FullInstr<Id> {
+    fn from(instr: ArithmInstr) -> Self { Self::Alu(instr.into()) }
+}
+
+impl<Id: SiteId> Display for FullInstr<Id> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            FullInstr::Alu(instr) => Display::fmt(instr, f),
+            FullInstr::Quad(instr) => Display::fmt(instr, f),
+        }
+    }
+}
+
+impl<Id: SiteId> Bytecode<Id> for FullInstr<Id> {
+    fn op_range() -> RangeInclusive<u8> { 0..=0xFF }
+
+    fn opcode_byte(&self) -> u8 {
+        match self {
+            FullInstr::Alu(instr) => instr.opcode_byte(),
+            FullInstr::Quad(instr) => instr.opcode_byte(),
+        }
+    }
+
+    fn code_byte_len(&self) -> u16 {
+        match self {
+            FullInstr::Alu(instr) => instr.code_byte_len(),
+            FullInstr::Quad(instr) => instr.code_byte_len(),
+        }
+    }
+
+    fn external_ref(&self) -> Option<Id> {
+        match self {
+            FullInstr::Alu(instr) => instr.external_ref(),
+            FullInstr::Quad(instr) => instr.external_ref(),
+        }
+    }
+
+    fn encode_operands<W>(&self, writer: &mut W) -> Result<(), W::Error>
+    where W: BytecodeWrite<Id> {
+        match self {
+            FullInstr::Alu(instr) => instr.encode_operands(writer),
+            FullInstr::Quad(instr) => instr.encode_operands(writer),
+        }
+    }
+
+    fn decode_operands<R>(reader: &mut R, opcode: u8) -> Result<Self, CodeEofError>
+    where
+        Self: Sized,
+        R: BytecodeRead<Id>,
+    {
+        if opcode == QuadInstr::<Id>::INC {
+            QuadInstr::decode_operands(reader, opcode).map(Self::Quad)
+        } else {
+            Alu64Instr::decode_operands(reader, opcode).map(Self::Alu)
+        }
+    }
+}
+
+impl<Id: SiteId> Instruction<Id> for FullInstr<Id> {
+    const ISA_EXT: &'static [&'static str] = &["ALU64", "QUAD"];
+
+    type Core = Alu64Core;
+    type Context<'ctx> = ();
+
+    fn is_reserved(&self) -> bool {
+        match self {
+            FullInstr::Alu(instr) => instr.is_reserved(),
+            FullInstr::Quad(instr) => instr.is_reserved(),
+        }
+    }
+
+    fn is_goto_target(&self) -> bool {
+        match self {
+            FullInstr::Alu(instr) => instr.is_goto_target(),
+            FullInstr::Quad(instr) => instr.is_goto_target(),
+        }
+    }
+
+    fn local_goto_pos(&mut self) -> GotoTarget<'_> {
+        match self {
+            FullInstr::Alu(instr) => instr.local_goto_pos(),
+            FullInstr::Quad(instr) => instr.local_goto_pos(),
+        }
+    }
+
+    fn remote_goto_pos(&mut self) -> Option<&mut Site<Id>> {
+        match self {
+            FullInstr::Alu(instr) => instr.remote_goto_pos(),
+            FullInstr::Quad(instr) => instr.remote_goto_pos(),
+        }
+    }
+
+    fn flow_kind(&self) -> FlowKind {
+        match self {
+            FullInstr::Alu(instr) => instr.flow_kind(),
+            FullInstr::Quad(instr) => instr.flow_kind(),
+        }
+    }
+
+    fn src_regs(&self) -> BTreeSet<RegA> {
+        match self {
+            FullInstr::Alu(instr) => instr.src_regs(),
+            FullInstr::Quad(instr) => instr.src_regs(),
+        }
+    }
+
+    fn dst_regs(&self) -> BTreeSet<RegA> {
+        match self {
+            FullInstr::Alu(instr) => instr.dst_regs(),
+            FullInstr::Quad(instr) => instr.dst_regs(),
+        }
+    }
+
+    fn op_data_bytes(&self) -> u16 {
+        match self {
+            FullInstr::Alu(instr) => instr.op_data_bytes(),
+            FullInstr::Quad(instr) => instr.op_data_bytes(),
+        }
+    }
+
+    fn ext_data_bytes(&self) -> u16 {
+        match self {
+            FullInstr::Alu(instr) => instr.ext_data_bytes(),
+            FullInstr::Quad(instr) => instr.ext_data_bytes(),
+        }
+    }
+
+    fn exec<const CALL_STACK_SIZE: usize>(
+        &self,
+        site: Site<Id>,
+        core: &mut Core<Id, Alu64Core, CALL_STACK_SIZE>,
+        context: &Self::Context<'_>,
+    ) -> ExecStep<Site<Id>> {
+        match self {
+            FullInstr::Alu(instr) => instr.exec(site, core, context),
+            FullInstr::Quad(instr) => {
+                let mut subcore: Core<Id, QuadCore, CALL_STACK_SIZE> = core.subcore();
+                let step = instr.exec(site, &mut subcore, context);
+                core.merge_subcore(subcore);
+                step
+            }
+        }
+    }
+}
+
+/// Library for the narrower ISA, incrementing `A0` and `A3`.
+fn quad_lib() -> Lib {
+    let code: Vec<QuadInstr<LibId>> = vec![
+        QuadInstr::Inc(RegA::A0),
+        CtrlInstr::Sh { shift: 3 }.into(),
+        CtrlInstr::Stop.into(),
+        QuadInstr::Inc(RegA::A3),
+        CtrlInstr::Ret.into(),
+    ];
+    Lib::assemble(&code).unwrap()
+}
+
+fn main_lib(quad_id: LibId) -> Lib {
+    let code: Vec<FullInstr<LibId>> = vec![
+        ArithmInstr::Put { dst: RegA::A0, val: 10 }.into(),
+        ArithmInstr::Put { dst: RegA::A3, val: 30 }.into(),
+        ArithmInstr::Put { dst: RegA::A4, val: 40 }.into(),
+        ArithmInstr::Put { dst: RegA::A15, val: 150 }.into(),
+        CtrlInstr::Call { site: Site::new(quad_id, 0) }.into(),
+        ArithmInstr::Add { wrap: false, dst: RegA::A15, src: RegA::A0 }.into(),
+        CtrlInstr::Stop.into(),
+    ];
+    Lib::assemble(&code).unwrap()
+}
+
+fn resolver<'lib>(libs: &'lib [&'lib Lib]) -> impl Fn(LibId) -> Option<&'lib Lib> {
+    |id| libs.iter().copied().find(|lib| lib.lib_id() == id)
+}
+
+#[test]
+fn nested_core() {
+    let quad = quad_lib();
+    let main = main_lib(quad.lib_id());
+    assert_eq!(quad.isae(), &IsaId::canonical_set([IsaId::from("QUAD")]));
+    assert_eq!(main.isae(), &IsaId::canonical_set([IsaId::from("ALU64"), IsaId::from("QUAD")]));
+    let libs = [&main, &quad];
+
+    let mut vm = Vm::<FullInstr<LibId>>::with(CoreConfig::default(), ());
+    let status = vm.exec(LibSite::new(main.lib_id(), 0), &(), resolver(&libs));
+    assert_eq!(status, Status::Ok);
+
+    // Registers exposed by the subcore are updated, and the rest survive the round trip
+    assert_eq!(vm.core.get(RegA::A0), Some(11));
+    assert_eq!(vm.core.get(RegA::A1), None);
+    assert_eq!(vm.core.get(RegA::A3), Some(31));
+    assert_eq!(vm.core.get(RegA::A4), Some(40));
+    assert_eq!(vm.core.get(RegA::A15), Some(161));
+
+    // Jumps and complexity of the instructions executed on the subcore are merged back
+    let main_code = main.disassemble::<FullInstr<LibId>>().unwrap();
+    let quad_code = quad.disassemble::<FullInstr<LibId>>().unwrap();
+    let trace = main_code[..5]
+        .iter()
+        .chain(&quad_code[..2])
+        .chain(&quad_code[3..])
+        .chain(&main_code[5..]);
+    let ca = trace.map(Instruction::<LibId>::complexity).sum::<u64>();
+    assert_eq!(vm.core.ca(), ca);
+    // relative jump, call and return
+    assert_eq!(vm.core.cy(), 3);
+    assert_eq!(vm.core.cf(), 0);
+    assert_eq!(vm.core.cp(), 0);
+    assert_eq!(vm.core.xcp(), 0);
+    assert_eq!(vm.core.xcs_high_water(), 1);
+}
+
+#[test]
+fn nested_core_failure() {
+    let quad = quad_lib();
+    let main = main_lib(quad.lib_id());
+    let libs = [&main, &quad];
+
+    // `A0` is not set when the library is called
+    let mut vm = Vm::<FullInstr<LibId>>::with(CoreConfig::default(), ());
+    let status = vm.exec(LibSite::new(quad.lib_id(), 0), &(), resolver(&libs));
+    assert_eq!(status, Status::Fail);
+    assert_eq!(vm.core.cf(), 1);
+    assert_eq!(vm.core.cy(), 0);
+    assert_eq!(vm.core.get(RegA::A0), None);
+    assert_eq!(vm.core.get(RegA::A3), None);
+    assert_eq!(vm.last_failure(), Some(&FailureReason::NoValue));
+
+    // Execution continues after the failure in the subcore if `CH` is not set
+    let config = CoreConfig { halt: false, ..CoreConfig::default() };
+    let mut vm = Vm::<FullInstr<LibId>>::with(config, ());
+    vm.core.set(RegA::A3, u64::MAX);
+    let status = vm.exec(LibSite::new(quad.lib_id(), 0), &(), resolver(&libs));
+    assert_eq!(status, Status::Fail);
+    assert_eq!(vm.core.cf(), 2);
+    assert_eq!(vm.core.cy(), 1);
+    assert_eq!(vm.core.get(RegA::A3), Some(u64::MAX));
+    assert_eq!(vm.last_failure(), Some(&FailureReason::Custom(IsaId::from("QUAD"), QUAD_FAIL_INC)));
+}
+
+#[test]
+fn subcore_standalone() {
+    let quad = quad_lib();
+    let mut vm = Vm::<QuadInstr<LibId>>::with(CoreConfig::default(), ());
+    for reg in &RegA::ALL[..4] {
+        vm.core.set(*reg, 0);
+    }
+    let status = vm.exec(LibSite::new(quad.lib_id(), 0), &(), |_| Some(&quad));
+    assert_eq!(status, Status::Ok);
+    assert_eq!(vm.core.get(RegA::A0), Some(1));
+    assert_eq!(vm.core.get(RegA::A3), Some(1));
+    assert_eq!(vm.core.cy(), 1);
+}